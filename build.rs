@@ -1,3 +1,5 @@
 fn main() {
+    // The terminal-only build has no Slint frontend to compile
+    #[cfg(feature = "gui")]
     slint_build::compile("src/ui/main_window.slint").unwrap();
 }
\ No newline at end of file
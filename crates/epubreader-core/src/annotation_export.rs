@@ -0,0 +1,1056 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::models::Book;
+use crate::models::annotation::{
+    Annotation, Bookmark, ExportFormat, ExportOptions, AnnotationSortBy, AnkiCardStyle, VaultFlavor
+};
+
+/// One book's Markdown note in a linked-notes or vault export
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkdownNoteFile {
+    pub book_id: String,
+    pub file_name: String,
+    pub content: String,
+}
+
+pub struct AnnotationExporter;
+
+impl AnnotationExporter {
+    /// Export annotations to specified format
+    pub fn export_annotations(
+        annotations: &[Annotation],
+        bookmarks: &[Bookmark],
+        options: &ExportOptions,
+        book_title: Option<&str>,
+    ) -> Result<String> {
+        // Filter annotations based on options
+        let filtered_annotations = Self::filter_annotations(annotations, options);
+        let mut filtered_bookmarks = if options.include_bookmarks {
+            bookmarks.to_vec()
+        } else {
+            Vec::new()
+        };
+        filtered_bookmarks.sort_by(|a, b| {
+            a.page_number.cmp(&b.page_number).then_with(|| a.id.cmp(&b.id))
+        });
+
+        // Sort annotations
+        let sorted_annotations = Self::sort_annotations(filtered_annotations, &options.sort_by);
+
+        match options.format {
+            ExportFormat::Json => Self::export_as_json(&sorted_annotations, &filtered_bookmarks, options, book_title),
+            ExportFormat::Csv => Self::export_as_csv(&sorted_annotations, &filtered_bookmarks, options),
+            ExportFormat::Markdown => Self::export_as_markdown(&sorted_annotations, &filtered_bookmarks, options, book_title),
+            ExportFormat::Html => Self::export_as_html(&sorted_annotations, &filtered_bookmarks, options, book_title),
+            ExportFormat::Txt => Self::export_as_txt(&sorted_annotations, &filtered_bookmarks, options, book_title),
+            ExportFormat::Anki => Self::export_as_anki(&sorted_annotations, options, book_title),
+            ExportFormat::Pdf => Err(anyhow::anyhow!("PDF export not yet implemented")),
+        }
+    }
+
+    /// Filter annotations based on export options
+    fn filter_annotations(annotations: &[Annotation], options: &ExportOptions) -> Vec<Annotation> {
+        annotations
+            .iter()
+            .filter(|annotation| {
+                // Filter by type
+                match annotation.annotation_type {
+                    crate::models::annotation::AnnotationType::Highlight => options.include_highlights,
+                    crate::models::annotation::AnnotationType::Note => options.include_notes,
+                    _ => true,
+                }
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Sort annotations based on sort criteria
+    ///
+    /// Ties fall back to document order and then id, so the output does not
+    /// depend on the order annotations were loaded in.
+    fn sort_annotations(mut annotations: Vec<Annotation>, sort_by: &AnnotationSortBy) -> Vec<Annotation> {
+        match sort_by {
+            AnnotationSortBy::CreatedAt => {
+                annotations.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| Self::document_order(a, b)));
+            }
+            AnnotationSortBy::PageNumber => {
+                annotations.sort_by(Self::document_order);
+            }
+            AnnotationSortBy::Type => {
+                annotations.sort_by(|a, b| {
+                    a.annotation_type.to_display_name().cmp(&b.annotation_type.to_display_name())
+                        .then_with(|| Self::document_order(a, b))
+                });
+            }
+            AnnotationSortBy::Color => {
+                annotations.sort_by(|a, b| a.color.to_name().cmp(&b.color.to_name()).then_with(|| Self::document_order(a, b)));
+            }
+            AnnotationSortBy::Category => {
+                annotations.sort_by(|a, b| a.category.cmp(&b.category).then_with(|| Self::document_order(a, b)));
+            }
+            AnnotationSortBy::Tag => {
+                annotations.sort_by(|a, b| {
+                    let a_tags = a.tags.join(", ");
+                    let b_tags = b.tags.join(", ");
+                    a_tags.cmp(&b_tags).then_with(|| Self::document_order(a, b))
+                });
+            }
+        }
+        annotations
+    }
+
+    /// Order annotations by where they appear in the book, then by id
+    fn document_order(a: &Annotation, b: &Annotation) -> Ordering {
+        a.page_number.cmp(&b.page_number)
+            .then_with(|| a.position.start_offset.cmp(&b.position.start_offset))
+            .then_with(|| a.id.cmp(&b.id))
+    }
+
+    /// Export as JSON
+    fn export_as_json(
+        annotations: &[Annotation],
+        bookmarks: &[Bookmark],
+        options: &ExportOptions,
+        book_title: Option<&str>,
+    ) -> Result<String> {
+        let mut export_data = serde_json::Map::new();
+        
+        // Metadata
+        export_data.insert("book_title".to_string(), Value::String(book_title.unwrap_or("Unknown").to_string()));
+        if options.include_export_date {
+            export_data.insert("exported_at".to_string(), Value::String(Utc::now().to_rfc3339()));
+        }
+        export_data.insert("total_annotations".to_string(), Value::Number(annotations.len().into()));
+        export_data.insert("total_bookmarks".to_string(), Value::Number(bookmarks.len().into()));
+        
+        // Export options
+        export_data.insert("export_options".to_string(), serde_json::to_value(options)?);
+        
+        // Annotations
+        if options.include_highlights || options.include_notes {
+            let annotation_data: Vec<serde_json::Value> = annotations
+                .iter()
+                .map(|a| {
+                    let mut annotation_obj = serde_json::Map::new();
+                    annotation_obj.insert("id".to_string(), Value::String(a.id.clone()));
+                    annotation_obj.insert("type".to_string(), Value::String(a.annotation_type.to_display_name()));
+                    annotation_obj.insert("page_number".to_string(), Value::Number(a.page_number.into()));
+                    annotation_obj.insert("selected_text".to_string(), Value::String(a.selected_text.clone()));
+                    if let Some(region) = &a.region {
+                        annotation_obj.insert("region".to_string(), serde_json::json!(region));
+                    }
+                    
+                    if let Some(note) = &a.note {
+                        annotation_obj.insert("note".to_string(), Value::String(note.clone()));
+                    }
+                    
+                    annotation_obj.insert("color".to_string(), Value::String(a.color.to_hex()));
+                    annotation_obj.insert("tags".to_string(), Value::Array(a.tags.iter().map(|t| Value::String(t.clone())).collect()));
+                    
+                    if options.include_timestamps {
+                        annotation_obj.insert("created_at".to_string(), Value::String(a.created_at.to_rfc3339()));
+                        annotation_obj.insert("modified_at".to_string(), Value::String(a.modified_at.to_rfc3339()));
+                    }
+                    
+                    annotation_obj.insert("is_favorite".to_string(), Value::Bool(a.is_favorite));
+                    
+                    if let Some(category) = &a.category {
+                        annotation_obj.insert("category".to_string(), Value::String(category.clone()));
+                    }
+                    
+                    Value::Object(annotation_obj)
+                })
+                .collect();
+            
+            export_data.insert("annotations".to_string(), Value::Array(annotation_data));
+        }
+        
+        // Bookmarks
+        if options.include_bookmarks {
+            let bookmark_data: Vec<serde_json::Value> = bookmarks
+                .iter()
+                .map(|b| {
+                    let mut bookmark_obj = serde_json::Map::new();
+                    bookmark_obj.insert("id".to_string(), Value::String(b.id.clone()));
+                    bookmark_obj.insert("page_number".to_string(), Value::Number(b.page_number.into()));
+                    bookmark_obj.insert("title".to_string(), Value::String(b.get_display_title()));
+                    bookmark_obj.insert("preview_text".to_string(), Value::String(b.preview_text.clone()));
+                    bookmark_obj.insert("color".to_string(), Value::String(b.color.to_hex()));
+                    bookmark_obj.insert("is_favorite".to_string(), Value::Bool(b.is_favorite));
+                    
+                    if options.include_timestamps {
+                        bookmark_obj.insert("created_at".to_string(), Value::String(b.created_at.to_rfc3339()));
+                    }
+                    
+                    if let Some(description) = &b.description {
+                        bookmark_obj.insert("description".to_string(), Value::String(description.clone()));
+                    }
+                    
+                    Value::Object(bookmark_obj)
+                })
+                .collect();
+            
+            export_data.insert("bookmarks".to_string(), Value::Array(bookmark_data));
+        }
+        
+        Ok(serde_json::to_string_pretty(&export_data)?)
+    }
+
+    /// Export as CSV
+    fn export_as_csv(
+        annotations: &[Annotation],
+        bookmarks: &[Bookmark],
+        options: &ExportOptions,
+    ) -> Result<String> {
+        let mut csv_data = String::new();
+        
+        // Headers
+        let mut headers = vec!["Type", "Page"];
+        if options.include_page_numbers {
+            headers.push("Page Number");
+        }
+        headers.extend_from_slice(&["Text", "Note", "Color"]);
+        if options.include_timestamps {
+            headers.push("Created At");
+        }
+        headers.push("Favorite");
+        
+        csv_data.push_str(&headers.join(","));
+        csv_data.push('\n');
+        
+        // Annotations
+        for annotation in annotations {
+            let mut row = vec![
+                annotation.annotation_type.to_display_name(),
+                annotation.page_number.to_string(),
+            ];
+            
+            if options.include_page_numbers {
+                row.push(annotation.page_number.to_string());
+            }
+            
+            row.push(Self::escape_csv_field(&annotation.selected_text));
+            row.push(Self::escape_csv_field(&annotation.note.clone().unwrap_or_default()));
+            row.push(annotation.color.to_name());
+            
+            if options.include_timestamps {
+                row.push(annotation.created_at.format("%Y-%m-%d %H:%M:%S").to_string());
+            }
+            
+            row.push(annotation.is_favorite.to_string());
+            
+            csv_data.push_str(&row.join(","));
+            csv_data.push('\n');
+        }
+        
+        // Bookmarks
+        if options.include_bookmarks {
+            for bookmark in bookmarks {
+                let mut row = vec![
+                    "Bookmark".to_string(),
+                    bookmark.page_number.to_string(),
+                ];
+                
+                if options.include_page_numbers {
+                    row.push(bookmark.page_number.to_string());
+                }
+                
+                row.push(Self::escape_csv_field(&bookmark.preview_text));
+                row.push(Self::escape_csv_field(&bookmark.description.clone().unwrap_or_default()));
+                row.push(bookmark.color.to_name());
+                
+                if options.include_timestamps {
+                    row.push(bookmark.created_at.format("%Y-%m-%d %H:%M:%S").to_string());
+                }
+                
+                row.push(bookmark.is_favorite.to_string());
+                
+                csv_data.push_str(&row.join(","));
+                csv_data.push('\n');
+            }
+        }
+        
+        Ok(csv_data)
+    }
+
+    /// Export as Markdown
+    fn export_as_markdown(
+        annotations: &[Annotation],
+        bookmarks: &[Bookmark],
+        options: &ExportOptions,
+        book_title: Option<&str>,
+    ) -> Result<String> {
+        let mut md_data = String::new();
+        
+        // Title
+        md_data.push_str(&format!("# Annotations - {}\n\n", book_title.unwrap_or("Unknown Book")));
+        
+        // Statistics
+        md_data.push_str("## Summary\n\n");
+        md_data.push_str(&format!("- **Total Annotations**: {}\n", annotations.len()));
+        md_data.push_str(&format!("- **Total Bookmarks**: {}\n", bookmarks.len()));
+        
+        if options.include_export_date {
+            md_data.push_str(&format!("- **Exported**: {}\n", Utc::now().format("%Y-%m-%d %H:%M:%S")));
+        }
+        
+        md_data.push_str("\n---\n\n");
+        
+        // Group by chapter if requested
+        if options.group_by_chapter {
+            // Chapters keep the order they first appear in, so repeated exports match
+            let mut chapters: Vec<(String, Vec<&Annotation>)> = Vec::new();
+            
+            for annotation in annotations {
+                let chapter = annotation.position.chapter_id.clone().unwrap_or_else(|| "Unknown Chapter".to_string());
+                match chapters.iter_mut().find(|(name, _)| *name == chapter) {
+                    Some((_, chapter_annotations)) => chapter_annotations.push(annotation),
+                    None => chapters.push((chapter, vec![annotation])),
+                }
+            }
+            
+            for (chapter, chapter_annotations) in chapters {
+                md_data.push_str(&format!("## Chapter: {}\n\n", chapter));
+                
+                for annotation in chapter_annotations {
+                    Self::write_annotation_markdown(&mut md_data, annotation, options);
+                }
+                
+                md_data.push_str("\n---\n\n");
+            }
+        } else {
+            // Regular format
+            if !annotations.is_empty() {
+                md_data.push_str("## Annotations\n\n");
+                
+                for annotation in annotations {
+                    Self::write_annotation_markdown(&mut md_data, annotation, options);
+                }
+            }
+        }
+        
+        // Bookmarks
+        if options.include_bookmarks && !bookmarks.is_empty() {
+            md_data.push_str("## Bookmarks\n\n");
+            
+            for bookmark in bookmarks {
+                md_data.push_str(&format!("### {} - Page {}\n\n", bookmark.get_display_title(), bookmark.page_number));
+                
+                if !bookmark.preview_text.is_empty() {
+                    md_data.push_str(&format!("> {}\n\n", bookmark.preview_text));
+                }
+                
+                if let Some(description) = &bookmark.description {
+                    md_data.push_str(&format!("**Description:** {}\n\n", description));
+                }
+                
+                if options.include_timestamps {
+                    md_data.push_str(&format!("*Created: {}*\n\n", bookmark.created_at.format("%Y-%m-%d %H:%M:%S")));
+                }
+                
+                md_data.push_str("---\n\n");
+            }
+        }
+        
+        Ok(md_data)
+    }
+
+    /// Write annotation in Markdown format
+    fn write_annotation_markdown(md_data: &mut String, annotation: &Annotation, options: &ExportOptions) {
+        md_data.push_str(&format!("### {} - Page {}\n\n", annotation.annotation_type.to_display_name(), annotation.page_number));
+        
+        // Selected text
+        md_data.push_str(&format!("> {}\n\n", annotation.excerpt()));
+        
+        // Note
+        if let Some(note) = &annotation.note {
+            md_data.push_str(&format!("**Note:** {}\n\n", note));
+        }
+        
+        // Tags
+        if !annotation.tags.is_empty() {
+            md_data.push_str(&format!("**Tags:** {}\n\n", annotation.tags.join(", ")));
+        }
+        
+        // Color
+        md_data.push_str(&format!("**Color:** {}\n\n", annotation.color.to_name()));
+        
+        // Timestamps
+        if options.include_timestamps {
+            md_data.push_str(&format!("*Created: {}*\n\n", annotation.created_at.format("%Y-%m-%d %H:%M:%S")));
+        }
+        
+        md_data.push_str("---\n\n");
+    }
+
+    /// Export as HTML
+    fn export_as_html(
+        annotations: &[Annotation],
+        bookmarks: &[Bookmark],
+        options: &ExportOptions,
+        book_title: Option<&str>,
+    ) -> Result<String> {
+        let mut html_data = String::new();
+        
+        // HTML Header
+        html_data.push_str(&format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Annotations - {}</title>
+    <style>
+        body {{ font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; margin: 40px; line-height: 1.6; }}
+        .annotation {{ border-left: 4px solid #007AFF; padding: 15px; margin: 20px 0; background: #f8f9fa; }}
+        .bookmark {{ border-left: 4px solid #FF6B6B; padding: 15px; margin: 20px 0; background: #fff5f5; }}
+        .page-number {{ color: #666; font-size: 0.9em; }}
+        .note {{ font-style: italic; color: #333; margin-top: 10px; }}
+        .tags {{ margin-top: 10px; }}
+        .tag {{ background: #e3f2fd; padding: 3px 8px; border-radius: 12px; font-size: 0.8em; margin-right: 5px; }}
+        .timestamp {{ color: #999; font-size: 0.8em; }}
+        .summary {{ background: #e8f5e8; padding: 20px; border-radius: 8px; margin-bottom: 30px; }}
+        h1 {{ color: #333; }}
+        h2 {{ color: #555; }}
+        blockquote {{ border-left: 3px solid #ddd; padding-left: 15px; margin: 15px 0; font-style: italic; }}
+    </style>
+</head>
+<body>
+    <h1>Annotations - {}</h1>
+"#,
+            book_title.unwrap_or("Unknown Book"),
+            book_title.unwrap_or("Unknown Book")
+        ));
+        
+        // Summary
+        html_data.push_str(&format!(
+            r#"<div class="summary">
+        <h2>Summary</h2>
+        <p><strong>Total Annotations:</strong> {}</p>
+        <p><strong>Total Bookmarks:</strong> {}</p>{}
+    </div>"#,
+            annotations.len(),
+            bookmarks.len(),
+            if options.include_export_date {
+                format!("\n        <p><strong>Exported:</strong> {}</p>", Utc::now().format("%Y-%m-%d %H:%M:%S"))
+            } else {
+                String::new()
+            }
+        ));
+        
+        // Annotations
+        if !annotations.is_empty() {
+            html_data.push_str("<h2>Annotations</h2>");
+            
+            for annotation in annotations {
+                html_data.push_str(&format!(
+                    r#"<div class="annotation">
+                <div class="page-number">Page {} - {}</div>
+                <blockquote>{}</blockquote>"#,
+                    annotation.page_number,
+                    annotation.annotation_type.to_display_name(),
+                    html_escape::encode_text(&annotation.excerpt())
+                ));
+                
+                if let Some(note) = &annotation.note {
+                    html_data.push_str(&format!(
+                        r#"<div class="note">Note: {}</div>"#,
+                        html_escape::encode_text(note)
+                    ));
+                }
+                
+                if !annotation.tags.is_empty() {
+                    html_data.push_str(r#"<div class="tags">"#);
+                    for tag in &annotation.tags {
+                        html_data.push_str(&format!(
+                            r#"<span class="tag">{}</span>"#,
+                            html_escape::encode_text(tag)
+                        ));
+                    }
+                    html_data.push_str("</div>");
+                }
+                
+                if options.include_timestamps {
+                    html_data.push_str(&format!(
+                        r#"<div class="timestamp">Created: {}</div>"#,
+                        annotation.created_at.format("%Y-%m-%d %H:%M:%S")
+                    ));
+                }
+                
+                html_data.push_str("</div>");
+            }
+        }
+        
+        // Bookmarks
+        if options.include_bookmarks && !bookmarks.is_empty() {
+            html_data.push_str("<h2>Bookmarks</h2>");
+            
+            for bookmark in bookmarks {
+                html_data.push_str(&format!(
+                    r#"<div class="bookmark">
+                <div class="page-number">Page {} - {}</div>
+                <blockquote>{}</blockquote>"#,
+                    bookmark.page_number,
+                    html_escape::encode_text(&bookmark.get_display_title()),
+                    html_escape::encode_text(&bookmark.preview_text)
+                ));
+                
+                if let Some(description) = &bookmark.description {
+                    html_data.push_str(&format!(
+                        r#"<div class="note">Description: {}</div>"#,
+                        html_escape::encode_text(description)
+                    ));
+                }
+                
+                if options.include_timestamps {
+                    html_data.push_str(&format!(
+                        r#"<div class="timestamp">Created: {}</div>"#,
+                        bookmark.created_at.format("%Y-%m-%d %H:%M:%S")
+                    ));
+                }
+                
+                html_data.push_str("</div>");
+            }
+        }
+        
+        // HTML Footer
+        html_data.push_str(r#"</body></html>"#);
+        
+        Ok(html_data)
+    }
+
+    /// Export as plain text
+    fn export_as_txt(
+        annotations: &[Annotation],
+        bookmarks: &[Bookmark],
+        options: &ExportOptions,
+        book_title: Option<&str>,
+    ) -> Result<String> {
+        let mut txt_data = String::new();
+        
+        // Title
+        txt_data.push_str(&format!("ANNOTATIONS - {}\n", book_title.unwrap_or("UNKNOWN BOOK")));
+        txt_data.push_str("=".repeat(50).as_str());
+        txt_data.push_str("\n\n");
+        
+        // Summary
+        txt_data.push_str("SUMMARY\n");
+        txt_data.push_str("-".repeat(20).as_str());
+        txt_data.push_str("\n");
+        txt_data.push_str(&format!("Total Annotations: {}\n", annotations.len()));
+        txt_data.push_str(&format!("Total Bookmarks: {}\n", bookmarks.len()));
+        
+        if options.include_export_date {
+            txt_data.push_str(&format!("Exported: {}\n", Utc::now().format("%Y-%m-%d %H:%M:%S")));
+        }
+        
+        txt_data.push_str("\n");
+        txt_data.push_str("=".repeat(50).as_str());
+        txt_data.push_str("\n\n");
+        
+        // Annotations
+        if !annotations.is_empty() {
+            txt_data.push_str("ANNOTATIONS\n");
+            txt_data.push_str("-".repeat(20).as_str());
+            txt_data.push_str("\n\n");
+            
+            for (i, annotation) in annotations.iter().enumerate() {
+                txt_data.push_str(&format!("{}. {} - Page {}\n", i + 1, annotation.annotation_type.to_display_name(), annotation.page_number));
+                txt_data.push_str(&format!("   \"{}\"\n", annotation.excerpt()));
+                
+                if let Some(note) = &annotation.note {
+                    txt_data.push_str(&format!("   Note: {}\n", note));
+                }
+                
+                if !annotation.tags.is_empty() {
+                    txt_data.push_str(&format!("   Tags: {}\n", annotation.tags.join(", ")));
+                }
+                
+                txt_data.push_str(&format!("   Color: {}\n", annotation.color.to_name()));
+                
+                if options.include_timestamps {
+                    txt_data.push_str(&format!("   Created: {}\n", annotation.created_at.format("%Y-%m-%d %H:%M:%S")));
+                }
+                
+                txt_data.push_str("\n");
+            }
+        }
+        
+        // Bookmarks
+        if options.include_bookmarks && !bookmarks.is_empty() {
+            txt_data.push_str("BOOKMARKS\n");
+            txt_data.push_str("-".repeat(20).as_str());
+            txt_data.push_str("\n\n");
+            
+            for (i, bookmark) in bookmarks.iter().enumerate() {
+                txt_data.push_str(&format!("{}. {} - Page {}\n", i + 1, bookmark.get_display_title(), bookmark.page_number));
+                txt_data.push_str(&format!("   \"{}\"\n", bookmark.preview_text));
+                
+                if let Some(description) = &bookmark.description {
+                    txt_data.push_str(&format!("   Description: {}\n", description));
+                }
+                
+                if options.include_timestamps {
+                    txt_data.push_str(&format!("   Created: {}\n", bookmark.created_at.format("%Y-%m-%d %H:%M:%S")));
+                }
+                
+                txt_data.push_str("\n");
+            }
+        }
+        
+        Ok(txt_data)
+    }
+
+    /// Export as notes for Anki's text import, one card per annotation
+    ///
+    /// The file header tells Anki the note type and deck, so importing it needs
+    /// no further setup. Tags with spaces have them replaced by underscores.
+    fn export_as_anki(
+        annotations: &[Annotation],
+        options: &ExportOptions,
+        book_title: Option<&str>,
+    ) -> Result<String> {
+        let anki = &options.anki;
+        let book = book_title.unwrap_or("Unknown Book");
+        let deck = Self::fill_template(&anki.deck_template, &[("book", book)]);
+
+        let mut tsv_data = String::new();
+        tsv_data.push_str("#separator:tab\n#html:true\n");
+        tsv_data.push_str(&format!("#notetype:{}\n", anki.card_style.display_name()));
+        tsv_data.push_str(&format!("#deck:{}\n", Self::clean_anki_field(&deck)));
+        tsv_data.push_str("#tags column:3\n");
+
+        for annotation in annotations {
+            let note = annotation.note.as_deref().unwrap_or_default();
+            if annotation.selected_text.trim().is_empty()
+                || (anki.card_style == AnkiCardStyle::Basic && note.trim().is_empty())
+            {
+                continue;
+            }
+
+            let text = Self::escape_anki_html(&annotation.selected_text);
+            let note = Self::escape_anki_html(note);
+            let book = Self::escape_anki_html(book);
+            let page = annotation.page_number.to_string();
+            let chapter = Self::escape_anki_html(annotation.position.chapter_id.as_deref().unwrap_or_default());
+            let tags = Self::escape_anki_html(&annotation.tags.join(", "));
+            let values = [
+                ("text", text.as_str()),
+                ("note", note.as_str()),
+                ("book", book.as_str()),
+                ("page", page.as_str()),
+                ("chapter", chapter.as_str()),
+                ("tags", tags.as_str()),
+            ];
+
+            let mut anki_tags: Vec<String> = Vec::new();
+            for tag in annotation.tags.iter().chain(&anki.extra_tags) {
+                let tag = tag.split_whitespace().collect::<Vec<_>>().join("_");
+                if !tag.is_empty() && !anki_tags.contains(&tag) {
+                    anki_tags.push(tag);
+                }
+            }
+
+            tsv_data.push_str(&Self::clean_anki_field(&Self::fill_template(&anki.front_template, &values)));
+            tsv_data.push('\t');
+            tsv_data.push_str(&Self::clean_anki_field(&Self::fill_template(&anki.back_template, &values)));
+            tsv_data.push('\t');
+            tsv_data.push_str(&Self::clean_anki_field(&anki_tags.join(" ")));
+            tsv_data.push('\n');
+        }
+
+        Ok(tsv_data)
+    }
+
+    /// Replace `{name}` placeholders in one pass, so values can't introduce placeholders of their own
+    fn fill_template(template: &str, values: &[(&str, &str)]) -> String {
+        let mut filled = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            filled.push_str(&rest[..start]);
+            let placeholder = &rest[start + 1..];
+            let value = values.iter().find(|(name, _)| {
+                placeholder.strip_prefix(name).is_some_and(|after| after.starts_with('}'))
+            });
+            match value {
+                Some((name, value)) => {
+                    filled.push_str(value);
+                    rest = &placeholder[name.len() + 1..];
+                }
+                None => {
+                    filled.push('{');
+                    rest = placeholder;
+                }
+            }
+        }
+        filled.push_str(rest);
+        filled
+    }
+
+    /// Escape annotation text for an HTML Anki field
+    fn escape_anki_html(text: &str) -> String {
+        html_escape::encode_text(text).replace('\n', "<br>")
+    }
+
+    /// Keep a field on one line of the file, quoting it if it contains quotes
+    fn clean_anki_field(field: &str) -> String {
+        let field = field.replace("\r\n", "<br>").replace(['\n', '\r'], "<br>").replace('\t', " ");
+        if field.contains('"') {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field
+        }
+    }
+
+    /// Export linked annotations as Markdown notes, one per book, joined by wiki-links
+    ///
+    /// Every annotation gets a block id, so a link such as `[[Dune#^id]]` opens
+    /// the annotation itself in Obsidian. Each annotation lists what it links to
+    /// and what links to it; links to annotations not given are left out.
+    pub fn export_linked_notes(annotations: &[Annotation], book_titles: &HashMap<String, String>) -> Vec<MarkdownNoteFile> {
+        let mut books: Vec<(&str, &str)> = Vec::new();
+        for annotation in annotations {
+            if !books.iter().any(|(book_id, _)| *book_id == annotation.book_id) {
+                let title = book_titles.get(&annotation.book_id).map_or("Unknown Book", String::as_str);
+                books.push((&annotation.book_id, title));
+            }
+        }
+        let annotations: Vec<&Annotation> = annotations.iter().collect();
+        Self::markdown_notes(books, &annotations, VaultFlavor::Obsidian, |_| String::new())
+    }
+
+    /// Export one Markdown note per book for an Obsidian or Logseq vault
+    ///
+    /// Each note starts with YAML frontmatter describing the book, followed by
+    /// its annotations in reading order as blockquotes with their notes, tags
+    /// and links. Annotations of books not given are left out.
+    pub fn export_vault(books: &[Book], annotations: &[Annotation], flavor: VaultFlavor) -> Vec<MarkdownNoteFile> {
+        let annotations: Vec<&Annotation> = annotations.iter()
+            .filter(|annotation| books.iter().any(|book| book.id == annotation.book_id))
+            .collect();
+        let book_entries = books.iter()
+            .filter(|book| annotations.iter().any(|annotation| annotation.book_id == book.id))
+            .map(|book| (book.id.as_str(), book.title.as_str()))
+            .collect();
+
+        Self::markdown_notes(book_entries, &annotations, flavor, |book_id| {
+            let book = books.iter().find(|book| book.id == book_id).expect("notes are only made for given books");
+            let book_annotations = annotations.iter().copied().filter(|annotation| annotation.book_id == book_id);
+            Self::vault_frontmatter(book, book_annotations, flavor)
+        })
+    }
+
+    fn vault_frontmatter<'a>(book: &Book, annotations: impl Iterator<Item = &'a Annotation>, flavor: VaultFlavor) -> String {
+        // JSON strings are valid YAML scalars, and need no YAML-specific escaping
+        let quoted = |value: &str| serde_json::to_string(value).unwrap_or_default();
+        let mut frontmatter = String::from("---\n");
+        // Logseq names a page by its title property, which would break links to the file's name
+        if flavor == VaultFlavor::Obsidian {
+            frontmatter.push_str(&format!("title: {}\n", quoted(&book.title)));
+        }
+        frontmatter.push_str(&format!("author: {}\n", quoted(&book.author)));
+        let optional = [
+            ("series", &book.series),
+            ("publisher", &book.publisher),
+            ("isbn", &book.isbn),
+            ("language", &book.language),
+        ];
+        for (key, value) in optional {
+            if let Some(value) = value {
+                frontmatter.push_str(&format!("{}: {}\n", key, quoted(value)));
+            }
+        }
+        if !book.tags.is_empty() {
+            let tags: Vec<String> = book.tags.iter().map(|tag| quoted(&Self::tag_name(tag))).collect();
+            frontmatter.push_str(&format!("tags: [{}]\n", tags.join(", ")));
+        }
+        frontmatter.push_str(&format!("book-id: {}\n", quoted(&book.id)));
+
+        let (count, updated) = annotations.fold((0, None), |(count, updated): (usize, Option<DateTime<Utc>>), annotation| {
+            (count + 1, updated.max(Some(annotation.modified_at)))
+        });
+        frontmatter.push_str(&format!("annotations: {}\n", count));
+        if let Some(updated) = updated {
+            frontmatter.push_str(&format!("updated: {}\n", updated.to_rfc3339_opts(SecondsFormat::Secs, true)));
+        }
+        frontmatter.push_str("---\n\n");
+        frontmatter
+    }
+
+    /// Render one note per book; `frontmatter` gives the text that opens a book's note
+    fn markdown_notes(
+        mut books: Vec<(&str, &str)>,
+        annotations: &[&Annotation],
+        flavor: VaultFlavor,
+        frontmatter: impl Fn(&str) -> String,
+    ) -> Vec<MarkdownNoteFile> {
+        books.sort_by(|a, b| a.1.cmp(b.1).then_with(|| a.0.cmp(b.0)));
+
+        // Books with the same title still need their own file
+        let mut file_stems: HashMap<&str, String> = HashMap::new();
+        for (book_id, title) in &books {
+            let stem = Self::note_file_stem(title);
+            let mut unique = stem.clone();
+            let mut copy = 2;
+            while file_stems.values().any(|taken| taken.eq_ignore_ascii_case(&unique)) {
+                unique = format!("{} ({})", stem, copy);
+                copy += 1;
+            }
+            file_stems.insert(book_id, unique);
+        }
+        let titles: HashMap<&str, &str> = books.iter().copied().collect();
+
+        let by_id: HashMap<&str, &Annotation> = annotations.iter().map(|annotation| (annotation.id.as_str(), *annotation)).collect();
+        let link = |target: &Annotation| {
+            let stem = &file_stems[target.book_id.as_str()];
+            let title = titles[target.book_id.as_str()];
+            match flavor {
+                VaultFlavor::Obsidian => format!(
+                    "[[{}#^{}|{}, page {}]]", stem, Self::block_id(&target.id), title, target.page_number
+                ),
+                // Logseq only references blocks by UUID
+                VaultFlavor::Logseq if Uuid::parse_str(&target.id).is_ok() => format!(
+                    "(({})) in [[{}]], page {}", target.id, stem, target.page_number
+                ),
+                VaultFlavor::Logseq => format!("[[{}]], page {}", stem, target.page_number),
+            }
+        };
+
+        books.iter().map(|(book_id, title)| {
+            let mut book_annotations: Vec<&Annotation> = annotations.iter()
+                .copied()
+                .filter(|annotation| annotation.book_id == *book_id)
+                .collect();
+            book_annotations.sort_by(|a, b| Self::document_order(a, b));
+
+            let mut content = frontmatter(book_id);
+            if flavor == VaultFlavor::Obsidian {
+                content.push_str(&format!("# {}\n\n", title));
+            }
+            for annotation in book_annotations {
+                let mut links: Vec<String> = annotation.cross_references.iter()
+                    .filter_map(|id| by_id.get(id.as_str()))
+                    .map(|target| format!("Links to {}", link(target)))
+                    .collect();
+                let mut backlinks: Vec<&Annotation> = annotations.iter()
+                    .copied()
+                    .filter(|source| source.cross_references.contains(&annotation.id))
+                    .collect();
+                backlinks.sort_by(|a, b| a.book_id.cmp(&b.book_id).then_with(|| Self::document_order(a, b)));
+                links.extend(backlinks.into_iter().map(|source| format!("Linked from {}", link(source))));
+
+                match flavor {
+                    VaultFlavor::Obsidian => Self::push_obsidian_annotation(&mut content, annotation, &links),
+                    VaultFlavor::Logseq => Self::push_logseq_annotation(&mut content, annotation, &links),
+                }
+            }
+
+            MarkdownNoteFile { book_id: book_id.to_string(), file_name: format!("{}.md", file_stems[book_id]), content }
+        })
+        .collect()
+    }
+
+    /// A heading per annotation, with a block id on its quote for links to point at
+    fn push_obsidian_annotation(content: &mut String, annotation: &Annotation, links: &[String]) {
+        content.push_str(&format!("## Page {}\n\n", annotation.page_number));
+        if !annotation.selected_text.is_empty() {
+            for line in annotation.selected_text.lines() {
+                content.push_str(&format!("> {}\n", line));
+            }
+            content.push('\n');
+        }
+        if let Some(note) = &annotation.note {
+            content.push_str(&format!("{}\n\n", note));
+        }
+        if !annotation.tags.is_empty() {
+            content.push_str(&format!("{}\n\n", Self::hashtags(&annotation.tags)));
+        }
+        content.push_str(&format!("^{}\n\n", Self::block_id(&annotation.id)));
+
+        for link in links {
+            content.push_str(&format!("- {}\n", link));
+        }
+        content.push('\n');
+    }
+
+    /// A top-level block per annotation, with its note, tags and links nested under it
+    fn push_logseq_annotation(content: &mut String, annotation: &Annotation, links: &[String]) {
+        let mut lines = annotation.selected_text.lines();
+        match lines.next() {
+            Some(first) => {
+                content.push_str(&format!("- > {}\n", first));
+                for line in lines {
+                    content.push_str(&format!("  > {}\n", line));
+                }
+            }
+            None => content.push_str(&format!("- Page {}\n", annotation.page_number)),
+        }
+        if Uuid::parse_str(&annotation.id).is_ok() {
+            content.push_str(&format!("  id:: {}\n", annotation.id));
+        }
+
+        let mut children = Vec::new();
+        if let Some(note) = &annotation.note {
+            children.push(note.lines().collect::<Vec<_>>().join("\n    "));
+        }
+        if !annotation.tags.is_empty() {
+            children.push(Self::hashtags(&annotation.tags));
+        }
+        children.extend(links.iter().cloned());
+        for child in children {
+            content.push_str(&format!("  - {}\n", child));
+        }
+    }
+
+    fn hashtags(tags: &[String]) -> String {
+        tags.iter().map(|tag| format!("#{}", Self::tag_name(tag))).collect::<Vec<_>>().join(" ")
+    }
+
+    /// Tags may only hold letters, digits, `_`, `-` and `/`
+    fn tag_name(tag: &str) -> String {
+        tag.trim()
+            .chars()
+            .map(|c| if c.is_alphanumeric() || "_-/".contains(c) { c } else { '-' })
+            .collect()
+    }
+
+    /// A file name stem without the characters Obsidian can't link to
+    fn note_file_stem(title: &str) -> String {
+        let stem: String = title.chars()
+            .map(|c| if "[]#^|\\/:*?\"<>".contains(c) || c.is_control() { ' ' } else { c })
+            .collect();
+        let stem = stem.split_whitespace().collect::<Vec<_>>().join(" ");
+        if stem.is_empty() { "Untitled".to_string() } else { stem }
+    }
+
+    /// Block ids may only hold letters, digits and dashes
+    fn block_id(annotation_id: &str) -> String {
+        annotation_id.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '-').collect()
+    }
+
+    /// Escape CSV field
+    fn escape_csv_field(field: &str) -> String {
+        if field.contains(',') || field.contains('"') || field.contains('\n') {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::annotation::{AnkiExportOptions, AnnotationType, TextPosition};
+
+    fn annotation(id: &str, page_number: u32, start_offset: usize) -> Annotation {
+        let position = TextPosition {
+            start_offset,
+            end_offset: start_offset + 10,
+            paragraph_index: 0,
+            chapter_id: Some(format!("ch{}", page_number)),
+            line_number: None,
+            column_number: None,
+            quote: None,
+        };
+        let mut annotation = Annotation::new(
+            "book".to_string(),
+            page_number,
+            format!("Text {}", id),
+            position,
+            AnnotationType::Highlight,
+        );
+        annotation.id = id.to_string();
+        annotation
+    }
+
+    #[test]
+    fn test_export_is_independent_of_load_order() {
+        let annotations = vec![annotation("b", 2, 5), annotation("a", 2, 5), annotation("c", 1, 40)];
+        let mut reversed = annotations.clone();
+        reversed.reverse();
+
+        for format in [ExportFormat::Json, ExportFormat::Markdown, ExportFormat::Html, ExportFormat::Txt] {
+            let options = ExportOptions {
+                format,
+                sort_by: AnnotationSortBy::PageNumber,
+                group_by_chapter: true,
+                ..ExportOptions::default()
+            };
+            let first = AnnotationExporter::export_annotations(&annotations, &[], &options, Some("Book")).unwrap();
+            let second = AnnotationExporter::export_annotations(&reversed, &[], &options, Some("Book")).unwrap();
+            assert_eq!(first, second);
+        }
+
+        let sorted = AnnotationExporter::sort_annotations(annotations, &AnnotationSortBy::PageNumber);
+        let ids: Vec<&str> = sorted.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_linked_notes_use_wiki_links_both_ways() {
+        let mut entropy = annotation("a1", 12, 0);
+        entropy.book_id = "chaos".to_string();
+        entropy.note = Some("Compare with Dune".to_string());
+        entropy.cross_references = vec!["b1".to_string(), "deleted".to_string()];
+        let mut spice = annotation("b1", 3, 0);
+        spice.book_id = "dune".to_string();
+        let titles = HashMap::from([
+            ("chaos".to_string(), "Order out of Chaos: Man's New Dialogue".to_string()),
+            ("dune".to_string(), "Dune".to_string()),
+        ]);
+
+        let files = AnnotationExporter::export_linked_notes(&[spice, entropy], &titles);
+        let names: Vec<&str> = files.iter().map(|file| file.file_name.as_str()).collect();
+        assert_eq!(names, ["Dune.md", "Order out of Chaos Man's New Dialogue.md"]);
+        assert!(files[1].content.contains("> Text a1\n\nCompare with Dune\n\n^a1\n\n- Links to [[Dune#^b1|Dune, page 3]]\n\n"));
+        assert!(files[0].content.contains(
+            "^b1\n\n- Linked from [[Order out of Chaos Man's New Dialogue#^a1|Order out of Chaos: Man's New Dialogue, page 12]]\n"
+        ));
+    }
+
+    #[test]
+    fn test_anki_export_makes_a_card_per_annotation() {
+        let mut entropy = annotation("a", 12, 0);
+        entropy.selected_text = "Entropy <always> increases".to_string();
+        entropy.note = Some("What happens to entropy?".to_string());
+        entropy.tags = vec!["thermo dynamics".to_string()];
+        let mut plain = annotation("b", 30, 0);
+        plain.selected_text = "The arrow\tof time".to_string();
+        let mut options = ExportOptions {
+            format: ExportFormat::Anki,
+            sort_by: AnnotationSortBy::PageNumber,
+            ..ExportOptions::default()
+        };
+        options.anki.extra_tags = vec!["reading".to_string()];
+
+        let cloze = AnnotationExporter::export_annotations(&[plain.clone(), entropy.clone()], &[], &options, Some("Order out of Chaos")).unwrap();
+        let lines: Vec<&str> = cloze.lines().collect();
+        assert_eq!(&lines[..5], [
+            "#separator:tab",
+            "#html:true",
+            "#notetype:Cloze",
+            "#deck:Books::Order out of Chaos",
+            "#tags column:3",
+        ]);
+        assert_eq!(lines[5], "{{c1::Entropy &lt;always&gt; increases}}\tWhat happens to entropy?<br><i>Order out of Chaos, page 12</i>\tthermo_dynamics reading");
+        assert_eq!(lines[6].split('\t').next(), Some("{{c1::The arrow of time}}"));
+        assert_eq!(lines.len(), 7);
+
+        // Basic cards need a note for their front
+        options.anki = AnkiExportOptions::with_style(AnkiCardStyle::Basic);
+        options.anki.deck_template = "{book}::{chapter}".to_string();
+        options.anki.front_template = "Q: {note} ({chapter})".to_string();
+        let basic = AnnotationExporter::export_annotations(&[plain, entropy], &[], &options, Some("Order out of Chaos")).unwrap();
+        let lines: Vec<&str> = basic.lines().collect();
+        assert_eq!(lines[2], "#notetype:Basic");
+        // The deck only knows the book, so other placeholders stay as written
+        assert_eq!(lines[3], "#deck:Order out of Chaos::{chapter}");
+        assert_eq!(lines[5], "Q: What happens to entropy? (ch12)\tEntropy &lt;always&gt; increases<br><i>Order out of Chaos, page 12</i>\tthermo_dynamics");
+        assert_eq!(lines.len(), 6);
+    }
+}
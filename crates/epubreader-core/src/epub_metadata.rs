@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use chrono::{DateTime, NaiveDate, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::isbn::normalize_isbn;
+
+static METADATA_BLOCK: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?is)<(?:\w+:)?metadata\b[^>]*>(.*?)</(?:\w+:)?metadata\s*>").unwrap()
+});
+static DC_ELEMENT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(concat!(
+        r"(?is)<(?:\w+:)?(title|creator|contributor|publisher|date|description|rights|language|subject|identifier)\b([^>]*?)",
+        r"(?:/>|>(.*?)</(?:\w+:)?(?:title|creator|contributor|publisher|date|description|rights|language|subject|identifier)\s*>)",
+    ))
+    .unwrap()
+});
+static META: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?is)<(?:\w+:)?meta\b([^>]*?)(?:/>|>(.*?)</(?:\w+:)?meta\s*>)").unwrap()
+});
+static ATTRIBUTE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"([\w:.-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+static BLOCK_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)</?(?:p|br|div|li|h[1-6])\b[^>]*>").unwrap());
+static TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]+>").unwrap());
+
+/// MARC relator code calibre uses for itself as "book producer"
+const BOOK_PRODUCER_ROLE: &str = "bkp";
+
+/// Person credited in the package metadata
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EpubContributor {
+    pub name: String,
+    pub role: Option<String>,    // MARC relator code, e.g. "aut", "trl", "ill"
+    pub file_as: Option<String>, // Sort name, e.g. "Austen, Jane"
+}
+
+impl EpubContributor {
+    /// Whether this person is credited as an author
+    pub fn is_author(&self) -> bool {
+        self.role.as_deref().is_none_or(|role| role.eq_ignore_ascii_case("aut"))
+    }
+}
+
+/// Identifier such as an ISBN or UUID
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EpubIdentifier {
+    pub scheme: Option<String>,
+    pub value: String,
+}
+
+/// Dublin Core and calibre metadata from the package document
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EpubMetadata {
+    pub title: Option<String>,
+    pub creators: Vec<EpubContributor>,
+    pub contributors: Vec<EpubContributor>,
+    pub publisher: Option<String>,
+    pub published: Option<String>, // As written, e.g. "1813" or "2004-01-28"
+    pub description: Option<String>,
+    pub rights: Option<String>,
+    pub language: Option<String>,
+    pub subjects: Vec<String>,
+    pub identifiers: Vec<EpubIdentifier>,
+    pub series: Option<String>,
+    pub series_index: Option<f32>,
+}
+
+impl EpubMetadata {
+    /// Parse the `<metadata>` element of an OPF package document
+    ///
+    /// Reads EPUB2 `opf:` attributes, EPUB3 `refines` metadata and calibre's
+    /// series entries.
+    pub fn parse(package: &str) -> Self {
+        let block = METADATA_BLOCK.captures(package)
+            .map(|captures| captures.get(1).unwrap().as_str())
+            .unwrap_or(package);
+
+        let metas: Vec<(HashMap<String, String>, String)> = META.captures_iter(block)
+            .map(|captures| {
+                let text = captures.get(2).map(|text| plain_text(text.as_str())).unwrap_or_default();
+                (attributes(&captures[1]), text)
+            })
+            .collect();
+        let refinement = |id: Option<&String>, property: &str| -> Option<String> {
+            let target = format!("#{}", id?);
+            metas.iter()
+                .find(|(attrs, _)| attrs.get("refines") == Some(&target) && attrs.get("property").map(String::as_str) == Some(property))
+                .map(|(_, text)| text.clone())
+                .filter(|text| !text.is_empty())
+        };
+
+        let mut metadata = EpubMetadata::default();
+        let mut has_publication_event = false;
+        for captures in DC_ELEMENT.captures_iter(block) {
+            let element = captures[1].to_lowercase();
+            let attrs = attributes(&captures[2]);
+            let Some(text) = captures.get(3).map(|text| plain_text(text.as_str())).filter(|text| !text.is_empty()) else {
+                continue;
+            };
+            let id = attrs.get("id");
+
+            match element.as_str() {
+                "title" => {
+                    metadata.title.get_or_insert(text);
+                }
+                "creator" | "contributor" => {
+                    let person = EpubContributor {
+                        name: text,
+                        role: attrs.get("role").cloned().or_else(|| refinement(id, "role")),
+                        file_as: attrs.get("file-as").cloned().or_else(|| refinement(id, "file-as")),
+                    };
+                    if element == "creator" {
+                        metadata.creators.push(person);
+                    } else {
+                        metadata.contributors.push(person);
+                    }
+                }
+                "publisher" => {
+                    metadata.publisher.get_or_insert(text);
+                }
+                "date" => {
+                    // EPUB2 books may list creation and modification dates too
+                    match attrs.get("event").map(|event| event.to_lowercase()).as_deref() {
+                        Some("publication") if !has_publication_event => {
+                            metadata.published = Some(text);
+                            has_publication_event = true;
+                        }
+                        None | Some("original-publication") | Some("issued") => {
+                            metadata.published.get_or_insert(text);
+                        }
+                        _ => {}
+                    }
+                }
+                "description" => {
+                    metadata.description.get_or_insert(text);
+                }
+                "rights" => {
+                    metadata.rights.get_or_insert(text);
+                }
+                "language" => {
+                    metadata.language.get_or_insert(text);
+                }
+                "subject" => {
+                    if !metadata.subjects.contains(&text) {
+                        metadata.subjects.push(text);
+                    }
+                }
+                _ => metadata.identifiers.push(EpubIdentifier {
+                    scheme: attrs.get("scheme").cloned().or_else(|| refinement(id, "identifier-type")),
+                    value: text,
+                }),
+            }
+        }
+
+        // EPUB3 collections, falling back to calibre's series metadata
+        for (attrs, text) in &metas {
+            if attrs.get("property").map(String::as_str) != Some("belongs-to-collection") || text.is_empty() {
+                continue;
+            }
+            let id = attrs.get("id");
+            if refinement(id, "collection-type").is_none_or(|kind| kind == "series") {
+                metadata.series = Some(text.clone());
+                metadata.series_index = refinement(id, "group-position").and_then(|position| position.parse().ok());
+                break;
+            }
+        }
+        if metadata.series.is_none() {
+            let calibre = |name: &str| {
+                metas.iter()
+                    .find(|(attrs, _)| attrs.get("name").map(String::as_str) == Some(name))
+                    .and_then(|(attrs, _)| attrs.get("content"))
+                    .map(|content| content.trim().to_string())
+                    .filter(|content| !content.is_empty())
+            };
+            metadata.series = calibre("calibre:series");
+            if metadata.series.is_some() {
+                metadata.series_index = calibre("calibre:series_index").and_then(|index| index.parse().ok());
+            }
+        }
+
+        metadata
+    }
+
+    /// Names of the people credited as authors
+    pub fn authors(&self) -> Vec<&str> {
+        self.creators.iter()
+            .filter(|creator| creator.is_author())
+            .map(|creator| creator.name.as_str())
+            .collect()
+    }
+
+    /// Everyone credited besides the authors, e.g. translators and illustrators
+    pub fn other_contributors(&self) -> Vec<&str> {
+        self.creators.iter()
+            .filter(|creator| !creator.is_author())
+            .chain(self.contributors.iter())
+            .filter(|person| person.role.as_deref() != Some(BOOK_PRODUCER_ROLE))
+            .map(|person| person.name.as_str())
+            .collect()
+    }
+
+    /// ISBN-13 from a scheme-tagged or `urn:isbn:` identifier
+    pub fn isbn(&self) -> Option<String> {
+        let tagged = self.identifiers.iter().find_map(|identifier| {
+            let value = identifier.value.trim();
+            let lower = value.to_lowercase();
+            if identifier.scheme.as_deref().is_some_and(|scheme| scheme.eq_ignore_ascii_case("isbn")) {
+                Some(value.to_string())
+            } else {
+                lower.strip_prefix("urn:isbn:")
+                    .or_else(|| lower.strip_prefix("isbn:"))
+                    .map(str::to_string)
+            }
+        });
+
+        tagged.as_deref()
+            .and_then(normalize_isbn)
+            .or_else(|| {
+                // Untagged identifiers that look like an ISBN-13
+                self.identifiers.iter()
+                    .filter(|identifier| identifier.value.chars().filter(char::is_ascii_digit).count() == 13)
+                    .find_map(|identifier| normalize_isbn(&identifier.value))
+            })
+    }
+
+    /// Publication date, accepting a year, year-month, full date or timestamp
+    pub fn publication_date(&self) -> Option<DateTime<Utc>> {
+        parse_date(self.published.as_deref()?)
+    }
+}
+
+/// Parse a year, year-month, full date or timestamp
+pub fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(date) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+
+    let mut parts = date.get(..10).unwrap_or(date).split('-');
+    let year = parts.next()?.parse::<i32>().ok()?;
+    let month = parts.next().and_then(|month| month.parse::<u32>().ok()).unwrap_or(1);
+    let day = parts.next().and_then(|day| day.parse::<u32>().ok()).unwrap_or(1);
+    Some(NaiveDate::from_ymd_opt(year, month, day)?.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+/// Attributes of a tag keyed by local name, so `opf:role` and `role` match alike
+pub fn attributes(tag: &str) -> HashMap<String, String> {
+    ATTRIBUTE.captures_iter(tag)
+        .map(|captures| {
+            let name = captures[1].rsplit(':').next().unwrap_or_default().to_lowercase();
+            let value = captures.get(2).or_else(|| captures.get(3)).map_or("", |value| value.as_str());
+            (name, html_escape::decode_html_entities(value).trim().to_string())
+        })
+        .collect()
+}
+
+/// Element text with markup removed, as descriptions often hold escaped HTML
+pub fn plain_text(markup: &str) -> String {
+    let decoded = html_escape::decode_html_entities(markup);
+    let text = BLOCK_TAG.replace_all(&decoded, " ");
+    let text = TAG.replace_all(&text, "");
+    html_escape::decode_html_entities(&text).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Datelike;
+
+    #[test]
+    fn test_parse_epub2_metadata_with_calibre_series() {
+        let package = r#"<package version="2.0"><metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+            <dc:title>The Fellowship of the Ring</dc:title>
+            <dc:creator opf:role="aut" opf:file-as="Tolkien, J. R. R.">J. R. R. Tolkien</dc:creator>
+            <dc:contributor opf:role="ill">Alan Lee</dc:contributor>
+            <dc:contributor opf:role="bkp">calibre (6.0.0) [https://calibre-ebook.com]</dc:contributor>
+            <dc:date opf:event="modification">2020-01-01</dc:date>
+            <dc:date opf:event="publication">1954-07-29</dc:date>
+            <dc:description>&lt;p&gt;The first &lt;b&gt;volume&lt;/b&gt;.&lt;/p&gt;</dc:description>
+            <dc:subject>Fantasy</dc:subject>
+            <dc:subject>Adventure</dc:subject>
+            <dc:identifier opf:scheme="ISBN">978-0-618-57494-1</dc:identifier>
+            <dc:identifier opf:scheme="uuid">a1b2c3</dc:identifier>
+            <dc:rights>All rights reserved</dc:rights>
+            <meta name="calibre:series" content="The Lord of the Rings"/>
+            <meta name="calibre:series_index" content="1.0"/>
+        </metadata></package>"#;
+
+        let metadata = EpubMetadata::parse(package);
+        assert_eq!(metadata.title.as_deref(), Some("The Fellowship of the Ring"));
+        assert_eq!(metadata.authors(), vec!["J. R. R. Tolkien"]);
+        assert_eq!(metadata.creators[0].file_as.as_deref(), Some("Tolkien, J. R. R."));
+        assert_eq!(metadata.other_contributors(), vec!["Alan Lee"]);
+        assert_eq!(metadata.publication_date().unwrap().year(), 1954);
+        assert_eq!(metadata.description.as_deref(), Some("The first volume."));
+        assert_eq!(metadata.subjects, vec!["Fantasy", "Adventure"]);
+        assert_eq!(metadata.isbn().as_deref(), Some("9780618574941"));
+        assert_eq!(metadata.rights.as_deref(), Some("All rights reserved"));
+        assert_eq!(metadata.series.as_deref(), Some("The Lord of the Rings"));
+        assert_eq!(metadata.series_index, Some(1.0));
+    }
+
+    #[test]
+    fn test_parse_epub3_refinements_and_collections() {
+        let package = r##"<package version="3.0"><metadata>
+            <dc:identifier id="uid">urn:isbn:9781234567897</dc:identifier>
+            <dc:title>Dune Messiah</dc:title>
+            <dc:creator id="c1">Frank Herbert</dc:creator>
+            <meta refines="#c1" property="role" scheme="marc:relators">aut</meta>
+            <meta refines="#c1" property="file-as">Herbert, Frank</meta>
+            <dc:creator id="c2">Jane Doe</dc:creator>
+            <meta refines="#c2" property="role" scheme="marc:relators">trl</meta>
+            <dc:date>1969</dc:date>
+            <dc:publisher>Putnam</dc:publisher>
+            <meta property="belongs-to-collection" id="s1">Dune Chronicles</meta>
+            <meta refines="#s1" property="collection-type">series</meta>
+            <meta refines="#s1" property="group-position">2</meta>
+            <meta property="dcterms:modified">2021-05-01T00:00:00Z</meta>
+        </metadata></package>"##;
+
+        let metadata = EpubMetadata::parse(package);
+        assert_eq!(metadata.authors(), vec!["Frank Herbert"]);
+        assert_eq!(metadata.creators[0].file_as.as_deref(), Some("Herbert, Frank"));
+        assert_eq!(metadata.other_contributors(), vec!["Jane Doe"]);
+        assert_eq!(metadata.publisher.as_deref(), Some("Putnam"));
+        assert_eq!(metadata.publication_date().unwrap().year(), 1969);
+        assert_eq!(metadata.isbn().as_deref(), Some("9781234567897"));
+        assert_eq!(metadata.series.as_deref(), Some("Dune Chronicles"));
+        assert_eq!(metadata.series_index, Some(2.0));
+    }
+}
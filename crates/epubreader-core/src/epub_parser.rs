@@ -0,0 +1,1140 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use epub::doc::{EpubDoc, NavPoint};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{info, warn};
+use zip::ZipArchive;
+use zip::result::ZipError;
+use zip::write::{SimpleFileOptions, ZipWriter};
+
+use crate::epub_metadata::{attributes, EpubMetadata};
+use crate::isbn::find_isbns;
+use crate::url_encoding::percent_decode;
+
+/// Spine documents read at each end of a book when looking for its copyright page
+const COPYRIGHT_PAGE_DOCUMENTS: usize = 3;
+
+static ROOTFILE_PATH: Lazy<Regex> = Lazy::new(|| Regex::new(r#"full-path\s*=\s*"([^"]+)""#).unwrap());
+static PACKAGE_VERSION: Lazy<Regex> = Lazy::new(|| Regex::new(r#"<(?:\w+:)?package\b[^>]*\bversion\s*=\s*"([^"]+)""#).unwrap());
+static NAV_ITEM: Lazy<Regex> = Lazy::new(|| Regex::new(r#"<(?:\w+:)?item\b[^>]*\bproperties\s*=\s*"[^"]*\bnav\b"#).unwrap());
+static MANIFEST_ITEM: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(?s)<(?:\w+:)?item\b[^>]*>"#).unwrap());
+static HREF_ATTR: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\bhref\s*=\s*"([^"]*)""#).unwrap());
+static EPUB_TYPE_ATTR: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\bepub:type\s*=\s*"([^"]*)""#).unwrap());
+static IMAGE_REF: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?is)<(?:\w+:)?(?:img|image)\b[^>]*?\s(?:src|xlink:href|href)\s*=\s*(?:"([^"]+)"|'([^']+)')"#).unwrap()
+});
+static XML_ENCODING: Lazy<regex::bytes::Regex> = Lazy::new(|| {
+    regex::bytes::Regex::new(r#"(?i)<\?xml\b[^>]*\bencoding\s*=\s*["']([\w.:-]+)["']"#).unwrap()
+});
+static META_CHARSET: Lazy<regex::bytes::Regex> = Lazy::new(|| {
+    regex::bytes::Regex::new(r#"(?i)<(?:\w+:)?meta\b[^>]*\bcharset\s*=\s*["']?([\w.:-]+)"#).unwrap()
+});
+static NAV_TOKEN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?is)<(/?)(?:\w+:)?(nav|li|a|span)\b([^>]*)>|<[^>]*>|([^<]+)"#).unwrap()
+});
+static TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]+>").unwrap());
+
+/// Errors raised while opening an EPUB container
+#[derive(Debug, Error)]
+pub enum EpubOpenError {
+    #[error("EPUB is password protected: {path}")]
+    PasswordRequired { path: PathBuf },
+
+    #[error("Incorrect password for EPUB: {path}")]
+    InvalidPassword { path: PathBuf },
+
+    #[error("Failed to read EPUB archive: {0}")]
+    Archive(#[from] ZipError),
+
+    #[error("Failed to read EPUB file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid EPUB document: {0}")]
+    Document(String),
+}
+
+impl EpubOpenError {
+    /// Short error category that does not include the file path
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EpubOpenError::PasswordRequired { .. } => "password-required",
+            EpubOpenError::InvalidPassword { .. } => "invalid-password",
+            EpubOpenError::Archive(_) => "archive",
+            EpubOpenError::Io(_) => "io",
+            EpubOpenError::Document(_) => "document",
+        }
+    }
+}
+
+/// Readable source for an EPUB archive
+pub trait EpubSource: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> EpubSource for T {}
+
+/// EPUB document backed by either the file on disk or a decrypted in-memory copy
+pub type EpubDocument = EpubDoc<Box<dyn EpubSource>>;
+
+/// Entry in a book's table of contents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TocEntry {
+    pub label: String,
+    pub href: String, // Archive path, optionally with a #fragment
+    pub play_order: usize,
+    pub children: Vec<TocEntry>,
+}
+
+impl TocEntry {
+    /// Number of nesting levels in this entry's subtree, counting itself
+    pub fn depth(&self) -> usize {
+        1 + self.children.iter().map(TocEntry::depth).max().unwrap_or(0)
+    }
+}
+
+/// Landmark from the EPUB3 navigation document, e.g. the start of the body matter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NavLandmark {
+    pub landmark_type: String, // epub:type such as "bodymatter" or "toc"
+    pub label: String,
+    pub href: String,
+}
+
+/// Print page location from the navigation document's page-list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageTarget {
+    pub label: String,
+    pub href: String,
+}
+
+/// Navigation structure of an EPUB
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpubInfo {
+    pub toc: Vec<TocEntry>,
+    pub toc_depth: usize,
+    pub landmarks: Vec<NavLandmark>,
+    pub page_list: Vec<PageTarget>,
+    pub nav_type: NavType,
+    #[serde(default)]
+    pub metadata: EpubMetadata,
+    #[serde(default)]
+    pub warnings: Vec<EpubWarning>,
+}
+
+/// Where a book's cover image was found
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum CoverSource {
+    Declared,       // Cover meta or cover-image property, or a cover-named image
+    FirstPageImage, // First image shown by the first spine item
+}
+
+/// Cover image bytes read from an EPUB
+#[derive(Debug, Clone)]
+pub struct EpubCover {
+    pub data: Vec<u8>,
+    pub mime: String,
+    pub source: CoverSource,
+}
+
+/// Kind of defect found when validating an EPUB
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum EpubDefect {
+    MissingFile,
+    BrokenSpineReference,
+    BadEncoding,
+    MissingCover,
+}
+
+impl EpubDefect {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            EpubDefect::MissingFile => "Missing File",
+            EpubDefect::BrokenSpineReference => "Broken Reading Order",
+            EpubDefect::BadEncoding => "Bad Text Encoding",
+            EpubDefect::MissingCover => "Missing Cover",
+        }
+    }
+}
+
+/// Defect found in an EPUB, explaining why a book may look wrong
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EpubWarning {
+    pub defect: EpubDefect,
+    pub detail: String,
+    pub repaired: bool, // Worked around when the book was opened
+}
+
+/// List item read from a nav document, before it is sorted into TOC, landmarks or pages
+#[derive(Default)]
+struct NavNode {
+    label: String,
+    href: String,
+    item_type: Option<String>,
+    play_order: usize,
+    children: Vec<NavNode>,
+}
+
+impl NavNode {
+    fn into_toc_entry(self) -> TocEntry {
+        TocEntry {
+            label: self.label,
+            href: self.href,
+            play_order: self.play_order,
+            children: self.children.into_iter().map(NavNode::into_toc_entry).collect(),
+        }
+    }
+
+    fn flatten(self, nodes: &mut Vec<NavNode>) {
+        let mut node = self;
+        let children = std::mem::take(&mut node.children);
+        nodes.push(node);
+        for child in children {
+            child.flatten(nodes);
+        }
+    }
+}
+
+/// Navigation document type found in an EPUB package
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum NavType {
+    Nav,
+    Ncx,
+    NavAndNcx,
+    Missing,
+}
+
+impl NavType {
+    pub fn to_string(&self) -> String {
+        match self {
+            NavType::Nav => "nav".to_string(),
+            NavType::Ncx => "ncx".to_string(),
+            NavType::NavAndNcx => "nav+ncx".to_string(),
+            NavType::Missing => "none".to_string(),
+        }
+    }
+}
+
+/// EPUB features relevant to format compatibility
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EpubFeatures {
+    pub version: Option<String>,
+    pub zip_encryption: bool,
+    pub encryption_xml: bool, // DRM or font obfuscation
+    pub fixed_layout: bool,
+    pub nav_type: Option<NavType>,
+    pub media_overlays: bool,
+}
+
+impl EpubFeatures {
+    /// Feature tags used as ledger keys
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags = vec![format!(
+            "version:{}",
+            self.version.as_deref().unwrap_or("unknown")
+        )];
+        if self.zip_encryption {
+            tags.push("zip-encryption".to_string());
+        }
+        if self.encryption_xml {
+            tags.push("encryption-xml".to_string());
+        }
+        if self.fixed_layout {
+            tags.push("fixed-layout".to_string());
+        }
+        if let Some(nav_type) = self.nav_type {
+            tags.push(format!("nav:{}", nav_type.to_string()));
+        }
+        if self.media_overlays {
+            tags.push("media-overlays".to_string());
+        }
+        tags
+    }
+}
+
+/// Parser entry point for opening EPUB files
+pub struct EpubParser;
+
+impl EpubParser {
+    /// Check whether any entry of the EPUB archive uses zip encryption
+    pub fn is_encrypted(path: &Path) -> Result<bool, EpubOpenError> {
+        let mut archive = ZipArchive::new(BufReader::new(File::open(path)?))?;
+        Self::archive_is_encrypted(&mut archive)
+    }
+
+    /// Open an EPUB, decrypting it first when it is zip-encrypted
+    ///
+    /// Structural defects are repaired on the way, see `open_with_warnings`.
+    pub fn open(path: &Path, password: Option<&str>) -> Result<EpubDocument, EpubOpenError> {
+        let (doc, warnings) = Self::open_repaired(path, password)?;
+        for warning in warnings {
+            warn!("{}: {}", path.display(), warning.detail);
+        }
+        Ok(doc)
+    }
+
+    /// Open an EPUB and report its defects
+    ///
+    /// Manifest items whose file is missing and spine entries pointing nowhere
+    /// are dropped, and a cover-like image stands in for a missing cover.
+    /// Chapters are also checked for text that is not valid in its declared
+    /// encoding; `read_text` transcodes those from the sniffed one.
+    pub fn open_with_warnings(path: &Path, password: Option<&str>) -> Result<(EpubDocument, Vec<EpubWarning>), EpubOpenError> {
+        let (mut doc, mut warnings) = Self::open_repaired(path, password)?;
+        warnings.extend(Self::check_encoding(&mut doc));
+        Ok((doc, warnings))
+    }
+
+    /// Read navigation and validation warnings for a book
+    pub fn book_info(path: &Path, password: Option<&str>) -> Result<EpubInfo, EpubOpenError> {
+        let (mut doc, warnings) = Self::open_with_warnings(path, password)?;
+        let mut info = Self::navigation(&mut doc);
+        info.warnings = warnings;
+        Ok(info)
+    }
+
+    /// Find the cover image, falling back to the first image on the first page
+    ///
+    /// Books opened through `open` already fall back to a cover-named image when
+    /// none is declared.
+    pub fn cover(doc: &mut EpubDocument) -> Option<EpubCover> {
+        if let Some((data, mime)) = doc.get_cover() {
+            return Some(EpubCover { data, mime, source: CoverSource::Declared });
+        }
+
+        let first_page = doc.spine.first()?.idref.clone();
+        let page_path = doc.resources.get(&first_page)?.0.to_string_lossy().replace('\\', "/");
+        let page = Self::read_text(doc, &first_page)?;
+        IMAGE_REF.captures_iter(&page)
+            .filter_map(|captures| captures.get(1).or_else(|| captures.get(2)))
+            .map(|href| html_escape::decode_html_entities(href.as_str()).into_owned())
+            .filter(|href| !href.starts_with("data:"))
+            .find_map(|href| {
+                let path = Self::resolve_href(&page_path, &href);
+                let path = path.split('#').next().unwrap_or_default();
+                let mime = doc.get_resource_mime_by_path(path)
+                    .or_else(|| doc.get_resource_mime_by_path(&percent_decode(path)))?;
+                if !mime.starts_with("image/") {
+                    return None;
+                }
+                let data = doc.get_resource_by_path(path)?;
+                Some(EpubCover { data, mime, source: CoverSource::FirstPageImage })
+            })
+    }
+
+    /// Read a text resource by manifest id, transcoding legacy encodings to UTF-8
+    pub fn read_text(doc: &mut EpubDocument, id: &str) -> Option<String> {
+        let (data, _) = doc.get_resource(id)?;
+        Some(decode_text(&data).0)
+    }
+
+    /// Open the archive and repair structural defects
+    fn open_repaired(path: &Path, password: Option<&str>) -> Result<(EpubDocument, Vec<EpubWarning>), EpubOpenError> {
+        let mut archive = ZipArchive::new(BufReader::new(File::open(path)?))?;
+        let entries: HashSet<String> = archive.file_names().map(str::to_string).collect();
+
+        let source: Box<dyn EpubSource> = if Self::archive_is_encrypted(&mut archive)? {
+            let password = password.ok_or_else(|| EpubOpenError::PasswordRequired {
+                path: path.to_path_buf(),
+            })?;
+            info!("Decrypting password-protected EPUB: {}", path.display());
+            Box::new(Self::decrypt_archive(path, &mut archive, password)?)
+        } else {
+            Box::new(BufReader::new(File::open(path)?))
+        };
+
+        let mut doc = EpubDoc::from_reader(source).map_err(|e| EpubOpenError::Document(e.to_string()))?;
+        let warnings = Self::repair_structure(&mut doc, &entries);
+        Ok((doc, warnings))
+    }
+
+    /// Drop dangling manifest items and spine entries, and find a fallback cover
+    fn repair_structure(doc: &mut EpubDocument, entries: &HashSet<String>) -> Vec<EpubWarning> {
+        let mut warnings = Vec::new();
+
+        let mut ids: Vec<String> = doc.resources.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            let path = doc.resources[&id].0.to_string_lossy().replace('\\', "/");
+            if !entries.contains(&path) && !entries.contains(&percent_decode(&path)) {
+                doc.resources.remove(&id);
+                warnings.push(EpubWarning {
+                    defect: EpubDefect::MissingFile,
+                    detail: format!("Manifest item \"{}\" points to {}, which is not in the archive", id, path),
+                    repaired: true,
+                });
+            }
+        }
+
+        let resources = &doc.resources;
+        doc.spine.retain(|item| {
+            let found = resources.contains_key(&item.idref);
+            if !found {
+                warnings.push(EpubWarning {
+                    defect: EpubDefect::BrokenSpineReference,
+                    detail: format!("Reading order refers to \"{}\", which is not an available manifest item", item.idref),
+                    repaired: true,
+                });
+            }
+            found
+        });
+
+        let has_cover = doc.cover_id.as_ref().is_some_and(|id| doc.resources.contains_key(id));
+        if !has_cover {
+            let fallback = doc.resources.iter()
+                .filter(|(id, (path, mime))| {
+                    mime.starts_with("image/")
+                        && (id.to_lowercase().contains("cover") || path.to_string_lossy().to_lowercase().contains("cover"))
+                })
+                .map(|(id, (path, _))| (path.to_string_lossy().replace('\\', "/"), id.clone()))
+                .min();
+            match fallback {
+                Some((path, id)) => {
+                    doc.cover_id = Some(id);
+                    warnings.push(EpubWarning {
+                        defect: EpubDefect::MissingCover,
+                        detail: format!("No cover image is declared, using {}", path),
+                        repaired: true,
+                    });
+                }
+                None => warnings.push(EpubWarning {
+                    defect: EpubDefect::MissingCover,
+                    detail: "No cover image is declared".to_string(),
+                    repaired: false,
+                }),
+            }
+        }
+
+        warnings
+    }
+
+    /// Report chapters whose text is not valid UTF-8
+    fn check_encoding(doc: &mut EpubDocument) -> Vec<EpubWarning> {
+        let mut warnings = Vec::new();
+        for item in doc.spine.clone() {
+            let Some((data, _)) = doc.get_resource(&item.idref) else {
+                continue;
+            };
+            if let (_, Some(encoding)) = decode_text(&data) {
+                let path = doc.resources.get(&item.idref)
+                    .map(|(path, _)| path.to_string_lossy().replace('\\', "/"))
+                    .unwrap_or_else(|| item.idref.clone());
+                warnings.push(EpubWarning {
+                    defect: EpubDefect::BadEncoding,
+                    detail: format!("{} is not valid in its declared encoding and was read as {}", path, encoding.name()),
+                    repaired: true,
+                });
+            }
+        }
+        warnings
+    }
+
+    /// Inspect the container and package document for compatibility-relevant features
+    ///
+    /// Works without fully parsing the book, so it can describe files that fail to open.
+    /// Package details are left unset when the archive is zip-encrypted.
+    pub fn detect_features(path: &Path) -> Result<EpubFeatures, EpubOpenError> {
+        let mut archive = ZipArchive::new(BufReader::new(File::open(path)?))?;
+        let mut features = EpubFeatures {
+            version: None,
+            zip_encryption: Self::archive_is_encrypted(&mut archive)?,
+            encryption_xml: archive.index_for_name("META-INF/encryption.xml").is_some(),
+            fixed_layout: false,
+            nav_type: None,
+            media_overlays: false,
+        };
+
+        if features.zip_encryption {
+            return Ok(features);
+        }
+
+        let Some(opf_path) = Self::read_entry(&mut archive, "META-INF/container.xml")
+            .and_then(|container| ROOTFILE_PATH.captures(&container).map(|c| c[1].to_string()))
+        else {
+            return Ok(features);
+        };
+        let Some(opf) = Self::read_entry(&mut archive, &opf_path) else {
+            return Ok(features);
+        };
+
+        let has_nav = NAV_ITEM.is_match(&opf);
+        let has_ncx = opf.contains("application/x-dtbncx+xml");
+        features.version = PACKAGE_VERSION.captures(&opf).map(|c| c[1].to_string());
+        features.fixed_layout = opf.contains("pre-paginated");
+        features.media_overlays = opf.contains("media-overlay") || opf.contains("application/smil+xml");
+        features.nav_type = Some(match (has_nav, has_ncx) {
+            (true, true) => NavType::NavAndNcx,
+            (true, false) => NavType::Nav,
+            (false, true) => NavType::Ncx,
+            (false, false) => NavType::Missing,
+        });
+
+        Ok(features)
+    }
+
+    /// Get the table of contents, falling back to the EPUB2 NCX navigation map
+    pub fn table_of_contents(doc: &mut EpubDocument) -> Vec<TocEntry> {
+        Self::navigation(doc).toc
+    }
+
+    /// Read the nested TOC, landmarks and page-list
+    ///
+    /// Prefers the EPUB3 nav document and falls back to the NCX for the TOC.
+    pub fn navigation(doc: &mut EpubDocument) -> EpubInfo {
+        let (mut toc, landmarks, page_list) = match Self::read_nav_document(doc) {
+            Some((nav_path, nav_html)) => Self::parse_nav_document(&nav_html, &nav_path),
+            None => (Vec::new(), Vec::new(), Vec::new()),
+        };
+
+        let has_nav = !toc.is_empty();
+        let has_ncx = !doc.toc.is_empty();
+        if !has_nav {
+            toc = Self::toc_from_ncx(&doc.toc);
+        }
+
+        EpubInfo {
+            toc_depth: toc.iter().map(TocEntry::depth).max().unwrap_or(0),
+            toc,
+            landmarks,
+            page_list,
+            nav_type: match (has_nav, has_ncx) {
+                (true, true) => NavType::NavAndNcx,
+                (true, false) => NavType::Nav,
+                (false, true) => NavType::Ncx,
+                (false, false) => NavType::Missing,
+            },
+            metadata: Self::metadata(doc),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Read the full Dublin Core and calibre metadata from the package document
+    pub fn metadata(doc: &mut EpubDocument) -> EpubMetadata {
+        Self::read_package(doc)
+            .map(|package| EpubMetadata::parse(&package))
+            .unwrap_or_default()
+    }
+
+    /// Find an ISBN printed on the copyright page, for books whose metadata has none
+    ///
+    /// Only the first and last few spine documents are read, as that is where
+    /// front and back matter live.
+    pub fn text_isbn(doc: &mut EpubDocument) -> Option<String> {
+        let spine: Vec<String> = doc.spine.iter().map(|item| item.idref.clone()).collect();
+        let front = spine.len().min(COPYRIGHT_PAGE_DOCUMENTS);
+        let back = spine.len().saturating_sub(COPYRIGHT_PAGE_DOCUMENTS).max(front);
+
+        spine[..front].iter()
+            .chain(&spine[back..])
+            .find_map(|id| {
+                let html = Self::read_text(doc, id)?;
+                let text = html_escape::decode_html_entities(&TAG.replace_all(&html, " ")).into_owned();
+                find_isbns(&text).into_iter().next()
+            })
+    }
+
+    fn read_package(doc: &mut EpubDocument) -> Option<String> {
+        let data = doc.get_resource_by_path(&doc.root_file.clone())?;
+        Some(decode_text(&data).0)
+    }
+
+    /// Find and read the nav document declared in the package manifest
+    fn read_nav_document(doc: &mut EpubDocument) -> Option<(String, String)> {
+        let package_path = doc.root_file.to_string_lossy().replace('\\', "/");
+        let package = Self::read_package(doc)?;
+
+        let href = MANIFEST_ITEM.find_iter(&package)
+            .map(|item| item.as_str())
+            .find(|item| NAV_ITEM.is_match(item))
+            .and_then(|item| HREF_ATTR.captures(item))
+            .map(|captures| html_escape::decode_html_entities(&captures[1]).into_owned())?;
+
+        let nav_path = Self::resolve_href(&package_path, &href);
+        let nav_html = decode_text(&doc.get_resource_by_path(&nav_path)?).0;
+        Some((nav_path, nav_html))
+    }
+
+    /// Properties of each manifest item by id, e.g. `nav`, `scripted` or `svg`
+    pub fn manifest_properties(doc: &mut EpubDocument) -> HashMap<String, Vec<String>> {
+        let Some(package) = Self::read_package(doc) else {
+            return HashMap::new();
+        };
+        MANIFEST_ITEM.find_iter(&package)
+            .filter_map(|item| {
+                let attrs = attributes(item.as_str());
+                let properties = attrs.get("properties")?.split_whitespace().map(str::to_string).collect();
+                Some((attrs.get("id")?.clone(), properties))
+            })
+            .collect()
+    }
+
+    /// Parse an EPUB3 nav document into TOC, landmarks and page-list
+    ///
+    /// Hrefs are resolved against `nav_path` so they are archive paths like NCX entries.
+    pub fn parse_nav_document(nav_html: &str, nav_path: &str) -> (Vec<TocEntry>, Vec<NavLandmark>, Vec<PageTarget>) {
+        let mut toc = Vec::new();
+        let mut landmarks = Vec::new();
+        let mut page_list = Vec::new();
+
+        let mut nav_type: Option<String> = None;
+        let mut roots: Vec<NavNode> = Vec::new();
+        let mut stack: Vec<NavNode> = Vec::new();
+        let mut capturing = false;
+        let mut play_order = 0;
+
+        for token in NAV_TOKEN.captures_iter(nav_html) {
+            if let Some(text) = token.get(4) {
+                if capturing {
+                    if let Some(node) = stack.last_mut() {
+                        node.label.push_str(text.as_str());
+                    }
+                }
+                continue;
+            }
+            let Some(element) = token.get(2) else {
+                continue;
+            };
+            let closing = &token[1] == "/";
+            let attributes = token.get(3).map_or("", |attributes| attributes.as_str());
+            let epub_type = EPUB_TYPE_ATTR.captures(attributes).map(|captures| captures[1].to_string());
+
+            match (element.as_str().to_lowercase().as_str(), closing) {
+                ("nav", false) => {
+                    nav_type = epub_type;
+                    roots.clear();
+                    stack.clear();
+                }
+                ("nav", true) => {
+                    let nodes = std::mem::take(&mut roots);
+                    let nav_types = nav_type.take().unwrap_or_default();
+                    let nav_types: Vec<&str> = nav_types.split_whitespace().collect();
+
+                    if nav_types.contains(&"toc") && toc.is_empty() {
+                        toc = nodes.into_iter().map(NavNode::into_toc_entry).collect();
+                    } else if nav_types.contains(&"landmarks") {
+                        let mut flat = Vec::new();
+                        nodes.into_iter().for_each(|node| node.flatten(&mut flat));
+                        landmarks.extend(flat.into_iter().map(|node| NavLandmark {
+                            landmark_type: node.item_type.unwrap_or_default(),
+                            label: node.label,
+                            href: node.href,
+                        }));
+                    } else if nav_types.contains(&"page-list") {
+                        let mut flat = Vec::new();
+                        nodes.into_iter().for_each(|node| node.flatten(&mut flat));
+                        page_list.extend(flat.into_iter().map(|node| PageTarget {
+                            label: node.label,
+                            href: node.href,
+                        }));
+                    }
+                }
+                ("li", false) => stack.push(NavNode::default()),
+                ("li", true) => {
+                    if let Some(mut node) = stack.pop() {
+                        node.label = Self::normalize_label(&node.label);
+                        if node.label.is_empty() && node.children.is_empty() {
+                            continue;
+                        }
+                        match stack.last_mut() {
+                            Some(parent) => parent.children.push(node),
+                            None => roots.push(node),
+                        }
+                    }
+                }
+                ("a", false) => {
+                    if let Some(node) = stack.last_mut() {
+                        if let Some(href) = HREF_ATTR.captures(attributes) {
+                            let href = html_escape::decode_html_entities(&href[1]).into_owned();
+                            node.href = Self::resolve_href(nav_path, &href);
+                        }
+                        node.item_type = epub_type;
+                        play_order += 1;
+                        node.play_order = play_order;
+                        capturing = true;
+                    }
+                }
+                ("span", false) => {
+                    capturing = stack.last().is_some_and(|node| node.label.trim().is_empty());
+                }
+                ("a", true) | ("span", true) => capturing = false,
+                _ => {}
+            }
+        }
+
+        (toc, landmarks, page_list)
+    }
+
+    /// Resolve an href against the archive path of the document containing it
+    ///
+    /// Any #fragment is kept.
+    pub fn resolve_href(base_path: &str, href: &str) -> String {
+        let (path, fragment) = match href.split_once('#') {
+            Some((path, fragment)) => (path, Some(fragment)),
+            None => (href, None),
+        };
+
+        let mut parts: Vec<&str> = base_path.split('/').collect();
+        parts.pop();
+        if path.is_empty() {
+            // Same-document link
+            parts = base_path.split('/').collect();
+        } else {
+            for part in path.split('/') {
+                match part {
+                    "" | "." => {}
+                    ".." => {
+                        parts.pop();
+                    }
+                    part => parts.push(part),
+                }
+            }
+        }
+
+        let resolved = parts.join("/");
+        match fragment {
+            Some(fragment) => format!("{}#{}", resolved, fragment),
+            None => resolved,
+        }
+    }
+
+    /// Collapse whitespace and decode entities in a nav label
+    fn normalize_label(label: &str) -> String {
+        let label = html_escape::decode_html_entities(label);
+        label.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// Convert NCX nav points into TOC entries
+    fn toc_from_ncx(nav_points: &[NavPoint]) -> Vec<TocEntry> {
+        nav_points
+            .iter()
+            .map(|point| TocEntry {
+                label: point.label.trim().to_string(),
+                href: point.content.to_string_lossy().replace('\\', "/"),
+                play_order: point.play_order,
+                children: Self::toc_from_ncx(&point.children),
+            })
+            .collect()
+    }
+
+    /// Find the TOC label for a chapter resource path
+    pub fn toc_label_for_path<'a>(toc: &'a [TocEntry], path: &Path) -> Option<&'a str> {
+        let path = path.to_string_lossy().replace('\\', "/");
+        for entry in toc {
+            let entry_path = entry.href.split('#').next().unwrap_or_default();
+            if entry_path == path {
+                return Some(&entry.label);
+            }
+            if let Some(label) = Self::toc_label_for_path(&entry.children, Path::new(&path)) {
+                return Some(label);
+            }
+        }
+        None
+    }
+
+    /// Read an archive entry as text, if present and readable
+    fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Option<String> {
+        let mut entry = archive.by_name(name).ok()?;
+        let mut text = String::new();
+        entry.read_to_string(&mut text).ok()?;
+        Some(text)
+    }
+
+    /// Check archive entries for the encryption flag
+    fn archive_is_encrypted<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<bool, EpubOpenError> {
+        for index in 0..archive.len() {
+            if archive.by_index_raw(index)?.encrypted() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Re-pack an encrypted archive into an unencrypted in-memory copy
+    fn decrypt_archive<R: Read + Seek>(
+        path: &Path,
+        archive: &mut ZipArchive<R>,
+        password: &str,
+    ) -> Result<Cursor<Vec<u8>>, EpubOpenError> {
+        let invalid_password = || EpubOpenError::InvalidPassword { path: path.to_path_buf() };
+        let options = SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+        for index in 0..archive.len() {
+            let mut entry = match archive.by_index_decrypt(index, password.as_bytes()) {
+                Ok(entry) => entry,
+                Err(ZipError::InvalidPassword) => return Err(invalid_password()),
+                Err(e) => return Err(e.into()),
+            };
+            let name = entry.name().to_string();
+
+            if entry.is_dir() {
+                writer.add_directory(name, options)?;
+                continue;
+            }
+
+            // ZipCrypto only checks one byte of the key, so a wrong password
+            // usually surfaces as a corrupt stream or CRC mismatch here
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).map_err(|_| invalid_password())?;
+
+            writer.start_file(name, options)?;
+            writer.write_all(&data)?;
+        }
+
+        let mut cursor = writer.finish()?;
+        cursor.set_position(0);
+        Ok(cursor)
+    }
+}
+
+/// Decode text content to UTF-8, returning the sniffed encoding when it was mislabeled
+///
+/// A byte order mark wins, then valid UTF-8, then a non-UTF-8 charset declared
+/// in the XML prolog or a meta tag. Anything else, typically a legacy book that
+/// claims UTF-8 but holds Windows-1251, GBK or Shift_JIS, is sniffed.
+pub fn decode_text(data: &[u8]) -> (String, Option<&'static Encoding>) {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(data) {
+        return (encoding.decode_without_bom_handling(&data[bom_length..]).0.into_owned(), None);
+    }
+    if let Ok(text) = std::str::from_utf8(data) {
+        return (text.to_string(), None);
+    }
+    if let Some(encoding) = declared_encoding(data).filter(|encoding| *encoding != UTF_8) {
+        return (encoding.decode_without_bom_handling(data).0.into_owned(), None);
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(data, true);
+    let encoding = detector.guess(None, true);
+    (encoding.decode_without_bom_handling(data).0.into_owned(), Some(encoding))
+}
+
+/// Charset named by the XML declaration or a meta tag near the start of a document
+fn declared_encoding(data: &[u8]) -> Option<&'static Encoding> {
+    let head = &data[..data.len().min(1024)];
+    [&*XML_ENCODING, &*META_CHARSET].iter()
+        .find_map(|pattern| pattern.captures(head))
+        .and_then(|captures| Encoding::for_label(&captures[1]))
+}
+
+/// Builds EPUB3 navigation documents from a table of contents
+pub struct NavDocumentBuilder;
+
+impl NavDocumentBuilder {
+    /// Synthesize a nav.xhtml document for books that only carry an NCX
+    ///
+    /// `base_dir` is the archive directory the nav document will be written to;
+    /// TOC hrefs are made relative to it.
+    pub fn build(title: &str, toc: &[TocEntry], base_dir: &str) -> String {
+        let mut nav = String::new();
+        nav.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        nav.push_str("<!DOCTYPE html>\n");
+        nav.push_str("<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n");
+        nav.push_str("<head>\n");
+        nav.push_str(&format!("  <title>{}</title>\n", html_escape::encode_text(title)));
+        nav.push_str("</head>\n");
+        nav.push_str("<body>\n");
+        nav.push_str("  <nav epub:type=\"toc\" id=\"toc\">\n");
+        nav.push_str(&format!("    <h1>{}</h1>\n", html_escape::encode_text(title)));
+        Self::write_list(&mut nav, toc, base_dir, 2);
+        nav.push_str("  </nav>\n");
+        nav.push_str("</body>\n");
+        nav.push_str("</html>\n");
+        nav
+    }
+
+    /// Write a nested ordered list of TOC entries
+    fn write_list(nav: &mut String, entries: &[TocEntry], base_dir: &str, depth: usize) {
+        if entries.is_empty() {
+            return;
+        }
+
+        let indent = "  ".repeat(depth);
+        nav.push_str(&format!("{}<ol>\n", indent));
+        for entry in entries {
+            let href = Self::relative_href(&entry.href, base_dir);
+            nav.push_str(&format!(
+                "{}  <li><a href=\"{}\">{}</a>",
+                indent,
+                html_escape::encode_double_quoted_attribute(&href),
+                html_escape::encode_text(&entry.label)
+            ));
+            if entry.children.is_empty() {
+                nav.push_str("</li>\n");
+            } else {
+                nav.push('\n');
+                Self::write_list(nav, &entry.children, base_dir, depth + 2);
+                nav.push_str(&format!("{}  </li>\n", indent));
+            }
+        }
+        nav.push_str(&format!("{}</ol>\n", indent));
+    }
+
+    /// Make an archive path relative to the nav document's directory
+    fn relative_href(href: &str, base_dir: &str) -> String {
+        let base = base_dir.trim_end_matches('/');
+        if base.is_empty() {
+            return href.to_string();
+        }
+        href.strip_prefix(&format!("{}/", base))
+            .unwrap_or(href)
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(label: &str, href: &str, children: Vec<TocEntry>) -> TocEntry {
+        TocEntry {
+            label: label.to_string(),
+            href: href.to_string(),
+            play_order: 0,
+            children,
+        }
+    }
+
+    #[test]
+    fn test_nav_document_from_ncx_toc() {
+        let toc = vec![
+            entry("Part One", "OEBPS/part1.xhtml", vec![
+                entry("Chapter 1", "OEBPS/ch1.xhtml#start", Vec::new()),
+            ]),
+            entry("Notes & Sources", "OEBPS/notes.xhtml", Vec::new()),
+        ];
+
+        let nav = NavDocumentBuilder::build("Contents", &toc, "OEBPS");
+
+        assert!(nav.contains("epub:type=\"toc\""));
+        assert!(nav.contains("<a href=\"part1.xhtml\">Part One</a>"));
+        assert!(nav.contains("<a href=\"ch1.xhtml#start\">Chapter 1</a>"));
+        assert!(nav.contains("Notes &amp; Sources"));
+        assert_eq!(nav.matches("<ol>").count(), 2);
+    }
+
+    #[test]
+    fn test_toc_label_lookup_ignores_fragments() {
+        let toc = vec![
+            entry("Part One", "OEBPS/part1.xhtml", vec![
+                entry("Chapter 1", "OEBPS/ch1.xhtml#start", Vec::new()),
+            ]),
+        ];
+
+        assert_eq!(EpubParser::toc_label_for_path(&toc, Path::new("OEBPS/ch1.xhtml")), Some("Chapter 1"));
+        assert_eq!(EpubParser::toc_label_for_path(&toc, Path::new("OEBPS/missing.xhtml")), None);
+    }
+
+    #[test]
+    fn test_parse_nested_nav_document() {
+        let nav = r#"<html xmlns:epub="http://www.idpf.org/2007/ops"><body>
+            <nav epub:type="toc"><h1>Contents</h1><ol>
+                <li><span>Part One</span><ol>
+                    <li><a href="text/ch1.xhtml">Chapter 1</a><ol>
+                        <li><a href="text/ch1.xhtml#s1">Section <em>1.1</em></a></li>
+                    </ol></li>
+                    <li><a href="text/ch2.xhtml">Fish &amp; Chips</a></li>
+                </ol></li>
+                <li><a href="../notes.xhtml">Notes</a></li>
+            </ol></nav>
+            <nav epub:type="landmarks" hidden=""><ol>
+                <li><a epub:type="bodymatter" href="text/ch1.xhtml">Start</a></li>
+            </ol></nav>
+            <nav epub:type="page-list" hidden=""><ol>
+                <li><a href="text/ch1.xhtml#p1">1</a></li>
+                <li><a href="text/ch2.xhtml#p2">2</a></li>
+            </ol></nav>
+        </body></html>"#;
+
+        let (toc, landmarks, page_list) = EpubParser::parse_nav_document(nav, "OEBPS/nav.xhtml");
+
+        assert_eq!(toc.len(), 2);
+        assert_eq!(toc[0].label, "Part One");
+        assert_eq!(toc[0].depth(), 3);
+        assert_eq!(toc[0].children[0].href, "OEBPS/text/ch1.xhtml");
+        assert_eq!(toc[0].children[0].children[0].label, "Section 1.1");
+        assert_eq!(toc[0].children[0].children[0].href, "OEBPS/text/ch1.xhtml#s1");
+        assert_eq!(toc[0].children[1].label, "Fish & Chips");
+        assert_eq!(toc[1].href, "notes.xhtml");
+
+        assert_eq!(landmarks.len(), 1);
+        assert_eq!(landmarks[0].landmark_type, "bodymatter");
+        assert_eq!(page_list.iter().map(|page| page.label.as_str()).collect::<Vec<_>>(), vec!["1", "2"]);
+        assert_eq!(page_list[1].href, "OEBPS/text/ch2.xhtml#p2");
+    }
+
+    #[test]
+    fn test_detect_features_of_fixed_layout_epub3() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("book.epub");
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        let options = SimpleFileOptions::default();
+
+        writer.start_file("META-INF/container.xml", options).unwrap();
+        writer.write_all(br#"<container><rootfiles><rootfile full-path="OEBPS/content.opf"/></rootfiles></container>"#).unwrap();
+        writer.start_file("OEBPS/content.opf", options).unwrap();
+        writer.write_all(br#"<package version="3.0">
+            <metadata><meta property="rendition:layout">pre-paginated</meta></metadata>
+            <manifest>
+                <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+                <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+            </manifest>
+        </package>"#).unwrap();
+        writer.finish().unwrap();
+
+        let features = EpubParser::detect_features(&path).unwrap();
+
+        assert_eq!(features.version.as_deref(), Some("3.0"));
+        assert!(features.fixed_layout);
+        assert!(!features.zip_encryption);
+        assert!(!features.media_overlays);
+        assert_eq!(features.nav_type, Some(NavType::NavAndNcx));
+        assert_eq!(features.tags(), vec!["version:3.0", "fixed-layout", "nav:nav+ncx"]);
+    }
+
+    #[test]
+    fn test_open_repairs_defects_and_reports_warnings() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("broken.epub");
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        let options = SimpleFileOptions::default();
+
+        writer.start_file("META-INF/container.xml", options).unwrap();
+        writer.write_all(br#"<container><rootfiles><rootfile full-path="content.opf"/></rootfiles></container>"#).unwrap();
+        writer.start_file("content.opf", options).unwrap();
+        writer.write_all(br#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+            <metadata/>
+            <manifest>
+                <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+                <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+                <item id="art" href="images/cover.jpg" media-type="image/jpeg"/>
+            </manifest>
+            <spine><itemref idref="c1"/><itemref idref="c2"/><itemref idref="gone"/></spine>
+        </package>"#).unwrap();
+        writer.start_file("c1.xhtml", options).unwrap();
+        writer.write_all(b"<html><body><p>Caf\xe9 \x93noir\x94</p></body></html>").unwrap();
+        writer.start_file("images/cover.jpg", options).unwrap();
+        writer.write_all(b"jpeg").unwrap();
+        writer.finish().unwrap();
+
+        let (mut doc, warnings) = EpubParser::open_with_warnings(&path, None).unwrap();
+
+        let defects: Vec<EpubDefect> = warnings.iter().map(|warning| warning.defect).collect();
+        assert_eq!(defects, vec![
+            EpubDefect::MissingFile,
+            EpubDefect::BrokenSpineReference,
+            EpubDefect::BrokenSpineReference,
+            EpubDefect::MissingCover,
+            EpubDefect::BadEncoding,
+        ]);
+        assert!(warnings.iter().all(|warning| warning.repaired));
+        assert_eq!(doc.spine.iter().map(|item| item.idref.as_str()).collect::<Vec<_>>(), vec!["c1"]);
+        assert_eq!(doc.get_cover().unwrap().0, b"jpeg");
+        assert_eq!(
+            EpubParser::read_text(&mut doc, "c1").unwrap(),
+            "<html><body><p>Café \u{201c}noir\u{201d}</p></body></html>"
+        );
+    }
+
+    #[test]
+    fn test_cover_falls_back_to_first_page_image() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("coverless.epub");
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        let options = SimpleFileOptions::default();
+
+        writer.start_file("META-INF/container.xml", options).unwrap();
+        writer.write_all(br#"<container><rootfiles><rootfile full-path="OEBPS/content.opf"/></rootfiles></container>"#).unwrap();
+        writer.start_file("OEBPS/content.opf", options).unwrap();
+        writer.write_all(br#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+            <metadata/>
+            <manifest>
+                <item id="front" href="text/front.xhtml" media-type="application/xhtml+xml"/>
+                <item id="art" href="images/front%20art.png" media-type="image/png"/>
+                <item id="logo" href="images/logo.png" media-type="image/png"/>
+            </manifest>
+            <spine><itemref idref="front"/></spine>
+        </package>"#).unwrap();
+        writer.start_file("OEBPS/text/front.xhtml", options).unwrap();
+        writer.write_all(br#"<html><body><svg xmlns:xlink="http://www.w3.org/1999/xlink"><image xlink:href="../images/front%20art.png"/></svg><img src="../images/logo.png"/></body></html>"#).unwrap();
+        for name in ["OEBPS/images/front art.png", "OEBPS/images/logo.png"] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(name.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut doc = EpubParser::open(&path, None).unwrap();
+        let cover = EpubParser::cover(&mut doc).unwrap();
+
+        assert_eq!(cover.source, CoverSource::FirstPageImage);
+        assert_eq!(cover.mime, "image/png");
+        assert_eq!(cover.data, b"OEBPS/images/front art.png");
+    }
+
+    #[test]
+    fn test_text_isbn_reads_copyright_page() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("no-identifier.epub");
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        let options = SimpleFileOptions::default();
+
+        writer.start_file("META-INF/container.xml", options).unwrap();
+        writer.write_all(br#"<container><rootfiles><rootfile full-path="content.opf"/></rootfiles></container>"#).unwrap();
+        writer.start_file("content.opf", options).unwrap();
+        let mut manifest = String::new();
+        let mut spine = String::new();
+        for i in 0..8 {
+            manifest.push_str(&format!(r#"<item id="c{i}" href="c{i}.xhtml" media-type="application/xhtml+xml"/>"#));
+            spine.push_str(&format!(r#"<itemref idref="c{i}"/>"#));
+        }
+        writer.write_all(format!(r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0"><metadata/><manifest>{manifest}</manifest><spine>{spine}</spine></package>"#).as_bytes()).unwrap();
+        for i in 0..8 {
+            let body = match i {
+                // A cited book in the middle of the text is not the book's own ISBN
+                4 => "<p>See ISBN 978-0-262-16209-8.</p>",
+                7 => "<p>Copyright &#169; 2020</p><p><span>ISBN</span> 0-8044-2957-X (ebook)</p>",
+                _ => "<p>Chapter text.</p>",
+            };
+            writer.start_file(format!("c{i}.xhtml"), options).unwrap();
+            writer.write_all(format!("<html><body>{body}</body></html>").as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut doc = EpubParser::open(&path, None).unwrap();
+        assert_eq!(EpubParser::metadata(&mut doc).isbn(), None);
+        assert_eq!(EpubParser::text_isbn(&mut doc).as_deref(), Some("9780804429573"));
+    }
+
+    #[test]
+    fn test_decode_text_handles_bom_and_utf16() {
+        assert_eq!(decode_text(b"\xEF\xBB\xBFhi"), ("hi".to_string(), None));
+        assert_eq!(decode_text(b"\xFF\xFEh\0i\0"), ("hi".to_string(), None));
+        assert_eq!(decode_text(b"\xFE\xFF\0h\0i"), ("hi".to_string(), None));
+    }
+
+    #[test]
+    fn test_decode_text_sniffs_legacy_encodings() {
+        use encoding_rs::{GBK, SHIFT_JIS, WINDOWS_1251};
+
+        let samples = [
+            (WINDOWS_1251, "<p>Всё смешалось в доме Облонских. Жена узнала, что муж был в связи с бывшею в их доме француженкою-гувернанткой.</p>"),
+            (SHIFT_JIS, "<p>吾輩は猫である。名前はまだ無い。どこで生れたかとんと見当がつかぬ。</p>"),
+            (GBK, "<p>滚滚长江东逝水，浪花淘尽英雄。是非成败转头空。青山依旧在，几度夕阳红。</p>"),
+        ];
+        for (encoding, text) in samples {
+            let html = format!(r#"<?xml version="1.0" encoding="utf-8"?><html><body>{}</body></html>"#, text);
+            let (decoded, sniffed) = decode_text(&encoding.encode(&html).0);
+            assert_eq!(decoded, html);
+            assert_eq!(sniffed, Some(encoding));
+        }
+
+        // A correct legacy declaration is honored without a warning
+        let declared = WINDOWS_1251.encode(r#"<?xml version="1.0" encoding="windows-1251"?><p>Мир</p>"#).0;
+        assert_eq!(decode_text(&declared), (r#"<?xml version="1.0" encoding="windows-1251"?><p>Мир</p>"#.to_string(), None));
+    }
+}
@@ -0,0 +1,85 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Number following an "ISBN" label, with or without separators
+static ISBN_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\bISBN(?:-1[03])?:?\s*((?:97[89][- ]?)?[0-9][0-9\- ]{7,12}[0-9X])\b").unwrap()
+});
+
+/// Normalize an ISBN-10 or ISBN-13 to ISBN-13 digits
+///
+/// Hyphens and spaces are dropped and ISBN-10s get the 978 prefix and a new
+/// check digit. The input's own check digit is not verified, see `is_valid_isbn`.
+pub fn normalize_isbn(raw: &str) -> Option<String> {
+    let isbn: String = raw.trim()
+        .chars()
+        .filter(|c| !matches!(c, '-' | ' ' | '\u{2010}'..='\u{2013}'))
+        .collect::<String>()
+        .to_uppercase();
+
+    match isbn.len() {
+        13 if isbn.chars().all(|c| c.is_ascii_digit()) && (isbn.starts_with("978") || isbn.starts_with("979")) => Some(isbn),
+        10 if isbn[..9].chars().all(|c| c.is_ascii_digit()) && isbn[9..].chars().all(|c| c.is_ascii_digit() || c == 'X') => {
+            let body = format!("978{}", &isbn[..9]);
+            let check = (10 - isbn13_sum(&body) % 10) % 10;
+            Some(format!("{}{}", body, check))
+        }
+        _ => None,
+    }
+}
+
+/// Check an ISBN-10 or ISBN-13, including its check digit
+pub fn is_valid_isbn(raw: &str) -> bool {
+    let isbn: String = raw.chars().filter(|c| c.is_ascii_digit() || matches!(c, 'X' | 'x')).collect();
+    match isbn.len() {
+        10 => {
+            let sum: u32 = isbn.chars()
+                .enumerate()
+                .map(|(i, c)| (10 - i as u32) * c.to_digit(10).unwrap_or(10))
+                .sum();
+            !isbn[..9].contains(['X', 'x']) && sum.is_multiple_of(11)
+        }
+        13 => isbn.chars().all(|c| c.is_ascii_digit()) && isbn13_sum(&isbn).is_multiple_of(10),
+        _ => false,
+    }
+}
+
+/// ISBNs labelled as such in text, such as a copyright page, as ISBN-13
+///
+/// Numbers with a wrong check digit are skipped, as they are usually page or
+/// catalog numbers rather than misprinted ISBNs.
+pub fn find_isbns(text: &str) -> Vec<String> {
+    ISBN_PATTERN.captures_iter(text)
+        .map(|captures| captures[1].to_string())
+        .filter(|isbn| is_valid_isbn(isbn))
+        .filter_map(|isbn| normalize_isbn(&isbn))
+        .collect()
+}
+
+/// Weighted digit sum used by ISBN-13 check digits
+fn isbn13_sum(digits: &str) -> u32 {
+    digits.chars()
+        .enumerate()
+        .map(|(i, c)| c.to_digit(10).unwrap_or(0) * if i % 2 == 0 { 1 } else { 3 })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_and_find_isbns() {
+        assert_eq!(normalize_isbn("0-262-16209-1").as_deref(), Some("9780262162098"));
+        assert_eq!(normalize_isbn("978-0-262-16209-8").as_deref(), Some("9780262162098"));
+        assert_eq!(normalize_isbn("080442957X").as_deref(), Some("9780804429573"));
+        assert_eq!(normalize_isbn("1234567890123"), None);
+
+        assert!(is_valid_isbn("080442957X"));
+        assert!(!is_valid_isbn("9780262162099"));
+
+        let copyright_page = "Copyright © 2002. ISBN 0-262-16209-1 (hardcover)\n\
+                              ISBN 978-0-262-16209-9 (misprint)\nebook ISBN: 9781400079988";
+        assert_eq!(find_isbns(copyright_page), vec!["9780262162098", "9781400079988"]);
+    }
+}
@@ -0,0 +1,12 @@
+//! UI-agnostic domain types and logic shared by every ebook reader frontend
+//!
+//! Nothing here depends on Slint or the database, so the crate also builds for
+//! `wasm32-unknown-unknown`. The EPUB parser opens books by path and so needs a
+//! file system at run time.
+
+pub mod annotation_export;
+pub mod epub_metadata;
+pub mod epub_parser;
+pub mod isbn;
+pub mod models;
+pub mod url_encoding;
@@ -20,6 +20,20 @@ pub struct Annotation {
     pub formatting: Option<TextFormatting>,
     pub is_favorite: bool,
     pub cross_references: Vec<String>, // IDs of related annotations
+    /// Marked area of a fixed-layout or scanned page; text annotations have none
+    #[serde(default)]
+    pub region: Option<PageRegion>,
+}
+
+/// A rectangle on a page, in fractions of the page's width and height
+///
+/// Fractions keep a region in place whatever size the page is drawn at.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PageRegion {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
 }
 
 /// Bookmark model for storing page bookmarks
@@ -37,8 +51,24 @@ pub struct Bookmark {
     pub is_favorite: bool,
 }
 
-/// Text position within a page/document
+/// Named save point capturing position, theme and notes
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub id: String,
+    pub book_id: String,
+    pub name: String,
+    pub page_number: u32,
+    pub position: TextPosition,
+    pub theme_name: Option<String>, // Reading theme active when the checkpoint was saved
+    pub notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Characters of surrounding text a quote anchor keeps on each side
+pub const QUOTE_CONTEXT_CHARS: usize = 32;
+
+/// Text position within a page/document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TextPosition {
     pub start_offset: usize,
     pub end_offset: usize,
@@ -46,6 +76,20 @@ pub struct TextPosition {
     pub chapter_id: Option<String>,
     pub line_number: Option<u32>,
     pub column_number: Option<u32>,
+    /// The anchored text with its surroundings, to find it again when offsets drift
+    #[serde(default)]
+    pub quote: Option<TextQuote>,
+}
+
+/// A text-quote anchor: the exact text with the text just before and after it
+///
+/// Offsets break when a book is re-parsed or edited; the quote still
+/// identifies the passage, and its context tells repeated passages apart.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextQuote {
+    pub prefix: String,
+    pub exact: String,
+    pub suffix: String,
 }
 
 /// Highlight colors for annotations
@@ -141,6 +185,34 @@ pub enum ExportFormat {
     Html,
     Pdf,
     Txt,
+    Anki, // Tab-separated notes for Anki's text import
+}
+
+/// How an annotation becomes an Anki card
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum AnkiCardStyle {
+    Cloze,
+    Basic, // Front and back; only annotations with a note become cards
+}
+
+/// Which note-taking app a vault export is written for
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum VaultFlavor {
+    Obsidian, // Headings per annotation, links to block ids
+    Logseq,   // An outline with a block per annotation, links by block reference
+}
+
+/// Templates for Anki cards
+///
+/// Templates may use `{text}`, `{note}`, `{book}`, `{page}`, `{chapter}` and
+/// `{tags}`, which are replaced with the annotation's HTML-escaped values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnkiExportOptions {
+    pub card_style: AnkiCardStyle,
+    pub deck_template: String,
+    pub front_template: String,
+    pub back_template: String,
+    pub extra_tags: Vec<String>, // Added to every card alongside the annotation's tags
 }
 
 /// Annotation export options
@@ -150,11 +222,18 @@ pub struct ExportOptions {
     pub include_highlights: bool,
     pub include_notes: bool,
     pub include_bookmarks: bool,
+    #[serde(default)]
+    pub include_checkpoints: bool,
     pub include_timestamps: bool,
+    /// Stamp the export with the time it was made; off keeps repeated exports identical
+    #[serde(default)]
+    pub include_export_date: bool,
     pub include_page_numbers: bool,
     pub include_context: bool,
     pub group_by_chapter: bool,
     pub sort_by: AnnotationSortBy,
+    #[serde(default)]
+    pub anki: AnkiExportOptions,
 }
 
 /// Sorting options for annotations
@@ -242,6 +321,31 @@ impl Annotation {
             formatting: None,
             is_favorite: false,
             cross_references: Vec::new(),
+            region: None,
+        }
+    }
+
+    /// Create an annotation marking an area of a page
+    pub fn new_area(book_id: String, page_number: u32, region: PageRegion, annotation_type: AnnotationType) -> Self {
+        let position = TextPosition {
+            start_offset: 0,
+            end_offset: 0,
+            paragraph_index: 0,
+            chapter_id: None,
+            line_number: None,
+            column_number: None,
+            quote: None,
+        };
+        let mut annotation = Self::new(book_id, page_number, String::new(), position, annotation_type);
+        annotation.region = Some(region);
+        annotation
+    }
+
+    /// The highlighted text, or a description of the marked area
+    pub fn excerpt(&self) -> String {
+        match &self.region {
+            Some(region) if self.selected_text.is_empty() => region.describe(),
+            _ => self.selected_text.clone(),
         }
     }
 
@@ -418,6 +522,22 @@ impl Bookmark {
     }
 }
 
+impl Checkpoint {
+    /// Create a new checkpoint
+    pub fn new(book_id: String, name: String, page_number: u32, position: TextPosition) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            book_id,
+            name,
+            page_number,
+            position,
+            theme_name: None,
+            notes: None,
+            created_at: Utc::now(),
+        }
+    }
+}
+
 impl HighlightColor {
     /// Get hex color value
     pub fn to_hex(&self) -> String {
@@ -524,6 +644,138 @@ impl AnnotationType {
     }
 }
 
+impl PageRegion {
+    /// A region between two corners given in pixels on a page of the given size
+    ///
+    /// Corners outside the page are moved onto it. Returns `None` when the page
+    /// has no size or the region has no area on it.
+    pub fn from_pixels(corner: (f32, f32), opposite: (f32, f32), page_width: f32, page_height: f32) -> Option<Self> {
+        if page_width <= 0.0 || page_height <= 0.0 {
+            return None;
+        }
+        let fraction = |value: f32, size: f32| (value / size).clamp(0.0, 1.0);
+        let (left, right) = (corner.0.min(opposite.0), corner.0.max(opposite.0));
+        let (top, bottom) = (corner.1.min(opposite.1), corner.1.max(opposite.1));
+        let x = fraction(left, page_width);
+        let y = fraction(top, page_height);
+        let width = fraction(right, page_width) - x;
+        let height = fraction(bottom, page_height) - y;
+        (width > 0.0 && height > 0.0).then_some(Self { x, y, width, height })
+    }
+
+    /// Left, top, width and height in pixels on a page of the given size
+    pub fn to_pixels(&self, page_width: f32, page_height: f32) -> (f32, f32, f32, f32) {
+        (self.x * page_width, self.y * page_height, self.width * page_width, self.height * page_height)
+    }
+
+    /// Whether a point, in fractions of the page, falls inside the region
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
+    }
+
+    /// Describe where the region is, for exports that have no picture of the page
+    pub fn describe(&self) -> String {
+        let percent = |fraction: f32| (fraction * 100.0).round() as u32;
+        format!(
+            "Area at {}% across, {}% down ({}% × {}% of the page)",
+            percent(self.x), percent(self.y), percent(self.width), percent(self.height)
+        )
+    }
+}
+
+impl TextQuote {
+    /// Capture the quote for a byte range of content
+    pub fn capture(content: &str, start: usize, end: usize) -> Option<Self> {
+        let exact = content.get(start..end).filter(|exact| !exact.is_empty())?;
+        let before = &content[..start];
+        let prefix_start = before.char_indices().rev()
+            .nth(QUOTE_CONTEXT_CHARS - 1)
+            .map_or(0, |(index, _)| index);
+        let suffix: String = content[end..].chars().take(QUOTE_CONTEXT_CHARS).collect();
+
+        Some(Self {
+            prefix: before[prefix_start..].to_string(),
+            exact: exact.to_string(),
+            suffix,
+        })
+    }
+}
+
+impl ExportFormat {
+    /// Get file extension for exported files
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::Txt => "txt",
+            ExportFormat::Anki => "tsv",
+        }
+    }
+}
+
+impl AnkiCardStyle {
+    /// Name of the Anki note type the cards use
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            AnkiCardStyle::Cloze => "Cloze",
+            AnkiCardStyle::Basic => "Basic",
+        }
+    }
+
+    /// Templates that suit this style
+    pub fn default_templates(&self) -> (&'static str, &'static str) {
+        match self {
+            AnkiCardStyle::Cloze => ("{{c1::{text}}}", "{note}<br><i>{book}, page {page}</i>"),
+            AnkiCardStyle::Basic => ("{note}", "{text}<br><i>{book}, page {page}</i>"),
+        }
+    }
+}
+
+impl Default for AnkiCardStyle {
+    fn default() -> Self {
+        AnkiCardStyle::Cloze
+    }
+}
+
+impl VaultFlavor {
+    /// Get display name
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            VaultFlavor::Obsidian => "Obsidian",
+            VaultFlavor::Logseq => "Logseq",
+        }
+    }
+}
+
+impl Default for VaultFlavor {
+    fn default() -> Self {
+        VaultFlavor::Obsidian
+    }
+}
+
+impl AnkiExportOptions {
+    /// Options for a card style with its default templates
+    pub fn with_style(card_style: AnkiCardStyle) -> Self {
+        let (front_template, back_template) = card_style.default_templates();
+        Self {
+            card_style,
+            deck_template: "Books::{book}".to_string(),
+            front_template: front_template.to_string(),
+            back_template: back_template.to_string(),
+            extra_tags: Vec::new(),
+        }
+    }
+}
+
+impl Default for AnkiExportOptions {
+    fn default() -> Self {
+        Self::with_style(AnkiCardStyle::default())
+    }
+}
+
 impl Default for TextFormatting {
     fn default() -> Self {
         Self {
@@ -545,11 +797,14 @@ impl Default for ExportOptions {
             include_highlights: true,
             include_notes: true,
             include_bookmarks: true,
+            include_checkpoints: true,
             include_timestamps: true,
+            include_export_date: false,
             include_page_numbers: true,
             include_context: true,
             group_by_chapter: false,
             sort_by: AnnotationSortBy::CreatedAt,
+            anki: AnkiExportOptions::default(),
         }
     }
 }
@@ -3,6 +3,12 @@ use std::path::PathBuf;
 use chrono::{DateTime, Utc};
 use crate::models::library::ReadingStatus;
 
+/// Typical adult silent reading speed, used until the reader sets their own
+pub const DEFAULT_READING_SPEED_WPM: u32 = 225;
+
+/// Words on a typical printed page, for books without a print page list
+pub const WORDS_PER_PAGE: u32 = 250;
+
 /// Book information model
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Book {
@@ -14,6 +20,18 @@ pub struct Book {
     pub description: Option<String>,
     pub publication_date: Option<DateTime<Utc>>,
     pub language: Option<String>,
+    pub series: Option<String>,
+    pub series_index: Option<f32>, // Position within the series, e.g. 2.5 for a novella
+    #[serde(default)]
+    pub publisher: Option<String>,
+    #[serde(default)]
+    pub rights: Option<String>,
+    #[serde(default)]
+    pub contributors: Vec<String>, // Translators, illustrators, editors
+    #[serde(default)]
+    pub subjects: Vec<String>,
+    #[serde(default)]
+    pub identifiers: Vec<String>, // Every identifier as written, e.g. "urn:uuid:..."
     pub file_path: PathBuf,
     pub file_size: u64,
     pub file_format: BookFormat,
@@ -21,6 +39,8 @@ pub struct Book {
     pub cover_url: Option<String>,
     pub page_count: Option<u32>,
     pub word_count: Option<u32>,
+    #[serde(default)]
+    pub chapter_word_counts: Vec<u32>, // In reading order
     pub reading_progress: f32, // 0.0 to 1.0
     pub reading_status: ReadingStatus,
     pub last_read_position: Option<ReadingPosition>,
@@ -92,11 +112,22 @@ pub struct BookViewModel {
     pub rating: Option<u8>,
     pub last_opened: Option<DateTime<Utc>>,
     pub added_date: DateTime<Utc>,
+    pub reading_time: Option<String>,
+    pub series: Option<String>, // e.g. "The Expanse #2"
 }
 
 impl From<Book> for BookViewModel {
     fn from(book: Book) -> Self {
+        Self::with_reading_speed(book, DEFAULT_READING_SPEED_WPM)
+    }
+}
+
+impl BookViewModel {
+    /// Build a view model whose reading time uses the reader's own speed
+    pub fn with_reading_speed(book: Book, words_per_minute: u32) -> Self {
         Self {
+            reading_time: book.reading_time_label(words_per_minute),
+            series: book.series_label(),
             id: book.id,
             title: book.title,
             author: book.author,
@@ -168,6 +199,40 @@ pub struct ReadingSession {
     pub progress_end: f32,
 }
 
+impl ReadingSession {
+    /// Start a session at the book's current progress
+    pub fn start(book: &Book, start_time: DateTime<Utc>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            book_id: book.id.clone(),
+            start_time,
+            end_time: None,
+            duration_minutes: 0,
+            pages_read: 0,
+            words_read: 0,
+            progress_start: book.reading_progress,
+            progress_end: book.reading_progress,
+        }
+    }
+
+    /// End the session, crediting the pages and words between its start and the book's progress now
+    pub fn end(&mut self, book: &Book, end_time: DateTime<Utc>) {
+        let advanced = (book.reading_progress - self.progress_start).max(0.0);
+        let seconds = (end_time - self.start_time).num_seconds().max(0);
+
+        self.end_time = Some(end_time);
+        self.duration_minutes = ((seconds + 30) / 60) as u32;
+        self.progress_end = book.reading_progress;
+        self.pages_read = (advanced * book.page_count.unwrap_or(0) as f32).round() as u32;
+        self.words_read = (advanced * book.word_count.unwrap_or(0) as f32).round() as u32;
+    }
+
+    /// Check whether the session is still running
+    pub fn is_active(&self) -> bool {
+        self.end_time.is_none()
+    }
+}
+
 /// Book statistics model
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BookStatistics {
@@ -199,6 +264,13 @@ impl Book {
             description: None,
             publication_date: None,
             language: None,
+            series: None,
+            series_index: None,
+            publisher: None,
+            rights: None,
+            contributors: Vec::new(),
+            subjects: Vec::new(),
+            identifiers: Vec::new(),
             file_path,
             file_size,
             file_format,
@@ -206,6 +278,7 @@ impl Book {
             cover_url: None,
             page_count: None,
             word_count: None,
+            chapter_word_counts: Vec::new(),
             reading_progress: 0.0,
             reading_status: ReadingStatus::Unread,
             last_read_position: None,
@@ -253,13 +326,30 @@ impl Book {
     
     /// Get estimated reading time in minutes
     pub fn estimated_reading_time(&self) -> Option<u32> {
-        self.word_count.map(|words| {
-            // Average reading speed: 200-250 words per minute
-            let reading_speed = 225.0;
-            (words as f32 / reading_speed).ceil() as u32
-        })
+        self.estimated_reading_time_at(DEFAULT_READING_SPEED_WPM)
+    }
+
+    /// Get estimated reading time in minutes at a given reading speed
+    pub fn estimated_reading_time_at(&self, words_per_minute: u32) -> Option<u32> {
+        self.word_count
+            .filter(|&words| words > 0)
+            .map(|words| words.div_ceil(words_per_minute.max(1)))
+    }
+
+    /// Reading time for book cards, e.g. "~6 h 20 m"
+    pub fn reading_time_label(&self, words_per_minute: u32) -> Option<String> {
+        self.estimated_reading_time_at(words_per_minute).map(format_reading_time)
     }
     
+    /// Series name with the book's position, e.g. "Dune #2"
+    pub fn series_label(&self) -> Option<String> {
+        let series = self.series.as_ref()?;
+        Some(match self.series_index {
+            Some(index) => format!("{} #{}", series, index),
+            None => series.clone(),
+        })
+    }
+
     /// Get reading progress percentage as integer
     pub fn progress_percentage(&self) -> u8 {
         (self.reading_progress * 100.0).round() as u8
@@ -300,4 +390,70 @@ impl BookCollection {
     pub fn book_count(&self) -> usize {
         self.book_ids.len()
     }
-}
\ No newline at end of file
+}
+
+/// Format a duration in minutes as "~45 m", "~2 h" or "~6 h 20 m"
+pub fn format_reading_time(minutes: u32) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("~{} m", minutes.max(1)),
+        (hours, 0) => format!("~{} h", hours),
+        (hours, minutes) => format!("~{} h {} m", hours, minutes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reading_time_label() {
+        let mut book = Book::new("Long".to_string(), "Author".to_string(), PathBuf::from("long.epub"), 0, BookFormat::Epub);
+        assert_eq!(book.reading_time_label(DEFAULT_READING_SPEED_WPM), None);
+
+        book.word_count = Some(85_500);
+        assert_eq!(book.estimated_reading_time(), Some(380));
+        assert_eq!(book.reading_time_label(DEFAULT_READING_SPEED_WPM).as_deref(), Some("~6 h 20 m"));
+        assert_eq!(book.reading_time_label(300).as_deref(), Some("~4 h 45 m"));
+
+        assert_eq!(format_reading_time(0), "~1 m");
+        assert_eq!(format_reading_time(45), "~45 m");
+        assert_eq!(format_reading_time(120), "~2 h");
+    }
+
+    #[test]
+    fn test_series_label() {
+        let mut book = Book::new("Dune Messiah".to_string(), "Frank Herbert".to_string(), PathBuf::from("dune2.epub"), 0, BookFormat::Epub);
+        assert_eq!(book.series_label(), None);
+
+        book.series = Some("Dune".to_string());
+        assert_eq!(book.series_label().as_deref(), Some("Dune"));
+        book.series_index = Some(2.0);
+        assert_eq!(book.series_label().as_deref(), Some("Dune #2"));
+        book.series_index = Some(2.5);
+        assert_eq!(book.series_label().as_deref(), Some("Dune #2.5"));
+    }
+
+    #[test]
+    fn test_reading_session_credits_progress() {
+        let mut book = Book::new("Long".to_string(), "Author".to_string(), PathBuf::from("long.epub"), 0, BookFormat::Epub);
+        book.page_count = Some(400);
+        book.word_count = Some(100_000);
+        book.reading_progress = 0.25;
+        let start = Utc::now();
+
+        let mut session = ReadingSession::start(&book, start);
+        assert!(session.is_active());
+
+        book.reading_progress = 0.3;
+        session.end(&book, start + chrono::Duration::seconds(25 * 60 + 40));
+        assert!(!session.is_active());
+        assert_eq!(session.duration_minutes, 26);
+        assert_eq!((session.pages_read, session.words_read), (20, 5_000));
+        assert_eq!(session.progress_end, 0.3);
+
+        // Paging back doesn't count as reading backwards
+        book.reading_progress = 0.1;
+        session.end(&book, start);
+        assert_eq!((session.duration_minutes, session.pages_read), (0, 0));
+    }
+}
@@ -22,6 +22,7 @@ pub enum Category {
     WantToRead,
     CurrentlyReading,
     Finished,
+    UpNext, // Ordered reading queue
     Collection(String),
     Author(String),
     Tag(String),
@@ -30,6 +31,7 @@ pub enum Category {
     Year(u32),
     Language(String),
     Rating(u8), // 1-5 stars
+    Trash, // Deleted books that can still be restored
 }
 
 /// User-created collection
@@ -47,6 +49,8 @@ pub struct Collection {
     pub smart_rules: Option<SmartCollectionRules>,
     pub is_favorite: bool,
     pub sort_order: u32,
+    #[serde(default)]
+    pub parent_id: Option<String>, // None for a top-level collection
 }
 
 /// Smart collection rules
@@ -80,6 +84,13 @@ pub enum SmartRuleField {
     FileSize,
     PageCount,
     Progress,
+    Subject,
+    Contributor,
+    Series,
+    WordCount,
+    ReadingTime, // Estimated minutes at the default reading speed
+    AnnotationCount,
+    LastOpened,
 }
 
 /// Operators for smart rules
@@ -116,6 +127,8 @@ pub struct LibraryStats {
     pub want_to_read: u32,
     pub currently_reading: u32,
     pub finished: u32,
+    pub up_next: u32, // Books in the reading queue
+    pub in_trash: u32,
     pub total_collections: u32,
     pub total_authors: u32,
     pub total_tags: u32,
@@ -171,11 +184,13 @@ pub enum LibrarySortBy {
     PublishDate,
     FileSize,
     PageCount,
+    WordCount,
     Progress,
     LastOpened,
     Genre,
     Publisher,
     Language,
+    Series,
 }
 
 /// Sort direction
@@ -289,6 +304,7 @@ impl Collection {
             smart_rules: None,
             is_favorite: false,
             sort_order: 0,
+            parent_id: None,
         }
     }
 
@@ -307,6 +323,7 @@ impl Collection {
             smart_rules: Some(rules),
             is_favorite: false,
             sort_order: 0,
+            parent_id: None,
         }
     }
 
@@ -475,6 +492,7 @@ impl Category {
             Category::WantToRead => "Want to Read".to_string(),
             Category::CurrentlyReading => "Currently Reading".to_string(),
             Category::Finished => "Finished".to_string(),
+            Category::UpNext => "Up Next".to_string(),
             Category::Collection(name) => name.clone(),
             Category::Author(name) => format!("Author: {}", name),
             Category::Tag(name) => format!("Tag: {}", name),
@@ -483,6 +501,7 @@ impl Category {
             Category::Year(year) => format!("Year: {}", year),
             Category::Language(lang) => format!("Language: {}", lang),
             Category::Rating(rating) => format!("{} Stars", rating),
+            Category::Trash => "Trash".to_string(),
         }
     }
 
@@ -493,6 +512,7 @@ impl Category {
             Category::WantToRead => "📚".to_string(),
             Category::CurrentlyReading => "📖".to_string(),
             Category::Finished => "✅".to_string(),
+            Category::UpNext => "⏭️".to_string(),
             Category::Collection(_) => "📂".to_string(),
             Category::Author(_) => "👤".to_string(),
             Category::Tag(_) => "🏷️".to_string(),
@@ -501,6 +521,7 @@ impl Category {
             Category::Year(_) => "📅".to_string(),
             Category::Language(_) => "🌐".to_string(),
             Category::Rating(_) => "⭐".to_string(),
+            Category::Trash => "🗑️".to_string(),
         }
     }
 }
@@ -512,6 +533,8 @@ impl Default for LibraryStats {
             want_to_read: 0,
             currently_reading: 0,
             finished: 0,
+            up_next: 0,
+            in_trash: 0,
             total_collections: 0,
             total_authors: 0,
             total_tags: 0,
@@ -545,6 +568,13 @@ impl SmartRuleField {
             SmartRuleField::FileSize => "File Size".to_string(),
             SmartRuleField::PageCount => "Page Count".to_string(),
             SmartRuleField::Progress => "Progress".to_string(),
+            SmartRuleField::Subject => "Subject".to_string(),
+            SmartRuleField::Contributor => "Contributor".to_string(),
+            SmartRuleField::Series => "Series".to_string(),
+            SmartRuleField::WordCount => "Word Count".to_string(),
+            SmartRuleField::ReadingTime => "Reading Time".to_string(),
+            SmartRuleField::AnnotationCount => "Annotation Count".to_string(),
+            SmartRuleField::LastOpened => "Last Opened".to_string(),
         }
     }
 
@@ -552,7 +582,8 @@ impl SmartRuleField {
     pub fn available_operators(&self) -> Vec<SmartRuleOperator> {
         match self {
             SmartRuleField::Title | SmartRuleField::Author | SmartRuleField::Genre | 
-            SmartRuleField::Publisher | SmartRuleField::Language | SmartRuleField::Tags => {
+            SmartRuleField::Publisher | SmartRuleField::Language | SmartRuleField::Tags |
+            SmartRuleField::Subject | SmartRuleField::Contributor | SmartRuleField::Series => {
                 vec![
                     SmartRuleOperator::Equals,
                     SmartRuleOperator::NotEquals,
@@ -564,7 +595,7 @@ impl SmartRuleField {
                     SmartRuleOperator::IsNotEmpty,
                 ]
             }
-            SmartRuleField::PublishDate | SmartRuleField::AddedDate => {
+            SmartRuleField::PublishDate | SmartRuleField::AddedDate | SmartRuleField::LastOpened => {
                 vec![
                     SmartRuleOperator::Equals,
                     SmartRuleOperator::NotEquals,
@@ -575,7 +606,8 @@ impl SmartRuleField {
                     SmartRuleOperator::IsNotEmpty,
                 ]
             }
-            SmartRuleField::Rating | SmartRuleField::FileSize | SmartRuleField::PageCount | SmartRuleField::Progress => {
+            SmartRuleField::Rating | SmartRuleField::FileSize | SmartRuleField::PageCount | SmartRuleField::Progress |
+            SmartRuleField::WordCount | SmartRuleField::ReadingTime | SmartRuleField::AnnotationCount => {
                 vec![
                     SmartRuleOperator::Equals,
                     SmartRuleOperator::NotEquals,
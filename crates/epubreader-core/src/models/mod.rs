@@ -0,0 +1,49 @@
+pub mod book;
+pub mod preferences;
+pub mod reading_theme;
+pub mod annotation;
+pub mod library;
+pub mod sync;
+pub mod sync_protocol;
+
+// Named rather than globbed: book and sync repeat AnnotationType, ReadingSession and UserPreferences
+pub use book::{
+    AnnotationPosition, Book, BookAnnotation, BookCollection, BookFormat, BookStatistics,
+    BookViewModel, DEFAULT_READING_SPEED_WPM, ReadingPosition, ReadingSession, WORDS_PER_PAGE,
+    format_reading_time,
+};
+pub use preferences::{
+    BackupSchedule, ChapterReadThreshold, DEFAULT_KOSYNC_SERVER, DEFAULT_SHARING_PORT,
+    DEFAULT_TRASH_RETENTION_DAYS, DuplicateHandling, FileDeletionPolicy, KosyncDocumentMatch,
+    KosyncSettings, LibraryPreferences, LibrarySharing, MetadataSource, NewsDelivery,
+    PrivacyPreferences, ReadLaterDelivery, ReadLaterProvider, ReadLaterSettings,
+    ReadingPreferences, SeriesAutoAdvance, SortBy, SortOrder, SpeechEngine, SpeechSettings,
+    SyncPreferences, SyncProvider, UiPreferences, UserPreferences, ViewMode,
+};
+pub use reading_theme::{
+    FontFamily, ReadingTheme, ReadingThemePreferences, ThemeEvent, ThemeManager, ThemeProperties,
+    ThemeTypography,
+};
+pub use annotation::{
+    AnkiCardStyle, AnkiExportOptions, Annotation, AnnotationCategory, AnnotationCluster,
+    AnnotationCollection, AnnotationFilter, AnnotationSortBy, AnnotationStats, AnnotationTag,
+    AnnotationType, Bookmark, BookmarkColor, Checkpoint, ExportFormat, ExportOptions,
+    HighlightColor, PageRegion, QUOTE_CONTEXT_CHARS, ReadingPatterns, TextFormatting, TextPosition,
+    TextQuote, VaultFlavor,
+};
+pub use library::{
+    Author, Category, Collection, Genre, LibraryFilter, LibraryOrganizer, LibrarySortBy,
+    LibraryStats, LibraryViewMode, MatchType, ReadingGoals, ReadingStatus, SmartCollectionRules,
+    SmartRule, SmartRuleField, SmartRuleOperator, SortDirection, Tag,
+};
+pub use sync::{
+    AppSettings, BookProgress, BookReadingGoals, CloudCredentials, CloudProvider, CloudSyncConfig,
+    ConflictResolution, ConflictVersion, DevicePosition, LibrarySettings, NotificationSettings,
+    POSITION_PROMPT_MARGIN, PositionConflict, PrivacySettings, QuietHours, ReadingBehaviorSettings,
+    ReadingEnvironment, ReadingInterruption, SyncCategories, SyncConflict, SyncConflictType,
+    SyncData, SyncHistoryEntry, SyncMetadata, SyncSettings, SyncStatistics, SyncStatus, SyncType,
+};
+pub use sync_protocol::{
+    BookFileRef, ChangeKind, Changeset, PullResponse, PushResponse, RegisterDeviceRequest,
+    RegisterDeviceResponse, SYNC_PROTOCOL_VERSION, SyncChange,
+};
@@ -0,0 +1,688 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Days a deleted book stays in the trash before it is removed for good
+pub const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+/// Public progress sync server run by the KOReader project
+pub const DEFAULT_KOSYNC_SERVER: &str = "https://sync.koreader.rocks";
+/// Port the library sharing server listens on unless set otherwise
+pub const DEFAULT_SHARING_PORT: u16 = 8090;
+
+/// User preferences model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserPreferences {
+    pub library: LibraryPreferences,
+    pub reading: ReadingPreferences,
+    pub ui: UiPreferences,
+    pub sync: SyncPreferences,
+    pub privacy: PrivacyPreferences,
+}
+
+/// Library management preferences
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryPreferences {
+    pub default_library_path: PathBuf,
+    pub auto_import: bool,
+    pub watch_folders: Vec<PathBuf>,
+    pub duplicate_handling: DuplicateHandling,
+    #[serde(default)]
+    pub file_deletion_policy: FileDeletionPolicy, // Applied when a book leaves the trash
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: u32, // 0 keeps deleted books until the trash is emptied
+    pub metadata_sources: Vec<MetadataSource>,
+    pub cover_download: bool,
+    pub organize_by_author: bool,
+    pub organize_by_genre: bool,
+    #[serde(default)]
+    pub backup_schedule: BackupSchedule,
+    #[serde(default)]
+    pub sharing: LibrarySharing,
+    #[serde(default)]
+    pub news: NewsDelivery,
+}
+
+/// Reading experience preferences
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadingPreferences {
+    pub default_font_family: String,
+    pub default_font_size: u16,
+    pub default_line_spacing: f32,
+    pub default_margin: u16,
+    pub default_theme: String,
+    pub page_turn_animation: bool,
+    pub reading_progress_sync: bool,
+    pub auto_bookmark: bool,
+    pub highlight_color: String,
+    pub note_color: String,
+    #[serde(default)]
+    pub series_auto_advance: SeriesAutoAdvance,
+    #[serde(default)]
+    pub chapter_read_threshold: ChapterReadThreshold,
+    #[serde(default = "default_reading_speed_wpm")]
+    pub reading_speed_wpm: u32, // Used for reading time estimates
+    #[serde(default)]
+    pub speech: SpeechSettings,
+}
+
+/// UI preferences
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiPreferences {
+    pub theme: String,
+    pub language: String,
+    pub view_mode: ViewMode,
+    pub grid_columns: u8,
+    pub sort_by: SortBy,
+    pub sort_order: SortOrder,
+    pub show_reading_progress: bool,
+    pub show_cover_thumbnails: bool,
+    pub animation_enabled: bool,
+    pub compact_mode: bool,
+}
+
+/// Synchronization preferences
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncPreferences {
+    pub enabled: bool,
+    pub provider: SyncProvider,
+    pub sync_library: bool,
+    pub sync_reading_progress: bool,
+    pub sync_annotations: bool,
+    pub sync_preferences: bool,
+    pub auto_sync: bool,
+    pub sync_interval_minutes: u32,
+    #[serde(default)]
+    pub kosync: KosyncSettings,
+    #[serde(default)]
+    pub read_later: ReadLaterSettings,
+}
+
+/// Privacy preferences
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivacyPreferences {
+    pub analytics_enabled: bool,
+    pub crash_reporting: bool,
+    pub usage_statistics: bool,
+    pub personalized_recommendations: bool,
+    pub data_retention_days: u32,
+    #[serde(default)]
+    pub compatibility_ledger_enabled: bool, // Local only, never uploaded
+}
+
+/// Duplicate handling strategies
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum DuplicateHandling {
+    Ask,
+    Skip,
+    Replace,
+    KeepBoth,
+}
+
+/// What happens to a book's file when it is removed from the library
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum FileDeletionPolicy {
+    KeepFile,
+    MoveToTrash,
+    DeletePermanently,
+}
+
+/// What happens when a book is finished and the next book in its series is in the library
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum SeriesAutoAdvance {
+    Off,
+    Offer,
+    MarkCurrentlyReading,
+    MarkAndOpen,
+}
+
+/// When a chapter counts as read
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChapterReadThreshold {
+    pub min_scroll_fraction: f32,      // Furthest point reached, 0.0 to 1.0
+    pub last_page_dwell_seconds: u32,  // Time on the chapter's last page
+}
+
+/// Which engine reads books aloud
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum SpeechEngine {
+    Platform, // The operating system's voices
+    Piper,    // A local Piper voice model
+}
+
+/// Reading aloud
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SpeechSettings {
+    pub engine: SpeechEngine,
+    pub voice: Option<String>, // Platform voice id; the system default when unset
+    pub rate: f32,             // 1.0 is the voice's normal speed
+    pub piper_model: Option<PathBuf>, // `.onnx` voice model for Piper
+    pub highlight_sentences: bool,
+}
+
+/// How a book is matched with the same book on KOReader devices
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum KosyncDocumentMatch {
+    Binary,   // Hash of samples of the file's contents, KOReader's default
+    FileName, // Hash of the file name
+}
+
+/// Reading progress sync with KOReader devices through a kosync server
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KosyncSettings {
+    pub enabled: bool,
+    pub server_url: String,
+    pub username: Option<String>, // The password's key is kept in the keyring
+    pub document_match: KosyncDocumentMatch,
+}
+
+/// Read-later service saved articles are pulled from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ReadLaterProvider {
+    Wallabag,
+    Pocket, // Pocket closed its API in 2025, so only its export file can be read
+}
+
+/// How pulled articles reach the library
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ReadLaterDelivery {
+    RollingBook, // One "Read Later" book, rebuilt with the unread articles
+    Individual,  // A book per article
+}
+
+/// Pulling saved articles from a read-later service
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReadLaterSettings {
+    pub enabled: bool,
+    pub provider: ReadLaterProvider,
+    pub server_url: String,        // A Wallabag instance
+    pub client_id: Option<String>, // The client secret and tokens are kept in the keyring
+    pub username: Option<String>,
+    pub delivery: ReadLaterDelivery,
+}
+
+/// Sharing the library with devices on the local network over OPDS
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LibrarySharing {
+    pub enabled: bool,
+    pub port: u16, // The access token is kept in the keyring
+}
+
+/// How often news feeds are bundled into a periodical and added to the library
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NewsDelivery {
+    pub enabled: bool,
+    pub interval_hours: u32,
+    pub max_articles_per_feed: usize,
+    pub include_images: bool,
+}
+
+/// How often the library database is backed up
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BackupSchedule {
+    pub enabled: bool,
+    pub interval_hours: u32,
+    pub keep_backups: usize, // Older backups are deleted
+}
+
+/// Metadata sources
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum MetadataSource {
+    GoogleBooks,
+    OpenLibrary,
+    Goodreads,
+    LocalFile,
+}
+
+/// View modes for the library
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ViewMode {
+    Grid,
+    List,
+    LargeCover,
+    Details,
+}
+
+/// Sort criteria
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum SortBy {
+    Title,
+    Author,
+    DateAdded,
+    DateModified,
+    ReadingProgress,
+    Rating,
+    FileSize,
+    PublicationDate,
+}
+
+/// Sort order
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+/// Sync providers
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum SyncProvider {
+    None,
+    GoogleDrive,
+    Dropbox,
+    OneDrive,
+    ICloud,
+    Custom(String),
+}
+
+impl Default for UserPreferences {
+    fn default() -> Self {
+        Self {
+            library: LibraryPreferences::default(),
+            reading: ReadingPreferences::default(),
+            ui: UiPreferences::default(),
+            sync: SyncPreferences::default(),
+            privacy: PrivacyPreferences::default(),
+        }
+    }
+}
+
+impl Default for LibraryPreferences {
+    fn default() -> Self {
+        Self {
+            default_library_path: dirs::document_dir()
+                .unwrap_or_else(|| dirs::home_dir().unwrap_or_default())
+                .join("Ebook Library"),
+            auto_import: true,
+            watch_folders: Vec::new(),
+            duplicate_handling: DuplicateHandling::Ask,
+            file_deletion_policy: FileDeletionPolicy::default(),
+            trash_retention_days: default_trash_retention_days(),
+            metadata_sources: vec![
+                MetadataSource::LocalFile,
+                MetadataSource::GoogleBooks,
+                MetadataSource::OpenLibrary,
+            ],
+            cover_download: true,
+            organize_by_author: false,
+            organize_by_genre: false,
+            backup_schedule: BackupSchedule::default(),
+            sharing: LibrarySharing::default(),
+            news: NewsDelivery::default(),
+        }
+    }
+}
+
+impl Default for ReadingPreferences {
+    fn default() -> Self {
+        Self {
+            default_font_family: "System".to_string(),
+            default_font_size: 16,
+            default_line_spacing: 1.5,
+            default_margin: 40,
+            default_theme: "light".to_string(),
+            page_turn_animation: true,
+            reading_progress_sync: true,
+            auto_bookmark: true,
+            highlight_color: "#FFD700".to_string(),
+            note_color: "#87CEEB".to_string(),
+            series_auto_advance: SeriesAutoAdvance::default(),
+            chapter_read_threshold: ChapterReadThreshold::default(),
+            reading_speed_wpm: default_reading_speed_wpm(),
+            speech: SpeechSettings::default(),
+        }
+    }
+}
+
+impl Default for UiPreferences {
+    fn default() -> Self {
+        Self {
+            theme: "light".to_string(),
+            language: "en".to_string(),
+            view_mode: ViewMode::Grid,
+            grid_columns: 6,
+            sort_by: SortBy::DateAdded,
+            sort_order: SortOrder::Descending,
+            show_reading_progress: true,
+            show_cover_thumbnails: true,
+            animation_enabled: true,
+            compact_mode: false,
+        }
+    }
+}
+
+impl Default for SyncPreferences {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: SyncProvider::None,
+            sync_library: true,
+            sync_reading_progress: true,
+            sync_annotations: true,
+            sync_preferences: true,
+            auto_sync: true,
+            sync_interval_minutes: 15,
+            kosync: KosyncSettings::default(),
+            read_later: ReadLaterSettings::default(),
+        }
+    }
+}
+
+impl Default for FileDeletionPolicy {
+    fn default() -> Self {
+        FileDeletionPolicy::KeepFile
+    }
+}
+
+impl Default for SeriesAutoAdvance {
+    fn default() -> Self {
+        SeriesAutoAdvance::Offer
+    }
+}
+
+fn default_trash_retention_days() -> u32 {
+    DEFAULT_TRASH_RETENTION_DAYS
+}
+
+fn default_reading_speed_wpm() -> u32 {
+    crate::models::book::DEFAULT_READING_SPEED_WPM
+}
+
+impl Default for ChapterReadThreshold {
+    fn default() -> Self {
+        Self {
+            min_scroll_fraction: 0.9,
+            last_page_dwell_seconds: 5,
+        }
+    }
+}
+
+impl Default for SpeechEngine {
+    fn default() -> Self {
+        SpeechEngine::Platform
+    }
+}
+
+impl Default for SpeechSettings {
+    fn default() -> Self {
+        Self {
+            engine: SpeechEngine::default(),
+            voice: None,
+            rate: 1.0,
+            piper_model: None,
+            highlight_sentences: true,
+        }
+    }
+}
+
+impl Default for KosyncDocumentMatch {
+    fn default() -> Self {
+        KosyncDocumentMatch::Binary
+    }
+}
+
+impl Default for KosyncSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            server_url: DEFAULT_KOSYNC_SERVER.to_string(),
+            username: None,
+            document_match: KosyncDocumentMatch::default(),
+        }
+    }
+}
+
+impl ReadLaterProvider {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            ReadLaterProvider::Wallabag => "Wallabag",
+            ReadLaterProvider::Pocket => "Pocket",
+        }
+    }
+}
+
+impl Default for ReadLaterProvider {
+    fn default() -> Self {
+        ReadLaterProvider::Wallabag
+    }
+}
+
+impl ReadLaterDelivery {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            ReadLaterDelivery::RollingBook => "One Read Later book",
+            ReadLaterDelivery::Individual => "A book per article",
+        }
+    }
+}
+
+impl Default for ReadLaterDelivery {
+    fn default() -> Self {
+        ReadLaterDelivery::RollingBook
+    }
+}
+
+impl Default for ReadLaterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: ReadLaterProvider::default(),
+            server_url: "https://app.wallabag.it".to_string(),
+            client_id: None,
+            username: None,
+            delivery: ReadLaterDelivery::default(),
+        }
+    }
+}
+
+impl Default for LibrarySharing {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: DEFAULT_SHARING_PORT,
+        }
+    }
+}
+
+impl Default for NewsDelivery {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_hours: 24,
+            max_articles_per_feed: 25,
+            include_images: true,
+        }
+    }
+}
+
+impl Default for BackupSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_hours: 24,
+            keep_backups: 5,
+        }
+    }
+}
+
+impl Default for PrivacyPreferences {
+    fn default() -> Self {
+        Self {
+            analytics_enabled: false,
+            crash_reporting: true,
+            usage_statistics: false,
+            personalized_recommendations: true,
+            data_retention_days: 365,
+            compatibility_ledger_enabled: false,
+        }
+    }
+}
+
+impl ViewMode {
+    pub fn to_string(&self) -> String {
+        match self {
+            ViewMode::Grid => "grid".to_string(),
+            ViewMode::List => "list".to_string(),
+            ViewMode::LargeCover => "large-cover".to_string(),
+            ViewMode::Details => "details".to_string(),
+        }
+    }
+    
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "grid" => Some(ViewMode::Grid),
+            "list" => Some(ViewMode::List),
+            "large-cover" => Some(ViewMode::LargeCover),
+            "details" => Some(ViewMode::Details),
+            _ => None,
+        }
+    }
+}
+
+impl FileDeletionPolicy {
+    pub fn to_string(&self) -> String {
+        match self {
+            FileDeletionPolicy::KeepFile => "keep_file".to_string(),
+            FileDeletionPolicy::MoveToTrash => "move_to_trash".to_string(),
+            FileDeletionPolicy::DeletePermanently => "delete_permanently".to_string(),
+        }
+    }
+    
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "keep_file" | "keep" => Some(FileDeletionPolicy::KeepFile),
+            "move_to_trash" | "trash" => Some(FileDeletionPolicy::MoveToTrash),
+            "delete_permanently" | "delete" => Some(FileDeletionPolicy::DeletePermanently),
+            _ => None,
+        }
+    }
+    
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            FileDeletionPolicy::KeepFile => "Keep File on Disk",
+            FileDeletionPolicy::MoveToTrash => "Move File to Trash",
+            FileDeletionPolicy::DeletePermanently => "Delete File Permanently",
+        }
+    }
+}
+
+impl SpeechEngine {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            SpeechEngine::Platform => "System Voices",
+            SpeechEngine::Piper => "Piper (Local Model)",
+        }
+    }
+}
+
+impl KosyncDocumentMatch {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            KosyncDocumentMatch::Binary => "File Contents",
+            KosyncDocumentMatch::FileName => "File Name",
+        }
+    }
+}
+
+impl ChapterReadThreshold {
+    /// Check if a chapter has been read far enough, or its last page viewed long enough
+    pub fn is_met(&self, max_scroll_fraction: f32, last_page_dwell_seconds: u32) -> bool {
+        max_scroll_fraction >= self.min_scroll_fraction
+            || (last_page_dwell_seconds > 0 && last_page_dwell_seconds >= self.last_page_dwell_seconds)
+    }
+}
+
+impl SeriesAutoAdvance {
+    pub fn to_string(&self) -> String {
+        match self {
+            SeriesAutoAdvance::Off => "off".to_string(),
+            SeriesAutoAdvance::Offer => "offer".to_string(),
+            SeriesAutoAdvance::MarkCurrentlyReading => "mark_currently_reading".to_string(),
+            SeriesAutoAdvance::MarkAndOpen => "mark_and_open".to_string(),
+        }
+    }
+    
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "off" => Some(SeriesAutoAdvance::Off),
+            "offer" => Some(SeriesAutoAdvance::Offer),
+            "mark_currently_reading" | "mark" => Some(SeriesAutoAdvance::MarkCurrentlyReading),
+            "mark_and_open" | "open" => Some(SeriesAutoAdvance::MarkAndOpen),
+            _ => None,
+        }
+    }
+    
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            SeriesAutoAdvance::Off => "Do Nothing",
+            SeriesAutoAdvance::Offer => "Suggest Next Book",
+            SeriesAutoAdvance::MarkCurrentlyReading => "Start Next Book",
+            SeriesAutoAdvance::MarkAndOpen => "Start and Open Next Book",
+        }
+    }
+}
+
+impl SortBy {
+    pub fn to_string(&self) -> String {
+        match self {
+            SortBy::Title => "title".to_string(),
+            SortBy::Author => "author".to_string(),
+            SortBy::DateAdded => "date_added".to_string(),
+            SortBy::DateModified => "date_modified".to_string(),
+            SortBy::ReadingProgress => "reading_progress".to_string(),
+            SortBy::Rating => "rating".to_string(),
+            SortBy::FileSize => "file_size".to_string(),
+            SortBy::PublicationDate => "publication_date".to_string(),
+        }
+    }
+    
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            SortBy::Title => "Title",
+            SortBy::Author => "Author",
+            SortBy::DateAdded => "Date Added",
+            SortBy::DateModified => "Date Modified",
+            SortBy::ReadingProgress => "Reading Progress",
+            SortBy::Rating => "Rating",
+            SortBy::FileSize => "File Size",
+            SortBy::PublicationDate => "Publication Date",
+        }
+    }
+}
+
+impl SortOrder {
+    pub fn to_string(&self) -> String {
+        match self {
+            SortOrder::Ascending => "asc".to_string(),
+            SortOrder::Descending => "desc".to_string(),
+        }
+    }
+    
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            SortOrder::Ascending => "Ascending",
+            SortOrder::Descending => "Descending",
+        }
+    }
+}
+
+impl SyncProvider {
+    pub fn to_string(&self) -> String {
+        match self {
+            SyncProvider::None => "none".to_string(),
+            SyncProvider::GoogleDrive => "google_drive".to_string(),
+            SyncProvider::Dropbox => "dropbox".to_string(),
+            SyncProvider::OneDrive => "onedrive".to_string(),
+            SyncProvider::ICloud => "icloud".to_string(),
+            SyncProvider::Custom(name) => format!("custom_{}", name),
+        }
+    }
+    
+    pub fn display_name(&self) -> String {
+        match self {
+            SyncProvider::None => "None".to_string(),
+            SyncProvider::GoogleDrive => "Google Drive".to_string(),
+            SyncProvider::Dropbox => "Dropbox".to_string(),
+            SyncProvider::OneDrive => "OneDrive".to_string(),
+            SyncProvider::ICloud => "iCloud".to_string(),
+            SyncProvider::Custom(name) => name.clone(),
+        }
+    }
+}
\ No newline at end of file
@@ -16,6 +16,10 @@ pub struct ReadingTheme {
     pub border_color: String,
     pub header_color: String,
     pub properties: ThemeProperties,
+    #[serde(default)]
+    pub typography: ThemeTypography,
+    #[serde(default)]
+    pub custom_css: Option<String>, // Added after the generated stylesheet
 }
 
 /// Theme properties for typography and layout
@@ -31,6 +35,18 @@ pub struct ThemeProperties {
     pub default_line_height: f32,
 }
 
+/// Typography a custom theme sets, overriding the reader's own settings while it is used
+///
+/// Settings left as None follow the reading preferences.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ThemeTypography {
+    pub font_family: Option<String>,
+    pub font_size: Option<u16>,
+    pub line_height: Option<f32>,
+    pub margin_horizontal: Option<u16>,
+    pub margin_vertical: Option<u16>,
+}
+
 /// Reading theme preferences model
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReadingThemePreferences {
@@ -48,6 +64,8 @@ pub struct ReadingThemePreferences {
     pub page_transition_duration: u16,
     pub auto_scroll_enabled: bool,
     pub auto_scroll_speed: f32,
+    #[serde(default)]
+    pub custom_css: Option<String>, // Added after the theme's stylesheet, for this profile or book
 }
 
 impl Default for ReadingThemePreferences {
@@ -67,6 +85,7 @@ impl Default for ReadingThemePreferences {
             page_transition_duration: 300,
             auto_scroll_enabled: false,
             auto_scroll_speed: 1.0,
+            custom_css: None,
         }
     }
 }
@@ -235,6 +254,8 @@ impl ThemeManager {
                 default_font_size: 16,
                 default_line_height: 1.5,
             },
+            typography: ThemeTypography::default(),
+            custom_css: None,
         }
     }
     
@@ -262,6 +283,8 @@ impl ThemeManager {
                 default_font_size: 16,
                 default_line_height: 1.6,
             },
+            typography: ThemeTypography::default(),
+            custom_css: None,
         }
     }
     
@@ -289,6 +312,8 @@ impl ThemeManager {
                 default_font_size: 16,
                 default_line_height: 1.5,
             },
+            typography: ThemeTypography::default(),
+            custom_css: None,
         }
     }
     
@@ -316,6 +341,8 @@ impl ThemeManager {
                 default_font_size: 18,
                 default_line_height: 1.8,
             },
+            typography: ThemeTypography::default(),
+            custom_css: None,
         }
     }
     
@@ -343,6 +370,8 @@ impl ThemeManager {
                 default_font_size: 16,
                 default_line_height: 1.6,
             },
+            typography: ThemeTypography::default(),
+            custom_css: None,
         }
     }
     
@@ -370,9 +399,21 @@ impl ThemeManager {
                 default_font_size: 16,
                 default_line_height: 1.5,
             },
+            typography: ThemeTypography::default(),
+            custom_css: None,
         }
     }
     
+    /// Whether a theme name belongs to one of the predefined themes
+    pub fn is_builtin_theme(name: &str) -> bool {
+        matches!(name, "original" | "quiet" | "paper" | "bold" | "calm" | "focus")
+    }
+
+    /// Stylesheet for chapter content in the current theme
+    pub fn current_stylesheet(&self) -> Option<String> {
+        self.get_current_theme().map(|theme| theme.stylesheet(&self.current_preferences))
+    }
+    
     /// Add a custom theme
     pub fn add_custom_theme(&mut self, theme: ReadingTheme) {
         self.themes.insert(theme.name.clone(), theme);
@@ -456,6 +497,51 @@ impl ReadingTheme {
         false
     }
     
+    /// Stylesheet for chapter content, from the theme and the reader's preferences
+    ///
+    /// Generated rules are `!important` so they win over the book's own styles.
+    /// The theme's custom CSS follows them, then that of the preferences.
+    pub fn stylesheet(&self, preferences: &ReadingThemePreferences) -> String {
+        let typography = &self.typography;
+        let font_family = typography.font_family.as_deref().unwrap_or(&preferences.font_family);
+        let font_size = typography.font_size.unwrap_or(preferences.font_size);
+        let line_height = typography.line_height.unwrap_or(preferences.line_height);
+        let margin_horizontal = typography.margin_horizontal.unwrap_or(preferences.margin_horizontal);
+        let margin_vertical = typography.margin_vertical.unwrap_or(preferences.margin_vertical);
+
+        let mut css = String::new();
+        css.push_str(&format!(
+            "html, body {{ background-color: {} !important; color: {} !important; }}\n",
+            self.background_color, self.text_color
+        ));
+        css.push_str("body {");
+        match FontFamily::from_string(font_family) {
+            FontFamily::Default => {}
+            FontFamily::Serif => css.push_str(" font-family: serif !important;"),
+            FontFamily::SansSerif => css.push_str(" font-family: sans-serif !important;"),
+            FontFamily::Monospace => css.push_str(" font-family: monospace !important;"),
+            FontFamily::Custom(name) => {
+                css.push_str(&format!(" font-family: \"{}\", serif !important;", name.replace(['"', '\\'], "")));
+            }
+        }
+        css.push_str(&format!(
+            " font-size: {}px !important; font-weight: {} !important; line-height: {} !important; \
+             letter-spacing: {}em !important; margin: {}px {}px !important; }}\n",
+            font_size, self.properties.font_weight, line_height, self.properties.letter_spacing,
+            margin_vertical, margin_horizontal
+        ));
+        css.push_str(&format!("p {{ margin-bottom: {}em !important; }}\n", self.properties.paragraph_spacing));
+        css.push_str(&format!("a, a:visited {{ color: {} !important; }}\n", self.link_color));
+        css.push_str(&format!("::selection {{ background-color: {}; }}\n", self.selection_color));
+
+        for custom in [&self.custom_css, &preferences.custom_css].into_iter().flatten() {
+            // Keep the CSS inside the <style> element it is injected in
+            css.push_str(&custom.replace("</", "<\\/"));
+            css.push('\n');
+        }
+        css
+    }
+
     /// Calculate contrast ratio between text and background
     pub fn calculate_contrast_ratio(&self) -> f32 {
         // This is a simplified contrast calculation
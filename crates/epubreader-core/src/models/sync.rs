@@ -6,6 +6,9 @@ use crate::models::annotation::{Annotation, Bookmark};
 use crate::models::reading_theme::ReadingThemePreferences;
 use crate::models::library::{Collection, ReadingStatus};
 
+/// How much further along (as a fraction of the book) another device must be before offering to jump there
+pub const POSITION_PROMPT_MARGIN: f32 = 0.005;
+
 /// Main synchronization data structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncData {
@@ -39,6 +42,40 @@ pub struct BookProgress {
     pub started_at: Option<DateTime<Utc>>,
     pub finished_at: Option<DateTime<Utc>>,
     pub reading_goals: Option<BookReadingGoals>,
+    #[serde(default)]
+    pub device_positions: HashMap<String, DevicePosition>, // Last position on each device, by device id
+    #[serde(default)]
+    pub furthest_read: Option<DevicePosition>, // Furthest any device has got in the book
+}
+
+/// Where a device last was in a book
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DevicePosition {
+    pub device_id: String,
+    pub device_name: String,
+    pub chapter_id: Option<String>,
+    pub position_in_chapter: Option<usize>,
+    pub progress: f32, // 0.0 to 1.0
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Another device got further in a book than this one, after this one last read it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PositionConflict {
+    pub book_id: String,
+    pub local: Option<DevicePosition>,
+    pub remote: DevicePosition,
+}
+
+impl PositionConflict {
+    /// Question to put to the reader
+    pub fn prompt(&self) -> String {
+        format!(
+            "You were further along on {} ({:.0}%) — jump there?",
+            self.remote.device_name,
+            self.remote.progress * 100.0
+        )
+    }
 }
 
 /// User preferences for synchronization
@@ -72,6 +109,8 @@ pub struct SyncSettings {
     pub sync_wifi_only: bool,
     pub max_conflict_resolution_attempts: u32,
     pub sync_categories: SyncCategories,
+    #[serde(default)]
+    pub server_url: Option<String>, // Self-hosted sync server, if one is set up
 }
 
 /// Privacy settings
@@ -200,6 +239,10 @@ pub struct SyncMetadata {
     pub encryption_used: bool,
     pub sync_conflicts: Vec<SyncConflict>,
     pub sync_history: Vec<SyncHistoryEntry>,
+    #[serde(default)]
+    pub server_cursor: u64, // Last change pulled from the sync server
+    #[serde(default)]
+    pub last_pushed_at: Option<DateTime<Utc>>,
 }
 
 /// Sync conflict information
@@ -314,11 +357,16 @@ pub enum CloudProvider {
 }
 
 /// Cloud credentials
+///
+/// API and secret keys are never written to settings JSON. The sync service
+/// keeps them in the OS keyring, and moves keys found in older files there.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CloudCredentials {
     pub access_token: Option<String>,
     pub refresh_token: Option<String>,
+    #[serde(skip_serializing, default)]
     pub api_key: Option<String>,
+    #[serde(skip_serializing, default)]
     pub secret_key: Option<String>,
     pub expires_at: Option<DateTime<Utc>>,
 }
@@ -439,6 +487,7 @@ impl Default for SyncSettings {
             sync_wifi_only: false,
             max_conflict_resolution_attempts: 3,
             sync_categories: SyncCategories::default(),
+            server_url: None,
         }
     }
 }
@@ -523,6 +572,25 @@ impl Default for SyncMetadata {
             encryption_used: false,
             sync_conflicts: Vec::new(),
             sync_history: Vec::new(),
+            server_cursor: 0,
+            last_pushed_at: None,
+        }
+    }
+}
+
+impl Default for SyncStatistics {
+    fn default() -> Self {
+        Self {
+            total_syncs: 0,
+            successful_syncs: 0,
+            failed_syncs: 0,
+            last_successful_sync: None,
+            last_failed_sync: None,
+            average_sync_duration_ms: 0,
+            total_conflicts: 0,
+            resolved_conflicts: 0,
+            data_transferred_bytes: 0,
+            sync_efficiency: 0.0,
         }
     }
 }
@@ -545,7 +613,62 @@ impl BookProgress {
             started_at: None,
             finished_at: None,
             reading_goals: None,
+            device_positions: HashMap::new(),
+            furthest_read: None,
+        }
+    }
+
+    /// Record where a device is in the book
+    ///
+    /// The book's current position follows the most recently recorded device.
+    pub fn record_position(&mut self, position: DevicePosition) {
+        if let Some(known) = self.device_positions.get(&position.device_id) {
+            if known.updated_at > position.updated_at {
+                return;
+            }
+        }
+        if self.furthest_read.as_ref().is_none_or(|furthest| position.progress > furthest.progress) {
+            self.furthest_read = Some(position.clone());
+        }
+        if position.updated_at >= self.last_read_at {
+            self.current_chapter = position.chapter_id.clone();
+            self.position_in_chapter = position.position_in_chapter;
+            self.reading_percentage = position.progress * 100.0;
+            self.last_read_at = position.updated_at;
         }
+        self.device_positions.insert(position.device_id.clone(), position);
+    }
+
+    /// Take in the device positions another copy of this book's progress knows about
+    pub fn merge_positions(&mut self, other: &BookProgress) {
+        let mut positions: Vec<&DevicePosition> = other.device_positions.values().collect();
+        positions.sort_by_key(|position| position.updated_at);
+        for position in positions {
+            self.record_position(position.clone());
+        }
+        if let Some(furthest) = &other.furthest_read {
+            if self.furthest_read.as_ref().is_none_or(|known| furthest.progress > known.progress) {
+                self.furthest_read = Some(furthest.clone());
+            }
+        }
+    }
+
+    /// The device furthest along in the book, if it read after this device did
+    pub fn position_conflict(&self, device_id: &str) -> Option<PositionConflict> {
+        let local = self.device_positions.get(device_id);
+        let remote = self.device_positions.values()
+            .filter(|position| position.device_id != device_id)
+            .filter(|position| local.is_none_or(|local| position.updated_at > local.updated_at))
+            .max_by(|a, b| a.progress.total_cmp(&b.progress))?;
+        let local_progress = local.map_or(0.0, |local| local.progress);
+        if remote.progress - local_progress < POSITION_PROMPT_MARGIN {
+            return None;
+        }
+        Some(PositionConflict {
+            book_id: self.book_id.clone(),
+            local: local.cloned(),
+            remote: remote.clone(),
+        })
     }
 
     /// Update reading progress
@@ -695,4 +818,51 @@ impl SyncConflict {
     pub fn is_resolved(&self) -> bool {
         self.resolution.is_some()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn position(device: &str, progress: f32, minutes_ago: i64) -> DevicePosition {
+        DevicePosition {
+            device_id: device.to_string(),
+            device_name: format!("{} device", device),
+            chapter_id: Some(format!("chapter-{}", (progress * 10.0) as u32)),
+            position_in_chapter: Some(120),
+            progress,
+            updated_at: Utc::now() - Duration::minutes(minutes_ago),
+        }
+    }
+
+    #[test]
+    fn test_device_positions_furthest_read_and_conflicts() {
+        let mut phone = BookProgress::new("book".to_string());
+        phone.last_read_at = Utc::now() - Duration::days(1);
+        phone.record_position(position("phone", 0.30, 60));
+
+        let mut tablet = BookProgress::new("book".to_string());
+        tablet.last_read_at = Utc::now() - Duration::days(1);
+        tablet.record_position(position("tablet", 0.55, 30));
+        tablet.record_position(position("tablet", 0.50, 10));
+        // Out-of-order updates from a device are ignored
+        tablet.record_position(position("tablet", 0.20, 20));
+
+        phone.merge_positions(&tablet);
+        assert_eq!(phone.device_positions.len(), 2);
+        assert_eq!(phone.furthest_read.as_ref().unwrap().progress, 0.55);
+        assert_eq!(phone.reading_percentage, 50.0);
+        assert_eq!(phone.current_chapter.as_deref(), Some("chapter-5"));
+
+        let conflict = phone.position_conflict("phone").unwrap();
+        assert_eq!(conflict.remote.device_id, "tablet");
+        assert_eq!(conflict.prompt(), "You were further along on tablet device (50%) — jump there?");
+        assert!(phone.position_conflict("tablet").is_none());
+
+        // Reading on the phone again settles it, even while behind
+        phone.record_position(position("phone", 0.31, 0));
+        assert!(phone.position_conflict("phone").is_none());
+        assert_eq!(phone.furthest_read.as_ref().unwrap().device_id, "tablet");
+    }
+}
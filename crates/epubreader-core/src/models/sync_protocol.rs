@@ -0,0 +1,264 @@
+//! Wire format of the self-hosted sync server
+//!
+//! The server keeps an append-only log of changes. Each change gets the next
+//! cursor value, so a device pulls everything after the cursor it last saw.
+//! Book files are stored once, under the SHA-256 of their contents.
+//!
+//! | Method | Path                | Body                    | Reply                    |
+//! |--------|---------------------|-------------------------|--------------------------|
+//! | POST   | `/v1/devices`       | `RegisterDeviceRequest` | `RegisterDeviceResponse` |
+//! | POST   | `/v1/changes`       | `Changeset`             | `PushResponse`           |
+//! | GET    | `/v1/changes?since=<cursor>&limit=<n>` |      | `PullResponse`           |
+//! | HEAD   | `/v1/files/<sha256>`|                         | 200 or 404               |
+//! | PUT    | `/v1/files/<sha256>`| file bytes              | 201                      |
+//! | GET    | `/v1/files/<sha256>`|                         | file bytes               |
+//!
+//! Every request but device registration carries the device token as
+//! `Authorization: Bearer <token>`.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::models::annotation::{Annotation, Bookmark};
+use crate::models::library::Collection;
+use crate::models::sync::{BookProgress, ReadingSession, SyncData, UserPreferences};
+
+/// Protocol version sent on registration; servers refuse versions they don't speak
+pub const SYNC_PROTOCOL_VERSION: u32 = 1;
+
+/// Sent once per device to get a token
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RegisterDeviceRequest {
+    pub device_id: String,
+    pub device_name: String,
+    pub protocol_version: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RegisterDeviceResponse {
+    pub device_token: String,
+    pub cursor: u64, // Latest cursor on the server when the device registered
+}
+
+/// Kind of record a change carries
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    BookProgress,
+    Annotation,
+    Bookmark,
+    Collection,
+    Preferences,
+    ReadingSession,
+    BookFile,
+}
+
+/// One record as a device last saved it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SyncChange {
+    pub kind: ChangeKind,
+    pub id: String,
+    pub device_id: String,
+    pub changed_at: DateTime<Utc>,
+    pub data: Value,
+}
+
+/// Changes pushed by a device in one request
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Changeset {
+    pub device_id: String,
+    pub changes: Vec<SyncChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PushResponse {
+    pub cursor: u64, // Cursor of the last change accepted
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PullResponse {
+    pub changes: Vec<SyncChange>,
+    pub cursor: u64,    // Pass as `since` on the next pull
+    pub has_more: bool, // More changes wait past this page
+}
+
+/// A book file stored on the server, announced through a `BookFile` change
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BookFileRef {
+    pub book_id: String,
+    pub sha256: String,
+    pub file_name: String,
+    pub size: u64,
+}
+
+impl SyncChange {
+    fn new<T: Serialize>(kind: ChangeKind, id: &str, device_id: &str, changed_at: DateTime<Utc>, record: &T) -> Result<Self> {
+        Ok(Self {
+            kind,
+            id: id.to_string(),
+            device_id: device_id.to_string(),
+            changed_at,
+            data: serde_json::to_value(record)?,
+        })
+    }
+
+    /// Announce a book file uploaded to the server
+    pub fn book_file(device_id: &str, file: &BookFileRef) -> Result<Self> {
+        Self::new(ChangeKind::BookFile, &file.book_id, device_id, Utc::now(), file)
+    }
+}
+
+impl SyncData {
+    /// Changes to push: records changed after `since`, or everything when never pushed
+    ///
+    /// Preferences carry no timestamp, so they only go out with the first push.
+    pub fn changes_since(&self, since: Option<DateTime<Utc>>) -> Result<Vec<SyncChange>> {
+        let is_new = |changed_at: DateTime<Utc>| since.is_none_or(|since| changed_at > since);
+        let device = self.device_id.as_str();
+        let mut changes = Vec::new();
+
+        let mut progress: Vec<&BookProgress> = self.books_progress.values().collect();
+        progress.sort_by(|a, b| a.book_id.cmp(&b.book_id));
+        for book in progress.into_iter().filter(|book| is_new(book.last_read_at)) {
+            changes.push(SyncChange::new(ChangeKind::BookProgress, &book.book_id, device, book.last_read_at, book)?);
+        }
+        for annotation in self.annotations.iter().filter(|a| is_new(a.modified_at)) {
+            changes.push(SyncChange::new(ChangeKind::Annotation, &annotation.id, device, annotation.modified_at, annotation)?);
+        }
+        for bookmark in self.bookmarks.iter().filter(|b| is_new(b.created_at)) {
+            changes.push(SyncChange::new(ChangeKind::Bookmark, &bookmark.id, device, bookmark.created_at, bookmark)?);
+        }
+        for collection in self.collections.iter().filter(|c| is_new(c.updated_at)) {
+            changes.push(SyncChange::new(ChangeKind::Collection, &collection.id, device, collection.updated_at, collection)?);
+        }
+        for session in &self.reading_sessions {
+            let changed_at = session.end_time.unwrap_or(session.start_time);
+            if is_new(changed_at) {
+                changes.push(SyncChange::new(ChangeKind::ReadingSession, &session.id, device, changed_at, session)?);
+            }
+        }
+        if since.is_none() {
+            changes.push(SyncChange::new(ChangeKind::Preferences, "preferences", device, Utc::now(), &self.preferences)?);
+        }
+        Ok(changes)
+    }
+
+    /// Apply a change pulled from another device; returns whether anything changed
+    ///
+    /// The newer copy of a record wins. Reading progress also keeps every
+    /// device's position. `BookFile` changes are left to the caller.
+    pub fn apply_change(&mut self, change: &SyncChange) -> Result<bool> {
+        let data = change.data.clone();
+        match change.kind {
+            ChangeKind::BookProgress => {
+                let remote: BookProgress = serde_json::from_value(data)?;
+                match self.books_progress.get_mut(&remote.book_id) {
+                    Some(local) => {
+                        let mut merged = if remote.last_read_at > local.last_read_at { remote.clone() } else { local.clone() };
+                        merged.merge_positions(local);
+                        merged.merge_positions(&remote);
+                        *local = merged;
+                    }
+                    None => {
+                        self.books_progress.insert(remote.book_id.clone(), remote);
+                    }
+                }
+            }
+            ChangeKind::Annotation => {
+                let remote: Annotation = serde_json::from_value(data)?;
+                return Ok(upsert_newer(&mut self.annotations, remote, |a| &a.id, |a| a.modified_at));
+            }
+            ChangeKind::Bookmark => {
+                let remote: Bookmark = serde_json::from_value(data)?;
+                return Ok(upsert_newer(&mut self.bookmarks, remote, |b| &b.id, |b| b.created_at));
+            }
+            ChangeKind::Collection => {
+                let remote: Collection = serde_json::from_value(data)?;
+                return Ok(upsert_newer(&mut self.collections, remote, |c| &c.id, |c| c.updated_at));
+            }
+            ChangeKind::ReadingSession => {
+                let remote: ReadingSession = serde_json::from_value(data)?;
+                let changed_at = |s: &ReadingSession| s.end_time.unwrap_or(s.start_time);
+                return Ok(upsert_newer(&mut self.reading_sessions, remote, |s| &s.id, changed_at));
+            }
+            ChangeKind::Preferences => {
+                self.preferences = serde_json::from_value::<UserPreferences>(data)?;
+            }
+            ChangeKind::BookFile => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+impl BookFileRef {
+    /// Read the file a `BookFile` change announces
+    pub fn from_change(change: &SyncChange) -> Result<Self> {
+        if change.kind != ChangeKind::BookFile {
+            return Err(anyhow!("Not a book file change: {:?}", change.kind));
+        }
+        Ok(serde_json::from_value(change.data.clone())?)
+    }
+}
+
+/// Insert a record, or replace the one with its id if this copy is newer
+fn upsert_newer<T>(
+    records: &mut Vec<T>,
+    record: T,
+    id: impl Fn(&T) -> &String,
+    changed_at: impl Fn(&T) -> DateTime<Utc>,
+) -> bool {
+    match records.iter_mut().find(|existing| id(existing) == id(&record)) {
+        Some(existing) if changed_at(&record) > changed_at(existing) => {
+            *existing = record;
+            true
+        }
+        Some(_) => false,
+        None => {
+            records.push(record);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::sync::DevicePosition;
+    use chrono::Duration;
+
+    #[test]
+    fn test_changes_round_trip_between_devices() {
+        let mut laptop = SyncData::new("laptop".to_string(), "Laptop".to_string());
+        let mut progress = BookProgress::new("dune".to_string());
+        progress.record_position(DevicePosition {
+            device_id: "laptop".to_string(),
+            device_name: "Laptop".to_string(),
+            chapter_id: Some("ch3".to_string()),
+            position_in_chapter: Some(40),
+            progress: 0.25,
+            updated_at: Utc::now(),
+        });
+        laptop.update_book_progress("dune".to_string(), progress);
+
+        let first = laptop.changes_since(None).unwrap();
+        assert_eq!(first.iter().map(|c| c.kind).collect::<Vec<_>>(), [ChangeKind::BookProgress, ChangeKind::Preferences]);
+        assert!(laptop.changes_since(Some(Utc::now() + Duration::seconds(1))).unwrap().is_empty());
+
+        let mut phone = SyncData::new("phone".to_string(), "Phone".to_string());
+        let wire = serde_json::to_string(&Changeset { device_id: "laptop".to_string(), changes: first }).unwrap();
+        assert!(wire.contains("\"kind\":\"book_progress\""));
+        for change in serde_json::from_str::<Changeset>(&wire).unwrap().changes {
+            assert!(phone.apply_change(&change).unwrap());
+        }
+        let synced = &phone.books_progress["dune"];
+        assert_eq!(synced.current_chapter.as_deref(), Some("ch3"));
+        assert_eq!(synced.position_conflict("phone").unwrap().remote.device_name, "Laptop");
+
+        let file = BookFileRef { book_id: "dune".to_string(), sha256: "ab".repeat(32), file_name: "dune.epub".to_string(), size: 1024 };
+        let change = SyncChange::book_file("laptop", &file).unwrap();
+        assert!(!phone.apply_change(&change).unwrap());
+        assert_eq!(BookFileRef::from_change(&change).unwrap(), file);
+    }
+}
@@ -0,0 +1,55 @@
+//! Percent-encoding for URL paths and queries
+
+/// Encode a query value; unreserved characters are kept and spaces become `+`
+pub fn encode_query(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            b' ' => "+".to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Decode %XX escapes in a URI reference; malformed escapes are kept as they are
+pub fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| uri.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Decode a query name or value, where `+` stands for a space
+pub fn decode_query(text: &str) -> String {
+    percent_decode(&text.replace('+', " "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_round_trip() {
+        let encoded = encode_query("jane eyre & 100% C++");
+        assert_eq!(encoded, "jane+eyre+%26+100%25+C%2B%2B");
+        assert_eq!(decode_query(&encoded), "jane eyre & 100% C++");
+        assert_eq!(percent_decode("Text/chapter%201.xhtml"), "Text/chapter 1.xhtml");
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+}
@@ -1,31 +1,55 @@
-use std::sync::Arc;
 use anyhow::Result;
+#[cfg(feature = "gui")]
+use std::sync::Arc;
+#[cfg(feature = "gui")]
 use slint::{ModelRc, VecModel, SharedString};
+#[cfg(feature = "gui")]
 use tokio::runtime::Runtime;
+#[cfg(feature = "gui")]
+use tokio::sync::Mutex;
 
 mod models;
 mod services;
 mod utils;
+#[cfg(not(feature = "gui"))]
+mod terminal_reader;
 
+#[cfg(feature = "gui")]
 use models::*;
+#[cfg(feature = "gui")]
 use services::*;
+#[cfg(feature = "gui")]
 use utils::image_cache::ImageCache;
+#[cfg(feature = "gui")]
+use utils::stall_detector::StallDetector;
 
+#[cfg(feature = "gui")]
 slint::include_modules!();
 
 /// Main application structure
+#[cfg(feature = "gui")]
 struct EbookReaderApp {
     rt: Runtime,
     book_service: Arc<BookService>,
     database: Arc<DatabaseService>,
     image_cache: Arc<ImageCache>,
+    search_indexer: Arc<SearchIndexer>,
+    folder_watcher: Arc<FolderWatcher>,
+    backup_service: Arc<BackupService>,
+    preferences: Arc<PreferencesService>,
+    reading_session: Arc<Mutex<Option<String>>>, // Session of the book open in the reading view
+    external_book: Arc<Mutex<Option<String>>>, // Book open in the reading view without being imported
+    _stall_detector: StallDetector,
     ui: AppWindow,
 }
 
+#[cfg(feature = "gui")]
 impl EbookReaderApp {
     /// Create a new application instance
     pub fn new() -> Result<Self> {
         let rt = Runtime::new()?;
+        // Logs a warning whenever blocking work holds up an async worker thread
+        let stall_detector = StallDetector::spawn(rt.handle());
         
         // Initialize services with improved error handling
         let database = Arc::new(rt.block_on(async {
@@ -62,6 +86,22 @@ impl EbookReaderApp {
             .unwrap_or_else(|_| std::env::temp_dir().join("ebook-reader-cache"));
         let image_cache = Arc::new(ImageCache::new(cache_dir)?);
         let book_service = Arc::new(BookService::new(database.clone(), image_cache.clone()));
+        // Imported books are checked against smart collection subscriptions
+        let library_service = Arc::new(LibraryService::new(database.pool()));
+        rt.block_on(async {
+            library_service.init_tables().await?;
+            book_service.set_library_service(library_service).await;
+            Ok::<_, anyhow::Error>(())
+        })?;
+        let book_search = Arc::new(match PathResolver::get_app_data_directory() {
+            Ok(app_dir) => BookSearchService::with_content_dir(app_dir.join("search_index")),
+            Err(_) => BookSearchService::new(),
+        });
+        let search_indexer = Arc::new(SearchIndexer::new(database.clone(), book_search));
+        let folder_watcher = Arc::new(FolderWatcher::new(database.clone(), book_service.clone()));
+        let backup_service = Arc::new(BackupService::for_database(PathResolver::resolve_database_path_with_fallback()?));
+        let preferences = Arc::new(PreferencesService::open_default()?);
+        rt.block_on(book_service.set_preferences(preferences.clone()));
         
         // Create UI
         let ui = AppWindow::new()?;
@@ -71,6 +111,13 @@ impl EbookReaderApp {
             book_service,
             database,
             image_cache,
+            search_indexer,
+            folder_watcher,
+            backup_service,
+            preferences,
+            reading_session: Arc::new(Mutex::new(None)),
+            external_book: Arc::new(Mutex::new(None)),
+            _stall_detector: stall_detector,
             ui,
         })
     }
@@ -82,10 +129,102 @@ impl EbookReaderApp {
         
         // Load initial data
         self.load_library()?;
+        self.follow_preferences();
+        self.start_search_indexing();
+        self.start_folder_watching();
+        self.start_scheduled_backups();
+        self.purge_expired_trash();
         
         Ok(())
     }
 
+    /// Apply the stored preferences, then again whenever they change
+    fn follow_preferences(&self) {
+        // Applied before the background tasks start, so none run with the defaults
+        let preferences = self.rt.block_on(self.preferences.load());
+        self.rt.block_on(Self::apply_preferences(&preferences, &self.backup_service, &self.search_indexer));
+
+        let mut changes = self.preferences.subscribe();
+        let backup_service = self.backup_service.clone();
+        let search_indexer = self.search_indexer.clone();
+        self.rt.spawn(async move {
+            while changes.changed().await.is_ok() {
+                let preferences = changes.borrow_and_update().clone();
+                Self::apply_preferences(&preferences, &backup_service, &search_indexer).await;
+            }
+        });
+    }
+
+    /// Pass preferences on to the services that follow them
+    async fn apply_preferences(
+        preferences: &models::preferences::UserPreferences,
+        backup_service: &BackupService,
+        search_indexer: &SearchIndexer,
+    ) {
+        backup_service.set_schedule(preferences.library.backup_schedule.clone()).await;
+        let ledger_enabled = preferences.privacy.compatibility_ledger_enabled;
+        if let Err(e) = search_indexer.set_compatibility_ledger_enabled(ledger_enabled).await {
+            eprintln!("❌ Opening the compatibility ledger failed: {}", e);
+        }
+    }
+
+    /// Index new and changed books for full-text search in the background
+    fn start_search_indexing(&self) {
+        let indexer = self.search_indexer.clone();
+        self.rt.spawn(async move {
+            match indexer.index_library().await {
+                Ok(summary) => println!(
+                    "🔎 Search index updated: {} indexed, {} unchanged, {} failed",
+                    summary.indexed, summary.unchanged, summary.failed
+                ),
+                Err(e) => eprintln!("❌ Search indexing failed: {}", e),
+            }
+        });
+    }
+
+    /// Permanently delete books that have outlived the trash retention period
+    fn purge_expired_trash(&self) {
+        let book_service = self.book_service.clone();
+        self.rt.spawn(async move {
+            match book_service.purge_expired_trash().await {
+                Ok(0) => {}
+                Ok(purged) => println!("🗑️ Removed {} books from the trash", purged),
+                Err(e) => eprintln!("❌ Emptying expired trash failed: {}", e),
+            }
+        });
+    }
+
+    /// Back up the library database in the background whenever a backup is due
+    fn start_scheduled_backups(&self) {
+        let backup_service = self.backup_service.clone();
+        self.rt.spawn(async move { backup_service.run().await });
+    }
+
+    /// Import books dropped into watched folders, refreshing the library as they arrive
+    fn start_folder_watching(&self) {
+        let watcher = self.folder_watcher.clone();
+        let book_service = self.book_service.clone();
+        let ui = self.ui.as_weak();
+        self.rt.spawn(async move {
+            let mut events = watcher.subscribe_events();
+            if let Err(e) = watcher.start().await {
+                eprintln!("❌ Folder watching failed: {}", e);
+                return;
+            }
+
+            loop {
+                match events.recv().await {
+                    Ok(FolderEvent::Imported { path, .. }) => println!("📥 Imported {}", path.display()),
+                    Ok(FolderEvent::Removed { path, .. }) => println!("🗑️ Removed {}", path.display()),
+                    Ok(FolderEvent::Duplicate { .. } | FolderEvent::Failed { .. }) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+                Self::refresh_library(book_service.clone(), ui.clone()).await;
+            }
+        });
+    }
+
     /// Set up UI callbacks
     fn setup_callbacks(&self) -> Result<()> {
         let book_service = self.book_service.clone();
@@ -95,12 +234,15 @@ impl EbookReaderApp {
         let ui_weak = self.ui.as_weak();
         let book_service_clone = book_service.clone();
         let rt_handle_clone = rt_handle.clone();
+        let reading_session = self.reading_session.clone();
         self.ui.on_book_selected(move |book_view_model| {
             let book_service = book_service_clone.clone();
             let ui = ui_weak.clone();
+            let reading_session = reading_session.clone();
             
             rt_handle_clone.spawn(async move {
                 if let Ok(book) = book_service.get_book_by_id(&book_view_model.id).await {
+                    Self::switch_reading_session(&book_service, &reading_session, Some(&book.id)).await;
                     let book_title = book.title.clone();
                     let book_author = book.author.clone();
                     let book_progress = book.reading_progress;
@@ -112,12 +254,109 @@ impl EbookReaderApp {
                             ui.set_current_book_title(SharedString::from(book_title));
                             ui.set_current_book_author(SharedString::from(book_author));
                             ui.set_current_book_progress(book_progress);
+                            ui.set_current_book_external(false);
                         }
                     }).unwrap();
                 }
             });
         });
 
+        // Stop timing the book when leaving the reading view, and forget an external file
+        let book_service_clone = book_service.clone();
+        let rt_handle_clone = rt_handle.clone();
+        let reading_session = self.reading_session.clone();
+        let external_book = self.external_book.clone();
+        self.ui.on_close_book(move || {
+            let book_service = book_service_clone.clone();
+            let reading_session = reading_session.clone();
+            let external_book = external_book.clone();
+            rt_handle_clone.spawn(async move {
+                Self::switch_reading_session(&book_service, &reading_session, None).await;
+                Self::close_external_book(&book_service, &external_book).await;
+            });
+        });
+
+        // Read a file without importing it; progress is kept for this session only
+        let ui_weak = self.ui.as_weak();
+        let book_service_clone = book_service.clone();
+        let rt_handle_clone = rt_handle.clone();
+        let reading_session = self.reading_session.clone();
+        let external_book = self.external_book.clone();
+        self.ui.on_open_external_file(move || {
+            let book_service = book_service_clone.clone();
+            let ui = ui_weak.clone();
+            let reading_session = reading_session.clone();
+            let external_book = external_book.clone();
+
+            rt_handle_clone.spawn(async move {
+                let Some(file_path) = rfd::AsyncFileDialog::new()
+                    .add_filter("eBooks", &["epub", "pdf", "mobi"])
+                    .pick_file()
+                    .await
+                else {
+                    return;
+                };
+
+                match book_service.open_external_file(file_path.path()).await {
+                    Ok(book) => {
+                        Self::switch_reading_session(&book_service, &reading_session, None).await;
+                        Self::close_external_book(&book_service, &external_book).await;
+                        *external_book.lock().await = Some(book.id.clone());
+
+                        slint::invoke_from_event_loop(move || {
+                            if let Some(ui) = ui.upgrade() {
+                                ui.set_current_view(SharedString::from("reading"));
+                                ui.set_current_book_title(SharedString::from(book.title));
+                                ui.set_current_book_author(SharedString::from(book.author));
+                                ui.set_current_book_progress(book.reading_progress);
+                                ui.set_current_book_external(true);
+                            }
+                        }).unwrap();
+                    }
+                    Err(e) => eprintln!("Error opening file: {}", e),
+                }
+            });
+        });
+
+        // Keep the external file being read, with its progress so far
+        let ui_weak = self.ui.as_weak();
+        let book_service_clone = book_service.clone();
+        let rt_handle_clone = rt_handle.clone();
+        let reading_session = self.reading_session.clone();
+        let external_book = self.external_book.clone();
+        self.ui.on_add_current_book_to_library(move || {
+            let book_service = book_service_clone.clone();
+            let ui = ui_weak.clone();
+            let reading_session = reading_session.clone();
+            let external_book = external_book.clone();
+
+            rt_handle_clone.spawn(async move {
+                let mut external_book = external_book.lock().await;
+                let Some(session_book_id) = external_book.clone() else {
+                    return;
+                };
+
+                match book_service.add_session_book_to_library(&session_book_id).await {
+                    Ok(book_id) => {
+                        *external_book = None;
+                        drop(external_book);
+                        Self::switch_reading_session(&book_service, &reading_session, Some(&book_id)).await;
+
+                        slint::invoke_from_event_loop({
+                            let ui = ui.clone();
+                            move || {
+                                if let Some(ui) = ui.upgrade() {
+                                    ui.set_current_book_external(false);
+                                }
+                            }
+                        }).unwrap();
+                        Self::refresh_library(book_service, ui).await;
+                    }
+                    Err(e) => eprintln!("Error adding book to library: {}", e),
+                }
+            });
+        });
+
         // Handle file opening
         let ui_weak = self.ui.as_weak();
         let book_service_clone = book_service.clone();
@@ -169,6 +408,8 @@ impl EbookReaderApp {
                                                         SharedString::from("")
                                                     },
                                                     added_date: SharedString::from(book.added_date.format("%Y-%m-%d").to_string()),
+                                                    reading_time: SharedString::from(book.reading_time.unwrap_or_default()),
+                                                    series: SharedString::from(book.series.unwrap_or_default()),
                                                 }
                                             }).collect::<Vec<_>>();
                                             
@@ -232,6 +473,8 @@ impl EbookReaderApp {
                                                 SharedString::from("")
                                             },
                                             added_date: SharedString::from(book.added_date.format("%Y-%m-%d").to_string()),
+                                            reading_time: SharedString::from(book.reading_time.unwrap_or_default()),
+                                            series: SharedString::from(book.series.unwrap_or_default()),
                                         }
                                     }).collect::<Vec<_>>();
                                     
@@ -267,6 +510,8 @@ impl EbookReaderApp {
                                                 SharedString::from("")
                                             },
                                             added_date: SharedString::from(book.added_date.format("%Y-%m-%d").to_string()),
+                                            reading_time: SharedString::from(book.reading_time.unwrap_or_default()),
+                                            series: SharedString::from(book.series.unwrap_or_default()),
                                         }
                                     }).collect::<Vec<_>>();
                                     
@@ -296,60 +541,143 @@ impl EbookReaderApp {
         Ok(())
     }
 
+    /// End the running reading session, if any, and start one for the opened book
+    async fn switch_reading_session(book_service: &BookService, current: &Mutex<Option<String>>, book_id: Option<&str>) {
+        let mut current = current.lock().await;
+        if let Some(session_id) = current.take() {
+            if let Err(e) = book_service.end_reading_session(&session_id).await {
+                eprintln!("Error ending reading session: {}", e);
+            }
+        }
+
+        if let Some(book_id) = book_id {
+            match book_service.start_reading_session(book_id).await {
+                Ok(session) => *current = session.map(|session| session.id),
+                Err(e) => eprintln!("Error starting reading session: {}", e),
+            }
+        }
+    }
+
+    /// Close the book opened without importing it, if any, discarding its progress
+    async fn close_external_book(book_service: &BookService, external_book: &Mutex<Option<String>>) {
+        if let Some(book_id) = external_book.lock().await.take() {
+            if let Err(e) = book_service.close_external_file(&book_id).await {
+                eprintln!("Error closing external file: {}", e);
+            }
+        }
+    }
+
     /// Load the book library
     fn load_library(&self) -> Result<()> {
-        let book_service = self.book_service.clone();
-        let ui = self.ui.as_weak();
+        self.rt.spawn(Self::refresh_library(self.book_service.clone(), self.ui.as_weak()));
         
-        self.rt.spawn(async move {
-            match book_service.get_library_books().await {
-                Ok(books) => {
-                    let book_models = books.into_iter().map(|book| {
-                        slint_generatedAppWindow::BookViewModel {
-                            id: SharedString::from(book.id),
-                            title: SharedString::from(book.title),
-                            author: SharedString::from(book.author),
-                            cover: if let Some(cover_path) = book.cover_path {
-                                slint::Image::load_from_path(&cover_path).unwrap_or_default()
-                            } else {
-                                slint::Image::default()
-                            },
-                            progress: book.progress,
-                            status: SharedString::from(book.status),
-                            is_favorite: book.is_favorite,
-                            rating: book.rating.unwrap_or(0) as i32,
-                            last_opened: if let Some(last_opened) = book.last_opened {
-                                SharedString::from(last_opened.format("%Y-%m-%d").to_string())
-                            } else {
-                                SharedString::from("")
-                            },
-                            added_date: SharedString::from(book.added_date.format("%Y-%m-%d").to_string()),
-                        }
-                    }).collect::<Vec<_>>();
-                    
-                    if let Some(ui) = ui.upgrade() {
-                        ui.set_books(ModelRc::new(VecModel::from(book_models)));
+        Ok(())
+    }
+
+    /// Reload the library grid from the database
+    async fn refresh_library(book_service: Arc<BookService>, ui: slint::Weak<AppWindow>) {
+        match book_service.get_library_books().await {
+            Ok(books) => {
+                let book_models = books.into_iter().map(|book| {
+                    slint_generatedAppWindow::BookViewModel {
+                        id: SharedString::from(book.id),
+                        title: SharedString::from(book.title),
+                        author: SharedString::from(book.author),
+                        cover: if let Some(cover_path) = book.cover_path {
+                            slint::Image::load_from_path(&cover_path).unwrap_or_default()
+                        } else {
+                            slint::Image::default()
+                        },
+                        progress: book.progress,
+                        status: SharedString::from(book.status),
+                        is_favorite: book.is_favorite,
+                        rating: book.rating.unwrap_or(0) as i32,
+                        last_opened: if let Some(last_opened) = book.last_opened {
+                            SharedString::from(last_opened.format("%Y-%m-%d").to_string())
+                        } else {
+                            SharedString::from("")
+                        },
+                        added_date: SharedString::from(book.added_date.format("%Y-%m-%d").to_string()),
+                        reading_time: SharedString::from(book.reading_time.unwrap_or_default()),
+                        series: SharedString::from(book.series.unwrap_or_default()),
                     }
-                }
-                Err(e) => {
-                    eprintln!("Error loading library: {}", e);
+                }).collect::<Vec<_>>();
+                
+                if let Some(ui) = ui.upgrade() {
+                    ui.set_books(ModelRc::new(VecModel::from(book_models)));
                 }
             }
-        });
-        
-        Ok(())
+            Err(e) => {
+                eprintln!("Error loading library: {}", e);
+            }
+        }
     }
 
     /// Run the application
     pub fn run(self) -> Result<()> {
         self.ui.run()?;
+        // Closing the window while reading ends the session
+        self.rt.block_on(async {
+            Self::switch_reading_session(&self.book_service, &self.reading_session, None).await;
+            Self::close_external_book(&self.book_service, &self.external_book).await;
+        });
         Ok(())
     }
 }
 
+/// Restore the library database from a backup, the newest one unless a file is given
+///
+/// Run as `ebook-reader --restore-backup [backup file]`, before the library is opened.
+fn restore_backup(backup: Option<std::path::PathBuf>) -> Result<()> {
+    use services::backup_service::BackupService;
+    use services::path_resolver::PathResolver;
+
+    let rt = tokio::runtime::Runtime::new()?;
+    let backups = BackupService::for_database(PathResolver::resolve_database_path_with_fallback()?);
+    let backup = match backup {
+        Some(backup) => backup,
+        None => backups.list_backups()?
+            .pop()
+            .map(|backup| backup.path)
+            .ok_or_else(|| anyhow::anyhow!("No backups found"))?,
+    };
+
+    if let Some(replaced) = rt.block_on(backups.restore_backup(&backup))? {
+        println!("📁 Previous database kept at {}", replaced.display());
+    }
+    println!("✅ Library restored from {}", backup.display());
+    Ok(())
+}
+
+/// Print the EPUB compatibility report, for attaching to bug reports
+///
+/// Run as `ebook-reader --compatibility-report`. The report only has entries
+/// while the compatibility ledger is turned on in the privacy preferences.
+fn print_compatibility_report() -> Result<()> {
+    use services::compatibility_ledger::CompatibilityLedger;
+    use services::preferences_service::PreferencesService;
+
+    let rt = tokio::runtime::Runtime::new()?;
+    let preferences = rt.block_on(PreferencesService::open_default()?.load());
+    if !preferences.privacy.compatibility_ledger_enabled {
+        println!("💡 The compatibility ledger is off, turn it on in the privacy preferences to record books");
+    }
+    print!("{}", CompatibilityLedger::open_default()?.report().to_text());
+    Ok(())
+}
+
+#[cfg(feature = "gui")]
 fn main() -> Result<()> {
     // Initialize logging
     tracing_subscriber::fmt::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "--restore-backup") {
+        return restore_backup(args.get(1).map(Into::into));
+    }
+    if args.first().is_some_and(|arg| arg == "--compatibility-report") {
+        return print_compatibility_report();
+    }
     
     // Create and run the application
     let app = EbookReaderApp::new()?;
@@ -357,4 +685,19 @@ fn main() -> Result<()> {
     app.run()?;
     
     Ok(())
+}
+
+/// Minimal reader for builds without the Slint frontend
+#[cfg(not(feature = "gui"))]
+fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "--restore-backup") {
+        return restore_backup(args.get(1).map(Into::into));
+    }
+    if args.first().is_some_and(|arg| arg == "--compatibility-report") {
+        return print_compatibility_report();
+    }
+    terminal_reader::run(args)
 }
\ No newline at end of file
@@ -1,13 +1 @@
-pub mod book;
-pub mod preferences;
-pub mod reading_theme;
-pub mod annotation;
-pub mod library;
-pub mod sync;
-
-pub use book::*;
-pub use preferences::*;
-pub use reading_theme::*;
-pub use annotation::*;
-pub use library::*;
-pub use sync::*;
\ No newline at end of file
+pub use epubreader_core::models::*;
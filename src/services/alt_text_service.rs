@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sqlx::{Row, SqlitePool};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::models::Book;
+use crate::services::epub_parser::EpubParser;
+use crate::services::secrets::EpubPasswordStore;
+
+static IMG_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<img\b[^>]*>").unwrap());
+static SRC_ATTR: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(?is)\bsrc\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+static ALT_ATTR: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(?is)\balt\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+
+/// Describes images for readers who cannot see them
+///
+/// Implemented by a local captioning model or a configured vision provider.
+#[async_trait]
+pub trait AltTextGenerator: Send + Sync {
+    async fn describe_image(&self, image: &[u8], media_type: &str) -> Result<String>;
+}
+
+/// Totals for an alt text generation job
+#[derive(Debug, Clone, Default)]
+pub struct AltTextJobSummary {
+    pub images_found: usize,
+    pub images_described: usize,
+    pub images_failed: usize,
+}
+
+/// Image in a chapter that has no alt text of its own
+struct MissingAltImage {
+    image_path: String,
+    data: Vec<u8>,
+    media_type: String,
+}
+
+/// Generated alt text for EPUB images lacking it
+#[derive(Clone)]
+pub struct AltTextService {
+    pool: SqlitePool,
+}
+
+impl AltTextService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize alt text tables
+    pub async fn init_tables(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS image_alt_text (
+                book_id TEXT NOT NULL,
+                image_path TEXT NOT NULL, -- Path within the EPUB archive
+                alt_text TEXT NOT NULL,
+                generated_at TEXT NOT NULL,
+                PRIMARY KEY (book_id, image_path),
+                FOREIGN KEY (book_id) REFERENCES books (id) ON DELETE CASCADE
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Store alt text for an image
+    pub async fn save_alt_text(&self, book_id: &str, image_path: &str, alt_text: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO image_alt_text (book_id, image_path, alt_text, generated_at) VALUES (?, ?, ?, ?)"
+        )
+        .bind(book_id)
+        .bind(image_path)
+        .bind(alt_text)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get generated alt text for a book, keyed by image path
+    pub async fn get_alt_texts(&self, book_id: &str) -> Result<HashMap<String, String>> {
+        let rows = sqlx::query("SELECT image_path, alt_text FROM image_alt_text WHERE book_id = ?")
+            .bind(book_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    /// Describe every image in a book that lacks alt text, in the background
+    ///
+    /// Images that already have generated alt text are skipped, so the job can be rerun.
+    pub fn spawn_alt_text_job(
+        &self,
+        book: Book,
+        generator: Arc<dyn AltTextGenerator>,
+    ) -> JoinHandle<Result<AltTextJobSummary>> {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let existing = service.get_alt_texts(&book.id).await?;
+            let file_path = book.file_path.clone();
+            let book_id = book.id.clone();
+            let images = tokio::task::spawn_blocking(move || {
+                let password = EpubPasswordStore::get(&book_id);
+                Self::collect_images_missing_alt(&file_path, password.as_deref(), &existing)
+            })
+            .await??;
+
+            let mut summary = AltTextJobSummary {
+                images_found: images.len(),
+                ..AltTextJobSummary::default()
+            };
+            for image in images {
+                match generator.describe_image(&image.data, &image.media_type).await {
+                    Ok(alt_text) if !alt_text.trim().is_empty() => {
+                        service.save_alt_text(&book.id, &image.image_path, alt_text.trim()).await?;
+                        summary.images_described += 1;
+                    }
+                    Ok(_) => summary.images_failed += 1,
+                    Err(e) => {
+                        warn!("Failed to describe {} in {}: {}", image.image_path, book.title, e);
+                        summary.images_failed += 1;
+                    }
+                }
+            }
+
+            info!(
+                "Alt text job finished for {}: {} of {} images described",
+                book.title, summary.images_described, summary.images_found
+            );
+            Ok(summary)
+        })
+    }
+
+    /// Read images with no alt attribute from every chapter in the spine
+    ///
+    /// An empty alt marks an image as decorative, so those are left alone.
+    fn collect_images_missing_alt(
+        file_path: &std::path::Path,
+        password: Option<&str>,
+        existing: &HashMap<String, String>,
+    ) -> Result<Vec<MissingAltImage>> {
+        let mut doc = EpubParser::open(file_path, password)?;
+        let mut image_paths = Vec::new();
+
+        for spine_item in doc.spine.clone() {
+            let Some((chapter_path, _)) = doc.resources.get(&spine_item.idref).cloned() else {
+                continue;
+            };
+            let Some(html) = EpubParser::read_text(&mut doc, &spine_item.idref) else {
+                continue;
+            };
+
+            let chapter_path = chapter_path.to_string_lossy().replace('\\', "/");
+            for tag in IMG_TAG.find_iter(&html) {
+                if tag_alt(tag.as_str()).is_some() {
+                    continue;
+                }
+                let Some(src) = tag_src(tag.as_str()) else {
+                    continue;
+                };
+                let image_path = resolve_href(&chapter_path, &src);
+                if !existing.contains_key(&image_path) && !image_paths.contains(&image_path) {
+                    image_paths.push(image_path);
+                }
+            }
+        }
+
+        let mut images = Vec::new();
+        for image_path in image_paths {
+            let media_type = doc.get_resource_mime_by_path(&image_path)
+                .unwrap_or_else(|| "application/octet-stream".to_string());
+            match doc.get_resource_by_path(&image_path) {
+                Some(data) => images.push(MissingAltImage { image_path, data, media_type }),
+                None => warn!("Image not found in EPUB: {}", image_path),
+            }
+        }
+
+        Ok(images)
+    }
+}
+
+/// Replace images with their alt text so it reaches rendered text and TTS
+///
+/// Uses the image's own alt attribute, falling back to generated alt text.
+/// Images without either are removed.
+pub fn inject_image_alt_text(html: &str, chapter_path: &str, generated: &HashMap<String, String>) -> String {
+    IMG_TAG.replace_all(html, |captures: &regex::Captures| {
+        let tag = &captures[0];
+        let alt_text = tag_alt(tag).or_else(|| {
+            tag_src(tag).and_then(|src| generated.get(&resolve_href(chapter_path, &src)).cloned())
+        });
+
+        match alt_text {
+            // An empty alt marks a decorative image
+            Some(alt_text) if alt_text.is_empty() => " ".to_string(),
+            Some(alt_text) => format!(" [Image: {}] ", html_escape::encode_text(&alt_text)),
+            None => " ".to_string(),
+        }
+    })
+    .into_owned()
+}
+
+/// Alt attribute of an img tag; empty for decorative images, `None` when missing
+fn tag_alt(tag: &str) -> Option<String> {
+    let captures = ALT_ATTR.captures(tag)?;
+    let alt = captures.get(1).or_else(|| captures.get(2))?.as_str().trim();
+    Some(html_escape::decode_html_entities(alt).into_owned())
+}
+
+/// Src attribute of an img tag
+fn tag_src(tag: &str) -> Option<String> {
+    let captures = SRC_ATTR.captures(tag)?;
+    captures.get(1).or_else(|| captures.get(2)).map(|src| src.as_str().to_string())
+}
+
+/// Resolve an href relative to the chapter that references it, dropping any fragment
+fn resolve_href(chapter_path: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or(href);
+    EpubParser::resolve_href(chapter_path, href)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inject_prefers_own_alt_and_falls_back_to_generated() {
+        let html = r#"<p>Intro</p><img src="../images/map.png"/><img alt="A lighthouse" src="../images/cover.jpg"/><img src="rule.png" alt=""/>"#;
+        let mut generated = HashMap::new();
+        generated.insert("OEBPS/images/map.png".to_string(), "Map of the coast".to_string());
+        generated.insert("OEBPS/text/rule.png".to_string(), "A horizontal rule".to_string());
+
+        // Decorative images, with an empty alt, stay out of the text
+        let injected = inject_image_alt_text(html, "OEBPS/text/ch1.xhtml", &generated);
+        assert_eq!(
+            injected,
+            "<p>Intro</p> [Image: Map of the coast]  [Image: A lighthouse]  "
+        );
+    }
+
+    #[test]
+    fn test_resolve_href() {
+        assert_eq!(resolve_href("OEBPS/text/ch1.xhtml", "../images/a.png"), "OEBPS/images/a.png");
+        assert_eq!(resolve_href("ch1.xhtml", "img/a.png#frag"), "img/a.png");
+    }
+}
@@ -1,14 +1,16 @@
 use std::sync::Arc;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use anyhow::Result;
 use slint::{ComponentHandle, Model, VecModel, ModelRc};
 use tokio::sync::RwLock;
 
 use crate::models::annotation::{
-    Annotation, Bookmark, AnnotationType, HighlightColor, BookmarkColor,
+    Annotation, Bookmark, Checkpoint, AnnotationType, HighlightColor, BookmarkColor,
     TextPosition, AnnotationFilter, ExportOptions, ExportFormat,
 };
 use crate::services::annotation_service::AnnotationService;
+use crate::services::export_share::share_export;
 
 /// Slint-compatible annotation model
 #[derive(Clone)]
@@ -277,6 +279,16 @@ impl AnnotationManager {
         Ok(page_bookmarks)
     }
 
+    /// Get checkpoints for the current book, for the quick-jump list
+    pub async fn get_checkpoints(&self) -> Result<Vec<Checkpoint>> {
+        let book_id = {
+            let book_id_guard = self.current_book_id.read().await;
+            book_id_guard.as_ref().ok_or_else(|| anyhow::anyhow!("No book loaded"))?.clone()
+        };
+
+        self.service.get_checkpoints_for_book(&book_id).await
+    }
+
     /// Export annotations
     pub async fn export_annotations(&self, format: ExportFormat) -> Result<String> {
         let book_id = {
@@ -289,16 +301,27 @@ impl AnnotationManager {
             include_highlights: true,
             include_notes: true,
             include_bookmarks: true,
+            include_checkpoints: true,
             include_timestamps: true,
+            include_export_date: false,
             include_page_numbers: true,
             include_context: true,
             group_by_chapter: false,
             sort_by: crate::models::annotation::AnnotationSortBy::CreatedAt,
+            anki: crate::models::annotation::AnkiExportOptions::default(),
         };
 
         self.service.export_annotations(&book_id, &options).await
     }
 
+    /// Export annotations to a file and share it through the OS
+    pub async fn share_annotations(&self, format: ExportFormat, book_title: &str) -> Result<PathBuf> {
+        let file_name = format!("{} annotations.{}", book_title, format.file_extension());
+        let contents = self.export_annotations(format).await?;
+
+        share_export(&file_name, contents.as_bytes()).await
+    }
+
     /// Get annotation statistics
     pub async fn get_statistics(&self) -> Result<HashMap<String, u32>> {
         let book_id = {
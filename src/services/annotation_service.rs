@@ -1,15 +1,71 @@
 use std::collections::HashMap;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use chrono::{DateTime, Utc};
+use serde_json::Value;
 use sqlx::{Row, SqlitePool, sqlite::SqliteRow};
 use uuid::Uuid;
 
+use crate::services::annotation_export::{AnnotationExporter, MarkdownNoteFile};
+use crate::services::epub_parser::EpubParser;
+use crate::services::secrets::EpubPasswordStore;
+use crate::services::epub_writer::{AnnotatedEpubMode, EpubBook, EpubWriter};
+use crate::services::reading_service::Chapter;
+use crate::utils::csv_format::csv_record;
+use crate::utils::text_anchor::{locate_quote, locate_text, AnchorMatch};
+use crate::models::book::{Book, BookFormat};
 use crate::models::annotation::{
-    Annotation, Bookmark, AnnotationType, HighlightColor, BookmarkColor,
-    TextPosition, AnnotationFilter, AnnotationStats, ExportOptions,
+    Annotation, Bookmark, Checkpoint, AnnotationType, HighlightColor, BookmarkColor,
+    TextPosition, TextQuote, PageRegion, AnnotationFilter, AnnotationStats, ExportOptions,
     ExportFormat, AnnotationSortBy, ReadingPatterns, TextFormatting,
 };
 
+/// Marks around the matched words in a search snippet
+const SNIPPET_START: &str = "**";
+const SNIPPET_END: &str = "**";
+/// Roughly how many words a search snippet shows
+const SNIPPET_WORDS: i64 = 16;
+
+/// An annotation whose text or note matched a search
+#[derive(Debug, Clone)]
+pub struct AnnotationMatch {
+    pub annotation: Annotation,
+    pub snippet: String, // Matched words wrapped in `**`
+}
+
+/// Search matches from one book, best match first
+#[derive(Debug, Clone)]
+pub struct BookAnnotationMatches {
+    pub book_id: String,
+    pub book_title: Option<String>,
+    pub matches: Vec<AnnotationMatch>,
+}
+
+/// An imported annotation that clashes with one already saved
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationImportConflict {
+    pub annotation_id: String,
+    pub reason: String,
+}
+
+/// Outcome of an annotation import
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnnotationImportReport {
+    pub imported: usize,
+    pub duplicates: usize,
+    pub reanchored: Vec<(String, AnchorMatch)>, // Annotations whose text had moved or changed
+    pub unanchored: Vec<String>,                // Text not found; kept at the exported offsets
+    pub conflicts: Vec<AnnotationImportConflict>,
+    pub invalid: Vec<String>,                   // Why entries were rejected
+}
+
+/// Outcome of re-anchoring a book's annotations in its current text
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReanchorReport {
+    pub checked: usize,
+    pub relocated: Vec<(String, AnchorMatch)>, // Annotations whose text had moved or changed
+    pub orphaned: Vec<String>,                 // Text not found; left where it was
+}
+
 #[derive(Clone)]
 pub struct AnnotationService {
     pool: SqlitePool,
@@ -46,6 +102,8 @@ impl AnnotationService {
                 formatting TEXT, -- JSON object
                 is_favorite BOOLEAN NOT NULL DEFAULT FALSE,
                 cross_references TEXT, -- JSON array
+                text_quote TEXT, -- JSON object
+                region TEXT, -- JSON object, for areas of fixed-layout and scanned pages
                 FOREIGN KEY (book_id) REFERENCES books (id) ON DELETE CASCADE
             );
             "#,
@@ -53,6 +111,14 @@ impl AnnotationService {
         .execute(&self.pool)
         .await?;
 
+        // Annotations saved before quote anchors are anchored by offsets until re-anchored
+        let _ = sqlx::query("ALTER TABLE annotations ADD COLUMN text_quote TEXT")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE annotations ADD COLUMN region TEXT")
+            .execute(&self.pool)
+            .await;
+
         // Create bookmarks table
         sqlx::query(
             r#"
@@ -79,6 +145,30 @@ impl AnnotationService {
         .execute(&self.pool)
         .await?;
 
+        // Create checkpoints table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS checkpoints (
+                id TEXT PRIMARY KEY,
+                book_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                page_number INTEGER NOT NULL,
+                start_offset INTEGER NOT NULL,
+                end_offset INTEGER NOT NULL,
+                paragraph_index INTEGER NOT NULL,
+                chapter_id TEXT,
+                line_number INTEGER,
+                column_number INTEGER,
+                theme_name TEXT,
+                notes TEXT,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (book_id) REFERENCES books (id) ON DELETE CASCADE
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
         // Create annotation categories table
         sqlx::query(
             r#"
@@ -131,6 +221,66 @@ impl AnnotationService {
             .execute(&self.pool)
             .await?;
 
+        self.init_search_index().await?;
+
+        Ok(())
+    }
+
+    /// Create the full-text index over annotation text and notes, kept current by triggers
+    async fn init_search_index(&self) -> Result<()> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'annotations_fts')"
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE VIRTUAL TABLE IF NOT EXISTS annotations_fts USING fts5(
+                selected_text,
+                note,
+                content = 'annotations',
+                content_rowid = 'rowid',
+                tokenize = 'porter unicode61 remove_diacritics 2'
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let triggers = [
+            r#"
+            CREATE TRIGGER IF NOT EXISTS annotations_fts_insert AFTER INSERT ON annotations BEGIN
+                INSERT INTO annotations_fts (rowid, selected_text, note)
+                VALUES (new.rowid, new.selected_text, new.note);
+            END;
+            "#,
+            r#"
+            CREATE TRIGGER IF NOT EXISTS annotations_fts_delete AFTER DELETE ON annotations BEGIN
+                INSERT INTO annotations_fts (annotations_fts, rowid, selected_text, note)
+                VALUES ('delete', old.rowid, old.selected_text, old.note);
+            END;
+            "#,
+            r#"
+            CREATE TRIGGER IF NOT EXISTS annotations_fts_update AFTER UPDATE ON annotations BEGIN
+                INSERT INTO annotations_fts (annotations_fts, rowid, selected_text, note)
+                VALUES ('delete', old.rowid, old.selected_text, old.note);
+                INSERT INTO annotations_fts (rowid, selected_text, note)
+                VALUES (new.rowid, new.selected_text, new.note);
+            END;
+            "#,
+        ];
+        for trigger in triggers {
+            sqlx::query(trigger).execute(&self.pool).await?;
+        }
+
+        // Index annotations made before the index existed
+        if !exists {
+            sqlx::query("INSERT INTO annotations_fts (annotations_fts) VALUES ('rebuild')")
+                .execute(&self.pool)
+                .await?;
+        }
+
         Ok(())
     }
 
@@ -141,16 +291,44 @@ impl AnnotationService {
         let formatting_json = annotation.formatting.as_ref()
             .map(|f| serde_json::to_string(f))
             .transpose()?;
+        let quote_json = annotation.position.quote.as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        let region_json = annotation.region.as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
 
         sqlx::query(
             r#"
-            INSERT OR REPLACE INTO annotations (
+            INSERT INTO annotations (
                 id, book_id, page_number, selected_text, note, color,
                 created_at, modified_at, start_offset, end_offset,
                 paragraph_index, chapter_id, line_number, column_number,
                 tags, category, annotation_type, formatting, is_favorite,
-                cross_references
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                cross_references, text_quote, region
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                book_id = excluded.book_id,
+                page_number = excluded.page_number,
+                selected_text = excluded.selected_text,
+                note = excluded.note,
+                color = excluded.color,
+                created_at = excluded.created_at,
+                modified_at = excluded.modified_at,
+                start_offset = excluded.start_offset,
+                end_offset = excluded.end_offset,
+                paragraph_index = excluded.paragraph_index,
+                chapter_id = excluded.chapter_id,
+                line_number = excluded.line_number,
+                column_number = excluded.column_number,
+                tags = excluded.tags,
+                category = excluded.category,
+                annotation_type = excluded.annotation_type,
+                formatting = excluded.formatting,
+                is_favorite = excluded.is_favorite,
+                cross_references = excluded.cross_references,
+                text_quote = excluded.text_quote,
+                region = excluded.region
             "#,
         )
         .bind(&annotation.id)
@@ -173,6 +351,8 @@ impl AnnotationService {
         .bind(&formatting_json)
         .bind(annotation.is_favorite)
         .bind(&cross_references_json)
+        .bind(&quote_json)
+        .bind(&region_json)
         .execute(&self.pool)
         .await?;
 
@@ -199,7 +379,7 @@ impl AnnotationService {
 
     /// Get all annotations for a book
     pub async fn get_annotations_for_book(&self, book_id: &str) -> Result<Vec<Annotation>> {
-        let rows = sqlx::query("SELECT * FROM annotations WHERE book_id = ? ORDER BY page_number, start_offset")
+        let rows = sqlx::query("SELECT * FROM annotations WHERE book_id = ? ORDER BY page_number, start_offset, id")
             .bind(book_id)
             .fetch_all(&self.pool)
             .await?;
@@ -212,6 +392,19 @@ impl AnnotationService {
         Ok(annotations)
     }
 
+    /// Get the areas marked on a page, oldest first so newer ones are drawn on top
+    pub async fn get_area_annotations_for_page(&self, book_id: &str, page_number: u32) -> Result<Vec<Annotation>> {
+        let rows = sqlx::query(
+            "SELECT * FROM annotations WHERE book_id = ? AND page_number = ? AND region IS NOT NULL ORDER BY created_at, id"
+        )
+        .bind(book_id)
+        .bind(page_number as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(|row| self.row_to_annotation(row)).collect()
+    }
+
     /// Get annotations with filter
     pub async fn get_annotations_filtered(&self, filter: &AnnotationFilter) -> Result<Vec<Annotation>> {
         // For now, implement a simplified version without dynamic parameters
@@ -240,6 +433,333 @@ impl AnnotationService {
         Ok(annotations)
     }
 
+    /// Search the text and notes of annotations across the library
+    ///
+    /// Every word of the query must match; the last one also matches as a
+    /// prefix so results appear while typing. Matches are grouped by book,
+    /// with the book holding the best match first.
+    pub async fn search_annotations(&self, query: &str, filter: &AnnotationFilter) -> Result<Vec<BookAnnotationMatches>> {
+        let Some(match_query) = Self::fts_query(query) else {
+            return Ok(Vec::new());
+        };
+
+        let rows = sqlx::query(
+            r#"
+            SELECT a.*, b.title AS book_title,
+                   snippet(annotations_fts, -1, ?, ?, '…', ?) AS snippet
+            FROM annotations_fts
+            JOIN annotations a ON a.rowid = annotations_fts.rowid
+            LEFT JOIN books b ON b.id = a.book_id
+            WHERE annotations_fts MATCH ?
+            ORDER BY bm25(annotations_fts), a.page_number
+            "#,
+        )
+        .bind(SNIPPET_START)
+        .bind(SNIPPET_END)
+        .bind(SNIPPET_WORDS)
+        .bind(&match_query)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut groups: Vec<BookAnnotationMatches> = Vec::new();
+        for row in rows {
+            let book_title: Option<String> = row.get("book_title");
+            let snippet: String = row.get("snippet");
+            let annotation = self.row_to_annotation(row)?;
+            if !Self::matches_filter(&annotation, filter) {
+                continue;
+            }
+
+            let found = AnnotationMatch { annotation, snippet };
+            match groups.iter_mut().find(|group| group.book_id == found.annotation.book_id) {
+                Some(group) => group.matches.push(found),
+                None => groups.push(BookAnnotationMatches {
+                    book_id: found.annotation.book_id.clone(),
+                    book_title,
+                    matches: vec![found],
+                }),
+            }
+        }
+
+        Ok(groups)
+    }
+
+    /// Turn what the user typed into an FTS5 query, quoting each word so punctuation can't break it
+    fn fts_query(query: &str) -> Option<String> {
+        let words: Vec<String> = query
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| format!("\"{}\"", word))
+            .collect();
+        if words.is_empty() {
+            return None;
+        }
+        Some(format!("{}*", words.join(" ")))
+    }
+
+    fn matches_filter(annotation: &Annotation, filter: &AnnotationFilter) -> bool {
+        if filter.book_id.as_ref().is_some_and(|book_id| &annotation.book_id != book_id) {
+            return false;
+        }
+        if filter.annotation_type.as_ref().is_some_and(|kind| &annotation.annotation_type != kind) {
+            return false;
+        }
+        if filter.color.as_ref().is_some_and(|color| &annotation.color != color) {
+            return false;
+        }
+        if filter.category.is_some() && annotation.category != filter.category {
+            return false;
+        }
+        if !filter.tags.is_empty() && !filter.tags.iter().any(|tag| annotation.tags.contains(tag)) {
+            return false;
+        }
+        if let Some((from, to)) = filter.date_range {
+            if annotation.created_at < from || annotation.created_at > to {
+                return false;
+            }
+        }
+        if filter.is_favorite.is_some_and(|favorite| annotation.is_favorite != favorite) {
+            return false;
+        }
+        true
+    }
+
+    /// Import annotations exported as JSON into a book, anchoring them in its current text
+    ///
+    /// Accepts this service's JSON export and the annotation exporter's. Each
+    /// annotation's text is looked up in `chapters`, so highlights land in the
+    /// right place even when the book was re-parsed or edited since the export.
+    /// Annotations already saved are skipped; ones saved with different
+    /// content are reported as conflicts and left untouched.
+    pub async fn import_annotations(&self, book_id: &str, json: &str, chapters: &[Chapter]) -> Result<AnnotationImportReport> {
+        let export: Value = serde_json::from_str(json).map_err(|e| anyhow!("Not an annotation export: {}", e))?;
+        let entries = match &export {
+            Value::Array(entries) => entries,
+            Value::Object(object) => object.get("annotations")
+                .and_then(Value::as_array)
+                .ok_or_else(|| anyhow!("Not an annotation export: it has no \"annotations\" list"))?,
+            _ => return Err(anyhow!("Not an annotation export: expected an object or a list")),
+        };
+
+        let mut report = AnnotationImportReport::default();
+        let mut existing = self.get_annotations_for_book(book_id).await?;
+        for (index, entry) in entries.iter().enumerate() {
+            let mut annotation = match Self::annotation_from_export(entry) {
+                Ok(annotation) => annotation,
+                Err(e) => {
+                    report.invalid.push(format!("Annotation {}: {}", index + 1, e));
+                    continue;
+                }
+            };
+            annotation.book_id = book_id.to_string();
+
+            match Self::reanchor(&mut annotation, chapters) {
+                Some(AnchorMatch::Exact) => {}
+                Some(matched) => report.reanchored.push((annotation.id.clone(), matched)),
+                None => report.unanchored.push(annotation.id.clone()),
+            }
+
+            if let Some(saved) = self.get_annotation(&annotation.id).await? {
+                if saved.book_id != book_id {
+                    report.conflicts.push(AnnotationImportConflict {
+                        annotation_id: annotation.id,
+                        reason: "Already saved in another book".to_string(),
+                    });
+                } else if Self::same_content(&saved, &annotation) {
+                    report.duplicates += 1;
+                } else {
+                    report.conflicts.push(AnnotationImportConflict {
+                        annotation_id: annotation.id,
+                        reason: "Differs from the saved annotation".to_string(),
+                    });
+                }
+                continue;
+            }
+
+            let duplicate = existing.iter().any(|saved| {
+                Self::same_content(saved, &annotation)
+                    && saved.position.chapter_id == annotation.position.chapter_id
+                    && saved.position.start_offset == annotation.position.start_offset
+            });
+            if duplicate {
+                report.duplicates += 1;
+                continue;
+            }
+
+            self.save_annotation(&annotation).await?;
+            existing.push(annotation);
+            report.imported += 1;
+        }
+
+        Ok(report)
+    }
+
+    /// Read an exported annotation, either serialized whole or in the annotation exporter's shape
+    fn annotation_from_export(entry: &Value) -> Result<Annotation> {
+        if let Ok(annotation) = serde_json::from_value::<Annotation>(entry.clone()) {
+            return Ok(annotation);
+        }
+
+        let object = entry.as_object().ok_or_else(|| anyhow!("not an object"))?;
+        let selected_text = object.get("selected_text")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("missing \"selected_text\""))?;
+        let page_number = object.get("page_number")
+            .and_then(Value::as_u64)
+            .and_then(|page| u32::try_from(page).ok())
+            .ok_or_else(|| anyhow!("missing or invalid \"page_number\""))?;
+        let note = object.get("note").and_then(Value::as_str).map(str::to_string);
+        let region = object.get("region")
+            .map(|region| serde_json::from_value::<PageRegion>(region.clone()))
+            .transpose()
+            .map_err(|e| anyhow!("invalid \"region\": {}", e))?;
+        if selected_text.trim().is_empty() && region.is_none() && note.as_deref().is_none_or(|note| note.trim().is_empty()) {
+            return Err(anyhow!("has neither text nor a note"));
+        }
+        let date = |field: &str| -> Result<Option<DateTime<Utc>>> {
+            object.get(field)
+                .and_then(Value::as_str)
+                .map(|date| DateTime::parse_from_rfc3339(date)
+                    .map(|date| date.with_timezone(&Utc))
+                    .map_err(|e| anyhow!("invalid \"{}\": {}", field, e)))
+                .transpose()
+        };
+
+        let position = TextPosition {
+            start_offset: 0,
+            end_offset: 0,
+            paragraph_index: 0,
+            chapter_id: None,
+            line_number: None,
+            column_number: None,
+            quote: None,
+        };
+        let annotation_type = object.get("type")
+            .and_then(Value::as_str)
+            .map_or(AnnotationType::Highlight, Self::annotation_type_from_name);
+        let mut annotation = Annotation::new(String::new(), page_number, selected_text.to_string(), position, annotation_type);
+        if let Some(id) = object.get("id").and_then(Value::as_str).filter(|id| !id.is_empty()) {
+            annotation.id = id.to_string();
+        }
+        annotation.note = note;
+        annotation.region = region;
+        if let Some(color) = object.get("color").and_then(Value::as_str) {
+            annotation.color = Self::highlight_color_from_name(color);
+        }
+        if let Some(created_at) = date("created_at")? {
+            annotation.created_at = created_at;
+            annotation.modified_at = created_at;
+        }
+        if let Some(modified_at) = date("modified_at")? {
+            annotation.modified_at = modified_at;
+        }
+        annotation.tags = object.get("tags")
+            .and_then(Value::as_array)
+            .map(|tags| tags.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default();
+        annotation.category = object.get("category").and_then(Value::as_str).map(str::to_string);
+        annotation.is_favorite = object.get("is_favorite").and_then(Value::as_bool).unwrap_or(false);
+        Ok(annotation)
+    }
+
+    /// Relocate a book's annotations after its content changed, e.g. after re-parsing
+    ///
+    /// Annotations are found by their quote anchor, or by their text when they
+    /// have none yet, and get a fresh quote for their current place.
+    pub async fn reanchor_book(&self, book_id: &str, chapters: &[Chapter]) -> Result<ReanchorReport> {
+        let mut report = ReanchorReport::default();
+        for mut annotation in self.get_annotations_for_book(book_id).await? {
+            report.checked += 1;
+            let before = annotation.position.clone();
+            match Self::reanchor(&mut annotation, chapters) {
+                Some(AnchorMatch::Exact) => {}
+                Some(matched) => report.relocated.push((annotation.id.clone(), matched)),
+                None => {
+                    report.orphaned.push(annotation.id.clone());
+                    continue;
+                }
+            }
+            if annotation.position != before {
+                self.save_annotation(&annotation).await?;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Point an annotation at where its text is now, searching its own chapter first
+    ///
+    /// Returns `None` when the text wasn't found, leaving the position as it was.
+    fn reanchor(annotation: &mut Annotation, chapters: &[Chapter]) -> Option<AnchorMatch> {
+        // Notes without highlighted text and areas of pages have nothing to find
+        if annotation.selected_text.is_empty() || annotation.region.is_some() {
+            return Some(AnchorMatch::Exact);
+        }
+
+        let position = &mut annotation.position;
+        let recorded = chapters.iter().position(|chapter| Some(&chapter.id) == position.chapter_id.as_ref());
+        let search_order = recorded.into_iter().chain((0..chapters.len()).filter(|index| Some(*index) != recorded));
+        for index in search_order {
+            let chapter = &chapters[index];
+            let expected = if Some(index) == recorded {
+                (position.start_offset, position.end_offset)
+            } else {
+                (0, 0)
+            };
+            let found = match &position.quote {
+                Some(quote) => locate_quote(&chapter.content, quote, expected),
+                None => locate_text(&chapter.content, &annotation.selected_text, expected),
+            };
+            if let Some(anchor) = found {
+                position.chapter_id = Some(chapter.id.clone());
+                position.start_offset = anchor.start;
+                position.end_offset = anchor.end;
+                position.quote = TextQuote::capture(&chapter.content, anchor.start, anchor.end);
+                return Some(anchor.matched);
+            }
+        }
+        None
+    }
+
+    fn same_content(a: &Annotation, b: &Annotation) -> bool {
+        a.selected_text == b.selected_text
+            && a.note == b.note
+            && a.tags == b.tags
+            && a.color == b.color
+            && a.annotation_type == b.annotation_type
+            && a.region == b.region
+    }
+
+    fn annotation_type_from_name(name: &str) -> AnnotationType {
+        match name {
+            "Highlight" => AnnotationType::Highlight,
+            "Note" => AnnotationType::Note,
+            "Bookmark" => AnnotationType::Bookmark,
+            "Underline" => AnnotationType::Underline,
+            "Strikethrough" => AnnotationType::Strikethrough,
+            "Question" => AnnotationType::Question,
+            "Important" => AnnotationType::Important,
+            "Reference" => AnnotationType::Reference,
+            _ => AnnotationType::Highlight,
+        }
+    }
+
+    /// Read a color saved by name, or exported as hex
+    fn highlight_color_from_name(name: &str) -> HighlightColor {
+        let named = [
+            HighlightColor::Yellow,
+            HighlightColor::Green,
+            HighlightColor::Blue,
+            HighlightColor::Pink,
+            HighlightColor::Orange,
+            HighlightColor::Purple,
+            HighlightColor::Red,
+            HighlightColor::Gray,
+        ];
+        named.into_iter()
+            .find(|color| color.to_name() == name || color.to_hex().eq_ignore_ascii_case(name))
+            .unwrap_or_else(|| HighlightColor::Custom(name.to_string()))
+    }
+
     /// Update annotation
     pub async fn update_annotation(&self, annotation: &Annotation) -> Result<()> {
         self.save_annotation(annotation).await
@@ -252,9 +772,118 @@ impl AnnotationService {
             .execute(&self.pool)
             .await?;
 
+        // Links to a deleted annotation would dangle
+        sqlx::query(
+            r#"
+            UPDATE annotations
+            SET cross_references = (SELECT json_group_array(value) FROM json_each(cross_references) WHERE value != ?1)
+            WHERE EXISTS (SELECT 1 FROM json_each(cross_references) WHERE value = ?1)
+            "#
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Link one annotation to another, usually in a different book
+    pub async fn link_annotations(&self, from_id: &str, to_id: &str) -> Result<()> {
+        if from_id == to_id {
+            return Err(anyhow!("An annotation can't be linked to itself"));
+        }
+        let mut from = self.get_annotation(from_id).await?
+            .ok_or_else(|| anyhow!("Annotation not found: {}", from_id))?;
+        if self.get_annotation(to_id).await?.is_none() {
+            return Err(anyhow!("Annotation not found: {}", to_id));
+        }
+
+        if !from.cross_references.iter().any(|id| id == to_id) {
+            from.cross_references.push(to_id.to_string());
+            from.modified_at = Utc::now();
+            self.save_annotation(&from).await?;
+        }
         Ok(())
     }
 
+    /// Remove a link between two annotations
+    pub async fn unlink_annotations(&self, from_id: &str, to_id: &str) -> Result<()> {
+        let Some(mut from) = self.get_annotation(from_id).await? else {
+            return Ok(());
+        };
+        let count = from.cross_references.len();
+        from.cross_references.retain(|id| id != to_id);
+        if from.cross_references.len() != count {
+            from.modified_at = Utc::now();
+            self.save_annotation(&from).await?;
+        }
+        Ok(())
+    }
+
+    /// Annotations an annotation links to
+    pub async fn get_linked_annotations(&self, id: &str) -> Result<Vec<Annotation>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT a.* FROM annotations a
+            JOIN (SELECT value, key FROM annotations, json_each(annotations.cross_references) WHERE annotations.id = ?) links
+                ON links.value = a.id
+            ORDER BY links.key
+            "#
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(|row| self.row_to_annotation(row)).collect()
+    }
+
+    /// Annotations that link to an annotation
+    pub async fn get_backlinks(&self, id: &str) -> Result<Vec<Annotation>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM annotations
+            WHERE EXISTS (SELECT 1 FROM json_each(cross_references) WHERE value = ?)
+            ORDER BY book_id, page_number, start_offset, id
+            "#
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(|row| self.row_to_annotation(row)).collect()
+    }
+
+    /// Export every linked annotation as Markdown notes joined by wiki-links, one per book
+    pub async fn export_linked_notes(&self) -> Result<Vec<MarkdownNoteFile>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM annotations
+            WHERE json_array_length(cross_references) > 0
+               OR id IN (SELECT value FROM annotations, json_each(annotations.cross_references))
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let annotations = rows.into_iter()
+            .map(|row| self.row_to_annotation(row))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut book_titles = HashMap::new();
+        for annotation in &annotations {
+            if !book_titles.contains_key(&annotation.book_id) {
+                let title: Option<String> = sqlx::query_scalar("SELECT title FROM books WHERE id = ?")
+                    .bind(&annotation.book_id)
+                    .fetch_optional(&self.pool)
+                    .await?;
+                if let Some(title) = title {
+                    book_titles.insert(annotation.book_id.clone(), title);
+                }
+            }
+        }
+
+        Ok(AnnotationExporter::export_linked_notes(&annotations, &book_titles))
+    }
+
     /// Save bookmark to database
     pub async fn save_bookmark(&self, bookmark: &Bookmark) -> Result<()> {
         sqlx::query(
@@ -289,7 +918,7 @@ impl AnnotationService {
 
     /// Get all bookmarks for a book
     pub async fn get_bookmarks_for_book(&self, book_id: &str) -> Result<Vec<Bookmark>> {
-        let rows = sqlx::query("SELECT * FROM bookmarks WHERE book_id = ? ORDER BY page_number")
+        let rows = sqlx::query("SELECT * FROM bookmarks WHERE book_id = ? ORDER BY page_number, id")
             .bind(book_id)
             .fetch_all(&self.pool)
             .await?;
@@ -312,6 +941,73 @@ impl AnnotationService {
         Ok(())
     }
 
+    /// Save checkpoint to database
+    pub async fn save_checkpoint(&self, checkpoint: &Checkpoint) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO checkpoints (
+                id, book_id, name, page_number, start_offset, end_offset,
+                paragraph_index, chapter_id, line_number, column_number,
+                theme_name, notes, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&checkpoint.id)
+        .bind(&checkpoint.book_id)
+        .bind(&checkpoint.name)
+        .bind(checkpoint.page_number as i64)
+        .bind(checkpoint.position.start_offset as i64)
+        .bind(checkpoint.position.end_offset as i64)
+        .bind(checkpoint.position.paragraph_index as i64)
+        .bind(&checkpoint.position.chapter_id)
+        .bind(checkpoint.position.line_number.map(|n| n as i64))
+        .bind(checkpoint.position.column_number.map(|n| n as i64))
+        .bind(&checkpoint.theme_name)
+        .bind(&checkpoint.notes)
+        .bind(checkpoint.created_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get a checkpoint by id, e.g. to restore it
+    pub async fn get_checkpoint(&self, id: &str) -> Result<Option<Checkpoint>> {
+        let row = sqlx::query("SELECT * FROM checkpoints WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|row| self.row_to_checkpoint(row)).transpose()
+    }
+
+    /// Get all checkpoints for a book in reading order
+    pub async fn get_checkpoints_for_book(&self, book_id: &str) -> Result<Vec<Checkpoint>> {
+        let rows = sqlx::query(
+            "SELECT * FROM checkpoints WHERE book_id = ? ORDER BY page_number, start_offset, id"
+        )
+        .bind(book_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut checkpoints = Vec::new();
+        for row in rows {
+            checkpoints.push(self.row_to_checkpoint(row)?);
+        }
+
+        Ok(checkpoints)
+    }
+
+    /// Delete checkpoint
+    pub async fn delete_checkpoint(&self, id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM checkpoints WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
     /// Get annotation statistics
     pub async fn get_annotation_stats(&self, book_id: Option<&str>) -> Result<AnnotationStats> {
         let book_filter = if let Some(book_id) = book_id {
@@ -428,15 +1124,23 @@ impl AnnotationService {
         } else {
             Vec::new()
         };
+        let checkpoints = if options.include_checkpoints {
+            self.get_checkpoints_for_book(book_id).await?
+        } else {
+            Vec::new()
+        };
 
         match options.format {
             ExportFormat::Json => {
-                let export_data = serde_json::json!({
+                let mut export_data = serde_json::json!({
                     "annotations": annotations,
                     "bookmarks": bookmarks,
-                    "exported_at": Utc::now().to_rfc3339(),
+                    "checkpoints": checkpoints,
                     "options": options,
                 });
+                if options.include_export_date {
+                    export_data["exported_at"] = serde_json::Value::String(Utc::now().to_rfc3339());
+                }
                 Ok(serde_json::to_string_pretty(&export_data)?)
             }
             ExportFormat::Csv => {
@@ -451,15 +1155,25 @@ impl AnnotationService {
                         continue;
                     }
                     
-                    csv_data.push_str(&format!(
-                        "{},{},{},{},{},{}\n",
+                    csv_data.push_str(&csv_record(&[
                         annotation.annotation_type.to_display_name(),
-                        annotation.page_number,
-                        annotation.selected_text.replace('"', "\"\""),
-                        annotation.note.unwrap_or_default().replace('"', "\"\""),
+                        annotation.page_number.to_string(),
+                        annotation.excerpt(),
+                        annotation.note.unwrap_or_default(),
                         annotation.color.to_name(),
-                        annotation.created_at.format("%Y-%m-%d %H:%M:%S")
-                    ));
+                        annotation.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    ]));
+                }
+
+                for checkpoint in checkpoints {
+                    csv_data.push_str(&csv_record(&[
+                        "Checkpoint".to_string(),
+                        checkpoint.page_number.to_string(),
+                        checkpoint.name,
+                        checkpoint.notes.unwrap_or_default(),
+                        String::new(),
+                        checkpoint.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    ]));
                 }
                 
                 Ok(csv_data)
@@ -475,7 +1189,7 @@ impl AnnotationService {
                         annotation.annotation_type.to_display_name()
                     ));
                     
-                    md_data.push_str(&format!("> {}\n\n", annotation.selected_text));
+                    md_data.push_str(&format!("> {}\n\n", annotation.excerpt()));
                     
                     if let Some(note) = &annotation.note {
                         md_data.push_str(&format!("**Note:** {}\n\n", note));
@@ -490,13 +1204,58 @@ impl AnnotationService {
                     
                     md_data.push_str("---\n\n");
                 }
+
+                if !checkpoints.is_empty() {
+                    md_data.push_str("# Checkpoints\n\n");
+                    for checkpoint in checkpoints {
+                        md_data.push_str(&format!("- **{}** (Page {})", checkpoint.name, checkpoint.page_number));
+                        if let Some(notes) = &checkpoint.notes {
+                            md_data.push_str(&format!(": {}", notes));
+                        }
+                        md_data.push('\n');
+                    }
+                    md_data.push('\n');
+                }
                 
                 Ok(md_data)
             }
+            ExportFormat::Anki => {
+                let book_title: Option<String> = sqlx::query_scalar("SELECT title FROM books WHERE id = ?")
+                    .bind(book_id)
+                    .fetch_optional(&self.pool)
+                    .await?;
+                AnnotationExporter::export_annotations(&annotations, &bookmarks, options, book_title.as_deref())
+            }
             _ => Err(anyhow::anyhow!("Export format not yet implemented")),
         }
     }
 
+    /// Write a copy of an EPUB with its annotations in it, to share or read in another app
+    pub async fn export_annotated_epub(&self, book: &Book, mode: AnnotatedEpubMode) -> Result<Vec<u8>> {
+        if book.file_format != BookFormat::Epub {
+            return Err(anyhow!("Only EPUB books can be exported with their annotations"));
+        }
+        let annotations = self.get_annotations_for_book(&book.id).await?;
+        let file_path = book.file_path.clone();
+        // The keyring blocks on its own runtime, so it must stay off async worker threads
+        let password = match tokio::task::spawn_blocking(move || EpubParser::is_encrypted(&file_path)).await?? {
+            true => {
+                let book_id = book.id.clone();
+                tokio::task::spawn_blocking(move || EpubPasswordStore::get(&book_id)).await?
+            }
+            false => None,
+        };
+        let book = book.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut doc = EpubParser::open(&book.file_path, password.as_deref())?;
+            let mut epub = EpubBook::from_document(&mut doc);
+            epub.apply_book_metadata(&book);
+            epub.add_annotations(&annotations, mode);
+            EpubWriter::write(&epub)
+        })
+        .await?
+    }
+
     /// Create new annotation
     pub async fn create_annotation(
         &self,
@@ -523,6 +1282,24 @@ impl AnnotationService {
         Ok(annotation)
     }
 
+    /// Create an annotation marking an area of a fixed-layout or scanned page
+    pub async fn create_area_annotation(
+        &self,
+        book_id: String,
+        page_number: u32,
+        region: PageRegion,
+        annotation_type: AnnotationType,
+        color: HighlightColor,
+        note: Option<String>,
+    ) -> Result<Annotation> {
+        let mut annotation = Annotation::new_area(book_id, page_number, region, annotation_type);
+        annotation.color = color;
+        annotation.note = note;
+
+        self.save_annotation(&annotation).await?;
+        Ok(annotation)
+    }
+
     /// Create new bookmark
     pub async fn create_bookmark(
         &self,
@@ -541,6 +1318,24 @@ impl AnnotationService {
         Ok(bookmark)
     }
 
+    /// Create new checkpoint at the current position
+    pub async fn create_checkpoint(
+        &self,
+        book_id: String,
+        name: String,
+        page_number: u32,
+        position: TextPosition,
+        theme_name: Option<String>,
+        notes: Option<String>,
+    ) -> Result<Checkpoint> {
+        let mut checkpoint = Checkpoint::new(book_id, name, page_number, position);
+        checkpoint.theme_name = theme_name;
+        checkpoint.notes = notes;
+
+        self.save_checkpoint(&checkpoint).await?;
+        Ok(checkpoint)
+    }
+
     /// Update tag usage count
     async fn update_tag_usage(&self, tag: &str) -> Result<()> {
         sqlx::query(
@@ -579,31 +1374,21 @@ impl AnnotationService {
             .map(|json| serde_json::from_str(&json))
             .transpose()?;
 
+        let quote_json: Option<String> = row.get("text_quote");
+        let quote: Option<TextQuote> = quote_json
+            .map(|json| serde_json::from_str(&json))
+            .transpose()?;
+
+        let region_json: Option<String> = row.get("region");
+        let region: Option<PageRegion> = region_json
+            .map(|json| serde_json::from_str(&json))
+            .transpose()?;
+
         let color_name: String = row.get("color");
-        let color = match color_name.as_str() {
-            "Yellow" => HighlightColor::Yellow,
-            "Green" => HighlightColor::Green,
-            "Blue" => HighlightColor::Blue,
-            "Pink" => HighlightColor::Pink,
-            "Orange" => HighlightColor::Orange,
-            "Purple" => HighlightColor::Purple,
-            "Red" => HighlightColor::Red,
-            "Gray" => HighlightColor::Gray,
-            _ => HighlightColor::Custom(color_name),
-        };
+        let color = Self::highlight_color_from_name(&color_name);
 
         let annotation_type_name: String = row.get("annotation_type");
-        let annotation_type = match annotation_type_name.as_str() {
-            "Highlight" => AnnotationType::Highlight,
-            "Note" => AnnotationType::Note,
-            "Bookmark" => AnnotationType::Bookmark,
-            "Underline" => AnnotationType::Underline,
-            "Strikethrough" => AnnotationType::Strikethrough,
-            "Question" => AnnotationType::Question,
-            "Important" => AnnotationType::Important,
-            "Reference" => AnnotationType::Reference,
-            _ => AnnotationType::Highlight,
-        };
+        let annotation_type = Self::annotation_type_from_name(&annotation_type_name);
 
         let created_at_str: String = row.get("created_at");
         let modified_at_str: String = row.get("modified_at");
@@ -624,6 +1409,7 @@ impl AnnotationService {
                 chapter_id: row.get("chapter_id"),
                 line_number: row.get::<Option<i64>, _>("line_number").map(|n| n as u32),
                 column_number: row.get::<Option<i64>, _>("column_number").map(|n| n as u32),
+                quote,
             },
             tags,
             category: row.get("category"),
@@ -631,6 +1417,7 @@ impl AnnotationService {
             formatting,
             is_favorite: row.get("is_favorite"),
             cross_references,
+            region,
         })
     }
 
@@ -665,9 +1452,345 @@ impl AnnotationService {
                 chapter_id: row.get("chapter_id"),
                 line_number: row.get::<Option<i64>, _>("line_number").map(|n| n as u32),
                 column_number: row.get::<Option<i64>, _>("column_number").map(|n| n as u32),
+                quote: None,
             },
             color,
             is_favorite: row.get("is_favorite"),
         })
     }
-}
\ No newline at end of file
+
+    /// Convert database row to Checkpoint
+    fn row_to_checkpoint(&self, row: SqliteRow) -> Result<Checkpoint> {
+        let created_at_str: String = row.get("created_at");
+
+        Ok(Checkpoint {
+            id: row.get("id"),
+            book_id: row.get("book_id"),
+            name: row.get("name"),
+            page_number: row.get::<i64, _>("page_number") as u32,
+            position: TextPosition {
+                start_offset: row.get::<i64, _>("start_offset") as usize,
+                end_offset: row.get::<i64, _>("end_offset") as usize,
+                paragraph_index: row.get::<i64, _>("paragraph_index") as usize,
+                chapter_id: row.get("chapter_id"),
+                line_number: row.get::<Option<i64>, _>("line_number").map(|n| n as u32),
+                column_number: row.get::<Option<i64>, _>("column_number").map(|n| n as u32),
+                quote: None,
+            },
+            theme_name: row.get("theme_name"),
+            notes: row.get("notes"),
+            created_at: DateTime::parse_from_rfc3339(&created_at_str)?.with_timezone(&Utc),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position() -> TextPosition {
+        TextPosition {
+            start_offset: 0,
+            end_offset: 0,
+            paragraph_index: 3,
+            chapter_id: Some("ch7".to_string()),
+            line_number: None,
+            column_number: None,
+            quote: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_checkpoints_are_restorable_and_exported() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE books (id TEXT PRIMARY KEY)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO books (id) VALUES ('book')").execute(&pool).await.unwrap();
+        let service = AnnotationService::new(pool);
+        service.init_tables().await.unwrap();
+
+        let checkpoint = service.create_checkpoint(
+            "book".to_string(),
+            "Start of exam-relevant section".to_string(),
+            42,
+            position(),
+            Some("Sepia".to_string()),
+            Some("Chapters 7-9".to_string()),
+        ).await.unwrap();
+
+        let restored = service.get_checkpoint(&checkpoint.id).await.unwrap().unwrap();
+        assert_eq!(restored.page_number, 42);
+        assert_eq!(restored.theme_name.as_deref(), Some("Sepia"));
+        assert_eq!(restored.position.chapter_id.as_deref(), Some("ch7"));
+
+        let options = ExportOptions { format: ExportFormat::Markdown, ..ExportOptions::default() };
+        let markdown = service.export_annotations("book", &options).await.unwrap();
+        assert!(markdown.contains("- **Start of exam-relevant section** (Page 42): Chapters 7-9"));
+
+        service.create_checkpoint("book".to_string(), "Notes, part \"two\"".to_string(), 50, position(), None, None).await.unwrap();
+        let options = ExportOptions { format: ExportFormat::Csv, ..ExportOptions::default() };
+        let csv = service.export_annotations("book", &options).await.unwrap();
+        assert!(csv.contains("Checkpoint,50,\"Notes, part \"\"two\"\"\",,,"));
+    }
+
+    #[tokio::test]
+    async fn test_import_reanchors_and_reports_duplicates() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE books (id TEXT PRIMARY KEY)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO books (id) VALUES ('book'), ('copy')").execute(&pool).await.unwrap();
+        let service = AnnotationService::new(pool);
+        service.init_tables().await.unwrap();
+
+        let chapter = |id: &str, content: &str| Chapter {
+            id: id.to_string(),
+            title: id.to_string(),
+            content: content.to_string(),
+            word_count: 0,
+            order: 0,
+            media_overlay: None,
+            blocks: Vec::new(),
+        };
+        let text = "It was the best of times, it was the worst of times.";
+        let mut position = position();
+        position.end_offset = 24;
+        let highlight = service.create_annotation(
+            "book".to_string(), 1, "the best of times".to_string(), position,
+            AnnotationType::Highlight, HighlightColor::Green, Some("Dickens".to_string()),
+        ).await.unwrap();
+        let mut highlight_position = highlight.position.clone();
+        highlight_position.start_offset = text.find("the best").unwrap();
+        highlight_position.end_offset = highlight_position.start_offset + "the best of times".len();
+        let highlight = Annotation { position: highlight_position, ..highlight };
+        service.update_annotation(&highlight).await.unwrap();
+        let export = service.export_annotations("book", &ExportOptions::default()).await.unwrap();
+
+        // The new edition has a foreword and moved the passage to another chapter
+        let edition = [
+            chapter("ch1", "Foreword."),
+            chapter("ch2", &format!("BOOK ONE. {}", text)),
+        ];
+        let report = service.import_annotations("copy", &export, &edition).await.unwrap();
+        assert_eq!(report.conflicts, vec![AnnotationImportConflict {
+            annotation_id: highlight.id.clone(),
+            reason: "Already saved in another book".to_string(),
+        }]);
+        assert_eq!(report.imported, 0);
+
+        // The exporter's shape carries no position, so the text is searched for
+        let exporter_json = serde_json::json!({
+            "book_title": "A Tale of Two Cities",
+            "annotations": [
+                {"id": "a1", "type": "Highlight", "page_number": 1, "selected_text": "the worst  of TIMES",
+                 "note": "Contrast", "color": "#90EE90", "tags": ["openings"]},
+                {"id": "a2", "page_number": 2, "selected_text": "Not in this edition"},
+                {"id": "a3", "selected_text": "No page"},
+                {"id": "a4", "page_number": 3, "selected_text": "It was the best of times", "modified_at": "yesterday"}
+            ]
+        }).to_string();
+        let report = service.import_annotations("copy", &exporter_json, &edition).await.unwrap();
+        assert_eq!(report.imported, 2);
+        assert_eq!(report.reanchored, vec![("a1".to_string(), AnchorMatch::Normalized)]);
+        assert_eq!(report.unanchored, vec!["a2".to_string()]);
+        assert_eq!(report.invalid.len(), 2);
+        let imported = service.get_annotation("a1").await.unwrap().unwrap();
+        assert_eq!(imported.book_id, "copy");
+        assert_eq!(imported.color, HighlightColor::Green);
+        assert_eq!(imported.position.chapter_id.as_deref(), Some("ch2"));
+        assert_eq!(&edition[1].content[imported.position.start_offset..imported.position.end_offset], "the worst of times");
+
+        let again = service.import_annotations("copy", &exporter_json, &edition).await.unwrap();
+        assert_eq!((again.imported, again.duplicates), (0, 2));
+        assert!(service.import_annotations("copy", "[1, 2", &edition).await.is_err());
+        assert!(service.import_annotations("copy", r#"{"bookmarks": []}"#, &edition).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reanchor_book_after_the_content_changed() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE books (id TEXT PRIMARY KEY)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO books (id) VALUES ('book')").execute(&pool).await.unwrap();
+        let service = AnnotationService::new(pool);
+        service.init_tables().await.unwrap();
+
+        let chapter = |content: &str| Chapter {
+            id: "ch7".to_string(),
+            title: "Seven".to_string(),
+            content: content.to_string(),
+            word_count: 0,
+            order: 7,
+            media_overlay: None,
+            blocks: Vec::new(),
+        };
+        let text = "Row, row. Gently down the stream. Row, row.";
+        let mut position = position();
+        position.start_offset = 34;
+        position.end_offset = 42;
+        let annotation = service.create_annotation(
+            "book".to_string(), 1, "Row, row".to_string(), position,
+            AnnotationType::Highlight, HighlightColor::Yellow, None,
+        ).await.unwrap();
+
+        // Offsets still fit, so the annotation only gains its quote anchor
+        let report = service.reanchor_book("book", &[chapter(text)]).await.unwrap();
+        assert_eq!((report.checked, report.relocated.len()), (1, 0));
+        let quote = service.get_annotation(&annotation.id).await.unwrap().unwrap().position.quote.unwrap();
+        assert_eq!(quote.prefix, "w, row. Gently down the stream. ");
+
+        let revised = format!("Verse two. {}", text);
+        let report = service.reanchor_book("book", &[chapter(&revised)]).await.unwrap();
+        assert_eq!(report.relocated, vec![(annotation.id.clone(), AnchorMatch::Moved)]);
+        let moved = service.get_annotation(&annotation.id).await.unwrap().unwrap();
+        assert_eq!(moved.position.start_offset, 45);
+
+        let report = service.reanchor_book("book", &[chapter("A different song")]).await.unwrap();
+        assert_eq!(report.orphaned, vec![annotation.id]);
+    }
+
+    #[tokio::test]
+    async fn test_search_annotations_groups_matches_by_book() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE books (id TEXT PRIMARY KEY, title TEXT)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO books (id, title) VALUES ('physics', 'Order out of Chaos'), ('novel', 'Stoner')")
+            .execute(&pool).await.unwrap();
+        let service = AnnotationService::new(pool);
+        service.init_tables().await.unwrap();
+
+        let highlight = |book: &str, page: u32, text: &str, note: Option<&str>| {
+            service.create_annotation(
+                book.to_string(),
+                page,
+                text.to_string(),
+                position(),
+                AnnotationType::Highlight,
+                HighlightColor::Yellow,
+                note.map(str::to_string),
+            )
+        };
+        let mut entropy = highlight("physics", 12, "Entropy always increases in an isolated system", None).await.unwrap();
+        highlight("physics", 80, "The arrow of time", Some("Ties back to entropy")).await.unwrap();
+        highlight("novel", 5, "He thought of the entropy of his days", None).await.unwrap();
+        highlight("novel", 9, "Nothing about physics here", None).await.unwrap();
+
+        let results = service.search_annotations("that highlight about \"entropy\"", &AnnotationFilter::default()).await.unwrap();
+        assert!(results.is_empty(), "every word has to match");
+
+        let results = service.search_annotations("entropy", &AnnotationFilter::default()).await.unwrap();
+        assert_eq!(results.len(), 2);
+        let physics = results.iter().find(|group| group.book_id == "physics").unwrap();
+        assert_eq!(physics.book_title.as_deref(), Some("Order out of Chaos"));
+        assert_eq!(physics.matches.len(), 2);
+        assert!(physics.matches.iter().any(|found| found.annotation.page_number == 80
+            && found.snippet.contains("**entropy**")));
+
+        // Edits and deletions are reflected in the index
+        entropy.selected_text = "Disorder always increases".to_string();
+        service.update_annotation(&entropy).await.unwrap();
+        let filter = AnnotationFilter { book_id: Some("physics".to_string()), ..AnnotationFilter::default() };
+        let results = service.search_annotations("entrop", &filter).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].matches.len(), 1);
+        assert_eq!(results[0].matches[0].annotation.page_number, 80);
+
+        service.delete_annotation(&results[0].matches[0].annotation.id).await.unwrap();
+        assert!(service.search_annotations("entropy", &filter).await.unwrap().is_empty());
+        assert_eq!(service.search_annotations("disorder", &filter).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_links_between_books_and_backlinks() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE books (id TEXT PRIMARY KEY, title TEXT)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO books (id, title) VALUES ('physics', 'Order out of Chaos'), ('novel', 'Stoner')")
+            .execute(&pool).await.unwrap();
+        let service = AnnotationService::new(pool);
+        service.init_tables().await.unwrap();
+
+        let highlight = |book: &str, page: u32, text: &str| {
+            service.create_annotation(
+                book.to_string(),
+                page,
+                text.to_string(),
+                position(),
+                AnnotationType::Highlight,
+                HighlightColor::Yellow,
+                None,
+            )
+        };
+        let entropy = highlight("physics", 12, "Entropy always increases").await.unwrap();
+        let days = highlight("novel", 5, "The entropy of his days").await.unwrap();
+        let unrelated = highlight("novel", 9, "Nothing about physics here").await.unwrap();
+
+        service.link_annotations(&days.id, &entropy.id).await.unwrap();
+        service.link_annotations(&days.id, &entropy.id).await.unwrap();
+        assert!(service.link_annotations(&days.id, &days.id).await.is_err());
+        assert!(service.link_annotations(&days.id, "missing").await.is_err());
+
+        let linked = service.get_linked_annotations(&days.id).await.unwrap();
+        assert_eq!(linked.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), [entropy.id.as_str()]);
+        let backlinks = service.get_backlinks(&entropy.id).await.unwrap();
+        assert_eq!(backlinks.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), [days.id.as_str()]);
+
+        let files = service.export_linked_notes().await.unwrap();
+        let names: Vec<&str> = files.iter().map(|file| file.file_name.as_str()).collect();
+        assert_eq!(names, ["Order out of Chaos.md", "Stoner.md"]);
+        assert!(files[1].content.contains(&format!("[[Order out of Chaos#^{}|", entropy.id)));
+        assert!(!files[1].content.contains(&unrelated.selected_text));
+
+        service.delete_annotation(&entropy.id).await.unwrap();
+        assert!(service.get_annotation(&days.id).await.unwrap().unwrap().cross_references.is_empty());
+        assert!(service.export_linked_notes().await.unwrap().is_empty());
+
+        service.link_annotations(&days.id, &unrelated.id).await.unwrap();
+        service.unlink_annotations(&days.id, &unrelated.id).await.unwrap();
+        assert!(service.get_backlinks(&unrelated.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_area_annotations_are_stored_with_text_annotations() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE books (id TEXT PRIMARY KEY)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO books (id) VALUES ('comic')").execute(&pool).await.unwrap();
+        let service = AnnotationService::new(pool);
+        service.init_tables().await.unwrap();
+
+        // Dragged from the bottom right corner, partly off a 800x1200 page
+        let region = PageRegion::from_pixels((600.0, 1300.0), (200.0, 600.0), 800.0, 1200.0).unwrap();
+        assert_eq!(region, PageRegion { x: 0.25, y: 0.5, width: 0.5, height: 0.5 });
+        assert_eq!(region.to_pixels(400.0, 600.0), (100.0, 300.0, 200.0, 300.0));
+        assert!(region.contains(0.5, 0.75) && !region.contains(0.1, 0.75));
+        assert!(PageRegion::from_pixels((10.0, 10.0), (10.0, 90.0), 800.0, 1200.0).is_none());
+
+        let panel = service.create_area_annotation(
+            "comic".to_string(),
+            4,
+            region,
+            AnnotationType::Important,
+            HighlightColor::Blue,
+            Some("The reveal".to_string()),
+        ).await.unwrap();
+        service.create_annotation(
+            "comic".to_string(),
+            4,
+            "Speech balloon text".to_string(),
+            position(),
+            AnnotationType::Highlight,
+            HighlightColor::Yellow,
+            None,
+        ).await.unwrap();
+
+        let areas = service.get_area_annotations_for_page("comic", 4).await.unwrap();
+        assert_eq!(areas.len(), 1);
+        assert_eq!(areas[0].region, Some(region));
+        assert_eq!(service.get_annotations_for_book("comic").await.unwrap().len(), 2);
+        assert!(service.get_area_annotations_for_page("comic", 5).await.unwrap().is_empty());
+
+        // Areas survive re-anchoring and the export round trip
+        let report = service.reanchor_book("comic", &[]).await.unwrap();
+        assert!(report.orphaned.iter().all(|id| *id != panel.id));
+        let options = ExportOptions { format: ExportFormat::Markdown, ..ExportOptions::default() };
+        let markdown = service.export_annotations("comic", &options).await.unwrap();
+        assert!(markdown.contains("> Area at 25% across, 50% down (50% × 50% of the page)"));
+        let exported = AnnotationExporter::export_annotations(&[panel.clone()], &[], &ExportOptions::default(), None).unwrap();
+        let entry = &serde_json::from_str::<Value>(&exported).unwrap()["annotations"][0];
+        assert_eq!(AnnotationService::annotation_from_export(entry).unwrap().region, Some(region));
+    }
+}
@@ -0,0 +1,250 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::models::preferences::BackupSchedule;
+
+/// How often the schedule is checked for a backup that is due
+const CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+const BACKUP_PREFIX: &str = "library_backup_";
+
+/// A snapshot of the library database
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackupInfo {
+    pub path: PathBuf,
+    pub created: DateTime<Utc>,
+    pub size_bytes: u64,
+}
+
+/// Backs up the library database on a schedule and restores it from a backup
+///
+/// Backups are taken with `VACUUM INTO`, SQLite's online snapshot, so they are
+/// consistent even while the app is writing. Each one passes
+/// `PRAGMA integrity_check` before it replaces an older backup.
+pub struct BackupService {
+    database_path: PathBuf,
+    backup_directory: PathBuf,
+    schedule: Arc<RwLock<BackupSchedule>>,
+}
+
+impl BackupService {
+    pub fn new(database_path: PathBuf, backup_directory: PathBuf) -> Self {
+        Self {
+            database_path,
+            backup_directory,
+            schedule: Arc::new(RwLock::new(BackupSchedule::default())),
+        }
+    }
+
+    /// Back up a database into the `backups` folder beside it
+    pub fn for_database(database_path: PathBuf) -> Self {
+        let backup_directory = database_path.parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("backups");
+        Self::new(database_path, backup_directory)
+    }
+
+    /// Set how often backups are taken and how many are kept
+    pub async fn set_schedule(&self, schedule: BackupSchedule) {
+        let mut current = self.schedule.write().await;
+        *current = schedule;
+    }
+
+    /// Get how often backups are taken and how many are kept
+    pub async fn get_schedule(&self) -> BackupSchedule {
+        self.schedule.read().await.clone()
+    }
+
+    /// Take backups whenever the schedule says one is due, until the task is dropped
+    pub async fn run(&self) {
+        let mut checks = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            checks.tick().await;
+            match self.backup_if_due().await {
+                Ok(Some(backup)) => info!("Database backed up to: {}", backup.path.display()),
+                Ok(None) => {}
+                Err(e) => warn!("Scheduled database backup failed: {}", e),
+            }
+        }
+    }
+
+    /// Back up the database if the newest backup is older than the schedule's interval
+    pub async fn backup_if_due(&self) -> Result<Option<BackupInfo>> {
+        let schedule = self.get_schedule().await;
+        if !schedule.enabled {
+            return Ok(None);
+        }
+
+        if let Some(latest) = self.list_backups()?.last() {
+            if Utc::now() - latest.created < chrono::Duration::hours(schedule.interval_hours as i64) {
+                return Ok(None);
+            }
+        }
+
+        self.backup_now().await.map(Some)
+    }
+
+    /// Back up the database now, then delete backups beyond the number kept
+    pub async fn backup_now(&self) -> Result<BackupInfo> {
+        std::fs::create_dir_all(&self.backup_directory)?;
+        let name = format!("{}{}.db", BACKUP_PREFIX, Utc::now().format("%Y%m%d_%H%M%S_%3f"));
+        let backup_path = self.backup_directory.join(&name);
+        // Not listed as a backup until it has been verified
+        let partial_path = self.backup_directory.join(format!("{}.partial", name));
+        let _ = std::fs::remove_file(&partial_path);
+
+        let pool = connect_existing(&self.database_path).await?;
+        let result = sqlx::query("VACUUM INTO ?")
+            .bind(partial_path.to_string_lossy().to_string())
+            .execute(&pool)
+            .await;
+        pool.close().await;
+        result.map_err(|e| anyhow!("Failed to back up the database: {}", e))?;
+
+        if !Self::verify_backup(&partial_path).await? {
+            let _ = std::fs::remove_file(&partial_path);
+            return Err(anyhow!("The new backup failed its integrity check"));
+        }
+        std::fs::rename(&partial_path, &backup_path)?;
+
+        let keep = self.get_schedule().await.keep_backups.max(1);
+        let backups = self.list_backups()?;
+        for old_backup in backups.iter().rev().skip(keep) {
+            if let Err(e) = std::fs::remove_file(&old_backup.path) {
+                warn!("Failed to delete old backup {}: {}", old_backup.path.display(), e);
+            }
+        }
+
+        Self::backup_info(&backup_path)
+    }
+
+    /// Backups in the backup directory, oldest first
+    pub fn list_backups(&self) -> Result<Vec<BackupInfo>> {
+        if !self.backup_directory.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&self.backup_directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(BACKUP_PREFIX) && name.ends_with(".db"))
+            })
+            .collect();
+        // Timestamped names sort chronologically
+        paths.sort();
+        paths.iter().map(|path| Self::backup_info(path)).collect()
+    }
+
+    /// Run SQLite's full integrity check on a backup
+    pub async fn verify_backup(path: &Path) -> Result<bool> {
+        let pool = connect_existing(path).await?;
+        let result = sqlx::query("PRAGMA integrity_check").fetch_all(&pool).await;
+        pool.close().await;
+
+        let rows = result.map_err(|e| anyhow!("Integrity check failed: {}", e))?;
+        Ok(rows.len() == 1 && rows[0].get::<String, _>(0) == "ok")
+    }
+
+    /// Replace the database with a backup, keeping the current database beside the backups
+    ///
+    /// The database must not be open while it is replaced. Returns where the
+    /// replaced database was kept, if there was one.
+    pub async fn restore_backup(&self, backup_path: &Path) -> Result<Option<PathBuf>> {
+        if !Self::verify_backup(backup_path).await? {
+            return Err(anyhow!("Backup {} failed its integrity check", backup_path.display()));
+        }
+
+        // Copied next to the database first, so the swap is a rename
+        let restoring_path = self.database_path.with_extension("restoring");
+        std::fs::copy(backup_path, &restoring_path)?;
+
+        let mut replaced = None;
+        if self.database_path.exists() {
+            std::fs::create_dir_all(&self.backup_directory)?;
+            let kept_path = self.backup_directory
+                .join(format!("library_before_restore_{}.db", Utc::now().format("%Y%m%d_%H%M%S")));
+            std::fs::rename(&self.database_path, &kept_path)?;
+            replaced = Some(kept_path);
+        }
+        for suffix in ["-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", self.database_path.display(), suffix));
+        }
+        std::fs::rename(&restoring_path, &self.database_path)?;
+
+        info!("Database restored from backup: {}", backup_path.display());
+        Ok(replaced)
+    }
+
+    fn backup_info(path: &Path) -> Result<BackupInfo> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(BackupInfo {
+            path: path.to_path_buf(),
+            created: DateTime::<Utc>::from(metadata.modified()?),
+            size_bytes: metadata.len(),
+        })
+    }
+}
+
+/// Open a database file that must already exist
+pub(crate) async fn connect_existing(path: &Path) -> Result<SqlitePool> {
+    let database_url = format!("sqlite://{}?mode=rw", path.display());
+    SqlitePool::connect(&database_url).await
+        .map_err(|e| anyhow!("Failed to open {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn count_books(path: &Path) -> i64 {
+        let pool = connect_existing(path).await.unwrap();
+        let count = sqlx::query_scalar("SELECT COUNT(*) FROM books").fetch_one(&pool).await.unwrap();
+        pool.close().await;
+        count
+    }
+
+    #[tokio::test]
+    async fn test_backups_rotate_and_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("library.db");
+        let pool = SqlitePool::connect(&format!("sqlite://{}?mode=rwc", db_path.display())).await.unwrap();
+        sqlx::query("CREATE TABLE books (title TEXT)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO books VALUES ('Dune')").execute(&pool).await.unwrap();
+
+        let service = BackupService::new(db_path.clone(), temp_dir.path().join("backups"));
+        service.set_schedule(BackupSchedule { enabled: true, interval_hours: 24, keep_backups: 2 }).await;
+        let first = service.backup_if_due().await.unwrap().unwrap();
+        // The newest backup is recent, so none is due
+        assert!(service.backup_if_due().await.unwrap().is_none());
+
+        sqlx::query("INSERT INTO books VALUES ('Emma')").execute(&pool).await.unwrap();
+        for _ in 0..2 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            service.backup_now().await.unwrap();
+        }
+        pool.close().await;
+
+        let backups = service.list_backups().unwrap();
+        assert_eq!(backups.len(), 2);
+        assert!(!backups.iter().any(|backup| backup.path == first.path));
+        assert!(BackupService::verify_backup(&backups[0].path).await.unwrap());
+
+        std::fs::write(&db_path, b"damaged").unwrap();
+        let replaced = service.restore_backup(&backups[1].path).await.unwrap().unwrap();
+        assert_eq!(std::fs::read(replaced).unwrap(), b"damaged");
+        assert_eq!(count_books(&db_path).await, 2);
+
+        // A damaged backup is never restored
+        std::fs::write(&backups[0].path, b"not a database").unwrap();
+        assert!(service.restore_backup(&backups[0].path).await.is_err());
+        assert_eq!(count_books(&db_path).await, 2);
+    }
+}
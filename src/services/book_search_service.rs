@@ -1,11 +1,20 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 use chrono::{DateTime, Utc};
 
 use crate::models::book::Book;
+use crate::services::book_service::{BookFilter, BookSort};
+use crate::services::database::DatabaseService;
+use crate::services::reading_service;
+use crate::utils::text_search::TextSearch;
+
+/// Approximate characters per page, for page numbers of search results
+const CHARS_PER_PAGE: usize = 2000;
 
 /// Search result within a book
 #[derive(Debug, Clone)]
@@ -19,6 +28,50 @@ pub struct SearchResult {
     pub chapter_title: String,
     pub chapter_id: Option<String>,
     pub relevance_score: f32,
+    pub snippet: SearchSnippet,
+    pub jump_target: SearchJumpTarget,
+}
+
+/// Snippet of words around a match
+#[derive(Debug, Clone)]
+pub struct SearchSnippet {
+    pub text: String,
+    pub match_start: usize, // Byte offsets of the match within `text`
+    pub match_end: usize,
+}
+
+impl SearchSnippet {
+    /// Render the snippet as HTML with the match wrapped in `<mark>`
+    pub fn to_highlighted_html(&self) -> String {
+        format!(
+            "{}<mark>{}</mark>{}",
+            html_escape::encode_text(&self.text[..self.match_start]),
+            html_escape::encode_text(&self.text[self.match_start..self.match_end]),
+            html_escape::encode_text(&self.text[self.match_end..])
+        )
+    }
+}
+
+/// Location to navigate to when a search result is opened
+#[derive(Debug, Clone)]
+pub struct SearchJumpTarget {
+    pub chapter_id: String,
+    pub chapter_index: usize,
+    pub char_offset: usize, // Character offset into the chapter's plain text
+    pub char_end: usize,    // Character offset just past the match, for highlighting
+    pub text_location: String, // "chapter_id:char_offset", a plain-text position rather than an EPUB CFI
+}
+
+impl SearchJumpTarget {
+    fn new(chapter_id: &str, chapter_index: usize, char_offset: usize, char_end: usize) -> Self {
+        Self {
+            chapter_id: chapter_id.to_string(),
+            chapter_index,
+            char_offset,
+            char_end,
+            text_location: format!("{}:{}", chapter_id, char_offset),
+        }
+    }
 }
 
 /// Search options
@@ -28,6 +81,7 @@ pub struct SearchOptions {
     pub whole_words: bool,
     pub regex_mode: bool,
     pub context_length: usize,
+    pub context_words: usize, // Words on each side of a match in the snippet
     pub max_results: usize,
 }
 
@@ -38,6 +92,7 @@ impl Default for SearchOptions {
             whole_words: false,
             regex_mode: false,
             context_length: 50,
+            context_words: 10,
             max_results: 100,
         }
     }
@@ -54,16 +109,17 @@ pub struct SearchStats {
 }
 
 /// Book content representation for search
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BookContent {
     pub book_id: String,
+    pub language: Option<String>,
     pub chapters: Vec<Chapter>,
     pub total_pages: u32,
     pub last_indexed: DateTime<Utc>,
 }
 
 /// Chapter content
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Chapter {
     pub id: String,
     pub title: String,
@@ -88,7 +144,9 @@ pub struct SearchIndex {
 pub struct WordPosition {
     pub chapter_id: String,
     pub page_number: u32,
-    pub position: usize,
+    pub position: usize,    // Byte span of the word in the chapter text
+    pub end: usize,
+    pub token_index: usize, // Order of the word within its chapter, for phrase matching
     pub word: String,
 }
 
@@ -97,6 +155,7 @@ pub struct BookSearchService {
     content_cache: Arc<RwLock<HashMap<String, BookContent>>>,
     search_indices: Arc<RwLock<HashMap<String, SearchIndex>>>,
     search_history: Arc<RwLock<Vec<SearchStats>>>,
+    content_dir: Option<PathBuf>, // Where book text is kept between sessions
 }
 
 impl BookSearchService {
@@ -105,10 +164,27 @@ impl BookSearchService {
             content_cache: Arc::new(RwLock::new(HashMap::new())),
             search_indices: Arc::new(RwLock::new(HashMap::new())),
             search_history: Arc::new(RwLock::new(Vec::new())),
+            content_dir: None,
+        }
+    }
+
+    /// Create a search service that keeps indexed book text in a directory
+    ///
+    /// Stored books can be searched in later sessions without reopening them;
+    /// their term index is rebuilt from the text on first search.
+    pub fn with_content_dir(content_dir: PathBuf) -> Self {
+        Self {
+            content_dir: Some(content_dir),
+            ..Self::new()
         }
     }
 
-    /// Search within a book
+    /// Search all chapter text of a book
+    ///
+    /// Plain case-insensitive queries use the book's prebuilt index (see
+    /// `index_book`) and match whole, stemmed words as a phrase, in reading
+    /// order. Queries the index can't answer, such as partial words, fall back
+    /// to scanning the chapter text.
     pub async fn search_in_book(
         &self,
         book_id: &str,
@@ -119,9 +195,25 @@ impl BookSearchService {
         
         // Get book content
         let book_content = self.get_book_content(book_id).await?;
+
+        // Stored books get their term index on first search
+        if !self.search_indices.read().await.contains_key(book_id) {
+            self.build_search_index(book_id).await?;
+        }
         
         // Perform search
-        let results = if options.regex_mode {
+        let indexed_results = if options.regex_mode || options.case_sensitive {
+            Vec::new()
+        } else {
+            let indices = self.search_indices.read().await;
+            match indices.get(book_id) {
+                Some(index) => self.indexed_search(index, &book_content, query, options)?,
+                None => Vec::new(),
+            }
+        };
+        let results = if !indexed_results.is_empty() {
+            indexed_results
+        } else if options.regex_mode {
             self.regex_search(&book_content, query, options).await?
         } else {
             self.text_search(&book_content, query, options).await?
@@ -144,6 +236,59 @@ impl BookSearchService {
         Ok(results)
     }
 
+    /// Match the query's terms as a phrase using the book's index
+    fn indexed_search(
+        &self,
+        index: &SearchIndex,
+        book_content: &BookContent,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchResult>> {
+        let terms = Self::text_search_for(book_content).tokenize(query);
+        let Some((first, rest)) = terms.split_first() else {
+            return Ok(Vec::new());
+        };
+        let Some(starts) = index.word_positions.get(&first.text) else {
+            return Ok(Vec::new());
+        };
+
+        // Where each following term occurs, by chapter and word order
+        let mut following = Vec::new();
+        for term in rest {
+            let Some(positions) = index.word_positions.get(&term.text) else {
+                return Ok(Vec::new());
+            };
+            let ends: HashMap<(&str, usize), usize> = positions.iter()
+                .map(|position| ((position.chapter_id.as_str(), position.token_index), position.end))
+                .collect();
+            following.push(ends);
+        }
+
+        let chapter_indices: HashMap<&str, usize> = book_content.chapters.iter()
+            .enumerate()
+            .map(|(chapter_index, chapter)| (chapter.id.as_str(), chapter_index))
+            .collect();
+
+        let mut matches: Vec<(usize, usize, usize)> = starts.iter()
+            .filter_map(|start| {
+                let mut end = start.end;
+                for (offset, ends) in following.iter().enumerate() {
+                    end = *ends.get(&(start.chapter_id.as_str(), start.token_index + offset + 1))?;
+                }
+                Some((*chapter_indices.get(start.chapter_id.as_str())?, start.position, end))
+            })
+            .collect();
+        matches.sort_unstable();
+        matches.truncate(options.max_results);
+
+        matches.into_iter()
+            .map(|(chapter_index, start, end)| {
+                let chapter = &book_content.chapters[chapter_index];
+                self.create_search_result(chapter, chapter_index, start, &chapter.content[start..end], options)
+            })
+            .collect()
+    }
+
     /// Text-based search
     async fn text_search(
         &self,
@@ -158,7 +303,7 @@ impl BookSearchService {
             query.to_lowercase()
         };
 
-        for chapter in &book_content.chapters {
+        for (chapter_index, chapter) in book_content.chapters.iter().enumerate() {
             let chapter_content = if options.case_sensitive {
                 chapter.content.clone()
             } else {
@@ -178,9 +323,10 @@ impl BookSearchService {
 
                 let result = self.create_search_result(
                     chapter,
+                    chapter_index,
                     match_pos,
                     query,
-                    options.context_length,
+                    options,
                 )?;
 
                 results.push(result);
@@ -215,7 +361,7 @@ impl BookSearchService {
         let full_pattern = format!("{}{}", regex_flags, pattern);
         let regex = Regex::new(&full_pattern)?;
 
-        for chapter in &book_content.chapters {
+        for (chapter_index, chapter) in book_content.chapters.iter().enumerate() {
             let matches: Vec<_> = regex.find_iter(&chapter.content).collect();
 
             for regex_match in matches {
@@ -225,9 +371,10 @@ impl BookSearchService {
 
                 let result = self.create_search_result(
                     chapter,
+                    chapter_index,
                     regex_match.start(),
                     regex_match.as_str(),
-                    options.context_length,
+                    options,
                 )?;
 
                 results.push(result);
@@ -278,12 +425,14 @@ impl BookSearchService {
     fn create_search_result(
         &self,
         chapter: &Chapter,
+        chapter_index: usize,
         position: usize,
         match_text: &str,
-        context_length: usize,
+        options: &SearchOptions,
     ) -> Result<SearchResult> {
         let content = &chapter.content;
         let match_len = match_text.len();
+        let context_length = options.context_length;
 
         // Calculate context boundaries
         let before_start = if position >= context_length {
@@ -304,7 +453,7 @@ impl BookSearchService {
         let full_context = content[before_start..after_end].trim().to_string();
 
         // Calculate page number (simplified - would need proper page calculation)
-        let page_number = self.calculate_page_number(chapter, position);
+        let page_number = Self::calculate_page_number(chapter, position);
 
         // Calculate relevance score
         let relevance_score = self.calculate_relevance_score(match_text, &full_context);
@@ -319,14 +468,57 @@ impl BookSearchService {
             chapter_title: chapter.title.clone(),
             chapter_id: Some(chapter.id.clone()),
             relevance_score,
+            snippet: Self::word_snippet(content, position, match_len, options.context_words),
+            jump_target: SearchJumpTarget::new(
+                &chapter.id,
+                chapter_index,
+                content[..position].chars().count(),
+                content[..position + match_len].chars().count(),
+            ),
         })
     }
 
+    /// Build a snippet of `context_words` words on each side of a match
+    fn word_snippet(content: &str, position: usize, match_len: usize, context_words: usize) -> SearchSnippet {
+        let match_end = position + match_len;
+        let before = &content[..position];
+        let after = &content[match_end..];
+
+        let word_starts: Vec<usize> = before
+            .char_indices()
+            .filter(|&(i, c)| !c.is_whitespace() && before[..i].chars().next_back().map_or(true, char::is_whitespace))
+            .map(|(i, _)| i)
+            .collect();
+        let start = if context_words == 0 {
+            position
+        } else {
+            word_starts
+                .len()
+                .checked_sub(context_words)
+                .map_or(0, |first| word_starts[first])
+        };
+
+        let end = if context_words == 0 {
+            match_end
+        } else {
+            after
+                .char_indices()
+                .filter(|&(i, c)| c.is_whitespace() && after[..i].chars().next_back().map_or(false, |p| !p.is_whitespace()))
+                .nth(context_words - 1)
+                .map_or(content.len(), |(i, _)| match_end + i)
+        };
+
+        SearchSnippet {
+            text: content[start..end].to_string(),
+            match_start: position - start,
+            match_end: match_end - start,
+        }
+    }
+
     /// Calculate page number from chapter and position
-    fn calculate_page_number(&self, chapter: &Chapter, position: usize) -> u32 {
+    fn calculate_page_number(chapter: &Chapter, position: usize) -> u32 {
         // Simplified calculation - in a real implementation, this would use proper page layout
-        let chars_per_page = 2000; // Approximate characters per page
-        let page_offset = position / chars_per_page;
+        let page_offset = position / CHARS_PER_PAGE;
         chapter.page_start + page_offset as u32
     }
 
@@ -374,59 +566,133 @@ impl BookSearchService {
 
     /// Load book content from storage
     async fn load_book_content(&self, book_id: &str) -> Result<BookContent> {
-        // This would integrate with your existing book loading system
-        // For now, return a placeholder
-        Ok(BookContent {
-            book_id: book_id.to_string(),
-            chapters: vec![
-                Chapter {
-                    id: "chapter1".to_string(),
-                    title: "Chapter 1".to_string(),
-                    content: "Sample chapter content for searching...".to_string(),
-                    page_start: 1,
-                    page_end: 10,
-                    word_count: 1000,
-                }
-            ],
-            total_pages: 100,
+        let Some(path) = self.content_path(book_id) else {
+            return Err(anyhow!("Book {} has not been indexed for search", book_id));
+        };
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(anyhow!("Book {} has not been indexed for search", book_id))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// File holding a book's stored text, when a content directory is set
+    fn content_path(&self, book_id: &str) -> Option<PathBuf> {
+        self.content_dir.as_ref().map(|dir| dir.join(format!("{}.json", book_id)))
+    }
+
+    /// Check whether a book's text is available for searching
+    pub async fn has_book_content(&self, book_id: &str) -> bool {
+        if self.content_cache.read().await.contains_key(book_id) {
+            return true;
+        }
+        match self.content_path(book_id) {
+            Some(path) => tokio::fs::try_exists(path).await.unwrap_or(false),
+            None => false,
+        }
+    }
+
+    /// Take an opened book's chapter text and build its search index
+    pub async fn index_book(&self, book: &Book, content: &reading_service::BookContent) -> Result<()> {
+        self.store_book_content(book, content).await?;
+        self.build_search_index(&book.id).await
+    }
+
+    /// Keep a book's chapter text for searching without building its term index yet
+    ///
+    /// With a content directory the text goes to disk only, so indexing a whole
+    /// library doesn't hold every book in memory.
+    pub async fn store_book_content(&self, book: &Book, content: &reading_service::BookContent) -> Result<()> {
+        let mut page_start = 1;
+        let chapters = content.chapters.iter()
+            .map(|chapter| {
+                let pages = (chapter.content.len() / CHARS_PER_PAGE) as u32;
+                let indexed = Chapter {
+                    id: chapter.id.clone(),
+                    title: chapter.title.clone(),
+                    content: chapter.content.clone(),
+                    page_start,
+                    page_end: page_start + pages,
+                    word_count: chapter.word_count,
+                };
+                page_start += pages + 1;
+                indexed
+            })
+            .collect();
+
+        let book_content = BookContent {
+            book_id: book.id.clone(),
+            language: book.language.clone(),
+            chapters,
+            total_pages: page_start - 1,
             last_indexed: Utc::now(),
-        })
+        };
+
+        // Text changed, so any term index built from the old text is stale
+        self.search_indices.write().await.remove(&book.id);
+        match self.content_path(&book.id) {
+            Some(path) => {
+                if let Some(dir) = path.parent() {
+                    tokio::fs::create_dir_all(dir).await?;
+                }
+                tokio::fs::write(&path, serde_json::to_vec(&book_content)?).await?;
+                self.content_cache.write().await.remove(&book.id);
+            }
+            None => {
+                self.content_cache.write().await.insert(book.id.clone(), book_content);
+            }
+        }
+
+        Ok(())
     }
 
     /// Build search index for a book
     pub async fn build_search_index(&self, book_id: &str) -> Result<()> {
         let book_content = self.get_book_content(book_id).await?;
+
+        // Tokenizing a whole book is CPU-bound, keep it off the executor
+        let index = tokio::task::spawn_blocking(move || Self::index_content(&book_content)).await?;
+
+        let mut indices = self.search_indices.write().await;
+        indices.insert(book_id.to_string(), index);
+
+        Ok(())
+    }
+
+    /// Tokenizer and stemmer for a book's language
+    fn text_search_for(book_content: &BookContent) -> TextSearch {
+        let sample_text = book_content.chapters.first().map_or("", |chapter| chapter.content.as_str());
+        TextSearch::for_language(book_content.language.as_deref(), sample_text)
+    }
+
+    /// Record where every term of a book occurs
+    fn index_content(book_content: &BookContent) -> SearchIndex {
         let mut word_positions = HashMap::new();
         let mut chapter_titles = HashMap::new();
         let mut page_content = HashMap::new();
 
+        // Tokenizer and stemmer follow the book's language
+        let text_search = Self::text_search_for(book_content);
+
         for chapter in &book_content.chapters {
             chapter_titles.insert(chapter.id.clone(), chapter.title.clone());
 
-            // Extract words and their positions
-            let words: Vec<&str> = chapter.content.split_whitespace().collect();
-            let mut current_pos = 0;
-
-            for word in words {
-                let clean_word = word.to_lowercase()
-                    .chars()
-                    .filter(|c| c.is_alphanumeric())
-                    .collect::<String>();
-
-                if !clean_word.is_empty() {
-                    let position = WordPosition {
-                        chapter_id: chapter.id.clone(),
-                        page_number: self.calculate_page_number(chapter, current_pos),
-                        position: current_pos,
-                        word: clean_word.clone(),
-                    };
-
-                    word_positions.entry(clean_word)
-                        .or_insert_with(Vec::new)
-                        .push(position);
-                }
+            // Extract terms and their positions
+            for (token_index, token) in text_search.tokenize(&chapter.content).into_iter().enumerate() {
+                let position = WordPosition {
+                    chapter_id: chapter.id.clone(),
+                    page_number: Self::calculate_page_number(chapter, token.start),
+                    position: token.start,
+                    end: token.end,
+                    token_index,
+                    word: token.text.clone(),
+                };
 
-                current_pos += word.len() + 1;
+                word_positions.entry(token.text)
+                    .or_insert_with(Vec::new)
+                    .push(position);
             }
 
             // Index page content
@@ -435,18 +701,13 @@ impl BookSearchService {
             }
         }
 
-        let index = SearchIndex {
-            book_id: book_id.to_string(),
+        SearchIndex {
+            book_id: book_content.book_id.clone(),
             word_positions,
             chapter_titles,
             page_content,
             created_at: Utc::now(),
-        };
-
-        let mut indices = self.search_indices.write().await;
-        indices.insert(book_id.to_string(), index);
-
-        Ok(())
+        }
     }
 
     /// Get search suggestions
@@ -546,6 +807,26 @@ impl BookSearchService {
         Ok(results)
     }
 
+    /// Search the content of library books matching a filter
+    ///
+    /// The filter is resolved in the database first, so only matching books are scanned.
+    pub async fn search_library(
+        &self,
+        database: &DatabaseService,
+        filter: &BookFilter,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<HashMap<String, Vec<SearchResult>>> {
+        let book_ids: Vec<String> = database
+            .get_filtered_books(filter, &BookSort::default(), None, None)
+            .await?
+            .into_iter()
+            .map(|book| book.id)
+            .collect();
+
+        self.search_across_books(&book_ids, query, options).await
+    }
+
     /// Get search result context with highlighting
     pub async fn get_highlighted_context(
         &self,
@@ -595,4 +876,84 @@ impl Default for BookSearchService {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_result_snippet_and_jump_target() {
+        let service = BookSearchService::new();
+        let chapter = Chapter {
+            id: "ch2".to_string(),
+            title: "Chapter 2".to_string(),
+            content: "one two three four <target> five six seven".to_string(),
+            page_start: 1,
+            page_end: 1,
+            word_count: 8,
+        };
+        let options = SearchOptions {
+            context_words: 2,
+            ..SearchOptions::default()
+        };
+        let position = chapter.content.find("<target>").unwrap();
+
+        let result = service.create_search_result(&chapter, 1, position, "<target>", &options).unwrap();
+
+        assert_eq!(result.snippet.text, "three four <target> five six");
+        assert_eq!(&result.snippet.text[result.snippet.match_start..result.snippet.match_end], "<target>");
+        assert_eq!(result.snippet.to_highlighted_html(), "three four <mark>&lt;target&gt;</mark> five six");
+        assert_eq!(result.jump_target.char_offset, position);
+        assert_eq!(result.jump_target.text_location, "ch2:19");
+    }
+
+    #[tokio::test]
+    async fn test_search_in_book_uses_chapter_index() {
+        use crate::models::BookFormat;
+
+        let chapter = |id: &str, order: usize, text: &str| reading_service::Chapter {
+            id: id.to_string(),
+            title: id.to_string(),
+            content: text.to_string(),
+            word_count: text.split_whitespace().count(),
+            order,
+            media_overlay: None,
+            blocks: Vec::new(),
+        };
+        let mut book = Book::new("Voyage".to_string(), "Author".to_string(), "voyage.epub".into(), 0, BookFormat::Epub);
+        book.language = Some("en".to_string());
+        let content = reading_service::BookContent {
+            book_id: book.id.clone(),
+            title: book.title.clone(),
+            author: book.author.clone(),
+            chapters: vec![
+                chapter("ch1", 0, "Café by the harbour. The ships sailed on the open sea."),
+                chapter("ch2", 1, "Ships sail, and the sea ship waits."),
+            ],
+            total_word_count: 17,
+            estimated_reading_time: 1,
+        };
+
+        let service = BookSearchService::new();
+        assert!(service.search_in_book(&book.id, "sea", &SearchOptions::default()).await.is_err());
+        service.index_book(&book, &content).await.unwrap();
+
+        // Stemmed phrase match, in reading order
+        let results = service.search_in_book(&book.id, "ship sail", &SearchOptions::default()).await.unwrap();
+        let found: Vec<(&str, &str)> = results.iter()
+            .map(|result| (result.chapter_id.as_deref().unwrap(), result.match_text.as_str()))
+            .collect();
+        assert_eq!(found, vec![("ch1", "ships sailed"), ("ch2", "Ships sail")]);
+
+        // Offsets count characters, so the accented letter before the match counts once
+        let target = &results[0].jump_target;
+        assert_eq!((target.char_offset, target.char_end), (25, 37));
+        assert_eq!(results[0].snippet.text, "Café by the harbour. The ships sailed on the open sea.");
+
+        // Partial words are not in the index and fall back to a scan
+        let results = service.search_in_book(&book.id, "harb", &SearchOptions::default()).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].jump_target.chapter_id, "ch1");
+    }
+}
@@ -1,16 +1,45 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, RwLock, Semaphore};
+use tokio::task::JoinSet;
 use anyhow::{Result, anyhow};
-use chrono::Utc;
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
 use image::imageops::FilterType;
+use thiserror::Error;
+use tracing::warn;
 use uuid::Uuid;
 
 use crate::models::{Book, BookViewModel, BookFormat, BookCollection};
+use crate::models::book::{ReadingSession, DEFAULT_READING_SPEED_WPM, WORDS_PER_PAGE};
 use crate::models::library::ReadingStatus;
+use crate::models::preferences::{FileDeletionPolicy, SeriesAutoAdvance, DEFAULT_TRASH_RETENTION_DAYS};
+use crate::services::calibre_library::{CalibreBook, CalibreLibrary};
 use crate::services::database::DatabaseService;
+use crate::services::destructive_confirmation::{ConfirmationGuard, ConfirmationToken, DestructiveAction, DestructiveImpact};
+use crate::services::epub_metadata::EpubMetadata;
+use crate::services::epub_parser::{EpubOpenError, EpubParser};
+use crate::services::library_service::LibraryService;
+#[cfg(feature = "network")]
+use crate::services::metadata_service::{MetadataCandidate, MetadataField, MetadataService};
+use crate::services::path_resolver::PathResolver;
+use crate::services::reading_service::ReadingService;
+use crate::services::pdf_parser::PdfParser;
+use crate::services::preferences_service::PreferencesService;
 use crate::utils::image_cache::ImageCache;
+use crate::utils::isbn::find_isbns;
+
+/// Covers regenerated at once, kept low so regeneration can run while reading
+const MAX_COVER_REGENERATION_JOBS: usize = 2;
+
+/// Pages read at each end of a PDF when looking for its ISBN
+const PDF_ISBN_SEARCH_PAGES: usize = 5;
+
+/// Books parsed at once during a directory import
+const MAX_IMPORT_JOBS: usize = 4;
+
+/// File extensions picked up when importing or watching a folder
+const FOLDER_IMPORT_EXTENSIONS: &[&str] = &["epub", "mobi", "pdf"];
 
 /// Book service for managing book operations
 pub struct BookService {
@@ -18,6 +47,10 @@ pub struct BookService {
     image_cache: Arc<ImageCache>,
     book_cache: Arc<RwLock<HashMap<String, Book>>>,
     collections_cache: Arc<RwLock<HashMap<String, BookCollection>>>,
+    preferences: Arc<RwLock<Option<Arc<PreferencesService>>>>, // Source of the deletion, trash, series and reading speed settings
+    session_books: Arc<RwLock<HashMap<String, Book>>>,
+    confirmations: ConfirmationGuard,
+    library_service: Arc<RwLock<Option<Arc<LibraryService>>>>, // Told about each imported book
 }
 
 impl BookService {
@@ -28,12 +61,65 @@ impl BookService {
             image_cache,
             book_cache: Arc::new(RwLock::new(HashMap::new())),
             collections_cache: Arc::new(RwLock::new(HashMap::new())),
+            preferences: Arc::new(RwLock::new(None)),
+            session_books: Arc::new(RwLock::new(HashMap::new())),
+            confirmations: ConfirmationGuard::new(),
+            library_service: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Check imported books against smart collection subscriptions
+    pub async fn set_library_service(&self, library_service: Arc<LibraryService>) {
+        let mut current = self.library_service.write().await;
+        *current = Some(library_service);
+    }
+
+    /// Follow the user's preferences for deletion, the trash, series and reading speed
+    pub async fn set_preferences(&self, preferences: Arc<PreferencesService>) {
+        let mut current = self.preferences.write().await;
+        *current = Some(preferences);
+    }
+
+    /// Get the default policy applied to book files on deletion, from the preferences
+    ///
+    /// Files are kept when no preferences are attached.
+    pub async fn get_file_deletion_policy(&self) -> FileDeletionPolicy {
+        match self.preferences.read().await.as_ref() {
+            Some(preferences) => preferences.get().library.file_deletion_policy,
+            None => FileDeletionPolicy::KeepFile,
+        }
+    }
+
+    /// Get what happens when a book in a series is finished, from the preferences
+    pub async fn get_series_auto_advance(&self) -> SeriesAutoAdvance {
+        match self.preferences.read().await.as_ref() {
+            Some(preferences) => preferences.get().reading.series_auto_advance,
+            None => SeriesAutoAdvance::default(),
+        }
+    }
+
+    /// Get the reading speed used for reading times on book cards, from the preferences
+    pub async fn get_reading_speed_wpm(&self) -> u32 {
+        match self.preferences.read().await.as_ref() {
+            Some(preferences) => preferences.get().reading.reading_speed_wpm.max(1),
+            None => DEFAULT_READING_SPEED_WPM,
+        }
+    }
+
+    /// Get how many days deleted books stay in the trash, from the preferences
+    ///
+    /// 0 keeps them until the trash is emptied.
+    pub async fn get_trash_retention_days(&self) -> u32 {
+        match self.preferences.read().await.as_ref() {
+            Some(preferences) => preferences.get().library.trash_retention_days,
+            None => DEFAULT_TRASH_RETENTION_DAYS,
         }
     }
 
     /// Get all books in the library
     pub async fn get_library_books(&self) -> Result<Vec<BookViewModel>> {
         let books = self.database.get_all_books().await?;
+        let words_per_minute = self.get_reading_speed_wpm().await;
         let mut view_models = Vec::new();
         
         for book in books {
@@ -56,6 +142,8 @@ impl BookService {
                 rating: book.rating,
                 last_opened: book.last_opened,
                 added_date: book.added_date,
+                reading_time: book.reading_time_label(words_per_minute),
+                series: book.series_label(),
             });
         }
         
@@ -71,6 +159,7 @@ impl BookService {
         offset: Option<usize>,
     ) -> Result<Vec<BookViewModel>> {
         let books = self.database.get_filtered_books(filter, sort, limit, offset).await?;
+        let words_per_minute = self.get_reading_speed_wpm().await;
         let mut view_models = Vec::new();
         
         for book in books {
@@ -81,7 +170,7 @@ impl BookService {
                 None
             };
             
-            view_models.push(BookViewModel::from(book));
+            view_models.push(BookViewModel::with_reading_speed(book, words_per_minute));
         }
         
         Ok(view_models)
@@ -89,7 +178,17 @@ impl BookService {
 
     /// Search books by query
     pub async fn search_books(&self, query: &str) -> Result<Vec<BookViewModel>> {
-        let books = self.database.search_books(query).await?;
+        self.search_books_filtered(query, &BookFilter::default()).await
+    }
+
+    /// Search books by query, restricted to books matching the filter
+    pub async fn search_books_filtered(
+        &self,
+        query: &str,
+        filter: &BookFilter,
+    ) -> Result<Vec<BookViewModel>> {
+        let books = self.database.search_books_filtered(query, filter).await?;
+        let words_per_minute = self.get_reading_speed_wpm().await;
         let mut view_models = Vec::new();
         
         for book in books {
@@ -100,7 +199,7 @@ impl BookService {
                 None
             };
             
-            view_models.push(BookViewModel::from(book));
+            view_models.push(BookViewModel::with_reading_speed(book, words_per_minute));
         }
         
         Ok(view_models)
@@ -108,35 +207,281 @@ impl BookService {
 
     /// Add a new book to the library
     pub async fn add_book(&self, file_path: &Path) -> Result<String> {
-        // Check if book already exists
-        if self.database.book_exists_by_path(file_path).await? {
-            return Err(anyhow!("Book already exists in library"));
+        // Check if book already exists; adding a deleted book again takes it out of the trash
+        if let Some(existing) = self.database.find_book_by_path(file_path).await? {
+            if !self.database.is_book_trashed(&existing.id).await? {
+                return Err(DuplicateBookError::SamePath.into());
+            }
+            self.restore_from_trash(&existing.id).await?;
+            return Ok(existing.id);
+        }
+
+        let (book, cover_data) = self.read_book_file(file_path).await?;
+        self.insert_new_book(book, cover_data).await
+    }
+
+    /// Import every book file in a directory, parsing several at once
+    ///
+    /// A file that fails to import is recorded in the summary and does not stop
+    /// the rest. Progress is sent after each file.
+    pub async fn import_directory(
+        self: &Arc<Self>,
+        path: &Path,
+        recursive: bool,
+        progress: Option<mpsc::UnboundedSender<ImportProgress>>,
+    ) -> Result<ImportSummary> {
+        let root = path.to_path_buf();
+        let files = tokio::task::spawn_blocking(move || Self::find_book_files(&root, recursive)).await??;
+
+        let total = files.len();
+        let semaphore = Arc::new(Semaphore::new(MAX_IMPORT_JOBS));
+        let mut jobs = JoinSet::new();
+        for file_path in files {
+            let semaphore = semaphore.clone();
+            let service = self.clone();
+            jobs.spawn(async move {
+                let _permit = semaphore.acquire_owned().await?;
+                let parsed = match service.database.book_exists_by_path(&file_path).await {
+                    Ok(true) => Err(DuplicateBookError::SamePath.into()),
+                    Ok(false) => service.read_book_file(&file_path).await,
+                    Err(e) => Err(e),
+                };
+                Ok::<_, anyhow::Error>((file_path, parsed))
+            });
+        }
+
+        let mut summary = ImportSummary::default();
+        let mut completed = 0;
+        while let Some(result) = jobs.join_next().await {
+            let (file_path, parsed) = result??;
+            completed += 1;
+
+            // Books are saved one at a time so copies within the directory are caught too
+            let imported = match parsed {
+                Ok((book, cover_data)) => self.insert_new_book(book, cover_data).await,
+                Err(e) => Err(e),
+            };
+            let outcome = summary.record(&file_path, imported);
+
+            if let Some(progress) = &progress {
+                let _ = progress.send(ImportProgress {
+                    file_path,
+                    completed,
+                    total,
+                    outcome,
+                });
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Import a Calibre library from its `metadata.db`, keeping Calibre's metadata
+    ///
+    /// Titles, authors, series, tags, ratings, custom columns and covers come
+    /// from the Calibre database, so book files are not parsed. Word counts are
+    /// filled in later by the search indexer reading the files.
+    pub async fn import_calibre_library(
+        &self,
+        root: &Path,
+        progress: Option<mpsc::UnboundedSender<ImportProgress>>,
+    ) -> Result<ImportSummary> {
+        let library = CalibreLibrary::open(root).await?;
+        let entries = library.books().await?;
+
+        let total = entries.len();
+        let mut summary = ImportSummary::default();
+        for (index, entry) in entries.into_iter().enumerate() {
+            let file_path = entry.book_file().cloned().unwrap_or_else(|| entry.folder.clone());
+            let imported = self.import_calibre_book(&entry).await;
+            let outcome = summary.record(&file_path, imported);
+
+            if let Some(progress) = &progress {
+                let _ = progress.send(ImportProgress {
+                    file_path,
+                    completed: index + 1,
+                    total,
+                    outcome,
+                });
+            }
+        }
+
+        Ok(summary)
+    }
+
+    async fn import_calibre_book(&self, entry: &CalibreBook) -> Result<String> {
+        let mut book = entry.to_book()
+            .ok_or_else(|| anyhow!("No supported book file in {}", entry.folder.display()))?;
+        if self.database.book_exists_by_path(&book.file_path).await? {
+            return Err(DuplicateBookError::SamePath.into());
+        }
+        book.id = Uuid::new_v4().to_string();
+
+        let cover_data = match &entry.cover {
+            Some(cover) => tokio::fs::read(cover).await.ok(),
+            None => None,
+        };
+        let book_id = self.insert_new_book(book, cover_data).await?;
+
+        for (name, value) in &entry.custom_fields {
+            self.database.set_book_custom_field(&book_id, name, value).await?;
+        }
+        Ok(book_id)
+    }
+
+    /// Book files in a directory, optionally including subdirectories
+    pub fn find_book_files(root: &Path, recursive: bool) -> std::io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    if recursive {
+                        pending.push(path);
+                    }
+                } else if Self::is_importable_file(&path) {
+                    files.push(path);
+                }
+            }
         }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Check whether a file is picked up by folder imports
+    pub fn is_importable_file(path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| FOLDER_IMPORT_EXTENSIONS.iter().any(|known| ext.eq_ignore_ascii_case(known)))
+    }
 
-        // Parse book metadata
+    /// Parse a book file and read its cover, without touching the library
+    async fn read_book_file(&self, file_path: &Path) -> Result<(Book, Option<Vec<u8>>)> {
         let mut book = self.parse_book_metadata(file_path).await?;
+        book.id = Uuid::new_v4().to_string();
+        let cover_data = self.extract_cover_data(&book).await?;
+        Ok((book, cover_data))
+    }
+
+    /// Save a parsed book unless its ISBN is already in the library
+    async fn insert_new_book(&self, mut book: Book, cover_data: Option<Vec<u8>>) -> Result<String> {
+        self.check_duplicate_isbn(&book).await?;
+
+        // Cache the cover, or draw a placeholder so the grid has a thumbnail
+        let cover_path = match cover_data {
+            Some(cover_data) => self.image_cache.save_cover(&book.id, &cover_data).await?,
+            None => self.image_cache.create_placeholder(&book.id, &book.title, &book.author).await?,
+        };
+        book.cover_path = Some(cover_path);
+
+        // Save to database
+        self.database.insert_book(&book).await?;
         
-        // Generate unique ID
+        // Update cache
+        self.book_cache.write().await.insert(book.id.clone(), book.clone());
+
+        let library_service = self.library_service.read().await.clone();
+        if let Some(library_service) = library_service {
+            if let Err(e) = library_service.notify_book_imported(&book.id).await {
+                warn!("Failed to check {} against collection subscriptions: {}", book.title, e);
+            }
+        }
+
+        Ok(book.id)
+    }
+
+    /// Open a book for reading without importing it into the library
+    pub async fn open_external_file(&self, file_path: &Path) -> Result<Book> {
+        let mut book = self.parse_book_metadata(file_path).await?;
         book.id = Uuid::new_v4().to_string();
         
-        // Extract and cache cover if available
-        if let Some(cover_data) = self.extract_cover_data(&book).await? {
-            let cover_path = self.image_cache.save_cover(&book.id, &cover_data).await?;
-            book.cover_path = Some(cover_path);
+        // Covers for session books live in the temp directory
+        let session_cache = Self::session_image_cache()?;
+        let cover_path = match self.extract_cover_data(&book).await? {
+            Some(cover_data) => session_cache.save_cover(&book.id, &cover_data).await?,
+            None => session_cache.create_placeholder(&book.id, &book.title, &book.author).await?,
+        };
+        book.cover_path = Some(cover_path);
+        
+        let mut session_books = self.session_books.write().await;
+        session_books.insert(book.id.clone(), book.clone());
+        
+        Ok(book)
+    }
+
+    /// Check if a book was opened for the current session only
+    pub async fn is_session_book(&self, book_id: &str) -> bool {
+        self.session_books.read().await.contains_key(book_id)
+    }
+
+    /// Refuse library changes to a session book, which has no library entry
+    async fn ensure_library_book(&self, book_id: &str) -> Result<()> {
+        if self.is_session_book(book_id).await {
+            return Err(anyhow!("Book is open as an external file, add it to the library first: {}", book_id));
         }
+        Ok(())
+    }
 
-        // Save to database
+    /// Close a session book, discarding its progress and temporary cover
+    pub async fn close_external_file(&self, book_id: &str) -> Result<()> {
+        let book = self.session_books.write().await.remove(book_id);
+        
+        if let Some(cover_path) = book.and_then(|book| book.cover_path) {
+            let session_cache = Self::session_image_cache()?;
+            let _ = session_cache.remove_cover(&cover_path).await;
+        }
+        
+        Ok(())
+    }
+
+    /// Import a session book into the library, keeping its session progress
+    pub async fn add_session_book_to_library(&self, book_id: &str) -> Result<String> {
+        let mut book = self.session_books.read().await
+            .get(book_id)
+            .cloned()
+            .ok_or_else(|| anyhow!("Book is not open as an external file"))?;
+        
+        if self.database.book_exists_by_path(&book.file_path).await? {
+            return Err(DuplicateBookError::SamePath.into());
+        }
+        self.check_duplicate_isbn(&book).await?;
+        
+        // Move the cover from the temp directory into the persistent cache
+        if let Some(session_cover) = book.cover_path.take() {
+            if let Ok(cover_data) = tokio::fs::read(&session_cover).await {
+                book.cover_path = Some(self.image_cache.save_cover(&book.id, &cover_data).await?);
+            }
+        }
+        
         self.database.insert_book(&book).await?;
+        self.close_external_file(book_id).await?;
         
-        // Update cache
         let mut cache = self.book_cache.write().await;
         cache.insert(book.id.clone(), book.clone());
         
         Ok(book.id)
     }
 
+    /// Refuse a book whose ISBN is already in the library, e.g. the same edition from another file
+    async fn check_duplicate_isbn(&self, book: &Book) -> Result<()> {
+        if let Some(isbn) = &book.isbn {
+            if let Some(existing) = self.database.find_book_by_isbn(isbn).await? {
+                return Err(DuplicateBookError::SameIsbn { title: existing.title, isbn: isbn.clone() }.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Image cache for session books, rooted in the temp directory
+    fn session_image_cache() -> Result<ImageCache> {
+        let temp_dir = PathResolver::get_temp_directory()?;
+        ImageCache::new(temp_dir.join("session"))
+    }
+
     /// Update book information
     pub async fn update_book(&self, book_id: &str, updated_book: &Book) -> Result<()> {
+        self.ensure_library_book(book_id).await?;
         self.database.update_book(updated_book).await?;
         
         // Update cache
@@ -146,10 +491,150 @@ impl BookService {
         Ok(())
     }
 
-    /// Delete a book from the library
+    /// Apply the fields of fetched metadata the user accepted after review
+    ///
+    /// A selected cover is downloaded and saved before the book is updated.
+    #[cfg(feature = "network")]
+    pub async fn apply_fetched_metadata(
+        &self,
+        book_id: &str,
+        candidate: &MetadataCandidate,
+        fields: &[MetadataField],
+        metadata_service: &MetadataService,
+    ) -> Result<Book> {
+        self.ensure_library_book(book_id).await?;
+        let mut book = self.get_book_by_id(book_id).await?;
+        candidate.apply(&mut book, fields);
+
+        if fields.contains(&MetadataField::Cover) {
+            if let Some(cover_data) = metadata_service.fetch_cover(candidate).await? {
+                book.cover_path = Some(self.image_cache.save_cover(&book.id, &cover_data).await?);
+            }
+        }
+
+        self.update_book(book_id, &book).await?;
+        Ok(book)
+    }
+
+    /// Move a book to the trash, where it can be restored until the trash is emptied
     pub async fn delete_book(&self, book_id: &str) -> Result<()> {
-        // Get book info before deletion
+        self.move_to_trash(&[book_id.to_string()]).await
+    }
+
+    /// Move books to the trash, leaving their files and annotations in place
+    pub async fn move_to_trash(&self, book_ids: &[String]) -> Result<()> {
+        for book_id in book_ids {
+            self.ensure_library_book(book_id).await?;
+        }
+
+        let now = Utc::now();
+        let mut cache = self.book_cache.write().await;
+        for book_id in book_ids {
+            self.database.set_book_deleted_at(book_id, Some(now)).await?;
+            cache.remove(book_id);
+        }
+        Ok(())
+    }
+
+    /// Put a book in the trash back in the library
+    pub async fn restore_from_trash(&self, book_id: &str) -> Result<()> {
+        if !self.database.is_book_trashed(book_id).await? {
+            return Err(anyhow!("Book is not in the trash: {}", book_id));
+        }
+        self.database.set_book_deleted_at(book_id, None).await
+    }
+
+    /// Get the books in the trash, most recently deleted first
+    pub async fn get_trash(&self) -> Result<Vec<TrashedBook>> {
+        let retention_days = self.get_trash_retention_days().await;
+        Ok(self.database.get_trashed_books().await?
+            .into_iter()
+            .map(|(book, deleted_at)| TrashedBook {
+                book,
+                deleted_at,
+                purge_at: (retention_days > 0).then(|| deleted_at + Duration::days(retention_days as i64)),
+            })
+            .collect())
+    }
+
+    /// Request confirmation to permanently delete the books in the trash
+    pub async fn request_empty_trash(&self) -> Result<ConfirmationToken> {
+        let book_ids: Vec<String> = self.database.get_trashed_books().await?
+            .into_iter()
+            .map(|(book, _)| book.id)
+            .collect();
+        let impact = self.deletion_impact(&book_ids).await?;
+        Ok(self.confirmations.issue(DestructiveAction::EmptyTrash, impact, book_ids).await)
+    }
+
+    /// Permanently delete the books that were in the trash when `request_empty_trash` issued the token
+    ///
+    /// Books restored since then are kept.
+    pub async fn empty_trash(&self, token: &str) -> Result<usize> {
+        let confirmed = self.confirmations.confirm(token, &DestructiveAction::EmptyTrash).await?;
+        let trashed: Vec<String> = self.database.get_trashed_books().await?
+            .into_iter()
+            .map(|(book, _)| book.id)
+            .collect();
+        self.delete_confirmed_books(&confirmed.book_ids, &trashed).await
+    }
+
+    /// Permanently delete books that have been in the trash longer than the retention period
+    ///
+    /// A book that fails to delete is logged and left in the trash.
+    pub async fn purge_expired_trash(&self) -> Result<usize> {
+        let now = Utc::now();
+        let mut purged = 0;
+        for trashed in self.get_trash().await? {
+            if trashed.purge_at.is_some_and(|purge_at| purge_at <= now) {
+                match self.delete_book_with_policy(&trashed.book.id, None).await {
+                    Ok(()) => purged += 1,
+                    Err(e) => warn!("Failed to purge {} from the trash: {}", trashed.book.id, e),
+                }
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Describe what deleting a book would do, for a confirmation prompt
+    pub async fn preview_book_deletion(
+        &self,
+        book_id: &str,
+        policy_override: Option<FileDeletionPolicy>,
+    ) -> Result<BookDeletionPreview> {
+        self.ensure_library_book(book_id).await?;
+        let book = self.get_book_by_id(book_id).await?;
+        let policy = match policy_override {
+            Some(policy) => policy,
+            None => self.get_file_deletion_policy().await,
+        };
+
+        Ok(BookDeletionPreview {
+            book_id: book.id,
+            title: book.title,
+            file_exists: book.file_path.exists(),
+            file_path: book.file_path,
+            file_size: book.file_size,
+            policy,
+        })
+    }
+
+    /// Permanently delete a book, optionally overriding the file deletion policy
+    pub async fn delete_book_with_policy(
+        &self,
+        book_id: &str,
+        policy_override: Option<FileDeletionPolicy>,
+    ) -> Result<()> {
+        // Get book info before deletion; a session book's file is not the library's to delete
+        self.ensure_library_book(book_id).await?;
         let book = self.get_book_by_id(book_id).await?;
+        let policy = match policy_override {
+            Some(policy) => policy,
+            None => self.get_file_deletion_policy().await,
+        };
+        
+        // Handle the underlying file first so a failure leaves the entry intact
+        self.remove_book_file(&book.file_path, &policy).await?;
         
         // Delete from database
         self.database.delete_book(book_id).await?;
@@ -166,8 +651,118 @@ impl BookService {
         Ok(())
     }
 
+    /// Request confirmation to permanently delete every book in the library
+    pub async fn request_delete_all_books(&self) -> Result<ConfirmationToken> {
+        let book_ids = self.all_book_ids().await?;
+        let impact = self.deletion_impact(&book_ids).await?;
+        Ok(self.confirmations.issue(DestructiveAction::DeleteAllBooks, impact, book_ids).await)
+    }
+
+    /// Permanently delete the books counted when `request_delete_all_books` issued the token
+    ///
+    /// Books imported after the prompt was shown are kept.
+    pub async fn delete_all_saved_books(&self, token: &str) -> Result<usize> {
+        let confirmed = self.confirmations.confirm(token, &DestructiveAction::DeleteAllBooks).await?;
+        let current = self.all_book_ids().await?;
+        self.delete_confirmed_books(&confirmed.book_ids, &current).await
+    }
+
+    /// Delete the confirmed books that are still among `current`
+    async fn delete_confirmed_books(&self, confirmed: &[String], current: &[String]) -> Result<usize> {
+        let current: HashSet<&String> = current.iter().collect();
+        let mut deleted = 0;
+        for book_id in confirmed.iter().filter(|book_id| current.contains(book_id)) {
+            self.delete_book_with_policy(book_id, None).await?;
+            deleted += 1;
+        }
+        Ok(deleted)
+    }
+
+    /// IDs of the books in the library and in the trash
+    async fn all_book_ids(&self) -> Result<Vec<String>> {
+        let mut book_ids: Vec<String> = self.database.get_all_books().await?
+            .into_iter()
+            .map(|book| book.id)
+            .collect();
+        book_ids.extend(self.database.get_trashed_books().await?.into_iter().map(|(book, _)| book.id));
+        Ok(book_ids)
+    }
+
+    /// Request confirmation to permanently delete a selection of books
+    pub async fn request_delete_books(&self, book_ids: &[String]) -> Result<ConfirmationToken> {
+        let impact = self.deletion_impact(book_ids).await?;
+        Ok(self.confirmations.issue(DestructiveAction::DeleteBooks(book_ids.to_vec()), impact, book_ids.to_vec()).await)
+    }
+
+    /// Permanently delete a selection of books, using a token from `request_delete_books`
+    pub async fn delete_books(&self, book_ids: &[String], token: &str) -> Result<()> {
+        self.confirmations.confirm(token, &DestructiveAction::DeleteBooks(book_ids.to_vec())).await?;
+
+        for book_id in book_ids {
+            self.delete_book_with_policy(book_id, None).await?;
+        }
+        Ok(())
+    }
+
+    /// Request confirmation to clear cached covers and thumbnails
+    pub async fn request_clear_caches(&self) -> Result<ConfirmationToken> {
+        let stats = self.image_cache.get_cache_stats().await?;
+        let impact = DestructiveImpact {
+            cache_bytes: stats.total_size_bytes,
+            ..DestructiveImpact::default()
+        };
+        Ok(self.confirmations.issue(DestructiveAction::ClearCaches, impact, Vec::new()).await)
+    }
+
+    /// Clear cached covers, thumbnails and in-memory caches, using a token from `request_clear_caches`
+    pub async fn clear_all_caches(&self, token: &str) -> Result<()> {
+        self.confirmations.confirm(token, &DestructiveAction::ClearCaches).await?;
+
+        self.image_cache.clear_cache().await?;
+        self.clear_caches().await;
+        Ok(())
+    }
+
+    /// Count what deleting these books would remove
+    async fn deletion_impact(&self, book_ids: &[String]) -> Result<DestructiveImpact> {
+        Ok(DestructiveImpact {
+            book_count: book_ids.len(),
+            annotation_count: self.database.count_annotations_for_books(book_ids).await?,
+            cache_bytes: 0,
+        })
+    }
+
+    /// Apply a file deletion policy to a book file
+    async fn remove_book_file(&self, file_path: &Path, policy: &FileDeletionPolicy) -> Result<()> {
+        if !file_path.exists() {
+            return Ok(());
+        }
+
+        match policy {
+            FileDeletionPolicy::KeepFile => Ok(()),
+            FileDeletionPolicy::MoveToTrash => {
+                let path = file_path.to_path_buf();
+                tokio::task::spawn_blocking(move || trash::delete(&path))
+                    .await?
+                    .map_err(|e| anyhow!("Failed to move {} to trash: {}", file_path.display(), e))
+            }
+            FileDeletionPolicy::DeletePermanently => {
+                tokio::fs::remove_file(file_path).await
+                    .map_err(|e| anyhow!("Failed to delete {}: {}", file_path.display(), e))
+            }
+        }
+    }
+
     /// Get a book by ID
     pub async fn get_book_by_id(&self, book_id: &str) -> Result<Book> {
+        // Books opened for the session only never touch the database
+        {
+            let session_books = self.session_books.read().await;
+            if let Some(book) = session_books.get(book_id) {
+                return Ok(book.clone());
+            }
+        }
+
         // Check cache first
         {
             let cache = self.book_cache.read().await;
@@ -187,25 +782,175 @@ impl BookService {
     }
 
     /// Update reading progress for a book
+    ///
+    /// When this update finishes the book and the next book in its series is in
+    /// the library, returns it along with the auto-advance action that was applied.
     pub async fn update_reading_progress(
         &self,
         book_id: &str,
         progress: f32,
-    ) -> Result<()> {
+    ) -> Result<Option<SeriesAdvance>> {
+        // Session books keep their progress in memory only
+        {
+            let mut session_books = self.session_books.write().await;
+            if let Some(book) = session_books.get_mut(book_id) {
+                book.update_progress(progress);
+                return Ok(None);
+            }
+        }
+
         let mut book = self.get_book_by_id(book_id).await?;
+        let was_finished = book.is_finished();
         book.update_progress(progress);
         
         self.database.update_book(&book).await?;
         
         // Update cache
         let mut cache = self.book_cache.write().await;
-        cache.insert(book_id.to_string(), book);
+        cache.insert(book_id.to_string(), book.clone());
+        drop(cache);
         
-        Ok(())
+        if book.is_finished() && !was_finished {
+            return self.on_book_finished(&book).await;
+        }
+        
+        Ok(None)
+    }
+
+    /// Set a book's reading status
+    ///
+    /// Marking a book finished advances the reading queue and its series like
+    /// reaching the end does.
+    pub async fn set_reading_status(&self, book_id: &str, status: ReadingStatus) -> Result<Option<SeriesAdvance>> {
+        {
+            let mut session_books = self.session_books.write().await;
+            if let Some(book) = session_books.get_mut(book_id) {
+                book.reading_status = status;
+                return Ok(None);
+            }
+        }
+
+        let mut book = self.get_book_by_id(book_id).await?;
+        let was_finished = book.is_finished();
+        book.reading_status = status;
+        self.update_book(book_id, &book).await?;
+
+        if book.is_finished() && !was_finished {
+            return self.on_book_finished(&book).await;
+        }
+        Ok(None)
+    }
+
+    /// Everything that follows finishing a book: the reading queue moves on,
+    /// then the next book in its series is offered
+    async fn on_book_finished(&self, finished: &Book) -> Result<Option<SeriesAdvance>> {
+        let library_service = self.library_service.read().await.clone();
+        if let Some(library_service) = library_service {
+            if let Some(next_book_id) = library_service.advance_queue(&finished.id).await? {
+                let mut next_book = self.get_book_by_id(&next_book_id).await?;
+                next_book.reading_status = ReadingStatus::CurrentlyReading;
+                self.update_book(&next_book_id, &next_book).await?;
+            }
+        }
+
+        self.advance_series(finished).await
+    }
+
+    /// Find the next book in a finished book's series and apply the auto-advance preference
+    async fn advance_series(&self, finished: &Book) -> Result<Option<SeriesAdvance>> {
+        let action = self.get_series_auto_advance().await;
+        if action == SeriesAutoAdvance::Off {
+            return Ok(None);
+        }
+
+        let (Some(series), Some(index)) = (&finished.series, finished.series_index) else {
+            return Ok(None);
+        };
+        let Some(mut next_book) = self.database.get_next_in_series(series, index).await? else {
+            return Ok(None);
+        };
+
+        // Leave books the reader has already started or finished alone
+        let startable = matches!(
+            next_book.reading_status,
+            ReadingStatus::Unread | ReadingStatus::WantToRead
+        );
+        if !startable {
+            return Ok(None);
+        }
+
+        if matches!(action, SeriesAutoAdvance::MarkCurrentlyReading | SeriesAutoAdvance::MarkAndOpen) {
+            next_book.reading_status = ReadingStatus::CurrentlyReading;
+            self.update_book(&next_book.id.clone(), &next_book).await?;
+        }
+
+        Ok(Some(SeriesAdvance { next_book, action }))
+    }
+
+    /// Start timing a reading session for a book
+    ///
+    /// Books opened for the session only aren't tracked and return `None`.
+    pub async fn start_reading_session(&self, book_id: &str) -> Result<Option<ReadingSession>> {
+        if self.is_session_book(book_id).await {
+            return Ok(None);
+        }
+
+        let book = self.get_book_by_id(book_id).await?;
+        let session = ReadingSession::start(&book, Utc::now());
+        self.database.save_reading_session(&session).await?;
+
+        Ok(Some(session))
+    }
+
+    /// End a reading session, crediting the progress made since it started
+    pub async fn end_reading_session(&self, session_id: &str) -> Result<ReadingSession> {
+        let mut session = self.database.get_reading_session(session_id).await?
+            .ok_or_else(|| anyhow!("Reading session not found: {}", session_id))?;
+
+        if session.is_active() {
+            let book = self.get_book_by_id(&session.book_id).await?;
+            session.end(&book, Utc::now());
+            self.database.save_reading_session(&session).await?;
+        }
+
+        Ok(session)
+    }
+
+    /// Get the minutes spent reading a book
+    pub async fn get_book_reading_time(&self, book_id: &str) -> Result<u64> {
+        self.database.get_reading_time(Some(book_id)).await
+    }
+
+    /// Get the minutes spent reading across the library
+    pub async fn get_total_reading_time(&self) -> Result<u64> {
+        self.database.get_reading_time(None).await
+    }
+
+    /// Minutes read on each of the last `days` days, oldest first
+    ///
+    /// Days are local dates, and a session counts toward the day it started.
+    pub async fn get_reading_heatmap(&self, days: u32) -> Result<Vec<(NaiveDate, u32)>> {
+        let today = Local::now().date_naive();
+        let first_day = today - Duration::days(days.saturating_sub(1) as i64);
+        let mut minutes: Vec<(NaiveDate, u32)> = (0..days)
+            .map(|offset| (first_day + Duration::days(offset as i64), 0))
+            .collect();
+
+        // A day of margin covers local days that start before the UTC cutoff
+        let since = Utc::now() - Duration::days(days as i64 + 1);
+        for session in self.database.get_reading_sessions_since(since).await? {
+            let day = session.start_time.with_timezone(&Local).date_naive();
+            if let Some((_, total)) = minutes.iter_mut().find(|(date, _)| *date == day) {
+                *total += session.duration_minutes;
+            }
+        }
+
+        Ok(minutes)
     }
 
     /// Toggle favorite status for a book
     pub async fn toggle_favorite(&self, book_id: &str) -> Result<bool> {
+        self.ensure_library_book(book_id).await?;
         let mut book = self.get_book_by_id(book_id).await?;
         book.is_favorite = !book.is_favorite;
         
@@ -252,6 +997,7 @@ impl BookService {
         book_id: &str,
         collection_id: &str,
     ) -> Result<()> {
+        self.ensure_library_book(book_id).await?;
         self.database.add_book_to_collection(book_id, collection_id).await?;
         
         // Update cache
@@ -263,9 +1009,20 @@ impl BookService {
         Ok(())
     }
 
+    /// Get the books of a series in reading order
+    pub async fn get_series(&self, series: &str) -> Result<Vec<BookViewModel>> {
+        let books = self.database.get_series_books(series).await?;
+        let words_per_minute = self.get_reading_speed_wpm().await;
+
+        Ok(books.into_iter()
+            .map(|book| BookViewModel::with_reading_speed(book, words_per_minute))
+            .collect())
+    }
+
     /// Get recently added books
     pub async fn get_recently_added(&self, limit: usize) -> Result<Vec<BookViewModel>> {
         let books = self.database.get_recently_added_books(limit).await?;
+        let words_per_minute = self.get_reading_speed_wpm().await;
         let mut view_models = Vec::new();
         
         for book in books {
@@ -276,7 +1033,7 @@ impl BookService {
                 None
             };
             
-            view_models.push(BookViewModel::from(book));
+            view_models.push(BookViewModel::with_reading_speed(book, words_per_minute));
         }
         
         Ok(view_models)
@@ -285,6 +1042,7 @@ impl BookService {
     /// Get currently reading books
     pub async fn get_currently_reading(&self) -> Result<Vec<BookViewModel>> {
         let books = self.database.get_books_by_status(ReadingStatus::CurrentlyReading).await?;
+        let words_per_minute = self.get_reading_speed_wpm().await;
         let mut view_models = Vec::new();
         
         for book in books {
@@ -295,7 +1053,7 @@ impl BookService {
                 None
             };
             
-            view_models.push(BookViewModel::from(book));
+            view_models.push(BookViewModel::with_reading_speed(book, words_per_minute));
         }
         
         Ok(view_models)
@@ -303,7 +1061,7 @@ impl BookService {
 
     /// Parse book metadata from file
     async fn parse_book_metadata(&self, file_path: &Path) -> Result<Book> {
-        let file_size = std::fs::metadata(file_path)?.len();
+        let file_size = tokio::fs::metadata(file_path).await?.len();
         let format = BookFormat::from_extension(
             file_path.extension()
                 .and_then(|ext| ext.to_str())
@@ -337,32 +1095,207 @@ impl BookService {
         Ok(book)
     }
 
-    /// Parse EPUB metadata
+    /// Parse EPUB metadata, falling back to the file name for untitled books
     async fn parse_epub_metadata(&self, book: &mut Book) -> Result<()> {
-        // Implementation would use epub crate to extract metadata
-        // This is a placeholder for the actual implementation
+        let file_path = book.file_path.clone();
+        let parsed = tokio::task::spawn_blocking(move || {
+            EpubParser::open(&file_path, None).map(|mut doc| {
+                let info = EpubParser::navigation(&mut doc);
+                // Fall back to the ISBN printed on the copyright page
+                let isbn = info.metadata.isbn().or_else(|| EpubParser::text_isbn(&mut doc));
+                (info.metadata, isbn, ReadingService::chapter_word_counts(&mut doc), info.page_list.len())
+            })
+        })
+        .await?;
+
+        // Password-protected books are imported by file name until unlocked
+        let (metadata, isbn, chapter_word_counts, print_pages) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e @ (EpubOpenError::PasswordRequired { .. } | EpubOpenError::InvalidPassword { .. })) => {
+                warn!("Could not read metadata from {}: {}", book.file_path.display(), e);
+                (EpubMetadata::default(), None, Vec::new(), 0)
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if !chapter_word_counts.is_empty() {
+            let word_count: u32 = chapter_word_counts.iter().sum();
+            book.word_count = Some(word_count);
+            // Prefer the print edition's page numbers when the book maps them
+            book.page_count = Some(match print_pages {
+                0 => word_count.div_ceil(WORDS_PER_PAGE).max(1),
+                pages => pages as u32,
+            });
+            book.chapter_word_counts = chapter_word_counts;
+        }
+
+        match metadata.title.clone() {
+            Some(title) => book.title = title,
+            None => {
+                if let Some(file_stem) = book.file_path.file_stem() {
+                    book.title = file_stem.to_string_lossy().to_string();
+                }
+            }
+        }
+        let authors = metadata.authors();
+        if !authors.is_empty() {
+            book.author = authors.join(", ");
+        }
+        book.contributors = metadata.other_contributors().into_iter().map(str::to_string).collect();
+        book.isbn = isbn;
+        book.publication_date = metadata.publication_date();
+        book.genre = metadata.subjects.first().cloned();
+        book.identifiers = metadata.identifiers.iter().map(|identifier| identifier.value.clone()).collect();
+        book.description = metadata.description;
+        book.language = metadata.language;
+        book.publisher = metadata.publisher;
+        book.rights = metadata.rights;
+        book.subjects = metadata.subjects;
+        book.series = metadata.series;
+        book.series_index = metadata.series_index;
         Ok(())
     }
 
-    /// Parse PDF metadata
+    /// Parse PDF metadata, falling back to the file name for untitled documents
     async fn parse_pdf_metadata(&self, book: &mut Book) -> Result<()> {
-        // Implementation would use pdf-extract crate to extract metadata
-        // This is a placeholder for the actual implementation
+        let file_path = book.file_path.clone();
+        let metadata = tokio::task::spawn_blocking(move || PdfParser::metadata(&file_path)).await??;
+
+        match metadata.title {
+            Some(title) => book.title = title,
+            None => {
+                if let Some(file_stem) = book.file_path.file_stem() {
+                    book.title = file_stem.to_string_lossy().to_string();
+                }
+            }
+        }
+        if let Some(author) = metadata.author {
+            book.author = author;
+        }
+        book.description = metadata.subject;
+        book.page_count = Some(metadata.page_count);
+
+        // Each page is a chapter when a PDF is read
+        let file_path = book.file_path.clone();
+        match tokio::task::spawn_blocking(move || PdfParser::extract_pages(&file_path)).await? {
+            Ok(pages) => {
+                book.chapter_word_counts = pages.iter().map(|page| page.split_whitespace().count() as u32).collect();
+                book.word_count = Some(book.chapter_word_counts.iter().sum());
+                // The copyright page is near the start, or the end for some print layouts
+                let back = pages.len().saturating_sub(PDF_ISBN_SEARCH_PAGES).max(PDF_ISBN_SEARCH_PAGES.min(pages.len()));
+                book.isbn = pages.iter()
+                    .take(PDF_ISBN_SEARCH_PAGES)
+                    .chain(&pages[back..])
+                    .find_map(|page| find_isbns(page).into_iter().next());
+            }
+            Err(e) => warn!("Could not count words in {}: {}", book.file_path.display(), e),
+        }
         Ok(())
     }
 
+    /// Re-extract covers and rebuild thumbnails, e.g. after a thumbnailer upgrade
+    ///
+    /// An existing cover is only replaced once the new one has been decoded, so a
+    /// failed extraction never leaves a book without its cover.
+    pub async fn regenerate_covers(
+        &self,
+        scope: CoverScope,
+        progress: Option<mpsc::UnboundedSender<CoverRegenerationProgress>>,
+    ) -> Result<CoverRegenerationSummary> {
+        let books = match scope {
+            CoverScope::Library => self.database.get_all_books().await?,
+            CoverScope::Books(book_ids) => {
+                let mut books = Vec::new();
+                for book_id in book_ids {
+                    self.ensure_library_book(&book_id).await?;
+                    books.push(self.get_book_by_id(&book_id).await?);
+                }
+                books
+            }
+        };
+
+        let total = books.len();
+        let semaphore = Arc::new(Semaphore::new(MAX_COVER_REGENERATION_JOBS));
+        let mut jobs = JoinSet::new();
+        for book in books {
+            let semaphore = semaphore.clone();
+            let image_cache = self.image_cache.clone();
+            jobs.spawn(async move {
+                let _permit = semaphore.acquire_owned().await?;
+                let outcome = match Self::regenerate_cover(&book, &image_cache).await {
+                    Ok(Some(cover_path)) => CoverRegenerationOutcome::Regenerated(cover_path),
+                    Ok(None) => CoverRegenerationOutcome::NoCover,
+                    Err(e) => CoverRegenerationOutcome::Failed(e.to_string()),
+                };
+                Ok::<_, anyhow::Error>((book, outcome))
+            });
+        }
+
+        let mut summary = CoverRegenerationSummary::default();
+        let mut completed = 0;
+        while let Some(result) = jobs.join_next().await {
+            let (mut book, outcome) = result??;
+            completed += 1;
+
+            match &outcome {
+                CoverRegenerationOutcome::Regenerated(cover_path) => {
+                    summary.regenerated += 1;
+                    if book.cover_path.as_ref() != Some(cover_path) {
+                        book.cover_path = Some(cover_path.clone());
+                        self.database.update_book(&book).await?;
+                    }
+                    // Drop the cached entry so views pick up the new cover
+                    self.book_cache.write().await.remove(&book.id);
+                }
+                CoverRegenerationOutcome::NoCover => summary.no_cover += 1,
+                CoverRegenerationOutcome::Failed(e) => {
+                    warn!("Failed to regenerate cover for {}: {}", book.id, e);
+                    summary.failed += 1;
+                }
+            }
+
+            if let Some(progress) = &progress {
+                let _ = progress.send(CoverRegenerationProgress {
+                    book_id: book.id.clone(),
+                    completed,
+                    total,
+                    outcome,
+                });
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Re-extract a single book's cover, returning the saved cover path
+    async fn regenerate_cover(book: &Book, image_cache: &ImageCache) -> Result<Option<PathBuf>> {
+        let file_path = book.file_path.clone();
+        let file_format = book.file_format.clone();
+        let cover_data = tokio::task::spawn_blocking(move || Self::read_cover_data(&file_path, &file_format)).await??;
+
+        match cover_data {
+            Some(cover_data) => Ok(Some(image_cache.save_cover(&book.id, &cover_data).await?)),
+            None => Ok(None),
+        }
+    }
+
     /// Extract cover data from book
     async fn extract_cover_data(&self, book: &Book) -> Result<Option<Vec<u8>>> {
-        match book.file_format {
+        let file_path = book.file_path.clone();
+        let file_format = book.file_format.clone();
+        tokio::task::spawn_blocking(move || Self::read_cover_data(&file_path, &file_format)).await?
+    }
+
+    /// Read raw cover image bytes from a book file
+    fn read_cover_data(file_path: &Path, file_format: &BookFormat) -> Result<Option<Vec<u8>>> {
+        match file_format {
             BookFormat::Epub => {
-                // Extract cover from EPUB
-                // This is a placeholder for the actual implementation
-                Ok(None)
+                let mut doc = EpubParser::open(file_path, None)
+                    .map_err(|e| anyhow!("Failed to open EPUB: {}", e))?;
+                Ok(EpubParser::cover(&mut doc).map(|cover| cover.data))
             }
             BookFormat::Pdf => {
-                // Extract first page as cover from PDF
-                // This is a placeholder for the actual implementation
-                Ok(None)
+                Ok(PdfParser::render_page_image(file_path, 1)?.map(|image| image.data))
             }
             _ => Ok(None),
         }
@@ -377,10 +1310,16 @@ impl BookService {
         let thumbnail_path = self.image_cache.get_thumbnail_path(book_id);
         
         if !thumbnail_path.exists() {
-            // Create thumbnail
-            let img = image::open(cover_path)?;
-            let thumbnail = img.resize(200, 300, FilterType::Lanczos3);
-            thumbnail.save(&thumbnail_path)?;
+            // Create thumbnail, decoding and resizing off the async workers
+            let cover_path = cover_path.to_path_buf();
+            let output_path = thumbnail_path.clone();
+            tokio::task::spawn_blocking(move || -> Result<()> {
+                let img = image::open(&cover_path)?;
+                let thumbnail = img.resize(200, 300, FilterType::Lanczos3);
+                thumbnail.save(&output_path)?;
+                Ok(())
+            })
+            .await??;
         }
         
         Ok(thumbnail_path)
@@ -406,6 +1345,128 @@ pub struct BookFilter {
     pub is_favorite: Option<bool>,
     pub rating_min: Option<u8>,
     pub rating_max: Option<u8>,
+    pub collection_id: Option<String>,
+    pub language: Option<String>,
+    pub year_min: Option<i32>, // Publication year, inclusive
+    pub year_max: Option<i32>,
+    pub word_count_min: Option<u32>, // Book length, inclusive
+    pub word_count_max: Option<u32>,
+}
+
+/// Raised when an imported book is already in the library
+#[derive(Debug, Error)]
+pub enum DuplicateBookError {
+    #[error("Book already exists in library")]
+    SamePath,
+
+    #[error("Book already exists in library as \"{title}\" (ISBN {isbn})")]
+    SameIsbn { title: String, isbn: String },
+}
+
+/// A deleted book waiting in the trash
+#[derive(Debug, Clone)]
+pub struct TrashedBook {
+    pub book: Book,
+    pub deleted_at: DateTime<Utc>,
+    pub purge_at: Option<DateTime<Utc>>, // None when the trash is only emptied by hand
+}
+
+/// Confirmation data shown before a book is deleted
+#[derive(Debug, Clone)]
+pub struct BookDeletionPreview {
+    pub book_id: String,
+    pub title: String,
+    pub file_path: PathBuf,
+    pub file_exists: bool,
+    pub file_size: u64,
+    pub policy: FileDeletionPolicy,
+}
+
+/// Next book in a series, returned when a book is finished
+#[derive(Debug, Clone)]
+pub struct SeriesAdvance {
+    pub next_book: Book,
+    pub action: SeriesAutoAdvance, // MarkAndOpen asks the caller to open `next_book`
+}
+
+/// Result of importing one file from a directory
+#[derive(Debug, Clone)]
+pub enum ImportOutcome {
+    Imported(String),
+    Duplicate(String),
+    Failed(String),
+}
+
+/// Progress event sent after each file of a directory import
+#[derive(Debug, Clone)]
+pub struct ImportProgress {
+    pub file_path: PathBuf,
+    pub completed: usize,
+    pub total: usize,
+    pub outcome: ImportOutcome,
+}
+
+/// Report of a directory import
+#[derive(Debug, Clone, Default)]
+pub struct ImportSummary {
+    pub imported: Vec<String>,              // IDs of the new books
+    pub duplicates: Vec<PathBuf>,
+    pub failures: Vec<(PathBuf, String)>,   // File and the reason it failed
+}
+
+impl ImportSummary {
+    /// Add a file's import result to the report
+    fn record(&mut self, file_path: &Path, result: Result<String>) -> ImportOutcome {
+        match result {
+            Ok(book_id) => {
+                self.imported.push(book_id.clone());
+                ImportOutcome::Imported(book_id)
+            }
+            Err(e) => match e.downcast_ref::<DuplicateBookError>() {
+                Some(duplicate) => {
+                    self.duplicates.push(file_path.to_path_buf());
+                    ImportOutcome::Duplicate(duplicate.to_string())
+                }
+                None => {
+                    warn!("Failed to import {}: {}", file_path.display(), e);
+                    self.failures.push((file_path.to_path_buf(), e.to_string()));
+                    ImportOutcome::Failed(e.to_string())
+                }
+            },
+        }
+    }
+}
+
+/// Books whose covers should be regenerated
+#[derive(Debug, Clone)]
+pub enum CoverScope {
+    Books(Vec<String>),
+    Library,
+}
+
+/// Result of regenerating one book's cover
+#[derive(Debug, Clone)]
+pub enum CoverRegenerationOutcome {
+    Regenerated(PathBuf),
+    NoCover,
+    Failed(String),
+}
+
+/// Progress event sent after each book's cover is processed
+#[derive(Debug, Clone)]
+pub struct CoverRegenerationProgress {
+    pub book_id: String,
+    pub completed: usize,
+    pub total: usize,
+    pub outcome: CoverRegenerationOutcome,
+}
+
+/// Totals for a cover regeneration run
+#[derive(Debug, Clone, Default)]
+pub struct CoverRegenerationSummary {
+    pub regenerated: usize,
+    pub no_cover: usize,
+    pub failed: usize,
 }
 
 /// Book sorting options
@@ -423,6 +1484,8 @@ pub enum SortField {
     LastOpened,
     ReadingProgress,
     Rating,
+    WordCount,
+    Series,
 }
 
 #[derive(Debug, Clone)]
@@ -441,6 +1504,12 @@ impl Default for BookFilter {
             is_favorite: None,
             rating_min: None,
             rating_max: None,
+            collection_id: None,
+            language: None,
+            year_min: None,
+            year_max: None,
+            word_count_min: None,
+            word_count_max: None,
         }
     }
 }
@@ -452,4 +1521,296 @@ impl Default for BookSort {
             order: SortOrder::Descending,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+
+    fn write_epub(path: &Path, title: &str, isbn: &str) {
+        let mut writer = ZipWriter::new(std::fs::File::create(path).unwrap());
+        let options = SimpleFileOptions::default();
+        let files = [
+            ("mimetype", "application/epub+zip".to_string()),
+            (
+                "META-INF/container.xml",
+                r#"<container><rootfiles><rootfile full-path="content.opf"/></rootfiles></container>"#.to_string(),
+            ),
+            (
+                "content.opf",
+                format!(
+                    r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0"><metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>{}</dc:title><dc:identifier>urn:isbn:{}</dc:identifier></metadata><manifest><item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/></manifest><spine><itemref idref="c1"/></spine></package>"#,
+                    title, isbn
+                ),
+            ),
+            ("c1.xhtml", "<html><body><p>Chapter one.</p></body></html>".to_string()),
+        ];
+        for (name, data) in files {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(data.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_external_files_are_read_without_importing() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let database = Arc::new(DatabaseService::new_in_memory().await.unwrap());
+        let image_cache = Arc::new(ImageCache::new(temp_dir.path().join("covers")).unwrap());
+        let service = BookService::new(database.clone(), image_cache);
+        let book_path = temp_dir.path().join("borrowed.epub");
+        write_epub(&book_path, "Borrowed", "9780262162098");
+
+        let book = service.open_external_file(&book_path).await.unwrap();
+        let session_cover = book.cover_path.clone().unwrap();
+        assert!(service.is_session_book(&book.id).await);
+        assert!(session_cover.exists());
+        assert!(database.get_all_books().await.unwrap().is_empty());
+
+        // Progress stays in memory
+        service.update_reading_progress(&book.id, 0.4).await.unwrap();
+        assert_eq!(service.get_book_by_id(&book.id).await.unwrap().reading_progress, 0.4);
+        assert!(database.get_all_books().await.unwrap().is_empty());
+
+        // Library changes are refused, and the user's file is never deleted
+        assert!(service.toggle_favorite(&book.id).await.is_err());
+        assert!(service.delete_book_with_policy(&book.id, Some(FileDeletionPolicy::DeletePermanently)).await.is_err());
+        assert!(book_path.exists());
+        assert!(database.get_all_books().await.unwrap().is_empty());
+
+        // Closing forgets the book and its temporary cover
+        service.close_external_file(&book.id).await.unwrap();
+        assert!(!service.is_session_book(&book.id).await);
+        assert!(!session_cover.exists());
+        assert!(service.get_book_by_id(&book.id).await.is_err());
+
+        // Adding to the library keeps the session's progress
+        let book = service.open_external_file(&book_path).await.unwrap();
+        let session_cover = book.cover_path.clone().unwrap();
+        service.update_reading_progress(&book.id, 0.25).await.unwrap();
+        let book_id = service.add_session_book_to_library(&book.id).await.unwrap();
+        assert!(!service.is_session_book(&book_id).await);
+        assert!(!session_cover.exists());
+        let saved = database.get_book_by_id(&book_id).await.unwrap();
+        assert_eq!(saved.reading_progress, 0.25);
+        assert!(saved.cover_path.unwrap().exists());
+        assert!(service.toggle_favorite(&book_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_import_directory_reports_duplicates_and_failures() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let library = temp_dir.path().join("library");
+        std::fs::create_dir_all(library.join("Author")).unwrap();
+        write_epub(&library.join("first.epub"), "First", "9780262162098");
+        write_epub(&library.join("Author/second.epub"), "Second", "9781400079988");
+        write_epub(&library.join("Author/first copy.epub"), "First", "9780262162098");
+        std::fs::write(library.join("Author/broken.epub"), "not a zip").unwrap();
+        std::fs::write(library.join("Author/metadata.opf"), "<package/>").unwrap();
+
+        let database = Arc::new(DatabaseService::new_in_memory().await.unwrap());
+        let image_cache = Arc::new(ImageCache::new(temp_dir.path().join("covers")).unwrap());
+        let service = Arc::new(BookService::new(database.clone(), image_cache));
+
+        let summary = service.import_directory(&library, false, None).await.unwrap();
+        assert_eq!(summary.imported.len(), 1);
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let summary = service.import_directory(&library, true, Some(sender)).await.unwrap();
+        assert_eq!(summary.imported.len(), 1);
+        assert_eq!(summary.duplicates.len(), 2);
+        assert_eq!(summary.failures.len(), 1);
+        assert_eq!(summary.failures[0].0, library.join("Author/broken.epub"));
+
+        let mut events = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            events.push(event);
+        }
+        assert_eq!(events.len(), 4);
+        assert!(events.iter().all(|event| event.total == 4));
+        assert_eq!(database.get_all_books().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_imported_books_notify_collection_subscriptions() {
+        use crate::models::library::{LibraryOrganizer, MatchType, SmartCollectionRules, SmartRule, SmartRuleField, SmartRuleOperator};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let database = Arc::new(DatabaseService::new_in_memory().await.unwrap());
+        let image_cache = Arc::new(ImageCache::new(temp_dir.path().join("covers")).unwrap());
+        let service = Arc::new(BookService::new(database.clone(), image_cache));
+        let library_service = Arc::new(LibraryService::new(database.pool()));
+        library_service.init_tables().await.unwrap();
+        service.set_library_service(library_service.clone()).await;
+
+        let rules = SmartCollectionRules {
+            rules: vec![SmartRule { field: SmartRuleField::Title, operator: SmartRuleOperator::Contains, value: "Dune".to_string() }],
+            match_type: MatchType::All,
+        };
+        let collection = library_service.create_smart_collection("Dune".to_string(), rules).await.unwrap();
+        library_service.subscribe_to_collection(&collection.id).await.unwrap();
+        let mut notifications = library_service.subscribe_match_notifications();
+
+        write_epub(&temp_dir.path().join("dune.epub"), "Dune", "9780441172719");
+        write_epub(&temp_dir.path().join("emma.epub"), "Emma", "9780141439587");
+        let dune = service.add_book(&temp_dir.path().join("dune.epub")).await.unwrap();
+        service.import_directory(temp_dir.path(), false, None).await.unwrap();
+
+        let notification = notifications.try_recv().unwrap();
+        assert_eq!((notification.collection_id, notification.book_id), (collection.id, dune));
+        assert!(notifications.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_finishing_a_book_advances_queue_and_series() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let database = Arc::new(DatabaseService::new_in_memory().await.unwrap());
+        let image_cache = Arc::new(ImageCache::new(temp_dir.path().join("covers")).unwrap());
+        let service = BookService::new(database.clone(), image_cache);
+        let library_service = Arc::new(LibraryService::new(database.pool()));
+        library_service.init_tables().await.unwrap();
+        service.set_library_service(library_service.clone()).await;
+
+        let mut books = Vec::new();
+        for (title, series_index) in [("Leviathan Wakes", Some(1.0)), ("Caliban's War", Some(2.0)), ("Dune", None), ("Emma", None)] {
+            let mut book = Book::new(title.to_string(), "Author".to_string(), temp_dir.path().join(title), 0, BookFormat::Epub);
+            book.series = series_index.map(|_| "The Expanse".to_string());
+            book.series_index = series_index;
+            database.insert_book(&book).await.unwrap();
+            books.push(book);
+        }
+        library_service.enqueue_book(&books[0].id).await.unwrap();
+        library_service.enqueue_book(&books[2].id).await.unwrap();
+        library_service.enqueue_book(&books[3].id).await.unwrap();
+        let preferences = Arc::new(PreferencesService::new(temp_dir.path().join("preferences.json")));
+        let mut updated = preferences.get();
+        updated.reading.series_auto_advance = SeriesAutoAdvance::MarkCurrentlyReading;
+        preferences.update(updated).await.unwrap();
+        service.set_preferences(preferences).await;
+
+        // Reaching the end starts the next queued book and the next in the series, as the preferences ask
+        let advance = service.update_reading_progress(&books[0].id, 1.0).await.unwrap().unwrap();
+        assert_eq!(advance.next_book.id, books[1].id);
+        assert_eq!(advance.action, SeriesAutoAdvance::MarkCurrentlyReading);
+        assert_eq!(service.get_book_by_id(&books[1].id).await.unwrap().reading_status, ReadingStatus::CurrentlyReading);
+        assert_eq!(service.get_book_by_id(&books[2].id).await.unwrap().reading_status, ReadingStatus::CurrentlyReading);
+        assert_eq!(library_service.get_reading_queue().await.unwrap(), vec![books[3].id.clone()]);
+
+        // Marking a book finished goes the same way
+        assert!(service.set_reading_status(&books[2].id, ReadingStatus::Finished).await.unwrap().is_none());
+        assert_eq!(service.get_book_by_id(&books[3].id).await.unwrap().reading_status, ReadingStatus::CurrentlyReading);
+        assert!(library_service.get_reading_queue().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_deleted_books_wait_in_the_trash() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let database = Arc::new(DatabaseService::new_in_memory().await.unwrap());
+        let image_cache = Arc::new(ImageCache::new(temp_dir.path().join("covers")).unwrap());
+        let service = BookService::new(database.clone(), image_cache);
+        let book_path = temp_dir.path().join("first.epub");
+        write_epub(&book_path, "First", "9780262162098");
+        let book_id = service.add_book(&book_path).await.unwrap();
+        let old = Book::new("Old".to_string(), "Author".to_string(), temp_dir.path().join("old.epub"), 0, BookFormat::Epub);
+        database.insert_book(&old).await.unwrap();
+
+        service.delete_book(&book_id).await.unwrap();
+        assert_eq!(database.get_all_books().await.unwrap().len(), 1);
+        assert_eq!(service.get_trash().await.unwrap()[0].book.id, book_id);
+
+        // Adding the same file again takes it out of the trash
+        assert_eq!(service.add_book(&book_path).await.unwrap(), book_id);
+        assert!(service.get_trash().await.unwrap().is_empty());
+
+        service.move_to_trash(&[book_id.clone(), old.id.clone()]).await.unwrap();
+        service.restore_from_trash(&book_id).await.unwrap();
+        assert!(service.restore_from_trash(&book_id).await.is_err());
+
+        // Only books deleted longer ago than the retention period are purged
+        database.set_book_deleted_at(&old.id, Some(Utc::now() - Duration::days(31))).await.unwrap();
+        assert_eq!(service.purge_expired_trash().await.unwrap(), 1);
+        assert!(service.get_trash().await.unwrap().is_empty());
+
+        // Retention comes from the preferences, where 0 keeps books until the trash is emptied
+        let preferences = Arc::new(PreferencesService::new(temp_dir.path().join("preferences.json")));
+        let mut updated = preferences.get();
+        updated.library.trash_retention_days = 0;
+        preferences.update(updated).await.unwrap();
+        service.set_preferences(preferences.clone()).await;
+        service.delete_book(&book_id).await.unwrap();
+        database.set_book_deleted_at(&book_id, Some(Utc::now() - Duration::days(400))).await.unwrap();
+        assert_eq!(service.purge_expired_trash().await.unwrap(), 0);
+        assert!(service.get_trash().await.unwrap()[0].purge_at.is_none());
+
+        let token = service.request_empty_trash().await.unwrap();
+        assert_eq!(token.impact.book_count, 1);
+        assert_eq!(service.empty_trash(&token.token).await.unwrap(), 1);
+        assert!(service.get_trash().await.unwrap().is_empty());
+        assert!(book_path.exists());
+
+        // Deleting everything only removes the books the prompt counted
+        database.insert_book(&old).await.unwrap();
+        let token = service.request_delete_all_books().await.unwrap();
+        let newer = Book::new("Newer".to_string(), "Author".to_string(), temp_dir.path().join("newer.epub"), 0, BookFormat::Epub);
+        database.insert_book(&newer).await.unwrap();
+        assert_eq!(service.delete_all_saved_books(&token.token).await.unwrap(), 1);
+        let remaining: Vec<String> = database.get_all_books().await.unwrap().into_iter().map(|book| book.id).collect();
+        assert_eq!(remaining, vec![newer.id.clone()]);
+
+        // Without an override, the policy comes from the preferences
+        let mut updated = preferences.get();
+        updated.library.file_deletion_policy = FileDeletionPolicy::DeletePermanently;
+        preferences.update(updated).await.unwrap();
+        std::fs::write(&newer.file_path, b"epub").unwrap();
+        assert_eq!(service.preview_book_deletion(&newer.id, None).await.unwrap().policy, FileDeletionPolicy::DeletePermanently);
+        service.delete_book_with_policy(&newer.id, None).await.unwrap();
+        assert!(!newer.file_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_reading_sessions_add_up_reading_time() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let database = Arc::new(DatabaseService::new_in_memory().await.unwrap());
+        let image_cache = Arc::new(ImageCache::new(temp_dir.path().join("covers")).unwrap());
+        let service = BookService::new(database.clone(), image_cache);
+        let mut book = Book::new("Long".to_string(), "Author".to_string(), temp_dir.path().join("long.epub"), 0, BookFormat::Epub);
+        book.page_count = Some(300);
+        database.insert_book(&book).await.unwrap();
+
+        let session = service.start_reading_session(&book.id).await.unwrap().unwrap();
+        service.update_reading_progress(&book.id, 0.1).await.unwrap();
+        let session = service.end_reading_session(&session.id).await.unwrap();
+        assert_eq!((session.pages_read, session.progress_end), (30, 0.1));
+
+        // An earlier session from yesterday, read back from the database
+        let mut earlier = ReadingSession::start(&book, Utc::now() - Duration::days(1));
+        earlier.end(&book, earlier.start_time + Duration::minutes(45));
+        database.save_reading_session(&earlier).await.unwrap();
+
+        assert_eq!(service.get_book_reading_time(&book.id).await.unwrap(), 45);
+        assert_eq!(service.get_total_reading_time().await.unwrap(), 45);
+        assert_eq!(service.get_book_reading_time("other").await.unwrap(), 0);
+
+        let heatmap = service.get_reading_heatmap(7).await.unwrap();
+        assert_eq!(heatmap.len(), 7);
+        assert_eq!(heatmap.last().unwrap().0, Local::now().date_naive());
+        let yesterday = earlier.start_time.with_timezone(&Local).date_naive();
+        assert_eq!(heatmap.iter().find(|(day, _)| *day == yesterday).unwrap().1, 45);
+        assert_eq!(heatmap.iter().map(|(_, minutes)| minutes).sum::<u32>(), 45);
+
+        // Reading times on cards follow the reading speed in the preferences
+        let preferences = Arc::new(PreferencesService::new(temp_dir.path().join("preferences.json")));
+        let mut updated = preferences.get();
+        updated.reading.reading_speed_wpm = 450;
+        preferences.update(updated).await.unwrap();
+        service.set_preferences(preferences).await;
+        let mut book = service.get_book_by_id(&book.id).await.unwrap();
+        book.word_count = Some(90_000);
+        database.update_book(&book).await.unwrap();
+        let card = &service.get_library_books().await.unwrap()[0];
+        assert_eq!(card.reading_time, book.reading_time_label(450));
+        assert_ne!(card.reading_time, book.reading_time_label(DEFAULT_READING_SPEED_WPM));
+    }
+}
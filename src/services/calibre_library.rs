@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Datelike, Utc};
+use sqlx::{Row, SqlitePool};
+
+use crate::models::{Book, BookFormat};
+use crate::services::epub_metadata::{parse_date, plain_text};
+use crate::utils::isbn::normalize_isbn;
+
+/// Book file used when a Calibre entry has several formats
+const FORMAT_PREFERENCE: &[&str] = &["epub", "azw3", "mobi", "pdf", "txt", "html"];
+
+/// A book as recorded in a Calibre library
+#[derive(Debug, Clone, Default)]
+pub struct CalibreBook {
+    pub calibre_id: i64,
+    pub title: String,
+    pub authors: Vec<String>,
+    pub series: Option<String>,
+    pub series_index: Option<f32>,
+    pub tags: Vec<String>,
+    pub rating: Option<u8>, // 1-5 stars
+    pub publisher: Option<String>,
+    pub languages: Vec<String>,
+    pub comments: Option<String>,
+    pub published: Option<DateTime<Utc>>,
+    pub added: Option<DateTime<Utc>>,
+    pub identifiers: Vec<(String, String)>, // Scheme and value, e.g. ("isbn", "978...")
+    pub folder: PathBuf,
+    pub files: Vec<PathBuf>,
+    pub cover: Option<PathBuf>,
+    pub custom_fields: Vec<(String, String)>, // Custom column name and value
+}
+
+impl CalibreBook {
+    /// The file to import, preferring formats the reader handles best
+    pub fn book_file(&self) -> Option<&PathBuf> {
+        FORMAT_PREFERENCE.iter()
+            .find_map(|preferred| self.files.iter().find(|file| Self::extension(file) == *preferred))
+    }
+
+    /// Build a library book from the Calibre record, without reading the book file
+    pub fn to_book(&self) -> Option<Book> {
+        let file_path = self.book_file()?;
+        let format = BookFormat::from_extension(&Self::extension(file_path))?;
+        let file_size = std::fs::metadata(file_path).map(|metadata| metadata.len()).unwrap_or(0);
+
+        let mut book = Book::new(self.title.clone(), self.authors.join(", "), file_path.clone(), file_size, format);
+        if book.author.is_empty() {
+            book.author = "Unknown Author".to_string();
+        }
+        book.series = self.series.clone();
+        book.series_index = self.series.as_ref().and(self.series_index);
+        book.tags = self.tags.clone();
+        book.rating = self.rating;
+        book.publisher = self.publisher.clone();
+        book.language = self.languages.first().cloned();
+        book.description = self.comments.as_deref().map(plain_text).filter(|text| !text.is_empty());
+        book.publication_date = self.published;
+        if let Some(added) = self.added {
+            book.added_date = added;
+        }
+        book.isbn = self.identifiers.iter()
+            .filter(|(scheme, _)| scheme == "isbn")
+            .find_map(|(_, value)| normalize_isbn(value));
+        book.identifiers = self.identifiers.iter()
+            .map(|(scheme, value)| format!("{}:{}", scheme, value))
+            .collect();
+        Some(book)
+    }
+
+    fn extension(path: &Path) -> String {
+        path.extension().map(|ext| ext.to_string_lossy().to_lowercase()).unwrap_or_default()
+    }
+}
+
+/// Reads a Calibre library: its `metadata.db` and the book folders beside it
+///
+/// The database is opened read-only so the library is never changed, even
+/// while Calibre itself has it open.
+pub struct CalibreLibrary {
+    root: PathBuf,
+    pool: SqlitePool,
+}
+
+impl CalibreLibrary {
+    pub async fn open(root: &Path) -> Result<Self> {
+        let database_path = root.join("metadata.db");
+        if !database_path.is_file() {
+            return Err(anyhow!("No Calibre library at {}: metadata.db not found", root.display()));
+        }
+
+        let pool = SqlitePool::connect(&format!("sqlite://{}?mode=ro", database_path.display())).await?;
+        Ok(Self { root: root.to_path_buf(), pool })
+    }
+
+    /// Every book in the library, in Calibre's order
+    pub async fn books(&self) -> Result<Vec<CalibreBook>> {
+        let mut authors = self.values("SELECT l.book, a.name FROM books_authors_link l JOIN authors a ON a.id = l.author ORDER BY l.id").await?;
+        let mut tags = self.values("SELECT l.book, t.name FROM books_tags_link l JOIN tags t ON t.id = l.tag ORDER BY t.name").await?;
+        let mut series = self.values("SELECT l.book, s.name FROM books_series_link l JOIN series s ON s.id = l.series").await?;
+        let mut ratings = self.values("SELECT l.book, CAST(r.rating AS TEXT) FROM books_ratings_link l JOIN ratings r ON r.id = l.rating").await?;
+        let mut publishers = self.values("SELECT l.book, p.name FROM books_publishers_link l JOIN publishers p ON p.id = l.publisher").await?;
+        let mut languages = self.values("SELECT l.book, g.lang_code FROM books_languages_link l JOIN languages g ON g.id = l.lang_code ORDER BY l.item_order").await?;
+        let mut comments = self.values("SELECT book, text FROM comments").await?;
+        let mut identifiers = self.values("SELECT book, type || ':' || val FROM identifiers").await?;
+        let mut files = self.values("SELECT book, name || '.' || lower(format) FROM data").await?;
+        let mut custom_fields = self.custom_fields().await?;
+
+        let rows = sqlx::query("SELECT id, title, path, has_cover, series_index, pubdate, timestamp FROM books ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut books = Vec::new();
+        for row in rows {
+            let id: i64 = row.get("id");
+            let folder = self.root.join(row.get::<String, _>("path"));
+            let dates = |column: &str| {
+                row.get::<Option<String>, _>(column)
+                    .and_then(|date| parse_date(&date))
+                    // Calibre stores unknown dates as the year 101
+                    .filter(|date| date.year() > 1000)
+            };
+
+            books.push(CalibreBook {
+                calibre_id: id,
+                title: row.get("title"),
+                authors: authors.remove(&id).unwrap_or_default(),
+                series: series.remove(&id).and_then(|names| names.into_iter().next()),
+                series_index: row.get::<Option<f64>, _>("series_index").map(|index| index as f32),
+                tags: tags.remove(&id).unwrap_or_default(),
+                // Calibre rates in half stars from 0 to 10
+                rating: ratings.remove(&id)
+                    .and_then(|values| values.first()?.parse::<u8>().ok())
+                    .map(|rating| rating.div_ceil(2).min(5))
+                    .filter(|stars| *stars > 0),
+                publisher: publishers.remove(&id).and_then(|names| names.into_iter().next()),
+                languages: languages.remove(&id).unwrap_or_default(),
+                comments: comments.remove(&id).and_then(|texts| texts.into_iter().next()),
+                published: dates("pubdate"),
+                added: dates("timestamp"),
+                identifiers: identifiers.remove(&id).unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|identifier| {
+                        let (scheme, value) = identifier.split_once(':')?;
+                        Some((scheme.to_lowercase(), value.to_string()))
+                    })
+                    .collect(),
+                files: files.remove(&id).unwrap_or_default()
+                    .into_iter()
+                    .map(|name| folder.join(name))
+                    .collect(),
+                cover: Some(folder.join("cover.jpg")).filter(|_| row.get::<i64, _>("has_cover") != 0),
+                custom_fields: custom_fields.remove(&id).unwrap_or_default(),
+                folder,
+            });
+        }
+
+        Ok(books)
+    }
+
+    /// Values of user-defined columns, with several values joined by commas
+    async fn custom_fields(&self) -> Result<HashMap<i64, Vec<(String, String)>>> {
+        // Composite columns are computed by Calibre and have no table
+        let columns = sqlx::query(
+            "SELECT id, name, normalized FROM custom_columns WHERE datatype != 'composite' AND mark_for_delete = 0 ORDER BY id"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut fields: HashMap<i64, Vec<(String, String)>> = HashMap::new();
+        for column in columns {
+            let id: i64 = column.get("id");
+            let name: String = column.get("name");
+            let query = if column.get::<i64, _>("normalized") != 0 {
+                format!(
+                    "SELECT l.book, CAST(c.value AS TEXT) FROM books_custom_column_{0}_link l \
+                     JOIN custom_column_{0} c ON c.id = l.value ORDER BY c.value",
+                    id
+                )
+            } else {
+                format!("SELECT book, CAST(value AS TEXT) FROM custom_column_{}", id)
+            };
+
+            for (book, values) in self.values(&query).await? {
+                fields.entry(book).or_default().push((name.clone(), values.join(", ")));
+            }
+        }
+        Ok(fields)
+    }
+
+    /// Run a `(book, value)` query and group the values by book
+    async fn values(&self, query: &str) -> Result<HashMap<i64, Vec<String>>> {
+        let rows = sqlx::query(query).fetch_all(&self.pool).await?;
+
+        let mut values: HashMap<i64, Vec<String>> = HashMap::new();
+        for row in rows {
+            if let Some(value) = row.get::<Option<String>, _>(1) {
+                values.entry(row.get(0)).or_default().push(value);
+            }
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::services::book_service::BookService;
+    use crate::services::database::DatabaseService;
+    use crate::utils::image_cache::ImageCache;
+
+    const SCHEMA: &str = r#"
+        CREATE TABLE books (id INTEGER PRIMARY KEY, title TEXT, path TEXT, has_cover BOOL, series_index REAL, pubdate TIMESTAMP, timestamp TIMESTAMP);
+        CREATE TABLE authors (id INTEGER PRIMARY KEY, name TEXT);
+        CREATE TABLE books_authors_link (id INTEGER PRIMARY KEY, book INTEGER, author INTEGER);
+        CREATE TABLE tags (id INTEGER PRIMARY KEY, name TEXT);
+        CREATE TABLE books_tags_link (id INTEGER PRIMARY KEY, book INTEGER, tag INTEGER);
+        CREATE TABLE series (id INTEGER PRIMARY KEY, name TEXT);
+        CREATE TABLE books_series_link (id INTEGER PRIMARY KEY, book INTEGER, series INTEGER);
+        CREATE TABLE ratings (id INTEGER PRIMARY KEY, rating INTEGER);
+        CREATE TABLE books_ratings_link (id INTEGER PRIMARY KEY, book INTEGER, rating INTEGER);
+        CREATE TABLE publishers (id INTEGER PRIMARY KEY, name TEXT);
+        CREATE TABLE books_publishers_link (id INTEGER PRIMARY KEY, book INTEGER, publisher INTEGER);
+        CREATE TABLE languages (id INTEGER PRIMARY KEY, lang_code TEXT);
+        CREATE TABLE books_languages_link (id INTEGER PRIMARY KEY, book INTEGER, lang_code INTEGER, item_order INTEGER);
+        CREATE TABLE comments (id INTEGER PRIMARY KEY, book INTEGER, text TEXT);
+        CREATE TABLE identifiers (id INTEGER PRIMARY KEY, book INTEGER, type TEXT, val TEXT);
+        CREATE TABLE data (id INTEGER PRIMARY KEY, book INTEGER, format TEXT, name TEXT);
+        CREATE TABLE custom_columns (id INTEGER PRIMARY KEY, label TEXT, name TEXT, datatype TEXT, mark_for_delete BOOL, normalized BOOL);
+        CREATE TABLE custom_column_1 (id INTEGER PRIMARY KEY, book INTEGER, value INTEGER);
+        CREATE TABLE custom_column_2 (id INTEGER PRIMARY KEY, value TEXT);
+        CREATE TABLE books_custom_column_2_link (id INTEGER PRIMARY KEY, book INTEGER, value INTEGER);
+
+        INSERT INTO books VALUES
+            (1, 'Leviathan Wakes', 'James S. A. Corey/Leviathan Wakes (1)', 1, 1.0, '2011-06-15 00:00:00+00:00', '2020-01-02 10:00:00+00:00'),
+            (2, 'Caliban''s War', 'James S. A. Corey/Caliban''s War (2)', 0, 2.0, '0101-01-01 00:00:00+00:00', '2020-01-03 10:00:00+00:00'),
+            (3, 'Missing Files', 'Someone/Missing Files (3)', 0, 1.0, NULL, NULL);
+        INSERT INTO authors VALUES (1, 'Daniel Abraham'), (2, 'Ty Franck');
+        INSERT INTO books_authors_link VALUES (1, 1, 1), (2, 1, 2), (3, 2, 1);
+        INSERT INTO tags VALUES (1, 'Space Opera'), (2, 'Fiction');
+        INSERT INTO books_tags_link VALUES (1, 1, 1), (2, 1, 2);
+        INSERT INTO series VALUES (1, 'The Expanse');
+        INSERT INTO books_series_link VALUES (1, 1, 1), (2, 2, 1);
+        INSERT INTO ratings VALUES (1, 8);
+        INSERT INTO books_ratings_link VALUES (1, 1, 1);
+        INSERT INTO publishers VALUES (1, 'Orbit');
+        INSERT INTO books_publishers_link VALUES (1, 1, 1);
+        INSERT INTO languages VALUES (1, 'eng');
+        INSERT INTO books_languages_link VALUES (1, 1, 1, 0);
+        INSERT INTO comments VALUES (1, 1, '<p>Humanity has colonized the solar system.</p>');
+        INSERT INTO identifiers VALUES (1, 1, 'isbn', '9780316129084'), (2, 1, 'goodreads', '8855321');
+        INSERT INTO data VALUES (1, 1, 'PDF', 'Leviathan Wakes'), (2, 1, 'EPUB', 'Leviathan Wakes'), (3, 2, 'EPUB', 'Caliban''s War');
+        INSERT INTO custom_columns VALUES (1, 'read_count', 'Times Read', 'int', 0, 0), (2, 'shelf', 'Shelf', 'text', 0, 1);
+        INSERT INTO custom_column_1 VALUES (1, 1, 3);
+        INSERT INTO custom_column_2 VALUES (1, 'Favourites'), (2, 'Sci-fi');
+        INSERT INTO books_custom_column_2_link VALUES (1, 1, 2), (2, 1, 1);
+    "#;
+
+    async fn create_library(root: &Path) {
+        let database_path = root.join("metadata.db");
+        let pool = SqlitePool::connect(&format!("sqlite://{}?mode=rwc", database_path.display())).await.unwrap();
+        sqlx::raw_sql(SCHEMA).execute(&pool).await.unwrap();
+        pool.close().await;
+
+        let first = root.join("James S. A. Corey/Leviathan Wakes (1)");
+        let second = root.join("James S. A. Corey/Caliban's War (2)");
+        std::fs::create_dir_all(&first).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+        std::fs::write(first.join("Leviathan Wakes.epub"), "epub").unwrap();
+        std::fs::write(first.join("Leviathan Wakes.pdf"), "pdf").unwrap();
+        std::fs::write(second.join("Caliban's War.epub"), "epub").unwrap();
+        image::RgbImage::new(4, 6).save(first.join("cover.jpg")).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_import_calibre_library() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path().join("Calibre Library");
+        std::fs::create_dir(&root).unwrap();
+        create_library(&root).await;
+
+        let entries = CalibreLibrary::open(&root).await.unwrap().books().await.unwrap();
+        assert_eq!(entries.len(), 3);
+        let book = entries[0].to_book().unwrap();
+        assert_eq!(book.title, "Leviathan Wakes");
+        assert_eq!(book.author, "Daniel Abraham, Ty Franck");
+        assert_eq!(book.file_format, BookFormat::Epub);
+        assert_eq!((book.series.as_deref(), book.series_index), (Some("The Expanse"), Some(1.0)));
+        assert_eq!(book.tags, ["Fiction", "Space Opera"]);
+        assert_eq!(book.rating, Some(4));
+        assert_eq!(book.publisher.as_deref(), Some("Orbit"));
+        assert_eq!(book.language.as_deref(), Some("eng"));
+        assert_eq!(book.description.as_deref(), Some("Humanity has colonized the solar system."));
+        assert_eq!(book.isbn.as_deref(), Some("9780316129084"));
+        assert_eq!(book.publication_date.unwrap().year(), 2011);
+        assert!(entries[1].to_book().unwrap().publication_date.is_none());
+        assert!(entries[2].to_book().is_none());
+
+        let database = Arc::new(DatabaseService::new_in_memory().await.unwrap());
+        let image_cache = Arc::new(ImageCache::new(temp_dir.path().join("covers")).unwrap());
+        let service = BookService::new(database.clone(), image_cache);
+
+        let summary = service.import_calibre_library(&root, None).await.unwrap();
+        assert_eq!(summary.imported.len(), 2);
+        assert_eq!(summary.failures.len(), 1);
+        let fields = database.get_book_custom_fields(&summary.imported[0]).await.unwrap();
+        assert_eq!(fields, [
+            ("Shelf".to_string(), "Favourites, Sci-fi".to_string()),
+            ("Times Read".to_string(), "3".to_string()),
+        ]);
+
+        let summary = service.import_calibre_library(&root, None).await.unwrap();
+        assert_eq!((summary.imported.len(), summary.duplicates.len()), (0, 2));
+    }
+}
@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use anyhow::Result;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexSet};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::models::Book;
+use crate::services::reading_service::ReadingService;
+use crate::utils::isbn::{find_isbns, normalize_isbn};
+
+static DOI_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b10\.\d{4,9}/[-._;()/:A-Za-z0-9]+").unwrap());
+
+/// Titles shorter than this are too likely to match ordinary prose
+const MIN_TITLE_LENGTH: usize = 8;
+
+/// How one book refers to another
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum CitationKind {
+    Isbn,
+    Doi,
+    Title,
+    Author,
+}
+
+impl CitationKind {
+    pub fn to_string(&self) -> String {
+        match self {
+            CitationKind::Isbn => "isbn".to_string(),
+            CitationKind::Doi => "doi".to_string(),
+            CitationKind::Title => "title".to_string(),
+            CitationKind::Author => "author".to_string(),
+        }
+    }
+
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s {
+            "isbn" => Some(CitationKind::Isbn),
+            "doi" => Some(CitationKind::Doi),
+            "title" => Some(CitationKind::Title),
+            "author" => Some(CitationKind::Author),
+            _ => None,
+        }
+    }
+}
+
+/// A reference found in a book's text
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Citation {
+    pub source_book_id: String,
+    pub target_book_id: Option<String>, // None for identifiers not in the library
+    pub identifier: Option<String>,     // ISBN-13 or DOI, when cited by identifier
+    pub kind: CitationKind,
+    pub occurrences: u32,
+}
+
+/// Book node in the citation graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CitationNode {
+    pub book_id: String,
+    pub title: String,
+    pub author: String,
+}
+
+/// Citations and mentions between library books
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CitationGraph {
+    pub nodes: Vec<CitationNode>,
+    pub edges: Vec<Citation>,              // Between library books
+    pub external_references: Vec<Citation>, // DOIs and ISBNs outside the library
+}
+
+/// Outcome of a library analysis run
+#[derive(Debug, Clone, Default)]
+pub struct CitationAnalysisSummary {
+    pub books_analyzed: usize,
+    pub books_failed: usize,
+    pub citations_found: usize,
+}
+
+/// A library book's title or author, as a whole-word pattern
+struct LibraryPhrase {
+    book_id: String,
+    kind: CitationKind,
+    phrase: String,
+    pattern: Regex,
+}
+
+/// Patterns for the library's ISBNs, titles and authors, built once per analysis run
+pub struct CitationMatcher {
+    isbns: HashMap<String, String>, // ISBN-13 to book id
+    phrases: Vec<LibraryPhrase>,
+    // Finds which phrases occur in one pass; None when too large to compile
+    phrase_set: Option<RegexSet>,
+}
+
+impl CitationMatcher {
+    pub fn new(library: &[Book]) -> Self {
+        let isbns = library
+            .iter()
+            .filter_map(|book| book.isbn.as_deref().and_then(normalize_isbn).map(|isbn| (isbn, book.id.clone())))
+            .collect();
+
+        let mut phrases = Vec::new();
+        for book in library {
+            let title = book.title.trim().to_lowercase();
+            if title.chars().count() >= MIN_TITLE_LENGTH {
+                phrases.push((book, CitationKind::Title, title));
+            }
+            // Only full names
+            let author = book.author.trim().to_lowercase();
+            if author.contains(' ') && author != "unknown author" {
+                phrases.push((book, CitationKind::Author, author));
+            }
+        }
+
+        let patterns: Vec<String> = phrases.iter()
+            .map(|(_, _, phrase)| format!(r"\b{}\b", regex::escape(phrase)))
+            .collect();
+        let phrase_set = match RegexSet::new(&patterns) {
+            Ok(set) => Some(set),
+            Err(e) => {
+                warn!("Matching citation phrases one at a time: {}", e);
+                None
+            }
+        };
+        let phrases = phrases.into_iter()
+            .zip(&patterns)
+            .filter_map(|((book, kind, phrase), pattern)| {
+                Regex::new(pattern).ok().map(|pattern| LibraryPhrase {
+                    book_id: book.id.clone(),
+                    kind,
+                    phrase,
+                    pattern,
+                })
+            })
+            .collect();
+
+        Self { isbns, phrases, phrase_set }
+    }
+
+    /// Phrases that occur in lowercased text
+    fn matching_phrases(&self, text: &str) -> Vec<&LibraryPhrase> {
+        match &self.phrase_set {
+            Some(set) if set.len() == self.phrases.len() => {
+                set.matches(text).into_iter().map(|index| &self.phrases[index]).collect()
+            }
+            _ => self.phrases.iter().collect(),
+        }
+    }
+}
+
+/// Detects references between library books and stores them as a graph
+#[derive(Clone)]
+pub struct CitationService {
+    pool: SqlitePool,
+}
+
+impl CitationService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize citation tables
+    pub async fn init_tables(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS book_citations (
+                source_book_id TEXT NOT NULL,
+                target_book_id TEXT,
+                identifier TEXT,
+                kind TEXT NOT NULL,
+                occurrences INTEGER NOT NULL,
+                analyzed_at TEXT NOT NULL,
+                FOREIGN KEY (source_book_id) REFERENCES books (id) ON DELETE CASCADE,
+                FOREIGN KEY (target_book_id) REFERENCES books (id) ON DELETE CASCADE
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_book_citations_source ON book_citations(source_book_id);")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_book_citations_target ON book_citations(target_book_id);")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Analyze every book in the background, replacing previously stored citations
+    pub fn spawn_library_analysis(
+        &self,
+        books: Vec<Book>,
+        reading_service: Arc<ReadingService>,
+    ) -> JoinHandle<Result<CitationAnalysisSummary>> {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut summary = CitationAnalysisSummary::default();
+            let matcher = CitationMatcher::new(&books);
+
+            for book in &books {
+                let content = match reading_service.load_book_content(book).await {
+                    Ok(content) => content,
+                    Err(e) => {
+                        warn!("Skipping citation analysis for {}: {}", book.title, e);
+                        summary.books_failed += 1;
+                        continue;
+                    }
+                };
+
+                let text = content.chapters
+                    .iter()
+                    .map(|chapter| chapter.content.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let citations = Self::find_citations(book, &text, &matcher);
+
+                service.save_citations(&book.id, &citations).await?;
+                summary.books_analyzed += 1;
+                summary.citations_found += citations.len();
+            }
+
+            info!(
+                "Citation analysis finished: {} books, {} citations",
+                summary.books_analyzed, summary.citations_found
+            );
+            Ok(summary)
+        })
+    }
+
+    /// Find references to other library books, DOIs and ISBNs in a book's text
+    pub fn find_citations(source: &Book, text: &str, matcher: &CitationMatcher) -> Vec<Citation> {
+        let mut counts: HashMap<(Option<String>, Option<String>, CitationKind), u32> = HashMap::new();
+        let lowered = text.to_lowercase();
+
+        for isbn in find_isbns(text) {
+            let target = matcher.isbns.get(&isbn).filter(|book_id| **book_id != source.id).cloned();
+            *counts.entry((target, Some(isbn), CitationKind::Isbn)).or_default() += 1;
+        }
+
+        for doi in DOI_PATTERN.find_iter(text) {
+            let doi = doi.as_str().trim_end_matches(['.', ',', ';', ':', ')']);
+            *counts.entry((None, Some(doi.to_lowercase()), CitationKind::Doi)).or_default() += 1;
+        }
+
+        let source_author = source.author.trim().to_lowercase();
+        for phrase in matcher.matching_phrases(&lowered) {
+            // Not the source itself, nor its own author
+            if phrase.book_id == source.id || (phrase.kind == CitationKind::Author && phrase.phrase == source_author) {
+                continue;
+            }
+            let occurrences = phrase.pattern.find_iter(&lowered).count() as u32;
+            if occurrences > 0 {
+                *counts.entry((Some(phrase.book_id.clone()), None, phrase.kind)).or_default() += occurrences;
+            }
+        }
+
+        let mut citations: Vec<Citation> = counts
+            .into_iter()
+            .map(|((target_book_id, identifier, kind), occurrences)| Citation {
+                source_book_id: source.id.clone(),
+                target_book_id,
+                identifier,
+                kind,
+                occurrences,
+            })
+            .collect();
+        citations.sort_by_key(|citation| std::cmp::Reverse(citation.occurrences));
+        citations
+    }
+
+    /// Replace the stored citations for a book
+    pub async fn save_citations(&self, source_book_id: &str, citations: &[Citation]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let analyzed_at = Utc::now().to_rfc3339();
+
+        sqlx::query("DELETE FROM book_citations WHERE source_book_id = ?")
+            .bind(source_book_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for citation in citations {
+            sqlx::query(
+                r#"
+                INSERT INTO book_citations (source_book_id, target_book_id, identifier, kind, occurrences, analyzed_at)
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&citation.source_book_id)
+            .bind(&citation.target_book_id)
+            .bind(&citation.identifier)
+            .bind(citation.kind.to_string())
+            .bind(citation.occurrences as i64)
+            .bind(&analyzed_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Get the books and identifiers a book refers to
+    pub async fn get_citations_from(&self, book_id: &str) -> Result<Vec<Citation>> {
+        self.query_citations("SELECT * FROM book_citations WHERE source_book_id = ? ORDER BY occurrences DESC", Some(book_id))
+            .await
+    }
+
+    /// Get the books that refer to a book
+    pub async fn get_citations_to(&self, book_id: &str) -> Result<Vec<Citation>> {
+        self.query_citations("SELECT * FROM book_citations WHERE target_book_id = ? ORDER BY occurrences DESC", Some(book_id))
+            .await
+    }
+
+    /// Build the citation graph for the given library books
+    pub async fn get_citation_graph(&self, books: &[Book]) -> Result<CitationGraph> {
+        let citations = self.query_citations("SELECT * FROM book_citations ORDER BY occurrences DESC", None).await?;
+        let (edges, external_references) = citations
+            .into_iter()
+            .partition(|citation| citation.target_book_id.is_some());
+
+        let nodes = books
+            .iter()
+            .map(|book| CitationNode {
+                book_id: book.id.clone(),
+                title: book.title.clone(),
+                author: book.author.clone(),
+            })
+            .collect();
+
+        Ok(CitationGraph {
+            nodes,
+            edges,
+            external_references,
+        })
+    }
+
+    async fn query_citations(&self, sql: &str, book_id: Option<&str>) -> Result<Vec<Citation>> {
+        let mut query = sqlx::query(sql);
+        if let Some(book_id) = book_id {
+            query = query.bind(book_id);
+        }
+
+        let rows = query.fetch_all(&self.pool).await?;
+        let mut citations = Vec::new();
+        for row in rows {
+            let kind: String = row.get("kind");
+            let Some(kind) = CitationKind::from_string(&kind) else {
+                continue;
+            };
+            citations.push(Citation {
+                source_book_id: row.get("source_book_id"),
+                target_book_id: row.get("target_book_id"),
+                identifier: row.get("identifier"),
+                kind,
+                occurrences: row.get::<i64, _>("occurrences") as u32,
+            });
+        }
+
+        Ok(citations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::models::BookFormat;
+
+    fn book(title: &str, author: &str, isbn: Option<&str>) -> Book {
+        let mut book = Book::new(
+            title.to_string(),
+            author.to_string(),
+            PathBuf::from(format!("/books/{}.epub", title)),
+            1024,
+            BookFormat::Epub,
+        );
+        book.isbn = isbn.map(str::to_string);
+        book
+    }
+
+    #[test]
+    fn test_find_citations_by_isbn_title_and_author() {
+        let source = book("Survey of Type Systems", "Ana Lima", None);
+        let cited = book("Types and Programming Languages", "Benjamin Pierce", Some("0-262-16209-1"));
+        let library = vec![source.clone(), cited.clone()];
+
+        let text = "As shown in Types and Programming Languages (ISBN 978-0-262-16209-8), \
+                    Benjamin Pierce argues otherwise. See also doi:10.1145/3290380.";
+        let citations = CitationService::find_citations(&source, text, &CitationMatcher::new(&library));
+
+        let kinds = |kind| citations.iter().find(|c| c.kind == kind).unwrap();
+        assert_eq!(kinds(CitationKind::Isbn).target_book_id.as_deref(), Some(cited.id.as_str()));
+        assert_eq!(kinds(CitationKind::Title).target_book_id.as_deref(), Some(cited.id.as_str()));
+        assert_eq!(kinds(CitationKind::Author).target_book_id.as_deref(), Some(cited.id.as_str()));
+        assert_eq!(kinds(CitationKind::Doi).identifier.as_deref(), Some("10.1145/3290380"));
+        assert_eq!(kinds(CitationKind::Doi).target_book_id, None);
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::services::epub_parser::EpubFeatures;
+use crate::services::path_resolver::PathResolver;
+
+/// Number of individual failures kept for diagnostics
+const MAX_RECENT_FAILURES: usize = 50;
+
+/// Success and failure counts for a single EPUB feature
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FeatureCounts {
+    pub successes: u64,
+    pub failures: u64,
+}
+
+/// A failed parse, described only by the book's features and an error category
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompatibilityFailure {
+    pub recorded_at: DateTime<Utc>,
+    pub features: Vec<String>,
+    pub error_kind: String,
+}
+
+/// Persisted ledger contents
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct LedgerData {
+    total_successes: u64,
+    total_failures: u64,
+    features: HashMap<String, FeatureCounts>,
+    recent_failures: Vec<CompatibilityFailure>,
+}
+
+/// Local ledger of EPUB parse outcomes by format feature
+///
+/// Holds no titles, paths or content, so the report can be shared as-is.
+pub struct CompatibilityLedger {
+    ledger_path: PathBuf,
+    data: LedgerData,
+}
+
+impl CompatibilityLedger {
+    /// Open the ledger stored at the given path, starting empty if it does not exist
+    pub fn open(ledger_path: PathBuf) -> Result<Self> {
+        let data = if ledger_path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&ledger_path)?)?
+        } else {
+            LedgerData::default()
+        };
+
+        Ok(Self { ledger_path, data })
+    }
+
+    /// Open the ledger in the application data directory
+    pub fn open_default() -> Result<Self> {
+        let app_dir = PathResolver::get_app_data_directory()?;
+        PathResolver::ensure_directory_exists(&app_dir)?;
+        Self::open(app_dir.join("compatibility_ledger.json"))
+    }
+
+    /// Record a successful parse
+    pub async fn record_success(&mut self, features: &EpubFeatures) -> Result<()> {
+        self.data.total_successes += 1;
+        for tag in features.tags() {
+            self.data.features.entry(tag).or_default().successes += 1;
+        }
+        self.save().await
+    }
+
+    /// Record a failed parse
+    pub async fn record_failure(&mut self, features: &EpubFeatures, error_kind: &str) -> Result<()> {
+        let tags = features.tags();
+        self.data.total_failures += 1;
+        for tag in &tags {
+            self.data.features.entry(tag.clone()).or_default().failures += 1;
+        }
+
+        self.data.recent_failures.push(CompatibilityFailure {
+            recorded_at: Utc::now(),
+            features: tags,
+            error_kind: error_kind.to_string(),
+        });
+        if self.data.recent_failures.len() > MAX_RECENT_FAILURES {
+            let excess = self.data.recent_failures.len() - MAX_RECENT_FAILURES;
+            self.data.recent_failures.drain(..excess);
+        }
+
+        self.save().await
+    }
+
+    /// Build a diagnostics report from the ledger
+    pub fn report(&self) -> CompatibilityReport {
+        let mut features: Vec<FeatureCompatibility> = self.data.features
+            .iter()
+            .map(|(feature, counts)| FeatureCompatibility {
+                feature: feature.clone(),
+                successes: counts.successes,
+                failures: counts.failures,
+            })
+            .collect();
+        features.sort_by(|a, b| b.failures.cmp(&a.failures).then_with(|| a.feature.cmp(&b.feature)));
+
+        CompatibilityReport {
+            total_successes: self.data.total_successes,
+            total_failures: self.data.total_failures,
+            features,
+            recent_failures: self.data.recent_failures.clone(),
+        }
+    }
+
+    /// Remove all recorded entries
+    pub async fn clear(&mut self) -> Result<()> {
+        self.data = LedgerData::default();
+        self.save().await
+    }
+
+    async fn save(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self.data)?;
+        tokio::fs::write(&self.ledger_path, json).await?;
+        Ok(())
+    }
+}
+
+/// Parse outcomes for a single EPUB feature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeatureCompatibility {
+    pub feature: String,
+    pub successes: u64,
+    pub failures: u64,
+}
+
+/// Diagnostics summary of the compatibility ledger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompatibilityReport {
+    pub total_successes: u64,
+    pub total_failures: u64,
+    pub features: Vec<FeatureCompatibility>,
+    pub recent_failures: Vec<CompatibilityFailure>,
+}
+
+impl CompatibilityReport {
+    /// Render the report as plain text for bug reports
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "EPUB compatibility: {} opened, {} failed\n",
+            self.total_successes, self.total_failures
+        );
+
+        if !self.features.is_empty() {
+            text.push_str("\nBy feature:\n");
+            for feature in &self.features {
+                text.push_str(&format!(
+                    "  {}: {} ok, {} failed\n",
+                    feature.feature, feature.successes, feature.failures
+                ));
+            }
+        }
+
+        if !self.recent_failures.is_empty() {
+            text.push_str("\nRecent failures:\n");
+            for failure in &self.recent_failures {
+                text.push_str(&format!(
+                    "  {} [{}] {}\n",
+                    failure.recorded_at.format("%Y-%m-%d %H:%M"),
+                    failure.error_kind,
+                    failure.features.join(", ")
+                ));
+            }
+        }
+
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::epub_parser::NavType;
+    use tempfile::TempDir;
+
+    fn ncx_only_features() -> EpubFeatures {
+        EpubFeatures {
+            version: Some("2.0".to_string()),
+            zip_encryption: false,
+            encryption_xml: false,
+            fixed_layout: false,
+            nav_type: Some(NavType::Ncx),
+            media_overlays: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ledger_counts_outcomes_by_feature() {
+        let temp_dir = TempDir::new().unwrap();
+        let ledger_path = temp_dir.path().join("ledger.json");
+
+        let mut ledger = CompatibilityLedger::open(ledger_path.clone()).unwrap();
+        ledger.record_success(&ncx_only_features()).await.unwrap();
+
+        let mut fixed_layout = ncx_only_features();
+        fixed_layout.fixed_layout = true;
+        ledger.record_failure(&fixed_layout, "document").await.unwrap();
+
+        // Reopen to make sure the ledger was persisted
+        let report = CompatibilityLedger::open(ledger_path).unwrap().report();
+        assert_eq!(report.total_successes, 1);
+        assert_eq!(report.total_failures, 1);
+        assert_eq!(report.features[0].feature, "fixed-layout");
+        assert_eq!(report.features[0].failures, 1);
+
+        let ncx = report.features.iter().find(|f| f.feature == "nav:ncx").unwrap();
+        assert_eq!((ncx.successes, ncx.failures), (1, 1));
+        assert_eq!(report.recent_failures[0].error_kind, "document");
+        assert!(report.to_text().contains("fixed-layout: 0 ok, 1 failed"));
+    }
+}
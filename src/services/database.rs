@@ -1,14 +1,33 @@
 use sqlx::{SqlitePool, Row};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use tracing::{info, error};
+use tracing::{info, warn, error};
 
 use crate::models::{Book, BookCollection, BookFormat};
+use crate::models::book::ReadingSession;
 use crate::models::library::ReadingStatus;
 use crate::services::book_service::{BookFilter, BookSort, SortField, SortOrder};
 use crate::services::database_initializer::{DatabaseInitializer, DatabaseInitError};
 use crate::services::path_resolver::PathResolver;
+use crate::services::folder_watcher::WatchedFolder;
+use crate::services::search_indexer::{SearchIndexState, SearchIndexStatus};
+
+/// Connections kept open; SQLite takes one writer at a time, so more mainly help reads
+const MAX_CONNECTIONS: u32 = 8;
+/// How long a write waits for another connection's lock before failing with "database is locked"
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connection settings checked at startup
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatabaseHealth {
+    pub journal_mode: String,
+    pub busy_timeout_ms: i64,
+    pub foreign_keys: bool,
+    pub writable: bool,
+}
 
 /// Database service for managing SQLite operations
 pub struct DatabaseService {
@@ -42,9 +61,21 @@ impl DatabaseService {
         let validated_path = initializer.ensure_database_ready().await
             .map_err(|e| anyhow::anyhow!("Database initialization failed: {}", e))?;
         
-        // Connect to database
-        let database_url = format!("sqlite://{}?mode=rwc", validated_path.display());
-        let pool = SqlitePool::connect(&database_url).await
+        // Connect to database. WAL lets reads go on while an import writes, and
+        // the busy timeout makes writers queue for the lock instead of failing.
+        let options = SqliteConnectOptions::new()
+            .filename(&validated_path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .synchronous(SqliteSynchronous::Normal)
+            .busy_timeout(BUSY_TIMEOUT)
+            .foreign_keys(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(MAX_CONNECTIONS)
+            .min_connections(1)
+            .acquire_timeout(BUSY_TIMEOUT * 2)
+            .connect_with(options)
+            .await
             .map_err(|e| anyhow::anyhow!("Failed to connect to database: {}", e))?;
         
         let service = Self { pool };
@@ -52,6 +83,16 @@ impl DatabaseService {
         // Initialize schema
         service.initialize_schema().await
             .map_err(|e| anyhow::anyhow!("Failed to initialize database schema: {}", e))?;
+
+        let health = service.health_check().await
+            .map_err(|e| anyhow::anyhow!("Database health check failed: {}", e))?;
+        if health.journal_mode != "wal" {
+            // Some network file systems can't share WAL memory, so SQLite falls back
+            warn!("Database is in {} journal mode, not WAL; concurrent writes may be slower", health.journal_mode);
+        }
+        if !health.writable {
+            return Err(anyhow::anyhow!("Database is locked by another process"));
+        }
         
         info!("Database service initialized successfully");
         Ok(service)
@@ -66,6 +107,38 @@ impl DatabaseService {
         Ok(service)
     }
 
+    /// The connection pool, for services that keep their own tables in the library database
+    pub fn pool(&self) -> SqlitePool {
+        self.pool.clone()
+    }
+
+    /// Check the connection settings and that a write lock can be taken
+    pub async fn health_check(&self) -> Result<DatabaseHealth> {
+        let mut connection = self.pool.acquire().await?;
+        let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&mut *connection)
+            .await?;
+        let busy_timeout_ms: i64 = sqlx::query_scalar("PRAGMA busy_timeout")
+            .fetch_one(&mut *connection)
+            .await?;
+        let foreign_keys: i64 = sqlx::query_scalar("PRAGMA foreign_keys")
+            .fetch_one(&mut *connection)
+            .await?;
+
+        // Waits up to the busy timeout for other writers, then gives up
+        let writable = sqlx::query("BEGIN IMMEDIATE").execute(&mut *connection).await.is_ok();
+        if writable {
+            sqlx::query("ROLLBACK").execute(&mut *connection).await?;
+        }
+
+        Ok(DatabaseHealth {
+            journal_mode: journal_mode.to_lowercase(),
+            busy_timeout_ms,
+            foreign_keys: foreign_keys == 1,
+            writable,
+        })
+    }
+
     /// Initialize database schema
     async fn initialize_schema(&self) -> Result<()> {
         // Create books table
@@ -80,6 +153,13 @@ impl DatabaseService {
                 description TEXT,
                 publication_date TEXT,
                 language TEXT,
+                series TEXT,
+                series_index REAL,
+                publisher TEXT,
+                rights TEXT,
+                contributors TEXT,
+                subjects TEXT,
+                identifiers TEXT,
                 file_path TEXT NOT NULL,
                 file_size INTEGER NOT NULL,
                 file_format TEXT NOT NULL,
@@ -87,6 +167,7 @@ impl DatabaseService {
                 cover_url TEXT,
                 page_count INTEGER,
                 word_count INTEGER,
+                chapter_word_counts TEXT,
                 reading_progress REAL DEFAULT 0.0,
                 reading_status TEXT DEFAULT 'new',
                 last_read_position TEXT,
@@ -172,10 +253,80 @@ impl DatabaseService {
         .execute(&self.pool)
         .await?;
 
+        // Create book_custom_fields table (e.g. custom columns imported from Calibre)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS book_custom_fields (
+                book_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (book_id, name),
+                FOREIGN KEY (book_id) REFERENCES books(id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create watched_folders table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS watched_folders (
+                path TEXT PRIMARY KEY,
+                remove_on_delete INTEGER DEFAULT 0,
+                added_date TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create search_index_status table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS search_index_status (
+                book_id TEXT PRIMARY KEY,
+                state TEXT NOT NULL,
+                file_size INTEGER NOT NULL,
+                file_modified TEXT,
+                updated_at TEXT NOT NULL,
+                error TEXT,
+                FOREIGN KEY (book_id) REFERENCES books(id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
         // Add missing columns for existing databases (simple migration)
         let _ = sqlx::query("ALTER TABLE books ADD COLUMN cover_url TEXT")
             .execute(&self.pool)
             .await; // Ignore error if column already exists
+        let _ = sqlx::query("ALTER TABLE books ADD COLUMN series TEXT")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE books ADD COLUMN series_index REAL")
+            .execute(&self.pool)
+            .await;
+        for column in ["publisher", "rights", "contributors", "subjects", "identifiers", "chapter_word_counts", "deleted_at"] {
+            let _ = sqlx::query(&format!("ALTER TABLE books ADD COLUMN {} TEXT", column))
+                .execute(&self.pool)
+                .await;
+        }
+        // Columns the library service's collections use, so both can share the table
+        for column in [
+            "icon TEXT NOT NULL DEFAULT '📚'",
+            "created_at TEXT",
+            "updated_at TEXT",
+            "is_smart BOOLEAN NOT NULL DEFAULT FALSE",
+            "smart_rules TEXT",
+            "is_favorite BOOLEAN NOT NULL DEFAULT FALSE",
+            "sort_order INTEGER NOT NULL DEFAULT 0",
+        ] {
+            let _ = sqlx::query(&format!("ALTER TABLE collections ADD COLUMN {}", column))
+                .execute(&self.pool)
+                .await;
+        }
 
         // Create indexes for better query performance
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_books_title ON books(title)")
@@ -194,6 +345,14 @@ impl DatabaseService {
             .execute(&self.pool)
             .await?;
 
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_books_series ON books(series, series_index)")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_books_deleted_at ON books(deleted_at)")
+            .execute(&self.pool)
+            .await?;
+
         Ok(())
     }
 
@@ -205,10 +364,11 @@ impl DatabaseService {
             r#"
             INSERT INTO books (
                 id, title, author, isbn, genre, description, publication_date, language,
+                series, series_index, publisher, rights, contributors, subjects, identifiers,
                 file_path, file_size, file_format, cover_path, cover_url, page_count, word_count,
-                reading_progress, reading_status, added_date, last_opened, is_favorite,
+                chapter_word_counts, reading_progress, reading_status, added_date, last_opened, is_favorite,
                 rating, notes, tags
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(&book.id)
@@ -219,12 +379,20 @@ impl DatabaseService {
         .bind(&book.description)
         .bind(book.publication_date.map(|d| d.to_rfc3339()))
         .bind(&book.language)
+        .bind(&book.series)
+        .bind(book.series_index)
+        .bind(&book.publisher)
+        .bind(&book.rights)
+        .bind(serde_json::to_string(&book.contributors)?)
+        .bind(serde_json::to_string(&book.subjects)?)
+        .bind(serde_json::to_string(&book.identifiers)?)
         .bind(book.file_path.to_string_lossy().to_string())
         .bind(book.file_size as i64)
         .bind(book.file_format.to_extension())
         .bind(book.cover_path.as_ref().map(|p| p.to_string_lossy().to_string()))
         .bind(&book.cover_url)        .bind(book.page_count.map(|p| p as i64))
         .bind(book.word_count.map(|w| w as i64))
+        .bind(serde_json::to_string(&book.chapter_word_counts)?)
         .bind(book.reading_progress)
         .bind(book.reading_status.to_string())
         .bind(book.added_date.to_rfc3339())
@@ -247,9 +415,11 @@ impl DatabaseService {
             r#"
             UPDATE books SET
                 title = ?, author = ?, isbn = ?, genre = ?, description = ?,
-                publication_date = ?, language = ?, file_path = ?, file_size = ?,
+                publication_date = ?, language = ?, series = ?, series_index = ?,
+                publisher = ?, rights = ?, contributors = ?, subjects = ?, identifiers = ?,
+                file_path = ?, file_size = ?,
                 file_format = ?, cover_path = ?, cover_url = ?, page_count = ?, word_count = ?,
-                reading_progress = ?, reading_status = ?, last_opened = ?,
+                chapter_word_counts = ?, reading_progress = ?, reading_status = ?, last_opened = ?,
                 is_favorite = ?, rating = ?, notes = ?, tags = ?
             WHERE id = ?
             "#,
@@ -261,12 +431,20 @@ impl DatabaseService {
         .bind(&book.description)
         .bind(book.publication_date.map(|d| d.to_rfc3339()))
         .bind(&book.language)
+        .bind(&book.series)
+        .bind(book.series_index)
+        .bind(&book.publisher)
+        .bind(&book.rights)
+        .bind(serde_json::to_string(&book.contributors)?)
+        .bind(serde_json::to_string(&book.subjects)?)
+        .bind(serde_json::to_string(&book.identifiers)?)
         .bind(book.file_path.to_string_lossy().to_string())
         .bind(book.file_size as i64)
         .bind(book.file_format.to_extension())
         .bind(book.cover_path.as_ref().map(|p| p.to_string_lossy().to_string()))
         .bind(&book.cover_url)        .bind(book.page_count.map(|p| p as i64))
         .bind(book.word_count.map(|w| w as i64))
+        .bind(serde_json::to_string(&book.chapter_word_counts)?)
         .bind(book.reading_progress)
         .bind(book.reading_status.to_string())
         .bind(book.last_opened.map(|d| d.to_rfc3339()))
@@ -281,7 +459,7 @@ impl DatabaseService {
         Ok(())
     }
 
-    /// Get a book by ID
+    /// Get a book by ID, including books in the trash
     pub async fn get_book_by_id(&self, book_id: &str) -> Result<Book> {
         let row = sqlx::query("SELECT * FROM books WHERE id = ?")
             .bind(book_id)
@@ -291,9 +469,9 @@ impl DatabaseService {
         self.row_to_book(row)
     }
 
-    /// Get all books
+    /// Get all books, leaving out books in the trash
     pub async fn get_all_books(&self) -> Result<Vec<Book>> {
-        let rows = sqlx::query("SELECT * FROM books ORDER BY added_date DESC")
+        let rows = sqlx::query("SELECT * FROM books WHERE deleted_at IS NULL ORDER BY added_date DESC")
             .fetch_all(&self.pool)
             .await?;
 
@@ -313,39 +491,14 @@ impl DatabaseService {
         limit: Option<usize>,
         offset: Option<usize>,
     ) -> Result<Vec<Book>> {
-        let mut query = "SELECT * FROM books WHERE 1=1".to_string();
+        let mut query = "SELECT * FROM books WHERE deleted_at IS NULL".to_string();
         let mut params = Vec::new();
+        Self::push_filter_clauses(filter, &mut query, &mut params);
 
-        // Apply filters
-        if let Some(status) = &filter.status {
-            query.push_str(" AND reading_status = ?");
-            params.push(status.to_string());
-        }
-
-        if let Some(author) = &filter.author {
-            query.push_str(" AND author LIKE ?");
-            params.push(format!("%{}%", author));
-        }
-
-        if let Some(genre) = &filter.genre {
-            query.push_str(" AND genre = ?");
-            params.push(genre.clone());
-        }
-
-        if let Some(is_favorite) = filter.is_favorite {
-            query.push_str(" AND is_favorite = ?");
-            params.push(if is_favorite { "1".to_string() } else { "0".to_string() });
-        }
-
-        if let Some(rating_min) = filter.rating_min {
-            query.push_str(" AND rating >= ?");
-            params.push(rating_min.to_string());
-        }
-
-        if let Some(rating_max) = filter.rating_max {
-            query.push_str(" AND rating <= ?");
-            params.push(rating_max.to_string());
-        }
+        let direction = match sort.order {
+            SortOrder::Ascending => "ASC",
+            SortOrder::Descending => "DESC",
+        };
 
         // Apply sorting
         match sort.field {
@@ -355,12 +508,12 @@ impl DatabaseService {
             SortField::LastOpened => query.push_str(" ORDER BY last_opened"),
             SortField::ReadingProgress => query.push_str(" ORDER BY reading_progress"),
             SortField::Rating => query.push_str(" ORDER BY rating"),
+            SortField::WordCount => query.push_str(" ORDER BY word_count"),
+            // Volumes of a series stay in reading order, books without one go last
+            SortField::Series => query.push_str(&format!(" ORDER BY series IS NULL, series {}, series_index", direction)),
         }
-
-        match sort.order {
-            SortOrder::Ascending => query.push_str(" ASC"),
-            SortOrder::Descending => query.push_str(" DESC"),
-        }
+        query.push(' ');
+        query.push_str(direction);
 
         // Apply pagination
         if let Some(limit) = limit {
@@ -386,12 +539,24 @@ impl DatabaseService {
 
     /// Search books by query
     pub async fn search_books(&self, query: &str) -> Result<Vec<Book>> {
+        self.search_books_filtered(query, &BookFilter::default()).await
+    }
+
+    /// Search books by query, restricted to books matching the filter
+    pub async fn search_books_filtered(&self, query: &str, filter: &BookFilter) -> Result<Vec<Book>> {
         let search_query = format!("%{}%", query);
         
-        let rows = sqlx::query(
-            r#"
+        let mut sql = r#"
             SELECT * FROM books 
-            WHERE title LIKE ? OR author LIKE ? OR description LIKE ? OR tags LIKE ?
+            WHERE deleted_at IS NULL
+              AND (title LIKE ? OR author LIKE ? OR description LIKE ? OR tags LIKE ?
+                OR contributors LIKE ? OR subjects LIKE ? OR publisher LIKE ? OR isbn LIKE ?)
+            "#.to_string();
+        let mut params = vec![search_query.clone(); 8];
+        Self::push_filter_clauses(filter, &mut sql, &mut params);
+
+        sql.push_str(
+            r#"
             ORDER BY 
                 CASE 
                     WHEN title LIKE ? THEN 1
@@ -401,16 +566,15 @@ impl DatabaseService {
                 END,
                 title
             "#,
-        )
-        .bind(&search_query)
-        .bind(&search_query)
-        .bind(&search_query)
-        .bind(&search_query)
-        .bind(&search_query)
-        .bind(&search_query)
-        .bind(&search_query)
-        .fetch_all(&self.pool)
-        .await?;
+        );
+        params.extend(vec![search_query; 3]);
+
+        let mut sql_query = sqlx::query(&sql);
+        for param in params {
+            sql_query = sql_query.bind(param);
+        }
+
+        let rows = sql_query.fetch_all(&self.pool).await?;
 
         let mut books = Vec::new();
         for row in rows {
@@ -420,7 +584,106 @@ impl DatabaseService {
         Ok(books)
     }
 
-    /// Delete a book
+    /// Append WHERE clauses for a book filter
+    fn push_filter_clauses(filter: &BookFilter, query: &mut String, params: &mut Vec<String>) {
+        if let Some(status) = &filter.status {
+            query.push_str(" AND reading_status = ?");
+            params.push(status.to_string());
+        }
+
+        if let Some(author) = &filter.author {
+            query.push_str(" AND author LIKE ?");
+            params.push(format!("%{}%", author));
+        }
+
+        if let Some(genre) = &filter.genre {
+            query.push_str(" AND genre = ?");
+            params.push(genre.clone());
+        }
+
+        if let Some(is_favorite) = filter.is_favorite {
+            query.push_str(" AND is_favorite = ?");
+            params.push(if is_favorite { "1".to_string() } else { "0".to_string() });
+        }
+
+        if let Some(rating_min) = filter.rating_min {
+            query.push_str(" AND rating >= ?");
+            params.push(rating_min.to_string());
+        }
+
+        if let Some(rating_max) = filter.rating_max {
+            query.push_str(" AND rating <= ?");
+            params.push(rating_max.to_string());
+        }
+
+        if let Some(collection_id) = &filter.collection_id {
+            query.push_str(" AND id IN (SELECT book_id FROM book_collections WHERE collection_id = ?)");
+            params.push(collection_id.clone());
+        }
+
+        if let Some(language) = &filter.language {
+            query.push_str(" AND language = ? COLLATE NOCASE");
+            params.push(language.clone());
+        }
+
+        // publication_date is stored as RFC 3339, so the year is the first four characters
+        if let Some(year_min) = filter.year_min {
+            query.push_str(" AND substr(publication_date, 1, 4) >= ?");
+            params.push(format!("{:04}", year_min));
+        }
+
+        if let Some(year_max) = filter.year_max {
+            query.push_str(" AND substr(publication_date, 1, 4) <= ?");
+            params.push(format!("{:04}", year_max));
+        }
+
+        if let Some(word_count_min) = filter.word_count_min {
+            query.push_str(" AND word_count >= ?");
+            params.push(word_count_min.to_string());
+        }
+
+        if let Some(word_count_max) = filter.word_count_max {
+            query.push_str(" AND word_count <= ?");
+            params.push(word_count_max.to_string());
+        }
+    }
+
+    /// Move a book to the trash, or back out of it with `None`
+    pub async fn set_book_deleted_at(&self, book_id: &str, deleted_at: Option<DateTime<Utc>>) -> Result<()> {
+        sqlx::query("UPDATE books SET deleted_at = ? WHERE id = ?")
+            .bind(deleted_at.map(|date| date.to_rfc3339()))
+            .bind(book_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the books in the trash with when they were deleted, most recent first
+    pub async fn get_trashed_books(&self) -> Result<Vec<(Book, DateTime<Utc>)>> {
+        let rows = sqlx::query("SELECT * FROM books WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let deleted_at = DateTime::parse_from_rfc3339(&row.get::<String, _>("deleted_at"))?.with_timezone(&Utc);
+                Ok((self.row_to_book(row)?, deleted_at))
+            })
+            .collect()
+    }
+
+    /// Check whether a book is in the trash
+    pub async fn is_book_trashed(&self, book_id: &str) -> Result<bool> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM books WHERE id = ? AND deleted_at IS NOT NULL")
+            .bind(book_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    /// Permanently delete a book
     pub async fn delete_book(&self, book_id: &str) -> Result<()> {
         sqlx::query("DELETE FROM books WHERE id = ?")
             .bind(book_id)
@@ -430,6 +693,241 @@ impl DatabaseService {
         Ok(())
     }
 
+    /// Count annotations attached to the given books
+    pub async fn count_annotations_for_books(&self, book_ids: &[String]) -> Result<usize> {
+        let counts: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT book_id, COUNT(*) FROM annotations GROUP BY book_id"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let book_ids: std::collections::HashSet<&str> = book_ids.iter().map(String::as_str).collect();
+        Ok(counts.into_iter()
+            .filter(|(book_id, _)| book_ids.contains(book_id.as_str()))
+            .map(|(_, count)| count as usize)
+            .sum())
+    }
+
+    /// Record a book's search index status
+    pub async fn set_search_index_status(&self, status: &SearchIndexStatus) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO search_index_status (book_id, state, file_size, file_modified, updated_at, error)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&status.book_id)
+        .bind(status.state.as_str())
+        .bind(status.file_size as i64)
+        .bind(status.file_modified.map(|d| d.to_rfc3339()))
+        .bind(status.updated_at.to_rfc3339())
+        .bind(&status.error)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Set a named custom field of a book
+    pub async fn set_book_custom_field(&self, book_id: &str, name: &str, value: &str) -> Result<()> {
+        sqlx::query("INSERT OR REPLACE INTO book_custom_fields (book_id, name, value) VALUES (?, ?, ?)")
+            .bind(book_id)
+            .bind(name)
+            .bind(value)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get a book's custom fields as name and value pairs
+    pub async fn get_book_custom_fields(&self, book_id: &str) -> Result<Vec<(String, String)>> {
+        let rows = sqlx::query("SELECT name, value FROM book_custom_fields WHERE book_id = ? ORDER BY name")
+            .bind(book_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(|row| (row.get("name"), row.get("value"))).collect())
+    }
+
+    /// Save a reading session, replacing an earlier save of it
+    pub async fn save_reading_session(&self, session: &ReadingSession) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO reading_sessions (
+                id, book_id, start_time, end_time, duration_minutes, pages_read, words_read,
+                progress_start, progress_end
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&session.id)
+        .bind(&session.book_id)
+        .bind(session.start_time.to_rfc3339())
+        .bind(session.end_time.map(|d| d.to_rfc3339()))
+        .bind(session.duration_minutes as i64)
+        .bind(session.pages_read as i64)
+        .bind(session.words_read as i64)
+        .bind(session.progress_start)
+        .bind(session.progress_end)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get a reading session by ID
+    pub async fn get_reading_session(&self, session_id: &str) -> Result<Option<ReadingSession>> {
+        let row = sqlx::query("SELECT * FROM reading_sessions WHERE id = ?")
+            .bind(session_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| Self::row_to_reading_session(&row)))
+    }
+
+    /// Get the reading sessions that started at or after a time, oldest first
+    pub async fn get_reading_sessions_since(&self, since: DateTime<Utc>) -> Result<Vec<ReadingSession>> {
+        let rows = sqlx::query("SELECT * FROM reading_sessions WHERE julianday(start_time) >= julianday(?) ORDER BY start_time")
+            .bind(since.to_rfc3339())
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(Self::row_to_reading_session).collect())
+    }
+
+    /// Get a book's reading sessions, oldest first
+    pub async fn get_book_reading_sessions(&self, book_id: &str) -> Result<Vec<ReadingSession>> {
+        let rows = sqlx::query("SELECT * FROM reading_sessions WHERE book_id = ? ORDER BY start_time")
+            .bind(book_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(Self::row_to_reading_session).collect())
+    }
+
+    /// Get the minutes spent reading, in one book or the whole library
+    pub async fn get_reading_time(&self, book_id: Option<&str>) -> Result<u64> {
+        let minutes: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(SUM(duration_minutes), 0) FROM reading_sessions WHERE ? IS NULL OR book_id = ?"
+        )
+        .bind(book_id)
+        .bind(book_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(minutes as u64)
+    }
+
+    fn row_to_reading_session(row: &sqlx::sqlite::SqliteRow) -> ReadingSession {
+        let parse_date = |value: String| {
+            DateTime::parse_from_rfc3339(&value).ok().map(|d| d.with_timezone(&Utc))
+        };
+
+        ReadingSession {
+            id: row.get("id"),
+            book_id: row.get("book_id"),
+            start_time: parse_date(row.get("start_time")).unwrap_or_else(Utc::now),
+            end_time: row.get::<Option<String>, _>("end_time").and_then(parse_date),
+            duration_minutes: row.get::<Option<i64>, _>("duration_minutes").unwrap_or(0) as u32,
+            pages_read: row.get::<Option<i64>, _>("pages_read").unwrap_or(0) as u32,
+            words_read: row.get::<Option<i64>, _>("words_read").unwrap_or(0) as u32,
+            progress_start: row.get::<Option<f64>, _>("progress_start").unwrap_or(0.0) as f32,
+            progress_end: row.get::<Option<f64>, _>("progress_end").unwrap_or(0.0) as f32,
+        }
+    }
+
+    /// Register a folder to watch for new books, or update its settings
+    pub async fn insert_watched_folder(&self, folder: &WatchedFolder) -> Result<()> {
+        sqlx::query("INSERT OR REPLACE INTO watched_folders (path, remove_on_delete, added_date) VALUES (?, ?, ?)")
+            .bind(folder.path.to_string_lossy().to_string())
+            .bind(if folder.remove_on_delete { 1 } else { 0 })
+            .bind(folder.added_date.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Stop watching a folder, leaving its books in the library
+    pub async fn delete_watched_folder(&self, path: &Path) -> Result<()> {
+        sqlx::query("DELETE FROM watched_folders WHERE path = ?")
+            .bind(path.to_string_lossy().to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get every watched folder
+    pub async fn get_watched_folders(&self) -> Result<Vec<WatchedFolder>> {
+        let rows = sqlx::query("SELECT * FROM watched_folders ORDER BY path")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(|row| WatchedFolder {
+            path: PathBuf::from(row.get::<String, _>("path")),
+            remove_on_delete: row.get::<i64, _>("remove_on_delete") != 0,
+            added_date: DateTime::parse_from_rfc3339(&row.get::<String, _>("added_date"))
+                .map(|d| d.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now()),
+        }).collect())
+    }
+
+    /// Get a book's search index status, if it was ever indexed
+    pub async fn get_search_index_status(&self, book_id: &str) -> Result<Option<SearchIndexStatus>> {
+        let row = sqlx::query("SELECT * FROM search_index_status WHERE book_id = ?")
+            .bind(book_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| Self::row_to_search_index_status(&row)))
+    }
+
+    /// Get the search index status of every book that was ever indexed
+    pub async fn get_search_index_statuses(&self) -> Result<Vec<SearchIndexStatus>> {
+        let rows = sqlx::query("SELECT * FROM search_index_status")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(Self::row_to_search_index_status).collect())
+    }
+
+    fn row_to_search_index_status(row: &sqlx::sqlite::SqliteRow) -> SearchIndexStatus {
+        let parse_date = |value: String| {
+            DateTime::parse_from_rfc3339(&value).ok().map(|d| d.with_timezone(&Utc))
+        };
+
+        SearchIndexStatus {
+            book_id: row.get("book_id"),
+            state: SearchIndexState::from_string(&row.get::<String, _>("state"))
+                .unwrap_or(SearchIndexState::NotIndexed),
+            file_size: row.get::<i64, _>("file_size") as u64,
+            file_modified: row.get::<Option<String>, _>("file_modified").and_then(parse_date),
+            updated_at: parse_date(row.get("updated_at")).unwrap_or_else(Utc::now),
+            error: row.get("error"),
+        }
+    }
+
+    /// Find a library book by its normalized ISBN-13
+    pub async fn find_book_by_isbn(&self, isbn: &str) -> Result<Option<Book>> {
+        let row = sqlx::query("SELECT * FROM books WHERE isbn = ? AND deleted_at IS NULL LIMIT 1")
+            .bind(isbn)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|row| self.row_to_book(row)).transpose()
+    }
+
+    /// Find the library book stored at a file path, including books in the trash
+    pub async fn find_book_by_path(&self, file_path: &Path) -> Result<Option<Book>> {
+        let row = sqlx::query("SELECT * FROM books WHERE file_path = ? LIMIT 1")
+            .bind(file_path.to_string_lossy().to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|row| self.row_to_book(row)).transpose()
+    }
+
     /// Check if book exists by file path
     pub async fn book_exists_by_path(&self, file_path: &Path) -> Result<bool> {
         let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM books WHERE file_path = ?")
@@ -442,7 +940,7 @@ impl DatabaseService {
 
     /// Get books by status
     pub async fn get_books_by_status(&self, status: ReadingStatus) -> Result<Vec<Book>> {
-        let rows = sqlx::query("SELECT * FROM books WHERE reading_status = ? ORDER BY last_opened DESC")
+        let rows = sqlx::query("SELECT * FROM books WHERE reading_status = ? AND deleted_at IS NULL ORDER BY last_opened DESC")
             .bind(status.to_string())
             .fetch_all(&self.pool)
             .await?;
@@ -455,9 +953,34 @@ impl DatabaseService {
         Ok(books)
     }
 
+    /// Get the next book in a series after the given position
+    pub async fn get_next_in_series(&self, series: &str, after_index: f32) -> Result<Option<Book>> {
+        let row = sqlx::query(
+            "SELECT * FROM books WHERE series = ? AND series_index > ? AND deleted_at IS NULL ORDER BY series_index LIMIT 1"
+        )
+        .bind(series)
+        .bind(after_index as f64)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|row| self.row_to_book(row)).transpose()
+    }
+
+    /// Get the books of a series ordered by their position in it
+    pub async fn get_series_books(&self, series: &str) -> Result<Vec<Book>> {
+        let rows = sqlx::query(
+            "SELECT * FROM books WHERE series = ? AND deleted_at IS NULL ORDER BY series_index IS NULL, series_index, title"
+        )
+        .bind(series)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(|row| self.row_to_book(row)).collect()
+    }
+
     /// Get recently added books
     pub async fn get_recently_added_books(&self, limit: usize) -> Result<Vec<Book>> {
-        let rows = sqlx::query("SELECT * FROM books ORDER BY added_date DESC LIMIT ?")
+        let rows = sqlx::query("SELECT * FROM books WHERE deleted_at IS NULL ORDER BY added_date DESC LIMIT ?")
             .bind(limit as i64)
             .fetch_all(&self.pool)
             .await?;
@@ -473,13 +996,15 @@ impl DatabaseService {
     /// Insert a new collection
     pub async fn insert_collection(&self, collection: &BookCollection) -> Result<()> {
         sqlx::query(
-            "INSERT INTO collections (id, name, description, color, created_date) VALUES (?, ?, ?, ?, ?)"
+            "INSERT INTO collections (id, name, description, color, created_date, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(&collection.id)
         .bind(&collection.name)
         .bind(&collection.description)
         .bind(&collection.color)
         .bind(collection.created_date.to_rfc3339())
+        .bind(collection.created_date.to_rfc3339())
+        .bind(collection.created_date.to_rfc3339())
         .execute(&self.pool)
         .await?;
 
@@ -538,6 +1063,11 @@ impl DatabaseService {
     fn row_to_book(&self, row: sqlx::sqlite::SqliteRow) -> Result<Book> {
         let tags_json: String = row.get("tags");
         let tags: Vec<String> = serde_json::from_str(&tags_json).unwrap_or_default();
+        let json_list = |column: &str| -> Vec<String> {
+            row.get::<Option<String>, _>(column)
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default()
+        };
         
         let publication_date = row.get::<Option<String>, _>("publication_date")
             .and_then(|d| DateTime::parse_from_rfc3339(&d).ok())
@@ -566,6 +1096,13 @@ impl DatabaseService {
             description: row.get("description"),
             publication_date,
             language: row.get("language"),
+            series: row.get("series"),
+            series_index: row.get::<Option<f64>, _>("series_index").map(|i| i as f32),
+            publisher: row.get("publisher"),
+            rights: row.get("rights"),
+            contributors: json_list("contributors"),
+            subjects: json_list("subjects"),
+            identifiers: json_list("identifiers"),
             file_path: row.get::<String, _>("file_path").into(),
             file_size: row.get::<i64, _>("file_size") as u64,
             file_format,
@@ -573,6 +1110,9 @@ impl DatabaseService {
             cover_url: row.get::<Option<String>, _>("cover_url"),
             page_count: row.get::<Option<i64>, _>("page_count").map(|p| p as u32),
             word_count: row.get::<Option<i64>, _>("word_count").map(|w| w as u32),
+            chapter_word_counts: row.get::<Option<String>, _>("chapter_word_counts")
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
             reading_progress: row.get("reading_progress"),
             reading_status,
             last_read_position: None, // TODO: Implement position parsing
@@ -584,4 +1124,136 @@ impl DatabaseService {
             notes: row.get("notes"),
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use chrono::TimeZone;
+
+    fn book(title: &str, language: &str, year: i32) -> Book {
+        let mut book = Book::new(
+            title.to_string(),
+            "Jane Author".to_string(),
+            PathBuf::from(format!("/books/{}.epub", title)),
+            1024,
+            BookFormat::Epub,
+        );
+        book.language = Some(language.to_string());
+        book.publication_date = Some(Utc.with_ymd_and_hms(year, 6, 1, 0, 0, 0).unwrap());
+        book
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_writes_wait_for_the_lock() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let database = Arc::new(DatabaseService::new_with_path(Some(temp_dir.path().join("library.db"))).await.unwrap());
+
+        let health = database.health_check().await.unwrap();
+        assert_eq!(health.journal_mode, "wal");
+        assert_eq!(health.busy_timeout_ms, BUSY_TIMEOUT.as_millis() as i64);
+        assert!(health.foreign_keys && health.writable);
+
+        let writers: Vec<_> = (0..32)
+            .map(|i| {
+                let database = database.clone();
+                tokio::spawn(async move { database.insert_book(&book(&format!("Book {}", i), "en", 2020)).await })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap().unwrap();
+        }
+        assert_eq!(database.get_all_books().await.unwrap().len(), 32);
+    }
+
+    #[tokio::test]
+    async fn test_search_books_with_language_and_year_filter() {
+        let database = DatabaseService::new_in_memory().await.unwrap();
+        database.insert_book(&book("Rust Basics", "en", 2015)).await.unwrap();
+        database.insert_book(&book("Rust Advanced", "en", 2022)).await.unwrap();
+        database.insert_book(&book("Rust Avancado", "pt", 2022)).await.unwrap();
+
+        let filter = BookFilter {
+            language: Some("EN".to_string()),
+            year_min: Some(2020),
+            ..BookFilter::default()
+        };
+        let books = database.search_books_filtered("rust", &filter).await.unwrap();
+
+        assert_eq!(books.len(), 1);
+        assert_eq!(books[0].title, "Rust Advanced");
+        assert_eq!(database.search_books("rust").await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_get_next_in_series() {
+        let database = DatabaseService::new_in_memory().await.unwrap();
+        for (title, index) in [("Book Three", 3.0), ("Book One", 1.0), ("Book Two", 2.0)] {
+            let mut book = book(title, "en", 2020);
+            book.series = Some("Saga".to_string());
+            book.series_index = Some(index);
+            database.insert_book(&book).await.unwrap();
+        }
+
+        let next = database.get_next_in_series("Saga", 1.0).await.unwrap().unwrap();
+        assert_eq!(next.title, "Book Two");
+        assert_eq!(next.series_index, Some(2.0));
+        assert!(database.get_next_in_series("Saga", 3.0).await.unwrap().is_none());
+
+        let mut other = book("Standalone", "en", 2021);
+        other.series_index = Some(1.0);
+        database.insert_book(&other).await.unwrap();
+        let titles: Vec<_> = database.get_series_books("Saga").await.unwrap()
+            .into_iter()
+            .map(|book| book.title)
+            .collect();
+        assert_eq!(titles, ["Book One", "Book Two", "Book Three"]);
+
+        let sort = BookSort { field: SortField::Series, order: SortOrder::Ascending };
+        let books = database.get_filtered_books(&BookFilter::default(), &sort, None, None).await.unwrap();
+        assert_eq!(books.first().unwrap().title, "Book One");
+        assert_eq!(books.last().unwrap().title, "Standalone");
+    }
+    #[tokio::test]
+    async fn test_filter_and_sort_by_length() {
+        let database = DatabaseService::new_in_memory().await.unwrap();
+        for (title, words) in [("Novel", 90_000), ("Novella", 30_000), ("Story", 6_000)] {
+            let mut book = book(title, "en", 2020);
+            book.word_count = Some(words);
+            book.chapter_word_counts = vec![words / 2, words / 2];
+            database.insert_book(&book).await.unwrap();
+        }
+
+        let filter = BookFilter { word_count_min: Some(10_000), ..BookFilter::default() };
+        let sort = BookSort { field: SortField::WordCount, order: SortOrder::Ascending };
+        let books = database.get_filtered_books(&filter, &sort, None, None).await.unwrap();
+
+        assert_eq!(books.iter().map(|book| book.title.as_str()).collect::<Vec<_>>(), vec!["Novella", "Novel"]);
+        assert_eq!(books[0].chapter_word_counts, vec![15_000, 15_000]);
+    }
+
+    #[tokio::test]
+    async fn test_full_metadata_round_trips_and_is_searchable() {
+        let database = DatabaseService::new_in_memory().await.unwrap();
+        let mut translated = book("War and Peace", "en", 1869);
+        translated.publisher = Some("Vintage".to_string());
+        translated.contributors = vec!["Richard Pevear".to_string()];
+        translated.subjects = vec!["Historical fiction".to_string()];
+        translated.identifiers = vec!["urn:isbn:9781400079988".to_string()];
+        database.insert_book(&translated).await.unwrap();
+        database.insert_book(&book("Anna Karenina", "en", 1878)).await.unwrap();
+
+        let stored = database.get_book_by_id(&translated.id).await.unwrap();
+        assert_eq!(stored.publisher.as_deref(), Some("Vintage"));
+        assert_eq!(stored.contributors, translated.contributors);
+        assert_eq!(stored.subjects, translated.subjects);
+        assert_eq!(stored.identifiers, translated.identifiers);
+
+        for query in ["pevear", "historical", "vintage"] {
+            let books = database.search_books(query).await.unwrap();
+            assert_eq!(books.len(), 1, "{}", query);
+            assert_eq!(books[0].title, "War and Peace");
+        }
+    }
+}
@@ -1,9 +1,17 @@
 use std::path::{Path, PathBuf};
+use std::process::Stdio;
 use anyhow::{Result, anyhow};
-use sqlx::SqlitePool;
+use sqlx::{Row, SqlitePool};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
 use thiserror::Error;
 use tracing::{info, warn, error};
 
+use crate::services::backup_service::BackupService;
+
+/// Tables a salvaged database must have to be kept over a backup
+const REQUIRED_TABLES: &[&str] = &["books"];
+
 /// Custom error types for database initialization
 #[derive(Debug, Error)]
 pub enum DatabaseInitError {
@@ -40,9 +48,21 @@ pub enum DatabaseStatus {
     InvalidSchema,
 }
 
+/// How a corrupted database was brought back
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecoveryOutcome {
+    /// Rows were salvaged from the damaged file with `sqlite3 .recover`
+    Recovered,
+    /// The latest backup was restored
+    RestoredFromBackup(PathBuf),
+    /// Nothing could be salvaged and an empty database was created
+    Recreated,
+}
+
 /// Database initializer for handling setup and validation
 pub struct DatabaseInitializer {
     database_path: PathBuf,
+    backup_directory: PathBuf,
     backup_enabled: bool,
     max_retry_attempts: u32,
 }
@@ -50,8 +70,13 @@ pub struct DatabaseInitializer {
 impl DatabaseInitializer {
     /// Create a new database initializer
     pub fn new(database_path: PathBuf) -> Self {
+        let backup_directory = database_path.parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("backups");
+
         Self {
             database_path,
+            backup_directory,
             backup_enabled: true,
             max_retry_attempts: 3,
         }
@@ -70,6 +95,12 @@ impl DatabaseInitializer {
         match status {
             DatabaseStatus::Ready => {
                 info!("Database is ready");
+                if self.backup_enabled {
+                    // A failed backup should never keep the app from starting
+                    if let Err(e) = self.create_rolling_backup().await {
+                        warn!("Failed to back up database: {}", e);
+                    }
+                }
                 Ok(self.database_path.clone())
             }
             DatabaseStatus::Missing => {
@@ -166,13 +197,11 @@ impl DatabaseInitializer {
         // Try to connect to database to check if it's valid
         match self.test_database_connection().await {
             Ok(true) => Ok(DatabaseStatus::Ready),
-            Ok(false) => Ok(DatabaseStatus::InvalidSchema),
+            Ok(false) => Ok(DatabaseStatus::Corrupted),
             Err(e) => {
                 let error_msg = e.to_string().to_lowercase();
                 if error_msg.contains("locked") {
                     Ok(DatabaseStatus::Locked)
-                } else if error_msg.contains("corrupt") || error_msg.contains("malformed") {
-                    Ok(DatabaseStatus::Corrupted)
                 } else {
                     Ok(DatabaseStatus::Corrupted)
                 }
@@ -182,24 +211,24 @@ impl DatabaseInitializer {
     
     /// Test database connection and basic functionality
     async fn test_database_connection(&self) -> Result<bool> {
-        let database_url = format!("sqlite://{}?mode=rwc", self.database_path.display());
-        
-        match SqlitePool::connect(&database_url).await {
-            Ok(pool) => {
-                // Test basic query
-                match sqlx::query("SELECT 1").fetch_one(&pool).await {
-                    Ok(_) => {
-                        pool.close().await;
-                        Ok(true)
-                    }
-                    Err(_) => {
-                        pool.close().await;
-                        Ok(false)
-                    }
-                }
-            }
-            Err(e) => Err(anyhow!("Connection failed: {}", e)),
-        }
+        Self::check_integrity(&self.database_path).await
+    }
+
+    /// Run SQLite's quick integrity check on a database file
+    ///
+    /// Returns false when the check finds damage; files that are not databases
+    /// at all fail with an error.
+    async fn check_integrity(path: &Path) -> Result<bool> {
+        let database_url = format!("sqlite://{}?mode=rw", path.display());
+        let pool = SqlitePool::connect(&database_url).await
+            .map_err(|e| anyhow!("Connection failed: {}", e))?;
+
+        // Unlike SELECT 1, this reads every page, so it notices a damaged file
+        let result = sqlx::query("PRAGMA quick_check").fetch_all(&pool).await;
+        pool.close().await;
+
+        let rows = result.map_err(|e| anyhow!("Integrity check failed: {}", e))?;
+        Ok(rows.len() == 1 && rows[0].get::<String, _>(0) == "ok")
     }
     
     /// Create a new database file
@@ -234,25 +263,196 @@ impl DatabaseInitializer {
     }
     
     /// Handle corrupted database
+    ///
+    /// The damaged file is always kept for inspection. Rows are salvaged with
+    /// `sqlite3 .recover` when the SQLite shell is installed, then the latest
+    /// backup is tried, and only then is an empty database created.
     async fn handle_corrupted_database(&self) -> Result<(), DatabaseInitError> {
         warn!("Handling corrupted database");
-        
-        // Create backup of corrupted file
-        if self.backup_enabled {
-            let backup_path = self.create_corruption_backup().await?;
-            info!("Corrupted database backed up to: {}", backup_path.display());
+
+        let damaged_path = self.preserve_corrupted_database().await?;
+        info!("Corrupted database preserved at: {}", damaged_path.display());
+
+        let outcome = self.recover_database(&damaged_path).await?;
+        match &outcome {
+            RecoveryOutcome::Recovered => info!("Database recovered from the damaged file"),
+            RecoveryOutcome::RestoredFromBackup(backup) => {
+                warn!("Database restored from backup: {}", backup.display());
+            }
+            RecoveryOutcome::Recreated => {
+                error!("Database could not be recovered; started a new one. Damaged file kept at {}", damaged_path.display());
+            }
         }
-        
-        // Remove corrupted file
-        if let Err(e) = std::fs::remove_file(&self.database_path) {
-            warn!("Failed to remove corrupted database: {}", e);
+
+        Ok(())
+    }
+
+    /// Rebuild the database from a damaged copy, a backup, or from scratch
+    ///
+    /// Salvaged rows are only kept when they hold at least what the latest
+    /// healthy backup does; a damaged schema leaves `.recover` with nothing
+    /// but a `lost_and_found` table, and the backup is the better copy then.
+    pub async fn recover_database(&self, damaged_path: &Path) -> Result<RecoveryOutcome, DatabaseInitError> {
+        let mut backup = None;
+        for candidate in self.list_backups()?.into_iter().rev() {
+            match Self::check_integrity(&candidate).await {
+                Ok(true) => {
+                    backup = Some(candidate);
+                    break;
+                }
+                _ => warn!("Skipping damaged backup: {}", candidate.display()),
+            }
         }
-        
-        // Create new database
+
+        match self.recover_with_sqlite_shell(damaged_path, backup.as_deref()).await {
+            Ok(()) => return Ok(RecoveryOutcome::Recovered),
+            Err(e) => warn!("Could not salvage the damaged database: {}", e),
+        }
+
+        if let Some(backup) = backup {
+            tokio::fs::copy(&backup, &self.database_path).await
+                .map_err(|e| DatabaseInitError::BackupFailed(e.to_string()))?;
+            return Ok(RecoveryOutcome::RestoredFromBackup(backup));
+        }
+
         self.create_new_database().await?;
-        
+        Ok(RecoveryOutcome::Recreated)
+    }
+
+    /// Pipe `sqlite3 <damaged> .recover` into a new database and swap it in
+    ///
+    /// The result must pass SQLite's integrity check and `salvage_is_complete`
+    /// against the backup, if there is one.
+    async fn recover_with_sqlite_shell(&self, damaged_path: &Path, backup: Option<&Path>) -> Result<()> {
+        let recovered_path = self.database_path.with_extension("recovered");
+        let _ = std::fs::remove_file(&recovered_path);
+
+        let dump = Command::new("sqlite3")
+            .arg(damaged_path)
+            .arg(".recover")
+            .output()
+            .await
+            .map_err(|e| anyhow!("sqlite3 shell not available: {}", e))?;
+        if !dump.status.success() || dump.stdout.is_empty() {
+            return Err(anyhow!(".recover failed: {}", String::from_utf8_lossy(&dump.stderr).trim()));
+        }
+
+        let mut import = Command::new("sqlite3")
+            .arg(&recovered_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        if let Some(mut stdin) = import.stdin.take() {
+            stdin.write_all(&dump.stdout).await?;
+        }
+        if !import.wait().await?.success() {
+            return Err(anyhow!("Failed to import recovered rows"));
+        }
+
+        if !Self::check_integrity(&recovered_path).await? {
+            return Err(anyhow!("Recovered database failed its integrity check"));
+        }
+        if let Err(e) = Self::salvage_is_complete(&recovered_path, backup).await {
+            let _ = std::fs::remove_file(&recovered_path);
+            return Err(e);
+        }
+        std::fs::rename(&recovered_path, &self.database_path)?;
         Ok(())
     }
+
+    /// Check that a salvaged database kept the library's tables and rows
+    ///
+    /// It needs the `REQUIRED_TABLES`, every table the backup has, and rows in
+    /// each table the backup has rows in.
+    async fn salvage_is_complete(recovered: &Path, backup: Option<&Path>) -> Result<()> {
+        let salvaged = Self::table_row_counts(recovered).await?;
+        let expected = match backup {
+            Some(backup) => Self::table_row_counts(backup).await?,
+            None => Vec::new(),
+        };
+
+        let missing: Vec<&str> = REQUIRED_TABLES.iter()
+            .copied()
+            .chain(expected.iter().map(|(table, _)| table.as_str()))
+            .filter(|table| !salvaged.iter().any(|(name, _)| name == table))
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow!("Recovered database lacks the {} table(s)", missing.join(", ")));
+        }
+
+        let emptied: Vec<&str> = expected.iter()
+            .filter(|(table, rows)| *rows > 0 && salvaged.iter().any(|(name, salvaged_rows)| name == table && *salvaged_rows == 0))
+            .map(|(table, _)| table.as_str())
+            .collect();
+        if !emptied.is_empty() {
+            return Err(anyhow!("Recovered database lost every row of {}, which the backup has", emptied.join(", ")));
+        }
+        Ok(())
+    }
+
+    /// Tables of a database with how many rows each holds
+    async fn table_row_counts(path: &Path) -> Result<Vec<(String, i64)>> {
+        let pool = SqlitePool::connect(&format!("sqlite://{}?mode=ro", path.display())).await?;
+        let result = async {
+            let tables: Vec<String> = sqlx::query_scalar(
+                "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+            )
+            .fetch_all(&pool)
+            .await?;
+            let mut counts = Vec::new();
+            for table in tables {
+                let rows: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM \"{}\"", table.replace('"', "\"\"")))
+                    .fetch_one(&pool)
+                    .await?;
+                counts.push((table, rows));
+            }
+            Ok::<_, anyhow::Error>(counts)
+        }
+        .await;
+        pool.close().await;
+        result
+    }
+
+    /// Move the damaged database and its journal files aside, keeping them for inspection
+    async fn preserve_corrupted_database(&self) -> Result<PathBuf, DatabaseInitError> {
+        let damaged_path = self.create_corruption_backup().await?;
+
+        for suffix in ["-wal", "-shm", "-journal"] {
+            let journal = PathBuf::from(format!("{}{}", self.database_path.display(), suffix));
+            if journal.exists() {
+                let target = PathBuf::from(format!("{}{}", damaged_path.display(), suffix));
+                if let Err(e) = std::fs::rename(&journal, &target) {
+                    warn!("Failed to preserve {}: {}", journal.display(), e);
+                }
+            }
+        }
+
+        if let Err(e) = std::fs::remove_file(&self.database_path) {
+            warn!("Failed to remove corrupted database: {}", e);
+        }
+
+        Ok(damaged_path)
+    }
+
+    /// Take a consistent snapshot of a healthy database, at most once per interval
+    async fn create_rolling_backup(&self) -> Result<Option<PathBuf>, DatabaseInitError> {
+        let backups = BackupService::new(self.database_path.clone(), self.backup_directory.clone());
+        let backup = backups.backup_if_due().await
+            .map_err(|e| DatabaseInitError::BackupFailed(e.to_string()))?;
+        if let Some(backup) = &backup {
+            info!("Database backed up to: {}", backup.path.display());
+        }
+        Ok(backup.map(|backup| backup.path))
+    }
+
+    /// Rolling backups, oldest first
+    fn list_backups(&self) -> Result<Vec<PathBuf>, DatabaseInitError> {
+        let backups = BackupService::new(self.database_path.clone(), self.backup_directory.clone());
+        let backups = backups.list_backups()
+            .map_err(|e| DatabaseInitError::BackupFailed(e.to_string()))?;
+        Ok(backups.into_iter().map(|backup| backup.path).collect())
+    }
     
     /// Handle locked database with retry logic
     async fn handle_locked_database(&self) -> Result<(), DatabaseInitError> {
@@ -308,6 +508,11 @@ impl DatabaseInitializer {
     pub fn set_backup_enabled(&mut self, enabled: bool) {
         self.backup_enabled = enabled;
     }
+
+    /// Set the directory rolling backups are kept in
+    pub fn set_backup_directory(&mut self, backup_directory: PathBuf) {
+        self.backup_directory = backup_directory;
+    }
     
     /// Set maximum retry attempts for locked database
     pub fn set_max_retry_attempts(&mut self, attempts: u32) {
@@ -366,4 +571,64 @@ mod tests {
         let status = initializer.validate_database_file().await.unwrap();
         assert_eq!(status, DatabaseStatus::Corrupted);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_corrupted_database_restored_from_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("library.db");
+        let initializer = DatabaseInitializer::new(db_path.clone());
+        initializer.ensure_database_ready().await.unwrap();
+
+        let pool = SqlitePool::connect(&format!("sqlite://{}", db_path.display())).await.unwrap();
+        sqlx::query("CREATE TABLE books (title TEXT)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO books VALUES ('Dune')").execute(&pool).await.unwrap();
+        pool.close().await;
+
+        // Startup with a healthy database takes the first backup
+        initializer.ensure_database_ready().await.unwrap();
+        assert_eq!(initializer.list_backups().unwrap().len(), 1);
+
+        std::fs::write(&db_path, b"this is not a database anymore").unwrap();
+        initializer.ensure_database_ready().await.unwrap();
+
+        let pool = SqlitePool::connect(&format!("sqlite://{}", db_path.display())).await.unwrap();
+        let title: String = sqlx::query_scalar("SELECT title FROM books").fetch_one(&pool).await.unwrap();
+        assert_eq!(title, "Dune");
+        pool.close().await;
+
+        let preserved = std::fs::read_dir(temp_dir.path()).unwrap()
+            .filter_map(|entry| entry.ok())
+            .find(|entry| entry.file_name().to_string_lossy().starts_with("corrupted_backup_"))
+            .unwrap();
+        assert_eq!(std::fs::read(preserved.path()).unwrap(), b"this is not a database anymore");
+    }
+
+    #[tokio::test]
+    async fn test_damaged_schema_falls_back_to_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("library.db");
+        let initializer = DatabaseInitializer::new(db_path.clone());
+        initializer.ensure_database_ready().await.unwrap();
+
+        let pool = SqlitePool::connect(&format!("sqlite://{}", db_path.display())).await.unwrap();
+        sqlx::query("CREATE TABLE books (title TEXT)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO books VALUES ('Dune'), ('Emma')").execute(&pool).await.unwrap();
+        pool.close().await;
+        initializer.ensure_database_ready().await.unwrap();
+
+        // Overwrite the schema on the first page; `.recover` then only finds loose rows
+        let damaged_path = temp_dir.path().join("damaged.db");
+        let mut damaged = std::fs::read(&db_path).unwrap();
+        damaged[100..500].fill(0xff);
+        std::fs::write(&damaged_path, damaged).unwrap();
+        std::fs::remove_file(&db_path).unwrap();
+
+        let outcome = initializer.recover_database(&damaged_path).await.unwrap();
+        assert!(matches!(outcome, RecoveryOutcome::RestoredFromBackup(_)));
+        let pool = SqlitePool::connect(&format!("sqlite://{}", db_path.display())).await.unwrap();
+        let books: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM books").fetch_one(&pool).await.unwrap();
+        assert_eq!(books, 2);
+        pool.close().await;
+        assert!(!db_path.with_extension("recovered").exists());
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, Utc};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// How long a confirmation token stays valid
+const TOKEN_LIFETIME_SECONDS: i64 = 120;
+
+/// Operation that cannot be undone and needs explicit confirmation
+#[derive(Debug, Clone, PartialEq)]
+pub enum DestructiveAction {
+    DeleteAllBooks,
+    DeleteBooks(Vec<String>),
+    EmptyTrash,
+    ClearCaches,
+}
+
+impl DestructiveAction {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            DestructiveAction::DeleteAllBooks => "Delete All Books",
+            DestructiveAction::DeleteBooks(_) => "Delete Books",
+            DestructiveAction::EmptyTrash => "Empty Trash",
+            DestructiveAction::ClearCaches => "Clear Caches",
+        }
+    }
+}
+
+/// What a destructive operation would remove
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DestructiveImpact {
+    pub book_count: usize,
+    pub annotation_count: usize,
+    pub cache_bytes: u64,
+}
+
+impl DestructiveImpact {
+    /// Short summary for the confirmation prompt
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.book_count > 0 {
+            parts.push(plural(self.book_count, "book"));
+        }
+        if self.annotation_count > 0 {
+            parts.push(plural(self.annotation_count, "annotation"));
+        }
+        if self.cache_bytes > 0 {
+            parts.push(format!("{:.1} MB of cached data", self.cache_bytes as f64 / (1024.0 * 1024.0)));
+        }
+
+        if parts.is_empty() {
+            "Nothing will be removed".to_string()
+        } else {
+            format!("This will permanently remove {}", parts.join(", "))
+        }
+    }
+}
+
+/// Short-lived token that must be echoed back to run a destructive operation
+#[derive(Debug, Clone)]
+pub struct ConfirmationToken {
+    pub token: String,
+    pub action: DestructiveAction,
+    pub impact: DestructiveImpact,
+    /// Books the confirmation covers; books added after it was issued are left alone
+    pub book_ids: Vec<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Issues and checks confirmation tokens for destructive operations
+///
+/// Tokens are single use and bound to the exact action they were issued for,
+/// so a stale or mismatched request from the UI cannot wipe data.
+#[derive(Clone)]
+pub struct ConfirmationGuard {
+    pending: Arc<RwLock<HashMap<String, ConfirmationToken>>>,
+    lifetime: Duration,
+}
+
+impl ConfirmationGuard {
+    pub fn new() -> Self {
+        Self::with_lifetime(Duration::seconds(TOKEN_LIFETIME_SECONDS))
+    }
+
+    /// Create a guard whose tokens expire after the given time
+    pub fn with_lifetime(lifetime: Duration) -> Self {
+        Self {
+            pending: Arc::new(RwLock::new(HashMap::new())),
+            lifetime,
+        }
+    }
+
+    /// Issue a token describing the impact of an action on the given books
+    pub async fn issue(
+        &self,
+        action: DestructiveAction,
+        impact: DestructiveImpact,
+        book_ids: Vec<String>,
+    ) -> ConfirmationToken {
+        let now = Utc::now();
+        let token = ConfirmationToken {
+            token: Uuid::new_v4().to_string(),
+            action,
+            impact,
+            book_ids,
+            expires_at: now + self.lifetime,
+        };
+
+        let mut pending = self.pending.write().await;
+        pending.retain(|_, issued| issued.expires_at > now);
+        pending.insert(token.token.clone(), token.clone());
+        token
+    }
+
+    /// Consume a token, checking it was issued for this action and has not expired
+    pub async fn confirm(&self, token: &str, action: &DestructiveAction) -> Result<ConfirmationToken> {
+        let issued = self.pending.write().await.remove(token)
+            .ok_or_else(|| anyhow!("Unknown or already used confirmation token"))?;
+
+        if issued.expires_at <= Utc::now() {
+            return Err(anyhow!("Confirmation token expired, please confirm again"));
+        }
+        if &issued.action != action {
+            return Err(anyhow!(
+                "Confirmation token was issued for \"{}\", not \"{}\"",
+                issued.action.display_name(),
+                action.display_name()
+            ));
+        }
+
+        Ok(issued)
+    }
+}
+
+impl Default for ConfirmationGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tokens_are_single_use_and_bound_to_action() {
+        let guard = ConfirmationGuard::new();
+        let impact = DestructiveImpact { book_count: 3, annotation_count: 1, cache_bytes: 0 };
+        assert_eq!(impact.describe(), "This will permanently remove 3 books, 1 annotation");
+
+        let issued = guard.issue(DestructiveAction::DeleteAllBooks, impact.clone(), Vec::new()).await;
+        assert!(guard.confirm(&issued.token, &DestructiveAction::ClearCaches).await.is_err());
+
+        let book_ids = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let issued = guard.issue(DestructiveAction::DeleteAllBooks, impact.clone(), book_ids.clone()).await;
+        let confirmed = guard.confirm(&issued.token, &DestructiveAction::DeleteAllBooks).await.unwrap();
+        assert_eq!(confirmed.impact, impact);
+        assert_eq!(confirmed.book_ids, book_ids);
+        assert!(guard.confirm(&issued.token, &DestructiveAction::DeleteAllBooks).await.is_err());
+
+        let selection = DestructiveAction::DeleteBooks(vec!["a".to_string()]);
+        let issued = guard.issue(selection, DestructiveImpact::default(), vec!["a".to_string()]).await;
+        let other = DestructiveAction::DeleteBooks(vec!["a".to_string(), "b".to_string()]);
+        assert!(guard.confirm(&issued.token, &other).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_expired_token_is_rejected() {
+        let guard = ConfirmationGuard::with_lifetime(Duration::zero());
+        let issued = guard.issue(DestructiveAction::ClearCaches, DestructiveImpact::default(), Vec::new()).await;
+        assert!(guard.confirm(&issued.token, &DestructiveAction::ClearCaches).await.is_err());
+    }
+}
@@ -0,0 +1,352 @@
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::models::book::{Book, BookFormat};
+use crate::services::export_share::sanitize_file_name;
+
+/// Folders a generic reader keeps its books in, tried in order
+const GENERIC_BOOK_FOLDERS: &[&str] = &["Books", "books", "eBooks", "Digital Editions"];
+/// Suffix of a file still being copied, so a reader never sees half a book
+const PARTIAL_SUFFIX: &str = ".part";
+
+/// Kind of e-reader, which decides where books go and what it can open
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EreaderKind {
+    Kindle,
+    Kobo,
+    Generic, // Any mass storage reader with a books folder
+}
+
+impl EreaderKind {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            EreaderKind::Kindle => "Kindle",
+            EreaderKind::Kobo => "Kobo",
+            EreaderKind::Generic => "E-reader",
+        }
+    }
+
+    /// Formats the reader opens when copied over USB
+    pub fn accepts(&self, format: &BookFormat) -> bool {
+        match self {
+            // Kindles only open EPUB once Amazon converts it, which USB copies skip
+            EreaderKind::Kindle => matches!(format, BookFormat::Mobi | BookFormat::Azw3 | BookFormat::Pdf | BookFormat::Txt),
+            EreaderKind::Kobo => !matches!(format, BookFormat::Azw3),
+            EreaderKind::Generic => true,
+        }
+    }
+
+    /// Work out the kind of reader mounted at a path from the files it keeps
+    pub fn identify(mount_point: &Path) -> Option<Self> {
+        if mount_point.join("system").is_dir() && mount_point.join("documents").is_dir() {
+            Some(EreaderKind::Kindle)
+        } else if mount_point.join(".kobo").is_dir() {
+            Some(EreaderKind::Kobo)
+        } else if GENERIC_BOOK_FOLDERS.iter().any(|folder| mount_point.join(folder).is_dir()) {
+            Some(EreaderKind::Generic)
+        } else {
+            None
+        }
+    }
+
+    fn books_dir(&self, mount_point: &Path) -> PathBuf {
+        match self {
+            EreaderKind::Kindle => mount_point.join("documents"),
+            EreaderKind::Kobo => mount_point.to_path_buf(),
+            EreaderKind::Generic => GENERIC_BOOK_FOLDERS.iter()
+                .map(|folder| mount_point.join(folder))
+                .find(|dir| dir.is_dir())
+                .unwrap_or_else(|| mount_point.to_path_buf()),
+        }
+    }
+}
+
+/// A mounted e-reader
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ereader {
+    pub name: String, // Volume label, e.g. "Kindle" or "KOBOeReader"
+    pub kind: EreaderKind,
+    pub mount_point: PathBuf,
+    pub books_dir: PathBuf,
+    pub free_bytes: u64,
+    pub total_bytes: u64,
+}
+
+impl Ereader {
+    /// The reader's `My Clippings.txt`, for importing highlights made on a Kindle
+    pub fn clippings_path(&self) -> Option<PathBuf> {
+        let path = self.books_dir.join("My Clippings.txt");
+        (self.kind == EreaderKind::Kindle && path.is_file()).then_some(path)
+    }
+}
+
+/// A book file found on a reader
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceBook {
+    pub path: PathBuf,
+    pub relative_path: PathBuf, // Under the reader's books folder
+    pub format: BookFormat,
+    pub size: u64,
+}
+
+/// Outcome of copying books to a reader
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SideloadReport {
+    pub copied: Vec<PathBuf>,
+    pub skipped: usize,                // Already on the reader
+    pub failed: Vec<(String, String)>, // Title and why
+}
+
+/// Finds e-readers mounted over USB and copies library books onto them
+///
+/// Readers are recognised by the files they keep: a Kindle by its `system`
+/// and `documents` folders, a Kobo by `.kobo`.
+pub struct DeviceManager {
+    mount_roots: Vec<PathBuf>,
+}
+
+impl DeviceManager {
+    /// Look where the platform mounts removable drives, or at every drive letter on Windows
+    pub fn new() -> Self {
+        Self::with_mount_roots(default_mount_roots())
+    }
+
+    /// Look for readers in the given folders, each holding mount points
+    pub fn with_mount_roots(mount_roots: Vec<PathBuf>) -> Self {
+        Self { mount_roots }
+    }
+
+    /// Readers mounted right now
+    pub fn detect(&self) -> Vec<Ereader> {
+        let mut readers = Vec::new();
+        for mount_point in self.mount_points() {
+            if let Some(kind) = EreaderKind::identify(&mount_point) {
+                match self.describe(&mount_point, kind) {
+                    Ok(reader) => readers.push(reader),
+                    Err(e) => warn!("Skipping reader at {}: {}", mount_point.display(), e),
+                }
+            }
+        }
+        readers.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+        readers
+    }
+
+    fn mount_points(&self) -> Vec<PathBuf> {
+        if cfg!(target_os = "windows") && self.mount_roots.is_empty() {
+            // Each drive letter is its own mount point
+            return (b'D'..=b'Z').map(|letter| PathBuf::from(format!("{}:\\", letter as char))).collect();
+        }
+        self.mount_roots.iter()
+            .filter_map(|root| std::fs::read_dir(root).ok())
+            .flat_map(|entries| entries.flatten().map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn describe(&self, mount_point: &Path, kind: EreaderKind) -> Result<Ereader> {
+        let name = mount_point.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| kind.display_name().to_string());
+        Ok(Ereader {
+            name,
+            kind,
+            mount_point: mount_point.to_path_buf(),
+            books_dir: kind.books_dir(mount_point),
+            free_bytes: fs2::available_space(mount_point)?,
+            total_bytes: fs2::total_space(mount_point)?,
+        })
+    }
+
+    /// Book files on a reader, by path
+    pub fn list_books(&self, reader: &Ereader) -> Result<Vec<DeviceBook>> {
+        let mut books = Vec::new();
+        let mut pending = vec![reader.books_dir.clone()];
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir)?.flatten() {
+                let path = entry.path();
+                let hidden = entry.file_name().to_string_lossy().starts_with('.');
+                let file_type = entry.file_type()?;
+                if file_type.is_dir() && !hidden {
+                    pending.push(path);
+                } else if file_type.is_file() && !hidden {
+                    let Some(format) = path.extension().and_then(|ext| BookFormat::from_extension(&ext.to_string_lossy())) else {
+                        continue;
+                    };
+                    books.push(DeviceBook {
+                        relative_path: path.strip_prefix(&reader.books_dir)?.to_path_buf(),
+                        size: entry.metadata()?.len(),
+                        path,
+                        format,
+                    });
+                }
+            }
+        }
+        books.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        Ok(books)
+    }
+
+    /// Copy books onto a reader, into a folder for the collection when one is given
+    ///
+    /// Books the reader can't open are reported rather than copied, and the
+    /// copy is refused up front when the reader lacks the space.
+    pub async fn sideload(&self, reader: &Ereader, books: &[Book], collection: Option<&str>) -> Result<SideloadReport> {
+        let mut report = SideloadReport::default();
+        let mut pending = Vec::new();
+        for book in books {
+            if !reader.kind.accepts(&book.file_format) {
+                report.failed.push((
+                    book.title.clone(),
+                    format!("{} can't open {} books", reader.kind.display_name(), book.file_format.to_extension().to_uppercase()),
+                ));
+                continue;
+            }
+            let destination = device_path(reader, book, collection);
+            let size = tokio::fs::metadata(&book.file_path).await
+                .map_err(|e| anyhow!("Failed to read {}: {}", book.file_path.display(), e))?
+                .len();
+            match tokio::fs::metadata(&destination).await {
+                Ok(existing) if existing.len() == size => report.skipped += 1,
+                _ => pending.push((book, destination, size)),
+            }
+        }
+
+        let needed: u64 = pending.iter().map(|(_, _, size)| size).sum();
+        let free = fs2::available_space(&reader.mount_point)?;
+        if needed > free {
+            return Err(anyhow!(
+                "The books need {:.1} MB but {} has {:.1} MB free",
+                needed as f64 / (1024.0 * 1024.0),
+                reader.name,
+                free as f64 / (1024.0 * 1024.0),
+            ));
+        }
+
+        for (book, destination, _) in pending {
+            match copy_book(&book.file_path, &destination).await {
+                Ok(()) => report.copied.push(destination),
+                Err(e) => report.failed.push((book.title.clone(), e.to_string())),
+            }
+        }
+        info!("Copied {} books to {}", report.copied.len(), reader.name);
+        Ok(report)
+    }
+}
+
+impl Default for DeviceManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where a book goes on a reader
+///
+/// Books in a series get a folder for it, named so the series reads in
+/// order; others are named for their author.
+pub fn device_path(reader: &Ereader, book: &Book, collection: Option<&str>) -> PathBuf {
+    let mut path = reader.books_dir.clone();
+    if let Some(collection) = collection.map(str::trim).filter(|name| !name.is_empty()) {
+        path.push(sanitize_file_name(collection));
+    }
+    let extension = book.file_format.to_extension();
+    match book.series.as_deref().map(str::trim).filter(|series| !series.is_empty()) {
+        Some(series) => {
+            path.push(sanitize_file_name(series));
+            let name = match book.series_index {
+                Some(index) if index.fract() == 0.0 => format!("{:02} - {}", index as u32, book.title),
+                Some(index) => format!("{:04.1} - {}", index, book.title),
+                None => book.title.clone(),
+            };
+            path.push(format!("{}.{}", sanitize_file_name(&name), extension));
+        }
+        None => path.push(format!("{}.{}", sanitize_file_name(&format!("{} - {}", book.author, book.title)), extension)),
+    }
+    path
+}
+
+async fn copy_book(source: &Path, destination: &Path) -> Result<()> {
+    if let Some(parent) = destination.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut partial = destination.as_os_str().to_owned();
+    partial.push(PARTIAL_SUFFIX);
+    let partial = PathBuf::from(partial);
+    if let Err(e) = tokio::fs::copy(source, &partial).await {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(anyhow!("Failed to copy {}: {}", source.display(), e));
+    }
+    tokio::fs::rename(&partial, destination).await?;
+    Ok(())
+}
+
+fn default_mount_roots() -> Vec<PathBuf> {
+    if cfg!(target_os = "windows") {
+        return Vec::new();
+    }
+    if cfg!(target_os = "macos") {
+        return vec![PathBuf::from("/Volumes")];
+    }
+    let mut roots = vec![PathBuf::from("/mnt")];
+    if let Ok(user) = std::env::var("USER") {
+        roots.push(PathBuf::from("/media").join(&user));
+        roots.push(PathBuf::from("/run/media").join(&user));
+    }
+    roots.push(PathBuf::from("/media"));
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_detects_readers_and_sideloads_with_series_names() {
+        let media = tempfile::tempdir().unwrap();
+        let kindle_root = media.path().join("Kindle");
+        std::fs::create_dir_all(kindle_root.join("system")).unwrap();
+        std::fs::create_dir_all(kindle_root.join("documents")).unwrap();
+        std::fs::write(kindle_root.join("documents/My Clippings.txt"), "").unwrap();
+        std::fs::create_dir_all(media.path().join("KOBOeReader/.kobo")).unwrap();
+        std::fs::create_dir_all(media.path().join("USB Stick/Photos")).unwrap();
+
+        let manager = DeviceManager::with_mount_roots(vec![media.path().to_path_buf()]);
+        let readers = manager.detect();
+        assert_eq!(readers.iter().map(|r| (r.name.as_str(), r.kind)).collect::<Vec<_>>(), [
+            ("KOBOeReader", EreaderKind::Kobo),
+            ("Kindle", EreaderKind::Kindle),
+        ]);
+        let (kobo, kindle) = (&readers[0], &readers[1]);
+        assert!(kindle.total_bytes > 0 && kindle.clippings_path().is_some());
+
+        let library = tempfile::tempdir().unwrap();
+        let book_file = |name: &str, format: BookFormat| {
+            let path = library.path().join(name);
+            std::fs::write(&path, name.as_bytes()).unwrap();
+            Book::new(name.to_string(), "Frank Herbert".to_string(), path, name.len() as u64, format)
+        };
+        let mut dune = book_file("Dune", BookFormat::Epub);
+        dune.series = Some("Dune Chronicles".to_string());
+        dune.series_index = Some(1.0);
+        let mut novella = book_file("Hunters", BookFormat::Epub);
+        novella.series = Some("Dune Chronicles".to_string());
+        novella.series_index = Some(2.5);
+        let whipping_star = book_file("Whipping Star", BookFormat::Azw3);
+
+        let books = [dune, novella, whipping_star];
+        let report = manager.sideload(kobo, &books, Some("Sci-Fi: Classics")).await.unwrap();
+        assert_eq!(report.failed, [("Whipping Star".to_string(), "Kobo can't open AZW3 books".to_string())]);
+        assert_eq!(report.copied, [
+            kobo.books_dir.join("Sci-Fi_ Classics/Dune Chronicles/01 - Dune.epub"),
+            kobo.books_dir.join("Sci-Fi_ Classics/Dune Chronicles/02.5 - Hunters.epub"),
+        ]);
+        assert_eq!(manager.sideload(kobo, &books[..2], Some("Sci-Fi: Classics")).await.unwrap().skipped, 2);
+
+        let on_kobo = manager.list_books(kobo).unwrap();
+        assert_eq!(on_kobo.len(), 2);
+        assert_eq!(on_kobo[0].relative_path, Path::new("Sci-Fi_ Classics/Dune Chronicles/01 - Dune.epub"));
+
+        let report = manager.sideload(kindle, &books[2..], None).await.unwrap();
+        assert_eq!(report.copied, [kindle.books_dir.join("Frank Herbert - Whipping Star.azw3")]);
+    }
+}
@@ -0,0 +1,1003 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use ripemd::{Digest, Ripemd128};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use tracing::info;
+
+use crate::services::vocabulary_service::VocabularyService;
+use crate::utils::text_search::{SnowballStemmer, Stemmer};
+
+static MDX_HEADER_ATTRIBUTE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(\w+)="([^"]*)""#).unwrap());
+
+/// MDX entries that only point at another headword
+const MDX_LINK_PREFIX: &str = "@@@LINK=";
+/// How many links in a row an MDX entry may go through to reach its definition
+const MAX_MDX_LINKS: usize = 8;
+/// Entries inserted per statement when installing a dictionary
+const INSERT_BATCH_SIZE: usize = 500;
+
+/// File format of an installed dictionary
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum DictionaryFormat {
+    StarDict,
+    Mdx,
+}
+
+impl DictionaryFormat {
+    /// Get display name
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            DictionaryFormat::StarDict => "StarDict",
+            DictionaryFormat::Mdx => "MDict",
+        }
+    }
+
+    pub fn to_string(&self) -> String {
+        match self {
+            DictionaryFormat::StarDict => "stardict".to_string(),
+            DictionaryFormat::Mdx => "mdx".to_string(),
+        }
+    }
+
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s {
+            "stardict" => Some(DictionaryFormat::StarDict),
+            "mdx" => Some(DictionaryFormat::Mdx),
+            _ => None,
+        }
+    }
+
+    /// Tell the format from a dictionary's file name
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".mdx") {
+            Some(DictionaryFormat::Mdx)
+        } else if [".ifo", ".idx", ".idx.gz", ".dict", ".dict.dz"].iter().any(|ext| name.ends_with(ext)) {
+            Some(DictionaryFormat::StarDict)
+        } else {
+            None
+        }
+    }
+}
+
+/// How a definition's text is marked up
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum DefinitionFormat {
+    PlainText,
+    Html,
+}
+
+impl DefinitionFormat {
+    pub fn to_string(&self) -> String {
+        match self {
+            DefinitionFormat::PlainText => "text".to_string(),
+            DefinitionFormat::Html => "html".to_string(),
+        }
+    }
+
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s {
+            "text" => Some(DefinitionFormat::PlainText),
+            "html" => Some(DefinitionFormat::Html),
+            _ => None,
+        }
+    }
+}
+
+/// A headword and its definition, as read from a dictionary file
+#[derive(Debug, Clone, PartialEq)]
+pub struct DictionaryEntry {
+    pub headword: String,
+    pub definition: String,
+    pub format: DefinitionFormat,
+}
+
+/// The contents of a dictionary file
+#[derive(Debug, Clone)]
+pub struct ParsedDictionary {
+    pub name: String,
+    pub entries: Vec<DictionaryEntry>,
+}
+
+/// A dictionary the user installed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InstalledDictionary {
+    pub id: String,
+    pub name: String,
+    pub format: DictionaryFormat,
+    pub language: String, // Primary language subtag of its headwords, e.g. "en"
+    pub source_path: PathBuf,
+    pub entry_count: usize,
+    pub priority: i64, // Lower comes first among dictionaries of the same language
+    pub enabled: bool,
+    pub installed_at: DateTime<Utc>,
+}
+
+/// A definition found for a looked-up word
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Definition {
+    pub dictionary_id: String,
+    pub dictionary_name: String,
+    pub headword: String,
+    pub definition: String,
+    pub format: DefinitionFormat,
+}
+
+/// A past lookup
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LookupHistoryEntry {
+    pub word: String,
+    pub language: String,
+    pub definitions_found: usize,
+    pub looked_up_at: DateTime<Utc>,
+}
+
+/// Offline dictionaries for looking up words while reading
+///
+/// Installing a dictionary reads its StarDict or MDX files into the database,
+/// so lookups are indexed queries and the original files can be moved away.
+/// Every lookup is added to the vocabulary of its language.
+#[derive(Clone)]
+pub struct DictionaryService {
+    pool: SqlitePool,
+    vocabulary: VocabularyService,
+}
+
+impl DictionaryService {
+    pub fn new(pool: SqlitePool) -> Self {
+        let vocabulary = VocabularyService::new(pool.clone());
+        Self { pool, vocabulary }
+    }
+
+    /// Words looked up so far, for review and export
+    pub fn vocabulary(&self) -> &VocabularyService {
+        &self.vocabulary
+    }
+
+    /// Initialize dictionary tables
+    pub async fn init_tables(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS dictionaries (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                format TEXT NOT NULL,
+                language TEXT NOT NULL,
+                source_path TEXT NOT NULL,
+                entry_count INTEGER NOT NULL,
+                priority INTEGER NOT NULL,
+                enabled INTEGER NOT NULL DEFAULT 1,
+                installed_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS dictionary_entries (
+                dictionary_id TEXT NOT NULL,
+                headword TEXT NOT NULL,
+                headword_key TEXT NOT NULL,
+                definition TEXT NOT NULL,
+                format TEXT NOT NULL,
+                FOREIGN KEY (dictionary_id) REFERENCES dictionaries (id) ON DELETE CASCADE
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_dictionary_entries_key ON dictionary_entries(headword_key, dictionary_id);")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS dictionary_lookups (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                word TEXT NOT NULL,
+                language TEXT NOT NULL,
+                definitions_found INTEGER NOT NULL,
+                looked_up_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        self.vocabulary.init_tables().await
+    }
+
+    /// Install a StarDict (`.ifo`) or MDX dictionary for looking up words of a language
+    ///
+    /// It goes after the language's other dictionaries in priority.
+    pub async fn install_dictionary(&self, path: &Path, language: &str) -> Result<InstalledDictionary> {
+        let format = DictionaryFormat::from_path(path)
+            .ok_or_else(|| anyhow!("Not a StarDict or MDX dictionary: {}", path.display()))?;
+        let language = language_key(language).ok_or_else(|| anyhow!("No dictionary language given"))?;
+        let source = path.to_path_buf();
+        let parsed = tokio::task::spawn_blocking(move || match format {
+            DictionaryFormat::StarDict => read_stardict(&source),
+            DictionaryFormat::Mdx => read_mdx(&source),
+        })
+        .await??;
+
+        let priority: i64 = sqlx::query_scalar("SELECT COALESCE(MAX(priority) + 1, 0) FROM dictionaries WHERE language = ?")
+            .bind(&language)
+            .fetch_one(&self.pool)
+            .await?;
+        let dictionary = InstalledDictionary {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: parsed.name,
+            format,
+            language,
+            source_path: path.to_path_buf(),
+            entry_count: parsed.entries.len(),
+            priority,
+            enabled: true,
+            installed_at: Utc::now(),
+        };
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO dictionaries (id, name, format, language, source_path, entry_count, priority, enabled, installed_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, 1, ?)",
+        )
+        .bind(&dictionary.id)
+        .bind(&dictionary.name)
+        .bind(dictionary.format.to_string())
+        .bind(&dictionary.language)
+        .bind(dictionary.source_path.to_string_lossy().to_string())
+        .bind(dictionary.entry_count as i64)
+        .bind(dictionary.priority)
+        .bind(dictionary.installed_at.to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+
+        for batch in parsed.entries.chunks(INSERT_BATCH_SIZE) {
+            let mut insert = sqlx::QueryBuilder::new(
+                "INSERT INTO dictionary_entries (dictionary_id, headword, headword_key, definition, format) ",
+            );
+            insert.push_values(batch, |mut row, entry| {
+                row.push_bind(&dictionary.id)
+                    .push_bind(&entry.headword)
+                    .push_bind(headword_key(&entry.headword))
+                    .push_bind(&entry.definition)
+                    .push_bind(entry.format.to_string());
+            });
+            insert.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+
+        info!("Installed dictionary {} with {} entries", dictionary.name, dictionary.entry_count);
+        Ok(dictionary)
+    }
+
+    /// Installed dictionaries in priority order, optionally only those of a language
+    pub async fn get_dictionaries(&self, language: Option<&str>) -> Result<Vec<InstalledDictionary>> {
+        let rows = match language.and_then(language_key) {
+            Some(language) => {
+                sqlx::query("SELECT * FROM dictionaries WHERE language = ? ORDER BY priority, name")
+                    .bind(language)
+                    .fetch_all(&self.pool)
+                    .await?
+            }
+            None => {
+                sqlx::query("SELECT * FROM dictionaries ORDER BY language, priority, name")
+                    .fetch_all(&self.pool)
+                    .await?
+            }
+        };
+        rows.iter().map(Self::row_to_dictionary).collect()
+    }
+
+    /// Set the order a language's dictionaries are consulted in, first to last
+    pub async fn set_dictionary_order(&self, language: &str, dictionary_ids: &[String]) -> Result<()> {
+        let language = language_key(language).ok_or_else(|| anyhow!("No dictionary language given"))?;
+        let mut tx = self.pool.begin().await?;
+        for (priority, id) in dictionary_ids.iter().enumerate() {
+            let updated = sqlx::query("UPDATE dictionaries SET priority = ? WHERE id = ? AND language = ?")
+                .bind(priority as i64)
+                .bind(id)
+                .bind(&language)
+                .execute(&mut *tx)
+                .await?;
+            if updated.rows_affected() == 0 {
+                return Err(anyhow!("No {} dictionary with id {}", language, id));
+            }
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Include or leave out a dictionary from lookups without removing it
+    pub async fn set_dictionary_enabled(&self, dictionary_id: &str, enabled: bool) -> Result<()> {
+        sqlx::query("UPDATE dictionaries SET enabled = ? WHERE id = ?")
+            .bind(enabled)
+            .bind(dictionary_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Remove a dictionary and its entries
+    pub async fn remove_dictionary(&self, dictionary_id: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM dictionary_entries WHERE dictionary_id = ?")
+            .bind(dictionary_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM dictionaries WHERE id = ?")
+            .bind(dictionary_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Look up a word selected in a book of a language, recording the lookup in the history
+    ///
+    /// Definitions come from the language's enabled dictionaries in priority
+    /// order. A word no dictionary has as a headword is looked up by its stem,
+    /// so "running" finds "run".
+    pub async fn lookup_word(&self, word: &str, language: &str) -> Result<Vec<Definition>> {
+        self.lookup_word_in_context(word, language, None, None).await
+    }
+
+    /// Look up a word, keeping the book and sentence it was selected in for the vocabulary
+    pub async fn lookup_word_in_context(
+        &self,
+        word: &str,
+        language: &str,
+        book_id: Option<&str>,
+        sentence: Option<&str>,
+    ) -> Result<Vec<Definition>> {
+        let word = word.trim().trim_matches(|c: char| !c.is_alphanumeric());
+        let language = language_key(language).ok_or_else(|| anyhow!("No language given for the lookup"))?;
+        if word.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let key = headword_key(word);
+        let mut definitions = self.find_definitions(&key, &language).await?;
+        if definitions.is_empty() {
+            if let Some(stemmer) = SnowballStemmer::for_language(&language) {
+                definitions = self.find_by_stem(&stemmer.stem(&key), &stemmer, &language).await?;
+            }
+        }
+
+        sqlx::query("INSERT INTO dictionary_lookups (word, language, definitions_found, looked_up_at) VALUES (?, ?, ?, ?)")
+            .bind(word)
+            .bind(&language)
+            .bind(definitions.len() as i64)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        self.vocabulary.record_lookup(word, &language, definitions.first(), book_id, sentence).await?;
+        Ok(definitions)
+    }
+
+    async fn find_definitions(&self, key: &str, language: &str) -> Result<Vec<Definition>> {
+        let rows = sqlx::query(
+            "SELECT e.*, d.name AS dictionary_name FROM dictionary_entries e \
+             JOIN dictionaries d ON d.id = e.dictionary_id \
+             WHERE e.headword_key = ? AND d.language = ? AND d.enabled = 1 \
+             ORDER BY d.priority, d.name, e.rowid",
+        )
+        .bind(key)
+        .bind(language)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(Self::row_to_definition).collect()
+    }
+
+    /// Definitions of headwords that share a stem, shortest headwords first
+    async fn find_by_stem(&self, stem: &str, stemmer: &SnowballStemmer, language: &str) -> Result<Vec<Definition>> {
+        if stem.is_empty() {
+            return Ok(Vec::new());
+        }
+        // Headwords with the same stem almost always start with it
+        let rows = sqlx::query(
+            "SELECT e.*, d.name AS dictionary_name FROM dictionary_entries e \
+             JOIN dictionaries d ON d.id = e.dictionary_id \
+             WHERE e.headword_key >= ? AND e.headword_key < ? AND d.language = ? AND d.enabled = 1 \
+             ORDER BY d.priority, d.name, length(e.headword_key), e.rowid",
+        )
+        .bind(stem)
+        .bind(format!("{}\u{10FFFF}", stem))
+        .bind(language)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut definitions = Vec::new();
+        for row in &rows {
+            let key: String = row.get("headword_key");
+            if stemmer.stem(&key) == stem {
+                definitions.push(Self::row_to_definition(row)?);
+            }
+        }
+        Ok(definitions)
+    }
+
+    /// The most recent lookups, newest first
+    pub async fn get_lookup_history(&self, limit: usize) -> Result<Vec<LookupHistoryEntry>> {
+        let rows = sqlx::query("SELECT * FROM dictionary_lookups ORDER BY id DESC LIMIT ?")
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter()
+            .map(|row| {
+                let looked_up_at: String = row.get("looked_up_at");
+                Ok(LookupHistoryEntry {
+                    word: row.get("word"),
+                    language: row.get("language"),
+                    definitions_found: row.get::<i64, _>("definitions_found") as usize,
+                    looked_up_at: DateTime::parse_from_rfc3339(&looked_up_at)?.with_timezone(&Utc),
+                })
+            })
+            .collect()
+    }
+
+    pub async fn clear_lookup_history(&self) -> Result<()> {
+        sqlx::query("DELETE FROM dictionary_lookups").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    fn row_to_dictionary(row: &sqlx::sqlite::SqliteRow) -> Result<InstalledDictionary> {
+        let format: String = row.get("format");
+        let source_path: String = row.get("source_path");
+        let installed_at: String = row.get("installed_at");
+        Ok(InstalledDictionary {
+            id: row.get("id"),
+            name: row.get("name"),
+            format: DictionaryFormat::from_string(&format).ok_or_else(|| anyhow!("Unknown dictionary format: {}", format))?,
+            language: row.get("language"),
+            source_path: PathBuf::from(source_path),
+            entry_count: row.get::<i64, _>("entry_count") as usize,
+            priority: row.get("priority"),
+            enabled: row.get("enabled"),
+            installed_at: DateTime::parse_from_rfc3339(&installed_at)?.with_timezone(&Utc),
+        })
+    }
+
+    fn row_to_definition(row: &sqlx::sqlite::SqliteRow) -> Result<Definition> {
+        let format: String = row.get("format");
+        Ok(Definition {
+            dictionary_id: row.get("dictionary_id"),
+            dictionary_name: row.get("dictionary_name"),
+            headword: row.get("headword"),
+            definition: row.get("definition"),
+            format: DefinitionFormat::from_string(&format).ok_or_else(|| anyhow!("Unknown definition format: {}", format))?,
+        })
+    }
+}
+
+/// Primary subtag of a language tag, e.g. "pt" for "pt-BR"
+pub(crate) fn language_key(language: &str) -> Option<String> {
+    language
+        .split(['-', '_'])
+        .next()
+        .map(|subtag| subtag.trim().to_lowercase())
+        .filter(|subtag| !subtag.is_empty())
+}
+
+/// Headwords are matched ignoring case
+fn headword_key(headword: &str) -> String {
+    headword.trim().to_lowercase()
+}
+
+/// Read a StarDict dictionary from any of its files, usually the `.ifo`
+///
+/// The index and definitions may be gzipped (`.idx.gz`, `.dict.dz`);
+/// synonyms in a `.syn` file become headwords of their own.
+pub fn read_stardict(path: &Path) -> Result<ParsedDictionary> {
+    let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let stem = [".ifo", ".idx.gz", ".idx", ".dict.dz", ".dict", ".syn"].iter()
+        .find_map(|ext| name.strip_suffix(ext))
+        .ok_or_else(|| anyhow!("Not a StarDict file: {}", path.display()))?;
+    let file = |ext: &str| path.with_file_name(format!("{}{}", stem, ext));
+
+    let ifo = std::fs::read_to_string(file(".ifo"))
+        .map_err(|e| anyhow!("Failed to read {}: {}", file(".ifo").display(), e))?;
+    let info: HashMap<&str, &str> = ifo.lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect();
+    let offset_bits = info.get("idxoffsetbits").copied().unwrap_or("32");
+    let same_type_sequence = info.get("sametypesequence").copied().unwrap_or_default();
+
+    let index = read_maybe_gzipped(&file(".idx"), &file(".idx.gz"))?;
+    let dict = read_maybe_gzipped(&file(".dict"), &file(".dict.dz"))?;
+
+    let mut reader = ByteReader::new(&index);
+    let mut entries = Vec::new();
+    while !reader.is_empty() {
+        let headword = String::from_utf8_lossy(reader.until_nul()?).to_string();
+        let offset = if offset_bits == "64" { reader.u64_be()? } else { reader.u32_be()? as u64 } as usize;
+        let size = reader.u32_be()? as usize;
+        let data = dict.get(offset..offset + size)
+            .ok_or_else(|| anyhow!("Definition of {:?} is past the end of the dictionary", headword))?;
+        let (definition, format) = stardict_definition(data, same_type_sequence)?;
+        entries.push(DictionaryEntry { headword, definition, format });
+    }
+
+    if let Ok(synonyms) = std::fs::read(file(".syn")) {
+        let mut reader = ByteReader::new(&synonyms);
+        let mut synonym_entries = Vec::new();
+        while !reader.is_empty() {
+            let synonym = String::from_utf8_lossy(reader.until_nul()?).to_string();
+            let index = reader.u32_be()? as usize;
+            if let Some(entry) = entries.get(index) {
+                synonym_entries.push(DictionaryEntry { headword: synonym, ..entry.clone() });
+            }
+        }
+        entries.extend(synonym_entries);
+    }
+
+    let name = info.get("bookname").map(|name| name.to_string()).unwrap_or_else(|| stem.to_string());
+    Ok(ParsedDictionary { name, entries })
+}
+
+fn read_maybe_gzipped(plain: &Path, gzipped: &Path) -> Result<Vec<u8>> {
+    if plain.exists() {
+        return std::fs::read(plain).map_err(|e| anyhow!("Failed to read {}: {}", plain.display(), e));
+    }
+    let file = std::fs::File::open(gzipped).map_err(|e| anyhow!("Failed to read {}: {}", gzipped.display(), e))?;
+    let mut data = Vec::new();
+    flate2::read::GzDecoder::new(file).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Turn a StarDict definition's fields into one text
+///
+/// Each field starts with a type letter unless the dictionary declares the
+/// same types for every definition. Lowercase types are text; uppercase ones
+/// are sized binary data such as sounds and pictures, which are left out.
+fn stardict_definition(data: &[u8], same_type_sequence: &str) -> Result<(String, DefinitionFormat)> {
+    let mut reader = ByteReader::new(data);
+    let mut fields: Vec<(u8, String)> = Vec::new();
+    let declared = same_type_sequence.as_bytes();
+    let mut field = 0;
+    while !reader.is_empty() && (declared.is_empty() || field < declared.len()) {
+        let kind = if declared.is_empty() { reader.bytes(1)?[0] } else { declared[field] };
+        let last = !declared.is_empty() && field == declared.len() - 1;
+        field += 1;
+        if kind.is_ascii_uppercase() {
+            // The last declared field runs to the end without a size
+            let size = if last { reader.remaining() } else { reader.u32_be()? as usize };
+            reader.bytes(size)?;
+        } else {
+            let text = if last { reader.rest() } else { reader.until_nul()? };
+            fields.push((kind, String::from_utf8_lossy(text).trim().to_string()));
+        }
+    }
+
+    // Pango markup, XDXF and HTML all read as HTML
+    if fields.iter().any(|(kind, _)| matches!(kind, b'g' | b'h' | b'x')) {
+        let html = fields.iter()
+            .map(|(kind, text)| match kind {
+                b'g' | b'h' | b'x' => text.clone(),
+                _ => format!("<p>{}</p>", html_escape::encode_text(text).replace('\n', "<br>")),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok((html, DefinitionFormat::Html))
+    } else {
+        let text = fields.into_iter().map(|(_, text)| text).collect::<Vec<_>>().join("\n");
+        Ok((text, DefinitionFormat::PlainText))
+    }
+}
+
+/// Read an MDict `.mdx` dictionary
+///
+/// Versions 1.2 and 2.0 with zlib-compressed or uncompressed blocks are read,
+/// including those whose key index is encrypted. Dictionaries locked to a
+/// registration code, and LZO-compressed ones, aren't supported.
+pub fn read_mdx(path: &Path) -> Result<ParsedDictionary> {
+    let data = std::fs::read(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let mut reader = ByteReader::new(&data);
+
+    let header_size = reader.u32_be()? as usize;
+    let header = decode_utf16le(reader.bytes(header_size)?);
+    reader.bytes(4)?; // Adler-32 of the header
+    let attributes: HashMap<String, String> = MDX_HEADER_ATTRIBUTE.captures_iter(&header)
+        .map(|captures| (captures[1].to_string(), html_escape::decode_html_entities(&captures[2]).to_string()))
+        .collect();
+
+    let version: f32 = attributes.get("GeneratedByEngineVersion").and_then(|v| v.trim().parse().ok()).unwrap_or(2.0);
+    let encrypted = match attributes.get("Encrypted").map(|v| v.trim()) {
+        Some("Yes") => 1,
+        Some("No") | None => 0,
+        Some(value) => value.parse::<u8>().unwrap_or(0),
+    };
+    if encrypted & 1 != 0 {
+        return Err(anyhow!("This MDX dictionary is locked to a registration code"));
+    }
+    let encoding_label = attributes.get("Encoding").map(|v| v.trim().to_string()).unwrap_or_default();
+    let utf16 = encoding_label.eq_ignore_ascii_case("UTF-16");
+    let encoding = match encoding_label.to_uppercase().as_str() {
+        "" | "UTF-8" | "UTF-16" => encoding_rs::UTF_8,
+        "GBK" | "GB2312" => encoding_rs::GB18030,
+        label => encoding_rs::Encoding::for_label(label.as_bytes()).unwrap_or(encoding_rs::UTF_8),
+    };
+    let decode = |bytes: &[u8]| -> String {
+        let text = if utf16 { decode_utf16le(bytes) } else { encoding.decode(bytes).0.into_owned() };
+        text.trim_end_matches('\0').to_string()
+    };
+    let v2 = version >= 2.0;
+    let number = |reader: &mut ByteReader| -> Result<usize> {
+        Ok(if v2 { reader.u64_be()? as usize } else { reader.u32_be()? as usize })
+    };
+
+    // Keys: which headwords there are and where their records start
+    let key_block_count = number(&mut reader)?;
+    let _entry_count = number(&mut reader)?;
+    if v2 {
+        number(&mut reader)?; // Decompressed size of the key block index
+    }
+    let key_index_size = number(&mut reader)?;
+    number(&mut reader)?; // Size of the key blocks
+    if v2 {
+        reader.bytes(4)?; // Adler-32 of the sizes
+    }
+    let key_index = reader.bytes(key_index_size)?;
+    let key_index = if v2 {
+        let key_index = if encrypted & 2 != 0 { decrypt_mdx_key_index(key_index) } else { key_index.to_vec() };
+        decompress_mdx_block(&key_index)?
+    } else {
+        key_index.to_vec()
+    };
+
+    let mut index_reader = ByteReader::new(&key_index);
+    let mut key_block_sizes = Vec::with_capacity(key_block_count);
+    for _ in 0..key_block_count {
+        number(&mut index_reader)?; // Entries in the block
+        for _ in 0..2 {
+            // The block's first and last headword
+            let chars = if v2 { index_reader.u16_be()? as usize } else { index_reader.bytes(1)?[0] as usize };
+            let terminator = if v2 { 1 } else { 0 };
+            let width = if utf16 { 2 } else { 1 };
+            index_reader.bytes((chars + terminator) * width)?;
+        }
+        let compressed_size = number(&mut index_reader)?;
+        number(&mut index_reader)?; // Decompressed size
+        key_block_sizes.push(compressed_size);
+    }
+
+    let mut keys: Vec<(usize, String)> = Vec::new();
+    for size in key_block_sizes {
+        let block = decompress_mdx_block(reader.bytes(size)?)?;
+        let mut block_reader = ByteReader::new(&block);
+        while !block_reader.is_empty() {
+            let record_offset = number(&mut block_reader)?;
+            let text = if utf16 { block_reader.until_nul_u16()? } else { block_reader.until_nul()? };
+            keys.push((record_offset, decode(text)));
+        }
+    }
+
+    // Records: the definitions, one after another in decompressed blocks
+    let record_block_count = number(&mut reader)?;
+    number(&mut reader)?; // Entries
+    number(&mut reader)?; // Size of the record block index
+    number(&mut reader)?; // Size of the record blocks
+    let mut record_block_sizes = Vec::with_capacity(record_block_count);
+    for _ in 0..record_block_count {
+        let compressed_size = number(&mut reader)?;
+        number(&mut reader)?; // Decompressed size
+        record_block_sizes.push(compressed_size);
+    }
+    let mut records = Vec::new();
+    for size in record_block_sizes {
+        records.extend(decompress_mdx_block(reader.bytes(size)?)?);
+    }
+
+    let mut definitions: Vec<(String, String)> = Vec::with_capacity(keys.len());
+    for (index, (start, headword)) in keys.iter().enumerate() {
+        let end = keys.get(index + 1).map_or(records.len(), |(next, _)| *next);
+        let record = records.get(*start..end.max(*start))
+            .ok_or_else(|| anyhow!("Definition of {:?} is past the end of the dictionary", headword))?;
+        definitions.push((headword.clone(), decode(record).trim().to_string()));
+    }
+
+    // Entries that only link to another headword take that headword's definition
+    let by_headword: HashMap<&str, &str> = definitions.iter()
+        .filter(|(_, definition)| !definition.starts_with(MDX_LINK_PREFIX))
+        .map(|(headword, definition)| (headword.as_str(), definition.as_str()))
+        .collect();
+    let linked: HashMap<&str, &str> = definitions.iter()
+        .filter_map(|(headword, definition)| {
+            definition.strip_prefix(MDX_LINK_PREFIX).map(|target| (headword.as_str(), target.trim()))
+        })
+        .collect();
+    let resolve = |target: &str| {
+        let mut target = target.to_string();
+        for _ in 0..MAX_MDX_LINKS {
+            if let Some(definition) = by_headword.get(target.as_str()) {
+                return Some(definition.to_string());
+            }
+            target = linked.get(target.as_str())?.to_string();
+        }
+        None
+    };
+    let entries = definitions.iter()
+        .filter_map(|(headword, definition)| {
+            let definition = match definition.strip_prefix(MDX_LINK_PREFIX) {
+                Some(target) => resolve(target.trim())?,
+                None => definition.clone(),
+            };
+            Some(DictionaryEntry { headword: headword.clone(), definition, format: DefinitionFormat::Html })
+        })
+        .collect();
+
+    let name = attributes.get("Title")
+        .map(|title| title.trim().to_string())
+        .filter(|title| !title.is_empty() && title != "Title (No HTML code allowed)")
+        .or_else(|| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+        .unwrap_or_default();
+    Ok(ParsedDictionary { name, entries })
+}
+
+/// Decompress an MDX block: a 4-byte compression type, an Adler-32 checksum, then the data
+fn decompress_mdx_block(block: &[u8]) -> Result<Vec<u8>> {
+    let (kind, data) = (block.get(..4), block.get(8..));
+    match (kind, data) {
+        (Some([0, 0, 0, 0]), Some(data)) => Ok(data.to_vec()),
+        (Some([2, 0, 0, 0]), Some(data)) => {
+            let mut decompressed = Vec::new();
+            flate2::read::ZlibDecoder::new(data).read_to_end(&mut decompressed)?;
+            Ok(decompressed)
+        }
+        (Some([1, 0, 0, 0]), Some(_)) => Err(anyhow!("LZO-compressed MDX dictionaries aren't supported")),
+        _ => Err(anyhow!("Unknown MDX block compression")),
+    }
+}
+
+/// The key of an encrypted MDX key index block
+fn mdx_key_index_key(block: &[u8]) -> Vec<u8> {
+    let mut hasher = Ripemd128::new();
+    hasher.update(&block[4..8]);
+    hasher.update(0x3695u32.to_le_bytes());
+    hasher.finalize().to_vec()
+}
+
+/// Decrypt an MDX key index block, keeping its compression header
+fn decrypt_mdx_key_index(block: &[u8]) -> Vec<u8> {
+    if block.len() < 8 {
+        return block.to_vec();
+    }
+    let key = mdx_key_index_key(block);
+    let mut decrypted = block[..8].to_vec();
+    let mut previous = 0x36u8;
+    for (index, byte) in block[8..].iter().enumerate() {
+        decrypted.push(byte.rotate_left(4) ^ previous ^ (index as u8) ^ key[index % key.len()]);
+        previous = *byte;
+    }
+    decrypted
+}
+
+fn decode_utf16le(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+    String::from_utf16_lossy(&units).trim_end_matches('\0').to_string()
+}
+
+/// Reads big-endian numbers and strings from dictionary files
+struct ByteReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.position)
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8]> {
+        let bytes = self.data.get(self.position..self.position + count)
+            .ok_or_else(|| anyhow!("Dictionary file ends unexpectedly"))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.position.min(self.data.len())..];
+        self.position = self.data.len();
+        rest
+    }
+
+    fn u16_be(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into()?))
+    }
+
+    fn u32_be(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into()?))
+    }
+
+    fn u64_be(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.bytes(8)?.try_into()?))
+    }
+
+    /// Bytes up to a NUL, skipping the NUL
+    fn until_nul(&mut self) -> Result<&'a [u8]> {
+        let rest = &self.data[self.position..];
+        let length = rest.iter().position(|byte| *byte == 0)
+            .ok_or_else(|| anyhow!("Dictionary file ends unexpectedly"))?;
+        self.position += length + 1;
+        Ok(&rest[..length])
+    }
+
+    /// UTF-16 code units up to a NUL unit, skipping the NUL
+    fn until_nul_u16(&mut self) -> Result<&'a [u8]> {
+        let rest = &self.data[self.position..];
+        let length = rest.chunks_exact(2).position(|unit| unit == [0, 0])
+            .ok_or_else(|| anyhow!("Dictionary file ends unexpectedly"))? * 2;
+        self.position += length + 2;
+        Ok(&rest[..length])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn write_stardict(folder: &Path, name: &str, words: &[(&str, &str)]) -> PathBuf {
+        let mut index = Vec::new();
+        let mut dict = Vec::new();
+        for (word, definition) in words {
+            index.extend(word.as_bytes());
+            index.push(0);
+            index.extend((dict.len() as u32).to_be_bytes());
+            index.extend((definition.len() as u32).to_be_bytes());
+            dict.extend(definition.as_bytes());
+        }
+        let mut gzipped = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzipped.write_all(&dict).unwrap();
+        std::fs::write(folder.join(format!("{}.idx", name)), index).unwrap();
+        std::fs::write(folder.join(format!("{}.dict.dz", name)), gzipped.finish().unwrap()).unwrap();
+        // "ran" is a synonym of the first word
+        std::fs::write(folder.join(format!("{}.syn", name)), b"ran\0\0\0\0\0").unwrap();
+        let ifo = folder.join(format!("{}.ifo", name));
+        let info = format!("StarDict's dict ifo file\nversion=2.4.2\nbookname={}\nwordcount={}\nsametypesequence=m\n", name, words.len());
+        std::fs::write(&ifo, info).unwrap();
+        ifo
+    }
+
+    fn mdx_block(data: &[u8]) -> Vec<u8> {
+        let mut compressed = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        compressed.write_all(data).unwrap();
+        let mut block = vec![2, 0, 0, 0, 0, 0, 0, 0];
+        block.extend(compressed.finish().unwrap());
+        block
+    }
+
+    /// A version 2.0 MDX with one key block and one record block, its key index encrypted
+    fn write_mdx(path: &Path, words: &[(&str, &str)]) {
+        let header = "<Dictionary GeneratedByEngineVersion=\"2.0\" Encrypted=\"2\" Encoding=\"UTF-8\" Title=\"Pocket &amp; Co\"/>\r\n\0";
+        let header: Vec<u8> = header.encode_utf16().flat_map(u16::to_le_bytes).collect();
+
+        let mut keys = Vec::new();
+        let mut records = Vec::new();
+        for (word, definition) in words {
+            keys.extend((records.len() as u64).to_be_bytes());
+            keys.extend(word.as_bytes());
+            keys.push(0);
+            records.extend(definition.as_bytes());
+            records.push(0);
+        }
+        let key_block = mdx_block(&keys);
+        let record_block = mdx_block(&records);
+
+        let (first, last) = (words[0].0, words[words.len() - 1].0);
+        let mut key_index = Vec::new();
+        key_index.extend((words.len() as u64).to_be_bytes());
+        for word in [first, last] {
+            key_index.extend((word.len() as u16).to_be_bytes());
+            key_index.extend(word.as_bytes());
+            key_index.push(0);
+        }
+        key_index.extend((key_block.len() as u64).to_be_bytes());
+        key_index.extend((keys.len() as u64).to_be_bytes());
+        // Encrypting is decrypting run backwards
+        let mut key_index = mdx_block(&key_index);
+        let key = mdx_key_index_key(&key_index);
+        let mut previous = 0x36u8;
+        for (index, byte) in key_index[8..].iter_mut().enumerate() {
+            *byte = (*byte ^ previous ^ (index as u8) ^ key[index % key.len()]).rotate_left(4);
+            previous = *byte;
+        }
+
+        let mut file = Vec::new();
+        file.extend((header.len() as u32).to_be_bytes());
+        file.extend(&header);
+        file.extend([0; 4]);
+        for size in [1, words.len(), 0, key_index.len(), key_block.len()] {
+            file.extend((size as u64).to_be_bytes());
+        }
+        file.extend([0; 4]);
+        file.extend(&key_index);
+        file.extend(&key_block);
+        for size in [1, words.len(), 16, record_block.len(), record_block.len(), records.len()] {
+            file.extend((size as u64).to_be_bytes());
+        }
+        file.extend(&record_block);
+        std::fs::write(path, file).unwrap();
+    }
+
+    #[test]
+    fn test_read_mdx_with_encrypted_key_index_and_links() {
+        let folder = TempDir::new().unwrap();
+        let path = folder.path().join("pocket.mdx");
+        write_mdx(&path, &[("colour", "@@@LINK=color"), ("color", "<b>color</b> hue"), ("grey", "@@@LINK=gray")]);
+
+        let dictionary = read_mdx(&path).unwrap();
+        assert_eq!(dictionary.name, "Pocket & Co");
+        let definitions: Vec<(&str, &str)> = dictionary.entries.iter()
+            .map(|entry| (entry.headword.as_str(), entry.definition.as_str()))
+            .collect();
+        // "grey" links to a headword the dictionary doesn't have
+        assert_eq!(definitions, [("colour", "<b>color</b> hue"), ("color", "<b>color</b> hue")]);
+    }
+
+    #[tokio::test]
+    async fn test_lookup_by_language_priority_and_stem() {
+        let folder = TempDir::new().unwrap();
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let service = DictionaryService::new(pool);
+        service.init_tables().await.unwrap();
+
+        let wordnet = write_stardict(folder.path(), "WordNet", &[("run", "move fast on foot"), ("Read", "look at and understand")]);
+        let learner = write_stardict(folder.path(), "Learner", &[("run", "to go quickly")]);
+        let larousse = write_stardict(folder.path(), "Larousse", &[("lire", "déchiffrer")]);
+        let wordnet = service.install_dictionary(&wordnet, "en-US").await.unwrap();
+        let learner = service.install_dictionary(&learner, "en").await.unwrap();
+        service.install_dictionary(&larousse, "fr").await.unwrap();
+        assert_eq!((wordnet.entry_count, learner.priority), (3, 1));
+
+        let names = |definitions: Vec<Definition>| -> Vec<(String, String)> {
+            definitions.into_iter().map(|d| (d.dictionary_name, d.definition)).collect()
+        };
+        assert_eq!(names(service.lookup_word("Run,", "en").await.unwrap()), [
+            ("WordNet".to_string(), "move fast on foot".to_string()),
+            ("Learner".to_string(), "to go quickly".to_string()),
+        ]);
+
+        service.set_dictionary_order("en", &[learner.id.clone(), wordnet.id.clone()]).await.unwrap();
+        let found = service.lookup_word("running", "en-GB").await.unwrap();
+        assert_eq!(found[0].dictionary_name, "Learner");
+        assert_eq!(found[1].headword, "run");
+        assert_eq!(names(service.lookup_word("ran", "en").await.unwrap()).len(), 2);
+
+        service.set_dictionary_enabled(&learner.id, false).await.unwrap();
+        assert_eq!(service.lookup_word("reading", "en").await.unwrap()[0].headword, "Read");
+        assert!(service.lookup_word("lire", "en").await.unwrap().is_empty());
+
+        let history = service.get_lookup_history(10).await.unwrap();
+        let words: Vec<(&str, usize)> = history.iter().map(|h| (h.word.as_str(), h.definitions_found)).collect();
+        assert_eq!(words, [("lire", 0), ("reading", 1), ("ran", 2), ("running", 2), ("Run", 2)]);
+
+        service.lookup_word_in_context("Runs", "en", Some("book-1"), Some("She runs daily.")).await.unwrap();
+        let words = service.vocabulary().get_words("en").await.unwrap();
+        assert_eq!((words[0].word.as_str(), words[0].contexts[0].sentence.as_str()), ("Runs", "She runs daily."));
+        assert_eq!(words.len(), 6);
+
+        service.remove_dictionary(&wordnet.id).await.unwrap();
+        assert_eq!(service.get_dictionaries(Some("en")).await.unwrap(), [InstalledDictionary { enabled: false, priority: 0, ..learner }]);
+    }
+}
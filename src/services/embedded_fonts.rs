@@ -0,0 +1,294 @@
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha1::{Digest, Sha1};
+use tracing::warn;
+
+use crate::services::epub_parser::{EpubDocument, EpubParser};
+use crate::utils::url_encoding::percent_decode;
+
+static ENCRYPTED_DATA: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?s)<(?:\w+:)?EncryptedData\b.*?</(?:\w+:)?EncryptedData>").unwrap()
+});
+static ALGORITHM_ATTR: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\bAlgorithm\s*=\s*"([^"]*)""#).unwrap());
+static URI_ATTR: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\bURI\s*=\s*"([^"]*)""#).unwrap());
+static FONT_FACE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)@font-face\s*\{([^}]*)\}").unwrap());
+static CSS_DECLARATION: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)([\w-]+)\s*:\s*([^;]+)").unwrap());
+static CSS_URL: Lazy<Regex> = Lazy::new(|| Regex::new(r#"url\(\s*['"]?([^'")]+)['"]?\s*\)"#).unwrap());
+
+/// Bytes obfuscated by the IDPF algorithm
+const IDPF_OBFUSCATED_LENGTH: usize = 1040;
+
+/// Bytes obfuscated by the Adobe algorithm
+const ADOBE_OBFUSCATED_LENGTH: usize = 1024;
+
+/// Font obfuscation schemes used to stop fonts being copied out of an EPUB
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FontObfuscation {
+    Idpf,
+    Adobe,
+}
+
+impl FontObfuscation {
+    /// Recognize the algorithm URI from META-INF/encryption.xml
+    pub fn from_algorithm(algorithm: &str) -> Option<Self> {
+        match algorithm {
+            "http://www.idpf.org/2008/embedding" => Some(Self::Idpf),
+            "http://ns.adobe.com/pdf/enc#RC" => Some(Self::Adobe),
+            _ => None,
+        }
+    }
+
+    /// Reverse the obfuscation using the book's unique identifier
+    ///
+    /// Both schemes XOR the start of the file with a key, so this also obfuscates.
+    pub fn deobfuscate(&self, data: &mut [u8], unique_identifier: &str) {
+        let (key, length) = match self {
+            Self::Idpf => (idpf_key(unique_identifier), IDPF_OBFUSCATED_LENGTH),
+            Self::Adobe => match adobe_key(unique_identifier) {
+                Some(key) => (key, ADOBE_OBFUSCATED_LENGTH),
+                None => {
+                    warn!("Adobe font obfuscation needs a UUID identifier, got {}", unique_identifier);
+                    return;
+                }
+            },
+        };
+
+        for (i, byte) in data.iter_mut().take(length).enumerate() {
+            *byte ^= key[i % key.len()];
+        }
+    }
+}
+
+/// Font file embedded in an EPUB, ready for the renderer
+#[derive(Debug, Clone)]
+pub struct EmbeddedFont {
+    pub path: String,
+    pub media_type: String,
+    pub family: Option<String>,
+    pub weight: Option<String>,
+    pub style: Option<String>,
+    pub obfuscation: Option<FontObfuscation>,
+    pub data: Vec<u8>,
+}
+
+/// `@font-face` rule from a book stylesheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFace {
+    pub family: String,
+    pub src: String,
+    pub weight: Option<String>,
+    pub style: Option<String>,
+}
+
+/// Reads embedded fonts out of EPUBs
+pub struct EmbeddedFontExtractor;
+
+impl EmbeddedFontExtractor {
+    /// Read every font in the manifest, de-obfuscated and matched to its `@font-face` rule
+    pub fn extract(doc: &mut EpubDocument) -> Vec<EmbeddedFont> {
+        let obfuscated = doc.get_resource_str_by_path("META-INF/encryption.xml")
+            .map(|xml| Self::parse_encryption_xml(&xml))
+            .unwrap_or_default();
+        let unique_identifier = doc.unique_identifier.clone().unwrap_or_default();
+
+        let mut resources: Vec<(String, String)> = doc.resources.values()
+            .map(|(path, media_type)| (path.to_string_lossy().replace('\\', "/"), media_type.clone()))
+            .collect();
+        resources.sort();
+
+        let mut font_faces = Vec::new();
+        for (path, media_type) in &resources {
+            if media_type == "text/css" {
+                if let Some(css) = doc.get_resource_str_by_path(path) {
+                    font_faces.extend(Self::parse_font_faces(&css, path));
+                }
+            }
+        }
+
+        let mut fonts = Vec::new();
+        for (path, media_type) in resources {
+            if !is_font(&path, &media_type) {
+                continue;
+            }
+            let Some(mut data) = doc.get_resource_by_path(&path) else {
+                warn!("Font not found in EPUB: {}", path);
+                continue;
+            };
+
+            let obfuscation = obfuscated.iter()
+                .find(|(uri, _)| *uri == path)
+                .map(|(_, obfuscation)| *obfuscation);
+            if let Some(obfuscation) = obfuscation {
+                obfuscation.deobfuscate(&mut data, &unique_identifier);
+            }
+
+            let face = font_faces.iter().find(|face| face.src == path);
+            fonts.push(EmbeddedFont {
+                family: face.map(|face| face.family.clone()),
+                weight: face.and_then(|face| face.weight.clone()),
+                style: face.and_then(|face| face.style.clone()),
+                path,
+                media_type,
+                obfuscation,
+                data,
+            });
+        }
+
+        fonts
+    }
+
+    /// Obfuscated resources listed in META-INF/encryption.xml, by archive path
+    ///
+    /// Entries using real encryption (DRM) are skipped.
+    pub fn parse_encryption_xml(xml: &str) -> Vec<(String, FontObfuscation)> {
+        ENCRYPTED_DATA.find_iter(xml)
+            .filter_map(|block| {
+                let block = block.as_str();
+                let obfuscation = FontObfuscation::from_algorithm(&ALGORITHM_ATTR.captures(block)?[1])?;
+                let uri = percent_decode(&URI_ATTR.captures(block)?[1]);
+                Some((uri.trim_start_matches('/').to_string(), obfuscation))
+            })
+            .collect()
+    }
+
+    /// Read `@font-face` rules, resolving each source against the stylesheet's path
+    pub fn parse_font_faces(css: &str, css_path: &str) -> Vec<FontFace> {
+        FONT_FACE.captures_iter(css)
+            .filter_map(|rule| {
+                let mut family = None;
+                let mut src = None;
+                let mut weight = None;
+                let mut style = None;
+
+                for declaration in CSS_DECLARATION.captures_iter(&rule[1]) {
+                    let value = declaration[2].trim();
+                    match declaration[1].to_ascii_lowercase().as_str() {
+                        "font-family" => family = Some(value.trim_matches(|c| c == '"' || c == '\'').to_string()),
+                        "src" => {
+                            src = CSS_URL.captures(value)
+                                .map(|url| EpubParser::resolve_href(css_path, &percent_decode(&url[1])));
+                        }
+                        "font-weight" => weight = Some(value.to_string()),
+                        "font-style" => style = Some(value.to_string()),
+                        _ => {}
+                    }
+                }
+
+                Some(FontFace { family: family?, src: src?, weight, style })
+            })
+            .collect()
+    }
+
+    /// Write fonts to a directory so the renderer can load them by path
+    pub fn write_to_directory(fonts: &[EmbeddedFont], directory: &Path) -> Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(directory)?;
+
+        let mut paths = Vec::new();
+        for font in fonts {
+            let file_name = font.path.replace('/', "_");
+            let path = directory.join(file_name);
+            std::fs::write(&path, &font.data)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+fn is_font(path: &str, media_type: &str) -> bool {
+    media_type.starts_with("font/")
+        || matches!(
+            media_type,
+            "application/vnd.ms-opentype"
+                | "application/font-sfnt"
+                | "application/font-woff"
+                | "application/x-font-ttf"
+                | "application/x-font-truetype"
+                | "application/x-font-opentype"
+        )
+        || [".ttf", ".otf", ".woff", ".woff2"].iter().any(|ext| path.to_ascii_lowercase().ends_with(ext))
+}
+
+/// SHA-1 of the identifier with whitespace removed
+fn idpf_key(unique_identifier: &str) -> Vec<u8> {
+    let identifier: String = unique_identifier.chars()
+        .filter(|c| !matches!(c, ' ' | '\t' | '\r' | '\n'))
+        .collect();
+    Sha1::digest(identifier.as_bytes()).to_vec()
+}
+
+/// The 16 bytes of the UUID in the identifier
+fn adobe_key(unique_identifier: &str) -> Option<Vec<u8>> {
+    let hex: String = unique_identifier.trim()
+        .trim_start_matches("urn:uuid:")
+        .chars()
+        .filter(|c| *c != '-')
+        .collect();
+    if hex.len() != 32 {
+        return None;
+    }
+
+    (0..32).step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTIFIER: &str = "urn:uuid:a1b2c3d4-0000-4000-8000-000000000001";
+
+    #[test]
+    fn test_deobfuscation_keys() {
+        let key = idpf_key(" urn:uuid:a1b2c3d4-0000-4000-8000-000000000001\n");
+        assert_eq!(key[..4], [0x6e, 0x02, 0xdd, 0x79]);
+
+        let key = adobe_key(IDENTIFIER).unwrap();
+        assert_eq!(key[..4], [0xa1, 0xb2, 0xc3, 0xd4]);
+        assert_eq!(key[15], 0x01);
+
+        let font: Vec<u8> = (0..2000).map(|i| (i % 251) as u8).collect();
+        let mut data = font.clone();
+        FontObfuscation::Idpf.deobfuscate(&mut data, IDENTIFIER);
+        assert_ne!(data[..IDPF_OBFUSCATED_LENGTH], font[..IDPF_OBFUSCATED_LENGTH]);
+        assert_eq!(data[IDPF_OBFUSCATED_LENGTH..], font[IDPF_OBFUSCATED_LENGTH..]);
+        FontObfuscation::Idpf.deobfuscate(&mut data, IDENTIFIER);
+        assert_eq!(data, font);
+    }
+
+    #[test]
+    fn test_parse_encryption_xml_and_font_faces() {
+        let xml = r#"<encryption xmlns="urn:oasis:names:tc:opendocument:xmlns:container"
+            xmlns:enc="http://www.w3.org/2001/04/xmlenc#">
+            <enc:EncryptedData>
+                <enc:EncryptionMethod Algorithm="http://www.idpf.org/2008/embedding"/>
+                <enc:CipherData><enc:CipherReference URI="OEBPS/fonts/Noto%20Serif.otf"/></enc:CipherData>
+            </enc:EncryptedData>
+            <enc:EncryptedData>
+                <enc:EncryptionMethod Algorithm="http://www.w3.org/2001/04/xmlenc#aes128-cbc"/>
+                <enc:CipherData><enc:CipherReference URI="OEBPS/text/ch1.xhtml"/></enc:CipherData>
+            </enc:EncryptedData>
+        </encryption>"#;
+        assert_eq!(
+            EmbeddedFontExtractor::parse_encryption_xml(xml),
+            vec![("OEBPS/fonts/Noto Serif.otf".to_string(), FontObfuscation::Idpf)]
+        );
+
+        let css = r#"body { margin: 0 }
+            @font-face {
+                font-family: "Noto Serif CJK";
+                font-weight: bold;
+                src: url('../fonts/Noto%20Serif.otf') format("opentype");
+            }
+            @font-face { font-family: Missing; }"#;
+        let faces = EmbeddedFontExtractor::parse_font_faces(css, "OEBPS/styles/book.css");
+        assert_eq!(faces, vec![FontFace {
+            family: "Noto Serif CJK".to_string(),
+            src: "OEBPS/fonts/Noto Serif.otf".to_string(),
+            weight: Some("bold".to_string()),
+            style: None,
+        }]);
+    }
+}
@@ -0,0 +1,100 @@
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+static MATH: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<(?:\w+:)?math\b([^>]*)>(.*?)</(?:\w+:)?math\s*>").unwrap());
+static SVG: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<(?:\w+:)?svg\b[^>]*>(.*?)</(?:\w+:)?svg\s*>").unwrap());
+static ALTTEXT_ATTR: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(?is)\balttext\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+static TEX_ANNOTATION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?is)<(?:\w+:)?annotation\b[^>]*\bencoding\s*=\s*["'](?:application/x-tex|TeX)["'][^>]*>(.*?)</(?:\w+:)?annotation\s*>"#).unwrap()
+});
+static ANNOTATIONS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?is)<(?:\w+:)?annotation(?:-xml)?\b[^>]*>.*?</(?:\w+:)?annotation(?:-xml)?\s*>").unwrap()
+});
+static SVG_TITLE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<(?:\w+:)?(?:title|desc)\b[^>]*>(.*?)</(?:\w+:)?(?:title|desc)\s*>").unwrap());
+static SVG_TEXT: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<(?:\w+:)?text\b[^>]*>(.*?)</(?:\w+:)?text\s*>").unwrap());
+static TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]+>").unwrap());
+
+/// Replace MathML and inline SVG with readable text before tags are stripped
+///
+/// Stripping tags from MathML leaves disconnected tokens and from SVG leaves
+/// nothing useful, so equations become their alttext or TeX source and SVG
+/// figures their title, description or labels.
+pub fn inject_math_and_svg_text(html: &str) -> String {
+    let html = MATH.replace_all(html, |captures: &Captures| {
+        match math_text(&captures[1], &captures[2]) {
+            Some(text) => format!(" [Math: {}] ", html_escape::encode_text(&text)),
+            None => " ".to_string(),
+        }
+    });
+
+    SVG.replace_all(&html, |captures: &Captures| {
+        match svg_text(&captures[1]) {
+            Some(text) => format!(" [Figure: {}] ", html_escape::encode_text(&text)),
+            None => " ".to_string(),
+        }
+    })
+    .into_owned()
+}
+
+/// Text for an equation: its alttext, TeX annotation, or linearized tokens
+fn math_text(attributes: &str, body: &str) -> Option<String> {
+    if let Some(captures) = ALTTEXT_ATTR.captures(attributes) {
+        let alttext = captures.get(1).or_else(|| captures.get(2))?.as_str();
+        if let Some(text) = non_empty(&html_escape::decode_html_entities(alttext)) {
+            return Some(text);
+        }
+    }
+    if let Some(text) = TEX_ANNOTATION.captures(body).and_then(|captures| plain_text(&captures[1])) {
+        return Some(text);
+    }
+    plain_text(&ANNOTATIONS.replace_all(body, " "))
+}
+
+/// Text for an SVG figure: its title or description, else the labels it draws
+fn svg_text(body: &str) -> Option<String> {
+    if let Some(text) = SVG_TITLE.captures(body).and_then(|captures| plain_text(&captures[1])) {
+        return Some(text);
+    }
+    let labels: Vec<String> = SVG_TEXT.captures_iter(body)
+        .filter_map(|captures| plain_text(&captures[1]))
+        .collect();
+    non_empty(&labels.join(" "))
+}
+
+fn plain_text(markup: &str) -> Option<String> {
+    non_empty(&html_escape::decode_html_entities(&TAG.replace_all(markup, " ")))
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_math_and_svg_become_readable_text() {
+        let html = concat!(
+            r#"<p>Energy: <math alttext="E = mc^2"><mi>E</mi><mo>=</mo><mi>m</mi><msup><mi>c</mi><mn>2</mn></msup></math></p>"#,
+            r#"<p><m:math><m:semantics><m:mi>x</m:mi><m:annotation encoding="application/x-tex">x^{2}</m:annotation></m:semantics></m:math></p>"#,
+            r#"<p><math><mi>a</mi><mo>&lt;</mo><mi>b</mi></math></p>"#,
+            r#"<svg viewBox="0 0 10 10"><title>Unit circle</title><circle r="5"/></svg>"#,
+            r#"<svg><text x="1">A</text><text x="9">B</text></svg>"#,
+            r#"<svg><image href="cover.jpg"/></svg>"#,
+        );
+
+        assert_eq!(
+            inject_math_and_svg_text(html),
+            concat!(
+                "<p>Energy:  [Math: E = mc^2] </p>",
+                "<p> [Math: x^{2}] </p>",
+                "<p> [Math: a &lt; b] </p>",
+                " [Figure: Unit circle] ",
+                " [Figure: A B] ",
+                " ",
+            )
+        );
+    }
+}
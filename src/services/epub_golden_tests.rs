@@ -0,0 +1,167 @@
+//! Golden-file regression tests over a corpus of tricky EPUB fixtures
+//!
+//! Each directory in `tests/fixtures/epub` is an unpacked EPUB. The test packs it,
+//! runs it through the parser and reading pipeline, and compares the structured
+//! result with `<name>.golden.json` next to it. Run with `UPDATE_GOLDEN=1` to
+//! rewrite the snapshots after an intended parser change, then review the diff.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use zip::write::{SimpleFileOptions, ZipWriter};
+use zip::CompressionMethod;
+
+use crate::models::{Book, BookFormat};
+use crate::services::epub_parser::{EpubFeatures, EpubInfo, EpubParser};
+use crate::services::reading_service::ReadingService;
+
+/// Characters of cleaned chapter text kept in a snapshot
+const EXCERPT_CHARS: usize = 160;
+
+#[derive(Debug, Serialize)]
+struct GoldenSnapshot {
+    features: EpubFeatures,
+    title: Option<String>,
+    creator: Option<String>,
+    language: Option<String>,
+    spine: Vec<String>,
+    navigation: Option<EpubInfo>,
+    chapters: Vec<ChapterSnapshot>,
+    total_word_count: usize,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ChapterSnapshot {
+    id: String,
+    title: String,
+    word_count: usize,
+    excerpt: String,
+}
+
+fn corpus_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/epub")
+}
+
+/// Zip an unpacked fixture, storing `mimetype` first and uncompressed as the spec requires
+fn pack_fixture(fixture_dir: &Path, epub_path: &Path) {
+    let mut files = Vec::new();
+    collect_files(fixture_dir, &mut files);
+    files.sort();
+
+    let mut writer = ZipWriter::new(File::create(epub_path).unwrap());
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    writer.start_file("mimetype", stored).unwrap();
+    writer.write_all(&fs::read(fixture_dir.join("mimetype")).unwrap()).unwrap();
+
+    for file in files {
+        let name = file.strip_prefix(fixture_dir).unwrap().to_string_lossy().replace('\\', "/");
+        if name == "mimetype" {
+            continue;
+        }
+        writer.start_file(name, SimpleFileOptions::default()).unwrap();
+        writer.write_all(&fs::read(&file).unwrap()).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+async fn snapshot(epub_path: &Path) -> GoldenSnapshot {
+    let features = EpubParser::detect_features(epub_path).unwrap();
+    let mut snapshot = GoldenSnapshot {
+        features,
+        title: None,
+        creator: None,
+        language: None,
+        spine: Vec::new(),
+        navigation: None,
+        chapters: Vec::new(),
+        total_word_count: 0,
+        error: None,
+    };
+
+    match EpubParser::open_with_warnings(epub_path, None) {
+        Ok((mut doc, warnings)) => {
+            snapshot.title = doc.mdata("title");
+            snapshot.creator = doc.mdata("creator");
+            snapshot.language = doc.mdata("language");
+            snapshot.spine = doc.spine.iter().map(|item| item.idref.clone()).collect();
+            let mut navigation = EpubParser::navigation(&mut doc);
+            navigation.warnings = warnings;
+            snapshot.navigation = Some(navigation);
+        }
+        Err(e) => {
+            snapshot.error = Some(e.kind().to_string());
+            return snapshot;
+        }
+    }
+
+    let size = fs::metadata(epub_path).unwrap().len();
+    let book = Book::new("Fixture".to_string(), "Fixture".to_string(), epub_path.to_path_buf(), size, BookFormat::Epub);
+    match ReadingService::new().load_book_content(&book).await {
+        Ok(content) => {
+            snapshot.total_word_count = content.total_word_count;
+            snapshot.chapters = content.chapters.iter()
+                .map(|chapter| ChapterSnapshot {
+                    id: chapter.id.clone(),
+                    title: chapter.title.clone(),
+                    word_count: chapter.word_count,
+                    excerpt: chapter.content.chars().take(EXCERPT_CHARS).collect(),
+                })
+                .collect();
+        }
+        Err(e) => snapshot.error = Some(e.to_string()),
+    }
+
+    snapshot
+}
+
+#[tokio::test]
+async fn test_epub_corpus_matches_golden_snapshots() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let temp_dir = tempfile::TempDir::new().unwrap();
+
+    let mut fixtures: Vec<PathBuf> = fs::read_dir(corpus_dir()).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty(), "EPUB fixture corpus is empty");
+
+    let mut mismatches = Vec::new();
+    for fixture_dir in fixtures {
+        let name = fixture_dir.file_name().unwrap().to_string_lossy().to_string();
+        let epub_path = temp_dir.path().join(format!("{}.epub", name));
+        pack_fixture(&fixture_dir, &epub_path);
+
+        let actual = serde_json::to_string_pretty(&snapshot(&epub_path).await).unwrap() + "\n";
+        let golden_path = corpus_dir().join(format!("{}.golden.json", name));
+        if update {
+            fs::write(&golden_path, &actual).unwrap();
+            continue;
+        }
+
+        match fs::read_to_string(&golden_path) {
+            Ok(expected) if expected == actual => {}
+            Ok(_) => mismatches.push(format!("{} differs from {}", name, golden_path.display())),
+            Err(_) => mismatches.push(format!("{} has no golden snapshot", name)),
+        }
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "EPUB corpus regressions (rerun with UPDATE_GOLDEN=1 if intended):\n{}",
+        mismatches.join("\n")
+    );
+}
@@ -0,0 +1,897 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Write};
+use std::ops::Range;
+use std::path::Path;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use uuid::Uuid;
+use zip::write::{SimpleFileOptions, ZipWriter};
+use zip::CompressionMethod;
+
+use crate::models::annotation::{Annotation, AnnotationType};
+use crate::models::book::Book;
+use crate::services::epub_metadata::{EpubContributor, EpubIdentifier, EpubMetadata};
+use crate::services::epub_parser::{decode_text, EpubDocument, EpubParser, NavDocumentBuilder, TocEntry};
+use crate::utils::text_anchor::{locate_quote, locate_text};
+use crate::utils::url_encoding::percent_decode;
+
+const XHTML_MEDIA_TYPE: &str = "application/xhtml+xml";
+const NCX_MEDIA_TYPE: &str = "application/x-dtbncx+xml";
+/// Manifest properties the writer sets itself
+const GENERATED_PROPERTIES: &[&str] = &["nav", "cover-image"];
+
+static HTML_START: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)<(?:\w+:)?html\b[^>]*>").unwrap());
+static BODY_END: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)</(?:\w+:)?body\s*>").unwrap());
+static BODY_START: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)<(?:\w+:)?body\b[^>]*>").unwrap());
+static MARKUP_TOKEN: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<!--.*?-->|<!\[CDATA\[.*?\]\]>|<[^>]*>|[^<]+").unwrap());
+static ENTITY: Lazy<Regex> = Lazy::new(|| Regex::new(r"&#?\w+;").unwrap());
+static RAW_TEXT_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)^<(/?)(?:\w+:)?(?:script|style)\b").unwrap());
+static BLOCK_BOUNDARY: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)^</?(?:\w+:)?(?:p|div|br|li|h[1-6]|blockquote|tr|td|th|dd|dt|pre|section|figcaption)\b").unwrap()
+});
+static DOCUMENT_TITLE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<(?:\w+:)?title\b[^>]*>(.*?)</(?:\w+:)?title\s*>").unwrap());
+
+/// A file of a book held in memory
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpubItem {
+    pub id: String,
+    pub path: String, // Archive path, e.g. "OEBPS/text/ch1.xhtml"
+    pub media_type: String,
+    pub properties: Vec<String>, // e.g. "scripted" or "svg"; "nav" and "cover-image" are set on writing
+    pub data: Vec<u8>,
+}
+
+/// Entry of the reading order
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpubSpineItem {
+    pub idref: String,
+    pub linear: bool,
+}
+
+/// A book held in memory, to be written out as an EPUB 3
+///
+/// Items keep their archive paths, so links between them survive a round
+/// trip. The package document and nav are generated from `metadata`,
+/// `spine` and `toc` when the book is written.
+#[derive(Debug, Clone)]
+pub struct EpubBook {
+    pub metadata: EpubMetadata,
+    pub package_dir: String, // Archive directory of the package document, e.g. "OEBPS"
+    pub items: Vec<EpubItem>,
+    pub spine: Vec<EpubSpineItem>,
+    pub toc: Vec<TocEntry>, // Hrefs are archive paths
+    pub cover_id: Option<String>,
+}
+
+impl EpubBook {
+    pub fn new(metadata: EpubMetadata, package_dir: &str) -> Self {
+        Self {
+            metadata,
+            package_dir: package_dir.trim_matches('/').to_string(),
+            items: Vec::new(),
+            spine: Vec::new(),
+            toc: Vec::new(),
+            cover_id: None,
+        }
+    }
+
+    /// Read every file of an opened EPUB, leaving out its nav and NCX, which are generated anew
+    pub fn from_document(doc: &mut EpubDocument) -> Self {
+        let properties = EpubParser::manifest_properties(doc);
+        let mut book = Self::new(EpubParser::metadata(doc), &doc.root_base.to_string_lossy().replace('\\', "/"));
+        book.toc = EpubParser::table_of_contents(doc);
+
+        let reading_order: HashMap<&String, usize> = doc.spine.iter()
+            .enumerate()
+            .map(|(index, item)| (&item.idref, index))
+            .collect();
+        let mut resources: Vec<(usize, String, String)> = doc.resources.iter()
+            .filter(|(id, (_, media_type))| {
+                media_type != NCX_MEDIA_TYPE
+                    && !properties.get(*id).is_some_and(|properties| properties.iter().any(|property| property == "nav"))
+            })
+            .map(|(id, (path, _))| {
+                let order = reading_order.get(id).copied().unwrap_or(usize::MAX);
+                (order, path.to_string_lossy().replace('\\', "/"), id.clone())
+            })
+            .collect();
+        // Reading order first, then the other files by path
+        resources.sort();
+
+        for (_, path, id) in resources {
+            let Some((data, media_type)) = doc.get_resource(&id) else {
+                continue;
+            };
+            let properties = properties.get(&id)
+                .map(|properties| {
+                    properties.iter()
+                        .filter(|property| !GENERATED_PROPERTIES.contains(&property.as_str()))
+                        .cloned()
+                        .collect()
+                })
+                .unwrap_or_default();
+            book.items.push(EpubItem { id, path, media_type, properties, data });
+        }
+
+        let kept: HashSet<&str> = book.items.iter().map(|item| item.id.as_str()).collect();
+        book.spine = doc.spine.iter()
+            .filter(|item| kept.contains(item.idref.as_str()))
+            .map(|item| EpubSpineItem { idref: item.idref.clone(), linear: item.linear })
+            .collect();
+        book.cover_id = doc.cover_id.clone().filter(|id| kept.contains(id.as_str()));
+        book
+    }
+
+    /// Add a file that is not part of the reading order, such as an image or stylesheet
+    pub fn add_resource(&mut self, id: &str, path: &str, media_type: &str, data: Vec<u8>) {
+        self.items.push(EpubItem {
+            id: id.to_string(),
+            path: path.to_string(),
+            media_type: media_type.to_string(),
+            properties: Vec::new(),
+            data,
+        });
+    }
+
+    /// Add an XHTML document at the end of the reading order
+    pub fn add_document(&mut self, id: &str, path: &str, xhtml: String) {
+        self.add_resource(id, path, XHTML_MEDIA_TYPE, xhtml.into_bytes());
+        self.spine.push(EpubSpineItem { idref: id.to_string(), linear: true });
+    }
+
+    pub fn item(&self, id: &str) -> Option<&EpubItem> {
+        self.items.iter().find(|item| item.id == id)
+    }
+
+    /// Text of an XHTML document, transcoded to UTF-8
+    pub fn document_text(&self, id: &str) -> Option<String> {
+        self.item(id)
+            .filter(|item| item.media_type == XHTML_MEDIA_TYPE)
+            .map(|item| decode_text(&item.data).0)
+    }
+
+    pub fn set_document_text(&mut self, id: &str, xhtml: String) {
+        if let Some(item) = self.items.iter_mut().find(|item| item.id == id) {
+            item.data = xhtml.into_bytes();
+        }
+    }
+
+    /// Take the library's edits to the book's metadata
+    ///
+    /// Credits for people other than the authors and values the library
+    /// doesn't keep, such as file-as names, are left as they were.
+    pub fn apply_book_metadata(&mut self, book: &Book) {
+        let metadata = &mut self.metadata;
+        metadata.title = Some(book.title.clone());
+
+        let authors: Vec<&str> = book.author.split(", ").map(str::trim).filter(|name| !name.is_empty()).collect();
+        if metadata.authors() != authors {
+            let previous = std::mem::take(&mut metadata.creators);
+            metadata.creators = authors.iter()
+                .map(|name| {
+                    previous.iter()
+                        .find(|creator| creator.is_author() && creator.name == *name)
+                        .cloned()
+                        .unwrap_or_else(|| EpubContributor { name: name.to_string(), role: Some("aut".to_string()), file_as: None })
+                })
+                .collect();
+            metadata.creators.extend(previous.into_iter().filter(|creator| !creator.is_author()));
+        }
+
+        // The date as written is kept when it still says the same, e.g. a bare year
+        if metadata.publication_date() != book.publication_date {
+            metadata.published = book.publication_date.map(|date| date.format("%Y-%m-%d").to_string());
+        }
+        if let Some(isbn) = book.isbn.as_ref().filter(|isbn| metadata.isbn().as_ref() != Some(isbn)) {
+            metadata.identifiers.retain(|identifier| {
+                !identifier.scheme.as_deref().is_some_and(|scheme| scheme.eq_ignore_ascii_case("isbn"))
+                    && !identifier.value.to_lowercase().starts_with("urn:isbn:")
+            });
+            metadata.identifiers.push(EpubIdentifier { scheme: Some("ISBN".to_string()), value: isbn.clone() });
+        }
+        metadata.description = book.description.clone();
+        metadata.language = book.language.clone();
+        metadata.publisher = book.publisher.clone();
+        metadata.rights = book.rights.clone();
+        metadata.subjects = book.subjects.clone();
+        metadata.series = book.series.clone();
+        metadata.series_index = book.series_index;
+    }
+
+    /// Append text annotations to the end of their chapters as a list of notes
+    ///
+    /// Each note quotes the highlighted passage and is given the id
+    /// `annotation_note_id` names, so links can point at it. Bookmarks and
+    /// marked areas of pages are left out. Returns how many were added.
+    pub fn append_annotation_notes(&mut self, annotations: &[Annotation]) -> usize {
+        let mut added = 0;
+        for (chapter_id, mut notes) in annotations_by_chapter(annotations) {
+            let Some(xhtml) = self.document_text(chapter_id) else {
+                continue;
+            };
+            notes.sort_by_key(|annotation| annotation.position.start_offset);
+            let mut section = String::from("<section epub:type=\"endnotes\" class=\"annotations\">\n<h2>Annotations</h2>\n<ol>\n");
+            for annotation in &notes {
+                section.push_str(&format!("<li id=\"{}\">", annotation_note_id(annotation)));
+                if !annotation.selected_text.is_empty() {
+                    section.push_str(&format!("<blockquote><p>{}</p></blockquote>", html_escape::encode_text(annotation.selected_text.trim())));
+                }
+                if let Some(note) = annotation.note.as_deref().filter(|note| !note.trim().is_empty()) {
+                    section.push_str(&format!("<p>{}</p>", html_escape::encode_text(note.trim())));
+                }
+                section.push_str("</li>\n");
+            }
+            section.push_str("</ol>\n</section>\n");
+
+            self.set_document_text(chapter_id, insert_before_body_end(&with_epub_namespace(&xhtml), &section));
+            added += notes.len();
+        }
+        added
+    }
+
+    /// Mark highlighted passages in their chapters and link notes to them
+    ///
+    /// Passages are wrapped in `<mark>` elements tinted with the highlight's
+    /// color. An annotation with a note gets a numbered reference after its
+    /// passage, pointing at a footnote at the end of the chapter that links
+    /// back. Passages that can't be found any more, or that overlap one
+    /// already marked, are listed as `append_annotation_notes` does.
+    /// Returns how many passages were marked.
+    pub fn embed_annotations(&mut self, annotations: &[Annotation]) -> usize {
+        let mut unplaced = Vec::new();
+        let mut marked = 0;
+        for (chapter_id, chapter_annotations) in annotations_by_chapter(annotations) {
+            let Some(xhtml) = self.document_text(chapter_id) else {
+                continue;
+            };
+            let text = TextMap::new(&xhtml);
+            let mut found = Vec::new();
+            for annotation in chapter_annotations {
+                let expected = (annotation.position.start_offset, annotation.position.end_offset);
+                let anchor = match &annotation.position.quote {
+                    Some(quote) => locate_quote(&text.text, quote, expected),
+                    None => locate_text(&text.text, &annotation.selected_text, expected),
+                };
+                match anchor {
+                    Some(anchor) => found.push((anchor.start..anchor.end, annotation)),
+                    None => unplaced.push(annotation.clone()),
+                }
+            }
+            // Numbered in reading order; of passages starting together the longer is marked
+            found.sort_by_key(|(range, _)| (range.start, std::cmp::Reverse(range.end)));
+
+            // Markup to insert at a source offset; at the same offset marks close before others open
+            let mut edits: Vec<(usize, u8, String)> = Vec::new();
+            let mut footnotes = String::new();
+            let mut taken: Vec<Range<usize>> = Vec::new();
+            let mut notes = 0;
+            for (range, annotation) in found {
+                let ranges = match taken.iter().any(|other| range.start < other.end && other.start < range.end) {
+                    true => Vec::new(),
+                    false => text.source_ranges(&range),
+                };
+                let Some(last) = ranges.last() else {
+                    unplaced.push(annotation.clone());
+                    continue;
+                };
+                taken.push(range);
+                marked += 1;
+
+                let style = html_escape::encode_double_quoted_attribute(&mark_style(annotation)).into_owned();
+                for (index, source) in ranges.iter().enumerate() {
+                    let id = match index {
+                        0 => format!(" id=\"highlight-{}\"", annotation.id),
+                        _ => String::new(),
+                    };
+                    edits.push((source.start, 2, format!("<mark class=\"annotation\"{} style=\"{}\">", id, style)));
+                    edits.push((source.end, 0, "</mark>".to_string()));
+                }
+                if let Some(note) = annotation.note.as_deref().map(str::trim).filter(|note| !note.is_empty()) {
+                    notes += 1;
+                    edits.push((last.end, 1, format!(
+                        "<sup><a epub:type=\"noteref\" class=\"annotation-ref\" href=\"#{}\">{}</a></sup>",
+                        annotation_note_id(annotation),
+                        notes,
+                    )));
+                    footnotes.push_str(&format!(
+                        "<aside epub:type=\"footnote\" id=\"{}\"><p><a href=\"#highlight-{}\">{}.</a> {}</p></aside>\n",
+                        annotation_note_id(annotation),
+                        annotation.id,
+                        notes,
+                        html_escape::encode_text(note),
+                    ));
+                }
+            }
+            if edits.is_empty() {
+                continue;
+            }
+
+            edits.sort_by_key(|(offset, order, _)| (*offset, *order));
+            let mut annotated = String::with_capacity(xhtml.len() + edits.len() * 64);
+            let mut copied = 0;
+            for (offset, _, markup) in edits {
+                annotated.push_str(&xhtml[copied..offset]);
+                annotated.push_str(&markup);
+                copied = offset;
+            }
+            annotated.push_str(&xhtml[copied..]);
+            if !footnotes.is_empty() {
+                let section = format!("<section epub:type=\"footnotes\" class=\"annotations\">\n{}</section>\n", footnotes);
+                annotated = insert_before_body_end(&annotated, &section);
+            }
+            self.set_document_text(chapter_id, with_epub_namespace(&annotated));
+        }
+
+        self.append_annotation_notes(&unplaced);
+        marked
+    }
+
+    /// Write annotations into the book the way `mode` says; returns how many were added
+    pub fn add_annotations(&mut self, annotations: &[Annotation], mode: AnnotatedEpubMode) -> usize {
+        match mode {
+            AnnotatedEpubMode::Endnotes => self.append_annotation_notes(annotations),
+            AnnotatedEpubMode::Highlights => self.embed_annotations(annotations),
+        }
+    }
+}
+
+/// How annotations are written into an exported copy of a book
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnnotatedEpubMode {
+    Endnotes,   // Listed at the end of each chapter
+    Highlights, // Marked in the text, with notes as linked footnotes
+}
+
+impl AnnotatedEpubMode {
+    /// Get display name
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            AnnotatedEpubMode::Endnotes => "Notes at chapter ends",
+            AnnotatedEpubMode::Highlights => "Highlights and footnotes",
+        }
+    }
+}
+
+impl Default for AnnotatedEpubMode {
+    fn default() -> Self {
+        AnnotatedEpubMode::Highlights
+    }
+}
+
+/// Text annotations by the chapter they are in; bookmarks and marked areas of pages are left out
+fn annotations_by_chapter(annotations: &[Annotation]) -> HashMap<&str, Vec<&Annotation>> {
+    let mut by_chapter: HashMap<&str, Vec<&Annotation>> = HashMap::new();
+    for annotation in annotations {
+        let is_text = annotation.region.is_none()
+            && annotation.annotation_type != AnnotationType::Bookmark
+            && (!annotation.selected_text.is_empty() || annotation.note.is_some());
+        if let Some(chapter_id) = annotation.position.chapter_id.as_deref().filter(|_| is_text) {
+            by_chapter.entry(chapter_id).or_default().push(annotation);
+        }
+    }
+    by_chapter
+}
+
+/// Inline style of a marked passage: a tint, or a colored line for underlines and strikethroughs
+fn mark_style(annotation: &Annotation) -> String {
+    let color = annotation.color.to_hex();
+    match annotation.annotation_type {
+        AnnotationType::Underline => format!("background: none; text-decoration: underline {}", color),
+        AnnotationType::Strikethrough => format!("background: none; text-decoration: line-through {}", color),
+        _ => format!("background-color: {}; color: inherit", color),
+    }
+}
+
+/// Part of a document's text: literal text or a character reference
+struct TextPiece {
+    text: Range<usize>,
+    source: Range<usize>,
+    entity: bool,
+}
+
+/// The text of a document's body, and where each part of it is in the markup
+struct TextMap {
+    text: String,
+    runs: Vec<Vec<TextPiece>>, // Text between two tags
+}
+
+impl TextMap {
+    fn new(xhtml: &str) -> Self {
+        let body_start = BODY_START.find(xhtml).map_or(0, |tag| tag.end());
+        let body_end = BODY_END.find_iter(xhtml).last().map_or(xhtml.len(), |tag| tag.start()).max(body_start);
+        let mut map = TextMap { text: String::new(), runs: Vec::new() };
+        let mut raw_text = false;
+        for token in MARKUP_TOKEN.find_iter(&xhtml[body_start..body_end]) {
+            let source = token.as_str();
+            let offset = body_start + token.start();
+            if source.starts_with('<') {
+                if let Some(c) = RAW_TEXT_TAG.captures(source) {
+                    raw_text = c[1].is_empty() && !source.ends_with("/>");
+                }
+                // Keeps words of neighboring paragraphs apart
+                if BLOCK_BOUNDARY.is_match(source) && !map.text.ends_with(char::is_whitespace) {
+                    map.text.push(' ');
+                }
+                continue;
+            }
+            if raw_text {
+                continue;
+            }
+
+            let mut run = Vec::new();
+            let mut literal_start = 0;
+            for entity in ENTITY.find_iter(source) {
+                map.push_piece(&mut run, &source[literal_start..entity.start()], offset + literal_start..offset + entity.start(), false);
+                let decoded = html_escape::decode_html_entities(entity.as_str());
+                map.push_piece(&mut run, &decoded, offset + entity.start()..offset + entity.end(), true);
+                literal_start = entity.end();
+            }
+            map.push_piece(&mut run, &source[literal_start..], offset + literal_start..offset + source.len(), false);
+            map.runs.push(run);
+        }
+        map
+    }
+
+    fn push_piece(&mut self, run: &mut Vec<TextPiece>, text: &str, source: Range<usize>, entity: bool) {
+        if text.is_empty() {
+            return;
+        }
+        let start = self.text.len();
+        self.text.push_str(text);
+        run.push(TextPiece { text: start..self.text.len(), source, entity });
+    }
+
+    /// Markup ranges holding a range of the text, one for each run it spans with more than whitespace
+    fn source_ranges(&self, range: &Range<usize>) -> Vec<Range<usize>> {
+        self.runs.iter()
+            .filter_map(|run| {
+                let (start, end) = (range.start.max(run.first()?.text.start), range.end.min(run.last()?.text.end));
+                if self.text.get(start..end).is_none_or(|text| text.trim().is_empty()) {
+                    return None;
+                }
+                let first = run.iter().find(|piece| piece.text.contains(&start))?;
+                let last = run.iter().find(|piece| piece.text.start < end && end <= piece.text.end)?;
+                let source_start = match first.entity {
+                    true => first.source.start,
+                    false => first.source.start + start - first.text.start,
+                };
+                let source_end = match last.entity {
+                    true => last.source.end,
+                    false => last.source.start + end - last.text.start,
+                };
+                Some(source_start..source_end)
+            })
+            .collect()
+    }
+}
+
+/// Id of the note `EpubBook` writes for an annotation, in either mode
+pub fn annotation_note_id(annotation: &Annotation) -> String {
+    format!("annotation-{}", annotation.id)
+}
+
+/// Writes in-memory books as EPUB 3 archives
+pub struct EpubWriter;
+
+impl EpubWriter {
+    /// Write a book with a generated package document and nav
+    pub fn write(book: &EpubBook) -> Result<Vec<u8>> {
+        if book.spine.is_empty() {
+            return Err(anyhow!("A book needs at least one document in its reading order"));
+        }
+        if let Some(missing) = book.spine.iter().find(|entry| book.item(&entry.idref).is_none()) {
+            return Err(anyhow!("The reading order refers to \"{}\", which is not in the book", missing.idref));
+        }
+
+        let package_path = package_member(&book.package_dir, "content.opf");
+        let taken: HashSet<&str> = book.items.iter().map(|item| item.path.as_str()).collect();
+        let nav_path = ["nav.xhtml", "toc-nav.xhtml", "generated-nav.xhtml"].into_iter()
+            .map(|name| package_member(&book.package_dir, name))
+            .find(|path| !taken.contains(path.as_str()) && *path != package_path)
+            .ok_or_else(|| anyhow!("No free path for the nav document"))?;
+        let nav_id = ["nav", "toc-nav", "generated-nav"].into_iter()
+            .find(|id| book.item(id).is_none())
+            .ok_or_else(|| anyhow!("No free id for the nav document"))?;
+
+        let title = book.metadata.title.as_deref().unwrap_or("Untitled");
+        let toc = match book.toc.is_empty() {
+            true => Self::spine_toc(book),
+            false => book.toc.clone(),
+        };
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.start_file("mimetype", SimpleFileOptions::default().compression_method(CompressionMethod::Stored))?;
+        writer.write_all(b"application/epub+zip")?;
+        writer.start_file("META-INF/container.xml", options)?;
+        writer.write_all(format!(
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n",
+                "  <rootfiles><rootfile full-path=\"{}\" media-type=\"application/oebps-package+xml\"/></rootfiles>\n",
+                "</container>\n",
+            ),
+            html_escape::encode_double_quoted_attribute(&package_path),
+        ).as_bytes())?;
+
+        for item in &book.items {
+            writer.start_file(percent_decode(&item.path), options)?;
+            writer.write_all(&item.data)?;
+        }
+        writer.start_file(nav_path.as_str(), options)?;
+        writer.write_all(NavDocumentBuilder::build(title, &toc, &book.package_dir).as_bytes())?;
+        writer.start_file(package_path.as_str(), options)?;
+        writer.write_all(Self::package_document(book, nav_id, &nav_path, Utc::now()).as_bytes())?;
+
+        Ok(writer.finish()?.into_inner())
+    }
+
+    pub fn write_to(book: &EpubBook, path: &Path) -> Result<()> {
+        std::fs::write(path, Self::write(book)?)?;
+        Ok(())
+    }
+
+    /// A flat TOC of the reading order, for books without one
+    fn spine_toc(book: &EpubBook) -> Vec<TocEntry> {
+        book.spine.iter()
+            .filter(|entry| entry.linear)
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                let item = book.item(&entry.idref)?;
+                let label = book.document_text(&item.id)
+                    .and_then(|xhtml| DOCUMENT_TITLE.captures(&xhtml).map(|c| html_escape::decode_html_entities(c[1].trim()).into_owned()))
+                    .filter(|label| !label.is_empty())
+                    .unwrap_or_else(|| format!("Chapter {}", index + 1));
+                Some(TocEntry { label, href: item.path.clone(), play_order: index + 1, children: Vec::new() })
+            })
+            .collect()
+    }
+
+    fn package_document(book: &EpubBook, nav_id: &str, nav_path: &str, modified: DateTime<Utc>) -> String {
+        let mut manifest = format!(
+            "    <item id=\"{}\" href=\"{}\" media-type=\"{}\" properties=\"nav\"/>\n",
+            nav_id,
+            html_escape::encode_double_quoted_attribute(&package_href(&book.package_dir, nav_path)),
+            XHTML_MEDIA_TYPE,
+        );
+        for item in &book.items {
+            let mut properties = item.properties.clone();
+            if book.cover_id.as_ref() == Some(&item.id) {
+                properties.push("cover-image".to_string());
+            }
+            let properties = match properties.is_empty() {
+                true => String::new(),
+                false => format!(" properties=\"{}\"", html_escape::encode_double_quoted_attribute(&properties.join(" "))),
+            };
+            manifest.push_str(&format!(
+                "    <item id=\"{}\" href=\"{}\" media-type=\"{}\"{}/>\n",
+                html_escape::encode_double_quoted_attribute(&item.id),
+                html_escape::encode_double_quoted_attribute(&package_href(&book.package_dir, &item.path)),
+                html_escape::encode_double_quoted_attribute(&item.media_type),
+                properties,
+            ));
+        }
+
+        let spine: String = book.spine.iter()
+            .map(|entry| format!(
+                "    <itemref idref=\"{}\"{}/>\n",
+                html_escape::encode_double_quoted_attribute(&entry.idref),
+                if entry.linear { "" } else { " linear=\"no\"" },
+            ))
+            .collect();
+
+        format!(
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"uid\">\n",
+                "  <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\" xmlns:opf=\"http://www.idpf.org/2007/opf\">\n",
+                "{}",
+                "  </metadata>\n",
+                "  <manifest>\n{}  </manifest>\n",
+                "  <spine>\n{}  </spine>\n",
+                "</package>\n",
+            ),
+            Self::metadata_elements(&book.metadata, book.cover_id.as_deref(), modified),
+            manifest,
+            spine,
+        )
+    }
+
+    /// Dublin Core elements with their EPUB 3 refinements, plus calibre's series entries
+    fn metadata_elements(metadata: &EpubMetadata, cover_id: Option<&str>, modified: DateTime<Utc>) -> String {
+        let mut elements = String::new();
+        let mut element = |name: &str, id: Option<&str>, text: &str| {
+            let id = id.map_or(String::new(), |id| format!(" id=\"{}\"", id));
+            elements.push_str(&format!("    <{name}{id}>{}</{name}>\n", html_escape::encode_text(text)));
+        };
+
+        let generated = [EpubIdentifier { scheme: None, value: format!("urn:uuid:{}", Uuid::new_v4()) }];
+        let identifiers = match metadata.identifiers.is_empty() {
+            true => &generated[..],
+            false => &metadata.identifiers[..],
+        };
+        let mut refinements = Vec::new();
+        for (index, identifier) in identifiers.iter().enumerate() {
+            let id = if index == 0 { "uid".to_string() } else { format!("identifier{}", index + 1) };
+            element("dc:identifier", Some(&id), &identifier.value);
+            if let Some(scheme) = &identifier.scheme {
+                refinements.push((id, "identifier-type", scheme.clone()));
+            }
+        }
+        element("dc:title", None, metadata.title.as_deref().unwrap_or("Untitled"));
+        for (name, people) in [("creator", &metadata.creators), ("contributor", &metadata.contributors)] {
+            for (index, person) in people.iter().enumerate() {
+                let id = format!("{}{}", name, index + 1);
+                element(&format!("dc:{}", name), Some(&id), &person.name);
+                if let Some(role) = &person.role {
+                    refinements.push((id.clone(), "role", role.clone()));
+                }
+                if let Some(file_as) = &person.file_as {
+                    refinements.push((id, "file-as", file_as.clone()));
+                }
+            }
+        }
+        element("dc:language", None, metadata.language.as_deref().unwrap_or("und"));
+        for (name, value) in [
+            ("dc:publisher", &metadata.publisher),
+            ("dc:date", &metadata.published),
+            ("dc:description", &metadata.description),
+            ("dc:rights", &metadata.rights),
+        ] {
+            if let Some(value) = value {
+                element(name, None, value);
+            }
+        }
+        for subject in &metadata.subjects {
+            element("dc:subject", None, subject);
+        }
+
+        if let Some(series) = &metadata.series {
+            elements.push_str(&format!("    <meta property=\"belongs-to-collection\" id=\"series\">{}</meta>\n", html_escape::encode_text(series)));
+            refinements.push(("series".to_string(), "collection-type", "series".to_string()));
+            if let Some(index) = metadata.series_index {
+                refinements.push(("series".to_string(), "group-position", index.to_string()));
+            }
+        }
+        for (id, property, value) in refinements {
+            let scheme = if property == "role" { " scheme=\"marc:relators\"" } else { "" };
+            elements.push_str(&format!(
+                "    <meta refines=\"#{}\" property=\"{}\"{}>{}</meta>\n",
+                id,
+                property,
+                scheme,
+                html_escape::encode_text(&value),
+            ));
+        }
+
+        if let Some(series) = &metadata.series {
+            elements.push_str(&format!("    <meta name=\"calibre:series\" content=\"{}\"/>\n", html_escape::encode_double_quoted_attribute(series)));
+            if let Some(index) = metadata.series_index {
+                elements.push_str(&format!("    <meta name=\"calibre:series_index\" content=\"{}\"/>\n", index));
+            }
+        }
+        if let Some(cover_id) = cover_id {
+            elements.push_str(&format!("    <meta name=\"cover\" content=\"{}\"/>\n", html_escape::encode_double_quoted_attribute(cover_id)));
+        }
+        elements.push_str(&format!("    <meta property=\"dcterms:modified\">{}</meta>\n", modified.format("%Y-%m-%dT%H:%M:%SZ")));
+        elements
+    }
+}
+
+/// Archive path of a file in the package directory
+fn package_member(package_dir: &str, name: &str) -> String {
+    match package_dir {
+        "" => name.to_string(),
+        dir => format!("{}/{}", dir, name),
+    }
+}
+
+/// Manifest href of an archive path, relative to the package directory
+fn package_href(package_dir: &str, path: &str) -> String {
+    let relative = match package_dir {
+        "" => path.to_string(),
+        dir => path.strip_prefix(&format!("{}/", dir))
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{}{}", "../".repeat(dir.split('/').count()), path)),
+    };
+    relative.replace(' ', "%20")
+}
+
+/// Declare the `epub:` namespace on a document's root, for `epub:type` attributes
+pub(crate) fn with_epub_namespace(xhtml: &str) -> String {
+    if xhtml.contains("xmlns:epub=") {
+        return xhtml.to_string();
+    }
+    HTML_START.replace(xhtml, |c: &regex::Captures| {
+        let tag = &c[0];
+        let end = tag.len() - if tag.ends_with("/>") { 2 } else { 1 };
+        format!("{} xmlns:epub=\"http://www.idpf.org/2007/ops\"{}", &tag[..end], &tag[end..])
+    })
+    .into_owned()
+}
+
+/// Insert markup at the end of a document's body, or its end when it has no closing body tag
+pub(crate) fn insert_before_body_end(xhtml: &str, markup: &str) -> String {
+    match BODY_END.find_iter(xhtml).last() {
+        Some(body_end) => format!("{}{}{}", &xhtml[..body_end.start()], markup, &xhtml[body_end.start()..]),
+        None => format!("{}{}", xhtml, markup),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::annotation::{HighlightColor, TextPosition};
+    use crate::models::book::BookFormat;
+    use std::fs::File;
+
+    const PACKAGE: &str = r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+    <dc:identifier id="id" opf:scheme="ISBN">9780141439518</dc:identifier>
+    <dc:title>Pride and Prejudice</dc:title>
+    <dc:creator opf:role="aut" opf:file-as="Austen, Jane">Jane Austen</dc:creator>
+    <dc:creator opf:role="ill">Hugh Thomson</dc:creator>
+    <dc:date>1813</dc:date>
+    <dc:language>en</dc:language>
+    <meta name="calibre:series" content="Austen Novels"/>
+    <meta name="calibre:series_index" content="2"/>
+    <meta name="cover" content="cover"/>
+  </metadata>
+  <manifest>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="cover" href="images/cover.png" media-type="image/png"/>
+    <item id="ch1" href="text/chapter 1.xhtml" media-type="application/xhtml+xml" properties="svg"/>
+    <item id="notes" href="text/notes.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine toc="ncx"><itemref idref="ch1"/><itemref idref="notes" linear="no"/></spine>
+</package>"#;
+
+    const NCX: &str = r#"<?xml version="1.0"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1"><navMap>
+  <navPoint id="p1" playOrder="1"><navLabel><text>Chapter 1</text></navLabel><content src="text/chapter%201.xhtml"/></navPoint>
+</navMap></ncx>"#;
+
+    fn write_epub(path: &Path) {
+        let mut writer = ZipWriter::new(File::create(path).unwrap());
+        let options = SimpleFileOptions::default();
+        for (name, data) in [
+            ("mimetype", "application/epub+zip"),
+            ("META-INF/container.xml", r#"<container><rootfiles><rootfile full-path="OEBPS/content.opf"/></rootfiles></container>"#),
+            ("OEBPS/content.opf", PACKAGE),
+            ("OEBPS/toc.ncx", NCX),
+            ("OEBPS/images/cover.png", "png"),
+            ("OEBPS/text/chapter 1.xhtml", "<html xmlns=\"http://www.w3.org/1999/xhtml\"><head><title>Chapter 1</title></head><body><p>It is a truth universally acknowledged.</p></body></html>"),
+            ("OEBPS/text/notes.xhtml", "<html xmlns=\"http://www.w3.org/1999/xhtml\"><body><p>Notes</p></body></html>"),
+        ] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(data.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_round_trip_with_edited_metadata_and_annotation_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("original.epub");
+        write_epub(&original);
+        let mut doc = EpubParser::open(&original, None).unwrap();
+        let mut book = EpubBook::from_document(&mut doc);
+        assert_eq!(book.items.iter().map(|item| item.id.as_str()).collect::<Vec<_>>(), ["ch1", "notes", "cover"]);
+
+        let mut edited = Book::new(
+            "Pride & Prejudice".to_string(),
+            "Jane Austen".to_string(),
+            original.clone(),
+            0,
+            BookFormat::Epub,
+        );
+        edited.publication_date = book.metadata.publication_date();
+        edited.isbn = book.metadata.isbn();
+        edited.series = Some("Austen Novels".to_string());
+        edited.series_index = Some(2.0);
+        book.apply_book_metadata(&edited);
+
+        let position = TextPosition {
+            start_offset: 8,
+            end_offset: 40,
+            paragraph_index: 0,
+            chapter_id: Some("ch1".to_string()),
+            line_number: None,
+            column_number: None,
+            quote: None,
+        };
+        let mut annotation = Annotation::new(
+            "book".to_string(),
+            1,
+            "a truth universally acknowledged".to_string(),
+            position,
+            AnnotationType::Note,
+        );
+        annotation.note = Some("Famous <first> line".to_string());
+        assert_eq!(book.append_annotation_notes(&[annotation.clone()]), 1);
+
+        let copy = dir.path().join("copy.epub");
+        EpubWriter::write_to(&book, &copy).unwrap();
+        let mut doc = EpubParser::open(&copy, None).unwrap();
+        let metadata = EpubParser::metadata(&mut doc);
+        assert_eq!(metadata.title.as_deref(), Some("Pride & Prejudice"));
+        assert_eq!(metadata.creators[0].file_as.as_deref(), Some("Austen, Jane"));
+        assert_eq!(metadata.creators[1].role.as_deref(), Some("ill"));
+        assert_eq!(metadata.published.as_deref(), Some("1813"));
+        assert_eq!(metadata.isbn().as_deref(), Some("9780141439518"));
+        assert_eq!((metadata.series.as_deref(), metadata.series_index), (Some("Austen Novels"), Some(2.0)));
+
+        let info = EpubParser::navigation(&mut doc);
+        assert_eq!(info.nav_type, crate::services::epub_parser::NavType::Nav);
+        assert_eq!(info.toc[0].label, "Chapter 1");
+        assert_eq!(info.toc[0].href, "OEBPS/text/chapter%201.xhtml");
+        assert!(!doc.spine[1].linear);
+        assert_eq!(EpubParser::cover(&mut doc).unwrap().data, b"png");
+
+        let chapter = EpubParser::read_text(&mut doc, "ch1").unwrap();
+        assert!(chapter.contains("xmlns:epub=\"http://www.idpf.org/2007/ops\""));
+        assert!(chapter.contains(&format!(
+            "<li id=\"{}\"><blockquote><p>a truth universally acknowledged</p></blockquote><p>Famous &lt;first&gt; line</p></li>",
+            annotation_note_id(&annotation),
+        )));
+        assert!(chapter.ends_with("</section>\n</body></html>"));
+    }
+
+    #[test]
+    fn test_highlights_are_marked_and_notes_linked_as_footnotes() {
+        let mut book = EpubBook::new(EpubMetadata::default(), "OEBPS");
+        book.add_document("ch1", "OEBPS/ch1.xhtml", concat!(
+            "<html xmlns=\"http://www.w3.org/1999/xhtml\"><head><title>One</title></head><body>",
+            "<p>It is a truth <em>universally</em> acknowledged, that a single man &amp; fortune</p>\n",
+            "<p>must be in want of a wife.</p></body></html>",
+        ).to_string());
+        let annotation = |text: &str, note: Option<&str>, annotation_type: AnnotationType| {
+            let position = TextPosition {
+                start_offset: 0,
+                end_offset: 0,
+                paragraph_index: 0,
+                chapter_id: Some("ch1".to_string()),
+                line_number: None,
+                column_number: None,
+                quote: None,
+            };
+            let mut annotation = Annotation::new("book".to_string(), 1, text.to_string(), position, annotation_type);
+            annotation.note = note.map(str::to_string);
+            annotation
+        };
+        let truth = annotation("truth universally acknowledged", Some("Irony"), AnnotationType::Note);
+        let mut fortune = annotation("man & fortune must be", None, AnnotationType::Underline);
+        fortune.color = HighlightColor::Custom("#3366FF".to_string());
+        let overlapping = annotation("universally", None, AnnotationType::Highlight);
+        let missing = annotation("Mr. Darcy", Some("Elsewhere"), AnnotationType::Note);
+
+        assert_eq!(book.embed_annotations(&[missing.clone(), overlapping.clone(), fortune.clone(), truth.clone()]), 2);
+        let xhtml = book.document_text("ch1").unwrap();
+        let tint = "style=\"background-color: #FFD700; color: inherit\"";
+        let underline = "style=\"background: none; text-decoration: underline #3366FF\"";
+        assert!(xhtml.starts_with("<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">"));
+        assert!(xhtml.contains(&format!(
+            concat!(
+                "<p>It is a <mark class=\"annotation\" id=\"highlight-{0}\" {1}>truth </mark>",
+                "<em><mark class=\"annotation\" {1}>universally</mark></em><mark class=\"annotation\" {1}> acknowledged</mark>",
+                "<sup><a epub:type=\"noteref\" class=\"annotation-ref\" href=\"#annotation-{0}\">1</a></sup>, that a single ",
+                "<mark class=\"annotation\" id=\"highlight-{2}\" {3}>man &amp; fortune</mark></p>\n",
+                "<p><mark class=\"annotation\" {3}>must be</mark> in want of a wife.</p>",
+            ),
+            truth.id, tint, fortune.id, underline,
+        )));
+        assert!(xhtml.contains(&format!(
+            "<aside epub:type=\"footnote\" id=\"annotation-{0}\"><p><a href=\"#highlight-{0}\">1.</a> Irony</p></aside>",
+            truth.id,
+        )));
+        // Passages that could not be marked are listed after the footnotes
+        let endnotes = xhtml.find("epub:type=\"endnotes\"").unwrap();
+        assert!(xhtml.find("epub:type=\"footnotes\"").unwrap() < endnotes);
+        assert!(xhtml[endnotes..].contains(&format!("<li id=\"{}\">", annotation_note_id(&overlapping))));
+        assert!(xhtml[endnotes..].contains(&format!("<li id=\"{}\">", annotation_note_id(&missing))));
+        assert!(EpubWriter::write(&book).is_ok());
+    }
+}
@@ -0,0 +1,84 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{Result, anyhow};
+use tracing::info;
+
+use crate::services::path_resolver::PathResolver;
+
+/// Write an export to the exports directory and reveal it in the file manager
+///
+/// There is no native share sheet available to the Slint UI, so revealing the
+/// file lets the user share it from the file manager instead.
+pub async fn share_export(file_name: &str, contents: &[u8]) -> Result<PathBuf> {
+    let export_path = write_export(file_name, contents).await?;
+    reveal_in_file_manager(&export_path).await?;
+    Ok(export_path)
+}
+
+/// Write an export to the exports directory without revealing it
+pub async fn write_export(file_name: &str, contents: &[u8]) -> Result<PathBuf> {
+    let exports_dir = PathResolver::get_exports_directory()?;
+    tokio::fs::create_dir_all(&exports_dir).await?;
+
+    let export_path = exports_dir.join(sanitize_file_name(file_name));
+    tokio::fs::write(&export_path, contents).await?;
+    info!("Wrote export to {}", export_path.display());
+    Ok(export_path)
+}
+
+/// Show a file in the platform file manager, selecting it where supported
+pub async fn reveal_in_file_manager(path: &Path) -> Result<()> {
+    if !tokio::fs::try_exists(path).await? {
+        return Err(anyhow!("File does not exist: {}", path.display()));
+    }
+
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("explorer");
+        command.arg(format!("/select,{}", path.display()));
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg("-R").arg(path);
+        command
+    } else {
+        // xdg-open cannot select a file, so open its folder
+        let mut command = Command::new("xdg-open");
+        command.arg(path.parent().unwrap_or(path));
+        command
+    };
+
+    command.spawn()
+        .map_err(|e| anyhow!("Failed to open file manager: {}", e))?;
+    Ok(())
+}
+
+/// Replace characters that are not allowed in file names
+pub(crate) fn sanitize_file_name(file_name: &str) -> String {
+    let sanitized: String = file_name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    let sanitized = sanitized.trim().trim_start_matches('.');
+    if sanitized.is_empty() {
+        "export".to_string()
+    } else {
+        sanitized.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("Dune: Part 1/2 annotations.md"), "Dune_ Part 1_2 annotations.md");
+        assert_eq!(sanitize_file_name("../.."), "_..");
+        assert_eq!(sanitize_file_name("  "), "export");
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::{broadcast, mpsc, Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::models::preferences::FileDeletionPolicy;
+use crate::services::book_service::{BookService, DuplicateBookError};
+use crate::services::database::DatabaseService;
+
+/// How often a new file's size is checked while it is still being written
+const SETTLE_INTERVAL: Duration = Duration::from_millis(500);
+/// Give up on a file that is still growing after this many checks
+const MAX_SETTLE_CHECKS: usize = 120;
+
+/// A directory whose new books are imported automatically
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchedFolder {
+    pub path: PathBuf,
+    pub remove_on_delete: bool, // Remove the library entry when its file is deleted
+    pub added_date: DateTime<Utc>,
+}
+
+/// What happened to a file in a watched folder
+#[derive(Debug, Clone, PartialEq)]
+pub enum FolderEvent {
+    Imported { path: PathBuf, book_id: String },
+    Duplicate { path: PathBuf, reason: String },
+    Failed { path: PathBuf, error: String },
+    Removed { path: PathBuf, book_id: String },
+}
+
+/// Imports books dropped into watched folders
+///
+/// Registered folders are watched recursively. Files already in a folder are
+/// imported when it is added and when watching starts, so books copied while
+/// the app was closed are picked up too. Books already in the library, by
+/// path or ISBN, are reported as duplicates rather than imported again.
+pub struct FolderWatcher {
+    database: Arc<DatabaseService>,
+    book_service: Arc<BookService>,
+    folders: RwLock<Vec<WatchedFolder>>,
+    watcher: Mutex<Option<RecommendedWatcher>>,
+    events: broadcast::Sender<FolderEvent>,
+    settling: Mutex<HashSet<PathBuf>>, // New files waiting to stop growing
+}
+
+impl FolderWatcher {
+    pub fn new(database: Arc<DatabaseService>, book_service: Arc<BookService>) -> Self {
+        let (events, _) = broadcast::channel(64);
+        Self {
+            database,
+            book_service,
+            folders: RwLock::new(Vec::new()),
+            watcher: Mutex::new(None),
+            events,
+            settling: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Receive an event for every file imported, skipped or removed
+    pub fn subscribe_events(&self) -> broadcast::Receiver<FolderEvent> {
+        self.events.subscribe()
+    }
+
+    /// Get the registered folders
+    pub async fn get_folders(&self) -> Vec<WatchedFolder> {
+        self.folders.read().await.clone()
+    }
+
+    /// Watch the registered folders, importing what they already contain
+    pub async fn start(self: &Arc<Self>) -> Result<JoinHandle<()>> {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |result| {
+            let _ = sender.send(result);
+        })?;
+
+        let folders = self.database.get_watched_folders().await?;
+        for folder in &folders {
+            if let Err(e) = watcher.watch(&folder.path, RecursiveMode::Recursive) {
+                warn!("Failed to watch {}: {}", folder.path.display(), e);
+            }
+        }
+        *self.folders.write().await = folders.clone();
+        *self.watcher.lock().await = Some(watcher);
+
+        let this = self.clone();
+        Ok(tokio::spawn(async move {
+            for folder in &folders {
+                if let Err(e) = this.scan_folder(&folder.path).await {
+                    warn!("Failed to scan {}: {}", folder.path.display(), e);
+                }
+            }
+
+            // Ends when `stop` drops the watcher and with it the sender
+            while let Some(result) = receiver.recv().await {
+                match result {
+                    Ok(event) => this.handle_event(event).await,
+                    Err(e) => warn!("Folder watcher error: {}", e),
+                }
+            }
+        }))
+    }
+
+    /// Stop watching all folders
+    pub async fn stop(&self) {
+        self.watcher.lock().await.take();
+    }
+
+    /// Register a folder and import the books already in it
+    pub async fn add_folder(&self, path: &Path, remove_on_delete: bool) -> Result<WatchedFolder> {
+        let path = tokio::fs::canonicalize(path).await
+            .map_err(|e| anyhow!("Cannot watch {}: {}", path.display(), e))?;
+        if !path.is_dir() {
+            return Err(anyhow!("Cannot watch {}: not a folder", path.display()));
+        }
+
+        let folder = WatchedFolder { path: path.clone(), remove_on_delete, added_date: Utc::now() };
+        self.database.insert_watched_folder(&folder).await?;
+
+        if let Some(watcher) = self.watcher.lock().await.as_mut() {
+            watcher.watch(&path, RecursiveMode::Recursive)?;
+        }
+        {
+            let mut folders = self.folders.write().await;
+            folders.retain(|existing| existing.path != path);
+            folders.push(folder.clone());
+        }
+
+        self.scan_folder(&path).await?;
+        Ok(folder)
+    }
+
+    /// Stop watching a folder, leaving its books in the library
+    pub async fn remove_folder(&self, path: &Path) -> Result<()> {
+        // Folders are registered by canonical path; one that is gone can't be resolved
+        let path = tokio::fs::canonicalize(path).await.unwrap_or_else(|_| path.to_path_buf());
+        if let Some(watcher) = self.watcher.lock().await.as_mut() {
+            let _ = watcher.unwatch(&path);
+        }
+        self.folders.write().await.retain(|folder| folder.path != path);
+        self.database.delete_watched_folder(&path).await
+    }
+
+    /// Import every book file under a folder that is not yet in the library
+    pub async fn scan_folder(&self, path: &Path) -> Result<Vec<FolderEvent>> {
+        let root = path.to_path_buf();
+        let files = tokio::task::spawn_blocking(move || BookService::find_book_files(&root, true)).await??;
+
+        let mut events = Vec::new();
+        for file in files {
+            if self.database.book_exists_by_path(&file).await? {
+                continue;
+            }
+            events.push(self.import_file(&file).await);
+        }
+        Ok(events)
+    }
+
+    async fn handle_event(self: &Arc<Self>, event: Event) {
+        let moved = matches!(event.kind, EventKind::Modify(ModifyKind::Name(_)));
+        if !matches!(event.kind, EventKind::Create(_) | EventKind::Remove(_)) && !moved {
+            return;
+        }
+
+        for path in event.paths {
+            if path.is_dir() {
+                if let Err(e) = self.scan_folder(&path).await {
+                    warn!("Failed to scan {}: {}", path.display(), e);
+                }
+            } else if path.exists() {
+                if BookService::is_importable_file(&path) {
+                    self.import_when_settled(path).await;
+                }
+            } else if let Err(e) = self.file_removed(&path).await {
+                warn!("Failed to remove {} from the library: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Import a new file once it stops growing, without holding up other files
+    async fn import_when_settled(self: &Arc<Self>, path: PathBuf) {
+        // Copying a file raises several events; one wait is enough
+        if !self.settling.lock().await.insert(path.clone()) {
+            return;
+        }
+
+        let this = self.clone();
+        tokio::spawn(async move {
+            if wait_until_settled(&path).await {
+                match this.database.book_exists_by_path(&path).await {
+                    // Already imported by a folder scan
+                    Ok(true) => {}
+                    Ok(false) => {
+                        this.import_file(&path).await;
+                    }
+                    Err(e) => warn!("Failed to check {} against the library: {}", path.display(), e),
+                }
+            }
+            this.settling.lock().await.remove(&path);
+        });
+    }
+
+    async fn import_file(&self, path: &Path) -> FolderEvent {
+        let event = match self.book_service.add_book(path).await {
+            Ok(book_id) => FolderEvent::Imported { path: path.to_path_buf(), book_id },
+            Err(e) => match e.downcast_ref::<DuplicateBookError>() {
+                Some(duplicate) => FolderEvent::Duplicate { path: path.to_path_buf(), reason: duplicate.to_string() },
+                None => {
+                    warn!("Failed to import {}: {}", path.display(), e);
+                    FolderEvent::Failed { path: path.to_path_buf(), error: e.to_string() }
+                }
+            },
+        };
+
+        let _ = self.events.send(event.clone());
+        event
+    }
+
+    /// Drop the library entry of a deleted file when its folder asks for it
+    async fn file_removed(&self, path: &Path) -> Result<()> {
+        let remove = self.folders.read().await
+            .iter()
+            .any(|folder| folder.remove_on_delete && path.starts_with(&folder.path));
+        if !remove {
+            return Ok(());
+        }
+
+        if let Some(book) = self.database.find_book_by_path(path).await? {
+            self.book_service.delete_book_with_policy(&book.id, Some(FileDeletionPolicy::KeepFile)).await?;
+            let _ = self.events.send(FolderEvent::Removed { path: path.to_path_buf(), book_id: book.id });
+        }
+        Ok(())
+    }
+}
+
+/// Wait until a file stops growing, so a book still being copied isn't imported half-written
+async fn wait_until_settled(path: &Path) -> bool {
+    let mut last_size = None;
+    for _ in 0..MAX_SETTLE_CHECKS {
+        let Ok(metadata) = tokio::fs::metadata(path).await else {
+            return false;
+        };
+        if metadata.len() > 0 && last_size == Some(metadata.len()) {
+            return true;
+        }
+        last_size = Some(metadata.len());
+        tokio::time::sleep(SETTLE_INTERVAL).await;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+    use crate::utils::image_cache::ImageCache;
+
+    fn write_epub(path: &Path, title: &str, isbn: &str) {
+        let mut writer = ZipWriter::new(std::fs::File::create(path).unwrap());
+        let options = SimpleFileOptions::default();
+        let files = [
+            ("mimetype", "application/epub+zip".to_string()),
+            (
+                "META-INF/container.xml",
+                r#"<container><rootfiles><rootfile full-path="content.opf"/></rootfiles></container>"#.to_string(),
+            ),
+            (
+                "content.opf",
+                format!(
+                    r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0"><metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>{}</dc:title><dc:identifier>urn:isbn:{}</dc:identifier></metadata><manifest><item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/></manifest><spine><itemref idref="c1"/></spine></package>"#,
+                    title, isbn
+                ),
+            ),
+            ("c1.xhtml", "<html><body><p>Chapter one.</p></body></html>".to_string()),
+        ];
+        for (name, data) in files {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(data.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    async fn next_event(events: &mut broadcast::Receiver<FolderEvent>) -> FolderEvent {
+        tokio::time::timeout(Duration::from_secs(20), events.recv()).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_watched_folder_imports_and_removes_books() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let inbox = temp_dir.path().join("inbox");
+        std::fs::create_dir(&inbox).unwrap();
+        write_epub(&inbox.join("first.epub"), "First", "9780262162098");
+        std::fs::write(inbox.join("notes.txt"), "not a book").unwrap();
+
+        let database = Arc::new(DatabaseService::new_in_memory().await.unwrap());
+        let image_cache = Arc::new(ImageCache::new(temp_dir.path().join("covers")).unwrap());
+        let book_service = Arc::new(BookService::new(database.clone(), image_cache));
+        let watcher = Arc::new(FolderWatcher::new(database.clone(), book_service));
+
+        // Books already in the folder are imported when it is added
+        let folder = watcher.add_folder(&inbox, true).await.unwrap();
+        assert_eq!(database.get_watched_folders().await.unwrap(), vec![folder.clone()]);
+        assert_eq!(database.get_all_books().await.unwrap().len(), 1);
+
+        let mut events = watcher.subscribe_events();
+        let _run = watcher.start().await.unwrap();
+
+        let second = folder.path.join("second.epub");
+        write_epub(&second, "Second", "9781400079988");
+        assert!(matches!(next_event(&mut events).await, FolderEvent::Imported { path, .. } if path == second));
+
+        // A copy of a book already in the library is not imported again
+        write_epub(&folder.path.join("first copy.epub"), "First", "9780262162098");
+        assert!(matches!(next_event(&mut events).await, FolderEvent::Duplicate { .. }));
+
+        std::fs::remove_file(&second).unwrap();
+        assert!(matches!(next_event(&mut events).await, FolderEvent::Removed { path, .. } if path == second));
+        assert_eq!(database.get_all_books().await.unwrap().len(), 1);
+
+        // Removing works with any spelling of the folder's path
+        watcher.remove_folder(&inbox.join("..").join("inbox")).await.unwrap();
+        assert!(database.get_watched_folders().await.unwrap().is_empty());
+        watcher.stop().await;
+    }
+}
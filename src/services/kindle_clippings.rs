@@ -0,0 +1,410 @@
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::models::Book;
+use crate::models::annotation::{Annotation, AnnotationType, TextPosition};
+use crate::services::annotation_service::AnnotationService;
+use crate::services::database::DatabaseService;
+use crate::services::library_service::LibraryService;
+
+const CLIPPING_SEPARATOR: &str = "==========";
+/// Tag added to every imported annotation
+const KINDLE_TAG: &str = "kindle";
+/// A Kindle location is about 128 bytes of text, so roughly this many fill a page
+const LOCATIONS_PER_PAGE: u32 = 16;
+
+static TITLE_AUTHOR: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(.*?)\s*\(([^()]*)\)\s*$").unwrap());
+static PAGE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\bpage\s+(\d+)").unwrap());
+static LOCATION: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\b(?:location|loc\.)\s+(\d+)(?:-(\d+))?").unwrap());
+static ADDED_ON: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)added on\s+(.+)$").unwrap());
+
+/// What kind of entry a clipping is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClippingKind {
+    Highlight,
+    Note,
+    Bookmark,
+}
+
+/// One entry of a Kindle `My Clippings.txt` file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clipping {
+    pub title: String,
+    pub author: Option<String>,
+    pub kind: ClippingKind,
+    pub page: Option<u32>,
+    pub location: Option<(u32, u32)>,
+    pub added_at: Option<DateTime<Utc>>,
+    pub content: String,
+}
+
+impl Clipping {
+    /// Page of the clipping, estimated from its location when the Kindle gave none
+    pub fn estimated_page(&self) -> u32 {
+        self.page
+            .or_else(|| self.location.map(|(start, _)| start / LOCATIONS_PER_PAGE + 1))
+            .unwrap_or(1)
+    }
+}
+
+/// Clippings from a book that isn't in the library
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnmatchedClippings {
+    pub title: String,
+    pub author: Option<String>,
+    pub clippings: usize,
+}
+
+/// Outcome of a clippings import
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClippingsImportReport {
+    pub highlights_created: usize,
+    pub notes_created: usize,
+    pub duplicates_skipped: usize,
+    pub unmatched: Vec<UnmatchedClippings>,
+}
+
+/// Imports highlights and notes from a Kindle's `My Clippings.txt`
+///
+/// Clippings are matched to library books by title, and by author when
+/// several books share a title. Kindles only record locations, so pages are
+/// estimated when the clipping has none. Importing the same file again
+/// skips what was already imported.
+pub struct KindleClippingsImporter {
+    database: Arc<DatabaseService>,
+    annotations: AnnotationService,
+}
+
+impl KindleClippingsImporter {
+    pub fn new(database: Arc<DatabaseService>, annotations: AnnotationService) -> Self {
+        Self { database, annotations }
+    }
+
+    /// Import a `My Clippings.txt` file
+    pub async fn import_file(&self, path: &Path) -> Result<ClippingsImportReport> {
+        let bytes = tokio::fs::read(path).await?;
+        self.import_text(&String::from_utf8_lossy(&bytes)).await
+    }
+
+    /// Import the text of a `My Clippings.txt` file
+    pub async fn import_text(&self, text: &str) -> Result<ClippingsImportReport> {
+        let books = self.database.get_all_books().await?;
+        let mut report = ClippingsImportReport::default();
+
+        // Clippings of a book are grouped, keeping the order books first appear in
+        let mut groups: Vec<(String, Option<String>, Vec<Clipping>)> = Vec::new();
+        for clipping in parse_clippings(text) {
+            if clipping.kind == ClippingKind::Bookmark {
+                continue;
+            }
+            match groups.iter_mut().find(|(title, author, _)| *title == clipping.title && *author == clipping.author) {
+                Some((_, _, clippings)) => clippings.push(clipping),
+                None => groups.push((clipping.title.clone(), clipping.author.clone(), vec![clipping])),
+            }
+        }
+
+        for (title, author, clippings) in groups {
+            match match_book(&books, &title, author.as_deref()) {
+                Some(book) => self.import_book_clippings(book, &clippings, &mut report).await?,
+                None => report.unmatched.push(UnmatchedClippings { title, author, clippings: clippings.len() }),
+            }
+        }
+
+        Ok(report)
+    }
+
+    async fn import_book_clippings(
+        &self,
+        book: &Book,
+        clippings: &[Clipping],
+        report: &mut ClippingsImportReport,
+    ) -> Result<()> {
+        let mut existing = self.annotations.get_annotations_for_book(&book.id).await?;
+        // Highlights of this import with their location, for attaching notes
+        let mut highlights: Vec<(Option<(u32, u32)>, String)> = Vec::new();
+
+        for clipping in clippings.iter().filter(|clipping| clipping.kind == ClippingKind::Highlight) {
+            if let Some(annotation) = existing.iter().find(|annotation| annotation.selected_text == clipping.content) {
+                highlights.push((clipping.location, annotation.id.clone()));
+                report.duplicates_skipped += 1;
+                continue;
+            }
+
+            let annotation = new_annotation(book, clipping, clipping.content.clone(), AnnotationType::Highlight);
+            self.annotations.save_annotation(&annotation).await?;
+            highlights.push((clipping.location, annotation.id.clone()));
+            existing.push(annotation);
+            report.highlights_created += 1;
+        }
+
+        for clipping in clippings.iter().filter(|clipping| clipping.kind == ClippingKind::Note) {
+            let target = clipping.location.and_then(|(note_location, _)| {
+                highlights.iter()
+                    .filter(|(location, _)| location.is_some_and(|(start, end)| start <= note_location && note_location <= end))
+                    // A note is recorded at the end of the highlight it belongs to
+                    .min_by_key(|(location, _)| location.map(|(_, end)| end.abs_diff(note_location)))
+                    .map(|(_, id)| id.clone())
+            });
+            let target = target.and_then(|id| existing.iter_mut().find(|annotation| annotation.id == id));
+
+            match target {
+                Some(annotation) => {
+                    match &annotation.note {
+                        Some(note) if note.contains(&clipping.content) => {
+                            report.duplicates_skipped += 1;
+                            continue;
+                        }
+                        Some(note) => annotation.note = Some(format!("{}\n\n{}", note, clipping.content)),
+                        None => annotation.note = Some(clipping.content.clone()),
+                    }
+                    annotation.modified_at = Utc::now();
+                    self.annotations.update_annotation(annotation).await?;
+                }
+                None => {
+                    if existing.iter().any(|annotation| annotation.note.as_deref() == Some(clipping.content.as_str())) {
+                        report.duplicates_skipped += 1;
+                        continue;
+                    }
+                    let mut annotation = new_annotation(book, clipping, String::new(), AnnotationType::Note);
+                    annotation.note = Some(clipping.content.clone());
+                    self.annotations.save_annotation(&annotation).await?;
+                    existing.push(annotation);
+                }
+            }
+            report.notes_created += 1;
+        }
+
+        Ok(())
+    }
+}
+
+fn new_annotation(book: &Book, clipping: &Clipping, text: String, annotation_type: AnnotationType) -> Annotation {
+    let position = TextPosition {
+        start_offset: 0,
+        end_offset: 0,
+        paragraph_index: 0,
+        chapter_id: None,
+        line_number: None,
+        column_number: None,
+        quote: None,
+    };
+    let mut annotation = Annotation::new(book.id.clone(), clipping.estimated_page(), text, position, annotation_type);
+    if let Some(added_at) = clipping.added_at {
+        annotation.created_at = added_at;
+        annotation.modified_at = added_at;
+    }
+    annotation.tags = vec![KINDLE_TAG.to_string()];
+    annotation
+}
+
+/// Find the library book a clipping's title and author refer to
+fn match_book<'a>(books: &'a [Book], title: &str, author: Option<&str>) -> Option<&'a Book> {
+    let key = title_key(title);
+    if key.is_empty() {
+        return None;
+    }
+    let candidates: Vec<&Book> = books.iter().filter(|book| title_key(&book.title) == key).collect();
+    if candidates.len() <= 1 {
+        return candidates.first().copied();
+    }
+
+    let author_key = author.map(LibraryService::author_match_key);
+    candidates.iter()
+        .find(|book| author_key.as_deref() == Some(LibraryService::author_match_key(&book.author).as_str()))
+        .or(candidates.first())
+        .copied()
+}
+
+/// Normalize a title for matching: subtitles, series in parentheses, case and punctuation are dropped
+fn title_key(title: &str) -> String {
+    let mut depth = 0usize;
+    let mut main_title = String::new();
+    for c in title.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            ':' if depth == 0 => break,
+            c if depth == 0 => main_title.push(c),
+            _ => {}
+        }
+    }
+
+    main_title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parse the entries of a Kindle `My Clippings.txt` file, skipping any that are malformed
+pub fn parse_clippings(text: &str) -> Vec<Clipping> {
+    text.trim_start_matches('\u{feff}')
+        .split(CLIPPING_SEPARATOR)
+        .filter_map(parse_clipping)
+        .collect()
+}
+
+fn parse_clipping(entry: &str) -> Option<Clipping> {
+    let mut lines = entry.lines().map(|line| line.trim_start_matches('\u{feff}').trim()).skip_while(|line| line.is_empty());
+    let title_line = lines.next()?;
+    let metadata = lines.next()?;
+    if !metadata.starts_with('-') {
+        return None;
+    }
+    let content = lines.collect::<Vec<_>>().join("\n").trim().to_string();
+
+    let (title, author) = match TITLE_AUTHOR.captures(title_line) {
+        Some(captures) if !captures[1].is_empty() => (captures[1].to_string(), Some(captures[2].trim().to_string())),
+        _ => (title_line.to_string(), None),
+    };
+
+    let lower = metadata.to_lowercase();
+    let kind = if lower.contains("bookmark") {
+        ClippingKind::Bookmark
+    } else if lower.contains("note") {
+        ClippingKind::Note
+    } else if lower.contains("highlight") || !content.is_empty() {
+        ClippingKind::Highlight
+    } else {
+        ClippingKind::Bookmark
+    };
+    if kind != ClippingKind::Bookmark && content.is_empty() {
+        return None;
+    }
+
+    let page = PAGE.captures(metadata).and_then(|captures| captures[1].parse().ok());
+    let location = LOCATION.captures(metadata).and_then(|captures| {
+        let start: u32 = captures[1].parse().ok()?;
+        let end = match captures.get(2) {
+            Some(end) => expand_location_end(&captures[1], end.as_str())?,
+            None => start,
+        };
+        Some((start, end.max(start)))
+    });
+    let added_at = ADDED_ON.captures(metadata).and_then(|captures| parse_added_on(&captures[1]));
+
+    Some(Clipping { title, author, kind, page, location, added_at, content })
+}
+
+/// Older Kindles abbreviate a range's end, as in "Loc. 171-73"
+fn expand_location_end(start: &str, end: &str) -> Option<u32> {
+    if end.len() < start.len() {
+        format!("{}{}", &start[..start.len() - end.len()], end).parse().ok()
+    } else {
+        end.parse().ok()
+    }
+}
+
+fn parse_added_on(date: &str) -> Option<DateTime<Utc>> {
+    const FORMATS: &[&str] = &[
+        "%A, %B %d, %Y %I:%M:%S %p",
+        "%A, %d %B %Y %H:%M:%S",
+        "%A, %B %d, %Y, %I:%M %p",
+    ];
+    FORMATS.iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date.trim(), format).ok())
+        .map(|date| date.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use chrono::Timelike;
+    use crate::models::BookFormat;
+
+    const CLIPPINGS: &str = "\u{feff}Dune (Dune Chronicles, Book 1) (Herbert, Frank)
+- Your Highlight on page 8 | Location 120-122 | Added on Sunday, March 5, 2017 10:42:11 PM
+
+I must not fear. Fear is the mind-killer.
+==========
+Dune (Dune Chronicles, Book 1) (Herbert, Frank)
+- Your Note on page 8 | Location 122 | Added on Sunday, March 5, 2017 10:43:02 PM
+
+The litany
+==========
+Dune (Dune Chronicles, Book 1) (Herbert, Frank)
+- Your Bookmark on Location 300 | Added on Sunday, March 5, 2017 10:44:00 PM
+
+
+==========
+Dune (Dune Chronicles, Book 1) (Herbert, Frank)
+- Highlight Loc. 1701-05 | Added on Monday, March 6, 2017, 08:15 AM
+
+The spice must flow.
+==========
+The Left Hand of Darkness (Ursula K. Le Guin)
+- Your Highlight on Location 50-51 | Added on Monday, March 6, 2017 9:00:00 AM
+
+Light is the left hand of darkness.
+==========
+";
+
+    #[test]
+    fn test_parse_clippings() {
+        let clippings = parse_clippings(CLIPPINGS);
+        assert_eq!(clippings.len(), 5);
+
+        let first = &clippings[0];
+        assert_eq!(first.title, "Dune (Dune Chronicles, Book 1)");
+        assert_eq!(first.author.as_deref(), Some("Herbert, Frank"));
+        assert_eq!(first.kind, ClippingKind::Highlight);
+        assert_eq!((first.page, first.location), (Some(8), Some((120, 122))));
+        assert_eq!(first.added_at.unwrap().hour(), 22);
+        assert_eq!(clippings[1].kind, ClippingKind::Note);
+        assert_eq!(clippings[2].kind, ClippingKind::Bookmark);
+
+        // Old-style entries abbreviate the range and have no page
+        assert_eq!(clippings[3].location, Some((1701, 1705)));
+        assert_eq!(clippings[3].estimated_page(), 1701 / LOCATIONS_PER_PAGE + 1);
+        assert!(clippings[3].added_at.is_some());
+
+        assert_eq!(title_key("Dune (Dune Chronicles, Book 1)"), "dune");
+        assert_eq!(title_key("Dune: Deluxe Edition"), "dune");
+    }
+
+    #[tokio::test]
+    async fn test_import_attaches_notes_and_reports_unmatched_books() {
+        let database = Arc::new(DatabaseService::new_in_memory().await.unwrap());
+        let mut book = Book::new(
+            "Dune".to_string(),
+            "Frank Herbert".to_string(),
+            PathBuf::from("/books/dune.epub"),
+            0,
+            BookFormat::Epub,
+        );
+        book.id = "dune".to_string();
+        database.insert_book(&book).await.unwrap();
+        // The annotations schema is the annotation service's own, so it gets its own database
+        let pool = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE books (id TEXT PRIMARY KEY)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO books (id) VALUES ('dune')").execute(&pool).await.unwrap();
+        let annotations = AnnotationService::new(pool);
+        annotations.init_tables().await.unwrap();
+        let importer = KindleClippingsImporter::new(database, annotations.clone());
+
+        let report = importer.import_text(CLIPPINGS).await.unwrap();
+        assert_eq!(report.highlights_created, 2);
+        assert_eq!(report.notes_created, 1);
+        assert_eq!(report.unmatched, vec![UnmatchedClippings {
+            title: "The Left Hand of Darkness".to_string(),
+            author: Some("Ursula K. Le Guin".to_string()),
+            clippings: 1,
+        }]);
+
+        let imported = annotations.get_annotations_for_book("dune").await.unwrap();
+        let fear = imported.iter().find(|annotation| annotation.page_number == 8).unwrap();
+        assert_eq!(fear.note.as_deref(), Some("The litany"));
+        assert_eq!(fear.tags, vec![KINDLE_TAG.to_string()]);
+
+        // Importing again adds nothing
+        let again = importer.import_text(CLIPPINGS).await.unwrap();
+        assert_eq!((again.highlights_created, again.notes_created, again.duplicates_skipped), (0, 0, 3));
+        assert_eq!(annotations.get_annotations_for_book("dune").await.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,288 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use md5::{Digest, Md5};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::{Client, RequestBuilder, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::models::book::BookFormat;
+use crate::models::preferences::{KosyncDocumentMatch, KosyncSettings};
+use crate::services::secrets;
+
+/// Provider name the kosync user key is stored under in the keyring
+pub const KOSYNC_PROVIDER: &str = "kosync";
+const KOSYNC_ACCEPT: &str = "application/vnd.koreader.v1+json";
+/// Bytes read at each sample point of a file when hashing it
+const SAMPLE_SIZE: u64 = 1024;
+/// How much further along (as a fraction of the book) a KOReader device must be before its position is offered
+const PULL_MARGIN: f32 = 0.005;
+
+static DOC_FRAGMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"^/body/DocFragment\[(\d+)\]").unwrap());
+
+/// Reading progress as a kosync server stores it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KosyncProgress {
+    pub document: String,
+    pub progress: String, // XPointer into an EPUB, or a page number
+    pub percentage: f32,  // 0.0 to 1.0
+    pub device: String,
+    pub device_id: String,
+    #[serde(default)]
+    pub timestamp: Option<i64>,
+}
+
+/// A position in a book, in the terms KOReader can share
+#[derive(Debug, Clone, PartialEq)]
+pub struct KosyncPosition {
+    pub chapter_order: usize, // Spine index of an EPUB chapter, or zero-based PDF page
+    pub percentage: f32,
+}
+
+/// What syncing a book's progress did
+#[derive(Debug, Clone, PartialEq)]
+pub enum KosyncOutcome {
+    Pushed, // The server now has this device's position
+    Pulled { position: KosyncPosition, device: String }, // A KOReader device is further along; jump there?
+}
+
+/// Syncs reading progress with KOReader devices through a kosync server
+///
+/// Books are matched by a hash of their file, computed the way KOReader does,
+/// so the same file on both sides is recognised without any shared ids.
+#[derive(Clone)]
+pub struct KosyncService {
+    client: Client,
+    settings: KosyncSettings,
+    device_id: String,
+    device_name: String,
+}
+
+impl KosyncService {
+    pub fn new(settings: KosyncSettings, device_id: String, device_name: String) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .user_agent(concat!("ebook-reader/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self { client, settings, device_id, device_name }
+    }
+
+    pub fn set_settings(&mut self, settings: KosyncSettings) {
+        self.settings = settings;
+    }
+
+    pub fn get_settings(&self) -> &KosyncSettings {
+        &self.settings
+    }
+
+    /// Check whether an account is set up
+    pub async fn is_configured(&self) -> bool {
+        self.settings.username.is_some() && secrets::has_api_key_async(KOSYNC_PROVIDER).await
+    }
+
+    /// Create an account on the server and sign in with it
+    pub async fn register(&mut self, username: &str, password: &str) -> Result<()> {
+        let userkey = user_key(password);
+        let response = self.client.post(self.endpoint("users/create")?)
+            .header("Accept", KOSYNC_ACCEPT)
+            .json(&json!({ "username": username, "password": userkey }))
+            .send()
+            .await?;
+        match response.status() {
+            status if status.is_success() => {}
+            StatusCode::PAYMENT_REQUIRED => return Err(anyhow!("The username {} is already taken", username)),
+            status => return Err(anyhow!("The sync server refused the account ({})", status)),
+        }
+        self.store_account(username, &userkey).await
+    }
+
+    /// Check an existing account with the server and remember it
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<()> {
+        let userkey = user_key(password);
+        let response = self.authorized(self.client.get(self.endpoint("users/auth")?), username, &userkey)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("The sync server did not accept the username or password ({})", response.status()));
+        }
+        self.store_account(username, &userkey).await
+    }
+
+    /// Forget the account
+    pub async fn logout(&mut self) -> Result<()> {
+        self.settings.username = None;
+        secrets::remove_api_key_async(KOSYNC_PROVIDER).await
+    }
+
+    /// Send this device's position in a book
+    pub async fn push_progress(&self, document: &str, progress: &str, percentage: f32) -> Result<()> {
+        let (username, userkey) = self.account().await?;
+        let body = json!({
+            "document": document,
+            "progress": progress,
+            "percentage": percentage.clamp(0.0, 1.0),
+            "device": self.device_name,
+            "device_id": self.device_id,
+        });
+        let response = self.authorized(self.client.put(self.endpoint("syncs/progress")?), &username, &userkey)
+            .json(&body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("The sync server did not take the progress ({})", response.status()));
+        }
+        Ok(())
+    }
+
+    /// The last position any device sent for a book
+    pub async fn pull_progress(&self, document: &str) -> Result<Option<KosyncProgress>> {
+        let (username, userkey) = self.account().await?;
+        let response = self.authorized(self.client.get(self.endpoint(&format!("syncs/progress/{}", document))?), &username, &userkey)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("The sync server did not return the progress ({})", response.status()));
+        }
+        // Books nobody has synced yet come back as an empty object
+        let value: serde_json::Value = response.json().await?;
+        if value.get("progress").is_none() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_value(value)?))
+    }
+
+    /// Sync a book both ways: offer a KOReader device's position when it is further along, else send ours
+    pub async fn sync_book(&self, path: &Path, format: &BookFormat, position: &KosyncPosition) -> Result<KosyncOutcome> {
+        let document = document_hash(path, &self.settings.document_match)?;
+
+        if let Some(remote) = self.pull_progress(&document).await? {
+            if remote.device_id != self.device_id && remote.percentage - position.percentage >= PULL_MARGIN {
+                if let Some(chapter_order) = parse_progress(&remote.progress, format) {
+                    return Ok(KosyncOutcome::Pulled {
+                        position: KosyncPosition { chapter_order, percentage: remote.percentage },
+                        device: remote.device,
+                    });
+                }
+            }
+        }
+
+        self.push_progress(&document, &format_progress(position.chapter_order, format), position.percentage).await?;
+        Ok(KosyncOutcome::Pushed)
+    }
+
+    /// Send this device's position without looking at the server's, as when the reader stays put
+    pub async fn push_position(&self, path: &Path, format: &BookFormat, position: &KosyncPosition) -> Result<()> {
+        let document = document_hash(path, &self.settings.document_match)?;
+        self.push_progress(&document, &format_progress(position.chapter_order, format), position.percentage).await
+    }
+
+    async fn store_account(&mut self, username: &str, userkey: &str) -> Result<()> {
+        secrets::store_api_key_async(KOSYNC_PROVIDER, userkey).await?;
+        self.settings.username = Some(username.to_string());
+        Ok(())
+    }
+
+    async fn account(&self) -> Result<(String, String)> {
+        let username = self.settings.username.clone()
+            .ok_or_else(|| anyhow!("No KOReader sync account is set up"))?;
+        let userkey = secrets::get_api_key_async(KOSYNC_PROVIDER).await
+            .ok_or_else(|| anyhow!("The KOReader sync password is missing; sign in again"))?;
+        Ok((username, userkey))
+    }
+
+    fn endpoint(&self, path: &str) -> Result<Url> {
+        let base = format!("{}/", self.settings.server_url.trim().trim_end_matches('/'));
+        Ok(Url::parse(&base)?.join(path)?)
+    }
+
+    fn authorized(&self, request: RequestBuilder, username: &str, userkey: &str) -> RequestBuilder {
+        request
+            .header("Accept", KOSYNC_ACCEPT)
+            .header("x-auth-user", username)
+            .header("x-auth-key", userkey)
+    }
+}
+
+/// The key KOReader sends in place of a password: its MD5
+fn user_key(password: &str) -> String {
+    format!("{:x}", Md5::digest(password.as_bytes()))
+}
+
+/// Identify a book the way KOReader does
+///
+/// By contents, MD5 of 1 KiB samples at 0, 1 KiB, 4 KiB, 16 KiB and on up by
+/// fours to 1 GiB, stopping at the end of the file.
+pub fn document_hash(path: &Path, document_match: &KosyncDocumentMatch) -> Result<String> {
+    match document_match {
+        KosyncDocumentMatch::FileName => {
+            let name = path.file_name().ok_or_else(|| anyhow!("No file name: {}", path.display()))?;
+            Ok(format!("{:x}", Md5::digest(name.to_string_lossy().as_bytes())))
+        }
+        KosyncDocumentMatch::Binary => {
+            let mut file = std::fs::File::open(path)?;
+            let mut hasher = Md5::new();
+            let mut sample = Vec::with_capacity(SAMPLE_SIZE as usize);
+            for step in 0..12 {
+                let offset = if step == 0 { 0 } else { SAMPLE_SIZE << (2 * (step - 1)) };
+                file.seek(SeekFrom::Start(offset))?;
+                sample.clear();
+                if (&mut file).take(SAMPLE_SIZE).read_to_end(&mut sample)? == 0 {
+                    break;
+                }
+                hasher.update(&sample);
+            }
+            Ok(format!("{:x}", hasher.finalize()))
+        }
+    }
+}
+
+/// Position as KOReader writes it: an XPointer to the chapter, or a one-based page
+fn format_progress(chapter_order: usize, format: &BookFormat) -> String {
+    match format {
+        BookFormat::Pdf => (chapter_order + 1).to_string(),
+        _ => format!("/body/DocFragment[{}]/body", chapter_order + 1),
+    }
+}
+
+/// Chapter or page a KOReader position points into
+fn parse_progress(progress: &str, format: &BookFormat) -> Option<usize> {
+    let number: usize = match format {
+        BookFormat::Pdf => progress.trim().parse().ok()?,
+        _ => DOC_FRAGMENT.captures(progress)?[1].parse().ok()?,
+    };
+    number.checked_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_hash_and_progress_match_koreader() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Dune.epub");
+        let bytes: Vec<u8> = (0..5000u32).map(|i| ((i * 7 + 3) % 251) as u8).collect();
+        std::fs::write(&path, bytes).unwrap();
+
+        assert_eq!(document_hash(&path, &KosyncDocumentMatch::Binary).unwrap(), "47c161c92e3628990d2674a8d716b10d");
+        assert_eq!(document_hash(&path, &KosyncDocumentMatch::FileName).unwrap(), "02d049480bfe81acc790650ed3032912");
+        assert_eq!(user_key("secret"), "5ebe2294ecd0e0f08eab7690d2a6ee69");
+
+        assert_eq!(format_progress(13, &BookFormat::Epub), "/body/DocFragment[14]/body");
+        assert_eq!(parse_progress("/body/DocFragment[14]/body/div[1]/p[12]/text().37", &BookFormat::Epub), Some(13));
+        assert_eq!(parse_progress(&format_progress(41, &BookFormat::Pdf), &BookFormat::Pdf), Some(41));
+        assert_eq!(parse_progress("/body/DocFragment[0]/body", &BookFormat::Epub), None);
+
+        let service = KosyncService::new(
+            KosyncSettings { server_url: "https://sync.example.org/kosync/".to_string(), ..KosyncSettings::default() },
+            "device".to_string(),
+            "Desktop".to_string(),
+        );
+        assert_eq!(service.endpoint("syncs/progress").unwrap().as_str(), "https://sync.example.org/kosync/syncs/progress");
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tokio::sync::RwLock;
+
+use crate::services::reading_service::{BookContent, Chapter, PaginationSettings};
+
+/// Average glyph width of a proportional body font, as a fraction of its size
+const AVERAGE_CHAR_WIDTH: f32 = 0.5;
+/// Space between the two columns of a page, as a fraction of the text area's width
+const COLUMN_GAP: f32 = 0.06;
+/// Space after a paragraph, in lines
+const PARAGRAPH_SPACING: f32 = 0.5;
+
+static TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]+>").unwrap());
+static TAG_NAME: Lazy<Regex> = Lazy::new(|| Regex::new(r"^<\s*(/?)\s*([a-zA-Z][a-zA-Z0-9]*)").unwrap());
+
+/// Elements that start a new block of text
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p", "div", "h1", "h2", "h3", "h4", "h5", "h6", "li", "ul", "ol", "dl", "dt", "dd",
+    "blockquote", "pre", "section", "article", "aside", "header", "footer", "nav", "figure",
+    "figcaption", "table", "tr", "td", "th", "br", "hr", "body",
+];
+
+/// What a block of chapter text is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockKind {
+    Paragraph,
+    Heading(u8), // 1 to 6
+}
+
+impl BlockKind {
+    /// Size of the block's text relative to body text, after browsers' defaults
+    fn scale(&self) -> f32 {
+        match self {
+            BlockKind::Paragraph => 1.0,
+            BlockKind::Heading(1) => 2.0,
+            BlockKind::Heading(2) => 1.5,
+            BlockKind::Heading(3) => 1.17,
+            BlockKind::Heading(_) => 1.0,
+        }
+    }
+}
+
+/// A paragraph or heading, as byte offsets into the chapter's text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextBlock {
+    pub kind: BlockKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Chapter text with the blocks it was made of
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructuredText {
+    pub text: String,
+    pub blocks: Vec<TextBlock>,
+}
+
+/// Turn chapter HTML into reading text, remembering its paragraphs and headings
+///
+/// Tags become spaces, runs of whitespace one space and entities are decoded,
+/// so offsets into the text are those annotations and progress are kept in.
+pub fn structure_html(html: &str) -> StructuredText {
+    let mut text = String::new();
+    let mut blocks = Vec::new();
+    let mut kind = BlockKind::Paragraph;
+    let mut block_start: Option<usize> = None;
+    let mut space_pending = false;
+
+    let mut last = 0;
+    for tag in TAG.find_iter(html) {
+        push_words(&html[last..tag.start()], &mut text, &mut block_start, &mut space_pending);
+        last = tag.end();
+        space_pending = true;
+
+        let Some(captures) = TAG_NAME.captures(tag.as_str()) else { continue };
+        let name = captures[2].to_ascii_lowercase();
+        if !BLOCK_ELEMENTS.contains(&name.as_str()) {
+            continue;
+        }
+        if let Some(start) = block_start.take() {
+            blocks.push(TextBlock { kind, start, end: text.len() });
+        }
+        let closing = !captures[1].is_empty();
+        kind = match name.as_bytes() {
+            [b'h', level @ b'1'..=b'6'] if !closing => BlockKind::Heading(level - b'0'),
+            _ => BlockKind::Paragraph,
+        };
+    }
+    push_words(&html[last..], &mut text, &mut block_start, &mut space_pending);
+    if let Some(start) = block_start {
+        blocks.push(TextBlock { kind, start, end: text.len() });
+    }
+
+    // Decoded entities may have left whitespace at either end
+    let trimmed_start = text.len() - text.trim_start().len();
+    let trimmed = text.trim().to_string();
+    let blocks = blocks.into_iter()
+        .map(|block| TextBlock {
+            kind: block.kind,
+            start: block.start.saturating_sub(trimmed_start).min(trimmed.len()),
+            end: block.end.saturating_sub(trimmed_start).min(trimmed.len()),
+        })
+        .filter(|block| block.end > block.start)
+        .collect();
+
+    StructuredText { text: trimmed, blocks }
+}
+
+/// Append a run of text between tags, collapsing its whitespace like the rest of the chapter
+fn push_words(run: &str, text: &mut String, block_start: &mut Option<usize>, space_pending: &mut bool) {
+    if run.starts_with(char::is_whitespace) {
+        *space_pending = true;
+    }
+    let mut words = run.split_whitespace().peekable();
+    if words.peek().is_none() {
+        return;
+    }
+    for word in words {
+        if *space_pending && !text.is_empty() {
+            text.push(' ');
+        }
+        block_start.get_or_insert(text.len());
+        text.push_str(&html_escape::decode_html_entities(word));
+        *space_pending = true;
+    }
+    *space_pending = run.ends_with(char::is_whitespace);
+}
+
+/// One screen of a laid-out book
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutPage {
+    pub page_number: usize, // 1-based, across the whole book
+    pub chapter_id: String,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// A book split into pages for one viewport and font size
+///
+/// The same content and settings always give the same pages. Positions are
+/// chapter offsets, so a position survives a re-layout after a resize even
+/// though its page number changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookLayout {
+    pub pages: Vec<LayoutPage>,
+}
+
+impl BookLayout {
+    /// Lay out chapters in reading order; each chapter starts on a new page
+    pub fn new(chapters: &[Chapter], settings: &PaginationSettings) -> Self {
+        let mut pages = Vec::new();
+        for chapter in chapters {
+            for (start_offset, end_offset) in layout_chapter(chapter, settings) {
+                pages.push(LayoutPage {
+                    page_number: pages.len() + 1,
+                    chapter_id: chapter.id.clone(),
+                    start_offset,
+                    end_offset,
+                });
+            }
+        }
+        Self { pages }
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Get a page by its number
+    pub fn page(&self, page_number: usize) -> Option<&LayoutPage> {
+        page_number.checked_sub(1).and_then(|index| self.pages.get(index))
+    }
+
+    /// The page showing a position in a chapter
+    pub fn page_for_position(&self, chapter_id: &str, offset: usize) -> Option<&LayoutPage> {
+        let first = self.pages.iter().position(|page| page.chapter_id == chapter_id)?;
+        let chapter_pages = self.pages[first..].iter().take_while(|page| page.chapter_id == chapter_id).count();
+        let chapter = &self.pages[first..first + chapter_pages];
+        let index = chapter.partition_point(|page| page.start_offset <= offset).max(1) - 1;
+        Some(&chapter[index])
+    }
+
+    /// How far into the book a position is, by the page it's on
+    pub fn progress_at(&self, chapter_id: &str, offset: usize) -> f32 {
+        match self.page_for_position(chapter_id, offset) {
+            Some(page) => page.page_number as f32 / self.page_count() as f32,
+            None => 0.0,
+        }
+    }
+}
+
+/// Split a chapter into pages, as byte ranges of its text
+///
+/// Lines are wrapped at word boundaries with an average glyph width, headings
+/// are set larger and kept with the line after them, and a page break may fall
+/// between any two lines. An empty chapter still gets a page.
+pub fn layout_chapter(chapter: &Chapter, settings: &PaginationSettings) -> Vec<(usize, usize)> {
+    let text = &chapter.content;
+    let whole_text;
+    let blocks = if chapter.blocks.is_empty() {
+        whole_text = [TextBlock { kind: BlockKind::Paragraph, start: 0, end: text.len() }];
+        &whole_text[..]
+    } else {
+        &chapter.blocks[..]
+    };
+
+    let font_size = settings.font_size.max(1) as f32;
+    let text_width = settings.viewport_width.saturating_sub(settings.margin_horizontal as u32 * 2).max(1) as f32;
+    let columns = if settings.two_column_mode { 2 } else { 1 };
+    let column_width = if columns == 2 { text_width * (1.0 - COLUMN_GAP) / 2.0 } else { text_width };
+    let column_height = settings.viewport_height.saturating_sub(settings.margin_vertical as u32 * 2).max(1) as f32;
+    let body_line_height = font_size * settings.line_height;
+
+    let mut page_starts = vec![0];
+    let mut column = 0;
+    let mut y = 0.0;
+    let mut next_column = |at: usize, y: &mut f32| {
+        column += 1;
+        if column == columns {
+            column = 0;
+            page_starts.push(at);
+        }
+        *y = 0.0;
+    };
+
+    for (index, block) in blocks.iter().enumerate() {
+        let scale = block.kind.scale();
+        let line_height = body_line_height * scale;
+        let chars_per_line = ((column_width / (font_size * scale * AVERAGE_CHAR_WIDTH)) as usize).max(1);
+        let lines = wrap_lines(&text[block.start..block.end], chars_per_line);
+
+        if y > 0.0 {
+            y += body_line_height * PARAGRAPH_SPACING;
+        }
+        // A heading at the foot of a column goes with the text it introduces
+        if matches!(block.kind, BlockKind::Heading(_)) && index + 1 < blocks.len() {
+            let needed = lines.len() as f32 * line_height + body_line_height;
+            if y > 0.0 && y + needed > column_height {
+                next_column(block.start, &mut y);
+            }
+        }
+
+        for line_start in lines {
+            // A line taller than the page still gets a page to itself
+            if y > 0.0 && y + line_height > column_height {
+                next_column(block.start + line_start, &mut y);
+            }
+            y += line_height;
+        }
+    }
+
+    // A break right at the end would leave a blank page
+    if page_starts.len() > 1 && page_starts.last() == Some(&text.len()) {
+        page_starts.pop();
+    }
+    page_starts.dedup();
+    let ends = page_starts.iter().skip(1).copied().chain(std::iter::once(text.len()));
+    page_starts.iter().copied().zip(ends).collect()
+}
+
+/// Offsets into a block's text where each wrapped line starts
+fn wrap_lines(text: &str, chars_per_line: usize) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut line_chars = 0;
+    let mut offset = 0;
+    for word in text.split(' ') {
+        let word_chars = word.chars().count();
+        if offset > 0 {
+            if line_chars + 1 + word_chars <= chars_per_line {
+                line_chars += 1;
+            } else {
+                starts.push(offset);
+                line_chars = 0;
+            }
+        }
+
+        if line_chars + word_chars <= chars_per_line {
+            line_chars += word_chars;
+        } else {
+            // A word longer than a line is broken across lines
+            for (index, _) in word.char_indices() {
+                if line_chars == chars_per_line {
+                    starts.push(offset + index);
+                    line_chars = 0;
+                }
+                line_chars += 1;
+            }
+        }
+        offset += word.len() + 1;
+    }
+    starts
+}
+
+/// Lays out books into pages and keeps each layout for its viewport and font settings
+#[derive(Clone, Default)]
+pub struct LayoutService {
+    layouts: Arc<RwLock<HashMap<String, Arc<BookLayout>>>>,
+}
+
+impl LayoutService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a book's layout for the settings, laying it out if it hasn't been
+    pub async fn layout_book(&self, content: &BookContent, settings: &PaginationSettings) -> Arc<BookLayout> {
+        let key = format!("{}_{}", content.book_id, Self::settings_key(settings));
+        if let Some(layout) = self.layouts.read().await.get(&key) {
+            return layout.clone();
+        }
+
+        let layout = Arc::new(BookLayout::new(&content.chapters, settings));
+        self.layouts.write().await.insert(key, layout.clone());
+        layout
+    }
+
+    /// Forget a book's layouts, e.g. after its content changed
+    pub async fn invalidate_book(&self, book_id: &str) {
+        let prefix = format!("{}_", book_id);
+        self.layouts.write().await.retain(|key, _| !key.starts_with(&prefix));
+    }
+
+    fn settings_key(settings: &PaginationSettings) -> String {
+        format!(
+            "{}x{}_{}_{}_{}_{}_{}_{}",
+            settings.viewport_width,
+            settings.viewport_height,
+            settings.font_size,
+            settings.line_height,
+            settings.margin_horizontal,
+            settings.margin_vertical,
+            settings.font_family,
+            settings.two_column_mode
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(id: &str, html: &str) -> Chapter {
+        let structured = structure_html(html);
+        Chapter {
+            id: id.to_string(),
+            title: id.to_string(),
+            word_count: structured.text.split_whitespace().count(),
+            content: structured.text,
+            order: 0,
+            media_overlay: None,
+            blocks: structured.blocks,
+        }
+    }
+
+    #[test]
+    fn test_structure_html_keeps_offsets_of_the_reading_text() {
+        let structured = structure_html(
+            "<body>\n  <h1>Loomings</h1>\n<p>Call me <em>Ishmael</em>.</p><p>Some&nbsp;years ago &amp; more</p>\n</body>"
+        );
+        assert_eq!(structured.text, "Loomings Call me Ishmael . Some\u{a0}years ago & more");
+        let blocks: Vec<(BlockKind, &str)> = structured.blocks.iter()
+            .map(|block| (block.kind, &structured.text[block.start..block.end]))
+            .collect();
+        assert_eq!(blocks, [
+            (BlockKind::Heading(1), "Loomings"),
+            (BlockKind::Paragraph, "Call me Ishmael ."),
+            (BlockKind::Paragraph, "Some\u{a0}years ago & more"),
+        ]);
+    }
+
+    #[test]
+    fn test_layout_maps_positions_to_stable_pages() {
+        let paragraph = "It was the best of times, it was the worst of times, it was the age of wisdom. ".repeat(6);
+        let html = format!("<h1>Book the First</h1>{}", format!("<p>{}</p>", paragraph).repeat(12));
+        let chapters = vec![chapter("one", &html), chapter("two", "<p>The end.</p>"), chapter("three", "")];
+        let settings = PaginationSettings::default();
+
+        let layout = BookLayout::new(&chapters, &settings);
+        assert_eq!(layout, BookLayout::new(&chapters, &settings));
+        assert!(layout.page_count() > 3);
+        let one: Vec<&LayoutPage> = layout.pages.iter().filter(|page| page.chapter_id == "one").collect();
+        // Pages follow each other without gaps, breaking between words
+        assert_eq!(one[0].start_offset, 0);
+        assert_eq!(one.last().unwrap().end_offset, chapters[0].content.len());
+        for pair in one.windows(2) {
+            assert_eq!(pair[0].end_offset, pair[1].start_offset);
+            assert_eq!(&chapters[0].content[pair[1].start_offset - 1..pair[1].start_offset], " ");
+        }
+        // Chapters start on their own page, even empty ones
+        assert_eq!(layout.page_for_position("two", 0).unwrap().page_number, one.len() + 1);
+        assert_eq!(layout.page_for_position("three", 0).unwrap().page_number, layout.page_count());
+        assert!(layout.page_for_position("missing", 0).is_none());
+
+        let position = one[2].start_offset + 5;
+        assert_eq!(layout.page_for_position("one", position).unwrap().page_number, 3);
+        assert_eq!(layout.page(3), Some(one[2]));
+
+        // Larger type means more pages, and the position moves to a later page
+        let larger = PaginationSettings { font_size: 24, ..PaginationSettings::default() };
+        let relaid = BookLayout::new(&chapters, &larger);
+        assert!(relaid.page_count() > layout.page_count());
+        let page = relaid.page_for_position("one", position).unwrap();
+        assert!(page.page_number > 3 && (page.start_offset..page.end_offset).contains(&position));
+
+        // A wide screen in two columns fits about twice the text of a narrow one
+        let two_columns = PaginationSettings { viewport_width: 1600, two_column_mode: true, ..PaginationSettings::default() };
+        assert!(BookLayout::new(&chapters, &two_columns).page_count() < layout.page_count());
+    }
+
+    #[test]
+    fn test_long_words_are_broken_across_lines() {
+        assert_eq!(wrap_lines("a bb ccc", 4), [0, 5]);
+        assert_eq!(wrap_lines("abcdefghij kl", 4), [0, 4, 8, 11]);
+        assert_eq!(wrap_lines("", 4), [0]);
+    }
+}
@@ -0,0 +1,509 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use tracing::info;
+use zip::write::{SimpleFileOptions, ZipWriter};
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::services::backup_service::{BackupService, connect_existing};
+use crate::services::path_resolver::PathResolver;
+use crate::services::preferences_service::PREFERENCES_FILE;
+
+/// Archive layout version, raised when older versions could not read it
+const ARCHIVE_VERSION: u32 = 1;
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "library.db";
+/// Settings files kept in the app data directory, relative to it
+const SETTINGS_FILES: &[&str] = &[
+    "config.toml",
+    "compatibility_ledger.json",
+    "navigation_history.json",
+    "sync/sync_data.json",
+    "sync/cloud_sync.json", // Keys are in the keyring, not in the file
+    PREFERENCES_FILE,
+];
+
+/// Where a library keeps its database, settings and cover images
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryLocation {
+    pub database_path: PathBuf,
+    pub data_dir: PathBuf, // Settings, and book files restored from an archive
+    pub cache_dir: PathBuf, // Cover images
+}
+
+impl LibraryLocation {
+    /// The location the app uses on this machine
+    pub fn current() -> Result<Self> {
+        Ok(Self {
+            database_path: PathResolver::resolve_database_path_with_fallback()?,
+            data_dir: PathResolver::get_app_data_directory()?,
+            cache_dir: PathResolver::get_cache_directory()?,
+        })
+    }
+
+    fn covers_dir(&self) -> PathBuf {
+        self.cache_dir.join("covers")
+    }
+}
+
+/// What an archive holds, so it can be restored without guessing at its entries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ArchiveManifest {
+    version: u32,
+    created: DateTime<Utc>,
+    books: Vec<ArchivedBook>,
+    settings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ArchivedBook {
+    id: String,
+    file: Option<String>, // Archive entry of the book file, when book files were exported
+    cover: Option<String>,
+}
+
+/// Counts of what was written to or restored from an archive
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArchiveSummary {
+    pub books: usize,
+    pub book_files: usize,
+    pub covers: usize,
+    pub settings: usize,
+}
+
+/// Moves a whole library between machines as a single zip archive
+///
+/// The archive holds a snapshot of the database, with its annotations and
+/// reading history, the cover images and the settings files. Book files are
+/// optional, as they are usually most of its size. A library restored from an
+/// archive keeps its book files under the app data directory.
+pub struct LibraryArchive {
+    location: LibraryLocation,
+}
+
+impl LibraryArchive {
+    pub fn new(location: LibraryLocation) -> Self {
+        Self { location }
+    }
+
+    /// Write the library to an archive, with or without its book files
+    pub async fn export_library(&self, path: &Path, include_books: bool) -> Result<ArchiveSummary> {
+        // A consistent copy of the database, even while it is in use
+        let staging = tempfile::tempdir()?;
+        let snapshot = staging.path().join(DATABASE_ENTRY);
+        let pool = connect_existing(&self.location.database_path).await?;
+        let result = sqlx::query("VACUUM INTO ?")
+            .bind(snapshot.to_string_lossy().to_string())
+            .execute(&pool)
+            .await;
+        pool.close().await;
+        result.map_err(|e| anyhow!("Failed to snapshot the library database: {}", e))?;
+
+        let pool = connect_existing(&snapshot).await?;
+        let rows = sqlx::query("SELECT id, file_path, cover_path FROM books ORDER BY id")
+            .fetch_all(&pool)
+            .await;
+        pool.close().await;
+        let books: Vec<(String, PathBuf, Option<PathBuf>)> = rows?
+            .into_iter()
+            .map(|row| {
+                let cover: Option<String> = row.get("cover_path");
+                (row.get("id"), PathBuf::from(row.get::<String, _>("file_path")), cover.map(PathBuf::from))
+            })
+            .collect();
+
+        let location = self.location.clone();
+        let path = path.to_path_buf();
+        let summary = tokio::task::spawn_blocking(move || {
+            write_archive(&location, &path, &snapshot, &books, include_books)
+        }).await??;
+
+        info!("Exported {} books to the library archive", summary.books);
+        Ok(summary)
+    }
+
+    /// Replace the library with the one in an archive
+    ///
+    /// The database must not be open while it is replaced. Everything is
+    /// unpacked to a staging folder and the database checked before anything
+    /// in the library is touched. The current database and the settings files
+    /// the archive replaces are kept in the backup directory.
+    pub async fn import_library(&self, path: &Path) -> Result<ArchiveSummary> {
+        let parent = self.location.database_path.parent()
+            .ok_or_else(|| anyhow!("Invalid database path: {}", self.location.database_path.display()))?;
+        PathResolver::ensure_directory_exists(parent)?;
+        // Next to the database, so the restored copy can be renamed into place
+        let staging = tempfile::Builder::new().prefix(".library-import").tempdir_in(parent)?;
+        let restored = staging.path().join(DATABASE_ENTRY);
+
+        let location = self.location.clone();
+        let archive_path = path.to_path_buf();
+        let staging_dir = staging.path().to_path_buf();
+        let (manifest, mut summary, staged) = tokio::task::spawn_blocking(move || {
+            read_archive(&location, &archive_path, &staging_dir)
+        }).await??;
+
+        let pool = connect_existing(&restored).await?;
+        let result = update_restored_paths(&pool, &staged).await;
+        pool.close().await;
+        result?;
+        if !BackupService::verify_backup(&restored).await? {
+            return Err(anyhow!("The archived library database is damaged"));
+        }
+
+        let location = self.location.clone();
+        tokio::task::spawn_blocking(move || replace_library(&location, &restored, &staged)).await??;
+
+        summary.books = manifest.books.len();
+        info!("Imported {} books from the library archive", summary.books);
+        Ok(summary)
+    }
+}
+
+/// A file unpacked to the staging folder, and where it goes in the library
+#[derive(Debug)]
+struct StagedFile {
+    staged: PathBuf,
+    target: PathBuf,
+}
+
+/// Files unpacked from an archive; book files and covers by book ID
+#[derive(Debug, Default)]
+struct StagedFiles {
+    book_files: Vec<(String, StagedFile)>,
+    covers: Vec<(String, StagedFile)>,
+    settings: Vec<StagedFile>,
+}
+
+fn write_archive(
+    location: &LibraryLocation,
+    path: &Path,
+    snapshot: &Path,
+    books: &[(String, PathBuf, Option<PathBuf>)],
+    include_books: bool,
+) -> Result<ArchiveSummary> {
+    let mut writer = ZipWriter::new(BufWriter::new(File::create(path)?));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    // Book files and images are compressed already
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut summary = ArchiveSummary { books: books.len(), ..Default::default() };
+    let mut manifest = ArchiveManifest {
+        version: ARCHIVE_VERSION,
+        created: Utc::now(),
+        books: Vec::new(),
+        settings: Vec::new(),
+    };
+
+    for (id, file_path, cover_path) in books {
+        let mut archived = ArchivedBook { id: id.clone(), file: None, cover: None };
+
+        let file_name = file_path.file_name().map(|name| name.to_string_lossy().to_string());
+        if let (true, Some(file_name)) = (include_books && file_path.is_file(), file_name) {
+            let entry = format!("books/{}/{}", id, file_name);
+            add_file(&mut writer, &entry, file_path, stored)?;
+            archived.file = Some(entry);
+            summary.book_files += 1;
+        }
+
+        let cover = cover_path.clone()
+            .filter(|cover| cover.is_file())
+            .or_else(|| Some(location.covers_dir().join(format!("{}.jpg", id))).filter(|cover| cover.is_file()));
+        if let Some(cover) = cover {
+            let extension = cover.extension().map_or("jpg".into(), |extension| extension.to_string_lossy());
+            let entry = format!("covers/{}.{}", id, extension);
+            add_file(&mut writer, &entry, &cover, stored)?;
+            archived.cover = Some(entry);
+            summary.covers += 1;
+        }
+
+        manifest.books.push(archived);
+    }
+
+    for settings_file in SETTINGS_FILES {
+        let settings_path = location.data_dir.join(settings_file);
+        if settings_path.is_file() {
+            add_file(&mut writer, &format!("settings/{}", settings_file), &settings_path, options)?;
+            manifest.settings.push(settings_file.to_string());
+        }
+    }
+    summary.settings = manifest.settings.len();
+
+    add_file(&mut writer, DATABASE_ENTRY, snapshot, options)?;
+    writer.start_file(MANIFEST_ENTRY, options)?;
+    writer.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    writer.finish()?.flush()?;
+
+    Ok(summary)
+}
+
+/// Unpack an archive into a staging folder, leaving the library as it is
+fn read_archive(location: &LibraryLocation, path: &Path, staging: &Path) -> Result<(ArchiveManifest, ArchiveSummary, StagedFiles)> {
+    let mut archive = ZipArchive::new(BufReader::new(File::open(path)?))
+        .map_err(|e| anyhow!("Not a library archive: {}", e))?;
+    let manifest: ArchiveManifest = {
+        let entry = archive.by_name(MANIFEST_ENTRY)
+            .map_err(|_| anyhow!("Not a library archive: {} is missing", MANIFEST_ENTRY))?;
+        serde_json::from_reader(entry)?
+    };
+    if manifest.version > ARCHIVE_VERSION {
+        return Err(anyhow!("The library archive was made by a newer version of the app"));
+    }
+
+    extract_file(&mut archive, DATABASE_ENTRY, &staging.join(DATABASE_ENTRY))?;
+
+    let mut summary = ArchiveSummary::default();
+    let mut staged = StagedFiles::default();
+    for book in &manifest.books {
+        // IDs become folder and file names, so they must not leave the target folder
+        if !is_plain_name(&book.id) {
+            return Err(anyhow!("Invalid book ID in the library archive: {}", book.id));
+        }
+
+        if let Some(entry) = &book.file {
+            let file_name = Path::new(entry).file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .filter(|name| is_plain_name(name))
+                .ok_or_else(|| anyhow!("Invalid book file in the library archive: {}", entry))?;
+            let relative = Path::new("books").join(&book.id).join(file_name);
+            let file = StagedFile { staged: staging.join(&relative), target: location.data_dir.join(&relative) };
+            extract_file(&mut archive, entry, &file.staged)?;
+            staged.book_files.push((book.id.clone(), file));
+            summary.book_files += 1;
+        }
+
+        if let Some(entry) = &book.cover {
+            let extension = Path::new(entry).extension()
+                .map(|extension| extension.to_string_lossy().to_string())
+                .filter(|extension| extension.chars().all(|c| c.is_ascii_alphanumeric()))
+                .unwrap_or_else(|| "jpg".to_string());
+            let file_name = format!("{}.{}", book.id, extension);
+            let file = StagedFile { staged: staging.join("covers").join(&file_name), target: location.covers_dir().join(&file_name) };
+            extract_file(&mut archive, entry, &file.staged)?;
+            staged.covers.push((book.id.clone(), file));
+            summary.covers += 1;
+        }
+    }
+
+    // Only known settings files are restored, wherever the archive says they are
+    for settings_file in SETTINGS_FILES.iter().filter(|file| manifest.settings.iter().any(|name| name == *file)) {
+        let file = StagedFile {
+            staged: staging.join("settings").join(settings_file),
+            target: location.data_dir.join(settings_file),
+        };
+        extract_file(&mut archive, &format!("settings/{}", settings_file), &file.staged)?;
+        staged.settings.push(file);
+        summary.settings += 1;
+    }
+
+    Ok((manifest, summary, staged))
+}
+
+/// Move a checked, staged library into place, backing up the database and settings it replaces
+fn replace_library(location: &LibraryLocation, restored: &Path, staged: &StagedFiles) -> Result<()> {
+    let backup_name = format!("library_before_import_{}", Utc::now().format("%Y%m%d_%H%M%S"));
+    let backup_dir = location.data_dir.join("backups");
+
+    if location.database_path.exists() {
+        PathResolver::ensure_directory_exists(&backup_dir)?;
+        let backup_path = backup_dir.join(format!("{}.db", backup_name));
+        std::fs::rename(&location.database_path, &backup_path)?;
+        for suffix in ["-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", location.database_path.display(), suffix));
+        }
+        info!("Previous library database kept at: {}", backup_path.display());
+    }
+
+    for file in &staged.settings {
+        if let Ok(relative) = file.target.strip_prefix(&location.data_dir) {
+            if file.target.is_file() {
+                let backup_path = backup_dir.join(format!("{}_settings", backup_name)).join(relative);
+                move_file(&file.target, &backup_path)?;
+            }
+        }
+    }
+
+    std::fs::rename(restored, &location.database_path)?;
+    for file in staged.book_files.iter().chain(&staged.covers).map(|(_, file)| file).chain(&staged.settings) {
+        move_file(&file.staged, &file.target)?;
+    }
+    Ok(())
+}
+
+/// Point the restored books at their files and covers on this machine
+async fn update_restored_paths(pool: &SqlitePool, staged: &StagedFiles) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    for (book_id, file) in &staged.book_files {
+        sqlx::query("UPDATE books SET file_path = ? WHERE id = ?")
+            .bind(file.target.to_string_lossy().to_string())
+            .bind(book_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    for (book_id, file) in &staged.covers {
+        sqlx::query("UPDATE books SET cover_path = ? WHERE id = ?")
+            .bind(file.target.to_string_lossy().to_string())
+            .bind(book_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+fn add_file<W: Write + Seek>(writer: &mut ZipWriter<W>, entry: &str, path: &Path, options: SimpleFileOptions) -> Result<()> {
+    writer.start_file(entry, options)?;
+    std::io::copy(&mut File::open(path)?, writer)
+        .map_err(|e| anyhow!("Failed to archive {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+fn extract_file<R: Read + Seek>(archive: &mut ZipArchive<R>, entry: &str, target: &Path) -> Result<()> {
+    let mut file = archive.by_name(entry)
+        .map_err(|_| anyhow!("The library archive is missing {}", entry))?;
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::io::copy(&mut file, &mut File::create(target)?)?;
+    Ok(())
+}
+
+/// Rename a file, copying it when the target is on another file system
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if std::fs::rename(from, to).is_err() {
+        std::fs::copy(from, to)
+            .map_err(|e| anyhow!("Failed to move {} to {}: {}", from.display(), to.display(), e))?;
+        std::fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// A single path component, not `.` or `..`
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(root: &Path) -> LibraryLocation {
+        LibraryLocation {
+            database_path: root.join("data").join("library.db"),
+            data_dir: root.join("data"),
+            cache_dir: root.join("cache"),
+        }
+    }
+
+    async fn create_library(location: &LibraryLocation, book_path: &Path) {
+        std::fs::create_dir_all(&location.data_dir).unwrap();
+        let database_url = format!("sqlite://{}?mode=rwc", location.database_path.display());
+        let pool = SqlitePool::connect(&database_url).await.unwrap();
+        sqlx::query("CREATE TABLE books (id TEXT PRIMARY KEY, title TEXT, file_path TEXT NOT NULL, cover_path TEXT)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE annotations (id TEXT PRIMARY KEY, book_id TEXT, content TEXT)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO books (id, title, file_path, cover_path) VALUES ('dune', 'Dune', ?, ?), ('emma', 'Emma', '/missing/emma.epub', NULL)")
+            .bind(book_path.to_string_lossy().to_string())
+            .bind(location.covers_dir().join("dune.png").to_string_lossy().to_string())
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO annotations (id, book_id, content) VALUES ('a1', 'dune', 'Fear is the mind-killer')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        std::fs::create_dir_all(location.covers_dir()).unwrap();
+        std::fs::write(location.covers_dir().join("dune.png"), b"cover").unwrap();
+        std::fs::write(location.data_dir.join("navigation_history.json"), b"{}").unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import_library() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let book_path = temp_dir.path().join("dune.epub");
+        std::fs::write(&book_path, b"book").unwrap();
+        let source = location(&temp_dir.path().join("old"));
+        create_library(&source, &book_path).await;
+
+        let archive_path = temp_dir.path().join("library.zip");
+        let exported = LibraryArchive::new(source).export_library(&archive_path, true).await.unwrap();
+        assert_eq!(exported, ArchiveSummary { books: 2, book_files: 1, covers: 1, settings: 1 });
+
+        let target = location(&temp_dir.path().join("new"));
+        std::fs::create_dir_all(&target.data_dir).unwrap();
+        std::fs::write(target.data_dir.join("navigation_history.json"), b"old").unwrap();
+        let imported = LibraryArchive::new(target.clone()).import_library(&archive_path).await.unwrap();
+        assert_eq!(imported, exported);
+
+        let restored_book = target.data_dir.join("books").join("dune").join("dune.epub");
+        assert_eq!(std::fs::read(&restored_book).unwrap(), b"book");
+        assert_eq!(std::fs::read(target.data_dir.join("navigation_history.json")).unwrap(), b"{}");
+        // The settings file it replaced is kept with the backups
+        let backups: Vec<PathBuf> = std::fs::read_dir(target.data_dir.join("backups")).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(std::fs::read(backups[0].join("navigation_history.json")).unwrap(), b"old");
+
+        let pool = connect_existing(&target.database_path).await.unwrap();
+        let paths: Vec<(String, Option<String>)> = sqlx::query_as("SELECT file_path, cover_path FROM books ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(paths[0], (
+            restored_book.to_string_lossy().to_string(),
+            Some(target.covers_dir().join("dune.png").to_string_lossy().to_string()),
+        ));
+        // Books whose files were not archived keep their paths
+        assert_eq!(paths[1], ("/missing/emma.epub".to_string(), None));
+        let annotations: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM annotations").fetch_one(&pool).await.unwrap();
+        assert_eq!(annotations, 1);
+        pool.close().await;
+
+        // Without book files, only the library data is archived
+        let light_path = temp_dir.path().join("light.zip");
+        let light = LibraryArchive::new(target).export_library(&light_path, false).await.unwrap();
+        assert_eq!((light.books, light.book_files), (2, 0));
+    }
+
+    #[tokio::test]
+    async fn test_damaged_archive_leaves_library_alone() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let archive_path = temp_dir.path().join("library.zip");
+        let manifest = ArchiveManifest {
+            version: ARCHIVE_VERSION,
+            created: Utc::now(),
+            books: Vec::new(),
+            settings: vec!["navigation_history.json".to_string()],
+        };
+        let mut writer = ZipWriter::new(File::create(&archive_path).unwrap());
+        let options = SimpleFileOptions::default();
+        writer.start_file(MANIFEST_ENTRY, options).unwrap();
+        writer.write_all(&serde_json::to_vec(&manifest).unwrap()).unwrap();
+        writer.start_file(DATABASE_ENTRY, options).unwrap();
+        writer.write_all(&[0xff; 4096]).unwrap();
+        writer.start_file("settings/navigation_history.json", options).unwrap();
+        writer.write_all(b"{}").unwrap();
+        writer.finish().unwrap();
+
+        let target = location(temp_dir.path());
+        std::fs::create_dir_all(&target.data_dir).unwrap();
+        std::fs::write(target.data_dir.join("navigation_history.json"), b"old").unwrap();
+        assert!(LibraryArchive::new(target.clone()).import_library(&archive_path).await.is_err());
+        assert_eq!(std::fs::read(target.data_dir.join("navigation_history.json")).unwrap(), b"old");
+    }
+}
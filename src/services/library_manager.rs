@@ -9,7 +9,7 @@ use crate::models::library::{
     Category, ReadingStatus, LibraryStats, LibraryFilter, LibrarySortBy, SortDirection,
     Author, Genre, Tag, LibraryOrganizer,
 };
-use crate::services::library_service::LibraryService;
+use crate::services::library_service::{BulkBookChanges, LibraryService};
 
 /// Slint-compatible library statistics
 #[derive(Clone, Default)]
@@ -18,6 +18,8 @@ pub struct SlintLibraryStats {
     pub want_to_read: i32,
     pub currently_reading: i32,
     pub finished: i32,
+    pub up_next: i32,
+    pub in_trash: i32,
     pub total_collections: i32,
     pub total_authors: i32,
     pub total_tags: i32,
@@ -34,6 +36,7 @@ pub struct SlintCollection {
     pub count: i32,
     pub is_smart: bool,
     pub is_favorite: bool,
+    pub parent_id: String, // Empty for a top-level collection
 }
 
 /// Slint-compatible author model
@@ -155,6 +158,8 @@ impl LibraryManager {
             want_to_read: stats.want_to_read as i32,
             currently_reading: stats.currently_reading as i32,
             finished: stats.finished as i32,
+            up_next: stats.up_next as i32,
+            in_trash: stats.in_trash as i32,
             total_collections: stats.total_collections as i32,
             total_authors: stats.total_authors as i32,
             total_tags: stats.total_tags as i32,
@@ -176,6 +181,7 @@ impl LibraryManager {
                 count: c.book_count() as i32,
                 is_smart: c.is_smart,
                 is_favorite: c.is_favorite,
+                parent_id: c.parent_id.clone().unwrap_or_default(),
             })
             .collect();
 
@@ -316,6 +322,18 @@ impl LibraryManager {
         Ok(())
     }
 
+    /// Move a collection under another one, or to the top level with an empty parent
+    pub async fn move_collection(&self, collection_id: &str, parent_id: &str) -> Result<()> {
+        let parent_id = Some(parent_id).filter(|id| !id.is_empty());
+        self.service.move_collection(collection_id, parent_id).await?;
+
+        // Refresh collections
+        let collections = self.service.get_all_collections().await?;
+        self.update_collections(&collections).await;
+
+        Ok(())
+    }
+
     /// Toggle collection favorite status
     pub async fn toggle_collection_favorite(&self, collection_id: &str) -> Result<()> {
         if let Some(mut collection) = self.service.get_collection(collection_id).await? {
@@ -378,6 +396,24 @@ impl LibraryManager {
         Ok(())
     }
 
+    /// Apply metadata changes to many books, then reload authors, tags and stats
+    pub async fn bulk_update_books(&self, book_ids: &[String], changes: &BulkBookChanges) -> Result<usize> {
+        let updated = self.service.bulk_update_books(book_ids, changes).await?;
+        self.refresh_all().await?;
+        Ok(updated)
+    }
+
+    /// Merge other spellings of an author's name into one, then reload authors and stats
+    pub async fn merge_authors(&self, author_name: &str, aliases: &[String]) -> Result<()> {
+        self.service.merge_authors(author_name, aliases).await?;
+        self.refresh_all().await
+    }
+
+    /// Get groups of author names that look like the same author
+    pub async fn find_duplicate_authors(&self) -> Result<Vec<Vec<String>>> {
+        self.service.find_duplicate_authors().await
+    }
+
     /// Get reading status for a book
     pub async fn get_reading_status(&self, book_id: &str) -> Result<Option<String>> {
         if let Some(status) = self.service.get_reading_status(book_id).await? {
@@ -439,11 +475,13 @@ impl LibraryManager {
             "publish-date" => LibrarySortBy::PublishDate,
             "file-size" => LibrarySortBy::FileSize,
             "page-count" => LibrarySortBy::PageCount,
+            "word-count" => LibrarySortBy::WordCount,
             "progress" => LibrarySortBy::Progress,
             "last-opened" => LibrarySortBy::LastOpened,
             "genre" => LibrarySortBy::Genre,
             "publisher" => LibrarySortBy::Publisher,
             "language" => LibrarySortBy::Language,
+            "series" => LibrarySortBy::Series,
             _ => LibrarySortBy::Title,
         };
 
@@ -501,15 +539,7 @@ impl LibraryManager {
             match_type: if match_type == "any" { MatchType::Any } else { MatchType::All },
         };
 
-        // Create temporary collection for preview
-        let temp_collection = Collection::new_smart(
-            "temp".to_string(),
-            "⚡".to_string(),
-            "#007AFF".to_string(),
-            smart_rules,
-        );
-
-        self.service.evaluate_smart_collection(&temp_collection.id).await
+        self.service.evaluate_smart_rules(&smart_rules).await
     }
 
     /// Parse category string to Category enum
@@ -519,6 +549,8 @@ impl LibraryManager {
             "want-to-read" => Category::WantToRead,
             "currently-reading" => Category::CurrentlyReading,
             "finished" => Category::Finished,
+            "up-next" => Category::UpNext,
+            "trash" => Category::Trash,
             _ => {
                 if let Some(collection_id) = category.strip_prefix("collection:") {
                     Category::Collection(collection_id.to_string())
@@ -549,6 +581,13 @@ impl LibraryManager {
             "File Size" => SmartRuleField::FileSize,
             "Page Count" => SmartRuleField::PageCount,
             "Progress" => SmartRuleField::Progress,
+            "Subject" => SmartRuleField::Subject,
+            "Contributor" => SmartRuleField::Contributor,
+            "Series" => SmartRuleField::Series,
+            "Word Count" => SmartRuleField::WordCount,
+            "Reading Time" => SmartRuleField::ReadingTime,
+            "Annotation Count" => SmartRuleField::AnnotationCount,
+            "Last Opened" => SmartRuleField::LastOpened,
             _ => SmartRuleField::Title,
         }
     }
@@ -596,6 +635,13 @@ impl LibraryManager {
             "File Size".to_string(),
             "Page Count".to_string(),
             "Progress".to_string(),
+            "Subject".to_string(),
+            "Contributor".to_string(),
+            "Series".to_string(),
+            "Word Count".to_string(),
+            "Reading Time".to_string(),
+            "Annotation Count".to_string(),
+            "Last Opened".to_string(),
         ]
     }
 
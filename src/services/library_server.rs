@@ -0,0 +1,507 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use chrono::Utc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::models::book::{Book, BookFormat};
+use crate::services::database::DatabaseService;
+use crate::services::secrets;
+use crate::utils::url_encoding::{decode_query, encode_query, percent_decode};
+
+/// Provider name the sharing server's access token is stored under in the keyring
+pub const LIBRARY_SERVER_PROVIDER: &str = "library-server";
+/// Books per page of the full library feed
+const PAGE_SIZE: usize = 50;
+/// Books in the recently added feed
+const RECENT_COUNT: usize = 25;
+/// Longest request head accepted; requests carry no body
+const MAX_REQUEST_HEAD: usize = 16 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const NAVIGATION_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=navigation";
+const ACQUISITION_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=acquisition";
+
+/// A parsed HTTP request head
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub headers: HashMap<String, String>, // Names lowercased
+}
+
+/// A response, written with `Connection: close`
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    fn new(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self { status, content_type: content_type.to_string(), headers: Vec::new(), body: body.into() }
+    }
+
+    fn text(status: u16, message: &str) -> Self {
+        Self::new(status, "text/plain; charset=utf-8", message)
+    }
+
+    fn feed(xml: String, kind: &str) -> Self {
+        Self::new(200, kind, xml)
+    }
+}
+
+/// Shares the library with phones and e-readers on the local network
+///
+/// Serves an OPDS 1.2 catalog at `/opds` and book files at `/books/<id>/file`.
+/// Every request needs the access token, as a bearer token, as the password
+/// of HTTP basic auth (any user name) or as a `token` query parameter.
+pub struct LibraryServer {
+    database: Arc<DatabaseService>,
+    token: RwLock<String>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl LibraryServer {
+    pub fn new(database: Arc<DatabaseService>, token: String) -> Self {
+        Self { database, token: RwLock::new(token), task: Mutex::new(None) }
+    }
+
+    /// Server using the token kept in the keyring, creating one the first time
+    pub async fn with_stored_token(database: Arc<DatabaseService>) -> Result<Self> {
+        let token = match secrets::get_api_key_async(LIBRARY_SERVER_PROVIDER).await {
+            Some(token) => token,
+            None => {
+                let token = new_token();
+                secrets::store_api_key_async(LIBRARY_SERVER_PROVIDER, &token).await?;
+                token
+            }
+        };
+        Ok(Self::new(database, token))
+    }
+
+    /// The token devices need to connect
+    pub async fn token(&self) -> String {
+        self.token.read().await.clone()
+    }
+
+    /// Replace the token, locking out every device that had the old one
+    pub async fn regenerate_token(&self) -> Result<String> {
+        let token = new_token();
+        secrets::store_api_key_async(LIBRARY_SERVER_PROVIDER, &token).await?;
+        *self.token.write().await = token.clone();
+        Ok(token)
+    }
+
+    /// Listen on every interface; returns the address bound, useful when `port` is 0
+    pub async fn start(self: &Arc<Self>, port: u16) -> Result<SocketAddr> {
+        let mut task = self.task.lock().await;
+        if task.is_some() {
+            return Err(anyhow!("The library server is already running"));
+        }
+        let listener = TcpListener::bind(("0.0.0.0", port)).await
+            .map_err(|e| anyhow!("Cannot share the library on port {}: {}", port, e))?;
+        let address = listener.local_addr()?;
+
+        let this = self.clone();
+        *task = Some(tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        let this = this.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = this.serve_connection(stream).await {
+                                debug!("Library server connection from {} failed: {}", peer, e);
+                            }
+                        });
+                    }
+                    Err(e) => warn!("Library server failed to accept a connection: {}", e),
+                }
+            }
+        }));
+
+        info!("Sharing the library on {}", address);
+        Ok(address)
+    }
+
+    /// Stop listening; transfers under way are cut off
+    pub async fn stop(&self) {
+        if let Some(task) = self.task.lock().await.take() {
+            task.abort();
+        }
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.task.lock().await.is_some()
+    }
+
+    async fn serve_connection(&self, mut stream: TcpStream) -> Result<()> {
+        let mut head = Vec::new();
+        let mut chunk = [0u8; 4096];
+        while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+            if head.len() > MAX_REQUEST_HEAD {
+                return write_response(&mut stream, &HttpResponse::text(431, "Request head too large"), true).await;
+            }
+            let read = tokio::time::timeout(REQUEST_TIMEOUT, stream.read(&mut chunk)).await??;
+            if read == 0 {
+                return Ok(());
+            }
+            head.extend_from_slice(&chunk[..read]);
+        }
+
+        let response = match parse_request(&head) {
+            Some(request) => {
+                let response = self.respond(&request).await;
+                return write_response(&mut stream, &response, request.method != "HEAD").await;
+            }
+            None => HttpResponse::text(400, "Bad request"),
+        };
+        write_response(&mut stream, &response, true).await
+    }
+
+    /// Answer a request
+    pub async fn respond(&self, request: &HttpRequest) -> HttpResponse {
+        if request.method != "GET" && request.method != "HEAD" {
+            return HttpResponse::text(405, "Only GET and HEAD are supported");
+        }
+        if !self.is_authorized(request).await {
+            let mut response = HttpResponse::text(401, "An access token is required");
+            response.headers.push(("WWW-Authenticate".to_string(), "Basic realm=\"Library\"".to_string()));
+            return response;
+        }
+
+        match self.route(request).await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("Library server failed to answer {}: {}", request.path, e);
+                HttpResponse::text(500, "The library could not be read")
+            }
+        }
+    }
+
+    async fn route(&self, request: &HttpRequest) -> Result<HttpResponse> {
+        let links = Links { token: request.query.get("token").map(String::as_str) };
+        let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+
+        let response = match segments.as_slice() {
+            [""] | ["opds"] => HttpResponse::feed(navigation_feed(&links), NAVIGATION_TYPE),
+            ["opds", "books"] => {
+                let page = request.query.get("page").and_then(|page| page.parse::<usize>().ok()).unwrap_or(1).max(1);
+                let mut books = self.database.get_all_books().await?;
+                books.sort_by_key(|book| book.title.to_lowercase());
+                HttpResponse::feed(books_feed("All Books", "/opds/books", &books, page, &links), ACQUISITION_TYPE)
+            }
+            ["opds", "recent"] => {
+                let books = self.database.get_recently_added_books(RECENT_COUNT).await?;
+                HttpResponse::feed(books_feed("Recently Added", "/opds/recent", &books, 1, &links), ACQUISITION_TYPE)
+            }
+            ["opds", "search"] => {
+                let query = request.query.get("q").map(|query| query.trim()).unwrap_or_default();
+                let books = if query.is_empty() { Vec::new() } else { self.database.search_books(query).await? };
+                HttpResponse::feed(books_feed(&format!("Search: {}", query), "/opds/search", &books, 1, &links), ACQUISITION_TYPE)
+            }
+            ["opds", "search.xml"] => {
+                HttpResponse::new(200, "application/opensearchdescription+xml", search_description(&links))
+            }
+            ["books", book_id, "file"] => match self.find_book(book_id).await {
+                Some(book) => {
+                    let mut response = HttpResponse::new(200, mime_type(&book.file_format), tokio::fs::read(&book.file_path).await?);
+                    response.headers.push((
+                        "Content-Disposition".to_string(),
+                        format!("attachment; filename=\"{}\"", download_name(&book)),
+                    ));
+                    response
+                }
+                None => HttpResponse::text(404, "No such book"),
+            },
+            ["books", book_id, "cover"] => match self.find_book(book_id).await.and_then(|book| book.cover_path) {
+                Some(cover) if cover.exists() => {
+                    let content_type = if cover.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")) { "image/png" } else { "image/jpeg" };
+                    HttpResponse::new(200, content_type, tokio::fs::read(&cover).await?)
+                }
+                _ => HttpResponse::text(404, "No cover"),
+            },
+            _ => HttpResponse::text(404, "Not found"),
+        };
+        Ok(response)
+    }
+
+    async fn find_book(&self, book_id: &str) -> Option<Book> {
+        let book = self.database.get_book_by_id(book_id).await.ok()?;
+        match self.database.is_book_trashed(book_id).await {
+            Ok(false) => Some(book),
+            _ => None,
+        }
+    }
+
+    async fn is_authorized(&self, request: &HttpRequest) -> bool {
+        let token = self.token.read().await;
+        if request.query.get("token") == Some(&*token) {
+            return true;
+        }
+        let Some(authorization) = request.headers.get("authorization") else {
+            return false;
+        };
+        if let Some(bearer) = authorization.strip_prefix("Bearer ") {
+            return bearer.trim() == *token;
+        }
+        authorization.strip_prefix("Basic ")
+            .and_then(|encoded| base64::engine::general_purpose::STANDARD.decode(encoded.trim()).ok())
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            .is_some_and(|credentials| credentials.split_once(':').is_some_and(|(_, password)| password == *token))
+    }
+}
+
+/// Builds links that keep a token given in the query string
+struct Links<'a> {
+    token: Option<&'a str>,
+}
+
+impl Links<'_> {
+    fn href(&self, path: &str) -> String {
+        let href = match self.token {
+            Some(token) => {
+                let separator = if path.contains('?') { '&' } else { '?' };
+                format!("{}{}token={}", path, separator, encode_query(token))
+            }
+            None => path.to_string(),
+        };
+        xml_attribute(&href)
+    }
+}
+
+fn navigation_feed(links: &Links) -> String {
+    let entries = [("All Books", "/opds/books", ACQUISITION_TYPE), ("Recently Added", "/opds/recent", ACQUISITION_TYPE)]
+        .iter()
+        .map(|(title, path, kind)| {
+            format!(
+                "<entry><title>{}</title><id>urn:library:{}</id><updated>{}</updated><link rel=\"subsection\" type=\"{}\" href=\"{}\"/></entry>",
+                title, path, Utc::now().to_rfc3339(), kind, links.href(path)
+            )
+        })
+        .collect::<String>();
+    feed("urn:library:root", "Library", &feed_links("/opds", None, None, links), &entries)
+}
+
+fn books_feed(title: &str, path: &str, books: &[Book], page: usize, links: &Links) -> String {
+    let start = (page - 1) * PAGE_SIZE;
+    let page_books = books.iter().skip(start).take(PAGE_SIZE);
+    let next = (start + PAGE_SIZE < books.len()).then(|| format!("{}?page={}", path, page + 1));
+    let previous = (page > 1).then(|| format!("{}?page={}", path, page - 1));
+    let entries: String = page_books.map(|book| book_entry(book, links)).collect();
+    feed(&format!("urn:library:{}", path), title, &feed_links(path, next, previous, links), &entries)
+}
+
+fn book_entry(book: &Book, links: &Links) -> String {
+    let mut entry = format!(
+        "<entry><title>{}</title><id>urn:uuid:{}</id><updated>{}</updated><author><name>{}</name></author>",
+        xml_text(&book.title), xml_text(&book.id), book.added_date.to_rfc3339(), xml_text(&book.author)
+    );
+    if let Some(language) = &book.language {
+        entry.push_str(&format!("<dc:language>{}</dc:language>", xml_text(language)));
+    }
+    if let Some(description) = &book.description {
+        entry.push_str(&format!("<summary>{}</summary>", xml_text(description)));
+    }
+    if book.cover_path.is_some() {
+        let cover = links.href(&format!("/books/{}/cover", book.id));
+        entry.push_str(&format!("<link rel=\"http://opds-spec.org/image\" type=\"image/jpeg\" href=\"{}\"/>", cover));
+        entry.push_str(&format!("<link rel=\"http://opds-spec.org/image/thumbnail\" type=\"image/jpeg\" href=\"{}\"/>", cover));
+    }
+    entry.push_str(&format!(
+        "<link rel=\"http://opds-spec.org/acquisition/open-access\" type=\"{}\" href=\"{}\" length=\"{}\"/></entry>",
+        mime_type(&book.file_format), links.href(&format!("/books/{}/file", book.id)), book.file_size
+    ));
+    entry
+}
+
+fn feed_links(path: &str, next: Option<String>, previous: Option<String>, links: &Links) -> String {
+    let mut xml = format!(
+        "<link rel=\"self\" type=\"{}\" href=\"{}\"/><link rel=\"start\" type=\"{}\" href=\"{}\"/>\
+         <link rel=\"search\" type=\"application/opensearchdescription+xml\" href=\"{}\"/>",
+        ACQUISITION_TYPE, links.href(path), NAVIGATION_TYPE, links.href("/opds"), links.href("/opds/search.xml")
+    );
+    for (rel, href) in [("next", next), ("previous", previous)] {
+        if let Some(href) = href {
+            xml.push_str(&format!("<link rel=\"{}\" type=\"{}\" href=\"{}\"/>", rel, ACQUISITION_TYPE, links.href(&href)));
+        }
+    }
+    xml
+}
+
+fn feed(id: &str, title: &str, links: &str, entries: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\" xmlns:dc=\"http://purl.org/dc/terms/\" xmlns:opds=\"http://opds-spec.org/2010/catalog\">\
+         <id>{}</id><title>{}</title><updated>{}</updated>{}{}</feed>",
+        xml_text(id), xml_text(title), Utc::now().to_rfc3339(), links, entries
+    )
+}
+
+fn search_description(links: &Links) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <OpenSearchDescription xmlns=\"http://a9.com/-/spec/opensearch/1.1/\">\
+         <ShortName>Library</ShortName><Description>Search the library</Description>\
+         <Url type=\"{}\" template=\"{}\"/></OpenSearchDescription>",
+        ACQUISITION_TYPE, links.href("/opds/search?q={searchTerms}")
+    )
+}
+
+/// Parse the request line and headers of an HTTP/1.x request
+pub fn parse_request(head: &[u8]) -> Option<HttpRequest> {
+    let head = std::str::from_utf8(head).ok()?;
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_uppercase();
+    let target = request_line.next()?;
+    if !request_line.next()?.starts_with("HTTP/1.") {
+        return None;
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode_query(name), decode_query(value))
+        })
+        .collect();
+    let headers = lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+
+    Some(HttpRequest { method, path: percent_decode(path), query, headers })
+}
+
+async fn write_response(stream: &mut TcpStream, response: &HttpResponse, with_body: bool) -> Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status, reason(response.status), response.content_type, response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    if with_body {
+        stream.write_all(&response.body).await?;
+    }
+    stream.flush().await?;
+    Ok(())
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+fn mime_type(format: &BookFormat) -> &'static str {
+    match format {
+        BookFormat::Epub => "application/epub+zip",
+        BookFormat::Pdf => "application/pdf",
+        BookFormat::Mobi => "application/x-mobipocket-ebook",
+        BookFormat::Azw3 => "application/vnd.amazon.ebook",
+        BookFormat::Txt => "text/plain",
+        BookFormat::Html => "text/html",
+    }
+}
+
+/// File name offered for a download, kept to characters every header parser takes
+fn download_name(book: &Book) -> String {
+    let stem: String = book.title.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || " -_.,()".contains(c) { c } else { '_' })
+        .collect();
+    format!("{}.{}", stem.trim(), book.file_format.to_extension())
+}
+
+fn new_token() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+fn xml_text(text: &str) -> String {
+    html_escape::encode_text(text).into_owned()
+}
+
+fn xml_attribute(text: &str) -> String {
+    html_escape::encode_double_quoted_attribute(text).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    async fn test_library_feed_search_download_and_auth() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("emma.epub");
+        std::fs::write(&path, b"epub bytes").unwrap();
+        let database = Arc::new(DatabaseService::new_in_memory().await.unwrap());
+        let mut book = Book::new("Emma & Co".to_string(), "Jane Austen".to_string(), path, 10, BookFormat::Epub);
+        book.cover_path = Some(PathBuf::from("/missing/cover.jpg"));
+        database.insert_book(&book).await.unwrap();
+        let server = Arc::new(LibraryServer::new(database, "s3cret".to_string()));
+
+        let get = |target: &str, authorization: Option<&str>| {
+            let mut head = format!("GET {} HTTP/1.1\r\nHost: desktop\r\n", target);
+            if let Some(authorization) = authorization {
+                head.push_str(&format!("Authorization: {}\r\n", authorization));
+            }
+            parse_request(format!("{}\r\n", head).as_bytes()).unwrap()
+        };
+
+        assert_eq!(server.respond(&get("/opds", None)).await.status, 401);
+        assert_eq!(server.respond(&get("/opds", Some("Bearer wrong"))).await.status, 401);
+        // Basic auth with any user name, as e-readers ask for one
+        let basic = format!("Basic {}", base64::engine::general_purpose::STANDARD.encode("kobo:s3cret"));
+        let root = server.respond(&get("/opds", Some(&basic))).await;
+        assert_eq!((root.status, root.content_type.as_str()), (200, NAVIGATION_TYPE));
+
+        let books = server.respond(&get("/opds/books?token=s3cret", None)).await;
+        let xml = String::from_utf8(books.body).unwrap();
+        assert!(xml.contains("<title>Emma &amp; Co</title>"));
+        assert!(xml.contains(&format!("href=\"/books/{}/file?token=s3cret\"", book.id)));
+        assert!(!xml.contains("rel=\"next\""));
+
+        let found = server.respond(&get("/opds/search?q=austen&token=s3cret", None)).await;
+        assert!(String::from_utf8(found.body).unwrap().contains(&book.id));
+        let description = server.respond(&get("/opds/search.xml?token=s3cret", None)).await;
+        assert!(String::from_utf8(description.body).unwrap().contains("template=\"/opds/search?q={searchTerms}&amp;token=s3cret\""));
+
+        // Over a real socket
+        let address = server.start(0).await.unwrap();
+        let mut stream = TcpStream::connect(("127.0.0.1", address.port())).await.unwrap();
+        let request = format!("GET /books/{}/file HTTP/1.1\r\nAuthorization: Bearer s3cret\r\n\r\n", book.id);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).await.unwrap();
+        assert!(reply.starts_with("HTTP/1.1 200 OK\r\nContent-Type: application/epub+zip\r\nContent-Length: 10\r\n"));
+        assert!(reply.contains("Content-Disposition: attachment; filename=\"Emma _ Co.epub\""));
+        assert!(reply.ends_with("\r\n\r\nepub bytes"));
+        assert!(server.start(0).await.is_err());
+        server.stop().await;
+        assert!(!server.is_running().await);
+
+        assert_eq!(server.respond(&get("/books/nope/file?token=s3cret", None)).await.status, 404);
+        assert_eq!(server.respond(&get(&format!("/books/{}/cover?token=s3cret", book.id), None)).await.status, 404);
+    }
+}
@@ -1,7 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use anyhow::Result;
 use chrono::{DateTime, Utc, Duration, Datelike};
-use sqlx::{Row, SqlitePool, sqlite::SqliteRow};
+use serde::{Deserialize, Serialize};
+use sqlx::query::QueryScalar;
+use sqlx::sqlite::{Sqlite, SqliteArguments, SqliteRow};
+use sqlx::{QueryBuilder, Row, SqlitePool};
+use tokio::sync::{broadcast, RwLock};
 use uuid::Uuid;
 
 use crate::models::library::{
@@ -9,16 +14,60 @@ use crate::models::library::{
     Category, ReadingStatus, LibraryStats, LibraryFilter, LibrarySortBy, SortDirection,
     Author, Genre, Tag, LibraryOrganizer,
 };
-use crate::models::book::Book;
+use crate::models::book::{Book, DEFAULT_READING_SPEED_WPM};
+
+/// Emitted when a newly imported book matches a subscribed smart collection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionMatchNotification {
+    pub collection_id: String,
+    pub collection_name: String,
+    pub book_id: String,
+    pub book_title: String,
+}
+
+/// Metadata changes applied to many books at once
+///
+/// `None` leaves a field as it is. An empty genre, series or language clears it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BulkBookChanges {
+    pub author: Option<String>,
+    pub genre: Option<String>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+    pub series: Option<String>, // Keeps each book's position unless cleared
+    pub language: Option<String>,
+    pub reading_status: Option<ReadingStatus>,
+}
+
+/// A value bound to a compiled smart rule
+#[derive(Debug, Clone, PartialEq)]
+enum RuleParam {
+    Text(String),
+    Number(f64),
+}
+
+/// Smart collection rules as a SQL condition and the values it binds
+#[derive(Debug, Clone, PartialEq)]
+struct CompiledRules {
+    condition: String,
+    params: Vec<RuleParam>,
+}
 
 #[derive(Clone)]
 pub struct LibraryService {
     pool: SqlitePool,
+    match_notifications: broadcast::Sender<CollectionMatchNotification>,
+    smart_cache: Arc<RwLock<HashMap<String, (i64, Vec<String>)>>>, // Rules JSON -> library version and matches
 }
 
 impl LibraryService {
     pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+        let (match_notifications, _) = broadcast::channel(64);
+        Self {
+            pool,
+            match_notifications,
+            smart_cache: Arc::new(RwLock::new(HashMap::new())),
+        }
     }
 
     /// Initialize library tables
@@ -37,13 +86,19 @@ impl LibraryService {
                 is_smart BOOLEAN NOT NULL DEFAULT FALSE,
                 smart_rules TEXT, -- JSON
                 is_favorite BOOLEAN NOT NULL DEFAULT FALSE,
-                sort_order INTEGER NOT NULL DEFAULT 0
+                sort_order INTEGER NOT NULL DEFAULT 0,
+                created_date TEXT -- Same as created_at; the book service reads it
             );
             "#,
         )
         .execute(&self.pool)
         .await?;
 
+        // Collections created before nesting are top-level
+        let _ = sqlx::query("ALTER TABLE collections ADD COLUMN parent_id TEXT")
+            .execute(&self.pool)
+            .await;
+
         // Create collection_books table (many-to-many relationship)
         sqlx::query(
             r#"
@@ -77,6 +132,49 @@ impl LibraryService {
         .execute(&self.pool)
         .await?;
 
+        // Create reading_queue table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS reading_queue (
+                book_id TEXT PRIMARY KEY,
+                position INTEGER NOT NULL,
+                added_at TEXT NOT NULL,
+                FOREIGN KEY (book_id) REFERENCES books (id) ON DELETE CASCADE
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create collection_subscriptions table (smart collections to watch for new matches)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS collection_subscriptions (
+                collection_id TEXT PRIMARY KEY,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (collection_id) REFERENCES collections (id) ON DELETE CASCADE
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create collection_new_matches table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS collection_new_matches (
+                collection_id TEXT NOT NULL,
+                book_id TEXT NOT NULL,
+                matched_at TEXT NOT NULL,
+                PRIMARY KEY (collection_id, book_id),
+                FOREIGN KEY (collection_id) REFERENCES collections (id) ON DELETE CASCADE,
+                FOREIGN KEY (book_id) REFERENCES books (id) ON DELETE CASCADE
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
         // Create authors table
         sqlx::query(
             r#"
@@ -96,6 +194,18 @@ impl LibraryService {
         .execute(&self.pool)
         .await?;
 
+        // Create author_aliases table (other spellings of an author's name)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS author_aliases (
+                alias TEXT PRIMARY KEY,
+                author_name TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
         // Create genres table
         sqlx::query(
             r#"
@@ -157,10 +267,18 @@ impl LibraryService {
             .execute(&self.pool)
             .await?;
 
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_collections_parent_id ON collections(parent_id);")
+            .execute(&self.pool)
+            .await?;
+
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_reading_status_status ON reading_status(status);")
             .execute(&self.pool)
             .await?;
 
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_reading_queue_position ON reading_queue(position);")
+            .execute(&self.pool)
+            .await?;
+
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_book_tags_book_id ON book_tags(book_id);")
             .execute(&self.pool)
             .await?;
@@ -169,44 +287,80 @@ impl LibraryService {
             .execute(&self.pool)
             .await?;
 
+        // Versions the book data smart collections read, so cached results can be checked
+        sqlx::query("CREATE TABLE IF NOT EXISTS library_changes (id INTEGER PRIMARY KEY CHECK (id = 1), version INTEGER NOT NULL);")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("INSERT OR IGNORE INTO library_changes (id, version) VALUES (1, 0);")
+            .execute(&self.pool)
+            .await?;
+
+        for table in ["books", "tags", "book_tags", "reading_status"] {
+            for event in ["INSERT", "UPDATE", "DELETE"] {
+                sqlx::query(&format!(
+                    "CREATE TRIGGER IF NOT EXISTS {0}_{1}_changes AFTER {2} ON {0} \
+                     BEGIN UPDATE library_changes SET version = version + 1; END;",
+                    table,
+                    event.to_lowercase(),
+                    event
+                ))
+                .execute(&self.pool)
+                .await?;
+            }
+        }
+
         Ok(())
     }
 
     /// Get library statistics
     pub async fn get_library_stats(&self) -> Result<LibraryStats> {
         // Get total books
-        let total_books: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM books")
+        let total_books: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM books WHERE deleted_at IS NULL")
+            .fetch_one(&self.pool)
+            .await?;
+        let in_trash: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM books WHERE deleted_at IS NOT NULL")
             .fetch_one(&self.pool)
             .await?;
 
         // Get reading status counts
         let want_to_read: i64 = sqlx::query_scalar(
-            "SELECT COUNT(*) FROM reading_status WHERE status = 'WantToRead'"
+            "SELECT COUNT(*) FROM reading_status rs JOIN books b ON b.id = rs.book_id WHERE rs.status = 'WantToRead' AND b.deleted_at IS NULL"
         )
         .fetch_one(&self.pool)
         .await?;
 
         let currently_reading: i64 = sqlx::query_scalar(
-            "SELECT COUNT(*) FROM reading_status WHERE status = 'CurrentlyReading'"
+            "SELECT COUNT(*) FROM reading_status rs JOIN books b ON b.id = rs.book_id WHERE rs.status = 'CurrentlyReading' AND b.deleted_at IS NULL"
         )
         .fetch_one(&self.pool)
         .await?;
 
         let finished: i64 = sqlx::query_scalar(
-            "SELECT COUNT(*) FROM reading_status WHERE status = 'Finished'"
+            "SELECT COUNT(*) FROM reading_status rs JOIN books b ON b.id = rs.book_id WHERE rs.status = 'Finished' AND b.deleted_at IS NULL"
         )
         .fetch_one(&self.pool)
         .await?;
 
-        // Get other counts
-        let total_collections: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM collections")
+        let up_next: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM reading_queue")
             .fetch_one(&self.pool)
             .await?;
 
-        let total_authors: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM authors")
+        // Get other counts
+        let total_collections: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM collections")
             .fetch_one(&self.pool)
             .await?;
 
+        let total_authors: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(DISTINCT COALESCE(aa.author_name, b.author))
+            FROM books b LEFT JOIN author_aliases aa ON aa.alias = b.author
+            WHERE b.author IS NOT NULL AND b.author != '' AND b.deleted_at IS NULL
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
         let total_tags: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tags")
             .fetch_one(&self.pool)
             .await?;
@@ -222,7 +376,7 @@ impl LibraryService {
 
         // Get average rating
         let average_rating: Option<f64> = sqlx::query_scalar(
-            "SELECT AVG(rating) FROM books WHERE rating > 0"
+            "SELECT AVG(rating) FROM books WHERE rating > 0 AND deleted_at IS NULL"
         )
         .fetch_one(&self.pool)
         .await?;
@@ -233,11 +387,17 @@ impl LibraryService {
         // Get top authors
         let top_authors = self.get_top_authors().await?;
 
+        let total_reading_time: i64 = sqlx::query_scalar("SELECT COALESCE(SUM(duration_minutes), 0) FROM reading_sessions")
+            .fetch_one(&self.pool)
+            .await?;
+
         Ok(LibraryStats {
             total_books: total_books as u32,
             want_to_read: want_to_read as u32,
             currently_reading: currently_reading as u32,
             finished: finished as u32,
+            up_next: up_next as u32,
+            in_trash: in_trash as u32,
             total_collections: total_collections as u32,
             total_authors: total_authors as u32,
             total_tags: total_tags as u32,
@@ -245,7 +405,7 @@ impl LibraryService {
             reading_streak,
             books_read_this_month,
             books_read_this_year,
-            total_reading_time: 0, // TODO: Implement reading time tracking
+            total_reading_time: total_reading_time as u64,
             average_rating: average_rating.unwrap_or(0.0) as f32,
             favorite_genres,
             top_authors,
@@ -253,347 +413,1094 @@ impl LibraryService {
         })
     }
 
-    /// Calculate reading streak
-    async fn calculate_reading_streak(&self) -> Result<u32> {
-        let finished_books = sqlx::query(
-            "SELECT finished_at FROM reading_status WHERE status = 'Finished' AND finished_at IS NOT NULL ORDER BY finished_at DESC"
+    /// Add a book to the end of the reading queue
+    pub async fn enqueue_book(&self, book_id: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO reading_queue (book_id, position, added_at)
+            VALUES (?, (SELECT COALESCE(MAX(position), -1) + 1 FROM reading_queue), ?)
+            "#
         )
-        .fetch_all(&self.pool)
+        .bind(book_id)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
         .await?;
 
-        let mut streak = 0u32;
-        let mut current_date = Utc::now().date_naive();
-
-        for row in finished_books {
-            let finished_at_str: String = row.get(0);
-            if let Ok(finished_at) = DateTime::parse_from_rfc3339(&finished_at_str) {
-                let finished_date = finished_at.date_naive();
-                
-                if finished_date == current_date || finished_date == current_date - Duration::days(1) {
-                    streak += 1;
-                    current_date = finished_date - Duration::days(1);
-                } else {
-                    break;
-                }
-            }
-        }
-
-        Ok(streak)
+        Ok(())
     }
 
-    /// Get books read in a specific period
-    async fn get_books_read_this_period(&self, period: &str) -> Result<u32> {
-        let (start_date, _) = match period {
-            "month" => {
-                let now = Utc::now();
-                let start_of_month = now.date_naive().with_day(1).unwrap().and_hms_opt(0, 0, 0).unwrap();
-                (start_of_month, now.naive_utc())
-            }
-            "year" => {
-                let now = Utc::now();
-                let start_of_year = now.date_naive().with_ordinal(1).unwrap().and_hms_opt(0, 0, 0).unwrap();
-                (start_of_year, now.naive_utc())
-            }
-            _ => return Ok(0),
-        };
-
-        let count: i64 = sqlx::query_scalar(
-            "SELECT COUNT(*) FROM reading_status WHERE status = 'Finished' AND finished_at >= ?"
-        )
-        .bind(start_date.format("%Y-%m-%d %H:%M:%S").to_string())
-        .fetch_one(&self.pool)
-        .await?;
-
-        Ok(count as u32)
+    /// Move a queued book to a new zero-based position
+    pub async fn reorder_queue(&self, book_id: &str, new_position: usize) -> Result<()> {
+        let mut queue = self.get_reading_queue().await?;
+        let current = queue
+            .iter()
+            .position(|id| id == book_id)
+            .ok_or_else(|| anyhow::anyhow!("Book is not in the reading queue"))?;
+
+        let book_id = queue.remove(current);
+        queue.insert(new_position.min(queue.len()), book_id);
+        self.save_queue_order(&queue).await
     }
 
-    /// Get favorite genres
-    async fn get_favorite_genres(&self) -> Result<Vec<(String, u32)>> {
-        let rows = sqlx::query(
-            r#"
-            SELECT b.genre, COUNT(*) as count
-            FROM books b
-            WHERE b.genre IS NOT NULL AND b.genre != ''
-            GROUP BY b.genre
-            ORDER BY count DESC
-            LIMIT 10
-            "#
-        )
-        .fetch_all(&self.pool)
-        .await?;
-
-        let mut genres = Vec::new();
-        for row in rows {
-            let genre: String = row.get(0);
-            let count: i64 = row.get(1);
-            genres.push((genre, count as u32));
+    /// Remove and return the book at the front of the reading queue
+    pub async fn dequeue_next(&self) -> Result<Option<String>> {
+        let next = self.peek_next_in_queue().await?;
+        if let Some(book_id) = &next {
+            self.remove_from_queue(book_id).await?;
         }
+        Ok(next)
+    }
 
-        Ok(genres)
+    /// Take a finished book out of the reading queue and start the next one, returning its id
+    pub async fn advance_queue(&self, finished_book_id: &str) -> Result<Option<String>> {
+        self.remove_from_queue(finished_book_id).await?;
+        let next = self.dequeue_next().await?;
+        if let Some(next_book_id) = &next {
+            self.update_reading_status(next_book_id, ReadingStatus::CurrentlyReading).await?;
+        }
+        Ok(next)
     }
 
-    /// Get top authors
-    async fn get_top_authors(&self) -> Result<Vec<(String, u32)>> {
-        let rows = sqlx::query(
-            r#"
-            SELECT b.author, COUNT(*) as count
-            FROM books b
-            WHERE b.author IS NOT NULL AND b.author != ''
-            GROUP BY b.author
-            ORDER BY count DESC
-            LIMIT 10
-            "#
+    /// Get the book at the front of the reading queue
+    pub async fn peek_next_in_queue(&self) -> Result<Option<String>> {
+        let book_id: Option<String> = sqlx::query_scalar(
+            "SELECT book_id FROM reading_queue ORDER BY position LIMIT 1"
         )
-        .fetch_all(&self.pool)
+        .fetch_optional(&self.pool)
         .await?;
 
-        let mut authors = Vec::new();
-        for row in rows {
-            let author: String = row.get(0);
-            let count: i64 = row.get(1);
-            authors.push((author, count as u32));
-        }
-
-        Ok(authors)
+        Ok(book_id)
     }
 
-    /// Evaluate smart collection rules
-    pub async fn evaluate_smart_collection(&self, collection_id: &str) -> Result<Vec<String>> {
-        let collection = self.get_collection(collection_id).await?;
-        
-        if let Some(collection) = collection {
-            if let Some(rules) = &collection.smart_rules {
-                return self.evaluate_smart_rules(rules).await;
-            }
-        }
-        
-        Ok(Vec::new())
-    }
+    /// Remove a book from the reading queue
+    pub async fn remove_from_queue(&self, book_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM reading_queue WHERE book_id = ?")
+            .bind(book_id)
+            .execute(&self.pool)
+            .await?;
 
-    /// Evaluate smart rules against all books
-    async fn evaluate_smart_rules(&self, rules: &SmartCollectionRules) -> Result<Vec<String>> {
-        if rules.rules.is_empty() {
-            return Ok(Vec::new());
-        }
+        Ok(())
+    }
 
-        let all_books = sqlx::query("SELECT * FROM books")
+    /// Get queued book ids in reading order
+    pub async fn get_reading_queue(&self) -> Result<Vec<String>> {
+        let rows = sqlx::query("SELECT book_id FROM reading_queue ORDER BY position")
             .fetch_all(&self.pool)
             .await?;
 
-        let mut matching_books = Vec::new();
-
-        for row in all_books {
-            let book_id: String = row.get("id");
-            let matches = self.evaluate_rules_for_book(&row, rules).await?;
+        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+    }
 
-            let book_matches = match rules.match_type {
-                MatchType::All => matches.iter().all(|&m| m),
-                MatchType::Any => matches.iter().any(|&m| m),
-            };
+    /// Rewrite queue positions to match the given order
+    async fn save_queue_order(&self, book_ids: &[String]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
 
-            if book_matches {
-                matching_books.push(book_id);
-            }
+        for (position, book_id) in book_ids.iter().enumerate() {
+            sqlx::query("UPDATE reading_queue SET position = ? WHERE book_id = ?")
+                .bind(position as i64)
+                .bind(book_id)
+                .execute(&mut *tx)
+                .await?;
         }
 
-        Ok(matching_books)
+        tx.commit().await?;
+        Ok(())
     }
 
-    /// Evaluate rules for a specific book
-    async fn evaluate_rules_for_book(&self, book_row: &SqliteRow, rules: &SmartCollectionRules) -> Result<Vec<bool>> {
-        let mut results = Vec::new();
-
-        for rule in &rules.rules {
-            let matches = self.evaluate_single_rule(book_row, rule).await?;
-            results.push(matches);
+    /// Apply the same metadata changes to many books in one transaction
+    ///
+    /// Nothing is changed if any of the books is missing. Finished books leave
+    /// the reading queue, but the next queued book is not started.
+    pub async fn bulk_update_books(&self, book_ids: &[String], changes: &BulkBookChanges) -> Result<usize> {
+        let now = Utc::now().to_rfc3339();
+        let mut tx = self.pool.begin().await?;
+
+        let mut tag_ids = Vec::new();
+        for name in changes.add_tags.iter().map(|name| name.trim()).filter(|name| !name.is_empty()) {
+            sqlx::query("INSERT OR IGNORE INTO tags (id, name, created_at) VALUES (?, ?, ?)")
+                .bind(Uuid::new_v4().to_string())
+                .bind(name)
+                .bind(&now)
+                .execute(&mut *tx)
+                .await?;
+            let tag_id: String = sqlx::query_scalar("SELECT id FROM tags WHERE name = ?")
+                .bind(name)
+                .fetch_one(&mut *tx)
+                .await?;
+            tag_ids.push((name.to_string(), tag_id));
         }
 
-        Ok(results)
-    }
+        for book_id in book_ids {
+            let tags_json: Option<Option<String>> = sqlx::query_scalar("SELECT tags FROM books WHERE id = ?")
+                .bind(book_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            let Some(tags_json) = tags_json else {
+                return Err(anyhow::anyhow!("Book not found: {}", book_id));
+            };
 
-    /// Evaluate a single rule
-    async fn evaluate_single_rule(&self, book_row: &SqliteRow, rule: &SmartRule) -> Result<bool> {
-        let field_value = self.get_field_value(book_row, &rule.field).await?;
-        
-        match rule.operator {
-            SmartRuleOperator::Equals => Ok(field_value == rule.value),
-            SmartRuleOperator::NotEquals => Ok(field_value != rule.value),
-            SmartRuleOperator::Contains => Ok(field_value.to_lowercase().contains(&rule.value.to_lowercase())),
-            SmartRuleOperator::NotContains => Ok(!field_value.to_lowercase().contains(&rule.value.to_lowercase())),
-            SmartRuleOperator::StartsWith => Ok(field_value.to_lowercase().starts_with(&rule.value.to_lowercase())),
-            SmartRuleOperator::EndsWith => Ok(field_value.to_lowercase().ends_with(&rule.value.to_lowercase())),
-            SmartRuleOperator::IsEmpty => Ok(field_value.is_empty()),
-            SmartRuleOperator::IsNotEmpty => Ok(!field_value.is_empty()),
-            SmartRuleOperator::GreaterThan => {
-                if let (Ok(field_num), Ok(rule_num)) = (field_value.parse::<f64>(), rule.value.parse::<f64>()) {
-                    Ok(field_num > rule_num)
-                } else {
-                    Ok(false)
-                }
+            if let Some(author) = &changes.author {
+                sqlx::query("UPDATE books SET author = ? WHERE id = ?")
+                    .bind(author.trim())
+                    .bind(book_id)
+                    .execute(&mut *tx)
+                    .await?;
             }
-            SmartRuleOperator::LessThan => {
-                if let (Ok(field_num), Ok(rule_num)) = (field_value.parse::<f64>(), rule.value.parse::<f64>()) {
-                    Ok(field_num < rule_num)
-                } else {
-                    Ok(false)
-                }
+            if let Some(genre) = Self::clearable(&changes.genre) {
+                sqlx::query("UPDATE books SET genre = ? WHERE id = ?")
+                    .bind(genre)
+                    .bind(book_id)
+                    .execute(&mut *tx)
+                    .await?;
             }
-            SmartRuleOperator::GreaterThanOrEqual => {
-                if let (Ok(field_num), Ok(rule_num)) = (field_value.parse::<f64>(), rule.value.parse::<f64>()) {
-                    Ok(field_num >= rule_num)
-                } else {
-                    Ok(false)
-                }
+            if let Some(language) = Self::clearable(&changes.language) {
+                sqlx::query("UPDATE books SET language = ? WHERE id = ?")
+                    .bind(language)
+                    .bind(book_id)
+                    .execute(&mut *tx)
+                    .await?;
             }
-            SmartRuleOperator::LessThanOrEqual => {
-                if let (Ok(field_num), Ok(rule_num)) = (field_value.parse::<f64>(), rule.value.parse::<f64>()) {
-                    Ok(field_num <= rule_num)
-                } else {
-                    Ok(false)
+            match Self::clearable(&changes.series) {
+                Some(Some(series)) => {
+                    sqlx::query("UPDATE books SET series = ? WHERE id = ?")
+                        .bind(series)
+                        .bind(book_id)
+                        .execute(&mut *tx)
+                        .await?;
                 }
-            }
-            SmartRuleOperator::Before => {
-                if let (Ok(field_date), Ok(rule_date)) = (
-                    DateTime::parse_from_rfc3339(&field_value),
-                    DateTime::parse_from_rfc3339(&rule.value)
-                ) {
-                    Ok(field_date < rule_date)
-                } else {
-                    Ok(false)
+                Some(None) => {
+                    sqlx::query("UPDATE books SET series = NULL, series_index = NULL WHERE id = ?")
+                        .bind(book_id)
+                        .execute(&mut *tx)
+                        .await?;
                 }
+                None => {}
             }
-            SmartRuleOperator::After => {
-                if let (Ok(field_date), Ok(rule_date)) = (
-                    DateTime::parse_from_rfc3339(&field_value),
-                    DateTime::parse_from_rfc3339(&rule.value)
-                ) {
-                    Ok(field_date > rule_date)
-                } else {
-                    Ok(false)
+
+            if !tag_ids.is_empty() || !changes.remove_tags.is_empty() {
+                for (_, tag_id) in &tag_ids {
+                    sqlx::query("INSERT OR IGNORE INTO book_tags (book_id, tag_id, added_at) VALUES (?, ?, ?)")
+                        .bind(book_id)
+                        .bind(tag_id)
+                        .bind(&now)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                for name in &changes.remove_tags {
+                    sqlx::query("DELETE FROM book_tags WHERE book_id = ? AND tag_id IN (SELECT id FROM tags WHERE name = ?)")
+                        .bind(book_id)
+                        .bind(name.trim())
+                        .execute(&mut *tx)
+                        .await?;
                 }
+
+                // Keep the tag list stored with the book in step
+                let mut tags: Vec<String> = tags_json
+                    .and_then(|json| serde_json::from_str(&json).ok())
+                    .unwrap_or_default();
+                tags.retain(|tag| !changes.remove_tags.iter().any(|removed| removed.trim() == tag));
+                for (name, _) in &tag_ids {
+                    if !tags.contains(name) {
+                        tags.push(name.clone());
+                    }
+                }
+                sqlx::query("UPDATE books SET tags = ? WHERE id = ?")
+                    .bind(serde_json::to_string(&tags)?)
+                    .bind(book_id)
+                    .execute(&mut *tx)
+                    .await?;
             }
-            SmartRuleOperator::InLast => {
-                if let (Ok(field_date), Ok(days)) = (
-                    DateTime::parse_from_rfc3339(&field_value),
-                    rule.value.parse::<i64>()
-                ) {
-                    let cutoff_date = Utc::now() - Duration::days(days);
-                    Ok(field_date.with_timezone(&Utc) >= cutoff_date)
-                } else {
-                    Ok(false)
+
+            if let Some(status) = &changes.reading_status {
+                sqlx::query("UPDATE books SET reading_status = ? WHERE id = ?")
+                    .bind(status.to_string())
+                    .bind(book_id)
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query(
+                    r#"
+                    INSERT OR REPLACE INTO reading_status (book_id, status, started_at, finished_at, progress)
+                    VALUES (?, ?,
+                        CASE WHEN ? THEN ? ELSE (SELECT started_at FROM reading_status WHERE book_id = ?) END,
+                        CASE WHEN ? THEN ? ELSE NULL END,
+                        CASE WHEN ? THEN 1.0 ELSE (SELECT COALESCE(progress, 0.0) FROM reading_status WHERE book_id = ?) END
+                    )
+                    "#
+                )
+                .bind(book_id)
+                .bind(Self::stored_status(status))
+                .bind(*status == ReadingStatus::CurrentlyReading)
+                .bind(&now)
+                .bind(book_id)
+                .bind(*status == ReadingStatus::Finished)
+                .bind(&now)
+                .bind(*status == ReadingStatus::Finished)
+                .bind(book_id)
+                .execute(&mut *tx)
+                .await?;
+
+                if *status == ReadingStatus::Finished {
+                    sqlx::query("DELETE FROM reading_queue WHERE book_id = ?")
+                        .bind(book_id)
+                        .execute(&mut *tx)
+                        .await?;
                 }
             }
         }
+
+        tx.commit().await?;
+        Ok(book_ids.len())
     }
 
-    /// Get field value from book row
-    async fn get_field_value(&self, book_row: &SqliteRow, field: &SmartRuleField) -> Result<String> {
-        match field {
-            SmartRuleField::Title => Ok(book_row.get::<Option<String>, _>("title").unwrap_or_default()),
-            SmartRuleField::Author => Ok(book_row.get::<Option<String>, _>("author").unwrap_or_default()),
-            SmartRuleField::Genre => Ok(book_row.get::<Option<String>, _>("genre").unwrap_or_default()),
-            SmartRuleField::Publisher => Ok(book_row.get::<Option<String>, _>("publisher").unwrap_or_default()),
-            SmartRuleField::Language => Ok(book_row.get::<Option<String>, _>("language").unwrap_or_default()),
-            SmartRuleField::PublishDate => Ok(book_row.get::<Option<String>, _>("publish_date").unwrap_or_default()),
-            SmartRuleField::AddedDate => Ok(book_row.get::<String, _>("added_date")),
-            SmartRuleField::Rating => Ok(book_row.get::<Option<f64>, _>("rating").unwrap_or(0.0).to_string()),
-            SmartRuleField::FileSize => Ok(book_row.get::<Option<i64>, _>("file_size").unwrap_or(0).to_string()),
-            SmartRuleField::PageCount => Ok(book_row.get::<Option<i64>, _>("page_count").unwrap_or(0).to_string()),
-            SmartRuleField::ReadingStatus => {
-                let book_id: String = book_row.get("id");
-                let status = self.get_reading_status(&book_id).await?;
-                Ok(status.map(|s| s.to_display_name()).unwrap_or_default())
-            }
-            SmartRuleField::Tags => {
-                let book_id: String = book_row.get("id");
-                let tags = self.get_book_tags(&book_id).await?;
-                Ok(tags.join(", "))
-            }
-            SmartRuleField::Progress => {
-                let book_id: String = book_row.get("id");
-                let progress = self.get_book_progress(&book_id).await?;
-                Ok(progress.to_string())
+    /// A change to a clearable field: `Some(None)` clears it
+    fn clearable(value: &Option<String>) -> Option<Option<&str>> {
+        value.as_deref().map(|value| Some(value.trim()).filter(|value| !value.is_empty()))
+    }
+
+    /// Status name as stored in the reading_status table
+    fn stored_status(status: &ReadingStatus) -> &'static str {
+        match status {
+            ReadingStatus::Unread => "Unread",
+            ReadingStatus::WantToRead => "WantToRead",
+            ReadingStatus::CurrentlyReading => "CurrentlyReading",
+            ReadingStatus::Finished => "Finished",
+            ReadingStatus::OnHold => "OnHold",
+            ReadingStatus::DNF => "DNF",
+            ReadingStatus::Reference => "Reference",
+        }
+    }
+
+    /// Record other spellings of an author's name, so their books count as one author
+    ///
+    /// Book metadata keeps the names as written. Names that were already merged
+    /// into one of the aliases move to the new name too.
+    pub async fn merge_authors(&self, author_name: &str, aliases: &[String]) -> Result<()> {
+        let author_name = author_name.trim();
+        if author_name.is_empty() {
+            return Err(anyhow::anyhow!("Author name cannot be empty"));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        // The merged name is the one shown, not an alias
+        sqlx::query("DELETE FROM author_aliases WHERE alias = ?")
+            .bind(author_name)
+            .execute(&mut *tx)
+            .await?;
+
+        for alias in aliases.iter().map(|alias| alias.trim()).filter(|alias| !alias.is_empty() && *alias != author_name) {
+            sqlx::query("UPDATE author_aliases SET author_name = ? WHERE author_name = ?")
+                .bind(author_name)
+                .bind(alias)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("INSERT OR REPLACE INTO author_aliases (alias, author_name) VALUES (?, ?)")
+                .bind(alias)
+                .bind(author_name)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Show books written under an alias as their own author again
+    pub async fn remove_author_alias(&self, alias: &str) -> Result<()> {
+        sqlx::query("DELETE FROM author_aliases WHERE alias = ?")
+            .bind(alias)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Find author names in the library that look like spellings of the same name
+    ///
+    /// "Tolkien, J. R. R." and "J.R.R. Tolkien" match. Names already merged are
+    /// left out, and each group lists the most used spelling first.
+    pub async fn find_duplicate_authors(&self) -> Result<Vec<Vec<String>>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT b.author, COUNT(*) as count
+            FROM books b
+            LEFT JOIN author_aliases aa ON aa.alias = b.author
+            WHERE b.author IS NOT NULL AND b.author != '' AND aa.alias IS NULL AND b.deleted_at IS NULL
+            GROUP BY b.author
+            ORDER BY count DESC, b.author
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+        for row in rows {
+            let name: String = row.get(0);
+            let key = Self::author_match_key(&name);
+            match groups.iter_mut().find(|(group_key, _)| *group_key == key) {
+                Some((_, names)) => names.push(name),
+                None => groups.push((key, vec![name])),
+            }
+        }
+
+        Ok(groups.into_iter()
+            .map(|(_, names)| names)
+            .filter(|names| names.len() > 1)
+            .collect())
+    }
+
+    /// Normalize an author's name for matching spellings
+    ///
+    /// "Last, First" is flipped, case and punctuation are dropped, and runs of
+    /// initials are joined, so "Tolkien, J. R. R." becomes "jrr tolkien".
+    pub(crate) fn author_match_key(name: &str) -> String {
+        let name = match name.split_once(',') {
+            Some((last, first)) if !first.contains(',') => format!("{} {}", first, last),
+            _ => name.to_string(),
+        };
+
+        let mut words: Vec<String> = Vec::new();
+        let mut initials = String::new();
+        for word in name.split(|c: char| c.is_whitespace() || c == '.').filter(|word| !word.is_empty()) {
+            let word: String = word.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect();
+            if word.chars().count() == 1 {
+                initials.push_str(&word);
+                continue;
+            }
+            if !initials.is_empty() {
+                words.push(std::mem::take(&mut initials));
+            }
+            if !word.is_empty() {
+                words.push(word);
+            }
+        }
+        if !initials.is_empty() {
+            words.push(initials);
+        }
+
+        words.join(" ")
+    }
+
+    /// Move a collection under another one, or to the top level with `None`
+    pub async fn move_collection(&self, collection_id: &str, new_parent_id: Option<&str>) -> Result<()> {
+        if self.get_parent_id(collection_id).await?.is_none() {
+            return Err(anyhow::anyhow!("Collection not found"));
+        }
+
+        // Walk up from the new parent; meeting the collection itself would make a cycle
+        let mut ancestor = new_parent_id.map(str::to_string);
+        while let Some(ancestor_id) = ancestor {
+            if ancestor_id == collection_id {
+                return Err(anyhow::anyhow!("A collection cannot be moved inside itself"));
+            }
+            ancestor = self.get_parent_id(&ancestor_id).await?
+                .ok_or_else(|| anyhow::anyhow!("Parent collection not found"))?;
+        }
+
+        sqlx::query("UPDATE collections SET parent_id = ?, updated_at = ? WHERE id = ?")
+            .bind(new_parent_id)
+            .bind(Utc::now().to_rfc3339())
+            .bind(collection_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the collections directly inside a collection, or the top-level ones for `None`
+    pub async fn get_child_collections(&self, parent_id: Option<&str>) -> Result<Vec<Collection>> {
+        let rows = sqlx::query("SELECT * FROM collections WHERE parent_id IS ? ORDER BY sort_order, name")
+            .bind(parent_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut collections = Vec::new();
+        for row in rows {
+            let mut collection = self.row_to_collection(row)?;
+            self.load_collection_books(&mut collection).await?;
+            collections.push(collection);
+        }
+
+        Ok(collections)
+    }
+
+    /// Get the collections from the top level down to a collection, e.g. Fiction, Sci-Fi, Cyberpunk
+    pub async fn get_collection_path(&self, collection_id: &str) -> Result<Vec<Collection>> {
+        let mut path = Vec::new();
+        let mut current = Some(collection_id.to_string());
+        while let Some(id) = current {
+            let collection = self.get_collection(&id).await?
+                .ok_or_else(|| anyhow::anyhow!("Collection not found"))?;
+            current = collection.parent_id.clone();
+            path.push(collection);
+        }
+
+        path.reverse();
+        Ok(path)
+    }
+
+    /// Get the books of a collection and of every collection nested inside it
+    pub async fn get_collection_books_recursive(&self, collection_id: &str) -> Result<Vec<String>> {
+        let root = self.get_collection(collection_id).await?
+            .ok_or_else(|| anyhow::anyhow!("Collection not found"))?;
+
+        let mut book_ids = Vec::new();
+        let mut pending = vec![root];
+        while let Some(collection) = pending.pop() {
+            for book_id in collection.book_ids {
+                if !book_ids.contains(&book_id) {
+                    book_ids.push(book_id);
+                }
+            }
+            // Reverse so children are visited in display order
+            let mut children = self.get_child_collections(Some(&collection.id)).await?;
+            children.reverse();
+            pending.extend(children);
+        }
+
+        Ok(book_ids)
+    }
+
+    /// The parent of a collection: `None` if it doesn't exist, `Some(None)` at the top level
+    async fn get_parent_id(&self, collection_id: &str) -> Result<Option<Option<String>>> {
+        let parent_id = sqlx::query_scalar("SELECT parent_id FROM collections WHERE id = ?")
+            .bind(collection_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(parent_id)
+    }
+
+    /// Subscribe to new matches for a smart collection
+    pub async fn subscribe_to_collection(&self, collection_id: &str) -> Result<()> {
+        let collection = self.get_collection(collection_id).await?
+            .ok_or_else(|| anyhow::anyhow!("Collection not found"))?;
+        if !collection.is_smart {
+            return Err(anyhow::anyhow!("Only smart collections can be subscribed to"));
+        }
+
+        sqlx::query("INSERT OR IGNORE INTO collection_subscriptions (collection_id, created_at) VALUES (?, ?)")
+            .bind(collection_id)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Stop watching a smart collection and drop its new matches
+    pub async fn unsubscribe_from_collection(&self, collection_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM collection_subscriptions WHERE collection_id = ?")
+            .bind(collection_id)
+            .execute(&self.pool)
+            .await?;
+
+        self.clear_new_matches(collection_id).await
+    }
+
+    /// Get ids of subscribed collections
+    pub async fn get_collection_subscriptions(&self) -> Result<Vec<String>> {
+        let rows = sqlx::query("SELECT collection_id FROM collection_subscriptions ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+    }
+
+    /// Receive notifications for books matching subscribed collections
+    pub fn subscribe_match_notifications(&self) -> broadcast::Receiver<CollectionMatchNotification> {
+        self.match_notifications.subscribe()
+    }
+
+    /// Check a newly imported book against subscribed smart collections
+    ///
+    /// Matches are added to each collection's new matches list and broadcast to
+    /// notification receivers.
+    pub async fn notify_book_imported(&self, book_id: &str) -> Result<Vec<CollectionMatchNotification>> {
+        let Some(book_row) = sqlx::query("SELECT * FROM books WHERE id = ?")
+            .bind(book_id)
+            .fetch_optional(&self.pool)
+            .await?
+        else {
+            return Ok(Vec::new());
+        };
+
+        let mut notifications = Vec::new();
+        for collection_id in self.get_collection_subscriptions().await? {
+            let Some(collection) = self.get_collection(&collection_id).await? else {
+                continue;
+            };
+            let Some(rules) = &collection.smart_rules else {
+                continue;
+            };
+            if rules.rules.is_empty() || !self.book_matches_rules(book_id, rules).await? {
+                continue;
+            }
+
+            sqlx::query("INSERT OR IGNORE INTO collection_new_matches (collection_id, book_id, matched_at) VALUES (?, ?, ?)")
+                .bind(&collection.id)
+                .bind(book_id)
+                .bind(Utc::now().to_rfc3339())
+                .execute(&self.pool)
+                .await?;
+
+            let notification = CollectionMatchNotification {
+                collection_id: collection.id.clone(),
+                collection_name: collection.name.clone(),
+                book_id: book_id.to_string(),
+                book_title: book_row.get("title"),
+            };
+            // Sending only fails when nobody is listening
+            let _ = self.match_notifications.send(notification.clone());
+            notifications.push(notification);
+        }
+
+        Ok(notifications)
+    }
+
+    /// Get books that newly matched a subscribed collection, most recent first
+    pub async fn get_new_matches(&self, collection_id: &str) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            "SELECT book_id FROM collection_new_matches WHERE collection_id = ? ORDER BY matched_at DESC"
+        )
+        .bind(collection_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+    }
+
+    /// Clear the new matches list for a collection, e.g. once the user has seen it
+    pub async fn clear_new_matches(&self, collection_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM collection_new_matches WHERE collection_id = ?")
+            .bind(collection_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Calculate reading streak
+    async fn calculate_reading_streak(&self) -> Result<u32> {
+        let finished_books = sqlx::query(
+            "SELECT finished_at FROM reading_status WHERE status = 'Finished' AND finished_at IS NOT NULL ORDER BY finished_at DESC"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut streak = 0u32;
+        let mut current_date = Utc::now().date_naive();
+
+        for row in finished_books {
+            let finished_at_str: String = row.get(0);
+            if let Ok(finished_at) = DateTime::parse_from_rfc3339(&finished_at_str) {
+                let finished_date = finished_at.date_naive();
+                
+                if finished_date == current_date || finished_date == current_date - Duration::days(1) {
+                    streak += 1;
+                    current_date = finished_date - Duration::days(1);
+                } else {
+                    break;
+                }
+            }
+        }
+
+        Ok(streak)
+    }
+
+    /// Get books read in a specific period
+    async fn get_books_read_this_period(&self, period: &str) -> Result<u32> {
+        let (start_date, _) = match period {
+            "month" => {
+                let now = Utc::now();
+                let start_of_month = now.date_naive().with_day(1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+                (start_of_month, now.naive_utc())
+            }
+            "year" => {
+                let now = Utc::now();
+                let start_of_year = now.date_naive().with_ordinal(1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+                (start_of_year, now.naive_utc())
+            }
+            _ => return Ok(0),
+        };
+
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM reading_status WHERE status = 'Finished' AND finished_at >= ?"
+        )
+        .bind(start_date.format("%Y-%m-%d %H:%M:%S").to_string())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count as u32)
+    }
+
+    /// Get favorite genres
+    async fn get_favorite_genres(&self) -> Result<Vec<(String, u32)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT b.genre, COUNT(*) as count
+            FROM books b
+            WHERE b.genre IS NOT NULL AND b.genre != '' AND b.deleted_at IS NULL
+            GROUP BY b.genre
+            ORDER BY count DESC
+            LIMIT 10
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut genres = Vec::new();
+        for row in rows {
+            let genre: String = row.get(0);
+            let count: i64 = row.get(1);
+            genres.push((genre, count as u32));
+        }
+
+        Ok(genres)
+    }
+
+    /// Get top authors
+    async fn get_top_authors(&self) -> Result<Vec<(String, u32)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT COALESCE(aa.author_name, b.author) as name, COUNT(*) as count
+            FROM books b
+            LEFT JOIN author_aliases aa ON aa.alias = b.author
+            WHERE b.author IS NOT NULL AND b.author != '' AND b.deleted_at IS NULL
+            GROUP BY name
+            ORDER BY count DESC
+            LIMIT 10
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut authors = Vec::new();
+        for row in rows {
+            let author: String = row.get(0);
+            let count: i64 = row.get(1);
+            authors.push((author, count as u32));
+        }
+
+        Ok(authors)
+    }
+
+    /// Evaluate smart collection rules
+    pub async fn evaluate_smart_collection(&self, collection_id: &str) -> Result<Vec<String>> {
+        let collection = self.get_collection(collection_id).await?;
+        
+        if let Some(collection) = collection {
+            if let Some(rules) = &collection.smart_rules {
+                return self.evaluate_smart_rules(rules).await;
+            }
+        }
+        
+        Ok(Vec::new())
+    }
+
+    /// Evaluate smart rules against all books
+    ///
+    /// The rules run as one SQL query and the result is cached until a book, its
+    /// tags or its reading status change. Rules on annotation counts are always
+    /// evaluated afresh, as annotations live outside the library tables, and so
+    /// are "in the last N days" rules, whose matches change with the date.
+    pub async fn evaluate_smart_rules(&self, rules: &SmartCollectionRules) -> Result<Vec<String>> {
+        if rules.rules.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let cacheable = !rules.rules.iter()
+            .any(|rule| rule.field == SmartRuleField::AnnotationCount || rule.operator == SmartRuleOperator::InLast);
+        let key = serde_json::to_string(rules)?;
+        let version = self.library_version().await?;
+        if cacheable {
+            if let Some((cached_version, book_ids)) = self.smart_cache.read().await.get(&key) {
+                if *cached_version == version {
+                    return Ok(book_ids.clone());
+                }
+            }
+        }
+
+        let compiled = Self::compile_rules(rules);
+        let sql = format!(
+            "SELECT b.id FROM books b LEFT JOIN reading_status rs ON rs.book_id = b.id WHERE b.deleted_at IS NULL AND ({})",
+            compiled.condition
+        );
+        let book_ids = Self::bind_rule_params(sqlx::query_scalar(&sql), compiled.params)
+            .fetch_all(&self.pool)
+            .await?;
+
+        if cacheable {
+            let mut cache = self.smart_cache.write().await;
+            cache.retain(|_, (cached_version, _)| *cached_version == version);
+            cache.insert(key, (version, book_ids.clone()));
+        }
+        Ok(book_ids)
+    }
+
+    /// Check whether a book satisfies a smart collection's rules
+    async fn book_matches_rules(&self, book_id: &str, rules: &SmartCollectionRules) -> Result<bool> {
+        let compiled = Self::compile_rules(rules);
+        let sql = format!(
+            "SELECT COUNT(*) FROM books b LEFT JOIN reading_status rs ON rs.book_id = b.id WHERE b.id = ? AND ({})",
+            compiled.condition
+        );
+        let count: i64 = Self::bind_rule_params(sqlx::query_scalar(&sql).bind(book_id), compiled.params)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    /// Counter bumped by triggers whenever book data read by smart rules changes
+    async fn library_version(&self) -> Result<i64> {
+        let version = sqlx::query_scalar("SELECT version FROM library_changes WHERE id = 1")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(version)
+    }
+
+    fn bind_rule_params<'q, O>(
+        mut query: QueryScalar<'q, Sqlite, O, SqliteArguments<'q>>,
+        params: Vec<RuleParam>,
+    ) -> QueryScalar<'q, Sqlite, O, SqliteArguments<'q>> {
+        for param in params {
+            query = match param {
+                RuleParam::Text(text) => query.bind(text),
+                RuleParam::Number(number) => query.bind(number),
+            };
+        }
+        query
+    }
+
+    /// Compile smart rules to a condition on `books b` joined with `reading_status rs`
+    ///
+    /// Text matching ignores case for ASCII letters only, like SQLite's LOWER.
+    fn compile_rules(rules: &SmartCollectionRules) -> CompiledRules {
+        let mut params = Vec::new();
+        let conditions: Vec<String> = rules.rules.iter()
+            // A comparison with NULL, such as a date that doesn't parse, is a non-match
+            .map(|rule| format!("COALESCE(({}), 0)", Self::compile_rule(rule, &mut params)))
+            .collect();
+        let separator = match rules.match_type {
+            MatchType::All => " AND ",
+            MatchType::Any => " OR ",
+        };
+
+        CompiledRules { condition: conditions.join(separator), params }
+    }
+
+    /// Compile one rule, pushing the values it binds
+    fn compile_rule(rule: &SmartRule, params: &mut Vec<RuleParam>) -> String {
+        let numeric = matches!(
+            rule.field,
+            SmartRuleField::Rating | SmartRuleField::FileSize | SmartRuleField::PageCount |
+            SmartRuleField::Progress | SmartRuleField::WordCount | SmartRuleField::ReadingTime |
+            SmartRuleField::AnnotationCount
+        );
+        let field = Self::field_sql(&rule.field);
+        let number = rule.value.trim().parse::<f64>().ok();
+
+        match rule.operator {
+            SmartRuleOperator::Equals | SmartRuleOperator::NotEquals => {
+                let operator = if rule.operator == SmartRuleOperator::Equals { "=" } else { "IS NOT" };
+                match (numeric, number) {
+                    (false, _) => params.push(RuleParam::Text(rule.value.clone())),
+                    (true, Some(number)) => params.push(RuleParam::Number(number)),
+                    // A number never equals text that isn't one
+                    (true, None) => return if operator == "=" { "0" } else { "1" }.to_string(),
+                }
+                format!("{} {} ?", field, operator)
+            }
+            SmartRuleOperator::Contains | SmartRuleOperator::NotContains |
+            SmartRuleOperator::StartsWith | SmartRuleOperator::EndsWith => {
+                let value = Self::escape_like(&rule.value.to_lowercase());
+                let pattern = match rule.operator {
+                    SmartRuleOperator::StartsWith => format!("{}%", value),
+                    SmartRuleOperator::EndsWith => format!("%{}", value),
+                    _ => format!("%{}%", value),
+                };
+                params.push(RuleParam::Text(pattern));
+
+                let text = if numeric { format!("COALESCE(CAST({} AS TEXT), '')", field) } else { field };
+                let negate = if rule.operator == SmartRuleOperator::NotContains { "NOT " } else { "" };
+                format!("LOWER({}) {}LIKE ? ESCAPE '\\'", text, negate)
+            }
+            SmartRuleOperator::IsEmpty if numeric => format!("{} IS NULL", field),
+            SmartRuleOperator::IsEmpty => format!("{} = ''", field),
+            SmartRuleOperator::IsNotEmpty if numeric => format!("{} IS NOT NULL", field),
+            SmartRuleOperator::IsNotEmpty => format!("{} != ''", field),
+            SmartRuleOperator::GreaterThan | SmartRuleOperator::LessThan |
+            SmartRuleOperator::GreaterThanOrEqual | SmartRuleOperator::LessThanOrEqual => {
+                let operator = match rule.operator {
+                    SmartRuleOperator::GreaterThan => ">",
+                    SmartRuleOperator::LessThan => "<",
+                    SmartRuleOperator::GreaterThanOrEqual => ">=",
+                    _ => "<=",
+                };
+                match number {
+                    Some(number) if numeric => {
+                        params.push(RuleParam::Number(number));
+                        format!("{} {} ?", field, operator)
+                    }
+                    _ => "0".to_string(),
+                }
+            }
+            SmartRuleOperator::Before | SmartRuleOperator::After => {
+                let Ok(date) = DateTime::parse_from_rfc3339(rule.value.trim()) else {
+                    return "0".to_string();
+                };
+                params.push(RuleParam::Text(date.to_rfc3339()));
+                let operator = if rule.operator == SmartRuleOperator::Before { "<" } else { ">" };
+                format!("julianday({}) {} julianday(?)", field, operator)
+            }
+            SmartRuleOperator::InLast => {
+                let Ok(days) = rule.value.trim().parse::<i64>() else {
+                    return "0".to_string();
+                };
+                params.push(RuleParam::Text((Utc::now() - Duration::days(days)).to_rfc3339()));
+                format!("julianday({}) >= julianday(?)", field)
+            }
+        }
+    }
+
+    /// SQL expression for a rule field; text fields are never NULL
+    fn field_sql(field: &SmartRuleField) -> String {
+        match field {
+            SmartRuleField::Title => "COALESCE(b.title, '')".to_string(),
+            SmartRuleField::Author => "COALESCE(b.author, '')".to_string(),
+            SmartRuleField::Genre => "COALESCE(b.genre, '')".to_string(),
+            SmartRuleField::Publisher => "COALESCE(b.publisher, '')".to_string(),
+            SmartRuleField::Language => "COALESCE(b.language, '')".to_string(),
+            SmartRuleField::PublishDate => "COALESCE(b.publication_date, '')".to_string(),
+            SmartRuleField::Subject => Self::json_list_sql("b.subjects"),
+            SmartRuleField::Contributor => Self::json_list_sql("b.contributors"),
+            SmartRuleField::Series => "COALESCE(b.series, '')".to_string(),
+            SmartRuleField::AddedDate => "COALESCE(b.added_date, '')".to_string(),
+            SmartRuleField::LastOpened => "COALESCE(b.last_opened, '')".to_string(),
+            SmartRuleField::Rating => "COALESCE(b.rating, 0)".to_string(),
+            SmartRuleField::FileSize => "COALESCE(b.file_size, 0)".to_string(),
+            SmartRuleField::PageCount => "COALESCE(b.page_count, 0)".to_string(),
+            // Books not yet counted have no word count rather than zero
+            SmartRuleField::WordCount => "b.word_count".to_string(),
+            SmartRuleField::ReadingTime => format!(
+                "CASE WHEN b.word_count > 0 THEN (b.word_count + {0} - 1) / {0} END",
+                DEFAULT_READING_SPEED_WPM
+            ),
+            SmartRuleField::AnnotationCount => "(SELECT COUNT(*) FROM annotations a WHERE a.book_id = b.id)".to_string(),
+            SmartRuleField::ReadingStatus => Self::reading_status_sql(),
+            SmartRuleField::Progress => "COALESCE(rs.progress, 0.0)".to_string(),
+            SmartRuleField::Tags => {
+                "COALESCE((SELECT group_concat(t.name, ', ') FROM tags t JOIN book_tags bt ON t.id = bt.tag_id \
+                 WHERE bt.book_id = b.id), '')".to_string()
+            }
+        }
+    }
+
+    /// Join a JSON list column for matching, e.g. subjects
+    fn json_list_sql(column: &str) -> String {
+        format!(
+            "CASE WHEN json_valid({0}) THEN COALESCE((SELECT group_concat(value, ', ') FROM json_each({0})), '') ELSE '' END",
+            column
+        )
+    }
+
+    /// Display name of a book's reading status, read as `get_reading_status` does
+    fn reading_status_sql() -> String {
+        let mut sql = "CASE WHEN rs.book_id IS NULL THEN ''".to_string();
+        for status in [ReadingStatus::CurrentlyReading, ReadingStatus::Finished, ReadingStatus::DNF, ReadingStatus::Reference] {
+            sql.push_str(&format!(" WHEN rs.status = '{}' THEN '{}'", Self::stored_status(&status), status.to_display_name()));
+        }
+        sql.push_str(&format!(" ELSE '{}' END", ReadingStatus::WantToRead.to_display_name()));
+        sql
+    }
+
+    /// Escape LIKE wildcards so a rule value matches literally
+    fn escape_like(value: &str) -> String {
+        value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+    }
+
+    /// Get book tags
+    async fn get_book_tags(&self, book_id: &str) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            "SELECT t.name FROM tags t JOIN book_tags bt ON t.id = bt.tag_id WHERE bt.book_id = ?"
+        )
+        .bind(book_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut tags = Vec::new();
+        for row in rows {
+            let tag: String = row.get(0);
+            tags.push(tag);
+        }
+
+        Ok(tags)
+    }
+
+    /// Convert database row to Collection
+    fn row_to_collection(&self, row: SqliteRow) -> Result<Collection> {
+        let smart_rules_json: Option<String> = row.get("smart_rules");
+        let smart_rules: Option<SmartCollectionRules> = if let Some(json) = smart_rules_json {
+            serde_json::from_str(&json).ok()
+        } else {
+            None
+        };
+
+        let created_at_str: String = row.get("created_at");
+        let updated_at_str: String = row.get("updated_at");
+
+        Ok(Collection {
+            id: row.get("id"),
+            name: row.get("name"),
+            description: row.get("description"),
+            icon: row.get("icon"),
+            color: row.get("color"),
+            created_at: DateTime::parse_from_rfc3339(&created_at_str)?.with_timezone(&Utc),
+            updated_at: DateTime::parse_from_rfc3339(&updated_at_str)?.with_timezone(&Utc),
+            book_ids: Vec::new(), // Will be populated separately
+            is_smart: row.get("is_smart"),
+            smart_rules,
+            is_favorite: row.get("is_favorite"),
+            sort_order: row.get::<i64, _>("sort_order") as u32,
+            parent_id: row.get("parent_id"),
+        })
+    }
+
+    /// Load book IDs for a collection
+    async fn load_collection_books(&self, collection: &mut Collection) -> Result<()> {
+        if collection.is_smart {
+            // For smart collections, evaluate rules; looking the collection up again would recurse
+            collection.book_ids = match &collection.smart_rules {
+                Some(rules) => self.evaluate_smart_rules(rules).await?,
+                None => Vec::new(),
+            };
+        } else {
+            // For regular collections, load from database
+            let rows = sqlx::query(
+                "SELECT book_id FROM collection_books WHERE collection_id = ? ORDER BY added_at"
+            )
+            .bind(&collection.id)
+            .fetch_all(&self.pool)
+            .await?;
+
+            collection.book_ids = rows.into_iter().map(|row| row.get(0)).collect();
+        }
+
+        Ok(())
+    }
+
+    /// Books in a category, trash included
+    async fn category_book_ids(&self, category: Category) -> Result<Vec<String>> {
+        match category {
+            Category::All => {
+                let rows = sqlx::query("SELECT id FROM books ORDER BY title")
+                    .fetch_all(&self.pool)
+                    .await?;
+                Ok(rows.into_iter().map(|row| row.get(0)).collect())
+            }
+            Category::WantToRead => {
+                let rows = sqlx::query(
+                    "SELECT book_id FROM reading_status WHERE status = 'WantToRead' ORDER BY book_id"
+                )
+                .fetch_all(&self.pool)
+                .await?;
+                Ok(rows.into_iter().map(|row| row.get(0)).collect())
+            }
+            Category::CurrentlyReading => {
+                let rows = sqlx::query(
+                    "SELECT book_id FROM reading_status WHERE status = 'CurrentlyReading' ORDER BY book_id"
+                )
+                .fetch_all(&self.pool)
+                .await?;
+                Ok(rows.into_iter().map(|row| row.get(0)).collect())
+            }
+            Category::Finished => {
+                let rows = sqlx::query(
+                    "SELECT book_id FROM reading_status WHERE status = 'Finished' ORDER BY finished_at DESC"
+                )
+                .fetch_all(&self.pool)
+                .await?;
+                Ok(rows.into_iter().map(|row| row.get(0)).collect())
+            }
+            Category::UpNext => self.get_reading_queue().await,
+            Category::Collection(collection_id) => {
+                if let Some(collection) = self.get_collection(&collection_id).await? {
+                    Ok(collection.book_ids)
+                } else {
+                    Ok(Vec::new())
+                }
+            }
+            Category::Author(author) => {
+                // Accepts an author's ID or name, and includes books under its aliases
+                let rows = sqlx::query(
+                    r#"
+                    SELECT b.id FROM books b
+                    LEFT JOIN author_aliases aa ON aa.alias = b.author
+                    WHERE COALESCE(aa.author_name, b.author) = COALESCE((SELECT name FROM authors WHERE id = ?), ?)
+                        AND b.deleted_at IS NULL
+                    ORDER BY b.title
+                    "#
+                )
+                .bind(&author)
+                .bind(&author)
+                .fetch_all(&self.pool)
+                .await?;
+                Ok(rows.into_iter().map(|row| row.get(0)).collect())
+            }
+            Category::Tag(tag_name) => {
+                let rows = sqlx::query(
+                    r#"
+                    SELECT b.id FROM books b
+                    JOIN book_tags bt ON b.id = bt.book_id
+                    JOIN tags t ON bt.tag_id = t.id
+                    WHERE t.name = ?
+                    ORDER BY b.title
+                    "#
+                )
+                .bind(&tag_name)
+                .fetch_all(&self.pool)
+                .await?;
+                Ok(rows.into_iter().map(|row| row.get(0)).collect())
+            }
+            Category::Genre(genre_name) => {
+                let rows = sqlx::query("SELECT id FROM books WHERE genre = ? ORDER BY title")
+                    .bind(&genre_name)
+                    .fetch_all(&self.pool)
+                    .await?;
+                Ok(rows.into_iter().map(|row| row.get(0)).collect())
+            }
+            Category::Publisher(publisher_name) => {
+                let rows = sqlx::query("SELECT id FROM books WHERE publisher = ? ORDER BY title")
+                    .bind(&publisher_name)
+                    .fetch_all(&self.pool)
+                    .await?;
+                Ok(rows.into_iter().map(|row| row.get(0)).collect())
+            }
+            Category::Year(year) => {
+                let rows = sqlx::query("SELECT id FROM books WHERE strftime('%Y', publish_date) = ? ORDER BY title")
+                    .bind(year.to_string())
+                    .fetch_all(&self.pool)
+                    .await?;
+                Ok(rows.into_iter().map(|row| row.get(0)).collect())
+            }
+            Category::Language(language) => {
+                let rows = sqlx::query("SELECT id FROM books WHERE language = ? ORDER BY title")
+                    .bind(&language)
+                    .fetch_all(&self.pool)
+                    .await?;
+                Ok(rows.into_iter().map(|row| row.get(0)).collect())
+            }
+            Category::Rating(rating) => {
+                let rows = sqlx::query("SELECT id FROM books WHERE rating = ? ORDER BY title")
+                    .bind(rating as f64)
+                    .fetch_all(&self.pool)
+                    .await?;
+                Ok(rows.into_iter().map(|row| row.get(0)).collect())
             }
+            Category::Trash => Ok(Vec::new()), // Listed by `get_books_by_category`
         }
     }
 
-    /// Get book tags
-    async fn get_book_tags(&self, book_id: &str) -> Result<Vec<String>> {
-        let rows = sqlx::query(
-            "SELECT t.name FROM tags t JOIN book_tags bt ON t.id = bt.tag_id WHERE bt.book_id = ?"
-        )
-        .bind(book_id)
-        .fetch_all(&self.pool)
-        .await?;
-
-        let mut tags = Vec::new();
-        for row in rows {
-            let tag: String = row.get(0);
-            tags.push(tag);
-        }
-
-        Ok(tags)
-    }
-
-    /// Get book progress
-    async fn get_book_progress(&self, book_id: &str) -> Result<f64> {
-        let progress: Option<f64> = sqlx::query_scalar(
-            "SELECT progress FROM reading_status WHERE book_id = ?"
-        )
-        .bind(book_id)
-        .fetch_optional(&self.pool)
-        .await?;
-
-        Ok(progress.unwrap_or(0.0))
-    }
-
-    /// Convert database row to Collection
-    fn row_to_collection(&self, row: SqliteRow) -> Result<Collection> {
-        let smart_rules_json: Option<String> = row.get("smart_rules");
-        let smart_rules: Option<SmartCollectionRules> = if let Some(json) = smart_rules_json {
-            serde_json::from_str(&json).ok()
-        } else {
-            None
-        };
-
-        let created_at_str: String = row.get("created_at");
-        let updated_at_str: String = row.get("updated_at");
-
-        Ok(Collection {
-            id: row.get("id"),
-            name: row.get("name"),
-            description: row.get("description"),
-            icon: row.get("icon"),
-            color: row.get("color"),
-            created_at: DateTime::parse_from_rfc3339(&created_at_str)?.with_timezone(&Utc),
-            updated_at: DateTime::parse_from_rfc3339(&updated_at_str)?.with_timezone(&Utc),
-            book_ids: Vec::new(), // Will be populated separately
-            is_smart: row.get("is_smart"),
-            smart_rules,
-            is_favorite: row.get("is_favorite"),
-            sort_order: row.get::<i64, _>("sort_order") as u32,
-        })
-    }
-
-    /// Load book IDs for a collection
-    async fn load_collection_books(&self, collection: &mut Collection) -> Result<()> {
-        if collection.is_smart {
-            // For smart collections, evaluate rules
-            collection.book_ids = self.evaluate_smart_collection(&collection.id).await?;
-        } else {
-            // For regular collections, load from database
-            let rows = sqlx::query(
-                "SELECT book_id FROM collection_books WHERE collection_id = ? ORDER BY added_at"
-            )
-            .bind(&collection.id)
-            .fetch_all(&self.pool)
-            .await?;
-
-            collection.book_ids = rows.into_iter().map(|row| row.get(0)).collect();
-        }
-
-        Ok(())
-    }
 }
 
 #[async_trait::async_trait]
@@ -603,8 +1510,8 @@ impl LibraryOrganizer for LibraryService {
         
         sqlx::query(
             r#"
-            INSERT INTO collections (id, name, description, icon, color, created_at, updated_at, is_smart, is_favorite, sort_order)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO collections (id, name, description, icon, color, created_at, updated_at, is_smart, is_favorite, sort_order, created_date)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#
         )
         .bind(&collection.id)
@@ -617,6 +1524,7 @@ impl LibraryOrganizer for LibraryService {
         .bind(collection.is_smart)
         .bind(collection.is_favorite)
         .bind(collection.sort_order as i64)
+        .bind(collection.created_at.to_rfc3339())
         .execute(&self.pool)
         .await?;
 
@@ -653,6 +1561,13 @@ impl LibraryOrganizer for LibraryService {
     }
 
     async fn delete_collection(&self, collection_id: &str) -> Result<()> {
+        // Nested collections move up to the deleted collection's parent
+        sqlx::query("UPDATE collections SET parent_id = (SELECT parent_id FROM collections WHERE id = ?) WHERE parent_id = ?")
+            .bind(collection_id)
+            .bind(collection_id)
+            .execute(&self.pool)
+            .await?;
+
         sqlx::query("DELETE FROM collections WHERE id = ?")
             .bind(collection_id)
             .execute(&self.pool)
@@ -716,102 +1631,22 @@ impl LibraryOrganizer for LibraryService {
     }
 
     async fn get_books_by_category(&self, category: Category) -> Result<Vec<String>> {
-        match category {
-            Category::All => {
-                let rows = sqlx::query("SELECT id FROM books ORDER BY title")
-                    .fetch_all(&self.pool)
-                    .await?;
-                Ok(rows.into_iter().map(|row| row.get(0)).collect())
-            }
-            Category::WantToRead => {
-                let rows = sqlx::query(
-                    "SELECT book_id FROM reading_status WHERE status = 'WantToRead' ORDER BY book_id"
-                )
-                .fetch_all(&self.pool)
-                .await?;
-                Ok(rows.into_iter().map(|row| row.get(0)).collect())
-            }
-            Category::CurrentlyReading => {
-                let rows = sqlx::query(
-                    "SELECT book_id FROM reading_status WHERE status = 'CurrentlyReading' ORDER BY book_id"
-                )
-                .fetch_all(&self.pool)
-                .await?;
-                Ok(rows.into_iter().map(|row| row.get(0)).collect())
-            }
-            Category::Finished => {
-                let rows = sqlx::query(
-                    "SELECT book_id FROM reading_status WHERE status = 'Finished' ORDER BY finished_at DESC"
-                )
-                .fetch_all(&self.pool)
-                .await?;
-                Ok(rows.into_iter().map(|row| row.get(0)).collect())
-            }
-            Category::Collection(collection_id) => {
-                if let Some(collection) = self.get_collection(&collection_id).await? {
-                    Ok(collection.book_ids)
-                } else {
-                    Ok(Vec::new())
-                }
-            }
-            Category::Author(author_name) => {
-                let rows = sqlx::query("SELECT id FROM books WHERE author = ? ORDER BY title")
-                    .bind(&author_name)
-                    .fetch_all(&self.pool)
-                    .await?;
-                Ok(rows.into_iter().map(|row| row.get(0)).collect())
-            }
-            Category::Tag(tag_name) => {
-                let rows = sqlx::query(
-                    r#"
-                    SELECT b.id FROM books b
-                    JOIN book_tags bt ON b.id = bt.book_id
-                    JOIN tags t ON bt.tag_id = t.id
-                    WHERE t.name = ?
-                    ORDER BY b.title
-                    "#
-                )
-                .bind(&tag_name)
+        if category == Category::Trash {
+            let rows = sqlx::query("SELECT id FROM books WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC")
                 .fetch_all(&self.pool)
                 .await?;
-                Ok(rows.into_iter().map(|row| row.get(0)).collect())
-            }
-            Category::Genre(genre_name) => {
-                let rows = sqlx::query("SELECT id FROM books WHERE genre = ? ORDER BY title")
-                    .bind(&genre_name)
-                    .fetch_all(&self.pool)
-                    .await?;
-                Ok(rows.into_iter().map(|row| row.get(0)).collect())
-            }
-            Category::Publisher(publisher_name) => {
-                let rows = sqlx::query("SELECT id FROM books WHERE publisher = ? ORDER BY title")
-                    .bind(&publisher_name)
-                    .fetch_all(&self.pool)
-                    .await?;
-                Ok(rows.into_iter().map(|row| row.get(0)).collect())
-            }
-            Category::Year(year) => {
-                let rows = sqlx::query("SELECT id FROM books WHERE strftime('%Y', publish_date) = ? ORDER BY title")
-                    .bind(year.to_string())
-                    .fetch_all(&self.pool)
-                    .await?;
-                Ok(rows.into_iter().map(|row| row.get(0)).collect())
-            }
-            Category::Language(language) => {
-                let rows = sqlx::query("SELECT id FROM books WHERE language = ? ORDER BY title")
-                    .bind(&language)
-                    .fetch_all(&self.pool)
-                    .await?;
-                Ok(rows.into_iter().map(|row| row.get(0)).collect())
-            }
-            Category::Rating(rating) => {
-                let rows = sqlx::query("SELECT id FROM books WHERE rating = ? ORDER BY title")
-                    .bind(rating as f64)
-                    .fetch_all(&self.pool)
-                    .await?;
-                Ok(rows.into_iter().map(|row| row.get(0)).collect())
-            }
+            return Ok(rows.into_iter().map(|row| row.get(0)).collect());
         }
+
+        // Books in the trash are left out of every other category
+        let trashed: HashSet<String> = sqlx::query_scalar("SELECT id FROM books WHERE deleted_at IS NOT NULL")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .collect();
+        let mut book_ids = self.category_book_ids(category).await?;
+        book_ids.retain(|book_id| !trashed.contains(book_id));
+        Ok(book_ids)
     }
 
     async fn create_smart_collection(&self, name: String, rules: SmartCollectionRules) -> Result<Collection> {
@@ -821,8 +1656,8 @@ impl LibraryOrganizer for LibraryService {
 
         sqlx::query(
             r#"
-            INSERT INTO collections (id, name, description, icon, color, created_at, updated_at, is_smart, smart_rules, is_favorite, sort_order)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO collections (id, name, description, icon, color, created_at, updated_at, is_smart, smart_rules, is_favorite, sort_order, created_date)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#
         )
         .bind(&collection.id)
@@ -836,6 +1671,7 @@ impl LibraryOrganizer for LibraryService {
         .bind(&smart_rules_json)
         .bind(collection.is_favorite)
         .bind(collection.sort_order as i64)
+        .bind(collection.created_at.to_rfc3339())
         .execute(&self.pool)
         .await?;
 
@@ -918,34 +1754,99 @@ impl LibraryOrganizer for LibraryService {
     }
 
     async fn get_all_authors(&self) -> Result<Vec<Author>> {
-        let rows = sqlx::query("SELECT * FROM authors ORDER BY name")
+        // Authors of library books, with aliases counted under the merged name
+        let rows = sqlx::query(
+            r#"
+            SELECT COALESCE(aa.author_name, b.author) as name, COUNT(*) as book_count, AVG(b.rating) as average_rating
+            FROM books b
+            LEFT JOIN author_aliases aa ON aa.alias = b.author
+            WHERE b.author IS NOT NULL AND b.author != '' AND b.deleted_at IS NULL
+            GROUP BY name
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut authors: HashMap<String, Author> = HashMap::new();
+        for row in rows {
+            let name: String = row.get("name");
+            authors.insert(name.clone(), Author {
+                id: name.clone(),
+                name,
+                bio: None,
+                birth_date: None,
+                death_date: None,
+                nationality: None,
+                photo_url: None,
+                website: None,
+                book_count: row.get::<i64, _>("book_count") as u32,
+                average_rating: row.get::<Option<f64>, _>("average_rating").unwrap_or(0.0) as f32,
+                genres: Vec::new(),
+                aliases: Vec::new(),
+            });
+        }
+
+        let genre_rows = sqlx::query(
+            r#"
+            SELECT DISTINCT COALESCE(aa.author_name, b.author) as name, b.genre
+            FROM books b
+            LEFT JOIN author_aliases aa ON aa.alias = b.author
+            WHERE b.genre IS NOT NULL AND b.genre != '' AND b.deleted_at IS NULL
+            ORDER BY b.genre
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for row in genre_rows {
+            if let Some(author) = authors.get_mut(&row.get::<String, _>("name")) {
+                author.genres.push(row.get("genre"));
+            }
+        }
+
+        let alias_rows = sqlx::query("SELECT alias, author_name FROM author_aliases ORDER BY alias")
             .fetch_all(&self.pool)
             .await?;
+        for row in alias_rows {
+            if let Some(author) = authors.get_mut(&row.get::<String, _>("author_name")) {
+                author.aliases.push(row.get("alias"));
+            }
+        }
 
-        let mut authors = Vec::new();
+        // Details saved for an author, including authors with no books yet
+        let rows = sqlx::query("SELECT * FROM authors")
+            .fetch_all(&self.pool)
+            .await?;
         for row in rows {
-            let created_at_str: String = row.get("created_at");
-            
-            authors.push(Author {
-                id: row.get("id"),
-                name: row.get("name"),
-                bio: row.get("bio"),
-                birth_date: row.get::<Option<String>, _>("birth_date")
-                    .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                    .map(|dt| dt.with_timezone(&Utc)),
-                death_date: row.get::<Option<String>, _>("death_date")
-                    .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                    .map(|dt| dt.with_timezone(&Utc)),
-                nationality: row.get("nationality"),
-                photo_url: row.get("photo_url"),
-                website: row.get("website"),
-                book_count: 0, // TODO: Calculate from books
-                average_rating: 0.0, // TODO: Calculate from books
-                genres: Vec::new(), // TODO: Calculate from books
-                aliases: Vec::new(), // TODO: Implement aliases
+            let name: String = row.get("name");
+            let author = authors.entry(name.clone()).or_insert_with(|| Author {
+                id: String::new(),
+                name,
+                bio: None,
+                birth_date: None,
+                death_date: None,
+                nationality: None,
+                photo_url: None,
+                website: None,
+                book_count: 0,
+                average_rating: 0.0,
+                genres: Vec::new(),
+                aliases: Vec::new(),
             });
+            author.id = row.get("id");
+            author.bio = row.get("bio");
+            author.birth_date = row.get::<Option<String>, _>("birth_date")
+                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                .map(|dt| dt.with_timezone(&Utc));
+            author.death_date = row.get::<Option<String>, _>("death_date")
+                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                .map(|dt| dt.with_timezone(&Utc));
+            author.nationality = row.get("nationality");
+            author.photo_url = row.get("photo_url");
+            author.website = row.get("website");
         }
 
+        let mut authors: Vec<Author> = authors.into_values().collect();
+        authors.sort_by(|a, b| a.name.cmp(&b.name));
         Ok(authors)
     }
 
@@ -996,108 +1897,102 @@ impl LibraryOrganizer for LibraryService {
     }
 
     async fn filter_books(&self, filter: &LibraryFilter) -> Result<Vec<String>> {
-        let mut query = String::from("SELECT DISTINCT b.id FROM books b");
-        let mut joins = Vec::new();
-        let mut conditions = Vec::new();
-        let mut params: Vec<Box<dyn sqlx::Encode<'_, sqlx::Sqlite> + Send + Sync>> = Vec::new();
-
-        // Add joins based on filter criteria
-        if !filter.tags.is_empty() {
-            joins.push("JOIN book_tags bt ON b.id = bt.book_id");
-            joins.push("JOIN tags t ON bt.tag_id = t.id");
-        }
-
-        if filter.reading_status.is_some() {
-            joins.push("LEFT JOIN reading_status rs ON b.id = rs.book_id");
-        }
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "SELECT b.id FROM books b LEFT JOIN reading_status rs ON b.id = rs.book_id WHERE b.deleted_at IS NULL"
+        );
 
-        // Add conditions
-        if let Some(ref author) = filter.author {
-            conditions.push("b.author = ?");
-            params.push(Box::new(author.clone()));
+        if let Some(author) = &filter.author {
+            query.push(" AND b.author = ").push_bind(author.clone());
         }
 
-        if let Some(ref genre) = filter.genre {
-            conditions.push("b.genre = ?");
-            params.push(Box::new(genre.clone()));
+        if let Some(genre) = &filter.genre {
+            query.push(" AND b.genre = ").push_bind(genre.clone());
         }
 
-        if let Some(ref language) = filter.language {
-            conditions.push("b.language = ?");
-            params.push(Box::new(language.clone()));
+        if let Some(language) = &filter.language {
+            query.push(" AND b.language = ").push_bind(language.clone());
         }
 
-        if let Some(ref publisher) = filter.publisher {
-            conditions.push("b.publisher = ?");
-            params.push(Box::new(publisher.clone()));
+        if let Some(publisher) = &filter.publisher {
+            query.push(" AND b.publisher = ").push_bind(publisher.clone());
         }
 
         if let Some((min_year, max_year)) = filter.year_range {
-            conditions.push("strftime('%Y', b.publish_date) BETWEEN ? AND ?");
-            params.push(Box::new(min_year.to_string()));
-            params.push(Box::new(max_year.to_string()));
+            // Publication dates start with the year, whether full dates or years alone
+            query.push(" AND CAST(substr(b.publication_date, 1, 4) AS INTEGER) BETWEEN ")
+                .push_bind(min_year as i64)
+                .push(" AND ")
+                .push_bind(max_year as i64);
         }
 
         if let Some((min_rating, max_rating)) = filter.rating_range {
-            conditions.push("b.rating BETWEEN ? AND ?");
-            params.push(Box::new(min_rating as f64));
-            params.push(Box::new(max_rating as f64));
+            query.push(" AND b.rating BETWEEN ")
+                .push_bind(min_rating as i64)
+                .push(" AND ")
+                .push_bind(max_rating as i64);
         }
 
-        if let Some(ref status) = filter.reading_status {
-            conditions.push("rs.status = ?");
-            params.push(Box::new(status.to_display_name()));
+        if let Some(status) = &filter.reading_status {
+            query.push(" AND rs.status = ").push_bind(Self::stored_status(status));
         }
 
-        if !filter.tags.is_empty() {
-            // For now, just use a simple approach - can be improved later
-            conditions.push("t.name IS NOT NULL");
-            for tag in &filter.tags {
-                params.push(Box::new(tag.clone()));
-            }
+        // Books must have every tag
+        for tag in &filter.tags {
+            query.push(" AND b.id IN (SELECT bt.book_id FROM book_tags bt JOIN tags t ON bt.tag_id = t.id WHERE t.name = ")
+                .push_bind(tag.clone())
+                .push(")");
         }
 
-        if let Some(ref search_query) = filter.search_query {
-            conditions.push("(b.title LIKE ? OR b.author LIKE ? OR b.description LIKE ?)");
-            let search_pattern = format!("%{}%", search_query);
-            params.push(Box::new(search_pattern.clone()));
-            params.push(Box::new(search_pattern.clone()));
-            params.push(Box::new(search_pattern));
+        if let Some(search_query) = &filter.search_query {
+            let pattern = format!("%{}%", Self::escape_like(search_query));
+            query.push(" AND (b.title LIKE ")
+                .push_bind(pattern.clone())
+                .push(" ESCAPE '\\' OR b.author LIKE ")
+                .push_bind(pattern.clone())
+                .push(" ESCAPE '\\' OR b.description LIKE ")
+                .push_bind(pattern)
+                .push(" ESCAPE '\\')");
         }
 
         if let Some(has_cover) = filter.has_cover {
             if has_cover {
-                conditions.push("b.cover_image IS NOT NULL AND b.cover_image != ''");
+                query.push(" AND b.cover_path IS NOT NULL AND b.cover_path != ''");
             } else {
-                conditions.push("(b.cover_image IS NULL OR b.cover_image = '')");
+                query.push(" AND (b.cover_path IS NULL OR b.cover_path = '')");
             }
         }
 
-        if let Some(ref file_format) = filter.file_format {
-            conditions.push("b.file_path LIKE ?");
-            params.push(Box::new(format!("%.{}", file_format)));
+        if let Some(file_format) = &filter.file_format {
+            let pattern = format!("%.{}", Self::escape_like(file_format.trim_start_matches('.')));
+            query.push(" AND b.file_path LIKE ").push_bind(pattern).push(" ESCAPE '\\'");
         }
 
-        // Build final query
-        if !joins.is_empty() {
-            query.push_str(" ");
-            query.push_str(&joins.join(" "));
+        if let Some((from, to)) = filter.added_date_range {
+            query.push(" AND julianday(b.added_date) BETWEEN julianday(")
+                .push_bind(from.to_rfc3339())
+                .push(") AND julianday(")
+                .push_bind(to.to_rfc3339())
+                .push(")");
         }
 
-        if !conditions.is_empty() {
-            query.push_str(" WHERE ");
-            query.push_str(&conditions.join(" AND "));
+        if let Some((from, to)) = filter.read_date_range {
+            query.push(" AND julianday(rs.finished_at) BETWEEN julianday(")
+                .push_bind(from.to_rfc3339())
+                .push(") AND julianday(")
+                .push_bind(to.to_rfc3339())
+                .push(")");
         }
 
-        query.push_str(" ORDER BY b.title");
+        query.push(" ORDER BY b.title");
 
-        // Execute query
-        // For now, use a simple query without dynamic binding
-        let rows = sqlx::query(&query)
-            .fetch_all(&self.pool)
-            .await?;
+        let mut book_ids: Vec<String> = query.build_query_scalar().fetch_all(&self.pool).await?;
 
-        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+        if let Some(category) = filter.category.clone() {
+            let in_category: HashSet<String> = self.get_books_by_category(category).await?.into_iter().collect();
+            book_ids.retain(|book_id| in_category.contains(book_id));
+        }
+
+        Ok(book_ids)
     }
 
     async fn sort_books(&self, book_ids: &[String], sort_by: LibrarySortBy, direction: SortDirection) -> Result<Vec<String>> {
@@ -1105,7 +2000,6 @@ impl LibraryOrganizer for LibraryService {
             return Ok(Vec::new());
         }
 
-        let placeholders = book_ids.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
         let order_direction = match direction {
             SortDirection::Ascending => "ASC",
             SortDirection::Descending => "DESC",
@@ -1119,24 +2013,345 @@ impl LibraryOrganizer for LibraryService {
             LibrarySortBy::PublishDate => ("b.publish_date", ""),
             LibrarySortBy::FileSize => ("b.file_size", ""),
             LibrarySortBy::PageCount => ("b.page_count", ""),
+            LibrarySortBy::WordCount => ("b.word_count", ""),
             LibrarySortBy::Genre => ("b.genre", ""),
             LibrarySortBy::Publisher => ("b.publisher", ""),
             LibrarySortBy::Language => ("b.language", ""),
+            LibrarySortBy::Series => ("b.series", ""),
             LibrarySortBy::ReadDate => ("rs.finished_at", "LEFT JOIN reading_status rs ON b.id = rs.book_id"),
             LibrarySortBy::Progress => ("rs.progress", "LEFT JOIN reading_status rs ON b.id = rs.book_id"),
             LibrarySortBy::LastOpened => ("b.last_opened", ""),
         };
 
+        // Volumes of a series stay in reading order, books without one go last
+        let order_clause = match sort_by {
+            LibrarySortBy::Series => format!(
+                "b.series IS NULL, {0} {1}, b.series_index {1}",
+                order_field, order_direction
+            ),
+            _ => format!("{} {}", order_field, order_direction),
+        };
+
+        // The ids go in as one JSON array, so any number of books takes a single parameter
         let query = format!(
-            "SELECT b.id FROM books b {} WHERE b.id IN ({}) ORDER BY {} {}",
-            additional_joins, placeholders, order_field, order_direction
+            "SELECT b.id FROM books b {} WHERE b.id IN (SELECT value FROM json_each(?)) ORDER BY {}",
+            additional_joins, order_clause
         );
 
-        // For now, use a simple query without dynamic binding
-        let rows = sqlx::query(&query)
+        let sorted = sqlx::query_scalar(&query)
+            .bind(serde_json::to_string(book_ids)?)
             .fetch_all(&self.pool)
             .await?;
 
-        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+        Ok(sorted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn service_with_books(path: &std::path::Path, book_ids: &[&str]) -> LibraryService {
+        let pool = SqlitePool::connect(&format!("sqlite://{}?mode=rwc", path.display())).await.unwrap();
+        sqlx::query(
+            "CREATE TABLE books (id TEXT PRIMARY KEY, title TEXT, author TEXT, genre TEXT, language TEXT, \
+             series TEXT, series_index REAL, tags TEXT, reading_status TEXT, word_count INTEGER, last_opened TEXT, \
+             publisher TEXT, description TEXT, publication_date TEXT, rating INTEGER, file_path TEXT, cover_path TEXT, added_date TEXT, \
+             deleted_at TEXT)"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        for book_id in book_ids {
+            sqlx::query("INSERT INTO books (id, title, author, series, series_index, tags, reading_status) VALUES (?, ?, 'Unknown Author', 'Old', 1.0, '[\"keep\", \"drop\"]', 'unread')")
+                .bind(book_id)
+                .bind(book_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let service = LibraryService::new(pool);
+        service.init_tables().await.unwrap();
+        service
+    }
+
+    #[tokio::test]
+    async fn test_bulk_update_books() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let service = service_with_books(&temp_dir.path().join("library.db"), &["a", "b", "c"]).await;
+        service.enqueue_book("a").await.unwrap();
+
+        let changes = BulkBookChanges {
+            author: Some("Ursula K. Le Guin".to_string()),
+            genre: Some("Fantasy".to_string()),
+            add_tags: vec!["earthsea".to_string()],
+            remove_tags: vec!["drop".to_string()],
+            series: Some(String::new()),
+            reading_status: Some(ReadingStatus::Finished),
+            ..BulkBookChanges::default()
+        };
+        let book_ids = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(service.bulk_update_books(&book_ids, &changes).await.unwrap(), 2);
+
+        let row = sqlx::query("SELECT * FROM books WHERE id = 'a'").fetch_one(&service.pool).await.unwrap();
+        assert_eq!(row.get::<String, _>("author"), "Ursula K. Le Guin");
+        assert_eq!(row.get::<Option<String>, _>("genre").as_deref(), Some("Fantasy"));
+        assert_eq!(row.get::<Option<String>, _>("series"), None);
+        assert_eq!(row.get::<String, _>("tags"), r#"["keep","earthsea"]"#);
+        assert_eq!(row.get::<String, _>("reading_status"), "finished");
+        assert_eq!(service.get_book_tags("b").await.unwrap(), ["earthsea"]);
+        assert_eq!(service.get_reading_status("b").await.unwrap(), Some(ReadingStatus::Finished));
+        assert!(service.get_reading_queue().await.unwrap().is_empty());
+
+        let untouched = sqlx::query("SELECT author FROM books WHERE id = 'c'").fetch_one(&service.pool).await.unwrap();
+        assert_eq!(untouched.get::<String, _>(0), "Unknown Author");
+
+        // A missing book rolls back the whole edit
+        let changes = BulkBookChanges { author: Some("Someone Else".to_string()), ..BulkBookChanges::default() };
+        let book_ids = vec!["c".to_string(), "missing".to_string()];
+        assert!(service.bulk_update_books(&book_ids, &changes).await.is_err());
+        let untouched = sqlx::query("SELECT author FROM books WHERE id = 'c'").fetch_one(&service.pool).await.unwrap();
+        assert_eq!(untouched.get::<String, _>(0), "Unknown Author");
+    }
+
+    #[tokio::test]
+    async fn test_nested_collections() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let service = service_with_books(&temp_dir.path().join("library.db"), &["a", "b"]).await;
+        let mut ids = Vec::new();
+        for name in ["Fiction", "Sci-Fi", "Cyberpunk"] {
+            let collection = service.create_collection(name.to_string(), "📁".to_string(), "#888".to_string()).await.unwrap();
+            ids.push(collection.id);
+        }
+        let (fiction, scifi, cyberpunk) = (&ids[0], &ids[1], &ids[2]);
+
+        service.move_collection(scifi, Some(fiction)).await.unwrap();
+        service.move_collection(cyberpunk, Some(scifi)).await.unwrap();
+        assert!(service.move_collection(fiction, Some(cyberpunk)).await.is_err());
+        assert!(service.move_collection(scifi, Some(scifi)).await.is_err());
+        assert!(service.move_collection(scifi, Some("missing")).await.is_err());
+
+        let path: Vec<_> = service.get_collection_path(cyberpunk).await.unwrap()
+            .into_iter()
+            .map(|collection| collection.name)
+            .collect();
+        assert_eq!(path, ["Fiction", "Sci-Fi", "Cyberpunk"]);
+        let top_level = service.get_child_collections(None).await.unwrap();
+        assert_eq!(top_level.len(), 1);
+        assert_eq!(&top_level[0].id, fiction);
+
+        service.add_to_collection("a".to_string(), fiction.clone()).await.unwrap();
+        service.add_to_collection("b".to_string(), cyberpunk.clone()).await.unwrap();
+        service.add_to_collection("a".to_string(), scifi.clone()).await.unwrap();
+        assert_eq!(service.get_collection_books_recursive(fiction).await.unwrap(), ["a", "b"]);
+        assert_eq!(service.get_collection_books_recursive(scifi).await.unwrap(), ["a", "b"]);
+
+        // Deleting a collection keeps its children, one level up
+        service.delete_collection(scifi).await.unwrap();
+        let cyberpunk = service.get_collection(cyberpunk).await.unwrap().unwrap();
+        assert_eq!(cyberpunk.parent_id.as_ref(), Some(fiction));
+    }
+
+    #[tokio::test]
+    async fn test_smart_rules_on_reading_fields() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let service = service_with_books(&temp_dir.path().join("library.db"), &["long", "short", "uncounted"]).await;
+        let last_week = (Utc::now() - Duration::days(7)).to_rfc3339();
+        let last_year = (Utc::now() - Duration::days(365)).to_rfc3339();
+        for (book_id, words, last_opened) in [("long", Some(180_000), Some(&last_year)), ("short", Some(20_000), Some(&last_week))] {
+            sqlx::query("UPDATE books SET word_count = ?, last_opened = ? WHERE id = ?")
+                .bind(words)
+                .bind(last_opened)
+                .bind(book_id)
+                .execute(&service.pool)
+                .await
+                .unwrap();
+        }
+        sqlx::query("CREATE TABLE annotations (id TEXT PRIMARY KEY, book_id TEXT NOT NULL)").execute(&service.pool).await.unwrap();
+        for (id, book_id) in [("1", "long"), ("2", "long"), ("3", "short")] {
+            sqlx::query("INSERT INTO annotations (id, book_id) VALUES (?, ?)")
+                .bind(id)
+                .bind(book_id)
+                .execute(&service.pool)
+                .await
+                .unwrap();
+        }
+
+        let rule = |field, operator, value: &str| SmartRule { field, operator, value: value.to_string() };
+        let rules = |rules| SmartCollectionRules { rules, match_type: MatchType::All };
+
+        // Long books not opened lately that have been annotated
+        let long_unread = rules(vec![
+            rule(SmartRuleField::ReadingTime, SmartRuleOperator::GreaterThan, "600"),
+            rule(SmartRuleField::LastOpened, SmartRuleOperator::Before, &(Utc::now() - Duration::days(30)).to_rfc3339()),
+            rule(SmartRuleField::AnnotationCount, SmartRuleOperator::GreaterThanOrEqual, "2"),
+        ]);
+        assert_eq!(service.evaluate_smart_rules(&long_unread).await.unwrap(), ["long"]);
+
+        let recent = rules(vec![rule(SmartRuleField::LastOpened, SmartRuleOperator::InLast, "30")]);
+        assert_eq!(service.evaluate_smart_rules(&recent).await.unwrap(), ["short"]);
+        let uncounted = rules(vec![rule(SmartRuleField::WordCount, SmartRuleOperator::IsEmpty, "")]);
+        assert_eq!(service.evaluate_smart_rules(&uncounted).await.unwrap(), ["uncounted"]);
+    }
+
+    #[tokio::test]
+    async fn test_smart_rules_run_in_sql_and_cache() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let service = service_with_books(&temp_dir.path().join("library.db"), &["a", "b", "c"]).await;
+        sqlx::query("UPDATE books SET title = '50% Off' WHERE id = 'a'").execute(&service.pool).await.unwrap();
+
+        let rule = |field, operator, value: &str| SmartRule { field, operator, value: value.to_string() };
+        let rules = |rules, match_type| SmartCollectionRules { rules, match_type };
+
+        // Wildcards in a value match literally
+        let percent = rules(vec![rule(SmartRuleField::Title, SmartRuleOperator::Contains, "0% o")], MatchType::All);
+        assert_eq!(service.evaluate_smart_rules(&percent).await.unwrap(), ["a"]);
+        let either = rules(vec![
+            rule(SmartRuleField::Title, SmartRuleOperator::StartsWith, "50"),
+            rule(SmartRuleField::Title, SmartRuleOperator::Equals, "c"),
+        ], MatchType::Any);
+        let mut matches = service.evaluate_smart_rules(&either).await.unwrap();
+        matches.sort();
+        assert_eq!(matches, ["a", "c"]);
+
+        let finished_sale = rules(vec![
+            rule(SmartRuleField::Tags, SmartRuleOperator::Contains, "sale"),
+            rule(SmartRuleField::ReadingStatus, SmartRuleOperator::Equals, "Finished"),
+        ], MatchType::All);
+        assert!(service.evaluate_smart_rules(&finished_sale).await.unwrap().is_empty());
+
+        // Changing the books invalidates the cached result
+        let changes = BulkBookChanges {
+            add_tags: vec!["Sale".to_string()],
+            reading_status: Some(ReadingStatus::Finished),
+            ..BulkBookChanges::default()
+        };
+        service.bulk_update_books(&["b".to_string()], &changes).await.unwrap();
+        assert_eq!(service.evaluate_smart_rules(&finished_sale).await.unwrap(), ["b"]);
+        assert!(service.book_matches_rules("b", &finished_sale).await.unwrap());
+        assert!(!service.book_matches_rules("c", &finished_sale).await.unwrap());
+
+        let rating = rules(vec![rule(SmartRuleField::Rating, SmartRuleOperator::GreaterThan, "not a number")], MatchType::All);
+        assert!(service.evaluate_smart_rules(&rating).await.unwrap().is_empty());
+
+        // Matches of rules relative to today change with the date, so they are not cached
+        let recent = rules(vec![rule(SmartRuleField::LastOpened, SmartRuleOperator::InLast, "7")], MatchType::All);
+        service.evaluate_smart_rules(&recent).await.unwrap();
+        assert!(!service.smart_cache.read().await.contains_key(&serde_json::to_string(&recent).unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_filter_and_sort_bind_their_values() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let service = service_with_books(&temp_dir.path().join("library.db"), &["a", "b", "c"]).await;
+        for (book_id, title, rating, published, file_path) in [
+            ("a", "Alpha", 5, "1999-03-01", "/books/a.epub"),
+            ("b", "Beta", 3, "2005", "/books/b.PDF"),
+            ("c", "O'Brien's Gamma", 4, "2012-01-01T00:00:00+00:00", "/books/c.epub"),
+        ] {
+            sqlx::query("UPDATE books SET title = ?, rating = ?, publication_date = ?, file_path = ? WHERE id = ?")
+                .bind(title)
+                .bind(rating)
+                .bind(published)
+                .bind(file_path)
+                .bind(book_id)
+                .execute(&service.pool)
+                .await
+                .unwrap();
+        }
+        let changes = BulkBookChanges {
+            add_tags: vec!["classic".to_string()],
+            reading_status: Some(ReadingStatus::Finished),
+            ..BulkBookChanges::default()
+        };
+        service.bulk_update_books(&["a".to_string(), "c".to_string()], &changes).await.unwrap();
+
+        let library = &service;
+        let filter = |filter: LibraryFilter| async move { library.filter_books(&filter).await.unwrap() };
+        assert_eq!(filter(LibraryFilter { year_range: Some((1990, 2006)), ..LibraryFilter::default() }).await, ["a", "b"]);
+        assert_eq!(filter(LibraryFilter { rating_range: Some((4, 5)), ..LibraryFilter::default() }).await, ["a", "c"]);
+        assert_eq!(filter(LibraryFilter { file_format: Some("pdf".to_string()), ..LibraryFilter::default() }).await, ["b"]);
+        assert_eq!(filter(LibraryFilter { search_query: Some("o'brien".to_string()), ..LibraryFilter::default() }).await, ["c"]);
+        let classics = LibraryFilter {
+            tags: vec!["classic".to_string()],
+            reading_status: Some(ReadingStatus::Finished),
+            category: Some(Category::Finished),
+            rating_range: Some((5, 5)),
+            ..LibraryFilter::default()
+        };
+        assert_eq!(filter(classics).await, ["a"]);
+
+        // Values are data, not SQL
+        let injection = LibraryFilter { author: Some("' OR 1 = 1 --".to_string()), ..LibraryFilter::default() };
+        assert!(filter(injection).await.is_empty());
+
+        let ids = ["b", "missing", "c", "a"].map(String::from);
+        let sorted = service.sort_books(&ids, LibrarySortBy::Rating, SortDirection::Descending).await.unwrap();
+        assert_eq!(sorted, ["a", "c", "b"]);
+        let many: Vec<String> = (0..40_000).map(|i| format!("id-{}", i)).chain(ids).collect();
+        let sorted = service.sort_books(&many, LibrarySortBy::Title, SortDirection::Ascending).await.unwrap();
+        assert_eq!(sorted, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_author_match_key() {
+        assert_eq!(LibraryService::author_match_key("J.R.R. Tolkien"), "jrr tolkien");
+        assert_eq!(LibraryService::author_match_key("Tolkien, J. R. R."), "jrr tolkien");
+        assert_eq!(LibraryService::author_match_key("Ursula K. Le Guin"), "ursula k le guin");
+        assert_ne!(LibraryService::author_match_key("Christopher Tolkien"), "jrr tolkien");
+    }
+
+    #[tokio::test]
+    async fn test_merged_authors_are_aggregated() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let service = service_with_books(&temp_dir.path().join("library.db"), &["hobbit", "silmarillion", "earthsea"]).await;
+        for (book_id, author, genre, rating) in [
+            ("hobbit", "J.R.R. Tolkien", "Fantasy", Some(5)),
+            ("silmarillion", "Tolkien, J. R. R.", "Mythology", Some(4)),
+            ("earthsea", "Ursula K. Le Guin", "Fantasy", None),
+        ] {
+            sqlx::query("UPDATE books SET author = ?, genre = ?, rating = ? WHERE id = ?")
+                .bind(author)
+                .bind(genre)
+                .bind(rating)
+                .bind(book_id)
+                .execute(&service.pool)
+                .await
+                .unwrap();
+        }
+
+        let duplicates = service.find_duplicate_authors().await.unwrap();
+        assert_eq!(duplicates, [["J.R.R. Tolkien", "Tolkien, J. R. R."]]);
+        service.merge_authors(&duplicates[0][0], &duplicates[0][1..]).await.unwrap();
+        assert!(service.find_duplicate_authors().await.unwrap().is_empty());
+
+        let authors = service.get_all_authors().await.unwrap();
+        assert_eq!(authors.len(), 2);
+        let tolkien = &authors[0];
+        assert_eq!((tolkien.name.as_str(), tolkien.book_count, tolkien.average_rating), ("J.R.R. Tolkien", 2, 4.5));
+        assert_eq!(tolkien.genres, ["Fantasy", "Mythology"]);
+        assert_eq!(tolkien.aliases, ["Tolkien, J. R. R."]);
+        assert_eq!((authors[1].book_count, authors[1].average_rating), (1, 0.0));
+
+        let books = service.get_books_by_category(Category::Author(tolkien.id.clone())).await.unwrap();
+        assert_eq!(books.len(), 2);
+
+        // Merging into the alias swaps which spelling is shown
+        service.merge_authors("Tolkien, J. R. R.", &["J.R.R. Tolkien".to_string()]).await.unwrap();
+        let authors = service.get_all_authors().await.unwrap();
+        assert_eq!(authors[0].name, "Tolkien, J. R. R.");
+        assert_eq!((authors[0].book_count, authors[0].aliases.clone()), (2, vec!["J.R.R. Tolkien".to_string()]));
+
+        // Books in the trash don't count towards their authors
+        sqlx::query("UPDATE books SET deleted_at = ? WHERE id IN ('silmarillion', 'earthsea')")
+            .bind(Utc::now().to_rfc3339())
+            .execute(&service.pool)
+            .await
+            .unwrap();
+        let authors = service.get_all_authors().await.unwrap();
+        assert_eq!(authors.iter().map(|author| (author.name.as_str(), author.book_count)).collect::<Vec<_>>(), [("Tolkien, J. R. R.", 1)]);
+        let books = service.get_books_by_category(Category::Author(authors[0].name.clone())).await.unwrap();
+        assert_eq!(books, ["hobbit"]);
     }
-}
\ No newline at end of file
+}
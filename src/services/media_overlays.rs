@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::services::epub_parser::{EpubDocument, EpubParser};
+
+static MANIFEST_ITEM: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(?s)<(?:\w+:)?item\b[^>]*>"#).unwrap());
+static ID_ATTR: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\bid\s*=\s*"([^"]*)""#).unwrap());
+static HREF_ATTR: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\bhref\s*=\s*"([^"]*)""#).unwrap());
+static MEDIA_OVERLAY_ATTR: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\bmedia-overlay\s*=\s*"([^"]*)""#).unwrap());
+static PAR: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<(?:\w+:)?par\b[^>]*>(.*?)</(?:\w+:)?par>").unwrap());
+static TEXT_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<(?:\w+:)?text\b[^>]*>").unwrap());
+static AUDIO_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<(?:\w+:)?audio\b[^>]*>").unwrap());
+static SRC_ATTR: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\bsrc\s*=\s*"([^"]*)""#).unwrap());
+static CLIP_BEGIN_ATTR: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\bclipBegin\s*=\s*"([^"]*)""#).unwrap());
+static CLIP_END_ATTR: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\bclipEnd\s*=\s*"([^"]*)""#).unwrap());
+
+/// Audio clip narrating one fragment of a chapter
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaOverlayClip {
+    pub text_path: String,
+    pub fragment: Option<String>, // Element id highlighted while the clip plays
+    pub audio_path: String,
+    pub clip_begin_ms: u64,
+    pub clip_end_ms: Option<u64>, // None plays to the end of the audio file
+}
+
+/// Synchronized narration for a chapter, from its SMIL media overlay
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaOverlay {
+    pub smil_path: String,
+    pub clips: Vec<MediaOverlayClip>,
+}
+
+impl MediaOverlay {
+    /// Find the clip narrating an element
+    pub fn clip_for_fragment(&self, fragment: &str) -> Option<&MediaOverlayClip> {
+        self.clips.iter().find(|clip| clip.fragment.as_deref() == Some(fragment))
+    }
+
+    /// Find the clip playing at a position in an audio file, for highlighting
+    pub fn clip_at(&self, audio_path: &str, position_ms: u64) -> Option<&MediaOverlayClip> {
+        self.clips.iter().find(|clip| {
+            clip.audio_path == audio_path
+                && clip.clip_begin_ms <= position_ms
+                && clip.clip_end_ms.map_or(true, |end| position_ms < end)
+        })
+    }
+}
+
+/// Reads EPUB3 media overlays (SMIL) for read-aloud books
+pub struct MediaOverlayParser;
+
+impl MediaOverlayParser {
+    /// Map manifest item ids to the archive path of their SMIL overlay
+    pub fn overlay_paths(doc: &mut EpubDocument) -> HashMap<String, String> {
+        let package_path = doc.root_file.to_string_lossy().replace('\\', "/");
+        let Some(package) = doc.get_resource_str_by_path(&doc.root_file.clone()) else {
+            return HashMap::new();
+        };
+
+        let items: Vec<&str> = MANIFEST_ITEM.find_iter(&package).map(|item| item.as_str()).collect();
+        let hrefs: HashMap<&str, String> = items.iter()
+            .filter_map(|item| {
+                let id = ID_ATTR.captures(item)?.get(1)?.as_str();
+                let href = HREF_ATTR.captures(item)?;
+                Some((id, html_escape::decode_html_entities(&href[1]).into_owned()))
+            })
+            .collect();
+
+        items.iter()
+            .filter_map(|item| {
+                let id = ID_ATTR.captures(item)?[1].to_string();
+                let overlay_id = MEDIA_OVERLAY_ATTR.captures(item)?;
+                let href = hrefs.get(&overlay_id[1])?;
+                Some((id, EpubParser::resolve_href(&package_path, href)))
+            })
+            .collect()
+    }
+
+    /// Read and parse a SMIL document from the archive
+    pub fn load(doc: &mut EpubDocument, smil_path: &str) -> Option<MediaOverlay> {
+        let smil = doc.get_resource_str_by_path(smil_path)?;
+        Some(MediaOverlay {
+            smil_path: smil_path.to_string(),
+            clips: Self::parse_smil(&smil, smil_path),
+        })
+    }
+
+    /// Parse the `<par>` elements of a SMIL document in playback order
+    ///
+    /// Text and audio sources are resolved against `smil_path` to archive paths.
+    pub fn parse_smil(smil: &str, smil_path: &str) -> Vec<MediaOverlayClip> {
+        PAR.captures_iter(smil)
+            .filter_map(|par| {
+                let body = &par[1];
+                let text_src = TEXT_TAG.find(body).and_then(|tag| attr(&SRC_ATTR, tag.as_str()))?;
+                let audio = AUDIO_TAG.find(body)?.as_str();
+                let audio_src = attr(&SRC_ATTR, audio)?;
+
+                let (text_href, fragment) = match text_src.split_once('#') {
+                    Some((href, fragment)) => (href.to_string(), Some(fragment.to_string())),
+                    None => (text_src, None),
+                };
+
+                Some(MediaOverlayClip {
+                    text_path: EpubParser::resolve_href(smil_path, &text_href),
+                    fragment,
+                    audio_path: EpubParser::resolve_href(smil_path, &audio_src),
+                    clip_begin_ms: attr(&CLIP_BEGIN_ATTR, audio)
+                        .and_then(|value| parse_clock_value(&value))
+                        .unwrap_or(0),
+                    clip_end_ms: attr(&CLIP_END_ATTR, audio).and_then(|value| parse_clock_value(&value)),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Parse a SMIL clock value into milliseconds
+///
+/// Accepts full and partial clock values ("0:01:02.5", "01:02.5") and timecounts
+/// ("62.5s", "500ms", "1.5min", "1h"); a bare number is in seconds.
+pub fn parse_clock_value(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let seconds = if value.contains(':') {
+        let parts: Vec<&str> = value.split(':').collect();
+        let (hours, minutes, seconds) = match parts.as_slice() {
+            [hours, minutes, seconds] => (hours.parse::<f64>().ok()?, minutes.parse::<f64>().ok()?, seconds),
+            [minutes, seconds] => (0.0, minutes.parse::<f64>().ok()?, seconds),
+            _ => return None,
+        };
+        hours * 3600.0 + minutes * 60.0 + seconds.parse::<f64>().ok()?
+    } else {
+        let (number, scale) = if let Some(number) = value.strip_suffix("ms") {
+            (number, 0.001)
+        } else if let Some(number) = value.strip_suffix("min") {
+            (number, 60.0)
+        } else if let Some(number) = value.strip_suffix('h') {
+            (number, 3600.0)
+        } else if let Some(number) = value.strip_suffix('s') {
+            (number, 1.0)
+        } else {
+            (value, 1.0)
+        };
+        number.trim().parse::<f64>().ok()? * scale
+    };
+
+    (seconds.is_finite() && seconds >= 0.0).then(|| (seconds * 1000.0).round() as u64)
+}
+
+fn attr(pattern: &Regex, tag: &str) -> Option<String> {
+    pattern.captures(tag)
+        .map(|captures| html_escape::decode_html_entities(&captures[1]).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clock_values() {
+        assert_eq!(parse_clock_value("0:01:02.5"), Some(62_500));
+        assert_eq!(parse_clock_value("01:02"), Some(62_000));
+        assert_eq!(parse_clock_value("3.2s"), Some(3_200));
+        assert_eq!(parse_clock_value("500ms"), Some(500));
+        assert_eq!(parse_clock_value("1.5min"), Some(90_000));
+        assert_eq!(parse_clock_value("12"), Some(12_000));
+        assert_eq!(parse_clock_value("soon"), None);
+    }
+
+    #[test]
+    fn test_parse_smil_resolves_paths_and_timing() {
+        let smil = r#"<smil xmlns="http://www.w3.org/ns/SMIL" version="3.0">
+            <body>
+                <seq epub:textref="../text/ch1.xhtml">
+                    <par id="p1">
+                        <text src="../text/ch1.xhtml#s1"/>
+                        <audio src="../audio/ch1.mp3" clipBegin="0s" clipEnd="2.4s"/>
+                    </par>
+                    <par id="p2">
+                        <text src="../text/ch1.xhtml#s2"/>
+                        <audio src="../audio/ch1.mp3" clipBegin="0:00:02.400"/>
+                    </par>
+                </seq>
+            </body>
+        </smil>"#;
+
+        let overlay = MediaOverlay {
+            smil_path: "OEBPS/smil/ch1.smil".to_string(),
+            clips: MediaOverlayParser::parse_smil(smil, "OEBPS/smil/ch1.smil"),
+        };
+        assert_eq!(overlay.clips.len(), 2);
+        assert_eq!(overlay.clips[0], MediaOverlayClip {
+            text_path: "OEBPS/text/ch1.xhtml".to_string(),
+            fragment: Some("s1".to_string()),
+            audio_path: "OEBPS/audio/ch1.mp3".to_string(),
+            clip_begin_ms: 0,
+            clip_end_ms: Some(2_400),
+        });
+
+        assert_eq!(overlay.clip_at("OEBPS/audio/ch1.mp3", 1_000).unwrap().fragment.as_deref(), Some("s1"));
+        assert_eq!(overlay.clip_at("OEBPS/audio/ch1.mp3", 9_000).unwrap().fragment.as_deref(), Some("s2"));
+        assert_eq!(overlay.clip_for_fragment("s2").unwrap().clip_begin_ms, 2_400);
+    }
+}
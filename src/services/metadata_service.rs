@@ -0,0 +1,521 @@
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::Client;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::models::Book;
+use crate::services::epub_metadata::{parse_date, plain_text};
+use crate::utils::isbn::normalize_isbn;
+
+const GOOGLE_BOOKS_URL: &str = "https://www.googleapis.com/books/v1/volumes";
+const OPEN_LIBRARY_BOOKS_URL: &str = "https://openlibrary.org/api/books";
+const OPEN_LIBRARY_SEARCH_URL: &str = "https://openlibrary.org/search.json";
+const OPEN_LIBRARY_COVER_URL: &str = "https://covers.openlibrary.org/b/id";
+
+/// Candidates requested from each source per lookup
+const MAX_RESULTS: usize = 5;
+
+static SERIES_NUMBER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)^(.+?)[\s,;:(]*(?:#|no\.\s*|vol(?:ume)?\.?\s*|book\s+)?(\d+(?:\.\d+)?)\)?$").unwrap()
+});
+static YEAR: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b(\d{4})\b").unwrap());
+
+/// Online catalog metadata is fetched from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataSource {
+    GoogleBooks,
+    OpenLibrary,
+}
+
+impl MetadataSource {
+    /// Get display name
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            MetadataSource::GoogleBooks => "Google Books",
+            MetadataSource::OpenLibrary => "Open Library",
+        }
+    }
+}
+
+/// Book field that fetched metadata can replace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MetadataField {
+    Title,
+    Authors,
+    Description,
+    Publisher,
+    PublicationDate,
+    Isbn,
+    Series,
+    Subjects,
+    Language,
+    PageCount,
+    Cover,
+}
+
+impl MetadataField {
+    /// Get display name
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            MetadataField::Title => "Title",
+            MetadataField::Authors => "Authors",
+            MetadataField::Description => "Description",
+            MetadataField::Publisher => "Publisher",
+            MetadataField::PublicationDate => "Publication date",
+            MetadataField::Isbn => "ISBN",
+            MetadataField::Series => "Series",
+            MetadataField::Subjects => "Subjects",
+            MetadataField::Language => "Language",
+            MetadataField::PageCount => "Pages",
+            MetadataField::Cover => "Cover",
+        }
+    }
+}
+
+/// A change fetched metadata would make to one field, shown for review
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataChange {
+    pub field: MetadataField,
+    pub current: Option<String>,
+    pub proposed: String,
+}
+
+/// Book metadata found in an online catalog
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetadataCandidate {
+    pub source: Option<MetadataSource>,
+    pub title: String,
+    pub authors: Vec<String>,
+    pub description: Option<String>,
+    pub publisher: Option<String>,
+    pub published: Option<DateTime<Utc>>,
+    pub isbn: Option<String>,
+    pub series: Option<String>,
+    pub series_index: Option<f32>,
+    pub subjects: Vec<String>,
+    pub language: Option<String>,
+    pub page_count: Option<u32>,
+    pub cover_url: Option<String>,
+}
+
+impl MetadataCandidate {
+    /// Fields this candidate would change on the book, for the user to pick from
+    pub fn review(&self, book: &Book) -> Vec<MetadataChange> {
+        let mut changes = Vec::new();
+        let mut propose = |field, current: Option<String>, proposed: Option<String>| {
+            if let Some(proposed) = proposed.filter(|proposed| !proposed.is_empty()) {
+                if current.as_deref() != Some(proposed.as_str()) {
+                    changes.push(MetadataChange { field, current, proposed });
+                }
+            }
+        };
+        let format_date = |date: DateTime<Utc>| date.format("%Y-%m-%d").to_string();
+        let join = |values: &[String]| (!values.is_empty()).then(|| values.join(", "));
+
+        propose(MetadataField::Title, Some(book.title.clone()), Some(self.title.clone()));
+        propose(MetadataField::Authors, Some(book.author.clone()), join(&self.authors));
+        propose(MetadataField::Description, book.description.clone(), self.description.clone());
+        propose(MetadataField::Publisher, book.publisher.clone(), self.publisher.clone());
+        propose(MetadataField::PublicationDate, book.publication_date.map(format_date), self.published.map(format_date));
+        propose(MetadataField::Isbn, book.isbn.clone(), self.isbn.clone());
+        propose(
+            MetadataField::Series,
+            book.series.as_deref().map(|series| series_label(series, book.series_index)),
+            self.series.as_deref().map(|series| series_label(series, self.series_index)),
+        );
+        propose(MetadataField::Subjects, join(&book.subjects), join(&self.subjects));
+        propose(MetadataField::Language, book.language.clone(), self.language.clone());
+        propose(MetadataField::PageCount, book.page_count.map(|pages| pages.to_string()), self.page_count.map(|pages| pages.to_string()));
+        propose(MetadataField::Cover, book.cover_url.clone(), self.cover_url.clone());
+
+        changes
+    }
+
+    /// Copy the chosen fields onto a book
+    ///
+    /// Only `cover_url` is set for the cover; downloading it is left to the caller.
+    pub fn apply(&self, book: &mut Book, fields: &[MetadataField]) {
+        for field in fields {
+            match field {
+                MetadataField::Title if !self.title.is_empty() => book.title = self.title.clone(),
+                MetadataField::Authors if !self.authors.is_empty() => book.author = self.authors.join(", "),
+                MetadataField::Description if self.description.is_some() => book.description = self.description.clone(),
+                MetadataField::Publisher if self.publisher.is_some() => book.publisher = self.publisher.clone(),
+                MetadataField::PublicationDate if self.published.is_some() => book.publication_date = self.published,
+                MetadataField::Isbn if self.isbn.is_some() => book.isbn = self.isbn.clone(),
+                MetadataField::Series if self.series.is_some() => {
+                    book.series = self.series.clone();
+                    book.series_index = self.series_index;
+                }
+                MetadataField::Subjects if !self.subjects.is_empty() => book.subjects = self.subjects.clone(),
+                MetadataField::Language if self.language.is_some() => book.language = self.language.clone(),
+                MetadataField::PageCount if self.page_count.is_some() => book.page_count = self.page_count,
+                MetadataField::Cover if self.cover_url.is_some() => book.cover_url = self.cover_url.clone(),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Looks up book metadata in Google Books and Open Library
+pub struct MetadataService {
+    client: Client,
+}
+
+impl MetadataService {
+    pub fn new() -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(15))
+            .user_agent(concat!("ebook-reader/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self { client }
+    }
+
+    /// Look up a book by its ISBN, or by title and author when it has none
+    pub async fn lookup_book(&self, book: &Book) -> Result<Vec<MetadataCandidate>> {
+        match book.isbn.as_deref().and_then(normalize_isbn) {
+            Some(isbn) => self.lookup_isbn(&isbn).await,
+            None => self.search(&book.title, &book.author).await,
+        }
+    }
+
+    /// Look up an ISBN in both catalogs
+    pub async fn lookup_isbn(&self, isbn: &str) -> Result<Vec<MetadataCandidate>> {
+        let isbn = normalize_isbn(isbn).ok_or_else(|| anyhow!("Invalid ISBN: {}", isbn))?;
+        let bibkey = format!("ISBN:{}", isbn);
+        let google = self.fetch_json(GOOGLE_BOOKS_URL, vec![("q", format!("isbn:{}", isbn))]);
+        let open_library = self.fetch_json(
+            OPEN_LIBRARY_BOOKS_URL,
+            vec![("bibkeys", bibkey), ("format", "json".to_string()), ("jscmd", "details".to_string())],
+        );
+        let (google, open_library) = tokio::join!(google, open_library);
+
+        Self::combine(google.map(|json| parse_google_books(&json)), open_library.map(|json| parse_open_library_details(&json)))
+    }
+
+    /// Search both catalogs by title and author
+    pub async fn search(&self, title: &str, author: &str) -> Result<Vec<MetadataCandidate>> {
+        let mut query = format!("intitle:\"{}\"", title);
+        if !author.trim().is_empty() {
+            query.push_str(&format!(" inauthor:\"{}\"", author));
+        }
+        let google = self.fetch_json(GOOGLE_BOOKS_URL, vec![("q", query), ("maxResults", MAX_RESULTS.to_string())]);
+        let open_library = self.fetch_json(
+            OPEN_LIBRARY_SEARCH_URL,
+            vec![("title", title.to_string()), ("author", author.to_string()), ("limit", MAX_RESULTS.to_string())],
+        );
+        let (google, open_library) = tokio::join!(google, open_library);
+
+        Self::combine(google.map(|json| parse_google_books(&json)), open_library.map(|json| parse_open_library_search(&json)))
+    }
+
+    /// Download a candidate's cover image
+    pub async fn fetch_cover(&self, candidate: &MetadataCandidate) -> Result<Option<Vec<u8>>> {
+        let Some(url) = &candidate.cover_url else {
+            return Ok(None);
+        };
+        let response = self.client.get(url).send().await?.error_for_status()?;
+        Ok(Some(response.bytes().await?.to_vec()))
+    }
+
+    async fn fetch_json(&self, url: &str, query: Vec<(&str, String)>) -> Result<Value> {
+        let response = self.client.get(url).query(&query).send().await?.error_for_status()?;
+        Ok(response.json().await?)
+    }
+
+    /// Merge results, failing only when neither catalog could be reached
+    fn combine(
+        google: Result<Vec<MetadataCandidate>>,
+        open_library: Result<Vec<MetadataCandidate>>,
+    ) -> Result<Vec<MetadataCandidate>> {
+        match (google, open_library) {
+            (Err(google), Err(open_library)) => Err(anyhow!(
+                "Metadata lookup failed: Google Books: {}; Open Library: {}", google, open_library
+            )),
+            (google, open_library) => {
+                let mut candidates = Vec::new();
+                for (source, result) in [(MetadataSource::GoogleBooks, google), (MetadataSource::OpenLibrary, open_library)] {
+                    match result {
+                        Ok(found) => candidates.extend(found),
+                        Err(e) => warn!("{} lookup failed: {}", source.display_name(), e),
+                    }
+                }
+                Ok(candidates)
+            }
+        }
+    }
+}
+
+impl Default for MetadataService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read candidates from a Google Books volumes response
+pub fn parse_google_books(json: &Value) -> Vec<MetadataCandidate> {
+    let Some(items) = json["items"].as_array() else {
+        return Vec::new();
+    };
+
+    items.iter()
+        .filter_map(|item| {
+            let info = &item["volumeInfo"];
+            let mut title = info["title"].as_str()?.to_string();
+            if let Some(subtitle) = info["subtitle"].as_str() {
+                title = format!("{}: {}", title, subtitle);
+            }
+            let identifiers: Vec<(&str, &str)> = info["industryIdentifiers"].as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|identifier| Some((identifier["type"].as_str()?, identifier["identifier"].as_str()?)))
+                .collect();
+            let isbn = ["ISBN_13", "ISBN_10"].iter()
+                .find_map(|kind| identifiers.iter().find(|(scheme, _)| scheme == kind))
+                .and_then(|(_, value)| normalize_isbn(value));
+
+            Some(MetadataCandidate {
+                source: Some(MetadataSource::GoogleBooks),
+                title,
+                authors: strings(&info["authors"]),
+                description: info["description"].as_str().map(plain_text).filter(|text| !text.is_empty()),
+                publisher: info["publisher"].as_str().map(str::to_string),
+                published: info["publishedDate"].as_str().and_then(parse_catalog_date),
+                isbn,
+                series: None, // Google only exposes opaque series ids
+                series_index: None,
+                subjects: strings(&info["categories"]),
+                language: info["language"].as_str().map(str::to_string),
+                page_count: info["pageCount"].as_u64().map(|pages| pages as u32),
+                cover_url: ["extraLarge", "large", "medium", "thumbnail", "smallThumbnail"].iter()
+                    .find_map(|size| info["imageLinks"][size].as_str())
+                    .map(|url| url.replacen("http://", "https://", 1).replace("&edge=curl", "")),
+            })
+        })
+        .collect()
+}
+
+/// Read candidates from an Open Library books API response (`jscmd=details`)
+pub fn parse_open_library_details(json: &Value) -> Vec<MetadataCandidate> {
+    let Some(entries) = json.as_object() else {
+        return Vec::new();
+    };
+
+    entries.values()
+        .filter_map(|entry| {
+            let details = &entry["details"];
+            let (series, series_index) = strings(&details["series"]).first()
+                .map(|series| parse_series(series))
+                .unwrap_or_default();
+            let isbn = strings(&details["isbn_13"]).into_iter()
+                .chain(strings(&details["isbn_10"]))
+                .find_map(|isbn| normalize_isbn(&isbn));
+
+            Some(MetadataCandidate {
+                source: Some(MetadataSource::OpenLibrary),
+                title: details["title"].as_str()?.to_string(),
+                authors: details["authors"].as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|author| author["name"].as_str().map(str::to_string))
+                    .collect(),
+                // Descriptions are either plain strings or typed text values
+                description: details["description"].as_str()
+                    .or_else(|| details["description"]["value"].as_str())
+                    .map(plain_text)
+                    .filter(|text| !text.is_empty()),
+                publisher: strings(&details["publishers"]).into_iter().next(),
+                published: details["publish_date"].as_str().and_then(parse_catalog_date),
+                isbn,
+                series,
+                series_index,
+                subjects: strings(&details["subjects"]),
+                language: details["languages"].as_array()
+                    .and_then(|languages| languages.first())
+                    .and_then(|language| language["key"].as_str())
+                    .map(language_code),
+                page_count: details["number_of_pages"].as_u64().map(|pages| pages as u32),
+                cover_url: details["covers"].as_array()
+                    .and_then(|covers| covers.iter().find_map(Value::as_i64))
+                    .filter(|id| *id > 0)
+                    .map(|id| format!("{}/{}-L.jpg", OPEN_LIBRARY_COVER_URL, id)),
+            })
+        })
+        .collect()
+}
+
+/// Read candidates from an Open Library search response
+pub fn parse_open_library_search(json: &Value) -> Vec<MetadataCandidate> {
+    let Some(docs) = json["docs"].as_array() else {
+        return Vec::new();
+    };
+
+    docs.iter()
+        .filter_map(|doc| {
+            Some(MetadataCandidate {
+                source: Some(MetadataSource::OpenLibrary),
+                title: doc["title"].as_str()?.to_string(),
+                authors: strings(&doc["author_name"]),
+                publisher: strings(&doc["publisher"]).into_iter().next(),
+                published: doc["first_publish_year"].as_i64().and_then(|year| parse_date(&year.to_string())),
+                isbn: strings(&doc["isbn"]).iter().find_map(|isbn| normalize_isbn(isbn)),
+                subjects: strings(&doc["subject"]).into_iter().take(10).collect(),
+                language: strings(&doc["language"]).first().map(|language| language_code(language)),
+                page_count: doc["number_of_pages_median"].as_u64().map(|pages| pages as u32),
+                cover_url: doc["cover_i"].as_i64()
+                    .filter(|id| *id > 0)
+                    .map(|id| format!("{}/{}-L.jpg", OPEN_LIBRARY_COVER_URL, id)),
+                ..MetadataCandidate::default()
+            })
+        })
+        .collect()
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    value.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|item| item.as_str().map(|text| text.trim().to_string()))
+        .filter(|text| !text.is_empty())
+        .collect()
+}
+
+/// Parse catalog dates, which range from "2004-05-01" to "May 1, 2004"
+fn parse_catalog_date(date: &str) -> Option<DateTime<Utc>> {
+    parse_date(date).or_else(|| parse_date(&YEAR.captures(date)?[1]))
+}
+
+/// Split a series entry like "Discworld ; 3" or "Discworld, #3" into name and position
+fn parse_series(series: &str) -> (Option<String>, Option<f32>) {
+    let series = series.trim();
+    match SERIES_NUMBER.captures(series) {
+        Some(captures) => (Some(captures[1].trim().to_string()), captures[2].parse().ok()),
+        None => (Some(series.to_string()), None),
+    }
+}
+
+/// Language tag from an Open Library language key like "/languages/eng"
+fn language_code(key: &str) -> String {
+    let code = key.rsplit('/').next().unwrap_or(key);
+    match code {
+        "eng" => "en",
+        "fre" | "fra" => "fr",
+        "ger" | "deu" => "de",
+        "spa" => "es",
+        "por" => "pt",
+        "ita" => "it",
+        "dut" | "nld" => "nl",
+        "rus" => "ru",
+        "jpn" => "ja",
+        "chi" | "zho" => "zh",
+        "kor" => "ko",
+        other => other,
+    }
+    .to_string()
+}
+
+fn series_label(series: &str, index: Option<f32>) -> String {
+    match index {
+        Some(index) => format!("{} #{}", series, index),
+        None => series.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Datelike;
+    use crate::models::BookFormat;
+
+    #[test]
+    fn test_parse_catalog_responses() {
+        let google = serde_json::json!({
+            "items": [{
+                "volumeInfo": {
+                    "title": "Small Gods",
+                    "subtitle": "A Discworld Novel",
+                    "authors": ["Terry Pratchett"],
+                    "publisher": "Harper",
+                    "publishedDate": "2013-10",
+                    "description": "<p>Religion &amp; tortoises.</p>",
+                    "industryIdentifiers": [
+                        {"type": "ISBN_10", "identifier": "0062237373"},
+                        {"type": "ISBN_13", "identifier": "9780062237378"}
+                    ],
+                    "pageCount": 400,
+                    "categories": ["Fiction"],
+                    "language": "en",
+                    "imageLinks": {"thumbnail": "http://books.google.com/books/content?id=x&zoom=1&edge=curl"}
+                }
+            }]
+        });
+        let candidate = &parse_google_books(&google)[0];
+        assert_eq!(candidate.title, "Small Gods: A Discworld Novel");
+        assert_eq!(candidate.isbn.as_deref(), Some("9780062237378"));
+        assert_eq!(candidate.description.as_deref(), Some("Religion & tortoises."));
+        assert_eq!(candidate.published.map(|date| (date.year(), date.month())), Some((2013, 10)));
+        assert_eq!(candidate.cover_url.as_deref(), Some("https://books.google.com/books/content?id=x&zoom=1"));
+
+        let open_library = serde_json::json!({
+            "ISBN:9780062237378": {
+                "details": {
+                    "title": "Small Gods",
+                    "authors": [{"key": "/authors/OL25712A", "name": "Terry Pratchett"}],
+                    "publishers": ["HarperTorch"],
+                    "publish_date": "May 1, 2013",
+                    "series": ["Discworld ; 13"],
+                    "isbn_13": ["9780062237378"],
+                    "languages": [{"key": "/languages/eng"}],
+                    "description": {"type": "/type/text", "value": "Brutha is chosen."},
+                    "covers": [8231856]
+                }
+            }
+        });
+        let candidate = &parse_open_library_details(&open_library)[0];
+        assert_eq!((candidate.series.as_deref(), candidate.series_index), (Some("Discworld"), Some(13.0)));
+        assert_eq!(candidate.language.as_deref(), Some("en"));
+        assert_eq!(candidate.published.map(|date| date.year()), Some(2013));
+        assert_eq!(candidate.description.as_deref(), Some("Brutha is chosen."));
+        assert_eq!(candidate.cover_url.as_deref(), Some("https://covers.openlibrary.org/b/id/8231856-L.jpg"));
+
+        let search = serde_json::json!({
+            "docs": [{"title": "Small Gods", "author_name": ["Terry Pratchett"], "first_publish_year": 1992, "isbn": ["0575052643", "9780575052642"], "cover_i": 42}]
+        });
+        let candidate = &parse_open_library_search(&search)[0];
+        assert_eq!(candidate.isbn.as_deref(), Some("9780575052642"));
+        assert_eq!(candidate.published.map(|date| date.year()), Some(1992));
+    }
+
+    #[test]
+    fn test_review_and_apply_selected_fields() {
+        let mut book = Book::new("small gods".to_string(), "Terry Pratchett".to_string(), "small_gods.epub".into(), 0, BookFormat::Epub);
+        book.publisher = Some("Harper".to_string());
+        let candidate = MetadataCandidate {
+            source: Some(MetadataSource::OpenLibrary),
+            title: "Small Gods".to_string(),
+            authors: vec!["Terry Pratchett".to_string()],
+            publisher: Some("Harper".to_string()),
+            description: Some("Brutha is chosen.".to_string()),
+            series: Some("Discworld".to_string()),
+            series_index: Some(13.0),
+            ..MetadataCandidate::default()
+        };
+
+        let changes = candidate.review(&book);
+        let fields: Vec<MetadataField> = changes.iter().map(|change| change.field).collect();
+        assert_eq!(fields, vec![MetadataField::Title, MetadataField::Description, MetadataField::Series]);
+        assert_eq!(changes[2].proposed, "Discworld #13");
+
+        // Only the accepted fields are overwritten
+        candidate.apply(&mut book, &[MetadataField::Series]);
+        assert_eq!(book.title, "small gods");
+        assert_eq!(book.description, None);
+        assert_eq!((book.series.as_deref(), book.series_index), (Some("Discworld"), Some(13.0)));
+    }
+}
@@ -1,33 +1,135 @@
+pub mod alt_text_service;
+pub mod backup_service;
 pub mod book_service;
+pub mod calibre_library;
+pub mod citation_service;
+pub mod compatibility_ledger;
 pub mod database;
 pub mod database_initializer;
+pub mod destructive_confirmation;
+pub mod device_manager;
+pub mod dictionary_service;
+pub mod embedded_fonts;
+pub mod embedded_markup;
+pub use epubreader_core::{epub_metadata, epub_parser};
+pub mod epub_writer;
+pub mod media_overlays;
+pub mod export_share;
+pub mod folder_watcher;
+pub mod kindle_clippings;
+pub mod layout_service;
+pub mod navigation_history;
 pub mod path_resolver;
+pub mod pdf_parser;
+pub mod preferences_service;
 pub mod reading_service;
+pub mod reading_style_service;
+pub mod secrets;
 pub mod annotation_service;
+#[cfg(feature = "gui")]
 pub mod annotation_manager;
-pub mod annotation_export;
+pub use epubreader_core::annotation_export;
+pub mod library_archive;
+pub mod library_server;
 pub mod library_service;
+#[cfg(feature = "gui")]
 pub mod library_manager;
 pub mod book_search_service;
+pub mod search_indexer;
 pub mod sync_service;
+pub mod tts_service;
+pub mod vault_export;
 pub mod virtual_library_service;
+pub mod vocabulary_service;
+pub mod year_in_books;
+#[cfg(feature = "network")]
 pub mod async_image_loader;
+#[cfg(feature = "network")]
+pub mod kosync_service;
+#[cfg(feature = "network")]
+pub mod news_service;
+#[cfg(feature = "network")]
+pub mod read_later_service;
+#[cfg(feature = "network")]
+pub mod metadata_service;
+#[cfg(feature = "network")]
+pub mod online_lookup;
+#[cfg(feature = "network")]
+pub mod opds_service;
+#[cfg(feature = "network")]
+pub mod readwise_service;
+#[cfg(feature = "network")]
+pub mod send_to_device_service;
+#[cfg(feature = "performance-monitoring")]
 pub mod performance_monitor;
 pub mod optimized_virtual_grid;
 
+#[cfg(test)]
+mod epub_golden_tests;
+
+pub use alt_text_service::*;
+pub use backup_service::*;
 pub use book_service::*;
+pub use calibre_library::*;
+pub use citation_service::*;
+pub use compatibility_ledger::*;
 pub use database::*;
 pub use database_initializer::*;
+pub use destructive_confirmation::*;
+pub use device_manager::*;
+pub use dictionary_service::*;
+pub use embedded_fonts::*;
+pub use embedded_markup::*;
+pub use epub_metadata::*;
+pub use epub_parser::*;
+pub use epub_writer::*;
+pub use media_overlays::*;
+pub use export_share::*;
+pub use folder_watcher::*;
+pub use kindle_clippings::*;
+pub use layout_service::*;
+pub use navigation_history::*;
 pub use path_resolver::*;
+pub use pdf_parser::*;
+pub use preferences_service::*;
 pub use reading_service::*;
+pub use reading_style_service::*;
+pub use secrets::*;
 pub use annotation_service::*;
+#[cfg(feature = "gui")]
 pub use annotation_manager::*;
 pub use annotation_export::*;
+pub use library_archive::*;
+pub use library_server::*;
 pub use library_service::*;
+#[cfg(feature = "gui")]
 pub use library_manager::*;
 pub use book_search_service::*;
+pub use search_indexer::*;
 pub use sync_service::*;
+pub use tts_service::*;
+pub use vault_export::*;
 pub use virtual_library_service::*;
+pub use vocabulary_service::*;
+pub use year_in_books::*;
+#[cfg(feature = "network")]
 pub use async_image_loader::*;
+#[cfg(feature = "network")]
+pub use kosync_service::*;
+#[cfg(feature = "network")]
+pub use news_service::*;
+#[cfg(feature = "network")]
+pub use read_later_service::*;
+#[cfg(feature = "network")]
+pub use metadata_service::*;
+#[cfg(feature = "network")]
+pub use online_lookup::*;
+#[cfg(feature = "network")]
+pub use opds_service::*;
+#[cfg(feature = "network")]
+pub use readwise_service::*;
+#[cfg(feature = "network")]
+pub use send_to_device_service::*;
+#[cfg(feature = "performance-monitoring")]
 pub use performance_monitor::*;
 pub use optimized_virtual_grid::*;
\ No newline at end of file
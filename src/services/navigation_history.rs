@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::models::book::ReadingPosition;
+use crate::services::path_resolver::PathResolver;
+
+/// Number of positions kept in each direction per book
+const MAX_HISTORY_ENTRIES: usize = 100;
+
+/// What caused a jump within a book
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum NavigationSource {
+    TableOfContents,
+    SearchResult,
+    Footnote,
+    Link,
+    Bookmark,
+}
+
+/// A position left behind by a jump
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NavigationEntry {
+    pub position: ReadingPosition,
+    pub source: NavigationSource,
+}
+
+/// Back and forward stacks for a single book
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BookNavigationHistory {
+    back: Vec<NavigationEntry>,
+    forward: Vec<NavigationEntry>,
+}
+
+impl BookNavigationHistory {
+    /// Remember the position a jump started from, dropping the forward stack
+    pub fn record_jump(&mut self, from: ReadingPosition, source: NavigationSource) {
+        let is_repeat = self.back.last()
+            .is_some_and(|entry| same_position(&entry.position, &from));
+        if !is_repeat {
+            push_capped(&mut self.back, NavigationEntry { position: from, source });
+        }
+        self.forward.clear();
+    }
+
+    /// Step back from the current position, returning where to go
+    pub fn go_back(&mut self, current: ReadingPosition) -> Option<NavigationEntry> {
+        let entry = self.back.pop()?;
+        push_capped(&mut self.forward, NavigationEntry { position: current, source: entry.source.clone() });
+        Some(entry)
+    }
+
+    /// Step forward again after going back, returning where to go
+    pub fn go_forward(&mut self, current: ReadingPosition) -> Option<NavigationEntry> {
+        let entry = self.forward.pop()?;
+        push_capped(&mut self.back, NavigationEntry { position: current, source: entry.source.clone() });
+        Some(entry)
+    }
+
+    /// Check if there is a position to go back to
+    pub fn can_go_back(&self) -> bool {
+        !self.back.is_empty()
+    }
+
+    /// Check if there is a position to go forward to
+    pub fn can_go_forward(&self) -> bool {
+        !self.forward.is_empty()
+    }
+}
+
+/// Per-book navigation history persisted across sessions
+pub struct NavigationHistoryStore {
+    history_path: PathBuf,
+    histories: HashMap<String, BookNavigationHistory>,
+}
+
+impl NavigationHistoryStore {
+    /// Open the history stored at the given path, starting empty if it does not exist
+    pub fn open(history_path: PathBuf) -> Result<Self> {
+        let histories = if history_path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&history_path)?)?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self { history_path, histories })
+    }
+
+    /// Open the history in the application data directory
+    pub fn open_default() -> Result<Self> {
+        let app_dir = PathResolver::get_app_data_directory()?;
+        PathResolver::ensure_directory_exists(&app_dir)?;
+        Self::open(app_dir.join("navigation_history.json"))
+    }
+
+    /// Get the history for a book
+    pub fn history(&self, book_id: &str) -> Option<&BookNavigationHistory> {
+        self.histories.get(book_id)
+    }
+
+    /// Remember the position a jump started from
+    pub async fn record_jump(
+        &mut self,
+        book_id: &str,
+        from: ReadingPosition,
+        source: NavigationSource,
+    ) -> Result<()> {
+        self.histories.entry(book_id.to_string()).or_default().record_jump(from, source);
+        self.save().await
+    }
+
+    /// Step back within a book
+    pub async fn go_back(&mut self, book_id: &str, current: ReadingPosition) -> Result<Option<NavigationEntry>> {
+        let entry = match self.histories.get_mut(book_id) {
+            Some(history) => history.go_back(current),
+            None => None,
+        };
+        if entry.is_some() {
+            self.save().await?;
+        }
+        Ok(entry)
+    }
+
+    /// Step forward within a book
+    pub async fn go_forward(&mut self, book_id: &str, current: ReadingPosition) -> Result<Option<NavigationEntry>> {
+        let entry = match self.histories.get_mut(book_id) {
+            Some(history) => history.go_forward(current),
+            None => None,
+        };
+        if entry.is_some() {
+            self.save().await?;
+        }
+        Ok(entry)
+    }
+
+    /// Forget the history for a book
+    pub async fn clear(&mut self, book_id: &str) -> Result<()> {
+        if self.histories.remove(book_id).is_some() {
+            self.save().await?;
+        }
+        Ok(())
+    }
+
+    async fn save(&self) -> Result<()> {
+        let json = serde_json::to_string(&self.histories)?;
+        tokio::fs::write(&self.history_path, json).await?;
+        Ok(())
+    }
+}
+
+fn push_capped(stack: &mut Vec<NavigationEntry>, entry: NavigationEntry) {
+    stack.push(entry);
+    if stack.len() > MAX_HISTORY_ENTRIES {
+        stack.remove(0);
+    }
+}
+
+fn same_position(a: &ReadingPosition, b: &ReadingPosition) -> bool {
+    a.chapter_id == b.chapter_id
+        && a.page_number == b.page_number
+        && a.character_offset == b.character_offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use tempfile::TempDir;
+
+    fn position(chapter_id: &str, character_offset: u64) -> ReadingPosition {
+        ReadingPosition {
+            chapter_id: Some(chapter_id.to_string()),
+            page_number: None,
+            character_offset: Some(character_offset),
+            percentage: 0.0,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_footnote_round_trip_survives_reopen() {
+        let temp_dir = TempDir::new().unwrap();
+        let history_path = temp_dir.path().join("history.json");
+
+        let mut store = NavigationHistoryStore::open(history_path.clone()).unwrap();
+        store.record_jump("book", position("ch3", 120), NavigationSource::Footnote).await.unwrap();
+
+        let mut store = NavigationHistoryStore::open(history_path).unwrap();
+        let back = store.go_back("book", position("notes", 40)).await.unwrap().unwrap();
+        assert_eq!(back.position.chapter_id.as_deref(), Some("ch3"));
+        assert_eq!(back.source, NavigationSource::Footnote);
+
+        let forward = store.go_forward("book", position("ch3", 120)).await.unwrap().unwrap();
+        assert_eq!(forward.position.chapter_id.as_deref(), Some("notes"));
+        assert!(store.history("book").unwrap().can_go_back());
+        assert!(!store.history("book").unwrap().can_go_forward());
+    }
+
+    #[test]
+    fn test_new_jump_clears_forward_stack() {
+        let mut history = BookNavigationHistory::default();
+        history.record_jump(position("ch1", 0), NavigationSource::TableOfContents);
+        history.record_jump(position("ch1", 0), NavigationSource::TableOfContents);
+        history.go_back(position("ch5", 0));
+        assert!(history.can_go_forward());
+        assert!(!history.can_go_back());
+
+        history.record_jump(position("ch1", 0), NavigationSource::SearchResult);
+        assert!(!history.can_go_forward());
+    }
+}
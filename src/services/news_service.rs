@@ -0,0 +1,863 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::models::preferences::NewsDelivery;
+use crate::services::book_service::BookService;
+use crate::services::epub_metadata::{attributes, parse_date, plain_text, EpubContributor, EpubIdentifier, EpubMetadata};
+use crate::services::epub_parser::TocEntry;
+use crate::services::epub_writer::{EpubBook, EpubWriter};
+
+/// How often the schedule is checked for an issue that is due
+const CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// Images larger than this are left out of an issue
+const MAX_IMAGE_BYTES: usize = 2 * 1024 * 1024;
+const MAX_IMAGES_PER_ARTICLE: usize = 20;
+
+static ITEM: Lazy<Regex> = Lazy::new(|| element(r"item"));
+static ENTRY: Lazy<Regex> = Lazy::new(|| element(r"(?:\w+:)?entry"));
+static TITLE: Lazy<Regex> = Lazy::new(|| element(r"(?:\w+:)?title"));
+static LINK_TEXT: Lazy<Regex> = Lazy::new(|| element(r"link"));
+static LINK_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<(?:\w+:)?link\b([^>]*?)/?>").unwrap());
+static GUID: Lazy<Regex> = Lazy::new(|| element(r"guid|(?:\w+:)?id"));
+static DATE: Lazy<Regex> = Lazy::new(|| element(r"pubDate|(?:\w+:)?published|(?:\w+:)?updated|dc:date"));
+static AUTHOR: Lazy<Regex> = Lazy::new(|| element(r"dc:creator|(?:\w+:)?author"));
+static AUTHOR_NAME: Lazy<Regex> = Lazy::new(|| element(r"(?:\w+:)?name"));
+/// Article bodies, fullest first
+static CONTENT: Lazy<[Regex; 4]> = Lazy::new(|| {
+    [element(r"content:encoded"), element(r"(?:atom:)?content"), element(r"description"), element(r"(?:atom:)?summary")]
+});
+static CDATA: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<!\[CDATA\[(.*?)\]\]>").unwrap());
+static MARKUP: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?s)<!--.*?-->|<!\[CDATA\[.*?\]\]>|<[!?][^<>]*>|<(/?)([a-zA-Z][a-zA-Z0-9]*)([^<>]*)>|[^<]+|<").unwrap()
+});
+static TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"^<(/?)([a-zA-Z][a-zA-Z0-9]*)([^<>]*)>$").unwrap());
+
+/// Tags kept in articles; anything else is dropped and its text kept
+const ALLOWED_TAGS: &[&str] = &[
+    "a", "abbr", "b", "blockquote", "br", "caption", "cite", "code", "dd", "del", "div", "dl", "dt", "em",
+    "figcaption", "figure", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "i", "img", "ins", "kbd", "li", "ol",
+    "p", "pre", "q", "s", "small", "span", "strong", "sub", "sup", "table", "tbody", "td", "tfoot", "th",
+    "thead", "tr", "u", "ul",
+];
+/// Tags dropped along with everything inside them
+const DROPPED_TAGS: &[&str] = &["script", "style", "iframe", "object", "noscript", "form", "svg", "video", "audio", "template", "nav", "aside", "footer"];
+const VOID_TAGS: &[&str] = &["br", "hr", "img"];
+
+const ISSUE_STYLESHEET: &str = "body { margin: 0 1em; }\n\
+h1 { font-size: 1.4em; margin-bottom: 0.2em; }\n\
+.byline, .source { color: #666; font-size: 0.85em; }\n\
+img { max-width: 100%; height: auto; }\n\
+.contents li { margin-bottom: 0.4em; }\n";
+
+fn element(names: &str) -> Regex {
+    Regex::new(&format!(r"(?is)<(?:{names})\b[^>]*>(.*?)</(?:{names})\s*>")).unwrap()
+}
+
+/// A subscribed RSS or Atom feed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NewsFeed {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    pub added_at: DateTime<Utc>,
+    pub last_fetched_at: Option<DateTime<Utc>>,
+}
+
+/// An article read from a feed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NewsArticle {
+    pub guid: String,
+    pub title: String,
+    pub link: Option<String>,
+    pub author: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub content: String, // HTML as the feed published it
+}
+
+/// A feed document, parsed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedFeed {
+    pub title: String,
+    pub articles: Vec<NewsArticle>,
+}
+
+/// A periodical added to the library
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewsIssue {
+    pub book_id: String,
+    pub title: String,
+    pub articles: usize,
+}
+
+/// Article HTML made into well-formed XHTML
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CleanedHtml {
+    pub xhtml: String,
+    pub image_urls: Vec<String>, // Absolute, in the order they appear
+}
+
+/// What an issue is called and who it is from
+pub(crate) struct IssueInfo<'a> {
+    pub(crate) identifier: &'a str, // Kept across rebuilds of a book that is updated in place
+    pub(crate) title: &'a str,
+    pub(crate) creator: &'a str,
+    pub(crate) subject: &'a str,
+    pub(crate) date: DateTime<Utc>,
+}
+
+/// Articles of one feed in an issue
+pub(crate) struct IssueSection {
+    pub(crate) title: String,
+    pub(crate) articles: Vec<IssueArticle>,
+}
+
+/// An article's page in an issue
+pub(crate) struct IssueArticle {
+    pub(crate) id: String, // Manifest id and file name of the page
+    pub(crate) article: NewsArticle,
+    pub(crate) cleaned: CleanedHtml,
+}
+
+/// An image downloaded for an issue
+pub(crate) struct IssueImage {
+    pub(crate) file_name: String,
+    pub(crate) media_type: String,
+    pub(crate) data: Vec<u8>,
+}
+
+/// Fetches RSS and Atom feeds and delivers their articles as periodical EPUBs
+///
+/// Articles are kept until an issue carries them, so each appears in one
+/// issue only. Issues are written to the news folder and added to the
+/// library like any other book.
+pub struct NewsService {
+    pool: SqlitePool,
+    client: Client,
+    book_service: Arc<BookService>,
+    output_dir: PathBuf,
+    delivery: Arc<RwLock<NewsDelivery>>,
+}
+
+impl NewsService {
+    pub fn new(pool: SqlitePool, book_service: Arc<BookService>, output_dir: PathBuf) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(60))
+            .user_agent(concat!("ebook-reader/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self { pool, client, book_service, output_dir, delivery: Arc::new(RwLock::new(NewsDelivery::default())) }
+    }
+
+    /// Initialize news tables
+    pub async fn init_tables(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS news_feeds (
+                id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                url TEXT NOT NULL UNIQUE,
+                added_at TEXT NOT NULL,
+                last_fetched_at TEXT
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Delivered articles keep their row, emptied, so they are not fetched again
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS news_articles (
+                feed_id TEXT NOT NULL,
+                guid TEXT NOT NULL,
+                article TEXT NOT NULL,
+                published_at TEXT,
+                fetched_at TEXT NOT NULL,
+                delivered_at TEXT,
+                PRIMARY KEY (feed_id, guid),
+                FOREIGN KEY (feed_id) REFERENCES news_feeds (id) ON DELETE CASCADE
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE TABLE IF NOT EXISTS news_state (id INTEGER PRIMARY KEY CHECK (id = 1), last_delivered_at TEXT);")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Set how often issues are delivered and what goes into them
+    pub async fn set_delivery(&self, delivery: NewsDelivery) {
+        *self.delivery.write().await = delivery;
+    }
+
+    pub async fn get_delivery(&self) -> NewsDelivery {
+        self.delivery.read().await.clone()
+    }
+
+    /// Subscribe to a feed, fetching it once to check it and learn its title
+    pub async fn subscribe(&self, url: &str) -> Result<NewsFeed> {
+        let url = Url::parse(url.trim()).map_err(|e| anyhow!("Invalid feed address {}: {}", url, e))?;
+        let parsed = self.fetch(&url).await?;
+        let feed = NewsFeed {
+            id: Uuid::new_v4().to_string(),
+            title: if parsed.title.is_empty() { url.host_str().unwrap_or_default().to_string() } else { parsed.title.clone() },
+            url: url.to_string(),
+            added_at: Utc::now(),
+            last_fetched_at: None,
+        };
+        sqlx::query("INSERT INTO news_feeds (id, title, url, added_at) VALUES (?, ?, ?, ?)")
+            .bind(&feed.id)
+            .bind(&feed.title)
+            .bind(&feed.url)
+            .bind(feed.added_at.to_rfc3339())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| anyhow!("Already subscribed to {}", feed.url))?;
+        self.store_articles(&feed.id, &parsed.articles).await?;
+        Ok(feed)
+    }
+
+    pub async fn unsubscribe(&self, feed_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM news_articles WHERE feed_id = ?")
+            .bind(feed_id)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("DELETE FROM news_feeds WHERE id = ?")
+            .bind(feed_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_feeds(&self) -> Result<Vec<NewsFeed>> {
+        let rows = sqlx::query("SELECT * FROM news_feeds ORDER BY title COLLATE NOCASE")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter()
+            .map(|row| Ok(NewsFeed {
+                id: row.get("id"),
+                title: row.get("title"),
+                url: row.get("url"),
+                added_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("added_at"))?.with_timezone(&Utc),
+                last_fetched_at: row.get::<Option<String>, _>("last_fetched_at")
+                    .and_then(|at| DateTime::parse_from_rfc3339(&at).ok())
+                    .map(|at| at.with_timezone(&Utc)),
+            }))
+            .collect()
+    }
+
+    /// Fetch a feed and keep the articles not seen before; returns how many were new
+    pub async fn refresh_feed(&self, feed: &NewsFeed) -> Result<usize> {
+        let parsed = self.fetch(&Url::parse(&feed.url)?).await?;
+        let added = self.store_articles(&feed.id, &parsed.articles).await?;
+        sqlx::query("UPDATE news_feeds SET last_fetched_at = ? WHERE id = ?")
+            .bind(Utc::now().to_rfc3339())
+            .bind(&feed.id)
+            .execute(&self.pool)
+            .await?;
+        Ok(added)
+    }
+
+    /// Fetch every feed; a feed that fails is logged and skipped
+    pub async fn refresh_all(&self) -> Result<usize> {
+        let mut added = 0;
+        for feed in self.get_feeds().await? {
+            match self.refresh_feed(&feed).await {
+                Ok(count) => added += count,
+                Err(e) => warn!("Failed to fetch feed {}: {}", feed.url, e),
+            }
+        }
+        Ok(added)
+    }
+
+    /// Store articles, skipping those already stored
+    pub async fn store_articles(&self, feed_id: &str, articles: &[NewsArticle]) -> Result<usize> {
+        let mut added = 0;
+        for article in articles {
+            let result = sqlx::query(
+                "INSERT OR IGNORE INTO news_articles (feed_id, guid, article, published_at, fetched_at) VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(feed_id)
+            .bind(&article.guid)
+            .bind(serde_json::to_string(article)?)
+            .bind(article.published_at.map(|at| at.to_rfc3339()))
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+            added += result.rows_affected() as usize;
+        }
+        Ok(added)
+    }
+
+    /// Articles waiting for an issue, newest first, by feed
+    pub async fn pending_articles(&self) -> Result<Vec<(NewsFeed, Vec<NewsArticle>)>> {
+        let mut pending = Vec::new();
+        for feed in self.get_feeds().await? {
+            let rows = sqlx::query(
+                "SELECT article FROM news_articles WHERE feed_id = ? AND delivered_at IS NULL \
+                 ORDER BY COALESCE(published_at, fetched_at) DESC",
+            )
+            .bind(&feed.id)
+            .fetch_all(&self.pool)
+            .await?;
+            let articles = rows.iter()
+                .map(|row| Ok(serde_json::from_str(&row.get::<String, _>("article"))?))
+                .collect::<Result<Vec<NewsArticle>>>()?;
+            if !articles.is_empty() {
+                pending.push((feed, articles));
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Take deliveries whenever the schedule says one is due, until the task is dropped
+    pub async fn run(&self) {
+        let mut checks = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            checks.tick().await;
+            match self.deliver_if_due().await {
+                Ok(Some(issue)) => info!("Delivered {} with {} articles", issue.title, issue.articles),
+                Ok(None) => {}
+                Err(e) => warn!("Scheduled news delivery failed: {}", e),
+            }
+        }
+    }
+
+    /// Deliver an issue if the last one is older than the delivery interval
+    pub async fn deliver_if_due(&self) -> Result<Option<NewsIssue>> {
+        let delivery = self.get_delivery().await;
+        if !delivery.enabled {
+            return Ok(None);
+        }
+        let last: Option<String> = sqlx::query_scalar("SELECT last_delivered_at FROM news_state WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await?
+            .flatten();
+        if let Some(last) = last.and_then(|last| DateTime::parse_from_rfc3339(&last).ok()) {
+            if Utc::now() - last.with_timezone(&Utc) < chrono::Duration::hours(delivery.interval_hours as i64) {
+                return Ok(None);
+            }
+        }
+        self.deliver_now().await
+    }
+
+    /// Fetch every feed and bundle what is new into an issue; `None` when there is nothing new
+    pub async fn deliver_now(&self) -> Result<Option<NewsIssue>> {
+        self.refresh_all().await?;
+        self.deliver_pending().await
+    }
+
+    /// Bundle the articles already fetched into an issue
+    ///
+    /// Only the newest articles of each feed go in; older ones left over are dropped.
+    pub async fn deliver_pending(&self) -> Result<Option<NewsIssue>> {
+        let delivery = self.get_delivery().await;
+        let pending = self.pending_articles().await?;
+        sqlx::query("INSERT OR REPLACE INTO news_state (id, last_delivered_at) VALUES (1, ?)")
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        if pending.is_empty() {
+            return Ok(None);
+        }
+
+        let mut sections = Vec::new();
+        let mut image_urls = Vec::new();
+        for (section_index, (feed, articles)) in pending.iter().enumerate() {
+            let base = Url::parse(&feed.url).ok();
+            let articles: Vec<IssueArticle> = articles.iter()
+                .take(delivery.max_articles_per_feed.max(1))
+                .enumerate()
+                .map(|(article_index, article)| {
+                    let base = article.link.as_deref().and_then(|link| Url::parse(link).ok()).or_else(|| base.clone());
+                    let cleaned = clean_html(&article.content, base.as_ref(), delivery.include_images);
+                    image_urls.extend(cleaned.image_urls.iter().take(MAX_IMAGES_PER_ARTICLE).cloned());
+                    IssueArticle { id: format!("s{}-a{}", section_index + 1, article_index + 1), article: article.clone(), cleaned }
+                })
+                .collect();
+            sections.push(IssueSection { title: feed.title.clone(), articles });
+        }
+        let images = download_images(&self.client, &image_urls).await;
+
+        let now = Utc::now();
+        let title = format!("News — {}", now.format("%Y-%m-%d %H:%M"));
+        let identifier = format!("urn:uuid:{}", Uuid::new_v4());
+        let info = IssueInfo { identifier: &identifier, title: &title, creator: "ePubReader News", subject: "News", date: now };
+        let epub = build_issue(&info, &sections, &images)?;
+        tokio::fs::create_dir_all(&self.output_dir).await?;
+        let path = self.output_dir.join(format!("News-{}.epub", now.format("%Y%m%d-%H%M%S")));
+        tokio::fs::write(&path, epub).await?;
+        let book_id = self.book_service.add_book(&path).await?;
+
+        for (feed, _) in &pending {
+            sqlx::query("UPDATE news_articles SET article = '{}', delivered_at = ? WHERE feed_id = ? AND delivered_at IS NULL")
+                .bind(now.to_rfc3339())
+                .bind(&feed.id)
+                .execute(&self.pool)
+                .await?;
+        }
+        let articles = sections.iter().map(|section| section.articles.len()).sum();
+        Ok(Some(NewsIssue { book_id, title, articles }))
+    }
+
+    async fn fetch(&self, url: &Url) -> Result<ParsedFeed> {
+        let response = self.client.get(url.clone())
+            .header("Accept", "application/rss+xml, application/atom+xml, application/xml;q=0.9, */*;q=0.8")
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("The feed returned {}", response.status()));
+        }
+        let parsed = parse_feed(&response.text().await?);
+        if parsed.articles.is_empty() && parsed.title.is_empty() {
+            return Err(anyhow!("{} is not an RSS or Atom feed", url));
+        }
+        Ok(parsed)
+    }
+
+}
+
+/// Download images for an issue; those that fail or are too large are left out
+pub(crate) async fn download_images(client: &Client, urls: &[String]) -> HashMap<String, IssueImage> {
+    let mut images = HashMap::new();
+    for url in urls {
+        if images.contains_key(url) {
+            continue;
+        }
+        match download_image(client, url).await {
+            Ok((media_type, data)) => {
+                let extension = match media_type.as_str() {
+                    "image/jpeg" => "jpg",
+                    "image/png" => "png",
+                    "image/gif" => "gif",
+                    "image/webp" => "webp",
+                    _ => continue,
+                };
+                let file_name = format!("image{}.{}", images.len() + 1, extension);
+                images.insert(url.clone(), IssueImage { file_name, media_type, data });
+            }
+            Err(e) => warn!("Leaving out image {}: {}", url, e),
+        }
+    }
+    images
+}
+
+async fn download_image(client: &Client, url: &str) -> Result<(String, Vec<u8>)> {
+    let response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("{}", response.status()));
+    }
+    if response.content_length().is_some_and(|length| length as usize > MAX_IMAGE_BYTES) {
+        return Err(anyhow!("too large"));
+    }
+    let media_type = response.headers().get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(';').next().unwrap_or_default().trim().to_lowercase())
+        .unwrap_or_default();
+    let data = response.bytes().await?;
+    if data.len() > MAX_IMAGE_BYTES {
+        return Err(anyhow!("too large"));
+    }
+    Ok((media_type, data.to_vec()))
+}
+
+/// Read the articles of an RSS 2.0 or Atom feed
+pub fn parse_feed(xml: &str) -> ParsedFeed {
+    let items: Vec<&str> = match ITEM.captures_iter(xml).map(|c| c.get(1).unwrap().as_str()).collect::<Vec<_>>() {
+        items if !items.is_empty() => items,
+        _ => ENTRY.captures_iter(xml).map(|c| c.get(1).unwrap().as_str()).collect(),
+    };
+    // The feed's own title comes before its first article
+    let head_end = ITEM.find(xml).or_else(|| ENTRY.find(xml)).map_or(xml.len(), |m| m.start());
+    let title = TITLE.captures(&xml[..head_end]).map(|c| plain_text(&text_value(&c[1]))).unwrap_or_default();
+
+    let articles = items.into_iter()
+        .filter_map(|item| {
+            let title = TITLE.captures(item).map(|c| plain_text(&text_value(&c[1]))).unwrap_or_default();
+            let link = article_link(item);
+            let guid = GUID.captures(item)
+                .map(|c| text_value(&c[1]).trim().to_string())
+                .filter(|guid| !guid.is_empty())
+                .or_else(|| link.clone())
+                .or_else(|| (!title.is_empty()).then(|| title.clone()))?;
+            let author = AUTHOR.captures(item).map(|c| {
+                let author = c.get(1).unwrap().as_str();
+                AUTHOR_NAME.captures(author).map_or_else(|| plain_text(&text_value(author)), |name| plain_text(&text_value(&name[1])))
+            });
+            let published_at = DATE.captures(item).and_then(|c| {
+                let date = text_value(&c[1]);
+                DateTime::parse_from_rfc2822(date.trim()).map(|at| at.with_timezone(&Utc)).ok().or_else(|| parse_date(&date))
+            });
+            let content = CONTENT.iter()
+                .find_map(|content| content.captures(item))
+                .map(|c| text_value(&c[1]))
+                .unwrap_or_default();
+            Some(NewsArticle {
+                guid,
+                title: if title.is_empty() { "Untitled".to_string() } else { title },
+                link,
+                author: author.filter(|author| !author.is_empty()),
+                published_at,
+                content,
+            })
+        })
+        .collect();
+    ParsedFeed { title, articles }
+}
+
+/// An RSS `<link>` text, or an Atom alternate link
+fn article_link(item: &str) -> Option<String> {
+    if let Some(link) = LINK_TEXT.captures(item).map(|c| text_value(&c[1]).trim().to_string()) {
+        if !link.is_empty() {
+            return Some(link);
+        }
+    }
+    LINK_TAG.captures_iter(item)
+        .map(|c| attributes(&c[1]))
+        .find(|attrs| attrs.get("rel").is_none_or(|rel| rel == "alternate"))
+        .and_then(|attrs| attrs.get("href").cloned())
+}
+
+/// Text of an element: CDATA as written, anything else with entities decoded
+fn text_value(raw: &str) -> String {
+    if CDATA.is_match(raw) {
+        return CDATA.captures_iter(raw).map(|c| c[1].to_string()).collect();
+    }
+    let trimmed = raw.trim();
+    // Atom `type="xhtml"` content is inline markup, already escaped as it should be
+    if trimmed.starts_with('<') {
+        return trimmed.to_string();
+    }
+    html_escape::decode_html_entities(raw).into_owned()
+}
+
+/// Make article HTML into well-formed XHTML an e-reader can show
+///
+/// Scripts, embeds and styling are removed, unknown tags are unwrapped,
+/// unclosed tags are closed, and links and images are made absolute. Images
+/// are dropped unless `keep_images` is set.
+pub fn clean_html(html: &str, base: Option<&Url>, keep_images: bool) -> CleanedHtml {
+    let mut cleaned = CleanedHtml::default();
+    let mut open: Vec<&'static str> = Vec::new();
+    let mut dropping: Option<(String, usize)> = None;
+
+    for token in MARKUP.find_iter(html).map(|m| m.as_str()) {
+        let Some(tag) = TAG.captures(token) else {
+            if dropping.is_none() && !token.starts_with("<!") && !token.starts_with("<?") {
+                let text = if token == "<" { "<".into() } else { html_escape::decode_html_entities(token) };
+                cleaned.xhtml.push_str(&html_escape::encode_text(&text));
+            }
+            continue;
+        };
+        let closing = !tag[1].is_empty();
+        let name = tag[2].to_lowercase();
+
+        if let Some((dropped, depth)) = dropping.as_mut() {
+            if *dropped == name && !tag[3].trim_end().ends_with('/') {
+                if closing {
+                    *depth -= 1;
+                } else {
+                    *depth += 1;
+                }
+                if *depth == 0 {
+                    dropping = None;
+                }
+            }
+            continue;
+        }
+        if DROPPED_TAGS.contains(&name.as_str()) {
+            if !closing && !tag[3].trim_end().ends_with('/') {
+                dropping = Some((name, 1));
+            }
+            continue;
+        }
+        let Some(&name) = ALLOWED_TAGS.iter().find(|allowed| **allowed == name) else {
+            continue;
+        };
+
+        if closing {
+            if let Some(position) = open.iter().rposition(|open| *open == name) {
+                for unclosed in open.drain(position..).rev() {
+                    cleaned.xhtml.push_str(&format!("</{}>", unclosed));
+                }
+            }
+            continue;
+        }
+
+        let attrs = attributes(&tag[3]);
+        let resolve = |href: &str| match base {
+            Some(base) => base.join(href.trim()).ok().map(String::from),
+            None => Url::parse(href.trim()).ok().map(String::from),
+        };
+        let mut kept = String::new();
+        match name {
+            "img" => {
+                let Some(src) = attrs.get("src").and_then(|src| resolve(src)).filter(|_| keep_images) else {
+                    continue;
+                };
+                kept.push_str(&format!(
+                    " src=\"{}\" alt=\"{}\"",
+                    html_escape::encode_double_quoted_attribute(&src),
+                    html_escape::encode_double_quoted_attribute(attrs.get("alt").map_or("", String::as_str)),
+                ));
+                cleaned.image_urls.push(src);
+            }
+            "a" => {
+                if let Some(href) = attrs.get("href").and_then(|href| resolve(href)).filter(|href| href.starts_with("http")) {
+                    kept.push_str(&format!(" href=\"{}\"", html_escape::encode_double_quoted_attribute(&href)));
+                }
+            }
+            "td" | "th" => {
+                for span in ["colspan", "rowspan"] {
+                    if let Some(value) = attrs.get(span).filter(|value| value.chars().all(|c| c.is_ascii_digit())) {
+                        kept.push_str(&format!(" {}=\"{}\"", span, value));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if VOID_TAGS.contains(&name) {
+            cleaned.xhtml.push_str(&format!("<{}{}/>", name, kept));
+        } else {
+            cleaned.xhtml.push_str(&format!("<{}{}>", name, kept));
+            open.push(name);
+        }
+    }
+
+    for unclosed in open.into_iter().rev() {
+        cleaned.xhtml.push_str(&format!("</{}>", unclosed));
+    }
+    cleaned
+}
+
+/// Package articles as an EPUB 3 periodical, one section per feed
+pub(crate) fn build_issue(info: &IssueInfo, sections: &[IssueSection], images: &HashMap<String, IssueImage>) -> Result<Vec<u8>> {
+    let feeds: Vec<&str> = sections.iter().map(|section| section.title.as_str()).collect();
+    let metadata = EpubMetadata {
+        title: Some(info.title.to_string()),
+        creators: vec![EpubContributor { name: info.creator.to_string(), role: None, file_as: None }],
+        published: Some(info.date.format("%Y-%m-%d").to_string()),
+        description: Some(feeds.join(", ")),
+        language: Some("en".to_string()),
+        subjects: vec![info.subject.to_string()],
+        identifiers: vec![EpubIdentifier { scheme: None, value: info.identifier.to_string() }],
+        ..EpubMetadata::default()
+    };
+    let mut book = EpubBook::new(metadata, "OEBPS");
+    book.add_resource("style", "OEBPS/style.css", "text/css", ISSUE_STYLESHEET.as_bytes().to_vec());
+
+    let mut play_order = 0;
+    for (section_index, section) in sections.iter().enumerate() {
+        play_order += 1;
+        let section_order = play_order;
+        let section_id = format!("s{}", section_index + 1);
+        let mut contents = String::new();
+        let mut children = Vec::new();
+        let mut pages = Vec::new();
+        for IssueArticle { id, article, cleaned } in &section.articles {
+            contents.push_str(&format!(
+                "<li><a href=\"{}.xhtml\">{}</a></li>\n",
+                id,
+                html_escape::encode_text(&article.title),
+            ));
+            play_order += 1;
+            children.push(TocEntry {
+                label: article.title.clone(),
+                href: format!("OEBPS/text/{}.xhtml", id),
+                play_order,
+                children: Vec::new(),
+            });
+            pages.push((id.clone(), article_page(&section.title, article, &cleaned.xhtml, images)));
+        }
+
+        let section_page = xhtml_page(
+            &section.title,
+            &format!(
+                "<h1>{}</h1>\n<ol class=\"contents\">\n{}</ol>\n",
+                html_escape::encode_text(&section.title),
+                contents,
+            ),
+        );
+        for (id, page) in std::iter::once((section_id.clone(), section_page)).chain(pages) {
+            book.add_document(&id, &format!("OEBPS/text/{}.xhtml", id), page);
+        }
+        book.toc.push(TocEntry {
+            label: section.title.clone(),
+            href: format!("OEBPS/text/{}.xhtml", section_id),
+            play_order: section_order,
+            children,
+        });
+    }
+
+    let mut images: Vec<&IssueImage> = images.values().collect();
+    images.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    for image in images {
+        book.add_resource(
+            &image.file_name.replace('.', "-"),
+            &format!("OEBPS/images/{}", image.file_name),
+            &image.media_type,
+            image.data.clone(),
+        );
+    }
+
+    EpubWriter::write(&book)
+}
+
+fn article_page(feed: &str, article: &NewsArticle, content: &str, images: &HashMap<String, IssueImage>) -> String {
+    let mut content = content.to_string();
+    for src in cleaned_image_sources(&content) {
+        let encoded = html_escape::encode_double_quoted_attribute(&src).into_owned();
+        content = match images.get(&src) {
+            Some(image) => content.replace(&format!("src=\"{}\"", encoded), &format!("src=\"../images/{}\"", image.file_name)),
+            None => Regex::new(&format!(r#"<img src="{}"[^>]*/>"#, regex::escape(&encoded)))
+                .map(|tag| tag.replace_all(&content, "").into_owned())
+                .unwrap_or(content),
+        };
+    }
+
+    let byline: Vec<String> = [Some(feed.to_string()), article.author.clone(), article.published_at.map(|at| at.format("%B %-d, %Y").to_string())]
+        .into_iter()
+        .flatten()
+        .collect();
+    let source = article.link.as_deref().map_or(String::new(), |link| {
+        format!("<p class=\"source\"><a href=\"{}\">Read online</a></p>\n", html_escape::encode_double_quoted_attribute(link))
+    });
+    xhtml_page(&article.title, &format!(
+        "<h1>{}</h1>\n<p class=\"byline\">{}</p>\n<div>{}</div>\n{}",
+        html_escape::encode_text(&article.title),
+        html_escape::encode_text(&byline.join(" · ")),
+        content,
+        source,
+    ))
+}
+
+/// Image sources written by `clean_html`, decoded
+fn cleaned_image_sources(xhtml: &str) -> Vec<String> {
+    static IMAGE_SOURCE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"<img src="([^"]*)""#).unwrap());
+    IMAGE_SOURCE.captures_iter(xhtml)
+        .map(|c| html_escape::decode_html_entities(&c[1]).into_owned())
+        .collect()
+}
+
+fn xhtml_page(title: &str, body: &str) -> String {
+    format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<!DOCTYPE html>\n",
+            "<html xmlns=\"http://www.w3.org/1999/xhtml\">\n",
+            "<head>\n  <title>{}</title>\n  <link rel=\"stylesheet\" type=\"text/css\" href=\"../style.css\"/>\n</head>\n",
+            "<body>\n{}</body>\n",
+            "</html>\n",
+        ),
+        html_escape::encode_text(title),
+        body,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::database::DatabaseService;
+    use crate::services::epub_parser::EpubParser;
+    use crate::utils::image_cache::ImageCache;
+
+    const RSS: &str = r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/" xmlns:dc="http://purl.org/dc/elements/1.1/">
+<channel><title>Tech &amp; Science</title><link>https://news.example.org/</link>
+<item>
+  <title><![CDATA[Rust 2.0 <em>released</em>]]></title>
+  <link>https://news.example.org/rust</link>
+  <guid isPermaLink="false">rust-2</guid>
+  <pubDate>Tue, 14 Oct 2025 09:30:00 +0200</pubDate>
+  <dc:creator>Ferris</dc:creator>
+  <description>Short summary</description>
+  <content:encoded><![CDATA[<p>Big news<br>today &nbsp;<img src="/img/crab.png" alt="Crab"></p><script>alert(1)</script><section><p>Read more</section>]]></content:encoded>
+</item>
+</channel></rss>"#;
+
+    const ATOM: &str = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title type="text">Physics Blog</title>
+<entry><title>Quarks</title><id>tag:physics,2025:1</id><updated>2025-10-13T08:00:00Z</updated>
+<link rel="alternate" href="https://physics.example.org/quarks"/><author><name>Ada</name></author>
+<summary>Up &amp; down &lt;b&gt;quarks&lt;/b&gt;</summary></entry></feed>"#;
+
+    #[test]
+    fn test_parse_feeds_and_clean_articles() {
+        let rss = parse_feed(RSS);
+        assert_eq!(rss.title, "Tech & Science");
+        let article = &rss.articles[0];
+        assert_eq!((article.guid.as_str(), article.title.as_str()), ("rust-2", "Rust 2.0 released"));
+        assert_eq!(article.author.as_deref(), Some("Ferris"));
+        assert_eq!(article.published_at.unwrap().to_rfc3339(), "2025-10-14T07:30:00+00:00");
+
+        let base = Url::parse(article.link.as_deref().unwrap()).unwrap();
+        let cleaned = clean_html(&article.content, Some(&base), true);
+        assert_eq!(
+            cleaned.xhtml,
+            "<p>Big news<br/>today \u{a0}<img src=\"https://news.example.org/img/crab.png\" alt=\"Crab\"/></p><p>Read more</p>",
+        );
+        assert_eq!(cleaned.image_urls, ["https://news.example.org/img/crab.png"]);
+        assert!(!clean_html(&article.content, Some(&base), false).xhtml.contains("<img"));
+
+        let atom = parse_feed(ATOM);
+        assert_eq!(atom.title, "Physics Blog");
+        let quarks = &atom.articles[0];
+        assert_eq!(quarks.link.as_deref(), Some("https://physics.example.org/quarks"));
+        assert_eq!((quarks.guid.as_str(), quarks.author.as_deref()), ("tag:physics,2025:1", Some("Ada")));
+        assert_eq!(clean_html(&quarks.content, None, true).xhtml, "Up &amp; down <b>quarks</b>");
+    }
+
+    #[tokio::test]
+    async fn test_delivery_adds_issue_with_nested_toc_to_library() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = Arc::new(DatabaseService::new_in_memory().await.unwrap());
+        let image_cache = Arc::new(ImageCache::new(dir.path().join("covers")).unwrap());
+        let book_service = Arc::new(BookService::new(database.clone(), image_cache));
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let news = NewsService::new(pool, book_service, dir.path().join("news"));
+        news.init_tables().await.unwrap();
+        news.set_delivery(NewsDelivery { include_images: false, ..NewsDelivery::default() }).await;
+        assert_eq!(news.deliver_pending().await.unwrap(), None);
+
+        sqlx::query("INSERT INTO news_feeds (id, title, url, added_at) VALUES ('tech', 'Tech & Science', 'https://news.example.org/feed', ?)")
+            .bind(Utc::now().to_rfc3339())
+            .execute(&news.pool)
+            .await
+            .unwrap();
+        let articles = parse_feed(RSS).articles;
+        assert_eq!(news.store_articles("tech", &articles).await.unwrap(), 1);
+        let issue = news.deliver_pending().await.unwrap().unwrap();
+        assert_eq!(issue.articles, 1);
+        assert!(news.pending_articles().await.unwrap().is_empty());
+        assert_eq!(news.store_articles("tech", &articles).await.unwrap(), 0);
+
+        let book = database.get_book_by_id(&issue.book_id).await.unwrap();
+        assert_eq!(book.title, issue.title);
+        let mut doc = EpubParser::open(&book.file_path, None).unwrap();
+        let toc = EpubParser::table_of_contents(&mut doc);
+        assert_eq!(toc[0].label, "Tech & Science");
+        assert_eq!(toc[0].children[0].label, "Rust 2.0 released");
+        assert_eq!(toc[0].children[0].href, "OEBPS/text/s1-a1.xhtml");
+
+        let page = EpubParser::read_text(&mut doc, "s1-a1").unwrap();
+        assert!(page.contains("<p class=\"byline\">Tech &amp; Science · Ferris · October 14, 2025</p>"));
+        assert!(page.contains("<div><p>Big news<br/>today \u{a0}</p><p>Read more</p></div>"));
+    }
+}
@@ -0,0 +1,379 @@
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use reqwest::{Client, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{Row, SqlitePool};
+use tracing::warn;
+
+use crate::services::dictionary_service::language_key;
+use crate::services::epub_metadata::plain_text;
+
+/// Wiktionary's definition API lives on the English site and covers every language
+const WIKTIONARY_DEFINITION_URL: &str = "https://en.wiktionary.org/api/rest_v1/page/definition/";
+const WIKTIONARY_PAGE_URL: &str = "https://en.wiktionary.org/wiki/";
+/// Days a saved lookup is shown without asking again
+const CACHE_DAYS: i64 = 30;
+/// Definitions kept per part of speech on a Wiktionary card
+const MAX_DEFINITIONS: usize = 3;
+
+/// Where a term is looked up online
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LookupSource {
+    Wikipedia,
+    Wiktionary,
+}
+
+impl LookupSource {
+    /// Get display name
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            LookupSource::Wikipedia => "Wikipedia",
+            LookupSource::Wiktionary => "Wiktionary",
+        }
+    }
+
+    pub fn to_string(&self) -> String {
+        match self {
+            LookupSource::Wikipedia => "wikipedia".to_string(),
+            LookupSource::Wiktionary => "wiktionary".to_string(),
+        }
+    }
+
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s {
+            "wikipedia" => Some(LookupSource::Wikipedia),
+            "wiktionary" => Some(LookupSource::Wiktionary),
+            _ => None,
+        }
+    }
+}
+
+/// A short summary of a term, shown as a card over the text
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SummaryCard {
+    pub source: LookupSource,
+    pub language: String,
+    pub term: String,
+    pub title: String,
+    pub description: Option<String>, // Wikipedia's one-line description
+    pub extract: String,
+    pub thumbnail_url: Option<String>,
+    pub page_url: Option<String>,
+    pub fetched_at: DateTime<Utc>,
+    pub from_cache: bool, // Saved earlier rather than fetched now
+}
+
+/// Looks terms up on Wikipedia and Wiktionary, keeping what it finds for offline use
+///
+/// Saved cards are shown again for `CACHE_DAYS` days without a request, and
+/// after that whenever the sites can't be reached.
+#[derive(Clone)]
+pub struct OnlineLookupService {
+    client: Client,
+    pool: SqlitePool,
+}
+
+impl OnlineLookupService {
+    pub fn new(pool: SqlitePool) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(10))
+            .user_agent(concat!("ebook-reader/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self { client, pool }
+    }
+
+    /// Initialize online lookup tables
+    pub async fn init_tables(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS online_lookups (
+                source TEXT NOT NULL,
+                language TEXT NOT NULL,
+                term_key TEXT NOT NULL,
+                term TEXT NOT NULL,
+                title TEXT NOT NULL,
+                description TEXT,
+                extract TEXT NOT NULL,
+                thumbnail_url TEXT,
+                page_url TEXT,
+                fetched_at TEXT NOT NULL,
+                PRIMARY KEY (source, language, term_key)
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Look a term up, returning None when the source has no page for it
+    pub async fn lookup(&self, term: &str, language: &str, source: LookupSource) -> Result<Option<SummaryCard>> {
+        let term = term.trim();
+        let language = language_key(language).ok_or_else(|| anyhow!("No language given for the lookup"))?;
+        if term.is_empty() {
+            return Ok(None);
+        }
+
+        let cached = self.cached_card(source, &language, term).await?;
+        if let Some(card) = &cached {
+            if Utc::now() - card.fetched_at < chrono::Duration::days(CACHE_DAYS) {
+                return Ok(cached);
+            }
+        }
+
+        let fetched = match source {
+            LookupSource::Wikipedia => self.fetch_wikipedia(term, &language).await,
+            LookupSource::Wiktionary => self.fetch_wiktionary(term, &language).await,
+        };
+        self.settle(fetched, cached).await
+    }
+
+    /// Save a fetched card, or fall back to the saved one when fetching failed
+    async fn settle(&self, fetched: Result<Option<SummaryCard>>, cached: Option<SummaryCard>) -> Result<Option<SummaryCard>> {
+        match (fetched, cached) {
+            (Ok(Some(card)), _) => {
+                self.save_card(&card).await?;
+                Ok(Some(card))
+            }
+            (Ok(None), _) => Ok(None),
+            (Err(e), Some(card)) => {
+                warn!("{} lookup of {:?} failed, showing the saved card: {}", card.source.display_name(), card.term, e);
+                Ok(Some(card))
+            }
+            (Err(e), None) => Err(e),
+        }
+    }
+
+    /// Saved cards, most recently fetched first
+    pub async fn get_saved_lookups(&self, limit: usize) -> Result<Vec<SummaryCard>> {
+        let rows = sqlx::query("SELECT * FROM online_lookups ORDER BY fetched_at DESC LIMIT ?")
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(Self::row_to_card).collect()
+    }
+
+    pub async fn clear_saved_lookups(&self) -> Result<()> {
+        sqlx::query("DELETE FROM online_lookups").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn cached_card(&self, source: LookupSource, language: &str, term: &str) -> Result<Option<SummaryCard>> {
+        let row = sqlx::query("SELECT * FROM online_lookups WHERE source = ? AND language = ? AND term_key = ?")
+            .bind(source.to_string())
+            .bind(language)
+            .bind(term.to_lowercase())
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(Self::row_to_card).transpose()
+    }
+
+    async fn save_card(&self, card: &SummaryCard) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO online_lookups \
+             (source, language, term_key, term, title, description, extract, thumbnail_url, page_url, fetched_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(card.source.to_string())
+        .bind(&card.language)
+        .bind(card.term.to_lowercase())
+        .bind(&card.term)
+        .bind(&card.title)
+        .bind(&card.description)
+        .bind(&card.extract)
+        .bind(&card.thumbnail_url)
+        .bind(&card.page_url)
+        .bind(card.fetched_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn fetch_wikipedia(&self, term: &str, language: &str) -> Result<Option<SummaryCard>> {
+        let url = page_url(&format!("https://{}.wikipedia.org/api/rest_v1/page/summary/", language), term)?;
+        let Some(json) = self.fetch_json(url).await? else {
+            return Ok(None);
+        };
+        Ok(parse_wikipedia_summary(&json, term, language))
+    }
+
+    async fn fetch_wiktionary(&self, term: &str, language: &str) -> Result<Option<SummaryCard>> {
+        let Some(json) = self.fetch_json(page_url(WIKTIONARY_DEFINITION_URL, term)?).await? else {
+            return Ok(None);
+        };
+        Ok(parse_wiktionary_definitions(&json, term, language))
+    }
+
+    /// Fetch JSON, with None for pages that don't exist
+    async fn fetch_json(&self, url: Url) -> Result<Option<Value>> {
+        let response = self.client.get(url).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(response.error_for_status()?.json().await?))
+    }
+
+    fn row_to_card(row: &sqlx::sqlite::SqliteRow) -> Result<SummaryCard> {
+        let source: String = row.get("source");
+        let fetched_at: String = row.get("fetched_at");
+        Ok(SummaryCard {
+            source: LookupSource::from_string(&source).ok_or_else(|| anyhow!("Unknown lookup source: {}", source))?,
+            language: row.get("language"),
+            term: row.get("term"),
+            title: row.get("title"),
+            description: row.get("description"),
+            extract: row.get("extract"),
+            thumbnail_url: row.get("thumbnail_url"),
+            page_url: row.get("page_url"),
+            fetched_at: DateTime::parse_from_rfc3339(&fetched_at)?.with_timezone(&Utc),
+            from_cache: true,
+        })
+    }
+}
+
+/// A page's URL, with the term as its last path segment
+fn page_url(base: &str, term: &str) -> Result<Url> {
+    let mut url = Url::parse(base)?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("Invalid lookup URL: {}", base))?
+        .pop_if_empty()
+        .push(&term.replace(' ', "_"));
+    Ok(url)
+}
+
+/// Read a Wikipedia page summary
+pub fn parse_wikipedia_summary(json: &Value, term: &str, language: &str) -> Option<SummaryCard> {
+    let extract = json["extract"].as_str().map(str::trim).filter(|extract| !extract.is_empty())?;
+    Some(SummaryCard {
+        source: LookupSource::Wikipedia,
+        language: language.to_string(),
+        term: term.to_string(),
+        title: json["title"].as_str().unwrap_or(term).to_string(),
+        description: json["description"].as_str().map(str::to_string),
+        extract: extract.to_string(),
+        thumbnail_url: json["thumbnail"]["source"].as_str().map(str::to_string),
+        page_url: json["content_urls"]["desktop"]["page"].as_str().map(str::to_string),
+        fetched_at: Utc::now(),
+        from_cache: false,
+    })
+}
+
+/// Read Wiktionary's definitions of a term in one language
+///
+/// The definitions come grouped by language code, then by part of speech.
+/// Each part of speech becomes a line of the card's extract.
+pub fn parse_wiktionary_definitions(json: &Value, term: &str, language: &str) -> Option<SummaryCard> {
+    let usages = json[language].as_array()?;
+    let mut lines = Vec::new();
+    for usage in usages {
+        let definitions: Vec<String> = usage["definitions"].as_array()
+            .map(|definitions| {
+                definitions.iter()
+                    .filter_map(|definition| definition["definition"].as_str())
+                    .map(plain_text)
+                    .filter(|definition| !definition.is_empty())
+                    .take(MAX_DEFINITIONS)
+                    .collect()
+            })
+            .unwrap_or_default();
+        if definitions.is_empty() {
+            continue;
+        }
+        let numbered: Vec<String> = definitions.iter()
+            .enumerate()
+            .map(|(index, definition)| format!("{}. {}", index + 1, definition))
+            .collect();
+        match usage["partOfSpeech"].as_str() {
+            Some(part_of_speech) => lines.push(format!("{}: {}", part_of_speech, numbered.join(" "))),
+            None => lines.push(numbered.join(" ")),
+        }
+    }
+    if lines.is_empty() {
+        return None;
+    }
+
+    let language_name = usages.first().and_then(|usage| usage["language"].as_str());
+    Some(SummaryCard {
+        source: LookupSource::Wiktionary,
+        language: language.to_string(),
+        term: term.to_string(),
+        title: term.to_string(),
+        description: language_name.map(str::to_string),
+        extract: lines.join("\n"),
+        thumbnail_url: None,
+        page_url: page_url(WIKTIONARY_PAGE_URL, term).ok().map(|url| {
+            let anchor = language_name.map(|name| format!("#{}", name.replace(' ', "_"))).unwrap_or_default();
+            format!("{}{}", url, anchor)
+        }),
+        fetched_at: Utc::now(),
+        from_cache: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_summary_and_definitions() {
+        let summary = serde_json::json!({
+            "type": "standard",
+            "title": "Photosynthesis",
+            "description": "Biological process to convert light into chemical energy",
+            "extract": "Photosynthesis is a system of biological processes. ",
+            "thumbnail": {"source": "https://upload.wikimedia.org/leaf.png"},
+            "content_urls": {"desktop": {"page": "https://en.wikipedia.org/wiki/Photosynthesis"}}
+        });
+        let card = parse_wikipedia_summary(&summary, "photosynthesis", "en").unwrap();
+        assert_eq!(card.title, "Photosynthesis");
+        assert_eq!(card.extract, "Photosynthesis is a system of biological processes.");
+        assert_eq!(card.page_url.as_deref(), Some("https://en.wikipedia.org/wiki/Photosynthesis"));
+        assert!(parse_wikipedia_summary(&serde_json::json!({"title": "Empty"}), "empty", "en").is_none());
+
+        let definitions = serde_json::json!({
+            "en": [{"partOfSpeech": "Noun", "language": "English", "definitions": [{"definition": "A wolf."}]}],
+            "fr": [
+                {"partOfSpeech": "Noun", "language": "French", "definitions": [
+                    {"definition": "<a href=\"/wiki/wolf\">wolf</a>"},
+                    {"definition": "(<i>figuratively</i>) a cruel person"}
+                ]},
+                {"partOfSpeech": "Verb", "language": "French", "definitions": [{"definition": ""}]}
+            ]
+        });
+        let card = parse_wiktionary_definitions(&definitions, "loup de mer", "fr").unwrap();
+        assert_eq!(card.extract, "Noun: 1. wolf 2. (figuratively) a cruel person");
+        assert_eq!(card.description.as_deref(), Some("French"));
+        assert_eq!(card.page_url.as_deref(), Some("https://en.wiktionary.org/wiki/loup_de_mer#French"));
+        assert!(parse_wiktionary_definitions(&definitions, "loup", "de").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_saved_lookups_are_used_offline() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let service = OnlineLookupService::new(pool);
+        service.init_tables().await.unwrap();
+
+        let mut card = parse_wikipedia_summary(
+            &serde_json::json!({"title": "Lisbon", "extract": "Lisbon is the capital of Portugal."}),
+            "Lisbon",
+            "en",
+        )
+        .unwrap();
+        card.fetched_at = Utc::now() - chrono::Duration::days(CACHE_DAYS + 1);
+        service.settle(Ok(Some(card.clone())), None).await.unwrap();
+
+        // Stale, so it would be fetched again; the fetch fails and the saved card is shown
+        let cached = service.cached_card(LookupSource::Wikipedia, "en", "lisbon").await.unwrap();
+        let shown = service.settle(Err(anyhow!("offline")), cached).await.unwrap().unwrap();
+        assert_eq!(shown, SummaryCard { from_cache: true, ..card });
+
+        let missing = service.cached_card(LookupSource::Wiktionary, "en", "lisbon").await.unwrap();
+        assert!(service.settle(Err(anyhow!("offline")), missing).await.is_err());
+        assert_eq!(service.get_saved_lookups(10).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,606 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{Row, SqlitePool};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::broadcast;
+use tracing::warn;
+
+use crate::services::book_service::BookService;
+use crate::services::epub_metadata::{attributes, plain_text};
+use crate::services::secrets;
+use crate::utils::url_encoding::encode_query;
+
+/// Catalogs offered when adding one; Calibre-web serves its own at `http://<host>:8083/opds`
+pub const SUGGESTED_CATALOGS: &[(&str, &str)] = &[
+    ("Project Gutenberg", "https://www.gutenberg.org/ebooks.opds/"),
+    ("Standard Ebooks", "https://standardebooks.org/feeds/opds"),
+];
+const ACQUISITION_REL: &str = "http://opds-spec.org/acquisition";
+const IMAGE_REL: &str = "http://opds-spec.org/image";
+const OPENSEARCH_TYPE: &str = "application/opensearchdescription+xml";
+/// Book files a catalog may offer, in the order they are preferred
+const DOWNLOAD_TYPES: &[(&str, &str)] = &[
+    ("application/epub+zip", "epub"),
+    ("application/pdf", "pdf"),
+    ("application/x-mobipocket-ebook", "mobi"),
+    ("application/vnd.amazon.ebook", "azw3"),
+    ("text/plain", "txt"),
+    ("text/html", "html"),
+];
+
+static ENTRY: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<(?:\w+:)?entry\b[^>]*>(.*?)</(?:\w+:)?entry\s*>").unwrap());
+static LINK: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<(?:\w+:)?link\b([^>]*?)/?>").unwrap());
+static TITLE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<(?:\w+:)?title\b[^>]*>(.*?)</(?:\w+:)?title\s*>").unwrap());
+static ID: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<(?:\w+:)?id\b[^>]*>(.*?)</(?:\w+:)?id\s*>").unwrap());
+static UPDATED: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<(?:\w+:)?updated\b[^>]*>(.*?)</(?:\w+:)?updated\s*>").unwrap());
+static SUMMARY: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?is)<(?:\w+:)?(summary|content)\b[^>]*>(.*?)</(?:\w+:)?(?:summary|content)\s*>").unwrap()
+});
+static AUTHOR_NAME: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?is)<(?:\w+:)?author\b[^>]*>.*?<(?:\w+:)?name\b[^>]*>(.*?)</(?:\w+:)?name\s*>.*?</(?:\w+:)?author\s*>").unwrap()
+});
+static SEARCH_URL: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<(?:\w+:)?Url\b([^>]*?)/?>").unwrap());
+static OPTIONAL_PARAMETER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{[\w:]+\?\}").unwrap());
+static QUERY_EXPRESSION: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\?([\w,]+)\}").unwrap());
+
+/// An OPDS catalog the reader added
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OpdsCatalog {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    pub username: Option<String>, // For catalogs behind HTTP basic auth; the password is kept in the keyring
+    pub added_at: DateTime<Utc>,
+}
+
+/// A book file offered by a catalog entry
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OpdsAcquisition {
+    pub url: String,
+    pub mime_type: String,
+    pub open_access: bool, // Free to download, rather than to buy or borrow
+}
+
+/// A book or a link to another feed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OpdsEntry {
+    pub id: String,
+    pub title: String,
+    pub authors: Vec<String>,
+    pub summary: Option<String>,
+    pub updated: Option<DateTime<Utc>>,
+    pub cover_url: Option<String>,
+    pub navigation_url: Option<String>, // Set on entries that lead to another feed
+    pub acquisitions: Vec<OpdsAcquisition>,
+}
+
+impl OpdsEntry {
+    /// The book file to download: the most preferred format that is free to take
+    pub fn best_acquisition(&self) -> Option<(&OpdsAcquisition, &'static str)> {
+        DOWNLOAD_TYPES.iter().find_map(|(mime_type, extension)| {
+            self.acquisitions.iter()
+                .find(|acquisition| acquisition.open_access && mime_base(&acquisition.mime_type) == *mime_type)
+                .map(|acquisition| (acquisition, *extension))
+        })
+    }
+}
+
+/// One page of an OPDS 1.2 or 2.0 feed, with links resolved against its URL
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OpdsFeed {
+    pub url: String,
+    pub title: String,
+    pub entries: Vec<OpdsEntry>,
+    pub next_url: Option<String>,
+    pub previous_url: Option<String>,
+    pub search_template: Option<String>,    // URL with `{searchTerms}` or `{?query}` in it
+    pub search_description: Option<String>, // OpenSearch description to read the template from
+}
+
+/// Progress of a download into the library
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpdsDownloadEvent {
+    Progress { title: String, downloaded: u64, total: Option<u64> },
+    Finished { title: String, book_id: String },
+    Failed { title: String, error: String },
+}
+
+/// Browses OPDS catalogs and downloads their books into the library
+pub struct OpdsService {
+    pool: SqlitePool,
+    client: Client,
+    book_service: Arc<BookService>,
+    download_dir: PathBuf,
+    events: broadcast::Sender<OpdsDownloadEvent>,
+}
+
+impl OpdsService {
+    pub fn new(pool: SqlitePool, book_service: Arc<BookService>, download_dir: PathBuf) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(300))
+            .user_agent(concat!("ebook-reader/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("Failed to create HTTP client");
+        let (events, _) = broadcast::channel(64);
+
+        Self { pool, client, book_service, download_dir, events }
+    }
+
+    /// Initialize OPDS tables
+    pub async fn init_tables(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS opds_catalogs (
+                id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                url TEXT NOT NULL UNIQUE,
+                username TEXT,
+                added_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Receive progress of every download
+    pub fn subscribe_events(&self) -> broadcast::Receiver<OpdsDownloadEvent> {
+        self.events.subscribe()
+    }
+
+    /// Add a catalog; the password, if any, goes to the keyring
+    pub async fn add_catalog(&self, title: &str, url: &str, username: Option<&str>, password: Option<&str>) -> Result<OpdsCatalog> {
+        let url = Url::parse(url.trim()).map_err(|e| anyhow!("Invalid catalog address {}: {}", url, e))?;
+        let catalog = OpdsCatalog {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: title.trim().to_string(),
+            url: url.to_string(),
+            username: username.map(str::to_string),
+            added_at: Utc::now(),
+        };
+
+        sqlx::query("INSERT INTO opds_catalogs (id, title, url, username, added_at) VALUES (?, ?, ?, ?, ?)")
+            .bind(&catalog.id)
+            .bind(&catalog.title)
+            .bind(&catalog.url)
+            .bind(&catalog.username)
+            .bind(catalog.added_at.to_rfc3339())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| anyhow!("Could not add catalog {}: {}", catalog.url, e))?;
+        if let Some(password) = password {
+            secrets::store_api_key_async(&password_key(&catalog.id), password).await?;
+        }
+        Ok(catalog)
+    }
+
+    pub async fn get_catalogs(&self) -> Result<Vec<OpdsCatalog>> {
+        let rows = sqlx::query("SELECT id, title, url, username, added_at FROM opds_catalogs ORDER BY title COLLATE NOCASE")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(OpdsCatalog {
+                    id: row.get("id"),
+                    title: row.get("title"),
+                    url: row.get("url"),
+                    username: row.get("username"),
+                    added_at: DateTime::parse_from_rfc3339(&row.get::<String, _>("added_at"))?.with_timezone(&Utc),
+                })
+            })
+            .collect()
+    }
+
+    pub async fn remove_catalog(&self, catalog_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM opds_catalogs WHERE id = ?")
+            .bind(catalog_id)
+            .execute(&self.pool)
+            .await?;
+        if let Err(e) = secrets::remove_api_key_async(&password_key(catalog_id)).await {
+            warn!("Failed to remove the password of catalog {}: {}", catalog_id, e);
+        }
+        Ok(())
+    }
+
+    /// Fetch a feed of a catalog: its root, or a page or section found while browsing
+    pub async fn browse(&self, catalog: &OpdsCatalog, url: Option<&str>) -> Result<OpdsFeed> {
+        let url = Url::parse(url.unwrap_or(&catalog.url))?;
+        let response = self.request(catalog, &url).await.send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("The catalog returned {} for {}", response.status(), url));
+        }
+        let url = response.url().clone();
+        parse_feed(&response.text().await?, &url)
+    }
+
+    /// Search a catalog from any of its feeds that offers search
+    pub async fn search(&self, catalog: &OpdsCatalog, feed: &OpdsFeed, query: &str) -> Result<OpdsFeed> {
+        let template = match (&feed.search_template, &feed.search_description) {
+            (Some(template), _) => template.clone(),
+            (None, Some(description_url)) => {
+                let url = Url::parse(description_url)?;
+                let description = self.request(catalog, &url).await.send().await?.text().await?;
+                parse_search_template(&description, &url)
+                    .ok_or_else(|| anyhow!("The catalog's search description has no feed URL"))?
+            }
+            (None, None) => return Err(anyhow!("{} cannot be searched", feed.title)),
+        };
+        self.browse(catalog, Some(&expand_search_template(&template, query))).await
+    }
+
+    /// Download an entry's book into the library, sending progress events; returns the new book's id
+    pub async fn download(&self, catalog: &OpdsCatalog, entry: &OpdsEntry) -> Result<String> {
+        let result = self.download_book(catalog, entry).await;
+        let event = match &result {
+            Ok(book_id) => OpdsDownloadEvent::Finished { title: entry.title.clone(), book_id: book_id.clone() },
+            Err(e) => OpdsDownloadEvent::Failed { title: entry.title.clone(), error: e.to_string() },
+        };
+        let _ = self.events.send(event);
+        result
+    }
+
+    async fn download_book(&self, catalog: &OpdsCatalog, entry: &OpdsEntry) -> Result<String> {
+        let (acquisition, extension) = entry.best_acquisition()
+            .ok_or_else(|| anyhow!("{} has no free download in a supported format", entry.title))?;
+        let url = Url::parse(&acquisition.url)?;
+        let mut response = self.request(catalog, &url).await.send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("The catalog returned {} for {}", response.status(), url));
+        }
+
+        tokio::fs::create_dir_all(&self.download_dir).await?;
+        let path = unused_path(&self.download_dir, &file_stem(entry), extension);
+        let total = response.content_length();
+        let mut downloaded = 0u64;
+        let mut file = tokio::fs::File::create(&path).await?;
+        let written: Result<()> = async {
+            while let Some(chunk) = response.chunk().await? {
+                file.write_all(&chunk).await?;
+                downloaded += chunk.len() as u64;
+                let _ = self.events.send(OpdsDownloadEvent::Progress { title: entry.title.clone(), downloaded, total });
+            }
+            file.flush().await?;
+            Ok(())
+        }
+        .await;
+
+        let added = match written {
+            Ok(()) => self.book_service.add_book(&path).await,
+            Err(e) => Err(e),
+        };
+        if added.is_err() {
+            let _ = tokio::fs::remove_file(&path).await;
+        }
+        added
+    }
+
+    async fn request(&self, catalog: &OpdsCatalog, url: &Url) -> RequestBuilder {
+        let request = self.client.get(url.clone())
+            .header("Accept", "application/opds+json, application/atom+xml;q=0.9, */*;q=0.5");
+        match &catalog.username {
+            Some(username) => request.basic_auth(username, secrets::get_api_key_async(&password_key(&catalog.id)).await),
+            None => request,
+        }
+    }
+}
+
+fn password_key(catalog_id: &str) -> String {
+    format!("opds:{}", catalog_id)
+}
+
+/// Parse an OPDS 2.0 (JSON) or OPDS 1.2 (Atom) feed
+pub fn parse_feed(body: &str, url: &Url) -> Result<OpdsFeed> {
+    if body.trim_start().starts_with('{') {
+        parse_opds2(&serde_json::from_str(body)?, url)
+    } else if ENTRY.is_match(body) || body.contains("<feed") {
+        Ok(parse_opds1(body, url))
+    } else {
+        Err(anyhow!("{} is not an OPDS feed", url))
+    }
+}
+
+fn parse_opds1(body: &str, url: &Url) -> OpdsFeed {
+    let header = ENTRY.replace_all(body, "");
+    let mut feed = OpdsFeed {
+        url: url.to_string(),
+        title: first_text(&TITLE, &header).unwrap_or_default(),
+        ..OpdsFeed::default()
+    };
+    for link in LINK.captures_iter(&header).map(|captures| attributes(&captures[1])) {
+        let Some(href) = link.get("href").and_then(|href| resolve(url, href)) else { continue };
+        let link_type = link.get("type").map(String::as_str).unwrap_or_default();
+        match link.get("rel").map(String::as_str).unwrap_or_default() {
+            "next" => feed.next_url = Some(href),
+            "previous" | "prev" => feed.previous_url = Some(href),
+            "search" if link.get("href").is_some_and(|href| href.contains("{searchTerms}")) => {
+                feed.search_template = link.get("href").and_then(|href| resolve_template(url, href))
+            }
+            "search" if link_type == OPENSEARCH_TYPE => feed.search_description = Some(href),
+            _ => {}
+        }
+    }
+
+    for entry in ENTRY.captures_iter(body) {
+        let entry = &entry[1];
+        let mut parsed = OpdsEntry {
+            id: first_text(&ID, entry).unwrap_or_default(),
+            title: first_text(&TITLE, entry).unwrap_or_default(),
+            authors: AUTHOR_NAME.captures_iter(entry).map(|captures| plain_text(&captures[1])).collect(),
+            summary: SUMMARY.captures(entry).map(|captures| plain_text(&captures[2])).filter(|text| !text.is_empty()),
+            updated: first_text(&UPDATED, entry)
+                .and_then(|updated| DateTime::parse_from_rfc3339(&updated).ok())
+                .map(|updated| updated.with_timezone(&Utc)),
+            cover_url: None,
+            navigation_url: None,
+            acquisitions: Vec::new(),
+        };
+        for link in LINK.captures_iter(entry).map(|captures| attributes(&captures[1])) {
+            let Some(href) = link.get("href").and_then(|href| resolve(url, href)) else { continue };
+            let rel = link.get("rel").map(String::as_str).unwrap_or_default();
+            let link_type = link.get("type").cloned().unwrap_or_default();
+            add_link(&mut parsed, rel, &link_type, href);
+        }
+        feed.entries.push(parsed);
+    }
+    feed
+}
+
+fn parse_opds2(json: &Value, url: &Url) -> Result<OpdsFeed> {
+    let metadata = json.get("metadata").ok_or_else(|| anyhow!("{} is not an OPDS 2.0 feed", url))?;
+    let mut feed = OpdsFeed {
+        url: url.to_string(),
+        title: metadata["title"].as_str().unwrap_or_default().to_string(),
+        ..OpdsFeed::default()
+    };
+    for link in json["links"].as_array().into_iter().flatten() {
+        let Some(raw_href) = link["href"].as_str() else { continue };
+        for rel in rels(link) {
+            match rel {
+                "next" => feed.next_url = resolve(url, raw_href),
+                "previous" | "prev" => feed.previous_url = resolve(url, raw_href),
+                "search" if link["templated"].as_bool() == Some(true) => feed.search_template = resolve_template(url, raw_href),
+                _ => {}
+            }
+        }
+    }
+
+    let groups = std::iter::once(json).chain(json["groups"].as_array().into_iter().flatten());
+    for group in groups {
+        for link in group["navigation"].as_array().into_iter().flatten() {
+            let Some(href) = link["href"].as_str().and_then(|href| resolve(url, href)) else { continue };
+            let title = link["title"].as_str().unwrap_or_default().to_string();
+            feed.entries.push(OpdsEntry {
+                id: href.clone(),
+                title,
+                authors: Vec::new(),
+                summary: None,
+                updated: None,
+                cover_url: None,
+                navigation_url: Some(href),
+                acquisitions: Vec::new(),
+            });
+        }
+        for publication in group["publications"].as_array().into_iter().flatten() {
+            let metadata = &publication["metadata"];
+            let mut entry = OpdsEntry {
+                id: metadata["identifier"].as_str().unwrap_or_default().to_string(),
+                title: metadata["title"].as_str().unwrap_or_default().to_string(),
+                authors: contributor_names(&metadata["author"]),
+                summary: metadata["description"].as_str().map(plain_text).filter(|text| !text.is_empty()),
+                updated: metadata["modified"].as_str()
+                    .and_then(|updated| DateTime::parse_from_rfc3339(updated).ok())
+                    .map(|updated| updated.with_timezone(&Utc)),
+                cover_url: None,
+                navigation_url: None,
+                acquisitions: Vec::new(),
+            };
+            let links = publication["links"].as_array().into_iter().flatten()
+                .chain(publication["images"].as_array().into_iter().flatten().take(1));
+            for link in links {
+                let Some(href) = link["href"].as_str().and_then(|href| resolve(url, href)) else { continue };
+                let link_type = link["type"].as_str().unwrap_or_default();
+                let link_rels = rels(link);
+                let rel = if link_rels.is_empty() && link_type.starts_with("image/") { IMAGE_REL } else { link_rels.first().copied().unwrap_or_default() };
+                add_link(&mut entry, rel, link_type, href);
+            }
+            feed.entries.push(entry);
+        }
+    }
+    Ok(feed)
+}
+
+/// Sort an entry's link into a cover, a feed to browse or a book file
+fn add_link(entry: &mut OpdsEntry, rel: &str, link_type: &str, href: String) {
+    if rel.starts_with(ACQUISITION_REL) {
+        let open_access = rel == ACQUISITION_REL || rel.ends_with("/open-access");
+        entry.acquisitions.push(OpdsAcquisition { url: href, mime_type: link_type.to_string(), open_access });
+    } else if rel.starts_with(IMAGE_REL) {
+        // The full image is preferred over the thumbnail
+        if entry.cover_url.is_none() || rel == IMAGE_REL {
+            entry.cover_url = Some(href);
+        }
+    } else if (link_type.contains("application/atom+xml") || link_type.contains("application/opds+json"))
+        && entry.navigation_url.is_none()
+    {
+        entry.navigation_url = Some(href);
+    }
+}
+
+/// The Atom feed template of an OpenSearch description
+fn parse_search_template(description: &str, url: &Url) -> Option<String> {
+    SEARCH_URL.captures_iter(description)
+        .map(|captures| attributes(&captures[1]))
+        .find(|search| search.get("type").is_some_and(|search_type| search_type.contains("atom")))
+        .and_then(|search| resolve_template(url, search.get("template")?))
+}
+
+/// Fill in a search template, from OpenSearch (`{searchTerms}`) or OPDS 2.0 (`{?query}`)
+fn expand_search_template(template: &str, query: &str) -> String {
+    let terms = encode_query(query.trim());
+    let url = template.replace("{searchTerms}", &terms);
+    let url = QUERY_EXPRESSION.replace_all(&url, |captures: &regex::Captures| {
+        if captures[1].split(',').any(|name| name == "query") { format!("?query={}", terms) } else { String::new() }
+    });
+    // Optional OpenSearch parameters are left out
+    OPTIONAL_PARAMETER.replace_all(&url, "").into_owned()
+}
+
+fn first_text(pattern: &Regex, xml: &str) -> Option<String> {
+    pattern.captures(xml).map(|captures| plain_text(&captures[1])).filter(|text| !text.is_empty())
+}
+
+fn rels(link: &Value) -> Vec<&str> {
+    match &link["rel"] {
+        Value::String(rel) => vec![rel.as_str()],
+        Value::Array(rels) => rels.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Names from an OPDS 2.0 contributor: a string, an object or a list of either
+fn contributor_names(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(name) => vec![name.clone()],
+        Value::Object(contributor) => contributor.get("name").and_then(Value::as_str).map(str::to_string).into_iter().collect(),
+        Value::Array(contributors) => contributors.iter().flat_map(contributor_names).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn resolve(base: &Url, href: &str) -> Option<String> {
+    base.join(href.trim()).ok().map(String::from)
+}
+
+/// Resolve a templated link, leaving its `{...}` expressions as written
+fn resolve_template(base: &Url, href: &str) -> Option<String> {
+    let href = href.trim();
+    let split = href.find('{').unwrap_or(href.len());
+    let resolved = resolve(base, &href[..split])?;
+    Some(format!("{}{}", resolved, &href[split..]))
+}
+
+fn mime_base(mime_type: &str) -> &str {
+    mime_type.split(';').next().unwrap_or_default().trim()
+}
+
+/// File name for a downloaded book: its authors and title, without characters file systems reject
+fn file_stem(entry: &OpdsEntry) -> String {
+    let name = match entry.authors.first() {
+        Some(author) => format!("{} - {}", author, entry.title),
+        None => entry.title.clone(),
+    };
+    let name: String = name.chars()
+        .map(|c| if c.is_control() || r#"/\:*?"<>|"#.contains(c) { '_' } else { c })
+        .take(120)
+        .collect();
+    match name.trim() {
+        "" => "Download".to_string(),
+        name => name.to_string(),
+    }
+}
+
+fn unused_path(dir: &Path, stem: &str, extension: &str) -> PathBuf {
+    let mut path = dir.join(format!("{}.{}", stem, extension));
+    let mut copy = 2;
+    while path.exists() {
+        path = dir.join(format!("{} ({}).{}", stem, copy, extension));
+        copy += 1;
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_opds1_feed_with_navigation_books_and_search() {
+        let url = Url::parse("https://www.gutenberg.org/ebooks/search.opds/?sort_order=downloads").unwrap();
+        let body = r#"<?xml version="1.0" encoding="utf-8"?>
+            <feed xmlns="http://www.w3.org/2005/Atom" xmlns:opds="http://opds-spec.org/2010/catalog">
+              <id>http://www.gutenberg.org/ebooks/search.opds/</id>
+              <title>Popular &amp; Free</title>
+              <link rel="search" type="application/opensearchdescription+xml" href="/catalog/osd-books.xml"/>
+              <link rel="next" type="application/atom+xml;profile=opds-catalog" href="?sort_order=downloads&amp;start_index=26"/>
+              <entry>
+                <title>Fiction</title>
+                <id>urn:fiction</id>
+                <link type="application/atom+xml;profile=opds-catalog;kind=navigation" href="/ebooks/bookshelf/fiction.opds"/>
+              </entry>
+              <entry>
+                <title>Pride and Prejudice</title>
+                <id>urn:gutenberg:1342</id>
+                <updated>2024-05-01T10:00:00+00:00</updated>
+                <author><name>Austen, Jane</name></author>
+                <content type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml"><p>A novel.</p></div></content>
+                <link rel="http://opds-spec.org/image/thumbnail" type="image/jpeg" href="/cache/1342/thumb.jpg"/>
+                <link rel="http://opds-spec.org/image" type="image/jpeg" href="/cache/1342/cover.jpg"/>
+                <link rel="http://opds-spec.org/acquisition/buy" type="application/pdf" href="/shop/1342.pdf"/>
+                <link rel="http://opds-spec.org/acquisition/open-access" type="application/x-mobipocket-ebook" href="/ebooks/1342.kf8"/>
+                <link rel="http://opds-spec.org/acquisition/open-access" type="application/epub+zip" href="/ebooks/1342.epub3.images"/>
+              </entry>
+            </feed>"#;
+
+        let feed = parse_feed(body, &url).unwrap();
+        assert_eq!(feed.title, "Popular & Free");
+        assert_eq!(feed.next_url.as_deref(), Some("https://www.gutenberg.org/ebooks/search.opds/?sort_order=downloads&start_index=26"));
+        assert_eq!(feed.search_description.as_deref(), Some("https://www.gutenberg.org/catalog/osd-books.xml"));
+        assert_eq!(feed.entries[0].navigation_url.as_deref(), Some("https://www.gutenberg.org/ebooks/bookshelf/fiction.opds"));
+
+        let book = &feed.entries[1];
+        assert_eq!((book.authors.as_slice(), book.summary.as_deref()), (&["Austen, Jane".to_string()][..], Some("A novel.")));
+        assert_eq!(book.cover_url.as_deref(), Some("https://www.gutenberg.org/cache/1342/cover.jpg"));
+        let (acquisition, extension) = book.best_acquisition().unwrap();
+        assert_eq!((acquisition.url.as_str(), extension), ("https://www.gutenberg.org/ebooks/1342.epub3.images", "epub"));
+        assert_eq!(file_stem(book), "Austen, Jane - Pride and Prejudice");
+
+        let description = r#"<OpenSearchDescription><Url type="text/html" template="/ebooks/search/?query={searchTerms}"/>
+            <Url type="application/atom+xml" template="https://www.gutenberg.org/ebooks/search.opds/?query={searchTerms}&amp;start_index={startIndex?}"/></OpenSearchDescription>"#;
+        let template = parse_search_template(description, &url).unwrap();
+        assert_eq!(template, "https://www.gutenberg.org/ebooks/search.opds/?query={searchTerms}&start_index={startIndex?}");
+        assert_eq!(expand_search_template(&template, "jane eyre"), "https://www.gutenberg.org/ebooks/search.opds/?query=jane+eyre&start_index=");
+    }
+
+    #[test]
+    fn test_parse_opds2_feed() {
+        let url = Url::parse("https://catalog.example.org/opds/").unwrap();
+        let body = r#"{
+            "metadata": { "title": "New Arrivals" },
+            "links": [
+                { "rel": "next", "href": "page2.json", "type": "application/opds+json" },
+                { "rel": "search", "href": "/search{?query}", "type": "application/opds+json", "templated": true }
+            ],
+            "navigation": [ { "href": "/sci-fi.json", "title": "Science Fiction", "type": "application/opds+json" } ],
+            "publications": [ {
+                "metadata": { "identifier": "urn:isbn:9780000000001", "title": "Moby-Dick",
+                              "author": [ { "name": "Herman Melville" } ], "description": "<p>A whale.</p>" },
+                "links": [ { "rel": "http://opds-spec.org/acquisition", "href": "/books/moby.epub", "type": "application/epub+zip" } ],
+                "images": [ { "href": "/covers/moby.jpg", "type": "image/jpeg" } ]
+            } ]
+        }"#;
+
+        let feed = parse_feed(body, &url).unwrap();
+        assert_eq!(feed.title, "New Arrivals");
+        assert_eq!(feed.next_url.as_deref(), Some("https://catalog.example.org/opds/page2.json"));
+        let template = feed.search_template.as_deref().unwrap();
+        assert_eq!(expand_search_template(template, "Brontë"), "https://catalog.example.org/search?query=Bront%C3%AB");
+        assert_eq!(feed.entries[0].navigation_url.as_deref(), Some("https://catalog.example.org/sci-fi.json"));
+
+        let book = &feed.entries[1];
+        assert_eq!(book.authors, ["Herman Melville"]);
+        assert_eq!(book.summary.as_deref(), Some("A whale."));
+        assert_eq!(book.cover_url.as_deref(), Some("https://catalog.example.org/covers/moby.jpg"));
+        assert_eq!(book.best_acquisition().unwrap().0.url, "https://catalog.example.org/books/moby.epub");
+        assert!(parse_feed("<html></html>", &url).is_err());
+    }
+}
@@ -89,6 +89,12 @@ impl PathResolver {
         Ok(cache_dir)
     }
     
+    /// Get directory for exported files
+    pub fn get_exports_directory() -> Result<PathBuf> {
+        let app_dir = Self::get_app_data_directory()?;
+        Ok(app_dir.join("exports"))
+    }
+    
     /// Get logs directory
     pub fn get_logs_directory() -> Result<PathBuf> {
         let app_dir = Self::get_app_data_directory()?;
@@ -0,0 +1,235 @@
+use std::io::Cursor;
+use std::path::Path;
+use anyhow::{Result, anyhow};
+use image::{DynamicImage, GrayImage, ImageOutputFormat, RgbImage};
+use pdf_extract::Document;
+
+/// Document information read from a PDF
+#[derive(Debug, Clone, Default)]
+pub struct PdfMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub page_count: u32,
+}
+
+/// Image shown for a PDF page
+#[derive(Debug, Clone)]
+pub struct PdfPageImage {
+    pub data: Vec<u8>,
+    pub media_type: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Parser entry point for PDF files
+pub struct PdfParser;
+
+impl PdfParser {
+    /// Read the document information dictionary and page count
+    pub fn metadata(path: &Path) -> Result<PdfMetadata> {
+        let doc = Self::load(path)?;
+        let info = doc.trailer.get(b"Info").ok()
+            .and_then(|info| doc.dereference(info).ok())
+            .and_then(|(_, info)| info.as_dict().ok());
+
+        let text_field = |key: &[u8]| {
+            info.and_then(|info| info.get(key).ok())
+                .and_then(|value| doc.dereference(value).ok())
+                .and_then(|(_, value)| pdf_extract::decode_text_string(value).ok())
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        Ok(PdfMetadata {
+            title: text_field(b"Title"),
+            author: text_field(b"Author"),
+            subject: text_field(b"Subject"),
+            page_count: doc.get_pages().len() as u32,
+        })
+    }
+
+    /// Extract the text of every page, in page order
+    pub fn extract_pages(path: &Path) -> Result<Vec<String>> {
+        pdf_extract::extract_text_by_pages(path)
+            .map_err(|e| anyhow!("Failed to extract PDF text: {}", e))
+    }
+
+    /// Get an image of a page, numbered from 1
+    ///
+    /// Uses the largest image drawn on the page, which covers scanned books and
+    /// cover pages. Pages made only of text and vector graphics have no image.
+    pub fn render_page_image(path: &Path, page_number: u32) -> Result<Option<PdfPageImage>> {
+        let doc = Self::load(path)?;
+        let page_id = *doc.get_pages().get(&page_number)
+            .ok_or_else(|| anyhow!("PDF has no page {}", page_number))?;
+
+        let Ok(images) = doc.get_page_images(page_id) else {
+            return Ok(None);
+        };
+        let Some(image) = images.iter().max_by_key(|image| image.width * image.height) else {
+            return Ok(None);
+        };
+
+        let width = image.width as u32;
+        let height = image.height as u32;
+        let filters = image.filters.clone().unwrap_or_default();
+
+        // JPEG and JPEG 2000 streams are complete image files already
+        if let [filter] = filters.as_slice() {
+            let media_type = match filter.as_str() {
+                "DCTDecode" => Some("image/jpeg"),
+                "JPXDecode" => Some("image/jp2"),
+                _ => None,
+            };
+            if let Some(media_type) = media_type {
+                return Ok(Some(PdfPageImage {
+                    data: image.content.to_vec(),
+                    media_type: media_type.to_string(),
+                    width,
+                    height,
+                }));
+            }
+        }
+
+        // Raw 8-bit samples are converted to PNG
+        if image.bits_per_component != Some(8) {
+            return Ok(None);
+        }
+        let stream = doc.get_object(image.id)?.as_stream()?;
+        let samples = if filters.is_empty() {
+            stream.content.clone()
+        } else {
+            stream.decompressed_content()?
+        };
+        let decoded = match image.color_space.as_deref() {
+            Some("DeviceRGB") => RgbImage::from_raw(width, height, samples).map(DynamicImage::ImageRgb8),
+            Some("DeviceGray") => GrayImage::from_raw(width, height, samples).map(DynamicImage::ImageLuma8),
+            _ => None,
+        };
+        let Some(decoded) = decoded else {
+            return Ok(None);
+        };
+
+        let mut data = Vec::new();
+        decoded.write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Png)?;
+        Ok(Some(PdfPageImage {
+            data,
+            media_type: "image/png".to_string(),
+            width,
+            height,
+        }))
+    }
+
+    fn load(path: &Path) -> Result<Document> {
+        let doc = Document::load(path)
+            .map_err(|e| anyhow!("Failed to open PDF: {}", e))?;
+        if doc.is_encrypted() {
+            return Err(anyhow!("Encrypted PDFs are not supported"));
+        }
+        Ok(doc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pdf_extract::content::{Content, Operation};
+    use pdf_extract::{dictionary, Object, Stream};
+
+    /// Build a PDF with one text page and one page showing an RGB image
+    fn sample_pdf(path: &Path) {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        let image_id = doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 2,
+                "Height" => 1,
+                "ColorSpace" => "DeviceRGB",
+                "BitsPerComponent" => 8,
+            },
+            vec![255, 0, 0, 0, 0, 255],
+        ));
+
+        let text = Content {
+            operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 24.into()]),
+                Operation::new("Td", vec![72.into(), 700.into()]),
+                Operation::new("Tj", vec![Object::string_literal("Call me Ishmael")]),
+                Operation::new("ET", vec![]),
+            ],
+        };
+        let picture = Content {
+            operations: vec![
+                Operation::new("q", vec![]),
+                Operation::new("cm", vec![200.into(), 0.into(), 0.into(), 100.into(), 72.into(), 500.into()]),
+                Operation::new("Do", vec!["Im1".into()]),
+                Operation::new("Q", vec![]),
+            ],
+        };
+
+        let mut page_ids = Vec::new();
+        for content in [text, picture] {
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+            page_ids.push(doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+                "Resources" => dictionary! {
+                    "Font" => dictionary! { "F1" => font_id },
+                    "XObject" => dictionary! { "Im1" => image_id },
+                },
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            }));
+        }
+
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => page_ids.iter().map(|id| Object::Reference(*id)).collect::<Vec<_>>(),
+            "Count" => page_ids.len() as i64,
+        }));
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        let info_id = doc.add_object(dictionary! {
+            "Title" => Object::string_literal("Moby-Dick"),
+            "Author" => Object::string_literal("Herman Melville"),
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc.trailer.set("Info", info_id);
+        doc.save(path).unwrap();
+    }
+
+    #[test]
+    fn test_metadata_text_and_page_image() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("book.pdf");
+        sample_pdf(&path);
+
+        let metadata = PdfParser::metadata(&path).unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Moby-Dick"));
+        assert_eq!(metadata.author.as_deref(), Some("Herman Melville"));
+        assert_eq!(metadata.page_count, 2);
+
+        let pages = PdfParser::extract_pages(&path).unwrap();
+        assert_eq!(pages.len(), 2);
+        assert!(pages[0].contains("Call me Ishmael"));
+
+        let image = PdfParser::render_page_image(&path, 2).unwrap().unwrap();
+        assert_eq!(image.media_type, "image/png");
+        assert_eq!((image.width, image.height), (2, 1));
+        let decoded = image::load_from_memory(&image.data).unwrap().to_rgb8();
+        assert_eq!(decoded.get_pixel(1, 0).0, [0, 0, 255]);
+
+        assert!(PdfParser::render_page_image(&path, 3).is_err());
+    }
+}
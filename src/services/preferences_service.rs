@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+use anyhow::Result;
+use tokio::sync::watch;
+use tracing::warn;
+
+use crate::models::preferences::UserPreferences;
+use crate::services::path_resolver::PathResolver;
+
+/// File the preferences are kept in, inside the app data directory
+pub const PREFERENCES_FILE: &str = "preferences.json";
+
+/// Loads and saves the user's preferences, and tells subscribers when they change
+pub struct PreferencesService {
+    path: PathBuf,
+    current: watch::Sender<UserPreferences>,
+}
+
+impl PreferencesService {
+    pub fn new(path: PathBuf) -> Self {
+        let (current, _) = watch::channel(UserPreferences::default());
+        Self { path, current }
+    }
+
+    /// Preferences stored in the application data directory
+    pub fn open_default() -> Result<Self> {
+        let app_dir = PathResolver::get_app_data_directory()?;
+        PathResolver::ensure_directory_exists(&app_dir)?;
+        Ok(Self::new(app_dir.join(PREFERENCES_FILE)))
+    }
+
+    /// Read the stored preferences; a missing or unreadable file gives the defaults
+    pub async fn load(&self) -> UserPreferences {
+        let preferences = match tokio::fs::read_to_string(&self.path).await {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable preferences in {}: {}", self.path.display(), e);
+                UserPreferences::default()
+            }),
+            Err(_) => UserPreferences::default(),
+        };
+        self.current.send_replace(preferences.clone());
+        preferences
+    }
+
+    pub fn get(&self) -> UserPreferences {
+        self.current.borrow().clone()
+    }
+
+    /// Save new preferences and pass them to subscribers
+    pub async fn update(&self, preferences: UserPreferences) -> Result<()> {
+        tokio::fs::write(&self.path, serde_json::to_string_pretty(&preferences)?).await?;
+        self.current.send_replace(preferences);
+        Ok(())
+    }
+
+    /// Watch for changes; the receiver starts with the current preferences
+    pub fn subscribe(&self) -> watch::Receiver<UserPreferences> {
+        self.current.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_update_is_saved_and_announced() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(PREFERENCES_FILE);
+        let service = PreferencesService::new(path.clone());
+        assert_eq!(service.load().await.library.trash_retention_days, UserPreferences::default().library.trash_retention_days);
+
+        let mut changes = service.subscribe();
+        let mut preferences = service.get();
+        preferences.library.backup_schedule.interval_hours = 6;
+        service.update(preferences).await.unwrap();
+
+        changes.changed().await.unwrap();
+        assert_eq!(changes.borrow().library.backup_schedule.interval_hours, 6);
+        let reloaded = PreferencesService::new(path).load().await;
+        assert_eq!(reloaded.library.backup_schedule.interval_hours, 6);
+    }
+
+    #[tokio::test]
+    async fn test_older_file_missing_new_fields_keeps_its_settings() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(PREFERENCES_FILE);
+        let mut json = serde_json::to_value(UserPreferences::default()).unwrap();
+        json["ui"]["theme"] = "dark".into();
+        json["library"].as_object_mut().unwrap().remove("trash_retention_days");
+        json["reading"].as_object_mut().unwrap().remove("series_auto_advance");
+        json["reading"].as_object_mut().unwrap().remove("reading_speed_wpm");
+        json["privacy"].as_object_mut().unwrap().remove("compatibility_ledger_enabled");
+        tokio::fs::write(&path, json.to_string()).await.unwrap();
+
+        let preferences = PreferencesService::new(path).load().await;
+        assert_eq!(preferences.ui.theme, "dark");
+        assert_eq!(preferences.library.trash_retention_days, crate::models::preferences::DEFAULT_TRASH_RETENTION_DAYS);
+        assert_eq!(preferences.reading.reading_speed_wpm, crate::models::book::DEFAULT_READING_SPEED_WPM);
+    }
+}
@@ -0,0 +1,770 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::{Row, SqlitePool};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::models::preferences::{ReadLaterDelivery, ReadLaterProvider, ReadLaterSettings};
+use crate::services::book_service::BookService;
+use crate::services::epub_metadata::plain_text;
+use crate::services::export_share::sanitize_file_name;
+use crate::services::news_service::{
+    build_issue, clean_html, download_images, IssueArticle, IssueInfo, IssueSection, NewsArticle,
+};
+use crate::services::secrets;
+
+/// Provider name the Wallabag client secret and tokens are stored under in the keyring
+pub const WALLABAG_PROVIDER: &str = "wallabag";
+const ROLLING_BOOK_TITLE: &str = "Read Later";
+const PAGE_SIZE: u32 = 100;
+
+static PAGE_TITLE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<title\b[^>]*>(.*?)</title\s*>").unwrap());
+/// Where a web page keeps its article, most specific first
+static PAGE_BODY: Lazy<[Regex; 3]> = Lazy::new(|| {
+    ["article", "main", "body"].map(|tag| Regex::new(&format!(r"(?is)<{tag}\b[^>]*>(.*)</{tag}\s*>")).unwrap())
+});
+
+/// An article saved to a read-later service
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SavedArticle {
+    pub provider: ReadLaterProvider,
+    pub remote_id: String,
+    pub title: String,
+    pub url: String,
+    pub author: Option<String>,
+    pub added_at: DateTime<Utc>,
+    pub content: String, // HTML
+    pub archived: bool,
+    pub starred: bool,
+}
+
+impl SavedArticle {
+    /// Id of the article's chapter in the books it is delivered in
+    pub fn chapter_id(&self) -> String {
+        chapter_id(self.provider, &self.remote_id)
+    }
+
+    fn to_news_article(&self) -> NewsArticle {
+        NewsArticle {
+            guid: self.remote_id.clone(),
+            title: self.title.clone(),
+            link: Some(self.url.clone()),
+            author: self.author.clone(),
+            published_at: Some(self.added_at),
+            content: self.content.clone(),
+        }
+    }
+}
+
+/// Outcome of a sync with a read-later service
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReadLaterSyncSummary {
+    pub pulled: usize,   // New articles
+    pub pushed: usize,   // Archive and favorite changes sent
+    pub archived: usize, // Articles archived elsewhere, so no longer delivered
+    pub books: Vec<String>, // Books added or rebuilt
+}
+
+/// Wallabag's OAuth client and tokens, kept together in the keyring
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WallabagCredentials {
+    client_secret: String,
+    access_token: String,
+    refresh_token: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// Pulls articles saved to Wallabag, or exported from Pocket, into the library
+///
+/// Articles go into one rolling "Read Later" book or a book each. Archiving
+/// or starring an article here is sent back to Wallabag; changes that fail
+/// to send are kept and sent with the next sync.
+pub struct ReadLaterService {
+    pool: SqlitePool,
+    client: Client,
+    book_service: Arc<BookService>,
+    output_dir: PathBuf,
+    settings: RwLock<ReadLaterSettings>,
+}
+
+impl ReadLaterService {
+    pub fn new(pool: SqlitePool, book_service: Arc<BookService>, output_dir: PathBuf, settings: ReadLaterSettings) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(60))
+            .user_agent(concat!("ebook-reader/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self { pool, client, book_service, output_dir, settings: RwLock::new(settings) }
+    }
+
+    /// Initialize read-later tables
+    pub async fn init_tables(&self) -> Result<()> {
+        // Status changes not yet sent back have `status_changed` set
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS read_later_articles (
+                provider TEXT NOT NULL,
+                remote_id TEXT NOT NULL,
+                article TEXT NOT NULL,
+                added_at TEXT NOT NULL,
+                archived INTEGER NOT NULL DEFAULT 0,
+                starred INTEGER NOT NULL DEFAULT 0,
+                status_changed INTEGER NOT NULL DEFAULT 0,
+                book_id TEXT,
+                PRIMARY KEY (provider, remote_id)
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS read_later_state (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                rolling_book_id TEXT,
+                rolling_identifier TEXT,
+                last_pulled_at TEXT
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_settings(&self, settings: ReadLaterSettings) {
+        *self.settings.write().await = settings;
+    }
+
+    pub async fn get_settings(&self) -> ReadLaterSettings {
+        self.settings.read().await.clone()
+    }
+
+    /// Sign in to a Wallabag instance with an API client created there
+    pub async fn connect_wallabag(
+        &self,
+        server_url: &str,
+        client_id: &str,
+        client_secret: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<()> {
+        let mut settings = self.get_settings().await;
+        settings.server_url = server_url.trim().trim_end_matches('/').to_string();
+        let form = [
+            ("grant_type", "password"),
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("username", username),
+            ("password", password),
+        ];
+        let credentials = self.request_token(&settings, client_secret, &form).await
+            .map_err(|e| anyhow!("Wallabag did not accept the sign in: {}", e))?;
+        secrets::store_api_key_async(WALLABAG_PROVIDER, &serde_json::to_string(&credentials)?).await?;
+
+        settings.provider = ReadLaterProvider::Wallabag;
+        settings.client_id = Some(client_id.to_string());
+        settings.username = Some(username.to_string());
+        self.set_settings(settings).await;
+        Ok(())
+    }
+
+    /// Forget the Wallabag account; articles already pulled stay
+    pub async fn disconnect(&self) -> Result<()> {
+        let mut settings = self.get_settings().await;
+        settings.client_id = None;
+        settings.username = None;
+        self.set_settings(settings).await;
+        secrets::remove_api_key_async(WALLABAG_PROVIDER).await
+    }
+
+    /// Send status changes, pull new articles and deliver them
+    pub async fn sync(&self) -> Result<ReadLaterSyncSummary> {
+        let settings = self.get_settings().await;
+        let mut summary = ReadLaterSyncSummary::default();
+        if settings.provider == ReadLaterProvider::Wallabag && settings.username.is_some() {
+            summary.pushed = self.push_statuses(&settings).await?;
+            let (pulled, archived) = self.pull_wallabag(&settings).await?;
+            summary.pulled = pulled;
+            summary.archived = archived;
+        }
+        summary.books = self.deliver().await?;
+        info!("Read later sync: {} pulled, {} pushed, {} archived elsewhere", summary.pulled, summary.pushed, summary.archived);
+        Ok(summary)
+    }
+
+    /// Import a Pocket export (`part_000000.csv`), fetching each unread article from the web
+    pub async fn import_pocket_export(&self, path: &Path) -> Result<usize> {
+        let text = tokio::fs::read_to_string(path).await?;
+        let mut imported = 0;
+        for mut article in parse_pocket_export(&text)? {
+            if !article.archived {
+                match self.fetch_page(&article.url).await {
+                    Ok((title, content)) => {
+                        if article.title.is_empty() || article.title == article.url {
+                            article.title = title.unwrap_or(article.title);
+                        }
+                        article.content = content;
+                    }
+                    Err(e) => warn!("Could not fetch {}: {}", article.url, e),
+                }
+            }
+            if self.store_article(&article).await? {
+                imported += 1;
+            }
+        }
+        Ok(imported)
+    }
+
+    /// Save an article, or take on the service's status for one already saved
+    ///
+    /// Returns whether the article was new. Status changes made here and not
+    /// yet sent are kept.
+    pub async fn store_article(&self, article: &SavedArticle) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO read_later_articles (provider, remote_id, article, added_at, archived, starred) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(provider_key(article.provider))
+        .bind(&article.remote_id)
+        .bind(serde_json::to_string(article)?)
+        .bind(article.added_at.to_rfc3339())
+        .bind(article.archived)
+        .bind(article.starred)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() > 0 {
+            return Ok(true);
+        }
+
+        sqlx::query(
+            "UPDATE read_later_articles SET archived = ?, starred = ? \
+             WHERE provider = ? AND remote_id = ? AND status_changed = 0",
+        )
+        .bind(article.archived)
+        .bind(article.starred)
+        .bind(provider_key(article.provider))
+        .bind(&article.remote_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(false)
+    }
+
+    /// Articles not yet archived, newest first
+    pub async fn get_unread(&self) -> Result<Vec<SavedArticle>> {
+        let rows = sqlx::query("SELECT * FROM read_later_articles WHERE archived = 0 ORDER BY added_at DESC")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(row_to_article).collect()
+    }
+
+    /// The article a chapter of a delivered book holds
+    pub async fn get_article_for_chapter(&self, chapter_id: &str) -> Result<Option<SavedArticle>> {
+        let rows = sqlx::query("SELECT * FROM read_later_articles")
+            .fetch_all(&self.pool)
+            .await?;
+        for row in &rows {
+            let article = row_to_article(row)?;
+            if article.chapter_id() == chapter_id {
+                return Ok(Some(article));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Archive an article, or bring it back, and tell the service
+    pub async fn set_archived(&self, provider: ReadLaterProvider, remote_id: &str, archived: bool) -> Result<()> {
+        self.change_status(provider, remote_id, "archived", archived).await
+    }
+
+    /// Star an article, or unstar it, and tell the service
+    pub async fn set_starred(&self, provider: ReadLaterProvider, remote_id: &str, starred: bool) -> Result<()> {
+        self.change_status(provider, remote_id, "starred", starred).await
+    }
+
+    async fn change_status(&self, provider: ReadLaterProvider, remote_id: &str, column: &str, value: bool) -> Result<()> {
+        let result = sqlx::query(&format!(
+            "UPDATE read_later_articles SET {} = ?, status_changed = 1 WHERE provider = ? AND remote_id = ?",
+            column,
+        ))
+        .bind(value)
+        .bind(provider_key(provider))
+        .bind(remote_id)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(anyhow!("No saved article {}", remote_id));
+        }
+
+        let settings = self.get_settings().await;
+        if provider == ReadLaterProvider::Wallabag && settings.username.is_some() {
+            if let Err(e) = self.push_statuses(&settings).await {
+                warn!("Wallabag status change will be sent with the next sync: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Send archive and favorite changes to Wallabag; returns how many were sent
+    async fn push_statuses(&self, settings: &ReadLaterSettings) -> Result<usize> {
+        let rows = sqlx::query(
+            "SELECT remote_id, archived, starred FROM read_later_articles WHERE provider = ? AND status_changed = 1",
+        )
+        .bind(provider_key(ReadLaterProvider::Wallabag))
+        .fetch_all(&self.pool)
+        .await?;
+        if rows.is_empty() {
+            return Ok(0);
+        }
+
+        let token = self.access_token(settings).await?;
+        for row in &rows {
+            let remote_id: String = row.get("remote_id");
+            let body = json!({
+                "archive": i32::from(row.get::<bool, _>("archived")),
+                "starred": i32::from(row.get::<bool, _>("starred")),
+            });
+            let response = self.client.patch(api_url(settings, &format!("entries/{}.json", remote_id))?)
+                .bearer_auth(&token)
+                .json(&body)
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                return Err(anyhow!("Wallabag refused the change to entry {} ({})", remote_id, response.status()));
+            }
+            sqlx::query("UPDATE read_later_articles SET status_changed = 0 WHERE provider = ? AND remote_id = ?")
+                .bind(provider_key(ReadLaterProvider::Wallabag))
+                .bind(&remote_id)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(rows.len())
+    }
+
+    /// Pull unread entries and archive changes since the last pull
+    async fn pull_wallabag(&self, settings: &ReadLaterSettings) -> Result<(usize, usize)> {
+        let since: Option<String> = sqlx::query_scalar("SELECT last_pulled_at FROM read_later_state WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await?
+            .flatten();
+        let since = since.and_then(|since| DateTime::parse_from_rfc3339(&since).ok()).map(|since| since.timestamp());
+        let started = Utc::now();
+        let token = self.access_token(settings).await?;
+
+        let mut pulled = 0;
+        let mut archived = 0;
+        // Archived entries are only needed to learn of articles archived elsewhere
+        for archive in [false, true] {
+            if archive && since.is_none() {
+                continue;
+            }
+            let mut page = 1;
+            loop {
+                let mut url = api_url(settings, "entries.json")?;
+                url.query_pairs_mut()
+                    .append_pair("archive", if archive { "1" } else { "0" })
+                    .append_pair("detail", if archive { "metadata" } else { "full" })
+                    .append_pair("sort", "updated")
+                    .append_pair("perPage", &PAGE_SIZE.to_string())
+                    .append_pair("page", &page.to_string())
+                    .append_pair("since", &since.unwrap_or(0).to_string());
+                let response = self.client.get(url).bearer_auth(&token).send().await?;
+                if !response.status().is_success() {
+                    return Err(anyhow!("Wallabag did not list the entries ({})", response.status()));
+                }
+                let (articles, pages) = parse_wallabag_entries(&response.json().await?)?;
+                for article in &articles {
+                    if archive {
+                        let result = sqlx::query(
+                            "UPDATE read_later_articles SET archived = 1 \
+                             WHERE provider = ? AND remote_id = ? AND archived = 0 AND status_changed = 0",
+                        )
+                        .bind(provider_key(ReadLaterProvider::Wallabag))
+                        .bind(&article.remote_id)
+                        .execute(&self.pool)
+                        .await?;
+                        archived += result.rows_affected() as usize;
+                    } else if self.store_article(article).await? {
+                        pulled += 1;
+                    }
+                }
+                if page >= pages {
+                    break;
+                }
+                page += 1;
+            }
+        }
+
+        sqlx::query(
+            "INSERT INTO read_later_state (id, last_pulled_at) VALUES (1, ?) \
+             ON CONFLICT (id) DO UPDATE SET last_pulled_at = excluded.last_pulled_at",
+        )
+        .bind(started.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok((pulled, archived))
+    }
+
+    /// Put unread articles into the library the way the settings say; returns the books touched
+    pub async fn deliver(&self) -> Result<Vec<String>> {
+        let unread = self.get_unread().await?;
+        tokio::fs::create_dir_all(&self.output_dir).await?;
+        match self.get_settings().await.delivery {
+            ReadLaterDelivery::RollingBook => Ok(self.deliver_rolling_book(&unread).await?.into_iter().collect()),
+            ReadLaterDelivery::Individual => self.deliver_individually(&unread).await,
+        }
+    }
+
+    /// Rebuild the "Read Later" book in place, keeping its id, file and chapter ids
+    async fn deliver_rolling_book(&self, unread: &[SavedArticle]) -> Result<Option<String>> {
+        let state = sqlx::query("SELECT rolling_book_id, rolling_identifier FROM read_later_state WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        let book_id: Option<String> = state.as_ref().and_then(|row| row.get("rolling_book_id"));
+        let identifier: Option<String> = state.as_ref().and_then(|row| row.get("rolling_identifier"));
+        if unread.is_empty() && book_id.is_none() {
+            return Ok(None);
+        }
+        let identifier = identifier.unwrap_or_else(|| format!("urn:uuid:{}", Uuid::new_v4()));
+
+        let path = self.output_dir.join(format!("{}.epub", ROLLING_BOOK_TITLE));
+        let epub = self.build_book(&identifier, ROLLING_BOOK_TITLE, unread).await?;
+        tokio::fs::write(&path, &epub).await?;
+
+        let existing = match &book_id {
+            Some(book_id) => self.book_service.get_book_by_id(book_id).await.ok(),
+            None => None,
+        };
+        let book_id = match existing {
+            Some(mut book) => {
+                book.file_size = epub.len() as u64;
+                self.book_service.update_book(&book.id.clone(), &book).await?;
+                book.id
+            }
+            None => self.book_service.add_book(&path).await?,
+        };
+
+        sqlx::query(
+            "INSERT INTO read_later_state (id, rolling_book_id, rolling_identifier) VALUES (1, ?, ?) \
+             ON CONFLICT (id) DO UPDATE SET rolling_book_id = excluded.rolling_book_id, \
+             rolling_identifier = excluded.rolling_identifier",
+        )
+        .bind(&book_id)
+        .bind(&identifier)
+        .execute(&self.pool)
+        .await?;
+        Ok(Some(book_id))
+    }
+
+    /// Add a book for each unread article that has none yet
+    async fn deliver_individually(&self, unread: &[SavedArticle]) -> Result<Vec<String>> {
+        let mut books = Vec::new();
+        for article in unread {
+            let delivered: Option<String> = sqlx::query_scalar(
+                "SELECT book_id FROM read_later_articles WHERE provider = ? AND remote_id = ?",
+            )
+            .bind(provider_key(article.provider))
+            .bind(&article.remote_id)
+            .fetch_one(&self.pool)
+            .await?;
+            if delivered.is_some() {
+                continue;
+            }
+
+            let identifier = format!("urn:uuid:{}", Uuid::new_v4());
+            let epub = self.build_book(&identifier, &article.title, std::slice::from_ref(article)).await?;
+            let path = self.output_dir.join(format!("{}.epub", sanitize_file_name(&article.chapter_id())));
+            tokio::fs::write(&path, epub).await?;
+            let book_id = self.book_service.add_book(&path).await?;
+            sqlx::query("UPDATE read_later_articles SET book_id = ? WHERE provider = ? AND remote_id = ?")
+                .bind(&book_id)
+                .bind(provider_key(article.provider))
+                .bind(&article.remote_id)
+                .execute(&self.pool)
+                .await?;
+            books.push(book_id);
+        }
+        Ok(books)
+    }
+
+    async fn build_book(&self, identifier: &str, title: &str, articles: &[SavedArticle]) -> Result<Vec<u8>> {
+        let mut image_urls = Vec::new();
+        let mut sections: Vec<IssueSection> = Vec::new();
+        for article in articles {
+            let base = Url::parse(&article.url).ok();
+            let cleaned = clean_html(&article.content, base.as_ref(), true);
+            image_urls.extend(cleaned.image_urls.iter().cloned());
+            let section_title = article.provider.display_name();
+            let entry = IssueArticle { id: article.chapter_id(), article: article.to_news_article(), cleaned };
+            match sections.iter_mut().find(|section| section.title == section_title) {
+                Some(section) => section.articles.push(entry),
+                None => sections.push(IssueSection { title: section_title.to_string(), articles: vec![entry] }),
+            }
+        }
+        let images = download_images(&self.client, &image_urls).await;
+        let info = IssueInfo { identifier, title, creator: "Read Later", subject: "Read Later", date: Utc::now() };
+        build_issue(&info, &sections, &images)
+    }
+
+    /// A web page's title and article HTML
+    async fn fetch_page(&self, url: &str) -> Result<(Option<String>, String)> {
+        let response = self.client.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("{}", response.status()));
+        }
+        let html = response.text().await?;
+        let title = PAGE_TITLE.captures(&html).map(|c| plain_text(&c[1])).filter(|title| !title.is_empty());
+        let content = PAGE_BODY.iter()
+            .find_map(|body| body.captures(&html))
+            .map_or(html.clone(), |c| c[1].to_string());
+        Ok((title, content))
+    }
+
+    async fn access_token(&self, settings: &ReadLaterSettings) -> Result<String> {
+        let stored = secrets::get_api_key_async(WALLABAG_PROVIDER).await
+            .ok_or_else(|| anyhow!("The Wallabag sign in is missing; sign in again"))?;
+        let credentials: WallabagCredentials = serde_json::from_str(&stored)?;
+        if credentials.expires_at > Utc::now() + chrono::Duration::minutes(1) {
+            return Ok(credentials.access_token);
+        }
+
+        let client_id = settings.client_id.clone().unwrap_or_default();
+        let form = [
+            ("grant_type", "refresh_token"),
+            ("client_id", client_id.as_str()),
+            ("client_secret", credentials.client_secret.as_str()),
+            ("refresh_token", credentials.refresh_token.as_str()),
+        ];
+        let refreshed = self.request_token(settings, &credentials.client_secret, &form).await
+            .map_err(|e| anyhow!("The Wallabag sign in has expired; sign in again ({})", e))?;
+        secrets::store_api_key_async(WALLABAG_PROVIDER, &serde_json::to_string(&refreshed)?).await?;
+        Ok(refreshed.access_token)
+    }
+
+    async fn request_token(&self, settings: &ReadLaterSettings, client_secret: &str, form: &[(&str, &str)]) -> Result<WallabagCredentials> {
+        let url = Url::parse(&format!("{}/oauth/v2/token", settings.server_url.trim_end_matches('/')))?;
+        let response = self.client.post(url).form(form).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("{}", response.status()));
+        }
+        let token: Value = response.json().await?;
+        let field = |name: &str| token[name].as_str().map(str::to_string).ok_or_else(|| anyhow!("No {} in the reply", name));
+        Ok(WallabagCredentials {
+            client_secret: client_secret.to_string(),
+            access_token: field("access_token")?,
+            refresh_token: field("refresh_token")?,
+            expires_at: Utc::now() + chrono::Duration::seconds(token["expires_in"].as_i64().unwrap_or(3600)),
+        })
+    }
+}
+
+fn api_url(settings: &ReadLaterSettings, path: &str) -> Result<Url> {
+    Ok(Url::parse(&format!("{}/api/{}", settings.server_url.trim_end_matches('/'), path))?)
+}
+
+fn provider_key(provider: ReadLaterProvider) -> &'static str {
+    match provider {
+        ReadLaterProvider::Wallabag => "wallabag",
+        ReadLaterProvider::Pocket => "pocket",
+    }
+}
+
+fn chapter_id(provider: ReadLaterProvider, remote_id: &str) -> String {
+    format!("{}-{}", provider_key(provider), remote_id)
+}
+
+fn row_to_article(row: &sqlx::sqlite::SqliteRow) -> Result<SavedArticle> {
+    let mut article: SavedArticle = serde_json::from_str(&row.get::<String, _>("article"))?;
+    article.archived = row.get("archived");
+    article.starred = row.get("starred");
+    Ok(article)
+}
+
+/// Entries of one page of Wallabag's entry list, and how many pages there are
+fn parse_wallabag_entries(page: &Value) -> Result<(Vec<SavedArticle>, u32)> {
+    let items = page["_embedded"]["items"].as_array()
+        .ok_or_else(|| anyhow!("Wallabag's reply has no entries"))?;
+    // Flags come as 0 and 1 from older servers
+    let flag = |value: &Value| value.as_bool().unwrap_or_else(|| value.as_i64() == Some(1));
+    let articles = items.iter()
+        .filter_map(|item| {
+            let url = item["url"].as_str()?.to_string();
+            let added_at = item["created_at"].as_str()
+                .and_then(|at| DateTime::parse_from_str(at, "%Y-%m-%dT%H:%M:%S%z").ok())
+                .map_or_else(Utc::now, |at| at.with_timezone(&Utc));
+            let author = item["published_by"].as_array()
+                .map(|authors| authors.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(", "))
+                .filter(|authors| !authors.is_empty());
+            Some(SavedArticle {
+                provider: ReadLaterProvider::Wallabag,
+                remote_id: item["id"].as_i64()?.to_string(),
+                title: item["title"].as_str().map(str::trim).filter(|title| !title.is_empty()).unwrap_or(&url).to_string(),
+                author,
+                added_at,
+                content: item["content"].as_str().unwrap_or_default().to_string(),
+                archived: flag(&item["is_archived"]),
+                starred: flag(&item["is_starred"]),
+                url,
+            })
+        })
+        .collect();
+    Ok((articles, page["pages"].as_u64().unwrap_or(1) as u32))
+}
+
+/// Articles in a Pocket export: `title,url,time_added,tags,status` with a header row
+fn parse_pocket_export(csv: &str) -> Result<Vec<SavedArticle>> {
+    let rows = parse_csv(csv);
+    let header = rows.first().ok_or_else(|| anyhow!("The Pocket export is empty"))?;
+    let column = |name: &str| header.iter().position(|field| field.trim() == name);
+    let url_column = column("url").ok_or_else(|| anyhow!("Not a Pocket export: no url column"))?;
+    let (title_column, added_column, status_column) = (column("title"), column("time_added"), column("status"));
+
+    Ok(rows.iter()
+        .skip(1)
+        .filter_map(|row| {
+            let url = row.get(url_column)?.trim().to_string();
+            if url.is_empty() {
+                return None;
+            }
+            let field = |column: Option<usize>| column.and_then(|column| row.get(column)).map(|field| field.trim());
+            let added_at = field(added_column)
+                .and_then(|seconds| seconds.parse::<i64>().ok())
+                .and_then(|seconds| Utc.timestamp_opt(seconds, 0).single())
+                .unwrap_or_else(Utc::now);
+            Some(SavedArticle {
+                provider: ReadLaterProvider::Pocket,
+                // Pocket's own ids are not in the export, so articles are known by their address
+                remote_id: format!("{:x}", Sha256::digest(url.as_bytes()))[..16].to_string(),
+                title: field(title_column).filter(|title| !title.is_empty()).unwrap_or(&url).to_string(),
+                author: None,
+                added_at,
+                content: String::new(),
+                archived: field(status_column) == Some("archive"),
+                starred: false,
+                url,
+            })
+        })
+        .collect())
+}
+
+/// Rows of a CSV document, with quoted fields that may hold commas, quotes and line breaks
+fn parse_csv(csv: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = csv.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            '\r' if !quoted => {}
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::database::DatabaseService;
+    use crate::services::epub_parser::EpubParser;
+    use crate::utils::image_cache::ImageCache;
+
+    #[test]
+    fn test_parse_wallabag_entries_and_pocket_export() {
+        let page = json!({
+            "page": 1, "pages": 3,
+            "_embedded": { "items": [{
+                "id": 42, "title": "Slow reading", "url": "https://blog.example.org/slow",
+                "content": "<p>Take your time.</p>", "is_archived": 0, "is_starred": true,
+                "created_at": "2025-10-14T09:30:00+0200", "published_by": ["Ada", "Grace"],
+            }]},
+        });
+        let (articles, pages) = parse_wallabag_entries(&page).unwrap();
+        assert_eq!(pages, 3);
+        let article = &articles[0];
+        assert_eq!((article.remote_id.as_str(), article.chapter_id().as_str()), ("42", "wallabag-42"));
+        assert_eq!((article.archived, article.starred), (false, true));
+        assert_eq!(article.author.as_deref(), Some("Ada, Grace"));
+        assert_eq!(article.added_at.to_rfc3339(), "2025-10-14T07:30:00+00:00");
+
+        let export = "title,url,time_added,tags,status\r\n\
+            \"Commas, and \"\"quotes\"\"\",https://a.example.org/1,1760427000,,unread\r\n\
+            https://b.example.org/2,https://b.example.org/2,1760427100,news|tech,archive\r\n";
+        let saved = parse_pocket_export(export).unwrap();
+        assert_eq!(saved.len(), 2);
+        assert_eq!(saved[0].title, "Commas, and \"quotes\"");
+        assert_eq!(saved[0].added_at.timestamp(), 1760427000);
+        assert_eq!(saved[0].remote_id.len(), 16);
+        assert!(!saved[0].archived && saved[1].archived);
+        assert!(parse_pocket_export("title,link\nA,b\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rolling_book_is_rebuilt_in_place_as_articles_are_archived() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = Arc::new(DatabaseService::new_in_memory().await.unwrap());
+        let image_cache = Arc::new(ImageCache::new(dir.path().join("covers")).unwrap());
+        let book_service = Arc::new(BookService::new(database.clone(), image_cache));
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let service = ReadLaterService::new(pool, book_service, dir.path().join("read-later"), ReadLaterSettings::default());
+        service.init_tables().await.unwrap();
+        assert!(service.deliver().await.unwrap().is_empty());
+
+        let article = |id: &str, title: &str| SavedArticle {
+            provider: ReadLaterProvider::Wallabag,
+            remote_id: id.to_string(),
+            title: title.to_string(),
+            url: format!("https://blog.example.org/{}", id),
+            author: None,
+            added_at: Utc::now(),
+            content: format!("<p>{}</p>", title),
+            archived: false,
+            starred: false,
+        };
+        assert!(service.store_article(&article("1", "First")).await.unwrap());
+        assert!(service.store_article(&article("2", "Second")).await.unwrap());
+        let book_id = service.deliver().await.unwrap()[0].clone();
+
+        // Archived here, so the service's stale copy does not bring it back
+        service.set_archived(ReadLaterProvider::Wallabag, "1", true).await.unwrap();
+        assert!(!service.store_article(&article("1", "First")).await.unwrap());
+        assert_eq!(service.get_unread().await.unwrap().len(), 1);
+        assert_eq!(service.deliver().await.unwrap(), vec![book_id.clone()]);
+
+        let book = database.get_book_by_id(&book_id).await.unwrap();
+        assert_eq!(book.title, "Read Later");
+        let mut doc = EpubParser::open(&book.file_path, None).unwrap();
+        let toc = EpubParser::table_of_contents(&mut doc);
+        assert_eq!(toc[0].children.iter().map(|entry| entry.label.as_str()).collect::<Vec<_>>(), ["Second"]);
+        assert!(EpubParser::read_text(&mut doc, "wallabag-2").unwrap().contains("<div><p>Second</p></div>"));
+        assert_eq!(service.get_article_for_chapter("wallabag-2").await.unwrap().unwrap().title, "Second");
+    }
+}
@@ -1,17 +1,47 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use anyhow::Result;
-use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 use crate::models::{Book, ThemeManager};
+use crate::models::book::{ReadingPosition, DEFAULT_READING_SPEED_WPM};
+use crate::models::preferences::ChapterReadThreshold;
 use crate::models::reading_theme::{ReadingTheme, ReadingThemePreferences};
+use crate::services::alt_text_service::{inject_image_alt_text, AltTextService};
+use crate::services::compatibility_ledger::{CompatibilityLedger, CompatibilityReport};
+use crate::services::embedded_fonts::{EmbeddedFont, EmbeddedFontExtractor};
+use crate::services::embedded_markup::inject_math_and_svg_text;
+use crate::services::epub_parser::{EpubDocument, EpubOpenError, EpubParser, TocEntry};
+use crate::services::secrets::EpubPasswordStore;
+use crate::services::layout_service::{structure_html, BookLayout, TextBlock};
+use crate::services::media_overlays::{MediaOverlay, MediaOverlayParser};
+use crate::services::pdf_parser::PdfParser;
+use crate::services::preferences_service::PreferencesService;
+use crate::services::reading_style_service::inject_stylesheet;
+use crate::services::navigation_history::{
+    BookNavigationHistory, NavigationEntry, NavigationHistoryStore, NavigationSource,
+};
+use crate::utils::chapter_cache::ChapterCache;
+use crate::utils::text_find::{match_context, FindOptions, TextFinder};
 
 /// Reading service for managing book content and reading experience
 pub struct ReadingService {
     theme_manager: Arc<RwLock<ThemeManager>>,
     content_cache: Arc<RwLock<HashMap<String, BookContent>>>,
     pagination_cache: Arc<RwLock<HashMap<String, Vec<Page>>>>,
+    compatibility_ledger: Arc<RwLock<Option<CompatibilityLedger>>>,
+    navigation_history: Arc<RwLock<Option<NavigationHistoryStore>>>,
+    reference_panes: Arc<RwLock<HashMap<String, ReferencePane>>>,
+    alt_text_service: Arc<RwLock<Option<AltTextService>>>,
+    chapter_read_threshold: Arc<RwLock<ChapterReadThreshold>>,
+    chapter_progress: Arc<RwLock<HashMap<String, HashMap<String, ChapterProgress>>>>,
+    chapter_progress_path: Arc<RwLock<Option<PathBuf>>>,
+    lazy_books: Arc<RwLock<HashMap<String, LazyBook>>>,
+    chapter_cache: Arc<RwLock<Option<ChapterCache>>>,
+    preferences: Arc<RwLock<Option<Arc<PreferencesService>>>>, // Source of the reading speed
+    find_session: Arc<RwLock<Option<FindSession>>>,
 }
 
 /// Book content structure
@@ -33,6 +63,36 @@ pub struct Chapter {
     pub content: String,
     pub word_count: usize,
     pub order: usize,
+    pub media_overlay: Option<MediaOverlay>, // Narration synchronized with the text
+    pub blocks: Vec<TextBlock>, // Paragraphs and headings; empty when the text has none, as on PDF pages
+}
+
+/// Metadata and reading order of a lazily opened book
+#[derive(Debug, Clone)]
+pub struct BookOutline {
+    pub book_id: String,
+    pub title: String,
+    pub author: String,
+    pub chapters: Vec<ChapterSummary>,
+    pub toc: Vec<TocEntry>,
+}
+
+/// Spine entry of a lazily opened book, before its content is loaded
+#[derive(Debug, Clone)]
+pub struct ChapterSummary {
+    pub id: String,
+    pub title: String,
+    pub order: usize,
+}
+
+/// EPUB kept open for on-demand chapter loading
+struct LazyBook {
+    doc: Arc<std::sync::Mutex<EpubDocument>>,
+    outline: BookOutline,
+    toc: Vec<TocEntry>,
+    generated_alt_text: HashMap<String, String>,
+    media_overlays: HashMap<String, String>,
+    chapters: HashMap<String, Chapter>,
 }
 
 /// Page structure for pagination
@@ -46,6 +106,25 @@ pub struct Page {
     pub end_position: usize,
 }
 
+/// How far a chapter has been read
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChapterProgress {
+    pub max_scroll_fraction: f32,
+    pub last_page_dwell_seconds: u32,
+    pub is_read: bool,
+}
+
+/// Pinned reference pane shown beside the main reading pane
+///
+/// Keeps its own position, pagination and history so following links in the
+/// reference pane never moves the main reading position.
+#[derive(Debug, Clone)]
+pub struct ReferencePane {
+    pub position: ReadingPosition,
+    pub pagination: PaginationSettings,
+    pub history: BookNavigationHistory,
+}
+
 /// Pagination settings
 #[derive(Debug, Clone)]
 pub struct PaginationSettings {
@@ -113,11 +192,262 @@ impl ReadingService {
             theme_manager: Arc::new(RwLock::new(ThemeManager::new())),
             content_cache: Arc::new(RwLock::new(HashMap::new())),
             pagination_cache: Arc::new(RwLock::new(HashMap::new())),
+            compatibility_ledger: Arc::new(RwLock::new(None)),
+            navigation_history: Arc::new(RwLock::new(None)),
+            reference_panes: Arc::new(RwLock::new(HashMap::new())),
+            alt_text_service: Arc::new(RwLock::new(None)),
+            chapter_read_threshold: Arc::new(RwLock::new(ChapterReadThreshold::default())),
+            chapter_progress: Arc::new(RwLock::new(HashMap::new())),
+            chapter_progress_path: Arc::new(RwLock::new(None)),
+            lazy_books: Arc::new(RwLock::new(HashMap::new())),
+            chapter_cache: Arc::new(RwLock::new(None)),
+            preferences: Arc::new(RwLock::new(None)),
+            find_session: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Attach the opt-in compatibility ledger, or detach it with `None`
+    pub async fn set_compatibility_ledger(&self, ledger: Option<CompatibilityLedger>) {
+        let mut current = self.compatibility_ledger.write().await;
+        *current = ledger;
+    }
+
+    /// Open the ledger in the app data directory when enabled, and detach it when not
+    pub async fn set_compatibility_ledger_enabled(&self, enabled: bool) -> Result<()> {
+        let mut current = self.compatibility_ledger.write().await;
+        if enabled && current.is_none() {
+            *current = Some(tokio::task::spawn_blocking(CompatibilityLedger::open_default).await??);
+        } else if !enabled {
+            *current = None;
+        }
+        Ok(())
+    }
+
+    /// Get the compatibility diagnostics report, if the ledger is enabled
+    pub async fn get_compatibility_report(&self) -> Option<CompatibilityReport> {
+        self.compatibility_ledger.read().await.as_ref().map(|ledger| ledger.report())
+    }
+
+    /// Set when a chapter counts as read
+    pub async fn set_chapter_read_threshold(&self, threshold: ChapterReadThreshold) {
+        let mut current = self.chapter_read_threshold.write().await;
+        *current = threshold;
+    }
+
+    /// Get when a chapter counts as read
+    pub async fn get_chapter_read_threshold(&self) -> ChapterReadThreshold {
+        self.chapter_read_threshold.read().await.clone()
+    }
+
+    /// Follow the user's preferences for the reading speed
+    pub async fn set_preferences(&self, preferences: Arc<PreferencesService>) {
+        let mut current = self.preferences.write().await;
+        *current = Some(preferences);
+    }
+
+    /// Get the reading speed used for reading time estimates, from the preferences
+    pub async fn get_reading_speed_wpm(&self) -> u32 {
+        match self.preferences.read().await.as_ref() {
+            Some(preferences) => preferences.get().reading.reading_speed_wpm.max(1),
+            None => DEFAULT_READING_SPEED_WPM,
+        }
+    }
+
+    /// Keep chapter progress in a file, starting from what it already holds
+    pub async fn set_chapter_progress_file(&self, path: PathBuf) -> Result<()> {
+        let progress = match tokio::fs::read_to_string(&path).await {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        *self.chapter_progress.write().await = progress;
+        *self.chapter_progress_path.write().await = Some(path);
+        Ok(())
+    }
+
+    /// Record a chapter view, returning true when the chapter has just become read
+    ///
+    /// `last_page_dwell_seconds` is the time spent on the chapter's last page in
+    /// this view, or zero when the last page is not showing.
+    pub async fn record_chapter_view(
+        &self,
+        book_id: &str,
+        chapter_id: &str,
+        scroll_fraction: f32,
+        last_page_dwell_seconds: u32,
+    ) -> Result<bool> {
+        let threshold = self.get_chapter_read_threshold().await;
+        let mut progress = self.chapter_progress.write().await;
+        let chapter = progress
+            .entry(book_id.to_string())
+            .or_default()
+            .entry(chapter_id.to_string())
+            .or_default();
+
+        chapter.max_scroll_fraction = chapter.max_scroll_fraction.max(scroll_fraction.clamp(0.0, 1.0));
+        chapter.last_page_dwell_seconds = chapter.last_page_dwell_seconds.max(last_page_dwell_seconds);
+        let newly_read = !chapter.is_read
+            && threshold.is_met(chapter.max_scroll_fraction, chapter.last_page_dwell_seconds);
+        chapter.is_read |= newly_read;
+
+        if let Some(path) = self.chapter_progress_path.read().await.as_ref() {
+            tokio::fs::write(path, serde_json::to_string(&*progress)?).await?;
+        }
+        Ok(newly_read)
+    }
+
+    /// Get ids of chapters that count as read
+    pub async fn get_read_chapters(&self, book_id: &str) -> Vec<String> {
+        self.chapter_progress.read().await
+            .get(book_id)
+            .map(|chapters| {
+                chapters.iter()
+                    .filter(|(_, progress)| progress.is_read)
+                    .map(|(chapter_id, _)| chapter_id.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Attach generated image alt text so it is included in chapter text
+    pub async fn set_alt_text_service(&self, service: Option<AltTextService>) {
+        let mut current = self.alt_text_service.write().await;
+        *current = service;
+    }
+
+    /// Keep chapters read from lazily opened books in a disk cache
+    pub async fn set_chapter_cache(&self, cache: Option<ChapterCache>) {
+        let mut current = self.chapter_cache.write().await;
+        *current = cache;
+    }
+
+    /// Attach the persisted in-book navigation history
+    pub async fn set_navigation_history(&self, history: Option<NavigationHistoryStore>) {
+        let mut current = self.navigation_history.write().await;
+        *current = history;
+    }
+
+    /// Record a TOC, search result or footnote jump so it can be undone with `go_back`
+    pub async fn record_navigation(
+        &self,
+        book_id: &str,
+        from: ReadingPosition,
+        source: NavigationSource,
+    ) -> Result<()> {
+        match self.navigation_history.write().await.as_mut() {
+            Some(history) => history.record_jump(book_id, from, source).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Return to the position before the last jump
+    pub async fn go_back(&self, book_id: &str, current: ReadingPosition) -> Result<Option<NavigationEntry>> {
+        match self.navigation_history.write().await.as_mut() {
+            Some(history) => history.go_back(book_id, current).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Redo a jump undone with `go_back`
+    pub async fn go_forward(&self, book_id: &str, current: ReadingPosition) -> Result<Option<NavigationEntry>> {
+        match self.navigation_history.write().await.as_mut() {
+            Some(history) => history.go_forward(book_id, current).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Pin a reference pane for a book, e.g. keeping the glossary open
+    pub async fn pin_reference_pane(
+        &self,
+        book_id: &str,
+        position: ReadingPosition,
+        pagination: PaginationSettings,
+    ) {
+        let pane = ReferencePane {
+            position,
+            pagination,
+            history: BookNavigationHistory::default(),
+        };
+        self.reference_panes.write().await.insert(book_id.to_string(), pane);
+    }
+
+    /// Close the reference pane for a book
+    pub async fn unpin_reference_pane(&self, book_id: &str) {
+        self.reference_panes.write().await.remove(book_id);
+    }
+
+    /// Get the reference pane for a book, if one is pinned
+    pub async fn get_reference_pane(&self, book_id: &str) -> Option<ReferencePane> {
+        self.reference_panes.read().await.get(book_id).cloned()
+    }
+
+    /// Move the reference pane, recording a jump when a source is given
+    pub async fn move_reference_pane(
+        &self,
+        book_id: &str,
+        position: ReadingPosition,
+        source: Option<NavigationSource>,
+    ) -> Result<()> {
+        let mut panes = self.reference_panes.write().await;
+        let pane = panes.get_mut(book_id)
+            .ok_or_else(|| anyhow::anyhow!("No reference pane pinned for book"))?;
+
+        if let Some(source) = source {
+            pane.history.record_jump(pane.position.clone(), source);
         }
+        pane.position = position;
+        Ok(())
+    }
+
+    /// Return the reference pane to its position before the last jump
+    pub async fn reference_pane_go_back(&self, book_id: &str) -> Option<ReadingPosition> {
+        let mut panes = self.reference_panes.write().await;
+        let pane = panes.get_mut(book_id)?;
+        let entry = pane.history.go_back(pane.position.clone())?;
+        pane.position = entry.position.clone();
+        Some(entry.position)
+    }
+
+    /// Redo a reference pane jump undone with `reference_pane_go_back`
+    pub async fn reference_pane_go_forward(&self, book_id: &str) -> Option<ReadingPosition> {
+        let mut panes = self.reference_panes.write().await;
+        let pane = panes.get_mut(book_id)?;
+        let entry = pane.history.go_forward(pane.position.clone())?;
+        pane.position = entry.position.clone();
+        Some(entry.position)
+    }
+
+    /// Resize the reference pane, re-paginating with its own settings
+    pub async fn set_reference_pane_pagination(&self, book_id: &str, pagination: PaginationSettings) -> Result<()> {
+        let mut panes = self.reference_panes.write().await;
+        let pane = panes.get_mut(book_id)
+            .ok_or_else(|| anyhow::anyhow!("No reference pane pinned for book"))?;
+        pane.pagination = pagination;
+        Ok(())
+    }
+
+    /// Paginate content for the reference pane's viewport
+    pub async fn paginate_reference_pane(&self, content: &BookContent) -> Result<Vec<Page>> {
+        let pagination = self.get_reference_pane(&content.book_id).await
+            .ok_or_else(|| anyhow::anyhow!("No reference pane pinned for book"))?
+            .pagination;
+        self.paginate_content(content, &pagination).await
     }
 
     /// Load book content for reading
+    ///
+    /// Password-protected EPUBs without a cached password fail with
+    /// `EpubOpenError::PasswordRequired`; retry with `load_book_content_with_password`.
     pub async fn load_book_content(&self, book: &Book) -> Result<BookContent> {
+        self.load_book_content_with_password(book, None).await
+    }
+
+    /// Load book content, supplying a password for zip-encrypted EPUBs
+    pub async fn load_book_content_with_password(
+        &self,
+        book: &Book,
+        password: Option<&str>,
+    ) -> Result<BookContent> {
         // Check cache first
         {
             let cache = self.content_cache.read().await;
@@ -129,7 +459,7 @@ impl ReadingService {
         // Parse book content based on format
         let content = match book.file_format {
             crate::models::BookFormat::Epub => {
-                self.parse_epub_content(book).await?
+                self.parse_epub_content(book, password).await?
             }
             crate::models::BookFormat::Pdf => {
                 self.parse_pdf_content(book).await?
@@ -149,38 +479,73 @@ impl ReadingService {
     }
 
     /// Parse EPUB content
-    async fn parse_epub_content(&self, book: &Book) -> Result<BookContent> {
-        use epub::doc::EpubDoc;
-        
-        let mut doc = EpubDoc::new(&book.file_path)?;
-        let mut chapters = Vec::new();
-        let mut total_word_count = 0;
+    async fn parse_epub_content(&self, book: &Book, password: Option<&str>) -> Result<BookContent> {
+        let result = self.parse_epub_document(book, password).await;
+        self.record_compatibility(book, &result).await;
+        result
+    }
 
-        // Get spine (reading order)
-        let spine = doc.spine.clone();
-        
-        for (order, spine_item) in spine.iter().enumerate() {
-            if let Some(id) = &spine_item.id {
-                if let Some((content, _)) = doc.get_resource_str(id) {
-                    let cleaned_content = self.clean_html_content(&content);
-                    let word_count = self.count_words(&cleaned_content);
-                    
-                    let chapter = Chapter {
-                        id: id.clone(),
-                    title: format!("Chapter {}", order + 1),
-                    content: cleaned_content,
-                    word_count,
-                    order,
+    /// Record a parse outcome in the compatibility ledger, if enabled
+    async fn record_compatibility(&self, book: &Book, result: &Result<BookContent>) {
+        let mut ledger = self.compatibility_ledger.write().await;
+        let Some(ledger) = ledger.as_mut() else {
+            return;
+        };
+
+        let error_kind = match result {
+            Ok(_) => None,
+            Err(e) => match e.downcast_ref::<EpubOpenError>() {
+                // Missing or wrong passwords are not format problems
+                Some(EpubOpenError::PasswordRequired { .. } | EpubOpenError::InvalidPassword { .. }) => return,
+                Some(open_error) => Some(open_error.kind()),
+                None => Some("content"),
+            },
+        };
+
+        let recorded = match EpubParser::detect_features(&book.file_path) {
+            Ok(features) => match error_kind {
+                None => ledger.record_success(&features).await,
+                Some(kind) => ledger.record_failure(&features, kind).await,
+            },
+            Err(e) => ledger.record_failure(&Default::default(), e.kind()).await,
+        };
+
+        if let Err(e) = recorded {
+            tracing::warn!("Failed to update compatibility ledger: {}", e);
+        }
+    }
+
+    /// Open an EPUB and extract its chapters
+    async fn parse_epub_document(&self, book: &Book, password: Option<&str>) -> Result<BookContent> {
+        let generated_alt_text = self.generated_alt_text(&book.id).await?;
+        let mut doc = self.open_epub_document(book, password).await?;
+
+        // Decompressing and cleaning every chapter is blocking work
+        let (chapters, total_word_count) = tokio::task::spawn_blocking(move || {
+            let mut chapters = Vec::new();
+            let mut total_word_count = 0;
+
+            // Chapter titles come from the TOC (NCX for EPUB2 books)
+            let toc = EpubParser::table_of_contents(&mut doc);
+            let media_overlays = MediaOverlayParser::overlay_paths(&mut doc);
+
+            // Get spine (reading order)
+            let spine = doc.spine.clone();
+
+            for (order, spine_item) in spine.iter().enumerate() {
+                let Some(content) = EpubParser::read_text(&mut doc, &spine_item.idref) else {
+                    continue;
                 };
-                
-                    total_word_count += word_count;
+                if let Some(chapter) = Self::read_chapter(&mut doc, &spine_item.idref, content, order, &toc, &generated_alt_text, &media_overlays) {
+                    total_word_count += chapter.word_count;
                     chapters.push(chapter);
                 }
             }
-        }
-
-        // Estimate reading time (average 200 words per minute)
-        let estimated_reading_time = (total_word_count as f32 / 200.0).ceil() as u32;
+            (chapters, total_word_count)
+        })
+        .await?;
+        
+        let estimated_reading_time = (total_word_count as u32).div_ceil(self.get_reading_speed_wpm().await);
 
         Ok(BookContent {
             book_id: book.id.clone(),
@@ -192,49 +557,315 @@ impl ReadingService {
         })
     }
 
-    /// Parse PDF content
-    async fn parse_pdf_content(&self, book: &Book) -> Result<BookContent> {
-        // For now, return a placeholder
-        // In a real implementation, you would use pdf-extract or similar
-        let content = format!("PDF content from {}", book.title);
-        let word_count = self.count_words(&content);
-
-        let chapter = Chapter {
-            id: "pdf_content".to_string(),
-            title: "PDF Content".to_string(),
-            content,
+    /// Generated alt text for a book's images, if alt text generation is enabled
+    async fn generated_alt_text(&self, book_id: &str) -> Result<HashMap<String, String>> {
+        match self.alt_text_service.read().await.as_ref() {
+            Some(service) => service.get_alt_texts(book_id).await,
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    /// Open an EPUB, using and remembering its password if it is encrypted
+    async fn open_epub_document(&self, book: &Book, password: Option<&str>) -> Result<EpubDocument> {
+        // The keyring blocks on its own runtime, so it must stay off async worker threads
+        let cached_password = match password {
+            Some(_) => None,
+            None => {
+                let book_id = book.id.clone();
+                tokio::task::spawn_blocking(move || EpubPasswordStore::get(&book_id)).await?
+            }
+        };
+        let file_path = book.file_path.clone();
+        let open_password = password.map(str::to_string).or(cached_password);
+        let doc = tokio::task::spawn_blocking(move || EpubParser::open(&file_path, open_password.as_deref())).await??;
+        
+        // Remember a freshly entered password for the next time the book is opened
+        if let Some(password) = password {
+            let file_path = book.file_path.clone();
+            if tokio::task::spawn_blocking(move || EpubParser::is_encrypted(&file_path)).await?? {
+                let book_id = book.id.clone();
+                let password = password.to_string();
+                tokio::task::spawn_blocking(move || EpubPasswordStore::store(&book_id, &password)).await??;
+            }
+        }
+
+        Ok(doc)
+    }
+
+    /// Read and clean a single spine item
+    fn read_chapter(
+        doc: &mut EpubDocument,
+        id: &str,
+        content: String,
+        order: usize,
+        toc: &[TocEntry],
+        generated_alt_text: &HashMap<String, String>,
+        media_overlays: &HashMap<String, String>,
+    ) -> Option<Chapter> {
+        let resource_path = doc.resources.get(id).map(|(path, _)| path.clone());
+
+        let chapter_path = resource_path.as_ref()
+            .map(|path| path.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_default();
+        let content = inject_math_and_svg_text(&content);
+        let content = inject_image_alt_text(&content, &chapter_path, generated_alt_text);
+        let structured = structure_html(&content);
+        let word_count = Self::count_words(&structured.text);
+        let title = resource_path
+            .and_then(|path| EpubParser::toc_label_for_path(toc, &path).map(str::to_string))
+            .unwrap_or_else(|| format!("Chapter {}", order + 1));
+        let media_overlay = media_overlays.get(id)
+            .and_then(|smil_path| MediaOverlayParser::load(doc, smil_path));
+
+        Some(Chapter {
+            id: id.to_string(),
+            title,
+            content: structured.text,
             word_count,
-            order: 0,
+            order,
+            media_overlay,
+            blocks: structured.blocks,
+        })
+    }
+
+    /// Open an EPUB for on-demand reading
+    ///
+    /// Only the package document, spine and navigation are parsed; chapters and
+    /// images are read from the archive when requested with `get_chapter` and
+    /// `get_book_resource`. Meant for very large books where `load_book_content`
+    /// would extract everything up front.
+    pub async fn open_book_lazily(&self, book: &Book, password: Option<&str>) -> Result<BookOutline> {
+        let generated_alt_text = self.generated_alt_text(&book.id).await?;
+        let doc = self.open_epub_document(book, password).await?;
+        let (doc, toc, media_overlays) = tokio::task::spawn_blocking(move || {
+            let mut doc = doc;
+            let toc = EpubParser::table_of_contents(&mut doc);
+            let media_overlays = MediaOverlayParser::overlay_paths(&mut doc);
+            (doc, toc, media_overlays)
+        })
+        .await?;
+
+        let chapters = doc.spine.iter()
+            .enumerate()
+            .filter_map(|(order, spine_item)| {
+                let (path, _) = doc.resources.get(&spine_item.idref)?;
+                let title = EpubParser::toc_label_for_path(&toc, path)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("Chapter {}", order + 1));
+                Some(ChapterSummary { id: spine_item.idref.clone(), title, order })
+            })
+            .collect();
+
+        let outline = BookOutline {
+            book_id: book.id.clone(),
+            title: book.title.clone(),
+            author: book.author.clone(),
+            chapters,
+            toc: toc.clone(),
+        };
+
+        let mut lazy_books = self.lazy_books.write().await;
+        lazy_books.insert(book.id.clone(), LazyBook {
+            doc: Arc::new(std::sync::Mutex::new(doc)),
+            outline: outline.clone(),
+            toc,
+            generated_alt_text,
+            media_overlays,
+            chapters: HashMap::new(),
+        });
+
+        Ok(outline)
+    }
+
+    /// Load a chapter of a lazily opened book
+    pub async fn get_chapter(&self, book_id: &str, chapter_id: &str) -> Result<Chapter> {
+        let (doc, order, toc, generated_alt_text, media_overlays) = {
+            let lazy_books = self.lazy_books.read().await;
+            let lazy_book = lazy_books.get(book_id)
+                .ok_or_else(|| anyhow::anyhow!("Book is not open: {}", book_id))?;
+            if let Some(chapter) = lazy_book.chapters.get(chapter_id) {
+                return Ok(chapter.clone());
+            }
+            let order = lazy_book.outline.chapters.iter()
+                .find(|chapter| chapter.id == chapter_id)
+                .map(|chapter| chapter.order)
+                .ok_or_else(|| anyhow::anyhow!("Chapter not found: {}", chapter_id))?;
+            (
+                lazy_book.doc.clone(),
+                order,
+                lazy_book.toc.clone(),
+                lazy_book.generated_alt_text.clone(),
+                lazy_book.media_overlays.clone(),
+            )
+        };
+
+        let cached = self.load_cached_chapter(book_id, chapter_id).await;
+        let was_cached = cached.is_some();
+        let id = chapter_id.to_string();
+        let (chapter, content) = tokio::task::spawn_blocking(move || {
+            let mut doc = doc.lock().map_err(|_| anyhow::anyhow!("EPUB document lock poisoned"))?;
+            let content = match cached {
+                Some(content) => content,
+                None => EpubParser::read_text(&mut doc, &id)
+                    .ok_or_else(|| anyhow::anyhow!("Chapter could not be read: {}", id))?,
+            };
+            let chapter = Self::read_chapter(&mut doc, &id, content.clone(), order, &toc, &generated_alt_text, &media_overlays)
+                .ok_or_else(|| anyhow::anyhow!("Chapter could not be read: {}", id))?;
+            anyhow::Ok((chapter, content))
+        })
+        .await??;
+        if !was_cached {
+            self.store_cached_chapter(book_id, chapter_id, &content).await;
+        }
+
+        let mut lazy_books = self.lazy_books.write().await;
+        if let Some(lazy_book) = lazy_books.get_mut(book_id) {
+            lazy_book.chapters.insert(chapter_id.to_string(), chapter.clone());
+        }
+        Ok(chapter)
+    }
+
+    /// Get a chapter's XHTML styled with the current reading theme, for rendering
+    pub async fn get_chapter_html(&self, book_id: &str, chapter_id: &str) -> Result<String> {
+        let doc = {
+            let lazy_books = self.lazy_books.read().await;
+            lazy_books.get(book_id)
+                .ok_or_else(|| anyhow::anyhow!("Book is not open: {}", book_id))?
+                .doc.clone()
+        };
+
+        let html = match self.load_cached_chapter(book_id, chapter_id).await {
+            Some(html) => html,
+            None => {
+                let id = chapter_id.to_string();
+                let html = tokio::task::spawn_blocking(move || {
+                    let mut doc = doc.lock().map_err(|_| anyhow::anyhow!("EPUB document lock poisoned"))?;
+                    EpubParser::read_text(&mut doc, &id).ok_or_else(|| anyhow::anyhow!("Chapter could not be read: {}", id))
+                })
+                .await??;
+                self.store_cached_chapter(book_id, chapter_id, &html).await;
+                html
+            }
+        };
+
+        match self.theme_manager.read().await.current_stylesheet() {
+            Some(css) => Ok(inject_stylesheet(&html, &css)),
+            None => Ok(html),
+        }
+    }
+
+    /// Chapter XHTML from the disk cache, if one is attached and holds it
+    async fn load_cached_chapter(&self, book_id: &str, chapter_id: &str) -> Option<String> {
+        let cache = self.chapter_cache.read().await;
+        match cache.as_ref()?.load(book_id, chapter_id).await {
+            Ok(html) => html,
+            Err(e) => {
+                tracing::warn!("Failed to read cached chapter {} of {}: {}", chapter_id, book_id, e);
+                None
+            }
+        }
+    }
+
+    /// Keep chapter XHTML in the disk cache, if one is attached
+    async fn store_cached_chapter(&self, book_id: &str, chapter_id: &str, html: &str) {
+        if let Some(cache) = self.chapter_cache.read().await.as_ref() {
+            if let Err(e) = cache.store(book_id, chapter_id, html).await {
+                tracing::warn!("Failed to cache chapter {} of {}: {}", chapter_id, book_id, e);
+            }
+        }
+    }
+
+    /// Read an image or other resource of a lazily opened book by its archive path
+    pub async fn get_book_resource(&self, book_id: &str, path: &str) -> Result<Option<(Vec<u8>, String)>> {
+        let doc = {
+            let lazy_books = self.lazy_books.read().await;
+            lazy_books.get(book_id)
+                .ok_or_else(|| anyhow::anyhow!("Book is not open: {}", book_id))?
+                .doc.clone()
         };
 
+        let path = path.to_string();
+        tokio::task::spawn_blocking(move || {
+            let mut doc = doc.lock().map_err(|_| anyhow::anyhow!("EPUB document lock poisoned"))?;
+            let mime = doc.get_resource_mime_by_path(&path)
+                .unwrap_or_else(|| "application/octet-stream".to_string());
+            Ok(doc.get_resource_by_path(&path).map(|data| (data, mime)))
+        })
+        .await?
+    }
+
+    /// Get the fonts embedded in an EPUB, de-obfuscated for the renderer
+    ///
+    /// Uses the open archive of a lazily opened book when there is one.
+    pub async fn get_embedded_fonts(&self, book: &Book) -> Result<Vec<EmbeddedFont>> {
+        let lazy_doc = self.lazy_books.read().await.get(&book.id).map(|lazy_book| lazy_book.doc.clone());
+        match lazy_doc {
+            Some(doc) => tokio::task::spawn_blocking(move || {
+                let mut doc = doc.lock().map_err(|_| anyhow::anyhow!("EPUB document lock poisoned"))?;
+                Ok(EmbeddedFontExtractor::extract(&mut doc))
+            })
+            .await?,
+            None => {
+                let mut doc = self.open_epub_document(book, None).await?;
+                Ok(tokio::task::spawn_blocking(move || EmbeddedFontExtractor::extract(&mut doc)).await?)
+            }
+        }
+    }
+
+    /// Close a lazily opened book, releasing its archive and loaded chapters
+    pub async fn close_lazy_book(&self, book_id: &str) {
+        self.lazy_books.write().await.remove(book_id);
+    }
+
+    /// Parse PDF content, one chapter per page so annotations keep their page numbers
+    async fn parse_pdf_content(&self, book: &Book) -> Result<BookContent> {
+        let file_path = book.file_path.clone();
+        let pages = tokio::task::spawn_blocking(move || PdfParser::extract_pages(&file_path)).await??;
+
+        let mut chapters = Vec::new();
+        let mut total_word_count = 0;
+
+        for (order, page_text) in pages.iter().enumerate() {
+            let content = page_text.split_whitespace().collect::<Vec<_>>().join(" ");
+            let word_count = Self::count_words(&content);
+
+            chapters.push(Chapter {
+                id: format!("page-{}", order + 1),
+                title: format!("Page {}", order + 1),
+                content,
+                word_count,
+                order,
+                media_overlay: None,
+                blocks: Vec::new(),
+            });
+            total_word_count += word_count;
+        }
+
         Ok(BookContent {
             book_id: book.id.clone(),
             title: book.title.clone(),
             author: book.author.clone(),
-            chapters: vec![chapter],
-            total_word_count: word_count,
-            estimated_reading_time: (word_count as f32 / 200.0).ceil() as u32,
+            chapters,
+            total_word_count,
+            estimated_reading_time: (total_word_count as u32).div_ceil(self.get_reading_speed_wpm().await),
         })
     }
 
+    /// Count the words of each spine item, without building full chapters
+    pub(crate) fn chapter_word_counts(doc: &mut EpubDocument) -> Vec<u32> {
+        doc.spine.clone().iter()
+            .filter_map(|spine_item| EpubParser::read_text(doc, &spine_item.idref))
+            .map(|html| Self::count_words(&Self::clean_html_content(&inject_math_and_svg_text(&html))) as u32)
+            .collect()
+    }
+
     /// Clean HTML content for reading
-    fn clean_html_content(&self, html: &str) -> String {
-        // Remove HTML tags but preserve structure
-        let re = Regex::new(r"<[^>]+>").unwrap();
-        let cleaned = re.replace_all(html, " ");
-        
-        // Clean up whitespace
-        let re = Regex::new(r"\s+").unwrap();
-        let cleaned = re.replace_all(&cleaned, " ");
-        
-        // Decode HTML entities
-        let cleaned = html_escape::decode_html_entities(&cleaned);
-        
-        cleaned.trim().to_string()
+    fn clean_html_content(html: &str) -> String {
+        structure_html(html).text
     }
 
     /// Count words in text
-    fn count_words(&self, text: &str) -> usize {
+    fn count_words(text: &str) -> usize {
         text.split_whitespace().count()
     }
 
@@ -271,87 +902,23 @@ impl ReadingService {
         content: &BookContent,
         settings: &PaginationSettings,
     ) -> Result<Vec<Page>> {
-        let mut pages = Vec::new();
-        let mut page_number = 1;
-
-        // Calculate available text area
-        let available_width = settings.viewport_width - (settings.margin_horizontal * 2) as u32;
-        let available_height = settings.viewport_height - (settings.margin_vertical * 2) as u32;
-
-        // Estimate characters per line and lines per page
-        let char_width = (settings.font_size as f32 * 0.6) as u32; // Rough estimate
-        let line_height = (settings.font_size as f32 * settings.line_height) as u32;
-        
-        let chars_per_line = available_width / char_width;
-        let lines_per_page = available_height / line_height;
-        let chars_per_page = chars_per_line * lines_per_page;
-
-        // Handle two-column mode
-        let effective_chars_per_page = if settings.two_column_mode {
-            chars_per_page * 2
-        } else {
-            chars_per_page
-        };
-
-        for chapter in &content.chapters {
-            let chapter_content = &chapter.content;
-            let mut start_position = 0;
-
-            while start_position < chapter_content.len() {
-                let end_position = std::cmp::min(
-                    start_position + effective_chars_per_page as usize,
-                    chapter_content.len(),
-                );
-
-                // Find a good break point (end of sentence or word)
-                let actual_end = self.find_break_point(chapter_content, start_position, end_position);
-                
-                let page_content = chapter_content[start_position..actual_end].to_string();
-                let word_count = self.count_words(&page_content);
-
-                pages.push(Page {
-                    page_number,
-                    content: page_content,
-                    word_count,
-                    chapter_id: chapter.id.clone(),
-                    start_position,
-                    end_position: actual_end,
-                });
-
-                page_number += 1;
-                start_position = actual_end;
-            }
-        }
-
-        Ok(pages)
-    }
-
-    /// Find a good break point for pagination
-    fn find_break_point(&self, text: &str, start: usize, max_end: usize) -> usize {
-        if max_end >= text.len() {
-            return text.len();
-        }
-
-        // Try to find end of sentence
-        for i in (start..max_end).rev() {
-            if let Some(ch) = text.chars().nth(i) {
-                if ch == '.' || ch == '!' || ch == '?' {
-                    return i + 1;
-                }
-            }
-        }
-
-        // Try to find end of word
-        for i in (start..max_end).rev() {
-            if let Some(ch) = text.chars().nth(i) {
-                if ch.is_whitespace() {
-                    return i;
-                }
+        let layout = BookLayout::new(&content.chapters, settings);
+        let chapters: HashMap<&str, &Chapter> = content.chapters.iter()
+            .map(|chapter| (chapter.id.as_str(), chapter))
+            .collect();
+
+        Ok(layout.pages.into_iter().map(|page| {
+            let page_content = chapters[page.chapter_id.as_str()].content[page.start_offset..page.end_offset].to_string();
+            Page {
+                page_number: page.page_number,
+                word_count: Self::count_words(&page_content),
+                content: page_content,
+                chapter_id: page.chapter_id,
+                start_position: page.start_offset,
+                end_position: page.end_offset,
             }
-        }
-
-        // Return max_end if no good break point found
-        max_end
+        })
+        .collect())
     }
 
     /// Generate cache key for pagination
@@ -394,41 +961,126 @@ impl ReadingService {
         Ok(())
     }
 
-    /// Search text in book content
+    /// Search text in book content, ignoring case
     pub async fn search_in_content(
         &self,
         content: &BookContent,
         query: &str,
     ) -> Result<Vec<SearchResult>> {
+        let finder = TextFinder::new(query, FindOptions::default())?;
         let mut results = Vec::new();
-        let query_lower = query.to_lowercase();
-
         for chapter in &content.chapters {
-            let chapter_content_lower = chapter.content.to_lowercase();
-            let mut start = 0;
-
-            while let Some(pos) = chapter_content_lower[start..].find(&query_lower) {
-                let absolute_pos = start + pos;
-                let context_start = absolute_pos.saturating_sub(50);
-                let context_end = std::cmp::min(absolute_pos + query.len() + 50, chapter.content.len());
-                
-                let context = &chapter.content[context_start..context_end];
-                
+            for (start, end) in finder.find_all(&chapter.content) {
                 results.push(SearchResult {
                     chapter_id: chapter.id.clone(),
                     chapter_title: chapter.title.clone(),
-                    position: absolute_pos,
-                    context: context.to_string(),
-                    matched_text: query.to_string(),
+                    position: start,
+                    context: match_context(&chapter.content, start, end),
+                    matched_text: chapter.content[start..end].to_string(),
                 });
-
-                start = absolute_pos + 1;
             }
         }
 
         Ok(results)
     }
 
+    /// Find a query in a book, replacing any earlier find
+    ///
+    /// The current match becomes the first one at or after `from`, a chapter
+    /// and offset such as the reading position, so stepping through matches
+    /// starts where the reader is. Matches are counted per chapter.
+    pub async fn find_in_book(
+        &self,
+        content: &BookContent,
+        query: &str,
+        options: FindOptions,
+        from: Option<(&str, usize)>,
+    ) -> Result<FindSummary> {
+        let finder = TextFinder::new(query, options)?;
+        let mut matches = Vec::new();
+        let mut chapters = Vec::new();
+        for (chapter_index, chapter) in content.chapters.iter().enumerate() {
+            let found = finder.find_all(&chapter.content);
+            if found.is_empty() {
+                continue;
+            }
+            chapters.push(ChapterMatchCount {
+                chapter_id: chapter.id.clone(),
+                chapter_title: chapter.title.clone(),
+                count: found.len(),
+            });
+            matches.extend(found.into_iter().map(|(start, end)| FindMatch {
+                chapter_id: chapter.id.clone(),
+                chapter_index,
+                start,
+                end,
+                matched_text: chapter.content[start..end].to_string(),
+                context: match_context(&chapter.content, start, end),
+            }));
+        }
+
+        let from = from.and_then(|(chapter_id, offset)| {
+            let chapter_index = content.chapters.iter().position(|chapter| chapter.id == chapter_id)?;
+            Some((chapter_index, offset))
+        });
+        // Past the last match, the first one is current
+        let current = from
+            .and_then(|from| matches.iter().position(|found| (found.chapter_index, found.start) >= from))
+            .or((!matches.is_empty()).then_some(0));
+
+        let summary = FindSummary {
+            query: query.to_string(),
+            options,
+            total_matches: matches.len(),
+            chapters,
+            current,
+        };
+        *self.find_session.write().await = Some(FindSession { book_id: content.book_id.clone(), matches, current });
+        Ok(summary)
+    }
+
+    /// The current match of the last find
+    pub async fn current_match(&self) -> Option<CurrentMatch> {
+        let session = self.find_session.read().await;
+        session.as_ref()?.current_match()
+    }
+
+    /// Move to the next match of the last find, wrapping around after the last
+    pub async fn next_match(&self) -> Option<CurrentMatch> {
+        self.step_match(true).await
+    }
+
+    /// Move to the previous match of the last find, wrapping around before the first
+    pub async fn prev_match(&self) -> Option<CurrentMatch> {
+        self.step_match(false).await
+    }
+
+    async fn step_match(&self, forward: bool) -> Option<CurrentMatch> {
+        let mut session = self.find_session.write().await;
+        let session = session.as_mut()?;
+        let total = session.matches.len();
+        if total == 0 {
+            return None;
+        }
+        session.current = Some(match (session.current, forward) {
+            (Some(current), true) => (current + 1) % total,
+            (Some(current), false) => (current + total - 1) % total,
+            (None, true) => 0,
+            (None, false) => total - 1,
+        });
+        session.current_match()
+    }
+
+    /// End the find, for instance when its book is closed
+    pub async fn clear_find(&self) {
+        *self.find_session.write().await = None;
+    }
+
+    /// Book the last find was made in
+    pub async fn find_book_id(&self) -> Option<String> {
+        self.find_session.read().await.as_ref().map(|session| session.book_id.clone())
+    }
+
     /// Create highlight annotation
     pub async fn create_highlight(
         &self,
@@ -462,6 +1114,7 @@ impl ReadingService {
         
         let remaining_words = total_words - words_read;
         let estimated_time_remaining = (remaining_words as f32 / reading_speed_wpm as f32).ceil() as u32;
+        let chapters_read = self.get_read_chapters(&content.book_id).await.len();
 
         ReadingStats {
             total_words,
@@ -469,6 +1122,8 @@ impl ReadingService {
             progress,
             estimated_time_remaining,
             estimated_total_time: content.estimated_reading_time,
+            chapters_read,
+            total_chapters: content.chapters.len(),
         }
     }
 
@@ -492,6 +1147,58 @@ pub struct SearchResult {
     pub matched_text: String,
 }
 
+/// One match of an in-book find, as byte offsets into its chapter's text
+#[derive(Debug, Clone, PartialEq)]
+pub struct FindMatch {
+    pub chapter_id: String,
+    pub chapter_index: usize,
+    pub start: usize,
+    pub end: usize,
+    pub matched_text: String,
+    pub context: String,
+}
+
+/// How many matches a chapter has
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChapterMatchCount {
+    pub chapter_id: String,
+    pub chapter_title: String,
+    pub count: usize,
+}
+
+/// Outcome of an in-book find
+#[derive(Debug, Clone, PartialEq)]
+pub struct FindSummary {
+    pub query: String,
+    pub options: FindOptions,
+    pub total_matches: usize,
+    pub chapters: Vec<ChapterMatchCount>, // Only chapters with matches, in reading order
+    pub current: Option<usize>,
+}
+
+/// The match being shown, e.g. "3 of 12"
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurrentMatch {
+    pub index: usize, // Zero-based
+    pub total: usize,
+    pub found: FindMatch,
+}
+
+/// Matches of the last find and which one is shown
+#[derive(Debug, Clone)]
+struct FindSession {
+    book_id: String,
+    matches: Vec<FindMatch>,
+    current: Option<usize>,
+}
+
+impl FindSession {
+    fn current_match(&self) -> Option<CurrentMatch> {
+        let index = self.current?;
+        Some(CurrentMatch { index, total: self.matches.len(), found: self.matches.get(index)?.clone() })
+    }
+}
+
 /// Reading statistics
 #[derive(Debug, Clone)]
 pub struct ReadingStats {
@@ -500,6 +1207,8 @@ pub struct ReadingStats {
     pub progress: f32,
     pub estimated_time_remaining: u32, // in minutes
     pub estimated_total_time: u32,     // in minutes
+    pub chapters_read: usize,          // Chapters meeting the read threshold
+    pub total_chapters: usize,
 }
 
 impl Default for PaginationSettings {
@@ -531,4 +1240,135 @@ impl Default for ReadingService {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_skimmed_chapter_does_not_count_as_read() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let progress_path = temp_dir.path().join("chapter_progress.json");
+        let service = ReadingService::new();
+        service.set_chapter_progress_file(progress_path.clone()).await.unwrap();
+
+        assert!(!service.record_chapter_view("book", "ch1", 0.5, 0).await.unwrap());
+        assert!(!service.record_chapter_view("book", "ch1", 0.3, 2).await.unwrap());
+        assert!(service.record_chapter_view("book", "ch1", 0.6, 6).await.unwrap());
+        // Already read chapters are not reported again
+        assert!(!service.record_chapter_view("book", "ch1", 1.0, 0).await.unwrap());
+
+        assert!(service.record_chapter_view("book", "ch2", 0.95, 0).await.unwrap());
+        assert_eq!(service.get_read_chapters("book").await.len(), 2);
+
+        // Read chapters are remembered across runs
+        let reopened = ReadingService::new();
+        reopened.set_chapter_progress_file(progress_path).await.unwrap();
+        assert_eq!(reopened.get_read_chapters("book").await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_find_in_book_steps_through_matches() {
+        let service = ReadingService::new();
+        let chapter = |id: &str, text: &str| Chapter {
+            id: id.to_string(),
+            title: id.to_uppercase(),
+            content: text.to_string(),
+            word_count: text.split_whitespace().count(),
+            order: 0,
+            media_overlay: None,
+            blocks: Vec::new(),
+        };
+        let content = BookContent {
+            book_id: "book".to_string(),
+            title: "Book".to_string(),
+            author: "Author".to_string(),
+            chapters: vec![
+                chapter("one", "Call me Ishmael. ishmaelite"),
+                chapter("two", "No match here"),
+                chapter("three", "Ishmael again, and Ishmael."),
+            ],
+            total_word_count: 0,
+            estimated_reading_time: 0,
+        };
+
+        let whole_word = FindOptions { whole_word: true, ..FindOptions::default() };
+        let summary = service.find_in_book(&content, "ishmael", whole_word, Some(("two", 0))).await.unwrap();
+        assert_eq!(summary.total_matches, 3);
+        let counts: Vec<(&str, usize)> = summary.chapters.iter().map(|c| (c.chapter_id.as_str(), c.count)).collect();
+        assert_eq!(counts, [("one", 1), ("three", 2)]);
+        assert_eq!(summary.current, Some(1));
+
+        let next = service.next_match().await.unwrap();
+        assert_eq!((next.index, next.total, next.found.chapter_id.as_str(), next.found.start), (2, 3, "three", 19));
+        assert_eq!(service.next_match().await.unwrap().index, 0);
+        assert_eq!(service.prev_match().await.unwrap().index, 2);
+        assert_eq!(service.current_match().await.unwrap().found.matched_text, "Ishmael");
+
+        let options = FindOptions { regex: true, case_sensitive: true, ..FindOptions::default() };
+        let summary = service.find_in_book(&content, r"Ishmael\w+", options, None).await.unwrap();
+        assert_eq!((summary.total_matches, summary.current), (0, None));
+        assert!(service.next_match().await.is_none());
+        assert!(service.find_in_book(&content, "[", options, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lazy_book_loads_chapters_on_demand() {
+        use std::io::Write;
+        use zip::write::{SimpleFileOptions, ZipWriter};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("book.epub");
+        let mut writer = ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let options = SimpleFileOptions::default();
+        let files: [(&str, &[u8]); 6] = [
+            ("mimetype", b"application/epub+zip"),
+            ("META-INF/container.xml", br#"<container><rootfiles><rootfile full-path="OEBPS/content.opf"/></rootfiles></container>"#),
+            ("OEBPS/content.opf", br#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+                <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Big Book</dc:title></metadata>
+                <manifest>
+                    <item id="ch1" href="ch1.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="ch2" href="ch2.xhtml" media-type="application/xhtml+xml" media-overlay="ch2-audio"/>
+                    <item id="ch2-audio" href="ch2.smil" media-type="application/smil+xml"/>
+                    <item id="img" href="plate.png" media-type="image/png"/>
+                </manifest>
+                <spine><itemref idref="ch1"/><itemref idref="ch2"/></spine>
+            </package>"#),
+            ("OEBPS/ch1.xhtml", b"<html><body><p>First chapter text</p></body></html>"),
+            ("OEBPS/ch2.xhtml", b"<html><body><p>Second <b>chapter</b> here</p></body></html>"),
+            ("OEBPS/ch2.smil", br#"<smil><body><par><text src="ch2.xhtml#p1"/><audio src="ch2.mp3" clipEnd="1.5s"/></par></body></smil>"#),
+        ];
+        for (name, data) in files {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.start_file("OEBPS/plate.png", options).unwrap();
+        writer.write_all(&[0x89, b'P', b'N', b'G']).unwrap();
+        writer.finish().unwrap();
+
+        let service = ReadingService::new();
+        let cache_dir = temp_dir.path().join("chapters");
+        service.set_chapter_cache(Some(ChapterCache::new(cache_dir.clone()).unwrap())).await;
+        let book = Book::new("Big Book".to_string(), "Author".to_string(), path, 0, crate::models::BookFormat::Epub);
+        let outline = service.open_book_lazily(&book, None).await.unwrap();
+        assert_eq!(outline.chapters.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), vec!["ch1", "ch2"]);
+
+        let chapter = service.get_chapter(&book.id, "ch2").await.unwrap();
+        assert_eq!(chapter.content, "Second chapter here");
+        assert_eq!(chapter.order, 1);
+        let overlay = chapter.media_overlay.unwrap();
+        assert_eq!(overlay.clips[0].audio_path, "OEBPS/ch2.mp3");
+        assert_eq!(overlay.clips[0].clip_end_ms, Some(1_500));
+        assert!(service.get_chapter(&book.id, "ch1").await.unwrap().media_overlay.is_none());
+        let cached = ChapterCache::new(cache_dir).unwrap().load(&book.id, "ch2").await.unwrap();
+        assert_eq!(cached.as_deref(), Some("<html><body><p>Second <b>chapter</b> here</p></body></html>"));
+
+        let (image, mime) = service.get_book_resource(&book.id, "OEBPS/plate.png").await.unwrap().unwrap();
+        assert_eq!(image.len(), 4);
+        assert_eq!(mime, "image/png");
+
+        assert!(service.get_chapter(&book.id, "missing").await.is_err());
+        service.close_lazy_book(&book.id).await;
+        assert!(service.get_chapter(&book.id, "ch1").await.is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sqlx::{Row, SqlitePool};
+
+use crate::models::reading_theme::{ReadingTheme, ReadingThemePreferences, ThemeManager};
+
+/// Profile used until the reader sets up others
+pub const DEFAULT_PROFILE: &str = "default";
+/// Marks the injected stylesheet, so it can be found and replaced
+const STYLESHEET_ID: &str = "epubreader-theme";
+
+static HEAD_END: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)</head\s*>").unwrap());
+static BODY_START: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)<body[^>]*>").unwrap());
+
+/// Custom reading themes and reading preferences, kept per profile and per book
+///
+/// A book's own preferences, when it has any, replace those of the profile.
+#[derive(Clone)]
+pub struct ReadingStyleService {
+    pool: SqlitePool,
+}
+
+impl ReadingStyleService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize reading style tables
+    pub async fn init_tables(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS reading_style_themes (
+                profile TEXT NOT NULL,
+                name TEXT NOT NULL,
+                theme TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (profile, name)
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Profile-wide preferences have an empty book id
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS reading_style_preferences (
+                profile TEXT NOT NULL,
+                book_id TEXT NOT NULL DEFAULT '',
+                preferences TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (profile, book_id)
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Save a custom theme to a profile, replacing one of the same name
+    pub async fn save_theme(&self, profile: &str, theme: &ReadingTheme) -> Result<()> {
+        if theme.name.trim().is_empty() {
+            return Err(anyhow!("A theme needs a name"));
+        }
+        if ThemeManager::is_builtin_theme(&theme.name) {
+            return Err(anyhow!("{} is a built-in theme; save the copy under another name", theme.name));
+        }
+        theme.validate_colors().map_err(|e| anyhow!(e))?;
+
+        sqlx::query("INSERT OR REPLACE INTO reading_style_themes (profile, name, theme, updated_at) VALUES (?, ?, ?, ?)")
+            .bind(profile)
+            .bind(&theme.name)
+            .bind(serde_json::to_string(theme)?)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_theme(&self, profile: &str, name: &str) -> Result<()> {
+        sqlx::query("DELETE FROM reading_style_themes WHERE profile = ? AND name = ?")
+            .bind(profile)
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// A profile's custom themes, by name
+    pub async fn get_themes(&self, profile: &str) -> Result<Vec<ReadingTheme>> {
+        let rows = sqlx::query("SELECT theme FROM reading_style_themes WHERE profile = ? ORDER BY name")
+            .bind(profile)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter()
+            .map(|row| Ok(serde_json::from_str(&row.get::<String, _>("theme"))?))
+            .collect()
+    }
+
+    /// Save preferences for a profile, or for one book when a book id is given
+    pub async fn save_preferences(
+        &self,
+        profile: &str,
+        book_id: Option<&str>,
+        preferences: &ReadingThemePreferences,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO reading_style_preferences (profile, book_id, preferences, updated_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(profile)
+        .bind(book_id.unwrap_or_default())
+        .bind(serde_json::to_string(preferences)?)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Make a book follow its profile's preferences again
+    pub async fn clear_book_preferences(&self, profile: &str, book_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM reading_style_preferences WHERE profile = ? AND book_id = ?")
+            .bind(profile)
+            .bind(book_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Whether a book has preferences of its own
+    pub async fn has_book_preferences(&self, profile: &str, book_id: &str) -> Result<bool> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM reading_style_preferences WHERE profile = ? AND book_id = ?")
+            .bind(profile)
+            .bind(book_id)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count > 0)
+    }
+
+    /// Preferences a book is read with: its own, else its profile's, else the defaults
+    pub async fn get_preferences(&self, profile: &str, book_id: Option<&str>) -> Result<ReadingThemePreferences> {
+        let row = sqlx::query(
+            "SELECT preferences FROM reading_style_preferences WHERE profile = ? AND book_id IN ('', ?) \
+             ORDER BY book_id DESC LIMIT 1",
+        )
+        .bind(profile)
+        .bind(book_id.unwrap_or_default())
+        .fetch_optional(&self.pool)
+        .await?;
+        match row {
+            Some(row) => Ok(serde_json::from_str(&row.get::<String, _>("preferences"))?),
+            None => Ok(ReadingThemePreferences::default()),
+        }
+    }
+
+    /// Load a profile's themes and a book's preferences into a theme manager
+    pub async fn apply_to(&self, manager: &mut ThemeManager, profile: &str, book_id: Option<&str>) -> Result<()> {
+        for theme in self.get_themes(profile).await? {
+            manager.add_custom_theme(theme);
+        }
+        manager.update_preferences(self.get_preferences(profile, book_id).await?);
+        Ok(())
+    }
+}
+
+/// Add a theme stylesheet to a chapter's XHTML, after the book's own styles
+///
+/// A stylesheet injected earlier is replaced.
+pub fn inject_stylesheet(html: &str, css: &str) -> String {
+    let html = remove_stylesheet(html);
+    let style = format!("<style id=\"{}\">\n{}</style>", STYLESHEET_ID, css);
+    if let Some(head_end) = HEAD_END.find(&html) {
+        format!("{}{}{}", &html[..head_end.start()], style, &html[head_end.start()..])
+    } else if let Some(body) = BODY_START.find(&html) {
+        format!("{}{}{}", &html[..body.end()], style, &html[body.end()..])
+    } else {
+        format!("{}{}", style, html)
+    }
+}
+
+fn remove_stylesheet(html: &str) -> String {
+    let opening = format!("<style id=\"{}\">", STYLESHEET_ID);
+    let Some(start) = html.find(&opening) else {
+        return html.to_string();
+    };
+    match html[start..].find("</style>") {
+        Some(length) => format!("{}{}", &html[..start], &html[start + length + "</style>".len()..]),
+        None => html.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::reading_theme::ThemeTypography;
+
+    #[tokio::test]
+    async fn test_custom_theme_and_preferences_per_profile_and_book() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let styles = ReadingStyleService::new(pool);
+        styles.init_tables().await.unwrap();
+
+        let mut theme = ThemeManager::new().get_theme("paper").unwrap().clone();
+        assert!(styles.save_theme(DEFAULT_PROFILE, &theme).await.is_err());
+        theme.name = "night-owl".to_string();
+        theme.display_name = "Night Owl".to_string();
+        theme.background_color = "#101820".to_string();
+        theme.typography = ThemeTypography { font_family: Some("Literata".to_string()), line_height: Some(1.8), ..ThemeTypography::default() };
+        theme.custom_css = Some("h1 { font-variant: small-caps; } /* </style><script> */".to_string());
+        styles.save_theme(DEFAULT_PROFILE, &theme).await.unwrap();
+        theme.background_color = "dark".to_string();
+        assert!(styles.save_theme(DEFAULT_PROFILE, &theme).await.is_err());
+
+        let profile = ReadingThemePreferences { theme_name: "night-owl".to_string(), font_size: 20, ..ReadingThemePreferences::default() };
+        styles.save_preferences(DEFAULT_PROFILE, None, &profile).await.unwrap();
+        let book = ReadingThemePreferences { custom_css: Some("p { text-indent: 0; }".to_string()), ..profile.clone() };
+        styles.save_preferences(DEFAULT_PROFILE, Some("poems"), &book).await.unwrap();
+        assert_eq!(styles.get_preferences(DEFAULT_PROFILE, Some("novel")).await.unwrap().custom_css, None);
+        assert_eq!(styles.get_preferences("work", Some("poems")).await.unwrap().theme_name, "default");
+
+        let mut manager = ThemeManager::new();
+        styles.apply_to(&mut manager, DEFAULT_PROFILE, Some("poems")).await.unwrap();
+        let css = manager.current_stylesheet().unwrap();
+        assert!(css.starts_with("html, body { background-color: #101820 !important;"));
+        assert!(css.contains("font-family: \"Literata\", serif !important; font-size: 20px !important;"));
+        assert!(css.contains("line-height: 1.8 !important;"));
+        assert!(css.ends_with("h1 { font-variant: small-caps; } /* <\\/style><script> */\np { text-indent: 0; }\n"));
+
+        let chapter = "<html><head><title>I</title></HEAD><body><p>Verse</p></body></html>";
+        let styled = inject_stylesheet(&inject_stylesheet(chapter, "p {}"), &css);
+        assert_eq!(styled.matches("<style").count(), 1);
+        assert!(styled.starts_with("<html><head><title>I</title><style id=\"epubreader-theme\">\nhtml, body"));
+        assert!(styled.ends_with("</style></HEAD><body><p>Verse</p></body></html>"));
+
+        styles.clear_book_preferences(DEFAULT_PROFILE, "poems").await.unwrap();
+        assert!(!styles.has_book_preferences(DEFAULT_PROFILE, "poems").await.unwrap());
+        assert_eq!(styles.get_preferences(DEFAULT_PROFILE, Some("poems")).await.unwrap().font_size, 20);
+    }
+}
@@ -0,0 +1,498 @@
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Value};
+use sqlx::{Row, SqlitePool};
+use tracing::{info, warn};
+
+use crate::models::annotation::Annotation;
+use crate::services::annotation_service::AnnotationService;
+use crate::services::secrets;
+
+const READWISE_API_URL: &str = "https://readwise.io/api/v2";
+/// Provider name the Readwise access token is stored under in the keyring
+pub const READWISE_PROVIDER: &str = "readwise";
+/// Every pushed highlight links back to its annotation, which is how pulled highlights are matched
+const HIGHLIGHT_URL_PREFIX: &str = "epubreader://annotation/";
+/// Highlights sent per create request
+const PUSH_BATCH_SIZE: usize = 100;
+
+/// A highlight as Readwise returns it from its export endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteHighlight {
+    pub id: i64,
+    pub text: String,
+    pub note: Option<String>,
+    pub url: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub is_deleted: bool,
+}
+
+impl RemoteHighlight {
+    /// The annotation this highlight was pushed from, if it came from this app
+    pub fn annotation_id(&self) -> Option<&str> {
+        self.url.as_deref()?.strip_prefix(HIGHLIGHT_URL_PREFIX)
+    }
+}
+
+/// Outcome of a sync with Readwise
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReadwiseSyncSummary {
+    pub pushed: usize,  // New highlights sent
+    pub updated: usize, // Edited highlights sent
+    pub pulled: usize,  // Annotations changed by edits made in Readwise
+}
+
+/// Pushes highlights and notes to Readwise and pulls edits made there back
+///
+/// Each synced annotation is recorded in `readwise_highlights`, so it is sent
+/// once and afterwards only updated. Books can be left out of the sync.
+#[derive(Clone)]
+pub struct ReadwiseService {
+    client: Client,
+    pool: SqlitePool,
+    annotations: AnnotationService,
+}
+
+impl ReadwiseService {
+    pub fn new(pool: SqlitePool, annotations: AnnotationService) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .user_agent(concat!("ebook-reader/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self { client, pool, annotations }
+    }
+
+    /// Initialize Readwise tables
+    pub async fn init_tables(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS readwise_highlights (
+                annotation_id TEXT PRIMARY KEY,
+                readwise_id INTEGER, -- Known once the highlight has been pulled back
+                synced_at TEXT NOT NULL,
+                remote_deleted INTEGER NOT NULL DEFAULT 0,
+                FOREIGN KEY (annotation_id) REFERENCES annotations (id) ON DELETE CASCADE
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS readwise_books (
+                book_id TEXT PRIMARY KEY,
+                enabled INTEGER NOT NULL,
+                FOREIGN KEY (book_id) REFERENCES books (id) ON DELETE CASCADE
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE TABLE IF NOT EXISTS readwise_state (id INTEGER PRIMARY KEY CHECK (id = 1), last_pulled_at TEXT);")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Check whether a Readwise access token is stored
+    pub async fn is_configured(&self) -> bool {
+        secrets::has_api_key_async(READWISE_PROVIDER).await
+    }
+
+    /// Check an access token with Readwise and store it in the keyring
+    pub async fn connect(&self, token: &str) -> Result<()> {
+        let token = token.trim();
+        let response = self.authorized(self.client.get(format!("{}/auth/", READWISE_API_URL)), token)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Readwise rejected the access token ({})", response.status()));
+        }
+        secrets::store_api_key_async(READWISE_PROVIDER, token).await
+    }
+
+    /// Forget the access token and which highlights were synced
+    pub async fn disconnect(&self) -> Result<()> {
+        secrets::remove_api_key_async(READWISE_PROVIDER).await?;
+        sqlx::query("DELETE FROM readwise_highlights").execute(&self.pool).await?;
+        sqlx::query("DELETE FROM readwise_state").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Include or leave out a book's annotations; books are included by default
+    pub async fn set_book_enabled(&self, book_id: &str, enabled: bool) -> Result<()> {
+        sqlx::query("INSERT OR REPLACE INTO readwise_books (book_id, enabled) VALUES (?, ?)")
+            .bind(book_id)
+            .bind(if enabled { 1 } else { 0 })
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Check whether a book's annotations are synced
+    pub async fn is_book_enabled(&self, book_id: &str) -> Result<bool> {
+        let enabled: Option<i64> = sqlx::query_scalar("SELECT enabled FROM readwise_books WHERE book_id = ?")
+            .bind(book_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(enabled.map_or(true, |enabled| enabled != 0))
+    }
+
+    /// Push new and edited annotations, then pull edits made in Readwise
+    pub async fn sync(&self) -> Result<ReadwiseSyncSummary> {
+        let token = secrets::get_api_key_async(READWISE_PROVIDER).await
+            .ok_or_else(|| anyhow!("Readwise is not connected"))?;
+
+        let mut summary = self.push(&token).await?;
+        summary.pulled = self.pull(&token).await?;
+        info!(
+            "Readwise sync: {} pushed, {} updated, {} pulled",
+            summary.pushed, summary.updated, summary.pulled
+        );
+        Ok(summary)
+    }
+
+    async fn push(&self, token: &str) -> Result<ReadwiseSyncSummary> {
+        let mut summary = ReadwiseSyncSummary::default();
+        let pending = self.pending_highlights().await?;
+
+        let (new, edited): (Vec<_>, Vec<_>) = pending.into_iter().partition(|pending| !pending.synced);
+        for batch in new.chunks(PUSH_BATCH_SIZE) {
+            let highlights: Vec<Value> = batch.iter()
+                .map(|pending| highlight_payload(&pending.annotation, &pending.book_title, &pending.book_author))
+                .collect();
+            self.authorized(self.client.post(format!("{}/highlights/", READWISE_API_URL)), token)
+                .json(&json!({ "highlights": highlights }))
+                .send()
+                .await?
+                .error_for_status()?;
+
+            for pending in batch {
+                self.record_synced(&pending.annotation).await?;
+            }
+            summary.pushed += batch.len();
+        }
+
+        for pending in edited {
+            // Edits wait until the highlight's Readwise id has been pulled
+            let Some(readwise_id) = pending.readwise_id else {
+                continue;
+            };
+            let result = self.authorized(self.client.patch(format!("{}/highlights/{}/", READWISE_API_URL, readwise_id)), token)
+                .json(&json!({
+                    "text": pending.annotation.selected_text,
+                    "note": pending.annotation.note.clone().unwrap_or_default(),
+                }))
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match result {
+                Ok(_) => {
+                    self.record_synced(&pending.annotation).await?;
+                    summary.updated += 1;
+                }
+                Err(e) => warn!("Failed to update Readwise highlight {}: {}", readwise_id, e),
+            }
+        }
+
+        Ok(summary)
+    }
+
+    async fn pull(&self, token: &str) -> Result<usize> {
+        let last_pulled_at: Option<String> = sqlx::query_scalar("SELECT last_pulled_at FROM readwise_state WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await?
+            .flatten();
+        let pulled_at = Utc::now();
+
+        let mut highlights = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut query = Vec::new();
+            if let Some(last_pulled_at) = &last_pulled_at {
+                query.push(("updatedAfter", last_pulled_at.clone()));
+            }
+            if let Some(cursor) = &cursor {
+                query.push(("pageCursor", cursor.clone()));
+            }
+            let page: Value = self.authorized(self.client.get(format!("{}/export/", READWISE_API_URL)), token)
+                .query(&query)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            highlights.extend(parse_export(&page));
+            cursor = match &page["nextPageCursor"] {
+                Value::Null => None,
+                next => Some(next.as_str().map(str::to_string).unwrap_or_else(|| next.to_string())),
+            };
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        let changed = self.apply_pulled(&highlights).await?;
+        sqlx::query("INSERT OR REPLACE INTO readwise_state (id, last_pulled_at) VALUES (1, ?)")
+            .bind(pulled_at.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(changed)
+    }
+
+    /// Record Readwise ids and apply edits made in Readwise, returning how many annotations changed
+    ///
+    /// Highlights that didn't come from this app are ignored. A highlight
+    /// deleted in Readwise keeps its annotation but is no longer synced.
+    pub async fn apply_pulled(&self, highlights: &[RemoteHighlight]) -> Result<usize> {
+        let mut changed = 0;
+        for highlight in highlights {
+            let Some(annotation_id) = highlight.annotation_id() else {
+                continue;
+            };
+            let Some(mut annotation) = self.annotations.get_annotation(annotation_id).await? else {
+                continue;
+            };
+
+            let synced_at: Option<String> = sqlx::query_scalar("SELECT synced_at FROM readwise_highlights WHERE annotation_id = ?")
+                .bind(annotation_id)
+                .fetch_optional(&self.pool)
+                .await?;
+            sqlx::query(
+                r#"
+                INSERT INTO readwise_highlights (annotation_id, readwise_id, synced_at, remote_deleted)
+                VALUES (?, ?, ?, ?)
+                ON CONFLICT(annotation_id) DO UPDATE SET
+                    readwise_id = excluded.readwise_id,
+                    remote_deleted = excluded.remote_deleted
+                "#,
+            )
+            .bind(annotation_id)
+            .bind(highlight.id)
+            .bind(annotation.modified_at.to_rfc3339())
+            .bind(if highlight.is_deleted { 1 } else { 0 })
+            .execute(&self.pool)
+            .await?;
+
+            let edited_remotely = match (&synced_at, highlight.updated_at) {
+                (Some(synced_at), Some(updated_at)) => DateTime::parse_from_rfc3339(synced_at)
+                    .map_or(true, |synced_at| updated_at > synced_at),
+                _ => synced_at.is_none(),
+            };
+            let note = highlight.note.clone().filter(|note| !note.trim().is_empty());
+            if highlight.is_deleted || !edited_remotely
+                || (annotation.selected_text == highlight.text && annotation.note == note)
+            {
+                continue;
+            }
+
+            annotation.selected_text = highlight.text.clone();
+            annotation.note = note;
+            annotation.modified_at = Utc::now();
+            self.annotations.update_annotation(&annotation).await?;
+            // Already matches Readwise, so it isn't pushed back
+            self.record_synced(&annotation).await?;
+            changed += 1;
+        }
+        Ok(changed)
+    }
+
+    /// Annotations of included books that were never pushed or changed since their last sync
+    async fn pending_highlights(&self) -> Result<Vec<PendingHighlight>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT a.id, b.title, b.author, r.annotation_id IS NOT NULL AS synced, r.readwise_id
+            FROM annotations a
+            JOIN books b ON b.id = a.book_id
+            LEFT JOIN readwise_highlights r ON r.annotation_id = a.id
+            LEFT JOIN readwise_books rb ON rb.book_id = a.book_id
+            WHERE COALESCE(rb.enabled, 1) = 1
+              AND b.deleted_at IS NULL
+              AND (r.annotation_id IS NULL OR (r.remote_deleted = 0 AND a.modified_at > r.synced_at))
+            ORDER BY a.book_id, a.page_number, a.start_offset
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut pending = Vec::new();
+        for row in rows {
+            let id: String = row.get("id");
+            let Some(annotation) = self.annotations.get_annotation(&id).await? else {
+                continue;
+            };
+            if annotation.selected_text.trim().is_empty() {
+                continue;
+            }
+            pending.push(PendingHighlight {
+                annotation,
+                book_title: row.get("title"),
+                book_author: row.get("author"),
+                synced: row.get("synced"),
+                readwise_id: row.get("readwise_id"),
+            });
+        }
+        Ok(pending)
+    }
+
+    async fn record_synced(&self, annotation: &Annotation) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO readwise_highlights (annotation_id, synced_at) VALUES (?, ?)
+            ON CONFLICT(annotation_id) DO UPDATE SET synced_at = excluded.synced_at
+            "#,
+        )
+        .bind(&annotation.id)
+        .bind(annotation.modified_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    fn authorized(&self, request: RequestBuilder, token: &str) -> RequestBuilder {
+        request.header("Authorization", format!("Token {}", token))
+    }
+}
+
+struct PendingHighlight {
+    annotation: Annotation,
+    book_title: String,
+    book_author: String,
+    synced: bool,
+    readwise_id: Option<i64>,
+}
+
+/// Build the Readwise create payload for an annotation
+fn highlight_payload(annotation: &Annotation, book_title: &str, book_author: &str) -> Value {
+    let mut highlight = json!({
+        "text": annotation.selected_text,
+        "title": book_title,
+        "author": book_author,
+        "source_type": "ebook_reader",
+        "category": "books",
+        "location": annotation.page_number,
+        "location_type": "page",
+        "highlighted_at": annotation.created_at.to_rfc3339(),
+        "highlight_url": format!("{}{}", HIGHLIGHT_URL_PREFIX, annotation.id),
+    });
+    if let Some(note) = annotation.note.as_deref().filter(|note| !note.trim().is_empty()) {
+        highlight["note"] = Value::String(note.to_string());
+    }
+    highlight
+}
+
+/// Read the highlights from a page of Readwise's export endpoint
+fn parse_export(page: &Value) -> Vec<RemoteHighlight> {
+    let mut highlights = Vec::new();
+    for book in page["results"].as_array().into_iter().flatten() {
+        for highlight in book["highlights"].as_array().into_iter().flatten() {
+            let Some(id) = highlight["id"].as_i64() else {
+                continue;
+            };
+            highlights.push(RemoteHighlight {
+                id,
+                text: highlight["text"].as_str().unwrap_or_default().to_string(),
+                note: highlight["note"].as_str().map(str::to_string),
+                url: highlight["url"].as_str()
+                    .or_else(|| highlight["highlight_url"].as_str())
+                    .map(str::to_string),
+                updated_at: highlight["updated_at"].as_str()
+                    .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+                    .map(|date| date.with_timezone(&Utc)),
+                is_deleted: highlight["is_deleted"].as_bool().unwrap_or(false),
+            });
+        }
+    }
+    highlights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::annotation::{AnnotationType, HighlightColor, TextPosition};
+
+    #[tokio::test]
+    async fn test_pending_highlights_and_pulled_edits() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE books (id TEXT PRIMARY KEY, title TEXT NOT NULL, author TEXT NOT NULL, deleted_at TEXT)")
+            .execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO books (id, title, author) VALUES ('dune', 'Dune', 'Frank Herbert'), ('emma', 'Emma', 'Jane Austen')")
+            .execute(&pool).await.unwrap();
+        let annotations = AnnotationService::new(pool.clone());
+        annotations.init_tables().await.unwrap();
+        let service = ReadwiseService::new(pool, annotations.clone());
+        service.init_tables().await.unwrap();
+
+        let position = TextPosition {
+            start_offset: 0,
+            end_offset: 10,
+            paragraph_index: 0,
+            chapter_id: None,
+            line_number: None,
+            column_number: None,
+            quote: None,
+        };
+        let fear = annotations.create_annotation(
+            "dune".to_string(), 8, "Fear is the mind-killer.".to_string(), position.clone(),
+            AnnotationType::Highlight, HighlightColor::Yellow, Some("Litany".to_string()),
+        ).await.unwrap();
+        annotations.create_annotation(
+            "emma".to_string(), 3, "Handsome, clever, and rich".to_string(), position,
+            AnnotationType::Highlight, HighlightColor::Blue, None,
+        ).await.unwrap();
+
+        service.set_book_enabled("emma", false).await.unwrap();
+        assert!(!service.is_book_enabled("emma").await.unwrap());
+        let pending = service.pending_highlights().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        let payload = highlight_payload(&pending[0].annotation, &pending[0].book_title, &pending[0].book_author);
+        assert_eq!(payload["title"], "Dune");
+        assert_eq!(payload["location"], 8);
+        assert_eq!(payload["note"], "Litany");
+
+        // Once pushed, it isn't sent again until it changes
+        service.record_synced(&fear).await.unwrap();
+        assert!(service.pending_highlights().await.unwrap().is_empty());
+
+        let export = json!({
+            "nextPageCursor": null,
+            "results": [{
+                "title": "Dune",
+                "highlights": [
+                    {
+                        "id": 41,
+                        "text": "Fear is the mind-killer.",
+                        "note": "The litany against fear",
+                        "url": payload["highlight_url"],
+                        "updated_at": (Utc::now() + chrono::Duration::minutes(1)).to_rfc3339(),
+                        "is_deleted": false
+                    },
+                    {"id": 42, "text": "From a Kindle", "url": null, "is_deleted": false}
+                ]
+            }]
+        });
+        let highlights = parse_export(&export);
+        assert_eq!(highlights.len(), 2);
+        assert_eq!(service.apply_pulled(&highlights).await.unwrap(), 1);
+        let fear = annotations.get_annotation(&fear.id).await.unwrap().unwrap();
+        assert_eq!(fear.note.as_deref(), Some("The litany against fear"));
+        // The pulled edit isn't pushed back, but later local edits are
+        assert!(service.pending_highlights().await.unwrap().is_empty());
+        let mut edited = fear.clone();
+        edited.note = Some("Bene Gesserit".to_string());
+        edited.modified_at = Utc::now() + chrono::Duration::minutes(2);
+        annotations.update_annotation(&edited).await.unwrap();
+        let pending = service.pending_highlights().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].readwise_id, Some(41));
+        assert!(pending[0].synced);
+    }
+}
@@ -0,0 +1,322 @@
+use std::sync::Arc;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::models::Book;
+use crate::services::book_search_service::BookSearchService;
+use crate::services::database::DatabaseService;
+use crate::services::reading_service::ReadingService;
+
+/// Where a book stands in the search index
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchIndexState {
+    NotIndexed,
+    Indexing,
+    Indexed,
+    Failed,
+}
+
+impl SearchIndexState {
+    /// Get display name
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            SearchIndexState::NotIndexed => "Not indexed",
+            SearchIndexState::Indexing => "Indexing",
+            SearchIndexState::Indexed => "Indexed",
+            SearchIndexState::Failed => "Indexing failed",
+        }
+    }
+
+    /// Convert to the stored representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchIndexState::NotIndexed => "not_indexed",
+            SearchIndexState::Indexing => "indexing",
+            SearchIndexState::Indexed => "indexed",
+            SearchIndexState::Failed => "failed",
+        }
+    }
+
+    /// Convert from the stored representation
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s {
+            "not_indexed" => Some(SearchIndexState::NotIndexed),
+            "indexing" => Some(SearchIndexState::Indexing),
+            "indexed" => Some(SearchIndexState::Indexed),
+            "failed" => Some(SearchIndexState::Failed),
+            _ => None,
+        }
+    }
+}
+
+impl Default for SearchIndexState {
+    fn default() -> Self {
+        SearchIndexState::NotIndexed
+    }
+}
+
+/// Search index status of one book, stored in the library database
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchIndexStatus {
+    pub book_id: String,
+    pub state: SearchIndexState,
+    pub file_size: u64,                       // Book file as it was when indexed
+    pub file_modified: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+    pub error: Option<String>,
+}
+
+/// Result of indexing one book
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexingOutcome {
+    Indexed,
+    Unchanged,
+    Failed(String),
+}
+
+/// Progress event sent after each book is processed
+#[derive(Debug, Clone)]
+pub struct IndexingProgress {
+    pub book_id: String,
+    pub completed: usize,
+    pub total: usize,
+    pub outcome: IndexingOutcome,
+}
+
+/// Totals for an indexing run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexingSummary {
+    pub indexed: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+}
+
+/// Builds the library's full-text search index in the background
+///
+/// Books are indexed one at a time. A book is skipped when its file is the
+/// same as when it was last indexed, including books that failed, so a run
+/// only does work for new and changed books. Pausing takes effect between books.
+pub struct SearchIndexer {
+    database: Arc<DatabaseService>,
+    search: Arc<BookSearchService>,
+    reading_service: ReadingService,
+    paused: watch::Sender<bool>,
+    progress: broadcast::Sender<IndexingProgress>,
+}
+
+impl SearchIndexer {
+    pub fn new(database: Arc<DatabaseService>, search: Arc<BookSearchService>) -> Self {
+        let (progress, _) = broadcast::channel(64);
+        Self {
+            database,
+            search,
+            reading_service: ReadingService::new(),
+            paused: watch::Sender::new(false),
+            progress,
+        }
+    }
+
+    /// Receive a progress event for every book processed
+    pub fn subscribe_progress(&self) -> broadcast::Receiver<IndexingProgress> {
+        self.progress.subscribe()
+    }
+
+    /// Stop indexing after the current book
+    pub fn pause(&self) {
+        self.paused.send_replace(true);
+    }
+
+    /// Continue a paused run
+    pub fn resume(&self) {
+        self.paused.send_replace(false);
+    }
+
+    /// Record whether indexed books open, following the compatibility ledger preference
+    pub async fn set_compatibility_ledger_enabled(&self, enabled: bool) -> Result<()> {
+        self.reading_service.set_compatibility_ledger_enabled(enabled).await
+    }
+
+    /// Check whether indexing is paused
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Index the library on a background task
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<Result<IndexingSummary>> {
+        tokio::spawn(async move { self.index_library().await })
+    }
+
+    /// Index every library book that is new or has changed
+    pub async fn index_library(&self) -> Result<IndexingSummary> {
+        let books = self.database.get_all_books().await?;
+        self.index_books(books).await
+    }
+
+    /// Index the given books that are new or have changed
+    pub async fn index_books(&self, books: Vec<Book>) -> Result<IndexingSummary> {
+        let total = books.len();
+        let mut summary = IndexingSummary::default();
+        let mut paused = self.paused.subscribe();
+
+        for (index, book) in books.into_iter().enumerate() {
+            paused.wait_for(|paused| !*paused).await?;
+
+            let outcome = match self.index_book(&book).await {
+                Ok(IndexingOutcome::Indexed) => {
+                    summary.indexed += 1;
+                    IndexingOutcome::Indexed
+                }
+                Ok(_) => {
+                    summary.unchanged += 1;
+                    IndexingOutcome::Unchanged
+                }
+                Err(e) => {
+                    warn!("Failed to index {} for search: {}", book.id, e);
+                    summary.failed += 1;
+                    IndexingOutcome::Failed(e.to_string())
+                }
+            };
+
+            let _ = self.progress.send(IndexingProgress {
+                book_id: book.id,
+                completed: index + 1,
+                total,
+                outcome,
+            });
+        }
+
+        Ok(summary)
+    }
+
+    /// Index one book unless it is unchanged since it was last indexed
+    async fn index_book(&self, book: &Book) -> Result<IndexingOutcome> {
+        let (file_size, file_modified) = match tokio::fs::metadata(&book.file_path).await {
+            Ok(metadata) => (metadata.len(), metadata.modified().ok().map(DateTime::<Utc>::from)),
+            Err(_) => (book.file_size, None),
+        };
+
+        if let Some(status) = self.database.get_search_index_status(&book.id).await? {
+            let same_file = status.file_size == file_size && status.file_modified == file_modified;
+            let done = match status.state {
+                SearchIndexState::Indexed => self.search.has_book_content(&book.id).await,
+                SearchIndexState::Failed => true,
+                _ => false,
+            };
+            if same_file && done {
+                return Ok(IndexingOutcome::Unchanged);
+            }
+        }
+
+        let mut status = SearchIndexStatus {
+            book_id: book.id.clone(),
+            state: SearchIndexState::Indexing,
+            file_size,
+            file_modified,
+            updated_at: Utc::now(),
+            error: None,
+        };
+        self.database.set_search_index_status(&status).await?;
+
+        let result = self.store_text(book).await;
+        status.updated_at = Utc::now();
+        match &result {
+            Ok(()) => status.state = SearchIndexState::Indexed,
+            Err(e) => {
+                status.state = SearchIndexState::Failed;
+                status.error = Some(e.to_string());
+            }
+        }
+        self.database.set_search_index_status(&status).await?;
+
+        result.map(|()| IndexingOutcome::Indexed)
+    }
+
+    async fn store_text(&self, book: &Book) -> Result<()> {
+        let content = self.reading_service.load_book_content(book).await;
+        // Don't let the reading service's cache hold every book of the run
+        self.reading_service.clear_caches().await;
+        self.search.store_book_content(book, &content?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+    use crate::models::BookFormat;
+    use crate::services::book_search_service::SearchOptions;
+
+    fn write_epub(path: &std::path::Path, text: &str) {
+        let mut writer = ZipWriter::new(std::fs::File::create(path).unwrap());
+        let options = SimpleFileOptions::default();
+        let files = [
+            ("mimetype", "application/epub+zip".to_string()),
+            (
+                "META-INF/container.xml",
+                r#"<container><rootfiles><rootfile full-path="content.opf"/></rootfiles></container>"#.to_string(),
+            ),
+            (
+                "content.opf",
+                r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0"><metadata/><manifest><item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/></manifest><spine><itemref idref="c1"/></spine></package>"#.to_string(),
+            ),
+            ("c1.xhtml", format!("<html><body><p>{}</p></body></html>", text)),
+        ];
+        for (name, data) in files {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(data.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_library_is_indexed_incrementally() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let database = Arc::new(DatabaseService::new_in_memory().await.unwrap());
+        let mut books = Vec::new();
+        for (name, text) in [("sea", "The lighthouse keeper watched the sea."), ("missing", "")] {
+            let path = temp_dir.path().join(format!("{}.epub", name));
+            if !text.is_empty() {
+                write_epub(&path, text);
+            }
+            let book = Book::new(name.to_string(), "Author".to_string(), path, 0, BookFormat::Epub);
+            database.insert_book(&book).await.unwrap();
+            books.push(book);
+        }
+
+        let search = Arc::new(BookSearchService::with_content_dir(temp_dir.path().join("index")));
+        let indexer = Arc::new(SearchIndexer::new(database.clone(), search));
+        let mut progress = indexer.subscribe_progress();
+
+        indexer.pause();
+        let run = indexer.clone().spawn();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(progress.try_recv().is_err());
+        indexer.resume();
+
+        let summary = run.await.unwrap().unwrap();
+        assert_eq!(summary, IndexingSummary { indexed: 1, unchanged: 0, failed: 1 });
+        let event = progress.recv().await.unwrap();
+        assert_eq!((event.completed, event.total), (1, 2));
+
+        let status = database.get_search_index_status(&books[0].id).await.unwrap().unwrap();
+        assert_eq!(status.state, SearchIndexState::Indexed);
+        let status = database.get_search_index_status(&books[1].id).await.unwrap().unwrap();
+        assert_eq!(status.state, SearchIndexState::Failed);
+        assert!(status.error.is_some());
+
+        // Stored text is searchable from a fresh session
+        let search = Arc::new(BookSearchService::with_content_dir(temp_dir.path().join("index")));
+        let results = search.search_in_book(&books[0].id, "lighthouse", &SearchOptions::default()).await.unwrap();
+        assert_eq!(results.len(), 1);
+
+        // A second run only revisits changed books
+        let indexer = SearchIndexer::new(database.clone(), search);
+        assert_eq!(indexer.index_library().await.unwrap(), IndexingSummary { indexed: 0, unchanged: 2, failed: 0 });
+        write_epub(&books[1].file_path, "A second book.");
+        assert_eq!(indexer.index_library().await.unwrap(), IndexingSummary { indexed: 1, unchanged: 1, failed: 0 });
+    }
+}
@@ -0,0 +1,118 @@
+use anyhow::Result;
+use tracing::warn;
+
+/// Keyring service name used for cached EPUB passwords and API keys
+pub(crate) const KEYRING_SERVICE: &str = "ebook-reader";
+
+/// Store an API key for a provider in the OS keyring, replacing any existing key
+pub fn store_api_key(provider: &str, key: &str) -> Result<()> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(anyhow::anyhow!("API key for {} is empty", provider));
+    }
+
+    api_key_entry(provider)?.set_password(key)?;
+    Ok(())
+}
+
+/// Check whether an API key is stored for a provider
+pub fn has_api_key(provider: &str) -> bool {
+    get_api_key(provider).is_some()
+}
+
+/// Get the stored API key for a provider
+pub fn get_api_key(provider: &str) -> Option<String> {
+    match api_key_entry(provider).and_then(|entry| entry.get_password()) {
+        Ok(key) => Some(key),
+        Err(keyring::Error::NoEntry) => None,
+        Err(e) => {
+            warn!("Failed to read API key for {} from keyring: {}", provider, e);
+            None
+        }
+    }
+}
+
+/// Remove the stored API key for a provider
+pub fn remove_api_key(provider: &str) -> Result<()> {
+    match api_key_entry(provider)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Store an API key without blocking the async runtime
+pub async fn store_api_key_async(provider: &str, key: &str) -> Result<()> {
+    let (provider, key) = (provider.to_string(), key.to_string());
+    on_blocking_pool(move || store_api_key(&provider, &key)).await?
+}
+
+/// Check for an API key without blocking the async runtime
+pub async fn has_api_key_async(provider: &str) -> bool {
+    get_api_key_async(provider).await.is_some()
+}
+
+/// Get an API key without blocking the async runtime
+pub async fn get_api_key_async(provider: &str) -> Option<String> {
+    let provider = provider.to_string();
+    match on_blocking_pool(move || get_api_key(&provider)).await {
+        Ok(key) => key,
+        Err(e) => {
+            warn!("Keyring lookup did not finish: {}", e);
+            None
+        }
+    }
+}
+
+/// Remove an API key without blocking the async runtime
+pub async fn remove_api_key_async(provider: &str) -> Result<()> {
+    let provider = provider.to_string();
+    on_blocking_pool(move || remove_api_key(&provider)).await?
+}
+
+/// Per-book EPUB password cache stored in the OS keyring
+pub struct EpubPasswordStore;
+
+impl EpubPasswordStore {
+    /// Get the cached password for a book
+    pub fn get(book_id: &str) -> Option<String> {
+        match Self::entry(book_id).and_then(|entry| entry.get_password()) {
+            Ok(password) => Some(password),
+            Err(keyring::Error::NoEntry) => None,
+            Err(e) => {
+                warn!("Failed to read EPUB password from keyring: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Cache a password for a book
+    pub fn store(book_id: &str, password: &str) -> Result<()> {
+        Self::entry(book_id)?.set_password(password)?;
+        Ok(())
+    }
+
+    /// Forget the cached password for a book
+    pub fn remove(book_id: &str) -> Result<()> {
+        match Self::entry(book_id)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn entry(book_id: &str) -> keyring::Result<keyring::Entry> {
+        keyring::Entry::new(KEYRING_SERVICE, &format!("epub-password:{}", book_id))
+    }
+}
+
+// The Secret Service backend runs its own D-Bus runtime and panics on a tokio worker thread
+async fn on_blocking_pool<T, F>(call: F) -> Result<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    Ok(tokio::task::spawn_blocking(call).await?)
+}
+
+fn api_key_entry(provider: &str) -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, &format!("api-key:{}", provider.to_lowercase()))
+}
@@ -0,0 +1,460 @@
+use std::path::Path;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use tokio::process::Command;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::models::book::{Book, BookFormat};
+use crate::services::export_share::sanitize_file_name;
+use crate::services::secrets;
+
+/// Provider name the SMTP password is stored under in the keyring
+pub const SMTP_PROVIDER: &str = "smtp";
+/// Calibre's command line converter, used when a device can't take a book's format
+const EBOOK_CONVERT: &str = "ebook-convert";
+/// Placeholders a file name template may use
+const TEMPLATE_FIELDS: &[&str] = &["{title}", "{author}", "{series}", "{series_index}"];
+
+/// How the SMTP connection is secured
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    StartTls, // Usually port 587
+    Tls,      // Usually port 465
+    None,     // Only for servers on the local network
+}
+
+impl SmtpSecurity {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            SmtpSecurity::StartTls => "STARTTLS",
+            SmtpSecurity::Tls => "SSL/TLS",
+            SmtpSecurity::None => "None",
+        }
+    }
+
+    pub fn default_port(&self) -> u16 {
+        match self {
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::None => 25,
+        }
+    }
+}
+
+impl Default for SmtpSecurity {
+    fn default() -> Self {
+        SmtpSecurity::StartTls
+    }
+}
+
+/// The outgoing mail server books are sent through; the password is kept in the keyring
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub from_address: String, // Must be on the device's approved senders list
+}
+
+/// Kind of device a profile sends to, which decides the formats it takes
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceKind {
+    Kindle,
+    PocketBook,
+    Other,
+}
+
+impl DeviceKind {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            DeviceKind::Kindle => "Kindle",
+            DeviceKind::PocketBook => "PocketBook",
+            DeviceKind::Other => "Other",
+        }
+    }
+
+    /// Formats the device's mail service accepts
+    pub fn accepts(&self, format: &BookFormat) -> bool {
+        match self {
+            // Amazon stopped taking MOBI and AZW3 by email in 2022
+            DeviceKind::Kindle => matches!(format, BookFormat::Epub | BookFormat::Pdf | BookFormat::Txt | BookFormat::Html),
+            DeviceKind::PocketBook | DeviceKind::Other => true,
+        }
+    }
+
+    /// Largest message the device's mail service takes, in MB
+    pub fn default_max_size_mb(&self) -> u32 {
+        match self {
+            DeviceKind::Kindle => 50,
+            DeviceKind::PocketBook | DeviceKind::Other => 25,
+        }
+    }
+}
+
+impl Default for DeviceKind {
+    fn default() -> Self {
+        DeviceKind::Kindle
+    }
+}
+
+/// A device that receives books by email, such as `name@kindle.com`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeviceProfile {
+    pub id: String,
+    pub name: String,
+    pub kind: DeviceKind,
+    pub address: String,
+    pub max_size_mb: u32,                   // Checked against the message, attachment encoding included
+    pub convert_to: Option<BookFormat>,     // Convert every book sent, not only those the device can't take
+    pub file_name_template: Option<String>, // e.g. "{author} - {title}"; the extension is added
+    pub created_at: DateTime<Utc>,
+}
+
+impl DeviceProfile {
+    pub fn new(name: &str, kind: DeviceKind, address: &str) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: name.trim().to_string(),
+            kind,
+            address: address.trim().to_string(),
+            max_size_mb: kind.default_max_size_mb(),
+            convert_to: None,
+            file_name_template: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    /// Format a book goes out in, or `None` to send it as it is
+    pub fn target_format(&self, format: &BookFormat) -> Option<BookFormat> {
+        match &self.convert_to {
+            Some(target) if target != format => Some(target.clone()),
+            Some(_) => None,
+            None if self.kind.accepts(format) => None,
+            None => Some(BookFormat::Epub),
+        }
+    }
+}
+
+/// A book file ready to attach
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreparedAttachment {
+    pub file_name: String,
+    pub content_type: &'static str,
+    pub bytes: Vec<u8>,
+    pub converted: bool,
+}
+
+/// What sending a book did
+#[derive(Debug, Clone, PartialEq)]
+pub struct SendReport {
+    pub device: String,
+    pub file_name: String,
+    pub size: u64, // Bytes of the message, attachment encoding included
+    pub converted: bool,
+}
+
+/// Emails books to e-readers through the reader's own SMTP server
+///
+/// Each device has a profile with its address, a size limit and how books
+/// are converted and named for it. Conversion uses Calibre's `ebook-convert`.
+#[derive(Clone)]
+pub struct SendToDeviceService {
+    pool: SqlitePool,
+}
+
+impl SendToDeviceService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize send to device tables
+    pub async fn init_tables(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS send_to_device_profiles (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                profile TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE TABLE IF NOT EXISTS send_to_device_smtp (id INTEGER PRIMARY KEY CHECK (id = 1), settings TEXT NOT NULL);")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Save the mail server, and its password when one is given
+    pub async fn save_smtp_settings(&self, settings: &SmtpSettings, password: Option<&str>) -> Result<()> {
+        if settings.host.trim().is_empty() {
+            return Err(anyhow!("The mail server needs a host name"));
+        }
+        settings.from_address.parse::<Mailbox>()
+            .map_err(|_| anyhow!("Not an email address: {}", settings.from_address))?;
+
+        sqlx::query("INSERT OR REPLACE INTO send_to_device_smtp (id, settings) VALUES (1, ?)")
+            .bind(serde_json::to_string(settings)?)
+            .execute(&self.pool)
+            .await?;
+        if let Some(password) = password {
+            secrets::store_api_key_async(SMTP_PROVIDER, password).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_smtp_settings(&self) -> Result<Option<SmtpSettings>> {
+        let row = sqlx::query("SELECT settings FROM send_to_device_smtp WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|row| Ok(serde_json::from_str(&row.get::<String, _>("settings"))?))
+            .transpose()
+    }
+
+    /// Forget the mail server and its password
+    pub async fn clear_smtp_settings(&self) -> Result<()> {
+        sqlx::query("DELETE FROM send_to_device_smtp")
+            .execute(&self.pool)
+            .await?;
+        secrets::remove_api_key_async(SMTP_PROVIDER).await
+    }
+
+    /// Add a device profile, or replace the one with its id
+    pub async fn save_device(&self, device: &DeviceProfile) -> Result<()> {
+        if device.name.is_empty() {
+            return Err(anyhow!("A device needs a name"));
+        }
+        device.address.parse::<Mailbox>()
+            .map_err(|_| anyhow!("Not an email address: {}", device.address))?;
+        if device.max_size_mb == 0 {
+            return Err(anyhow!("The size limit must be at least 1 MB"));
+        }
+
+        sqlx::query("INSERT OR REPLACE INTO send_to_device_profiles (id, name, profile, created_at) VALUES (?, ?, ?, ?)")
+            .bind(&device.id)
+            .bind(&device.name)
+            .bind(serde_json::to_string(device)?)
+            .bind(device.created_at.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn remove_device(&self, device_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM send_to_device_profiles WHERE id = ?")
+            .bind(device_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_devices(&self) -> Result<Vec<DeviceProfile>> {
+        let rows = sqlx::query("SELECT profile FROM send_to_device_profiles ORDER BY name COLLATE NOCASE")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter()
+            .map(|row| Ok(serde_json::from_str(&row.get::<String, _>("profile"))?))
+            .collect()
+    }
+
+    pub async fn get_device(&self, device_id: &str) -> Result<DeviceProfile> {
+        let row = sqlx::query("SELECT profile FROM send_to_device_profiles WHERE id = ?")
+            .bind(device_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| anyhow!("No device with id {}", device_id))?;
+        Ok(serde_json::from_str(&row.get::<String, _>("profile"))?)
+    }
+
+    /// Email a book to a device, converting and renaming it as its profile says
+    pub async fn send_book(&self, device_id: &str, book: &Book) -> Result<SendReport> {
+        let device = self.get_device(device_id).await?;
+        let settings = self.get_smtp_settings().await?
+            .ok_or_else(|| anyhow!("Set up a mail server before sending books"))?;
+
+        let attachment = prepare_attachment(&device, book).await?;
+        let size = encoded_size(attachment.bytes.len() as u64);
+        check_size(&device, size)?;
+
+        let message = Message::builder()
+            .from(settings.from_address.parse()?)
+            .to(device.address.parse()?)
+            .subject(book.title.clone())
+            .multipart(
+                MultiPart::mixed()
+                    .singlepart(SinglePart::plain(format!("{} by {}", book.title, book.author)))
+                    .singlepart(Attachment::new(attachment.file_name.clone())
+                        .body(attachment.bytes, ContentType::parse(attachment.content_type)?)),
+            )?;
+
+        let password = match settings.username {
+            Some(_) => Some(secrets::get_api_key_async(SMTP_PROVIDER).await
+                .ok_or_else(|| anyhow!("The mail server password is missing; enter it again"))?),
+            None => None,
+        };
+        let transport = smtp_transport(&settings, password)?;
+        transport.send(message).await
+            .map_err(|e| anyhow!("The mail server did not send the book: {}", e))?;
+
+        info!("Sent {} to {} as {}", book.title, device.name, attachment.file_name);
+        Ok(SendReport { device: device.name, file_name: attachment.file_name, size, converted: attachment.converted })
+    }
+}
+
+fn smtp_transport(settings: &SmtpSettings, password: Option<String>) -> Result<AsyncSmtpTransport<Tokio1Executor>> {
+    let host = settings.host.trim();
+    let builder = match settings.security {
+        SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+        SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+        SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+    };
+    let port = if settings.port == 0 { settings.security.default_port() } else { settings.port };
+    let builder = match (&settings.username, password) {
+        (Some(username), Some(password)) => builder.port(port).credentials(Credentials::new(username.clone(), password)),
+        _ => builder.port(port),
+    };
+    Ok(builder.build())
+}
+
+/// Read a book's file, converted and renamed for a device
+pub async fn prepare_attachment(device: &DeviceProfile, book: &Book) -> Result<PreparedAttachment> {
+    let (path, format, _converted_dir) = match device.target_format(&book.file_format) {
+        Some(target) => {
+            let dir = tempfile::tempdir()?;
+            let output = dir.path().join(format!("book.{}", target.to_extension()));
+            convert_book(&book.file_path, &output).await?;
+            (output, target, Some(dir))
+        }
+        None => (book.file_path.clone(), book.file_format.clone(), None),
+    };
+
+    let bytes = tokio::fs::read(&path).await
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    Ok(PreparedAttachment {
+        file_name: attachment_name(device, book, &format),
+        content_type: content_type(&format),
+        bytes,
+        converted: format != book.file_format,
+    })
+}
+
+async fn convert_book(input: &Path, output: &Path) -> Result<()> {
+    let result = Command::new(EBOOK_CONVERT)
+        .arg(input)
+        .arg(output)
+        .output()
+        .await
+        .map_err(|_| anyhow!("Converting books needs Calibre's {} on the PATH", EBOOK_CONVERT))?;
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(anyhow!("Could not convert {}: {}", input.display(), stderr.lines().last().unwrap_or_default()));
+    }
+    Ok(())
+}
+
+/// File name a device shows the book under
+fn attachment_name(device: &DeviceProfile, book: &Book, format: &BookFormat) -> String {
+    let stem = match &device.file_name_template {
+        Some(template) => {
+            let series_index = book.series_index.map(|index| index.to_string()).unwrap_or_default();
+            let values = [book.title.as_str(), book.author.as_str(), book.series.as_deref().unwrap_or_default(), &series_index];
+            let mut name = template.clone();
+            for (field, value) in TEMPLATE_FIELDS.iter().zip(values) {
+                name = name.replace(field, value);
+            }
+            name
+        }
+        None => book.file_path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default(),
+    };
+    // Fields left empty, like a missing series, leave separators behind
+    let stem = stem.trim_matches(|c: char| c.is_whitespace() || c == '-' || c == '_');
+    let stem = if stem.is_empty() { book.title.as_str() } else { stem };
+    format!("{}.{}", sanitize_file_name(stem), format.to_extension())
+}
+
+fn content_type(format: &BookFormat) -> &'static str {
+    match format {
+        BookFormat::Epub => "application/epub+zip",
+        BookFormat::Pdf => "application/pdf",
+        BookFormat::Mobi => "application/x-mobipocket-ebook",
+        BookFormat::Azw3 => "application/vnd.amazon.ebook",
+        BookFormat::Txt => "text/plain",
+        BookFormat::Html => "text/html",
+    }
+}
+
+/// Size of an attachment once base64 encoded into 76 character lines
+fn encoded_size(bytes: u64) -> u64 {
+    let encoded = bytes.div_ceil(3) * 4;
+    encoded + encoded.div_ceil(76) * 2
+}
+
+fn check_size(device: &DeviceProfile, size: u64) -> Result<()> {
+    let limit = u64::from(device.max_size_mb) * 1024 * 1024;
+    if size > limit {
+        return Err(anyhow!(
+            "The book is {:.1} MB once attached, over the {} MB {} takes",
+            size as f64 / (1024.0 * 1024.0),
+            device.max_size_mb,
+            device.name,
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_device_profiles_naming_and_size_checks() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let service = SendToDeviceService::new(pool);
+        service.init_tables().await.unwrap();
+
+        let mut kindle = DeviceProfile::new("Paperwhite", DeviceKind::Kindle, "reader_42@kindle.com");
+        kindle.file_name_template = Some("{author} - {title} - {series}".to_string());
+        service.save_device(&kindle).await.unwrap();
+        assert!(service.save_device(&DeviceProfile::new("Inkpad", DeviceKind::PocketBook, "not an address")).await.is_err());
+        assert_eq!(service.get_devices().await.unwrap(), vec![kindle.clone()]);
+        assert!(service.get_smtp_settings().await.unwrap().is_none());
+
+        assert_eq!(kindle.target_format(&BookFormat::Epub), None);
+        assert_eq!(kindle.target_format(&BookFormat::Azw3), Some(BookFormat::Epub));
+        let pocketbook = DeviceProfile { convert_to: Some(BookFormat::Epub), ..DeviceProfile::new("Inkpad", DeviceKind::PocketBook, "me@pbsync.com") };
+        assert_eq!(pocketbook.target_format(&BookFormat::Mobi), Some(BookFormat::Epub));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dune-1965.epub");
+        std::fs::write(&path, b"PK epub").unwrap();
+        let mut book = Book::new("Dune".to_string(), "Frank Herbert".to_string(), path, 7, BookFormat::Epub);
+        let attachment = prepare_attachment(&kindle, &book).await.unwrap();
+        assert_eq!(attachment.file_name, "Frank Herbert - Dune.epub");
+        assert_eq!((attachment.content_type, attachment.bytes.as_slice(), attachment.converted), ("application/epub+zip", &b"PK epub"[..], false));
+        book.title = "Dune: Messiah".to_string();
+        assert_eq!(prepare_attachment(&pocketbook, &book).await.unwrap().file_name, "dune-1965.epub");
+        kindle.file_name_template = Some("{series} {title}".to_string());
+        assert_eq!(prepare_attachment(&kindle, &book).await.unwrap().file_name, "Dune_ Messiah.epub");
+
+        assert_eq!(encoded_size(57), 78);
+        assert!(check_size(&kindle, 50 * 1024 * 1024).is_ok());
+        assert_eq!(
+            check_size(&kindle, encoded_size(40 * 1024 * 1024)).unwrap_err().to_string(),
+            "The book is 54.7 MB once attached, over the 50 MB Paperwhite takes",
+        );
+    }
+}
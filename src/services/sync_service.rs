@@ -10,12 +10,32 @@ use serde_json;
 use crate::models::sync::{
     SyncData, BookProgress, ReadingSession, SyncConflict, SyncConflictType,
     ConflictVersion, ConflictResolution, SyncHistoryEntry, SyncType, SyncStatus,
-    SyncStatistics, CloudSyncConfig, UserPreferences,
+    SyncStatistics, CloudSyncConfig, CloudCredentials, UserPreferences, DevicePosition, PositionConflict,
 };
 use crate::models::annotation::{Annotation, Bookmark};
 use crate::models::library::{Collection, ReadingStatus};
 use crate::services::annotation_service::AnnotationService;
 use crate::services::library_service::LibraryService;
+#[cfg(feature = "network")]
+use crate::models::sync_protocol::{
+    BookFileRef, ChangeKind, Changeset, PullResponse, PushResponse, RegisterDeviceRequest,
+    RegisterDeviceResponse, SyncChange, SYNC_PROTOCOL_VERSION,
+};
+use crate::services::secrets;
+#[cfg(feature = "network")]
+use reqwest::{Client, RequestBuilder, StatusCode, Url};
+#[cfg(feature = "network")]
+use sha2::{Digest, Sha256};
+
+/// Provider names the cloud sync keys are stored under in the keyring
+const CLOUD_API_KEY_PROVIDER: &str = "cloud-sync-api-key";
+const CLOUD_SECRET_KEY_PROVIDER: &str = "cloud-sync-secret-key";
+/// Provider name the sync server device token is stored under in the keyring
+#[cfg(feature = "network")]
+pub const SYNC_SERVER_PROVIDER: &str = "sync-server";
+/// Changes asked for per pull request
+#[cfg(feature = "network")]
+const PULL_PAGE_SIZE: usize = 500;
 
 /// Synchronization service for managing reading progress and data sync
 pub struct SyncService {
@@ -56,12 +76,56 @@ impl SyncService {
             self.sync_from_file(&sync_file_path).await?;
         }
 
+        self.load_cloud_config().await?;
+
         // Initialize with current data
         self.collect_local_data().await?;
 
         Ok(())
     }
 
+    /// Use a cloud sync account; its keys go to the keyring, the rest to the settings file
+    pub async fn set_cloud_config(&self, config: CloudSyncConfig) -> Result<()> {
+        store_cloud_keys(&config.credentials).await?;
+        let path = self.get_cloud_config_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(&config)?).await?;
+
+        let mut current = self.cloud_config.write().await;
+        *current = Some(config);
+        Ok(())
+    }
+
+    /// Get the cloud sync account, keys included
+    pub async fn get_cloud_config(&self) -> Option<CloudSyncConfig> {
+        self.cloud_config.read().await.clone()
+    }
+
+    /// Load the cloud sync account, moving keys an older version saved in the file to the keyring
+    async fn load_cloud_config(&self) -> Result<()> {
+        let path = self.get_cloud_config_path();
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let mut config: CloudSyncConfig = serde_json::from_str(&fs::read_to_string(&path).await?)?;
+        let credentials = &mut config.credentials;
+        if credentials.api_key.is_some() || credentials.secret_key.is_some() {
+            // The file is only rewritten, without the keys, once the keyring has them
+            store_cloud_keys(credentials).await?;
+            fs::write(&path, serde_json::to_string_pretty(&config)?).await?;
+        } else {
+            credentials.api_key = secrets::get_api_key_async(CLOUD_API_KEY_PROVIDER).await;
+            credentials.secret_key = secrets::get_api_key_async(CLOUD_SECRET_KEY_PROVIDER).await;
+        }
+
+        let mut current = self.cloud_config.write().await;
+        *current = Some(config);
+        Ok(())
+    }
+
     /// Sync data to file
     pub async fn sync_to_file(&self, path: &Path) -> Result<()> {
         let data = self.local_data.read().await;
@@ -146,18 +210,23 @@ impl SyncService {
         remote: &BookProgress,
     ) -> Result<BookProgress, SyncConflict> {
         // Use the most recent progress
-        if local.last_read_at > remote.last_read_at {
-            Ok(local.clone())
+        let mut resolved = if local.last_read_at > remote.last_read_at {
+            local.clone()
         } else if remote.last_read_at > local.last_read_at {
-            Ok(remote.clone())
+            remote.clone()
         } else {
             // If timestamps are equal, use the furthest progress
             if local.current_page >= remote.current_page {
-                Ok(local.clone())
+                local.clone()
             } else {
-                Ok(remote.clone())
+                remote.clone()
             }
-        }
+        };
+
+        // Either side may know of devices the other has not seen
+        resolved.merge_positions(local);
+        resolved.merge_positions(remote);
+        Ok(resolved)
     }
 
     /// Merge annotations
@@ -312,6 +381,81 @@ impl SyncService {
         data.books_progress.get(book_id).cloned()
     }
 
+    /// Record where this device is in a book
+    pub async fn record_position(
+        &self,
+        book_id: &str,
+        chapter_id: Option<String>,
+        position_in_chapter: Option<usize>,
+        progress: f32,
+    ) -> Result<()> {
+        let position = DevicePosition {
+            device_id: self.device_id.clone(),
+            device_name: self.device_name.clone(),
+            chapter_id,
+            position_in_chapter,
+            progress: progress.clamp(0.0, 1.0),
+            updated_at: Utc::now(),
+        };
+
+        {
+            let mut data = self.local_data.write().await;
+            data.books_progress
+                .entry(book_id.to_string())
+                .or_insert_with(|| BookProgress::new(book_id.to_string()))
+                .record_position(position);
+            data.last_sync = Utc::now();
+        }
+
+        if self.should_auto_save().await {
+            self.save_local_data().await?;
+        }
+        Ok(())
+    }
+
+    /// Where each device last was in a book, most recent first
+    pub async fn get_device_positions(&self, book_id: &str) -> Vec<DevicePosition> {
+        let data = self.local_data.read().await;
+        let mut positions: Vec<DevicePosition> = data.books_progress.get(book_id)
+            .map(|progress| progress.device_positions.values().cloned().collect())
+            .unwrap_or_default();
+        positions.sort_by_key(|position| std::cmp::Reverse(position.updated_at));
+        positions
+    }
+
+    /// Furthest any device has got in a book
+    pub async fn get_furthest_read(&self, book_id: &str) -> Option<DevicePosition> {
+        let data = self.local_data.read().await;
+        data.books_progress.get(book_id)?.furthest_read.clone()
+    }
+
+    /// Another device that got further in a book since this device last read it
+    pub async fn get_position_conflict(&self, book_id: &str) -> Option<PositionConflict> {
+        let data = self.local_data.read().await;
+        data.books_progress.get(book_id)?.position_conflict(&self.device_id)
+    }
+
+    /// Settle a position conflict by jumping to the other device's position or staying put
+    ///
+    /// Returns the position to open the book at. Either way the conflict is not
+    /// raised again until the other device moves on.
+    pub async fn resolve_position_conflict(&self, conflict: &PositionConflict, jump: bool) -> Result<Option<DevicePosition>> {
+        let target = if jump { Some(conflict.remote.clone()) } else { conflict.local.clone() };
+        match &target {
+            Some(position) => {
+                self.record_position(
+                    &conflict.book_id,
+                    position.chapter_id.clone(),
+                    position.position_in_chapter,
+                    position.progress,
+                ).await?;
+            }
+            // Staying at the start of a book this device never opened
+            None => self.record_position(&conflict.book_id, None, None, 0.0).await?,
+        }
+        Ok(target)
+    }
+
     /// Start reading session
     pub async fn start_reading_session(&self, book_id: String) -> Result<String> {
         let session = ReadingSession::new(book_id, self.device_id.clone());
@@ -475,6 +619,11 @@ impl SyncService {
         path
     }
 
+    /// Get cloud sync account file path
+    fn get_cloud_config_path(&self) -> std::path::PathBuf {
+        self.get_sync_file_path().with_file_name("cloud_sync.json")
+    }
+
     /// Check if auto-save is enabled
     async fn should_auto_save(&self) -> bool {
         let data = self.local_data.read().await;
@@ -527,9 +676,18 @@ impl SyncService {
     }
 
     /// Export sync data
+    ///
+    /// Lists are sorted by id and maps are written with sorted keys, so exporting
+    /// unchanged data produces an identical file.
     pub async fn export_sync_data(&self, path: &Path) -> Result<()> {
-        let data = self.local_data.read().await;
-        let json_data = serde_json::to_string_pretty(&*data)?;
+        let mut data = self.local_data.read().await.clone();
+        data.annotations.sort_by(|a, b| a.id.cmp(&b.id));
+        data.bookmarks.sort_by(|a, b| a.id.cmp(&b.id));
+        data.collections.sort_by(|a, b| a.id.cmp(&b.id));
+        data.reading_sessions.sort_by(|a, b| a.start_time.cmp(&b.start_time).then_with(|| a.id.cmp(&b.id)));
+
+        // serde_json::Value keeps object keys sorted, unlike the HashMaps in SyncData
+        let json_data = serde_json::to_string_pretty(&serde_json::to_value(&data)?)?;
         fs::write(path, json_data).await?;
         Ok(())
     }
@@ -581,19 +739,234 @@ impl SyncService {
     }
 }
 
-impl Default for SyncStatistics {
-    fn default() -> Self {
-        Self {
-            total_syncs: 0,
-            successful_syncs: 0,
-            failed_syncs: 0,
-            last_successful_sync: None,
-            last_failed_sync: None,
-            average_sync_duration_ms: 0,
-            total_conflicts: 0,
-            resolved_conflicts: 0,
-            data_transferred_bytes: 0,
-            sync_efficiency: 0.0,
+/// Outcome of a sync with the sync server
+#[cfg(feature = "network")]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServerSyncSummary {
+    pub pushed: usize,
+    pub pulled: usize,                // Changes from other devices that changed local data
+    pub book_files: Vec<BookFileRef>, // Books other devices uploaded, to download if missing
+}
+
+#[cfg(feature = "network")]
+impl SyncService {
+    /// Push local changes to a sync server and pull those of other devices
+    pub async fn sync_with_server(&self, server: &SyncServerClient) -> Result<ServerSyncSummary> {
+        let start_time = std::time::Instant::now();
+        let result = self.exchange_with_server(server).await;
+        let status = if result.is_ok() { SyncStatus::Success } else { SyncStatus::Failed };
+        self.update_sync_statistics(SyncType::Manual, status, start_time.elapsed().as_millis() as u64).await;
+        result
+    }
+
+    async fn exchange_with_server(&self, server: &SyncServerClient) -> Result<ServerSyncSummary> {
+        let mut summary = ServerSyncSummary::default();
+
+        let (changes, pushed_at) = {
+            let data = self.local_data.read().await;
+            (data.changes_since(data.sync_metadata.last_pushed_at)?, Utc::now())
+        };
+        if !changes.is_empty() {
+            summary.pushed = changes.len();
+            server.push(&Changeset { device_id: self.device_id.clone(), changes }).await?;
         }
+        self.local_data.write().await.sync_metadata.last_pushed_at = Some(pushed_at);
+
+        loop {
+            let since = self.local_data.read().await.sync_metadata.server_cursor;
+            let page = server.pull(since, PULL_PAGE_SIZE).await?;
+            {
+                let mut data = self.local_data.write().await;
+                // Our own changes come back too; they are already applied
+                for change in page.changes.iter().filter(|change| change.device_id != self.device_id) {
+                    if change.kind == ChangeKind::BookFile {
+                        summary.book_files.push(BookFileRef::from_change(change)?);
+                    } else if data.apply_change(change)? {
+                        summary.pulled += 1;
+                    }
+                }
+                data.sync_metadata.server_cursor = page.cursor;
+            }
+            if !page.has_more {
+                break;
+            }
+        }
+
+        self.save_local_data().await?;
+        Ok(summary)
+    }
+
+    /// Upload a book file, if the server lacks it, and announce it to other devices
+    pub async fn share_book_file(&self, server: &SyncServerClient, book_id: &str, path: &Path) -> Result<BookFileRef> {
+        let file = server.upload_file(book_id, path).await?;
+        let change = SyncChange::book_file(&self.device_id, &file)?;
+        server.push(&Changeset { device_id: self.device_id.clone(), changes: vec![change] }).await?;
+        Ok(file)
+    }
+}
+
+async fn store_cloud_keys(credentials: &CloudCredentials) -> Result<()> {
+    if let Some(api_key) = &credentials.api_key {
+        secrets::store_api_key_async(CLOUD_API_KEY_PROVIDER, api_key).await?;
+    }
+    if let Some(secret_key) = &credentials.secret_key {
+        secrets::store_api_key_async(CLOUD_SECRET_KEY_PROVIDER, secret_key).await?;
+    }
+    Ok(())
+}
+
+/// Client for a self-hosted sync server
+///
+/// The protocol is described in `epubreader_core::models::sync_protocol`.
+#[cfg(feature = "network")]
+#[derive(Clone)]
+pub struct SyncServerClient {
+    client: Client,
+    base_url: Url,
+    device_token: Option<String>,
+}
+
+#[cfg(feature = "network")]
+impl SyncServerClient {
+    /// Client for a server, with the device token stored by an earlier registration
+    pub async fn new(server_url: &str) -> Result<Self> {
+        Self::with_token(server_url, secrets::get_api_key_async(SYNC_SERVER_PROVIDER).await)
+    }
+
+    fn with_token(server_url: &str, device_token: Option<String>) -> Result<Self> {
+        let mut base_url = Url::parse(server_url.trim())
+            .map_err(|e| anyhow::anyhow!("Invalid sync server address {}: {}", server_url, e))?;
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+        let client = Client::builder()
+            .timeout(std::time::Duration::from_secs(60))
+            .user_agent(concat!("ebook-reader/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+
+        Ok(Self { client, base_url, device_token })
+    }
+
+    /// Check whether this device has registered with a server
+    pub fn is_registered(&self) -> bool {
+        self.device_token.is_some()
+    }
+
+    /// Register this device and store the token the server hands out
+    pub async fn register_device(&mut self, device_id: &str, device_name: &str) -> Result<RegisterDeviceResponse> {
+        let request = RegisterDeviceRequest {
+            device_id: device_id.to_string(),
+            device_name: device_name.to_string(),
+            protocol_version: SYNC_PROTOCOL_VERSION,
+        };
+        let response = self.client.post(self.endpoint("v1/devices")?).json(&request).send().await?;
+        let registered: RegisterDeviceResponse = check_status(response).await?.json().await?;
+        secrets::store_api_key_async(SYNC_SERVER_PROVIDER, &registered.device_token).await?;
+        self.device_token = Some(registered.device_token.clone());
+        Ok(registered)
+    }
+
+    /// Forget the device token
+    pub async fn unregister(&mut self) -> Result<()> {
+        self.device_token = None;
+        secrets::remove_api_key_async(SYNC_SERVER_PROVIDER).await
+    }
+
+    pub async fn push(&self, changeset: &Changeset) -> Result<PushResponse> {
+        let request = self.authorized(self.client.post(self.endpoint("v1/changes")?))?;
+        Ok(check_status(request.json(changeset).send().await?).await?.json().await?)
     }
-}
\ No newline at end of file
+
+    /// Changes after a cursor, oldest first
+    pub async fn pull(&self, since: u64, limit: usize) -> Result<PullResponse> {
+        let mut url = self.endpoint("v1/changes")?;
+        url.query_pairs_mut()
+            .append_pair("since", &since.to_string())
+            .append_pair("limit", &limit.to_string());
+        let request = self.authorized(self.client.get(url))?;
+        Ok(check_status(request.send().await?).await?.json().await?)
+    }
+
+    /// Check whether the server already stores a file
+    pub async fn has_file(&self, sha256: &str) -> Result<bool> {
+        let response = self.authorized(self.client.head(self.endpoint(&format!("v1/files/{}", sha256))?))?
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        check_status(response).await?;
+        Ok(true)
+    }
+
+    /// Upload a book file under the hash of its contents, unless the server has it already
+    pub async fn upload_file(&self, book_id: &str, path: &Path) -> Result<BookFileRef> {
+        let bytes = fs::read(path).await?;
+        let file = BookFileRef {
+            book_id: book_id.to_string(),
+            sha256: sha256_hex(&bytes),
+            file_name: path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+            size: bytes.len() as u64,
+        };
+        if !self.has_file(&file.sha256).await? {
+            let request = self.authorized(self.client.put(self.endpoint(&format!("v1/files/{}", file.sha256))?))?;
+            check_status(request.body(bytes).send().await?).await?;
+        }
+        Ok(file)
+    }
+
+    /// Download a book file, refusing contents that don't match its hash
+    pub async fn download_file(&self, file: &BookFileRef, destination: &Path) -> Result<()> {
+        let request = self.authorized(self.client.get(self.endpoint(&format!("v1/files/{}", file.sha256))?))?;
+        let bytes = check_status(request.send().await?).await?.bytes().await?;
+        if sha256_hex(&bytes) != file.sha256.to_lowercase() {
+            return Err(anyhow::anyhow!("Downloaded {} does not match its hash", file.file_name));
+        }
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(destination, &bytes).await?;
+        Ok(())
+    }
+
+    fn endpoint(&self, path: &str) -> Result<Url> {
+        Ok(self.base_url.join(path)?)
+    }
+
+    fn authorized(&self, request: RequestBuilder) -> Result<RequestBuilder> {
+        let token = self.device_token.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("This device is not registered with the sync server"))?;
+        Ok(request.bearer_auth(token))
+    }
+}
+
+#[cfg(feature = "network")]
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let message = response.text().await.unwrap_or_default();
+    Err(anyhow::anyhow!("Sync server replied {}: {}", status, message.trim()))
+}
+
+#[cfg(feature = "network")]
+fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+#[cfg(all(test, feature = "network"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_endpoints_and_file_hashes() {
+        let client = SyncServerClient::with_token("https://books.example.org/sync", None).unwrap();
+        assert_eq!(client.endpoint("v1/changes").unwrap().as_str(), "https://books.example.org/sync/v1/changes");
+        assert!(client.authorized(client.client.get("https://books.example.org/")).is_err());
+        assert!(SyncServerClient::with_token("books.example.org", None).is_err());
+
+        assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+}
+
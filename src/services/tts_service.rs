@@ -0,0 +1,682 @@
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tokio::sync::{broadcast, RwLock};
+
+use crate::models::preferences::{SpeechEngine, SpeechSettings};
+use crate::services::reading_service::Chapter;
+
+/// A sentence ends at one of these, after any closing quotes or brackets
+static SENTENCE_END: Lazy<Regex> = Lazy::new(|| Regex::new(r#"[.!?…]+["'”’»)\]]*\s+"#).unwrap());
+/// Words whose full stop doesn't end a sentence
+const ABBREVIATIONS: &[&str] = &[
+    "mr", "mrs", "ms", "dr", "st", "prof", "sr", "jr", "vs", "etc", "e.g", "i.e", "cf", "no", "vol", "ch", "fig",
+];
+/// How often playback checks whether the voice has finished a sentence
+const SPEAKING_POLL: Duration = Duration::from_millis(50);
+
+/// A sentence to read aloud, as byte offsets into its chapter's text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpokenSentence {
+    pub chapter_id: String,
+    pub paragraph: usize, // Index of the paragraph within its chapter
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Split a chapter's paragraphs into sentences
+pub fn split_sentences(chapter: &Chapter) -> Vec<SpokenSentence> {
+    let text = &chapter.content;
+    let paragraphs: Vec<(usize, usize)> = if chapter.blocks.is_empty() {
+        vec![(0, text.len())]
+    } else {
+        chapter.blocks.iter().map(|block| (block.start, block.end)).collect()
+    };
+
+    let mut sentences = Vec::new();
+    for (paragraph, (paragraph_start, paragraph_end)) in paragraphs.into_iter().enumerate() {
+        let paragraph_text = &text[paragraph_start..paragraph_end];
+        let mut start = 0;
+        for end in SENTENCE_END.find_iter(paragraph_text) {
+            if is_abbreviation(&paragraph_text[start..end.start()]) {
+                continue;
+            }
+            let sentence_end = paragraph_text[..end.end()].trim_end().len();
+            sentences.push(SpokenSentence {
+                chapter_id: chapter.id.clone(),
+                paragraph,
+                start: paragraph_start + start,
+                end: paragraph_start + sentence_end,
+            });
+            start = end.end();
+        }
+        let rest = paragraph_text[start..].trim_end();
+        if !rest.trim().is_empty() {
+            sentences.push(SpokenSentence {
+                chapter_id: chapter.id.clone(),
+                paragraph,
+                start: paragraph_start + start,
+                end: paragraph_start + start + rest.len(),
+            });
+        }
+    }
+    sentences
+}
+
+/// Whether text ends with an abbreviation or an initial, such as "Dr" or "J"
+fn is_abbreviation(before_stop: &str) -> bool {
+    let word = before_stop.rsplit(char::is_whitespace).next().unwrap_or_default();
+    let word = word.trim_start_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+    ABBREVIATIONS.contains(&word.as_str())
+        || (word.chars().count() == 1 && word.chars().all(char::is_alphabetic) && before_stop.ends_with(|c: char| c.is_uppercase()))
+}
+
+/// Stop flag that wakes a backend waiting on its voice; backends speak on `spawn_blocking` threads
+#[derive(Default)]
+struct StopSignal {
+    stopped: Mutex<bool>,
+    changed: Condvar,
+}
+
+impl StopSignal {
+    fn reset(&self) {
+        *self.stopped.lock().unwrap() = false;
+    }
+
+    fn stop(&self) {
+        *self.stopped.lock().unwrap() = true;
+        self.changed.notify_all();
+    }
+
+    fn is_stopped(&self) -> bool {
+        *self.stopped.lock().unwrap()
+    }
+
+    /// Wait until `stop` is called or the timeout passes, returning whether it was stopped
+    fn wait(&self, timeout: Duration) -> bool {
+        let stopped = self.stopped.lock().unwrap();
+        let (stopped, _) = self.changed.wait_timeout_while(stopped, timeout, |stopped| !*stopped).unwrap();
+        *stopped
+    }
+}
+
+/// A voice a speech engine offers
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeechVoice {
+    pub id: String,
+    pub name: String,
+    pub language: String,
+}
+
+/// A speech engine that says one piece of text at a time
+pub trait SpeechBackend: Send + Sync {
+    fn voices(&self) -> Result<Vec<SpeechVoice>>;
+
+    /// Say text, returning once it has been said or `stop` was called
+    fn speak(&self, text: &str, settings: &SpeechSettings) -> Result<()>;
+
+    /// Interrupt what is being said
+    fn stop(&self);
+}
+
+/// The operating system's voices
+#[cfg(feature = "speech")]
+pub struct PlatformSpeech {
+    tts: Mutex<tts::Tts>,
+    stopped: StopSignal,
+}
+
+#[cfg(feature = "speech")]
+impl PlatformSpeech {
+    pub fn new() -> Result<Self> {
+        let tts = tts::Tts::default().map_err(|e| anyhow!("No speech engine available: {}", e))?;
+        Ok(Self { tts: Mutex::new(tts), stopped: StopSignal::default() })
+    }
+}
+
+#[cfg(feature = "speech")]
+impl SpeechBackend for PlatformSpeech {
+    fn voices(&self) -> Result<Vec<SpeechVoice>> {
+        let voices = self.tts.lock().unwrap().voices()?;
+        Ok(voices.into_iter()
+            .map(|voice| SpeechVoice { id: voice.id(), name: voice.name(), language: voice.language().to_string() })
+            .collect())
+    }
+
+    fn speak(&self, text: &str, settings: &SpeechSettings) -> Result<()> {
+        self.stopped.reset();
+        let polls_speaking = {
+            let mut tts = self.tts.lock().unwrap();
+            let features = tts.supported_features();
+            if features.rate {
+                let rate = (tts.normal_rate() * settings.rate).clamp(tts.min_rate(), tts.max_rate());
+                tts.set_rate(rate)?;
+            }
+            if let (true, Some(voice_id)) = (features.voice, &settings.voice) {
+                if let Some(voice) = tts.voices()?.into_iter().find(|voice| voice.id() == *voice_id) {
+                    tts.set_voice(&voice)?;
+                }
+            }
+            tts.speak(text, true)?;
+            features.is_speaking
+        };
+
+        if !polls_speaking {
+            // Without a way to ask, wait about as long as the text takes to say
+            let words = text.split_whitespace().count() as f32;
+            let seconds = words / (crate::models::book::DEFAULT_READING_SPEED_WPM as f32 / 60.0) / settings.rate.max(0.1);
+            self.stopped.wait(Duration::from_secs_f32(seconds));
+            return Ok(());
+        }
+        while !self.stopped.is_stopped() && self.tts.lock().unwrap().is_speaking()? {
+            self.stopped.wait(SPEAKING_POLL);
+        }
+        Ok(())
+    }
+
+    fn stop(&self) {
+        self.stopped.stop();
+        if let Err(e) = self.tts.lock().unwrap().stop() {
+            tracing::warn!("Failed to stop speech: {}", e);
+        }
+    }
+}
+
+/// A local Piper voice model, run with the `piper` command and played with an audio player
+///
+/// Piper writes each sentence to a WAV file, which the player command plays.
+pub struct PiperSpeech {
+    executable: PathBuf,
+    model: PathBuf,
+    player: Vec<String>, // Command and arguments; the WAV file's path is appended
+    playing: Mutex<Option<Child>>,
+    stopped: StopSignal,
+}
+
+impl PiperSpeech {
+    pub fn new(model: PathBuf) -> Self {
+        Self::with_commands(PathBuf::from("piper"), model, Self::default_player())
+    }
+
+    pub fn with_commands(executable: PathBuf, model: PathBuf, player: Vec<String>) -> Self {
+        Self { executable, model, player, playing: Mutex::new(None), stopped: StopSignal::default() }
+    }
+
+    fn default_player() -> Vec<String> {
+        let command: &[&str] = if cfg!(target_os = "macos") {
+            &["afplay"]
+        } else if cfg!(windows) {
+            &["powershell", "-NoProfile", "-Command", "(New-Object Media.SoundPlayer $args[0]).PlaySync()"]
+        } else {
+            &["aplay", "-q"]
+        };
+        command.iter().map(|part| part.to_string()).collect()
+    }
+
+    /// Run a command, keeping it where `stop` can kill it, until it exits
+    fn run(&self, command: &mut Command, input: Option<&str>) -> Result<()> {
+        let mut child = command
+            .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to run {:?}: {}", command.get_program(), e))?;
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            std::io::Write::write_all(&mut stdin, input.as_bytes())?;
+        }
+        *self.playing.lock().unwrap() = Some(child);
+
+        loop {
+            if self.stopped.is_stopped() {
+                return Ok(());
+            }
+            let mut playing = self.playing.lock().unwrap();
+            let Some(child) = playing.as_mut() else { return Ok(()) };
+            if let Some(status) = child.try_wait()? {
+                playing.take();
+                if !status.success() {
+                    return Err(anyhow!("{:?} failed with {}", command.get_program(), status));
+                }
+                return Ok(());
+            }
+            drop(playing);
+            self.stopped.wait(SPEAKING_POLL);
+        }
+    }
+}
+
+impl SpeechBackend for PiperSpeech {
+    fn voices(&self) -> Result<Vec<SpeechVoice>> {
+        let name = self.model.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+        // Piper models are named like `en_US-lessac-medium`
+        let language = name.split('-').next().unwrap_or_default().replace('_', "-");
+        Ok(vec![SpeechVoice { id: self.model.to_string_lossy().to_string(), name, language }])
+    }
+
+    fn speak(&self, text: &str, settings: &SpeechSettings) -> Result<()> {
+        self.stopped.reset();
+        let audio = tempfile::Builder::new().suffix(".wav").tempfile()?;
+        // Piper stretches speech by its length scale, so a faster rate is a smaller scale
+        let length_scale = 1.0 / settings.rate.clamp(0.25, 4.0);
+        self.run(
+            Command::new(&self.executable)
+                .arg("--model").arg(&self.model)
+                .arg("--length_scale").arg(length_scale.to_string())
+                .arg("--output_file").arg(audio.path()),
+            Some(text),
+        )?;
+        if self.stopped.is_stopped() {
+            return Ok(());
+        }
+
+        let (program, args) = self.player.split_first().ok_or_else(|| anyhow!("No audio player configured"))?;
+        self.run(Command::new(program).args(args).arg(audio.path()), None)
+    }
+
+    fn stop(&self) {
+        self.stopped.stop();
+        if let Some(mut child) = self.playing.lock().unwrap().take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Whether the book is being read aloud
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackState {
+    Stopped,
+    Playing,
+    Paused,
+}
+
+/// What the reader hears about, to highlight the sentence and follow along
+#[derive(Debug, Clone, PartialEq)]
+pub enum TtsEvent {
+    SentenceStarted(SpokenSentence),
+    Paused(SpokenSentence),
+    Stopped,
+    Finished,
+    Error(String),
+}
+
+#[derive(Debug)]
+struct Playback {
+    sentences: Vec<SpokenSentence>,
+    texts: Vec<String>,
+    index: usize,
+    state: PlaybackState,
+    session: u64,   // Changes whenever playback starts, so only one playback runs
+    generation: u64, // Changes whenever the sentence being read changes
+}
+
+/// Reads books aloud a sentence at a time
+///
+/// Reading goes on into the following chapters. Every sentence started is
+/// announced with its chapter offsets, so the reader can highlight it, turn
+/// pages along and save it as the reading position.
+pub struct TtsService {
+    backend: Arc<dyn SpeechBackend>,
+    settings: Arc<RwLock<SpeechSettings>>,
+    playback: Arc<RwLock<Playback>>,
+    events: broadcast::Sender<TtsEvent>,
+}
+
+impl TtsService {
+    pub fn new(backend: Arc<dyn SpeechBackend>) -> Self {
+        let (events, _) = broadcast::channel(64);
+        Self {
+            backend,
+            settings: Arc::new(RwLock::new(SpeechSettings::default())),
+            playback: Arc::new(RwLock::new(Playback {
+                sentences: Vec::new(),
+                texts: Vec::new(),
+                index: 0,
+                state: PlaybackState::Stopped,
+                session: 0,
+                generation: 0,
+            })),
+            events,
+        }
+    }
+
+    /// Use the engine chosen in the speech settings
+    pub fn for_settings(settings: &SpeechSettings) -> Result<Self> {
+        let backend: Arc<dyn SpeechBackend> = match settings.engine {
+            SpeechEngine::Piper => {
+                let model = settings.piper_model.clone().ok_or_else(|| anyhow!("No Piper voice model chosen"))?;
+                Arc::new(PiperSpeech::new(model))
+            }
+            #[cfg(feature = "speech")]
+            SpeechEngine::Platform => Arc::new(PlatformSpeech::new()?),
+            #[cfg(not(feature = "speech"))]
+            SpeechEngine::Platform => return Err(anyhow!("This build can't use the system's voices")),
+        };
+        Ok(Self::new(backend))
+    }
+
+    pub fn subscribe_events(&self) -> broadcast::Receiver<TtsEvent> {
+        self.events.subscribe()
+    }
+
+    /// Set the voice, speed and engine options
+    pub async fn set_settings(&self, settings: SpeechSettings) {
+        *self.settings.write().await = settings;
+    }
+
+    /// Get the voice, speed and engine options
+    pub async fn get_settings(&self) -> SpeechSettings {
+        self.settings.read().await.clone()
+    }
+
+    /// Voices the engine offers
+    pub fn voices(&self) -> Result<Vec<SpeechVoice>> {
+        self.backend.voices()
+    }
+
+    pub async fn state(&self) -> PlaybackState {
+        self.playback.read().await.state
+    }
+
+    /// Where reading aloud is, as a chapter and offset to keep as the reading position
+    pub async fn current_position(&self) -> Option<(String, usize)> {
+        let playback = self.playback.read().await;
+        playback.sentences.get(playback.index).map(|sentence| (sentence.chapter_id.clone(), sentence.start))
+    }
+
+    /// Start reading aloud from a position, going on through the chapters after it
+    pub async fn play(&self, chapters: &[Chapter], chapter_id: &str, offset: usize) -> Result<()> {
+        let first = chapters.iter()
+            .position(|chapter| chapter.id == chapter_id)
+            .ok_or_else(|| anyhow!("Chapter not found: {}", chapter_id))?;
+
+        let mut sentences = Vec::new();
+        let mut texts = Vec::new();
+        for chapter in &chapters[first..] {
+            for sentence in split_sentences(chapter) {
+                texts.push(chapter.content[sentence.start..sentence.end].to_string());
+                sentences.push(sentence);
+            }
+        }
+        // Start with the sentence the position is in
+        let index = sentences.iter()
+            .position(|sentence| sentence.chapter_id != chapter_id || sentence.end > offset)
+            .unwrap_or(sentences.len());
+
+        {
+            let mut playback = self.playback.write().await;
+            playback.sentences = sentences;
+            playback.texts = texts;
+            playback.index = index;
+            playback.generation += 1;
+        }
+        self.backend.stop();
+        self.start().await;
+        Ok(())
+    }
+
+    /// Pause, keeping the sentence being read to start again on `resume`
+    pub async fn pause(&self) {
+        let paused = {
+            let mut playback = self.playback.write().await;
+            if playback.state != PlaybackState::Playing {
+                return;
+            }
+            playback.state = PlaybackState::Paused;
+            playback.generation += 1;
+            playback.sentences.get(playback.index).cloned()
+        };
+        self.backend.stop();
+        if let Some(sentence) = paused {
+            let _ = self.events.send(TtsEvent::Paused(sentence));
+        }
+    }
+
+    /// Go on reading from where reading was paused
+    pub async fn resume(&self) {
+        if self.playback.read().await.state == PlaybackState::Paused {
+            self.start().await;
+        }
+    }
+
+    /// Stop reading aloud
+    pub async fn stop(&self) {
+        {
+            let mut playback = self.playback.write().await;
+            if playback.state == PlaybackState::Stopped {
+                return;
+            }
+            playback.state = PlaybackState::Stopped;
+            playback.generation += 1;
+        }
+        self.backend.stop();
+        let _ = self.events.send(TtsEvent::Stopped);
+    }
+
+    /// Skip to the start of the next paragraph
+    pub async fn next_paragraph(&self) {
+        self.jump(|playback| {
+            let current = &playback.sentences[playback.index];
+            playback.sentences[playback.index..].iter()
+                .position(|sentence| sentence.paragraph != current.paragraph || sentence.chapter_id != current.chapter_id)
+                .map_or(playback.sentences.len(), |skipped| playback.index + skipped)
+        })
+        .await;
+    }
+
+    /// Go back to the start of this paragraph, or of the previous one when already at its start
+    pub async fn previous_paragraph(&self) {
+        self.jump(|playback| {
+            let paragraph_start = |end: usize| {
+                let last = &playback.sentences[end];
+                playback.sentences[..end].iter()
+                    .rposition(|sentence| sentence.paragraph != last.paragraph || sentence.chapter_id != last.chapter_id)
+                    .map_or(0, |before| before + 1)
+            };
+            let start = paragraph_start(playback.index);
+            if start == playback.index && start > 0 {
+                paragraph_start(start - 1)
+            } else {
+                start
+            }
+        })
+        .await;
+    }
+
+    /// Skip to the next sentence
+    pub async fn next_sentence(&self) {
+        self.jump(|playback| playback.index + 1).await;
+    }
+
+    async fn jump(&self, target: impl FnOnce(&Playback) -> usize) {
+        let interrupted = {
+            let mut playback = self.playback.write().await;
+            if playback.index >= playback.sentences.len() {
+                return;
+            }
+            playback.index = target(&playback).min(playback.sentences.len());
+            playback.generation += 1;
+            playback.state == PlaybackState::Playing
+        };
+        if interrupted {
+            self.backend.stop();
+        }
+    }
+
+    /// Read sentences from the current one until stopped, paused or out of sentences
+    async fn start(&self) {
+        let session = {
+            let mut playback = self.playback.write().await;
+            playback.state = PlaybackState::Playing;
+            playback.session += 1;
+            playback.session
+        };
+
+        let backend = self.backend.clone();
+        let settings = self.settings.clone();
+        let playback = self.playback.clone();
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            loop {
+                let (sentence, text, generation) = {
+                    let mut playback = playback.write().await;
+                    if playback.session != session || playback.state != PlaybackState::Playing {
+                        return;
+                    }
+                    let Some(sentence) = playback.sentences.get(playback.index).cloned() else {
+                        playback.state = PlaybackState::Stopped;
+                        let _ = events.send(TtsEvent::Finished);
+                        return;
+                    };
+                    (sentence, playback.texts[playback.index].clone(), playback.generation)
+                };
+                let _ = events.send(TtsEvent::SentenceStarted(sentence));
+
+                let settings = settings.read().await.clone();
+                let speaker = backend.clone();
+                let spoken = tokio::task::spawn_blocking(move || speaker.speak(&text, &settings)).await;
+                let error = match spoken {
+                    Ok(Ok(())) => None,
+                    Ok(Err(e)) => Some(e.to_string()),
+                    Err(e) => Some(e.to_string()),
+                };
+
+                let mut playback = playback.write().await;
+                if let Some(error) = error {
+                    if playback.session == session {
+                        playback.state = PlaybackState::Stopped;
+                    }
+                    let _ = events.send(TtsEvent::Error(error));
+                    return;
+                }
+                // A jump or pause during the sentence has already moved playback on
+                if playback.generation == generation {
+                    playback.index += 1;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::layout_service::structure_html;
+
+    /// Says nothing, recording what it was asked to say
+    #[derive(Default)]
+    struct SilentSpeech {
+        said: Mutex<Vec<String>>,
+    }
+
+    impl SpeechBackend for SilentSpeech {
+        fn voices(&self) -> Result<Vec<SpeechVoice>> {
+            Ok(Vec::new())
+        }
+
+        fn speak(&self, text: &str, _settings: &SpeechSettings) -> Result<()> {
+            self.said.lock().unwrap().push(text.to_string());
+            Ok(())
+        }
+
+        fn stop(&self) {}
+    }
+
+    fn chapter(id: &str, html: &str) -> Chapter {
+        let structured = structure_html(html);
+        Chapter {
+            id: id.to_string(),
+            title: id.to_string(),
+            word_count: structured.text.split_whitespace().count(),
+            content: structured.text,
+            order: 0,
+            media_overlay: None,
+            blocks: structured.blocks,
+        }
+    }
+
+    #[test]
+    fn test_split_sentences_by_paragraph() {
+        let chapter = chapter("one", "<p>Mr. Holmes said “Come!” Then he left… J. Watson followed.</p><p>Was it late? Yes</p>");
+        let sentences: Vec<(usize, &str)> = split_sentences(&chapter).iter()
+            .map(|sentence| (sentence.paragraph, &chapter.content[sentence.start..sentence.end]))
+            .collect();
+        assert_eq!(sentences, [
+            (0, "Mr. Holmes said “Come!”"),
+            (0, "Then he left…"),
+            (0, "J. Watson followed."),
+            (1, "Was it late?"),
+            (1, "Yes"),
+        ]);
+    }
+
+    #[test]
+    fn test_stop_wakes_a_waiting_backend() {
+        let signal = Arc::new(StopSignal::default());
+        assert!(!signal.wait(Duration::ZERO));
+
+        // Well before the timeout, or this test would hang for an hour
+        let waiting = std::thread::spawn({
+            let signal = signal.clone();
+            move || signal.wait(Duration::from_secs(3600))
+        });
+        signal.stop();
+        assert!(waiting.join().unwrap());
+
+        signal.reset();
+        assert!(!signal.is_stopped());
+    }
+
+    #[tokio::test]
+    async fn test_reads_on_through_chapters_from_a_position() {
+        let backend = Arc::new(SilentSpeech::default());
+        let tts = TtsService::new(backend.clone());
+        let mut events = tts.subscribe_events();
+        let chapters = [
+            chapter("one", "<p>First. Second.</p><p>Third.</p>"),
+            chapter("two", "<p>Fourth.</p>"),
+        ];
+
+        tts.play(&chapters, "one", 8).await.unwrap();
+        let mut started = Vec::new();
+        loop {
+            match events.recv().await.unwrap() {
+                TtsEvent::SentenceStarted(sentence) => started.push((sentence.chapter_id, sentence.start)),
+                TtsEvent::Finished => break,
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        assert_eq!(started, [("one".to_string(), 7), ("one".to_string(), 15), ("two".to_string(), 0)]);
+        assert_eq!(*backend.said.lock().unwrap(), ["Second.", "Third.", "Fourth."]);
+        assert_eq!(tts.state().await, PlaybackState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn test_paragraph_navigation_while_paused() {
+        let tts = TtsService::new(Arc::new(SilentSpeech::default()));
+        let chapters = [chapter("one", "<p>First. Second.</p><p>Third.</p>")];
+        {
+            let mut playback = tts.playback.write().await;
+            for sentence in split_sentences(&chapters[0]) {
+                playback.texts.push(chapters[0].content[sentence.start..sentence.end].to_string());
+                playback.sentences.push(sentence);
+            }
+            playback.index = 1;
+            playback.state = PlaybackState::Paused;
+        }
+
+        tts.next_paragraph().await;
+        assert_eq!(tts.current_position().await, Some(("one".to_string(), 15)));
+        tts.previous_paragraph().await;
+        assert_eq!(tts.current_position().await, Some(("one".to_string(), 0)));
+        tts.next_sentence().await;
+        assert_eq!(tts.current_position().await, Some(("one".to_string(), 7)));
+        tts.previous_paragraph().await;
+        assert_eq!(tts.current_position().await, Some(("one".to_string(), 0)));
+        assert_eq!(tts.state().await, PlaybackState::Paused);
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+use crate::models::annotation::VaultFlavor;
+use crate::services::annotation_export::AnnotationExporter;
+use crate::services::annotation_service::AnnotationService;
+use crate::services::database::DatabaseService;
+
+/// Where an export remembers the files it wrote, inside the export folder
+const MANIFEST_FILE: &str = ".epubreader-vault.json";
+
+/// What a vault export changed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VaultExportReport {
+    pub written: Vec<String>, // File names created or updated
+    pub unchanged: usize,
+    pub removed: Vec<String>, // Notes of books that no longer have annotations, or were renamed
+}
+
+/// A note written by an earlier export
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct VaultManifestEntry {
+    file_name: String,
+    sha1: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct VaultManifest {
+    books: HashMap<String, VaultManifestEntry>,
+}
+
+/// Writes a Markdown note per annotated book into an Obsidian or Logseq vault
+///
+/// Re-running an export only rewrites notes whose content changed. The notes
+/// belong to the export: edits made to them in the vault are replaced.
+pub struct VaultExporter {
+    database: Arc<DatabaseService>,
+    annotations: AnnotationService,
+}
+
+impl VaultExporter {
+    pub fn new(database: Arc<DatabaseService>, annotations: AnnotationService) -> Self {
+        Self { database, annotations }
+    }
+
+    /// Export every annotated book's note into a folder of the vault
+    pub async fn export(&self, folder: &Path, flavor: VaultFlavor) -> Result<VaultExportReport> {
+        tokio::fs::create_dir_all(folder).await
+            .map_err(|e| anyhow!("Failed to create {}: {}", folder.display(), e))?;
+        let manifest_path = folder.join(MANIFEST_FILE);
+        let manifest = Self::load_manifest(&manifest_path).await;
+
+        let mut books = Vec::new();
+        let mut annotations = Vec::new();
+        for book in self.database.get_all_books().await? {
+            let book_annotations = self.annotations.get_annotations_for_book(&book.id).await?;
+            if !book_annotations.is_empty() {
+                annotations.extend(book_annotations);
+                books.push(book);
+            }
+        }
+        let notes = AnnotationExporter::export_vault(&books, &annotations, flavor);
+
+        let mut report = VaultExportReport::default();
+        let mut written = VaultManifest::default();
+        for note in notes {
+            let entry = VaultManifestEntry {
+                file_name: note.file_name.clone(),
+                sha1: format!("{:x}", Sha1::digest(note.content.as_bytes())),
+            };
+            let path = folder.join(&note.file_name);
+            if manifest.books.get(&note.book_id).is_some_and(|previous| *previous == entry) && path.exists() {
+                report.unchanged += 1;
+            } else {
+                tokio::fs::write(&path, &note.content).await
+                    .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+                report.written.push(note.file_name);
+            }
+            written.books.insert(note.book_id, entry);
+        }
+
+        for previous in manifest.books.values() {
+            // A renamed book's old note goes, unless another book's note took its name
+            if !written.books.values().any(|entry| entry.file_name == previous.file_name) {
+                Self::remove_note(&folder.join(&previous.file_name)).await?;
+                report.removed.push(previous.file_name.clone());
+            }
+        }
+        report.removed.sort();
+
+        tokio::fs::write(&manifest_path, serde_json::to_string_pretty(&written)?).await?;
+        Ok(report)
+    }
+
+    async fn load_manifest(path: &Path) -> VaultManifest {
+        match tokio::fs::read_to_string(path).await {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_default(),
+            Err(_) => VaultManifest::default(),
+        }
+    }
+
+    async fn remove_note(path: &Path) -> Result<()> {
+        match tokio::fs::remove_file(path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(anyhow!("Failed to remove {}: {}", path.display(), e))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+    use crate::models::{Book, BookFormat};
+    use crate::models::annotation::{AnnotationType, HighlightColor, TextPosition};
+
+    fn book(id: &str, title: &str) -> Book {
+        let mut book = Book::new(
+            title.to_string(),
+            "Frank Herbert".to_string(),
+            PathBuf::from(format!("/books/{}.epub", id)),
+            0,
+            BookFormat::Epub,
+        );
+        book.id = id.to_string();
+        book
+    }
+
+    #[tokio::test]
+    async fn test_reexport_only_rewrites_changed_books() {
+        let database = Arc::new(DatabaseService::new_in_memory().await.unwrap());
+        let mut dune = book("dune", "Dune");
+        dune.series = Some("Dune Chronicles".to_string());
+        database.insert_book(&dune).await.unwrap();
+        database.insert_book(&book("messiah", "Dune Messiah")).await.unwrap();
+        database.insert_book(&book("unread", "Children of Dune")).await.unwrap();
+        // The annotations schema is the annotation service's own, so it gets its own database
+        let pool = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE books (id TEXT PRIMARY KEY)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO books (id) VALUES ('dune'), ('messiah')").execute(&pool).await.unwrap();
+        let annotations = AnnotationService::new(pool);
+        annotations.init_tables().await.unwrap();
+
+        let highlight = |book: &str, text: &str| {
+            annotations.create_annotation(
+                book.to_string(),
+                8,
+                text.to_string(),
+                TextPosition {
+                    start_offset: 0,
+                    end_offset: text.len(),
+                    paragraph_index: 0,
+                    chapter_id: None,
+                    line_number: None,
+                    column_number: None,
+                    quote: None,
+                },
+                AnnotationType::Highlight,
+                HighlightColor::Yellow,
+                None,
+            )
+        };
+        let mut fear = highlight("dune", "I must not fear.\nFear is the mind-killer.").await.unwrap();
+        let prophet = highlight("messiah", "The prophet is the most dangerous").await.unwrap();
+        annotations.link_annotations(&prophet.id, &fear.id).await.unwrap();
+
+        let vault = TempDir::new().unwrap();
+        let exporter = VaultExporter::new(database, annotations.clone());
+        let report = exporter.export(vault.path(), VaultFlavor::Obsidian).await.unwrap();
+        assert_eq!(report.written, ["Dune.md", "Dune Messiah.md"]);
+
+        let note = std::fs::read_to_string(vault.path().join("Dune.md")).unwrap();
+        assert!(note.starts_with("---\ntitle: \"Dune\"\nauthor: \"Frank Herbert\"\nseries: \"Dune Chronicles\"\n"));
+        assert!(note.contains("> I must not fear.\n> Fear is the mind-killer.\n"));
+        assert!(note.contains(&format!("^{}\n\n- Linked from [[Dune Messiah#^{}|Dune Messiah, page 8]]", fear.id, prophet.id)));
+
+        let report = exporter.export(vault.path(), VaultFlavor::Obsidian).await.unwrap();
+        assert_eq!((report.written.len(), report.unchanged), (0, 2));
+
+        fear.note = Some("The litany".to_string());
+        fear.tags = vec!["bene gesserit".to_string()];
+        annotations.update_annotation(&fear).await.unwrap();
+        let report = exporter.export(vault.path(), VaultFlavor::Obsidian).await.unwrap();
+        assert_eq!((report.written, report.unchanged), (vec!["Dune.md".to_string()], 1));
+        let note = std::fs::read_to_string(vault.path().join("Dune.md")).unwrap();
+        assert!(note.contains("The litany\n\n#bene-gesserit\n\n"));
+
+        annotations.delete_annotation(&prophet.id).await.unwrap();
+        let report = exporter.export(vault.path(), VaultFlavor::Logseq).await.unwrap();
+        assert_eq!(report.removed, ["Dune Messiah.md"]);
+        assert!(!vault.path().join("Dune Messiah.md").exists());
+        let note = std::fs::read_to_string(vault.path().join("Dune.md")).unwrap();
+        assert!(!note.contains("title:"));
+        assert!(note.contains(&format!("- > I must not fear.\n  > Fear is the mind-killer.\n  id:: {}\n  - The litany\n", fear.id)));
+    }
+}
@@ -0,0 +1,392 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+
+use crate::services::dictionary_service::{Definition, DefinitionFormat};
+use crate::services::epub_metadata::plain_text;
+use crate::utils::csv_format::csv_record;
+
+/// Days until a word is reviewed again, by how many times in a row it was remembered
+const REVIEW_INTERVAL_DAYS: [i64; 6] = [0, 1, 3, 7, 16, 35];
+
+/// A sentence a word was looked up in
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VocabularyContext {
+    pub book_id: Option<String>,
+    pub sentence: String,
+    pub looked_up_at: DateTime<Utc>,
+}
+
+/// A looked-up word being learned
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VocabularyWord {
+    pub id: i64,
+    pub word: String,
+    pub language: String,
+    pub definition: Option<String>, // Plain text of the first definition found
+    pub lookup_count: u32,
+    pub first_looked_up_at: DateTime<Utc>,
+    pub last_looked_up_at: DateTime<Utc>,
+    pub review_stage: usize, // Times in a row it was remembered, up to the last review interval
+    pub next_review_at: DateTime<Utc>,
+    pub contexts: Vec<VocabularyContext>, // Newest first
+}
+
+impl VocabularyWord {
+    /// Whether the word has been remembered at every review interval
+    pub fn is_learned(&self) -> bool {
+        self.review_stage >= REVIEW_INTERVAL_DAYS.len() - 1
+    }
+}
+
+/// Words looked up while reading, with the sentences they were found in
+///
+/// Every dictionary lookup adds to the word list of its language. Words come
+/// up for review at growing intervals while they are remembered, and start
+/// over when they are not.
+#[derive(Clone)]
+pub struct VocabularyService {
+    pool: SqlitePool,
+}
+
+impl VocabularyService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize vocabulary tables
+    pub async fn init_tables(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS vocabulary_words (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                word TEXT NOT NULL,
+                word_key TEXT NOT NULL,
+                language TEXT NOT NULL,
+                definition TEXT,
+                lookup_count INTEGER NOT NULL DEFAULT 1,
+                first_looked_up_at TEXT NOT NULL,
+                last_looked_up_at TEXT NOT NULL,
+                review_stage INTEGER NOT NULL DEFAULT 0,
+                next_review_at TEXT NOT NULL,
+                UNIQUE (language, word_key)
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS vocabulary_contexts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                word_id INTEGER NOT NULL,
+                book_id TEXT,
+                sentence TEXT NOT NULL,
+                looked_up_at TEXT NOT NULL,
+                FOREIGN KEY (word_id) REFERENCES vocabulary_words (id) ON DELETE CASCADE
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_vocabulary_contexts_word ON vocabulary_contexts(word_id);")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Add a lookup to the word list, with the sentence and book it was made in
+    pub async fn record_lookup(
+        &self,
+        word: &str,
+        language: &str,
+        definition: Option<&Definition>,
+        book_id: Option<&str>,
+        sentence: Option<&str>,
+    ) -> Result<i64> {
+        let now = Utc::now().to_rfc3339();
+        let definition = definition.map(|definition| match definition.format {
+            DefinitionFormat::Html => plain_text(&definition.definition),
+            DefinitionFormat::PlainText => definition.definition.trim().to_string(),
+        });
+
+        let mut tx = self.pool.begin().await?;
+        let word_id: i64 = sqlx::query_scalar(
+            "INSERT INTO vocabulary_words (word, word_key, language, definition, first_looked_up_at, last_looked_up_at, next_review_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?) \
+             ON CONFLICT (language, word_key) DO UPDATE SET \
+                lookup_count = lookup_count + 1, \
+                last_looked_up_at = excluded.last_looked_up_at, \
+                definition = COALESCE(definition, excluded.definition) \
+             RETURNING id",
+        )
+        .bind(word)
+        .bind(word.to_lowercase())
+        .bind(language)
+        .bind(&definition)
+        .bind(&now)
+        .bind(&now)
+        .bind(&now)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if let Some(sentence) = sentence.map(str::trim).filter(|sentence| !sentence.is_empty()) {
+            sqlx::query("INSERT INTO vocabulary_contexts (word_id, book_id, sentence, looked_up_at) VALUES (?, ?, ?, ?)")
+                .bind(word_id)
+                .bind(book_id)
+                .bind(sentence)
+                .bind(&now)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(word_id)
+    }
+
+    /// Languages with words in the vocabulary, and how many each has
+    pub async fn get_languages(&self) -> Result<Vec<(String, usize)>> {
+        let rows = sqlx::query("SELECT language, COUNT(*) AS words FROM vocabulary_words GROUP BY language ORDER BY language")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(|row| (row.get("language"), row.get::<i64, _>("words") as usize)).collect())
+    }
+
+    /// A language's word list, most recently looked up first
+    pub async fn get_words(&self, language: &str) -> Result<Vec<VocabularyWord>> {
+        let rows = sqlx::query("SELECT * FROM vocabulary_words WHERE language = ? ORDER BY last_looked_up_at DESC, id DESC")
+            .bind(language)
+            .fetch_all(&self.pool)
+            .await?;
+        self.rows_to_words(&rows).await
+    }
+
+    /// Words of a language due for review, longest overdue first
+    pub async fn get_due_words(&self, language: &str, limit: usize) -> Result<Vec<VocabularyWord>> {
+        let rows = sqlx::query(
+            "SELECT * FROM vocabulary_words WHERE language = ? AND next_review_at <= ? \
+             ORDER BY next_review_at, id LIMIT ?",
+        )
+        .bind(language)
+        .bind(Utc::now().to_rfc3339())
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        self.rows_to_words(&rows).await
+    }
+
+    /// Record whether a word was remembered, scheduling its next review
+    pub async fn review_word(&self, word_id: i64, remembered: bool) -> Result<VocabularyWord> {
+        let stage: i64 = sqlx::query_scalar("SELECT review_stage FROM vocabulary_words WHERE id = ?")
+            .bind(word_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| anyhow!("Vocabulary word not found: {}", word_id))?;
+        let stage = if remembered { (stage as usize + 1).min(REVIEW_INTERVAL_DAYS.len() - 1) } else { 0 };
+        let next_review_at = Utc::now() + Duration::days(REVIEW_INTERVAL_DAYS[stage]);
+
+        sqlx::query("UPDATE vocabulary_words SET review_stage = ?, next_review_at = ? WHERE id = ?")
+            .bind(stage as i64)
+            .bind(next_review_at.to_rfc3339())
+            .bind(word_id)
+            .execute(&self.pool)
+            .await?;
+        self.get_word(word_id).await?.ok_or_else(|| anyhow!("Vocabulary word not found: {}", word_id))
+    }
+
+    pub async fn get_word(&self, word_id: i64) -> Result<Option<VocabularyWord>> {
+        let rows = sqlx::query("SELECT * FROM vocabulary_words WHERE id = ?")
+            .bind(word_id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(self.rows_to_words(&rows).await?.pop())
+    }
+
+    /// Remove a word and its sentences from the vocabulary
+    pub async fn remove_word(&self, word_id: i64) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM vocabulary_contexts WHERE word_id = ?")
+            .bind(word_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM vocabulary_words WHERE id = ?")
+            .bind(word_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Export a language's word list as CSV, one row per word with its latest sentence
+    pub async fn export_csv(&self, language: &str) -> Result<String> {
+        let mut csv = String::from("Word,Language,Definition,Context,Book ID,Lookups,First Looked Up,Learned\n");
+        for word in self.get_words(language).await? {
+            let context = word.contexts.first();
+            let fields = [
+                word.word.clone(),
+                word.language.clone(),
+                word.definition.clone().unwrap_or_default(),
+                context.map(|context| context.sentence.clone()).unwrap_or_default(),
+                context.and_then(|context| context.book_id.clone()).unwrap_or_default(),
+                word.lookup_count.to_string(),
+                word.first_looked_up_at.format("%Y-%m-%d").to_string(),
+                word.is_learned().to_string(),
+            ];
+            csv.push_str(&csv_record(&fields));
+        }
+        Ok(csv)
+    }
+
+    /// Export a language's word list for Anki's text import
+    ///
+    /// Each card has the word on the front and, on the back, its definition and
+    /// the sentence it was last looked up in with the word in bold. Words without
+    /// a definition or a sentence are left out, as their cards would be blank.
+    pub async fn export_anki(&self, language: &str) -> Result<String> {
+        let mut tsv = String::new();
+        tsv.push_str("#separator:tab\n#html:true\n#notetype:Basic\n");
+        tsv.push_str(&format!("#deck:Vocabulary::{}\n", clean_anki_field(language)));
+        tsv.push_str("#tags column:3\n");
+
+        for word in self.get_words(language).await? {
+            let mut back = Vec::new();
+            if let Some(definition) = &word.definition {
+                back.push(html_escape::encode_text(definition).to_string());
+            }
+            if let Some(context) = word.contexts.first() {
+                back.push(format!("<i>{}</i>", highlight_word(&context.sentence, &word.word)));
+            }
+            if back.is_empty() {
+                continue;
+            }
+            tsv.push_str(&clean_anki_field(&html_escape::encode_text(&word.word)));
+            tsv.push('\t');
+            tsv.push_str(&clean_anki_field(&back.join("<br><br>")));
+            tsv.push('\t');
+            tsv.push_str(&clean_anki_field(&format!("vocabulary {}", language)));
+            tsv.push('\n');
+        }
+        Ok(tsv)
+    }
+
+    async fn rows_to_words(&self, rows: &[sqlx::sqlite::SqliteRow]) -> Result<Vec<VocabularyWord>> {
+        let mut words = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id: i64 = row.get("id");
+            let context_rows = sqlx::query("SELECT * FROM vocabulary_contexts WHERE word_id = ? ORDER BY id DESC")
+                .bind(id)
+                .fetch_all(&self.pool)
+                .await?;
+            let mut contexts = Vec::with_capacity(context_rows.len());
+            for context in &context_rows {
+                contexts.push(VocabularyContext {
+                    book_id: context.get("book_id"),
+                    sentence: context.get("sentence"),
+                    looked_up_at: parse_time(context.get("looked_up_at"))?,
+                });
+            }
+
+            words.push(VocabularyWord {
+                id,
+                word: row.get("word"),
+                language: row.get("language"),
+                definition: row.get("definition"),
+                lookup_count: row.get::<i64, _>("lookup_count") as u32,
+                first_looked_up_at: parse_time(row.get("first_looked_up_at"))?,
+                last_looked_up_at: parse_time(row.get("last_looked_up_at"))?,
+                review_stage: row.get::<i64, _>("review_stage") as usize,
+                next_review_at: parse_time(row.get("next_review_at"))?,
+                contexts,
+            });
+        }
+        Ok(words)
+    }
+}
+
+fn parse_time(value: String) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(&value)?.with_timezone(&Utc))
+}
+
+/// Escape a sentence for HTML, putting the looked-up word in bold
+fn highlight_word(sentence: &str, word: &str) -> String {
+    let lower = sentence.to_lowercase();
+    // Lowercasing can change lengths; only highlight when offsets still line up
+    let found = (lower.len() == sentence.len())
+        .then(|| lower.find(&word.to_lowercase()))
+        .flatten()
+        .filter(|start| sentence.is_char_boundary(*start) && sentence.is_char_boundary(start + word.len()));
+    match found {
+        Some(start) => format!(
+            "{}<b>{}</b>{}",
+            html_escape::encode_text(&sentence[..start]),
+            html_escape::encode_text(&sentence[start..start + word.len()]),
+            html_escape::encode_text(&sentence[start + word.len()..]),
+        ),
+        None => html_escape::encode_text(sentence).to_string(),
+    }
+}
+
+/// Keep a field on one line of the file, quoting it if it contains quotes
+fn clean_anki_field(field: &str) -> String {
+    let field = field.replace("\r\n", "<br>").replace(['\n', '\r'], "<br>").replace('\t', " ");
+    if field.contains('"') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(text: &str) -> Definition {
+        Definition {
+            dictionary_id: "wordnet".to_string(),
+            dictionary_name: "WordNet".to_string(),
+            headword: "ephemeral".to_string(),
+            definition: text.to_string(),
+            format: DefinitionFormat::Html,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_word_lists_reviews_and_exports() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let vocabulary = VocabularyService::new(pool);
+        vocabulary.init_tables().await.unwrap();
+
+        let fame = definition("<b>lasting</b> a very short time");
+        let ephemeral = vocabulary.record_lookup("Ephemeral", "en", Some(&fame), Some("book-1"), Some("Fame is ephemeral.")).await.unwrap();
+        let again = vocabulary.record_lookup("ephemeral", "en", None, Some("book-2"), Some("An \"ephemeral\" joy, brief as dew.")).await.unwrap();
+        assert_eq!(ephemeral, again);
+        vocabulary.record_lookup("éphémère", "fr", None, None, None).await.unwrap();
+        assert_eq!(vocabulary.get_languages().await.unwrap(), [("en".to_string(), 1), ("fr".to_string(), 1)]);
+
+        let words = vocabulary.get_words("en").await.unwrap();
+        assert_eq!((words[0].lookup_count, words[0].definition.as_deref()), (2, Some("lasting a very short time")));
+        assert_eq!(words[0].contexts.iter().map(|c| c.book_id.as_deref()).collect::<Vec<_>>(), [Some("book-2"), Some("book-1")]);
+
+        assert_eq!(vocabulary.get_due_words("en", 10).await.unwrap().len(), 1);
+        let reviewed = vocabulary.review_word(ephemeral, true).await.unwrap();
+        assert_eq!(reviewed.review_stage, 1);
+        assert!(vocabulary.get_due_words("en", 10).await.unwrap().is_empty());
+        assert_eq!(vocabulary.review_word(ephemeral, false).await.unwrap().review_stage, 0);
+
+        let csv = vocabulary.export_csv("en").await.unwrap();
+        assert!(csv.contains("Ephemeral,en,lasting a very short time,\"An \"\"ephemeral\"\" joy, brief as dew.\",book-2,2,"));
+        let anki = vocabulary.export_anki("en").await.unwrap();
+        assert!(anki.contains("#deck:Vocabulary::en\n"));
+        assert!(anki.ends_with(
+            "Ephemeral\t\"lasting a very short time<br><br><i>An \"\"<b>ephemeral</b>\"\" joy, brief as dew.</i>\"\tvocabulary en\n"
+        ));
+        // No definition or sentence, so no card
+        assert!(!vocabulary.export_anki("fr").await.unwrap().contains("éphémère"));
+
+        vocabulary.remove_word(ephemeral).await.unwrap();
+        assert!(vocabulary.get_words("en").await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::models::Book;
+use crate::models::book::ReadingSession;
+use crate::models::library::ReadingStatus;
+use crate::services::annotation_service::AnnotationService;
+use crate::services::database::DatabaseService;
+use crate::services::export_share::{share_export, write_export};
+
+/// Genres and authors listed in a report
+const TOP_ENTRIES: usize = 5;
+
+/// Favorite highlights quoted in a report
+const MAX_HIGHLIGHTS: usize = 10;
+
+/// A book finished during the report's year
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FinishedBook {
+    pub book_id: String,
+    pub title: String,
+    pub author: String,
+    pub genre: Option<String>,
+    pub page_count: Option<u32>,
+    pub word_count: Option<u32>,
+    pub started_at: Option<DateTime<Utc>>, // First reading session
+    pub finished_at: DateTime<Utc>,
+    pub reading_minutes: u64,
+}
+
+impl FinishedBook {
+    /// Days from the first reading session to finishing, counting both
+    pub fn days_to_finish(&self) -> Option<i64> {
+        self.started_at.map(|started| {
+            let started = started.with_timezone(&Local).date_naive();
+            (self.finished_at.with_timezone(&Local).date_naive() - started).num_days() + 1
+        })
+    }
+}
+
+/// A favorite highlight quoted in a report
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ReportHighlight {
+    pub book_title: String,
+    pub text: String,
+    pub note: Option<String>,
+}
+
+/// A reader's year in books, ready to export and share
+#[derive(Debug, Clone, Serialize)]
+pub struct YearInBooks {
+    pub year: i32,
+    pub books_finished: Vec<FinishedBook>, // In the order they were finished
+    pub reading_minutes: u64,              // Every book read during the year, finished or not
+    pub pages_read: u64,
+    pub longest_book: Option<FinishedBook>,
+    pub fastest_read: Option<FinishedBook>,
+    pub top_genres: Vec<(String, u32)>,
+    pub top_authors: Vec<(String, u32)>,
+    pub favorite_highlights: Vec<ReportHighlight>,
+    pub generated_at: DateTime<Utc>,
+}
+
+impl YearInBooks {
+    /// Hours spent reading, to one decimal place
+    pub fn reading_hours(&self) -> f64 {
+        (self.reading_minutes as f64 / 6.0).round() / 10.0
+    }
+
+    /// Export the report as JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Export the report as a standalone HTML page
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!("<title>My {} in Books</title>\n", self.year));
+        html.push_str(REPORT_STYLE);
+        html.push_str("</head>\n<body>\n");
+        html.push_str(&format!("<h1>My {} in Books</h1>\n", self.year));
+
+        html.push_str("<section class=\"totals\">\n");
+        html.push_str(&format!("<div><strong>{}</strong> books finished</div>\n", self.books_finished.len()));
+        html.push_str(&format!("<div><strong>{}</strong> hours reading</div>\n", self.reading_hours()));
+        html.push_str(&format!("<div><strong>{}</strong> pages read</div>\n", self.pages_read));
+        html.push_str("</section>\n");
+
+        if let Some(book) = &self.longest_book {
+            let length = match (book.word_count, book.page_count) {
+                (Some(words), _) => format!("{} words", words),
+                (None, Some(pages)) => format!("{} pages", pages),
+                (None, None) => String::new(),
+            };
+            html.push_str(&format!(
+                "<p class=\"highlight\">Longest book: <cite>{}</cite> by {}, {}</p>\n",
+                escape_html(&book.title), escape_html(&book.author), length
+            ));
+        }
+        if let Some(book) = &self.fastest_read {
+            let days = book.days_to_finish().unwrap_or(1);
+            html.push_str(&format!(
+                "<p class=\"highlight\">Fastest read: <cite>{}</cite> by {}, in {} day{}</p>\n",
+                escape_html(&book.title), escape_html(&book.author), days, if days == 1 { "" } else { "s" }
+            ));
+        }
+
+        for (heading, entries) in [("Top genres", &self.top_genres), ("Top authors", &self.top_authors)] {
+            if entries.is_empty() {
+                continue;
+            }
+            html.push_str(&format!("<h2>{}</h2>\n<ol>\n", heading));
+            for (name, count) in entries {
+                html.push_str(&format!(
+                    "<li>{} <span class=\"count\">{} book{}</span></li>\n",
+                    escape_html(name), count, if *count == 1 { "" } else { "s" }
+                ));
+            }
+            html.push_str("</ol>\n");
+        }
+
+        if !self.books_finished.is_empty() {
+            html.push_str("<h2>Books finished</h2>\n<ol class=\"books\">\n");
+            for book in &self.books_finished {
+                html.push_str(&format!(
+                    "<li><cite>{}</cite> by {} <span class=\"count\">{}</span></li>\n",
+                    escape_html(&book.title),
+                    escape_html(&book.author),
+                    book.finished_at.with_timezone(&Local).format("%b %-d")
+                ));
+            }
+            html.push_str("</ol>\n");
+        }
+
+        if !self.favorite_highlights.is_empty() {
+            html.push_str("<h2>Favorite highlights</h2>\n");
+            for highlight in &self.favorite_highlights {
+                html.push_str(&format!("<blockquote>{}", escape_html(&highlight.text)));
+                if let Some(note) = &highlight.note {
+                    html.push_str(&format!("<p class=\"note\">{}</p>", escape_html(note)));
+                }
+                html.push_str(&format!("<footer><cite>{}</cite></footer></blockquote>\n", escape_html(&highlight.book_title)));
+            }
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    /// Write the JSON and HTML exports, revealing the HTML page to share
+    pub async fn share(&self) -> Result<PathBuf> {
+        let file_name = format!("year-in-books-{}", self.year);
+        write_export(&format!("{}.json", file_name), self.to_json()?.as_bytes()).await?;
+        share_export(&format!("{}.html", file_name), self.to_html().as_bytes()).await
+    }
+}
+
+/// Compiles end-of-year reading reports from the library and its reading sessions
+pub struct YearInBooksGenerator {
+    database: Arc<DatabaseService>,
+    annotations: Arc<AnnotationService>,
+}
+
+impl YearInBooksGenerator {
+    pub fn new(database: Arc<DatabaseService>, annotations: Arc<AnnotationService>) -> Self {
+        Self { database, annotations }
+    }
+
+    /// Compile the report for a calendar year in local time
+    ///
+    /// A finished book is dated by the first session that reached its end, or by
+    /// its last session for books marked finished early. Books finished without
+    /// any recorded session fall back to when they were last opened.
+    pub async fn generate(&self, year: i32) -> Result<YearInBooks> {
+        let in_year = |date: &DateTime<Utc>| date.with_timezone(&Local).year() == year;
+
+        let mut books_finished = Vec::new();
+        for book in self.database.get_books_by_status(ReadingStatus::Finished).await? {
+            let sessions = self.database.get_book_reading_sessions(&book.id).await?;
+            match Self::finished_at(&book, &sessions) {
+                Some(finished_at) if in_year(&finished_at) => {
+                    books_finished.push(Self::finished_book(book, &sessions, finished_at));
+                }
+                _ => {}
+            }
+        }
+        books_finished.sort_by_key(|book| book.finished_at);
+
+        // A day of margin covers a local new year that starts before the UTC one
+        let year_start = NaiveDate::from_ymd_opt(year, 1, 1)
+            .ok_or_else(|| anyhow!("Invalid year: {}", year))?
+            .and_hms_opt(0, 0, 0)
+            .map(|start| start.and_utc() - Duration::days(1))
+            .ok_or_else(|| anyhow!("Invalid year: {}", year))?;
+        let sessions: Vec<ReadingSession> = self.database.get_reading_sessions_since(year_start).await?
+            .into_iter()
+            .filter(|session| in_year(&session.start_time))
+            .collect();
+
+        let longest_book = books_finished.iter()
+            .filter(|book| book.word_count.is_some() || book.page_count.is_some())
+            .max_by_key(|book| (book.word_count.unwrap_or(0), book.page_count.unwrap_or(0)))
+            .cloned();
+        let fastest_read = books_finished.iter()
+            .filter_map(|book| book.days_to_finish().map(|days| (days, book.reading_minutes, book)))
+            .min_by_key(|(days, minutes, _)| (*days, *minutes))
+            .map(|(_, _, book)| book.clone());
+
+        let top_genres = Self::top_entries(books_finished.iter().filter_map(|book| book.genre.clone()));
+        let top_authors = Self::top_entries(books_finished.iter().map(|book| book.author.clone()));
+        let favorite_highlights = self.favorite_highlights(&books_finished).await?;
+
+        Ok(YearInBooks {
+            year,
+            reading_minutes: sessions.iter().map(|session| session.duration_minutes as u64).sum(),
+            pages_read: sessions.iter().map(|session| session.pages_read as u64).sum(),
+            books_finished,
+            longest_book,
+            fastest_read,
+            top_genres,
+            top_authors,
+            favorite_highlights,
+            generated_at: Utc::now(),
+        })
+    }
+
+    fn finished_at(book: &Book, sessions: &[ReadingSession]) -> Option<DateTime<Utc>> {
+        sessions.iter()
+            .find(|session| session.progress_end >= 1.0)
+            .or_else(|| sessions.last())
+            .and_then(|session| session.end_time)
+            .or(book.last_opened)
+    }
+
+    fn finished_book(book: Book, sessions: &[ReadingSession], finished_at: DateTime<Utc>) -> FinishedBook {
+        FinishedBook {
+            book_id: book.id,
+            title: book.title,
+            author: book.author,
+            genre: book.genre.filter(|genre| !genre.trim().is_empty()),
+            page_count: book.page_count,
+            word_count: book.word_count,
+            started_at: sessions.first().map(|session| session.start_time),
+            finished_at,
+            reading_minutes: sessions.iter().map(|session| session.duration_minutes as u64).sum(),
+        }
+    }
+
+    /// Most frequent names first, ties in alphabetical order
+    fn top_entries(names: impl Iterator<Item = String>) -> Vec<(String, u32)> {
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for name in names {
+            *counts.entry(name).or_insert(0) += 1;
+        }
+
+        let mut entries: Vec<(String, u32)> = counts.into_iter().collect();
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        entries.truncate(TOP_ENTRIES);
+        entries
+    }
+
+    /// Highlights marked as favorites in the year's books, oldest first
+    async fn favorite_highlights(&self, books: &[FinishedBook]) -> Result<Vec<ReportHighlight>> {
+        let mut favorites = Vec::new();
+        for book in books {
+            for annotation in self.annotations.get_annotations_for_book(&book.book_id).await? {
+                if annotation.is_favorite && !annotation.selected_text.trim().is_empty() {
+                    favorites.push((annotation.created_at, ReportHighlight {
+                        book_title: book.title.clone(),
+                        text: annotation.selected_text,
+                        note: annotation.note,
+                    }));
+                }
+            }
+        }
+
+        favorites.sort_by_key(|(created_at, _)| *created_at);
+        Ok(favorites.into_iter().take(MAX_HIGHLIGHTS).map(|(_, highlight)| highlight).collect())
+    }
+}
+
+/// Escape text for HTML element content
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const REPORT_STYLE: &str = "<style>
+body { font-family: Georgia, serif; max-width: 40em; margin: 3em auto; padding: 0 1em; color: #222; }
+h1 { font-size: 2.4em; }
+.totals { display: flex; gap: 2em; margin: 2em 0; }
+.totals strong { display: block; font-size: 2em; }
+.count { color: #777; font-size: 0.9em; }
+blockquote { border-left: 3px solid #d4a017; margin: 1.5em 0; padding-left: 1em; }
+blockquote footer, .note { color: #555; font-size: 0.9em; }
+</style>
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use sqlx::SqlitePool;
+    use crate::models::BookFormat;
+    use crate::models::annotation::{Annotation, AnnotationType, TextPosition};
+
+    fn local(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Local.with_ymd_and_hms(year, month, day, 12, 0, 0).unwrap().with_timezone(&Utc)
+    }
+
+    /// Save a session reading the book from `from` to `to` progress
+    async fn read(database: &DatabaseService, book: &mut Book, start: DateTime<Utc>, minutes: i64, to: f32) {
+        let mut session = ReadingSession::start(book, start);
+        book.reading_progress = to;
+        session.end(book, start + Duration::minutes(minutes));
+        database.save_reading_session(&session).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_year_in_books_report() {
+        let database = Arc::new(DatabaseService::new_in_memory().await.unwrap());
+        let mut books = Vec::new();
+        for (title, author, genre, words) in [
+            ("War & Peace", "Leo Tolstoy", "Classics", 587_000),
+            ("The Death of Ivan Ilyich", "Leo Tolstoy", "Classics", 25_000),
+            ("Last Year's Book", "Someone", "Mystery", 90_000),
+            ("Still Reading", "Someone Else", "Mystery", 60_000),
+        ] {
+            let mut book = Book::new(title.to_string(), author.to_string(), PathBuf::from(format!("{}.epub", title)), 0, BookFormat::Epub);
+            book.genre = Some(genre.to_string());
+            book.word_count = Some(words);
+            book.page_count = Some(words / 250);
+            book.reading_status = if title == "Still Reading" { ReadingStatus::CurrentlyReading } else { ReadingStatus::Finished };
+            database.insert_book(&book).await.unwrap();
+            books.push(book);
+        }
+        read(&database, &mut books[0], local(2025, 2, 1), 300, 0.5).await;
+        read(&database, &mut books[0], local(2025, 3, 10), 330, 1.0).await;
+        read(&database, &mut books[1], local(2025, 6, 5), 90, 1.0).await;
+        read(&database, &mut books[2], local(2024, 12, 1), 200, 1.0).await;
+        read(&database, &mut books[3], local(2025, 7, 1), 60, 0.4).await;
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE books (id TEXT PRIMARY KEY)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO books (id) VALUES (?)").bind(&books[0].id).execute(&pool).await.unwrap();
+        let annotations = Arc::new(AnnotationService::new(pool));
+        annotations.init_tables().await.unwrap();
+        let position = TextPosition {
+            start_offset: 0,
+            end_offset: 0,
+            paragraph_index: 0,
+            chapter_id: None,
+            line_number: None,
+            column_number: None,
+            quote: None,
+        };
+        for (text, favorite) in [("All happy families <are> alike", true), ("A passing remark", false)] {
+            let mut annotation = Annotation::new(books[0].id.clone(), 1, text.to_string(), position.clone(), AnnotationType::Highlight);
+            annotation.is_favorite = favorite;
+            annotations.save_annotation(&annotation).await.unwrap();
+        }
+
+        let report = YearInBooksGenerator::new(database, annotations).generate(2025).await.unwrap();
+        let titles: Vec<_> = report.books_finished.iter().map(|book| book.title.as_str()).collect();
+        assert_eq!(titles, ["War & Peace", "The Death of Ivan Ilyich"]);
+        assert_eq!(report.reading_minutes, 300 + 330 + 90 + 60);
+        assert_eq!(report.reading_hours(), 13.0);
+        assert_eq!(report.longest_book.as_ref().unwrap().title, "War & Peace");
+        let fastest = report.fastest_read.as_ref().unwrap();
+        assert_eq!((fastest.title.as_str(), fastest.days_to_finish()), ("The Death of Ivan Ilyich", Some(1)));
+        assert_eq!(report.top_genres, [("Classics".to_string(), 2)]);
+        assert_eq!(report.top_authors, [("Leo Tolstoy".to_string(), 2)]);
+        assert_eq!(report.favorite_highlights.len(), 1);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["books_finished"].as_array().unwrap().len(), 2);
+        let html = report.to_html();
+        assert!(html.contains("<h1>My 2025 in Books</h1>"));
+        assert!(html.contains("<cite>War &amp; Peace</cite>"));
+        assert!(html.contains("All happy families &lt;are&gt; alike"));
+    }
+}
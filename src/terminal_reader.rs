@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use anyhow::{Result, anyhow};
+
+use crate::models::{Book, BookFormat};
+use crate::services::path_resolver::PathResolver;
+use crate::services::preferences_service::PreferencesService;
+use crate::services::reading_service::{BookContent, ReadingService};
+
+/// Column the chapter text is wrapped at
+const LINE_WIDTH: usize = 80;
+
+const USAGE: &str = "Usage: ebook-reader <book file> [chapter number]";
+
+/// Print a book's chapter list, or one chapter's text, to the terminal
+///
+/// Used by builds without the `gui` feature. Books are read straight from the
+/// file, so no library database is created.
+pub fn run(args: Vec<String>) -> Result<()> {
+    let path = PathBuf::from(args.first().ok_or_else(|| anyhow!(USAGE))?);
+    let chapter_number = args.get(1)
+        .map(|number| number.parse::<usize>().map_err(|_| anyhow!(USAGE)))
+        .transpose()?;
+
+    let format = BookFormat::from_extension(
+        path.extension().and_then(|ext| ext.to_str()).unwrap_or("")
+    ).ok_or_else(|| anyhow!("Unsupported file format: {}", path.display()))?;
+    let title = path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let file_size = std::fs::metadata(&path)?.len();
+    let mut book = Book::new(title, String::new(), path.clone(), file_size, format);
+    // No library here, so the file's path identifies the book from one run to the next
+    book.id = path.canonicalize()?.to_string_lossy().to_string();
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    let reading_service = ReadingService::new();
+    let content = runtime.block_on(async {
+        if let Ok(preferences) = PreferencesService::open_default() {
+            let preferences = Arc::new(preferences);
+            let loaded = preferences.load().await;
+            reading_service.set_chapter_read_threshold(loaded.reading.chapter_read_threshold).await;
+            reading_service.set_compatibility_ledger_enabled(loaded.privacy.compatibility_ledger_enabled).await?;
+            reading_service.set_preferences(preferences).await;
+        }
+        if let Ok(app_dir) = PathResolver::get_app_data_directory() {
+            PathResolver::ensure_directory_exists(&app_dir)?;
+            reading_service.set_chapter_progress_file(app_dir.join("chapter_progress.json")).await?;
+        }
+        reading_service.load_book_content(&book).await
+    })?;
+
+    match chapter_number {
+        None => {
+            let read_chapters = runtime.block_on(reading_service.get_read_chapters(&book.id));
+            print_contents(&content, &read_chapters);
+        }
+        Some(number) => {
+            let chapter = number.checked_sub(1)
+                .and_then(|index| content.chapters.get(index))
+                .ok_or_else(|| anyhow!("Chapter {} not found, the book has {}", number, content.chapters.len()))?;
+            println!("{}\n", chapter.title);
+            for line in wrap(&chapter.content, LINE_WIDTH) {
+                println!("{}", line);
+            }
+            // The whole chapter has been printed, as if scrolled to its end
+            runtime.block_on(reading_service.record_chapter_view(&book.id, &chapter.id, 1.0, 0))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_contents(content: &BookContent, read_chapters: &[String]) {
+    println!("{}", content.title);
+    println!("{} words, about {} min\n", content.total_word_count, content.estimated_reading_time);
+    for (index, chapter) in content.chapters.iter().enumerate() {
+        let mark = if read_chapters.contains(&chapter.id) { "✓" } else { " " };
+        println!("{:>4} {} {} ({} words)", index + 1, mark, chapter.title, chapter.word_count);
+    }
+}
+
+/// Break text into lines of at most `width` characters at word boundaries
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
@@ -0,0 +1,215 @@
+use std::path::{Path, PathBuf};
+use std::fs;
+use anyhow::{Result, anyhow};
+use sha2::{Digest, Sha256};
+use tokio::fs as async_fs;
+
+/// zstd level; low levels keep compression cheap enough to run while reading
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Maximum size of a trained dictionary
+const DICTIONARY_SIZE: usize = 112 * 1024;
+
+/// Header flag for entries compressed with the trained dictionary
+const FLAG_DICTIONARY: u8 = 1;
+
+/// Header flag for entries compressed without a dictionary
+const FLAG_PLAIN: u8 = 0;
+
+/// Disk cache for chapter and rendered HTML, stored zstd-compressed
+///
+/// Each entry is a one-byte dictionary flag, the uncompressed length as a
+/// little-endian u32, then the zstd frame. A dictionary trained on typical
+/// chapters shrinks small entries much further than plain zstd.
+pub struct ChapterCache {
+    cache_dir: PathBuf,
+    dictionary: Option<Vec<u8>>,
+}
+
+impl ChapterCache {
+    /// Create a new chapter cache instance, loading a previously trained dictionary
+    pub fn new(cache_dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&cache_dir)?;
+
+        let dictionary_path = cache_dir.join("chapters.dict");
+        let dictionary = if dictionary_path.exists() {
+            Some(fs::read(&dictionary_path)?)
+        } else {
+            None
+        };
+
+        Ok(Self { cache_dir, dictionary })
+    }
+
+    /// Store chapter HTML
+    pub async fn store(&self, book_id: &str, chapter_id: &str, html: &str) -> Result<()> {
+        let entry_path = self.get_entry_path(book_id, chapter_id);
+        if let Some(parent) = entry_path.parent() {
+            async_fs::create_dir_all(parent).await?;
+        }
+
+        async_fs::write(&entry_path, self.compress(html.as_bytes())?).await?;
+        Ok(())
+    }
+
+    /// Load chapter HTML, if cached
+    pub async fn load(&self, book_id: &str, chapter_id: &str) -> Result<Option<String>> {
+        let entry_path = self.get_entry_path(book_id, chapter_id);
+        if !entry_path.exists() {
+            return Ok(None);
+        }
+
+        let data = async_fs::read(&entry_path).await?;
+        let html = self.decompress(&data)?;
+        Ok(Some(String::from_utf8(html)?))
+    }
+
+    /// Remove all cached chapters for a book
+    pub async fn remove_book(&self, book_id: &str) -> Result<()> {
+        let book_dir = self.cache_dir.join(cache_key(book_id));
+        if book_dir.exists() {
+            async_fs::remove_dir_all(book_dir).await?;
+        }
+        Ok(())
+    }
+
+    /// Train a compression dictionary from typical chapter HTML
+    ///
+    /// Cached entries compressed with a previous dictionary can no longer be read,
+    /// so the cache is cleared.
+    pub async fn train_dictionary(&mut self, samples: &[String]) -> Result<()> {
+        let samples: Vec<&[u8]> = samples.iter().map(|sample| sample.as_bytes()).collect();
+        let dictionary = zstd::dict::from_samples(&samples, DICTIONARY_SIZE)
+            .map_err(|e| anyhow!("Failed to train chapter dictionary: {}", e))?;
+
+        self.clear_cache().await?;
+        async_fs::write(self.cache_dir.join("chapters.dict"), &dictionary).await?;
+        self.dictionary = Some(dictionary);
+        Ok(())
+    }
+
+    /// Get the cache's size on disk in bytes
+    pub fn disk_usage(&self) -> Result<u64> {
+        directory_size(&self.cache_dir)
+    }
+
+    /// Check if a trained dictionary is in use
+    pub fn has_dictionary(&self) -> bool {
+        self.dictionary.is_some()
+    }
+
+    /// Clear all cached chapters, keeping the dictionary
+    pub async fn clear_cache(&self) -> Result<()> {
+        let mut entries = async_fs::read_dir(&self.cache_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                async_fs::remove_dir_all(entry.path()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let length = u32::try_from(data.len())
+            .map_err(|_| anyhow!("Chapter too large to cache"))?;
+
+        let (flag, frame) = match &self.dictionary {
+            Some(dictionary) => {
+                let mut compressor = zstd::bulk::Compressor::with_dictionary(COMPRESSION_LEVEL, dictionary)?;
+                (FLAG_DICTIONARY, compressor.compress(data)?)
+            }
+            None => (FLAG_PLAIN, zstd::bulk::compress(data, COMPRESSION_LEVEL)?),
+        };
+
+        let mut entry = Vec::with_capacity(frame.len() + 5);
+        entry.push(flag);
+        entry.extend_from_slice(&length.to_le_bytes());
+        entry.extend_from_slice(&frame);
+        Ok(entry)
+    }
+
+    fn decompress(&self, entry: &[u8]) -> Result<Vec<u8>> {
+        if entry.len() < 5 {
+            return Err(anyhow!("Corrupt chapter cache entry"));
+        }
+        let length = u32::from_le_bytes([entry[1], entry[2], entry[3], entry[4]]) as usize;
+        let frame = &entry[5..];
+
+        match entry[0] {
+            FLAG_DICTIONARY => {
+                let dictionary = self.dictionary.as_ref()
+                    .ok_or_else(|| anyhow!("Chapter cache entry needs a missing dictionary"))?;
+                let mut decompressor = zstd::bulk::Decompressor::with_dictionary(dictionary)?;
+                Ok(decompressor.decompress(frame, length)?)
+            }
+            FLAG_PLAIN => Ok(zstd::bulk::decompress(frame, length)?),
+            _ => Err(anyhow!("Corrupt chapter cache entry")),
+        }
+    }
+
+    /// Get the path for a cached chapter
+    fn get_entry_path(&self, book_id: &str, chapter_id: &str) -> PathBuf {
+        self.cache_dir
+            .join(cache_key(book_id))
+            .join(format!("{}.zst", cache_key(chapter_id)))
+    }
+}
+
+/// File name for an id; hashing keeps distinct ids apart whatever characters they use
+fn cache_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+/// Total size of a directory's files
+fn directory_size(dir: &Path) -> Result<u64> {
+    let mut total_size = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            total_size += directory_size(&entry.path())?;
+        } else {
+            total_size += metadata.len();
+        }
+    }
+    Ok(total_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn chapter(n: usize) -> String {
+        format!(
+            "<html><head><link rel=\"stylesheet\" href=\"../styles/book.css\"/></head><body>\
+             <h2 class=\"chapter-title\">Chapter {n}</h2>\
+             <p class=\"first\">It was the {n}th morning of the voyage when the fog lifted.</p>\
+             <p>The crew gathered on deck to watch the coastline appear.</p></body></html>"
+        )
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_with_and_without_dictionary() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cache = ChapterCache::new(temp_dir.path().to_path_buf()).unwrap();
+
+        cache.store("book-1", "ch/1", &chapter(1)).await.unwrap();
+        assert_eq!(cache.load("book-1", "ch/1").await.unwrap(), Some(chapter(1)));
+        assert_eq!(cache.load("book-1", "missing").await.unwrap(), None);
+        // Ids that only differ in characters a file name can't hold stay separate
+        cache.store("book-1", "ch:1", &chapter(3)).await.unwrap();
+        assert_eq!(cache.load("book-1", "ch/1").await.unwrap(), Some(chapter(1)));
+        assert_eq!(cache.load("book-1", "ch_1").await.unwrap(), None);
+
+        let samples: Vec<String> = (0..200).map(chapter).collect();
+        cache.train_dictionary(&samples).await.unwrap();
+        // Training clears entries written without the dictionary
+        assert_eq!(cache.load("book-1", "ch/1").await.unwrap(), None);
+
+        cache.store("book-1", "ch2", &chapter(2)).await.unwrap();
+        let reopened = ChapterCache::new(temp_dir.path().to_path_buf()).unwrap();
+        assert!(reopened.has_dictionary());
+        assert_eq!(reopened.load("book-1", "ch2").await.unwrap(), Some(chapter(2)));
+    }
+}
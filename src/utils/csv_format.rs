@@ -0,0 +1,27 @@
+/// Quote a CSV field when it holds a comma, quote or line break (RFC 4180)
+pub fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// One line of a CSV file, with each field quoted as needed
+pub fn csv_record<S: AsRef<str>>(fields: &[S]) -> String {
+    let fields: Vec<String> = fields.iter().map(|field| escape_csv_field(field.as_ref())).collect();
+    format!("{}\n", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields_are_quoted_only_when_needed() {
+        assert_eq!(
+            csv_record(&["Highlight", "12", "Call me \"Ishmael\"", "one, two", "line\nbreak", ""]),
+            "Highlight,12,\"Call me \"\"Ishmael\"\"\",\"one, two\",\"line\nbreak\",\n"
+        );
+    }
+}
@@ -32,33 +32,35 @@ impl ImageCache {
     pub async fn save_cover(&self, book_id: &str, image_data: &[u8]) -> Result<PathBuf> {
         let cover_path = self.covers_dir.join(format!("{}.jpg", book_id));
         
-        // Load and convert image to JPEG
-        let image = image::load_from_memory(image_data)?;
-        let mut output = Vec::new();
-        let mut cursor = std::io::Cursor::new(&mut output);
-        image.write_to(&mut cursor, ImageFormat::Jpeg)?;
+        // Load and convert image to JPEG off the async workers, decoding is CPU bound
+        let image_data = image_data.to_vec();
+        let (image, output) = tokio::task::spawn_blocking(move || -> Result<(DynamicImage, Vec<u8>)> {
+            let image = image::load_from_memory(&image_data)?;
+            let output = encode_jpeg(&image)?;
+            Ok((image, output))
+        })
+        .await??;
         
         // Save the converted image
         async_fs::write(&cover_path, output).await?;
         
         // Generate thumbnail
-        self.generate_thumbnail(book_id, &image).await?;
+        self.generate_thumbnail(book_id, image).await?;
         
         Ok(cover_path)
     }
 
     /// Generate thumbnail for a book cover
-    async fn generate_thumbnail(&self, book_id: &str, image: &DynamicImage) -> Result<()> {
+    async fn generate_thumbnail(&self, book_id: &str, image: DynamicImage) -> Result<()> {
         let thumbnail_path = self.get_thumbnail_path(book_id);
         
         // Create thumbnail (200x300 pixels)
-        let thumbnail = image.resize_to_fill(200, 300, image::imageops::FilterType::Lanczos3);
+        let output = tokio::task::spawn_blocking(move || {
+            encode_jpeg(&image.resize_to_fill(200, 300, image::imageops::FilterType::Lanczos3))
+        })
+        .await??;
         
         // Save thumbnail
-        let mut output = Vec::new();
-        let mut cursor = std::io::Cursor::new(&mut output);
-        thumbnail.write_to(&mut cursor, ImageFormat::Jpeg)?;
-        
         async_fs::write(&thumbnail_path, output).await?;
         
         Ok(())
@@ -203,14 +205,10 @@ impl ImageCache {
         let image = self.generate_placeholder_image(title, author)?;
         
         // Save placeholder
-        let mut output = Vec::new();
-        let mut cursor = std::io::Cursor::new(&mut output);
-        image.write_to(&mut cursor, ImageFormat::Jpeg)?;
-        
-        async_fs::write(&placeholder_path, output).await?;
+        async_fs::write(&placeholder_path, encode_jpeg(&image)?).await?;
         
         // Generate thumbnail
-        self.generate_thumbnail(book_id, &image).await?;
+        self.generate_thumbnail(book_id, image).await?;
         
         Ok(placeholder_path)
     }
@@ -240,6 +238,13 @@ impl ImageCache {
     }
 }
 
+/// Encode an image as JPEG
+fn encode_jpeg(image: &DynamicImage) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    image.write_to(&mut std::io::Cursor::new(&mut output), ImageFormat::Jpeg)?;
+    Ok(output)
+}
+
 /// Cache statistics
 #[derive(Debug, Clone)]
 pub struct CacheStats {
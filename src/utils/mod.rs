@@ -1,5 +1,18 @@
+pub mod chapter_cache;
+pub mod csv_format;
 pub mod image_cache;
+pub use epubreader_core::isbn;
+pub mod stall_detector;
+pub mod text_anchor;
+pub mod text_find;
 pub mod text_search;
+pub use epubreader_core::url_encoding;
 
+pub use chapter_cache::*;
+pub use csv_format::*;
 pub use image_cache::*;
+pub use isbn::*;
+pub use stall_detector::*;
+pub use text_anchor::*;
+pub use text_find::*;
 pub use text_search::*;
\ No newline at end of file
@@ -0,0 +1,166 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::warn;
+
+/// How often the watchdog task asks to be woken
+const CHECK_INTERVAL: Duration = Duration::from_millis(25);
+
+/// Lateness after which a wake-up counts as a stall
+const STALL_THRESHOLD: Duration = Duration::from_millis(100);
+
+/// Watches a runtime for tasks that block its worker threads
+///
+/// A watchdog task sleeps for a short interval; when it wakes much later than
+/// requested, something held the executor thread with blocking work (file IO,
+/// zip extraction, image decoding) instead of using `spawn_blocking`. Times
+/// come from tokio's clock, so tests can pause and advance it.
+pub struct StallDetector {
+    checks: Arc<AtomicU64>,
+    stalls: Arc<AtomicU64>,
+    longest_stall_ms: Arc<AtomicU64>,
+    task: JoinHandle<()>,
+}
+
+impl StallDetector {
+    /// Start watching the runtime behind `handle`
+    pub fn spawn(handle: &Handle) -> Self {
+        Self::spawn_with(handle, CHECK_INTERVAL, STALL_THRESHOLD)
+    }
+
+    /// Start watching with a custom check interval and stall threshold
+    pub fn spawn_with(handle: &Handle, interval: Duration, threshold: Duration) -> Self {
+        let checks = Arc::new(AtomicU64::new(0));
+        let stalls = Arc::new(AtomicU64::new(0));
+        let longest_stall_ms = Arc::new(AtomicU64::new(0));
+
+        let task = handle.spawn({
+            let checks = checks.clone();
+            let stalls = stalls.clone();
+            let longest_stall_ms = longest_stall_ms.clone();
+            async move {
+                loop {
+                    let started = Instant::now();
+                    tokio::time::sleep(interval).await;
+                    let late = started.elapsed().saturating_sub(interval);
+                    checks.fetch_add(1, Ordering::Relaxed);
+                    if late >= threshold {
+                        stalls.fetch_add(1, Ordering::Relaxed);
+                        longest_stall_ms.fetch_max(late.as_millis() as u64, Ordering::Relaxed);
+                        warn!("Async executor stalled for {} ms by blocking work", late.as_millis());
+                    }
+                }
+            }
+        });
+
+        Self { checks, stalls, longest_stall_ms, task }
+    }
+
+    /// Number of times the watchdog has woken so far
+    pub fn check_count(&self) -> u64 {
+        self.checks.load(Ordering::Relaxed)
+    }
+
+    /// Number of stalls seen so far
+    pub fn stall_count(&self) -> u64 {
+        self.stalls.load(Ordering::Relaxed)
+    }
+
+    /// Longest stall seen so far
+    pub fn longest_stall(&self) -> Duration {
+        Duration::from_millis(self.longest_stall_ms.load(Ordering::Relaxed))
+    }
+}
+
+impl Drop for StallDetector {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wait until the watchdog has woken again
+    async fn next_check(detector: &StallDetector) {
+        let checks = detector.check_count();
+        while detector.check_count() == checks {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_late_wake_ups_count_as_stalls() {
+        let detector = StallDetector::spawn_with(&Handle::current(), Duration::from_millis(10), Duration::from_millis(150));
+
+        // Wake-ups on time are not stalls
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(detector.check_count() >= 9);
+        assert_eq!(detector.stall_count(), 0);
+
+        // Blocking work looks like the clock moving on while the watchdog can't run
+        tokio::time::advance(Duration::from_millis(400)).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(detector.stall_count(), 1);
+        assert!(detector.longest_stall() >= Duration::from_millis(380));
+    }
+
+    #[test]
+    fn test_reading_a_book_does_not_stall_the_executor() {
+        use std::io::Write;
+        use zip::write::{SimpleFileOptions, ZipWriter};
+        use crate::models::{Book, BookFormat};
+        use crate::services::reading_service::ReadingService;
+
+        // Enough chapter text that parsing on the executor would stall it
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("long.epub");
+        let mut writer = ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let options = SimpleFileOptions::default();
+        let mut manifest = String::new();
+        let mut spine = String::new();
+        for i in 0..40 {
+            manifest.push_str(&format!(r#"<item id="c{i}" href="c{i}.xhtml" media-type="application/xhtml+xml"/>"#));
+            spine.push_str(&format!(r#"<itemref idref="c{i}"/>"#));
+        }
+        let files = [
+            ("mimetype".to_string(), "application/epub+zip".to_string()),
+            (
+                "META-INF/container.xml".to_string(),
+                r#"<container><rootfiles><rootfile full-path="content.opf"/></rootfiles></container>"#.to_string(),
+            ),
+            (
+                "content.opf".to_string(),
+                format!(r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0"><metadata/><manifest>{manifest}</manifest><spine>{spine}</spine></package>"#),
+            ),
+        ];
+        for (name, data) in files {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(data.as_bytes()).unwrap();
+        }
+        let paragraph = "<p>The <em>long</em> voyage &amp; the sea went on and on.</p>".repeat(2000);
+        for i in 0..40 {
+            writer.start_file(format!("c{i}.xhtml"), options).unwrap();
+            writer.write_all(format!("<html><body>{paragraph}</body></html>").as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let book = Book::new("Long".to_string(), "Author".to_string(), path, 0, BookFormat::Epub);
+
+        // A single-threaded runtime, where blocking hurts most
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let stalls = runtime.block_on(async {
+            let detector = StallDetector::spawn_with(&Handle::current(), Duration::from_millis(10), Duration::from_millis(150));
+            next_check(&detector).await;
+            let content = ReadingService::new().load_book_content(&book).await.unwrap();
+            assert_eq!(content.chapters.len(), 40);
+            next_check(&detector).await;
+            detector.stall_count()
+        });
+        assert_eq!(stalls, 0);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::models::annotation::TextQuote;
+
+/// Characters at each end of a passage used to find it when its middle has changed
+const FUZZY_EDGE_CHARS: usize = 24;
+
+/// How a passage was found again in content that may have changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnchorMatch {
+    Exact,      // Still at its recorded offsets
+    Moved,      // Same text at different offsets
+    Normalized, // Same words, different spacing, case or punctuation
+    Fuzzy,      // Start and end found, the middle differs
+}
+
+impl AnchorMatch {
+    /// Get display name
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            AnchorMatch::Exact => "Exact",
+            AnchorMatch::Moved => "Moved",
+            AnchorMatch::Normalized => "Reformatted",
+            AnchorMatch::Fuzzy => "Approximate",
+        }
+    }
+}
+
+/// Where a passage was found, as byte offsets into the content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextAnchor {
+    pub start: usize,
+    pub end: usize,
+    pub matched: AnchorMatch,
+}
+
+/// Find a passage in content, preferring the occurrence nearest where it used to start
+///
+/// `expected` are the byte offsets the passage was recorded at. When the text
+/// is no longer there verbatim, it is matched ignoring case, whitespace and
+/// punctuation, and then by its first and last words alone.
+pub fn locate_text(content: &str, text: &str, expected: (usize, usize)) -> Option<TextAnchor> {
+    if text.is_empty() {
+        return None;
+    }
+    let (expected_start, expected_end) = expected;
+    if content.get(expected_start..expected_end) == Some(text) {
+        return Some(TextAnchor { start: expected_start, end: expected_end, matched: AnchorMatch::Exact });
+    }
+
+    if let Some(start) = nearest(content.match_indices(text).map(|(start, _)| start), expected_start) {
+        return Some(TextAnchor { start, end: start + text.len(), matched: AnchorMatch::Moved });
+    }
+
+    let content = NormalizedText::new(content);
+    let text = NormalizedText::new(text).text;
+    if text.is_empty() {
+        return None;
+    }
+
+    let found = content.text.match_indices(&text).map(|(start, _)| (start, start + text.len()));
+    if let Some((start, end)) = content.nearest_span(found, expected_start) {
+        return Some(TextAnchor { start, end, matched: AnchorMatch::Normalized });
+    }
+
+    // Too short to tell its ends from its middle
+    if text.chars().count() < FUZZY_EDGE_CHARS * 2 {
+        return None;
+    }
+    let prefix: String = text.chars().take(FUZZY_EDGE_CHARS).collect();
+    let suffix: String = text.chars().skip(text.chars().count() - FUZZY_EDGE_CHARS).collect();
+    // The middle may have grown or shrunk by half
+    let max_len = text.len() * 3 / 2;
+    let found = content.text.match_indices(&prefix).filter_map(|(start, _)| {
+        let window_end = floor_char_boundary(&content.text, (start + max_len).min(content.text.len()));
+        let window = &content.text[start + prefix.len()..window_end];
+        window.rfind(&suffix).map(|offset| (start, start + prefix.len() + offset + suffix.len()))
+    });
+    content.nearest_span(found, expected_start)
+        .map(|(start, end)| TextAnchor { start, end, matched: AnchorMatch::Fuzzy })
+}
+
+/// Find a quote anchor in content, using its context to tell repeated passages apart
+///
+/// Among verbatim occurrences of the quote, the one whose surroundings match
+/// the quote's prefix and suffix best wins, then the one nearest where it used
+/// to start. Otherwise the quoted text is looked for like `locate_text` does.
+pub fn locate_quote(content: &str, quote: &TextQuote, expected: (usize, usize)) -> Option<TextAnchor> {
+    if quote.exact.is_empty() {
+        return None;
+    }
+    let (expected_start, expected_end) = expected;
+    let context_score = |start: usize| {
+        let end = start + quote.exact.len();
+        common_suffix_chars(&content[..start], &quote.prefix) + common_prefix_chars(&content[end..], &quote.suffix)
+    };
+
+    let at_expected = content.get(expected_start..expected_end) == Some(quote.exact.as_str());
+    let best = content.match_indices(quote.exact.as_str())
+        .map(|(start, _)| start)
+        .max_by_key(|start| (context_score(*start), std::cmp::Reverse(start.abs_diff(expected_start))));
+    match best {
+        Some(start) if at_expected && context_score(start) <= context_score(expected_start) => {
+            Some(TextAnchor { start: expected_start, end: expected_end, matched: AnchorMatch::Exact })
+        }
+        Some(start) => Some(TextAnchor { start, end: start + quote.exact.len(), matched: AnchorMatch::Moved }),
+        None => locate_text(content, &quote.exact, expected),
+    }
+}
+
+fn common_prefix_chars(a: &str, b: &str) -> usize {
+    a.chars().zip(b.chars()).take_while(|(a, b)| a == b).count()
+}
+
+fn common_suffix_chars(a: &str, b: &str) -> usize {
+    a.chars().rev().zip(b.chars().rev()).take_while(|(a, b)| a == b).count()
+}
+
+fn nearest(starts: impl Iterator<Item = usize>, expected_start: usize) -> Option<usize> {
+    starts.min_by_key(|start| start.abs_diff(expected_start))
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// Lowercase letters and digits of a text, each mapped back to where it came from
+struct NormalizedText {
+    text: String,
+    // Byte range in the original text for every byte of `text`
+    origins: Vec<(usize, usize)>,
+}
+
+impl NormalizedText {
+    fn new(original: &str) -> Self {
+        let mut text = String::new();
+        let mut origins = Vec::new();
+        for (index, c) in original.char_indices() {
+            if !c.is_alphanumeric() {
+                continue;
+            }
+            for lower in c.to_lowercase() {
+                text.push(lower);
+                origins.extend(std::iter::repeat_n((index, index + c.len_utf8()), lower.len_utf8()));
+            }
+        }
+        Self { text, origins }
+    }
+
+    /// The span in the original text nearest the expected start
+    fn nearest_span(&self, spans: impl Iterator<Item = (usize, usize)>, expected_start: usize) -> Option<(usize, usize)> {
+        spans
+            .map(|(start, end)| (self.origins[start].0, self.origins[end - 1].1))
+            .min_by_key(|(start, _)| start.abs_diff(expected_start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_text_after_the_content_changed() {
+        let content = "Call me Ishmael. Some years ago, never mind how long precisely, I went to sea. Call me Ishmael.";
+        let anchor = locate_text(content, "Some years ago", (17, 31)).unwrap();
+        assert_eq!((anchor.start, anchor.matched), (17, AnchorMatch::Exact));
+
+        // The occurrence nearest the old offset wins
+        let anchor = locate_text(content, "Call me Ishmael", (70, 85)).unwrap();
+        assert_eq!((anchor.start, anchor.end, anchor.matched), (79, 94, AnchorMatch::Moved));
+
+        let reflowed = "Call me Ishmael.  SOME years\nago — never mind how long";
+        let anchor = locate_text(reflowed, "Some years ago, never", (0, 0)).unwrap();
+        assert_eq!(anchor.matched, AnchorMatch::Normalized);
+        assert_eq!(&reflowed[anchor.start..anchor.end], "SOME years\nago — never");
+
+        let original = "Whenever I find myself growing grim about the mouth; whenever it is a damp, drizzly November in my soul";
+        let revised = "Intro. Whenever I find myself growing stern about the mouth, whenever it is a damp drizzly November in my soul.";
+        let anchor = locate_text(revised, original, (0, original.len())).unwrap();
+        assert_eq!(anchor.matched, AnchorMatch::Fuzzy);
+        assert_eq!(&revised[anchor.start..anchor.end], &revised[7..revised.len() - 1]);
+
+        assert!(locate_text(content, "white whale", (0, 0)).is_none());
+    }
+
+    #[test]
+    fn test_locate_quote_uses_its_context() {
+        let content = "Call me Ishmael. Some years ago, never mind how long precisely, I went to sea. Call me Ishmael.";
+        let quote = TextQuote::capture(content, 79, 94).unwrap();
+        assert_eq!(quote.exact, "Call me Ishmael");
+        assert_eq!(quote.suffix, ".");
+        assert!(quote.prefix.ends_with("I went to sea. "));
+
+        // A new opening shifts every offset; the context still picks the second occurrence
+        let revised = format!("Chapter 1. Loomings. {}", content);
+        let anchor = locate_quote(&revised, &quote, (79, 94)).unwrap();
+        assert_eq!((anchor.start, anchor.matched), (100, AnchorMatch::Moved));
+
+        let anchor = locate_quote(content, &quote, (79, 94)).unwrap();
+        assert_eq!(anchor.matched, AnchorMatch::Exact);
+
+        let reworded = "Call me Ishmael. I went to sea. Call me  ISHMAEL!";
+        let anchor = locate_quote(reworded, &quote, (79, 94)).unwrap();
+        assert_eq!(anchor.matched, AnchorMatch::Moved);
+        assert_eq!(anchor.start, 0);
+    }
+}
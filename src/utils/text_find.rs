@@ -0,0 +1,99 @@
+use anyhow::{anyhow, Result};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Bytes of text shown on each side of a match
+const CONTEXT_BYTES: usize = 50;
+
+/// How a find query matches text
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FindOptions {
+    pub case_sensitive: bool,
+    pub whole_word: bool, // Matches may not start or end inside a word
+    pub regex: bool,      // The query is a regular expression rather than literal text
+}
+
+/// Finds a query in text, by the options the reader chose
+#[derive(Debug, Clone)]
+pub struct TextFinder {
+    pattern: Regex,
+    whole_word: bool,
+}
+
+impl TextFinder {
+    pub fn new(query: &str, options: FindOptions) -> Result<Self> {
+        if query.is_empty() {
+            return Err(anyhow!("Nothing to find"));
+        }
+        let pattern = if options.regex { query.to_string() } else { regex::escape(query) };
+        let pattern = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .build()
+            .map_err(|e| anyhow!("Invalid regular expression: {}", e))?;
+        Ok(Self { pattern, whole_word: options.whole_word })
+    }
+
+    /// Byte spans of every match, in order; empty matches are skipped
+    pub fn find_all(&self, text: &str) -> Vec<(usize, usize)> {
+        self.pattern.find_iter(text)
+            .map(|found| (found.start(), found.end()))
+            .filter(|(start, end)| start < end)
+            .filter(|(start, end)| !self.whole_word || is_whole_word(text, *start, *end))
+            .collect()
+    }
+}
+
+/// Whether a span neither starts nor ends in the middle of a word
+fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let starts_inside = text[..start].chars().next_back().is_some_and(is_word)
+        && text[start..end].chars().next().is_some_and(is_word);
+    let ends_inside = text[start..end].chars().next_back().is_some_and(is_word)
+        && text[end..].chars().next().is_some_and(is_word);
+    !starts_inside && !ends_inside
+}
+
+/// Text around a match, cut at character boundaries
+pub fn match_context(text: &str, start: usize, end: usize) -> String {
+    let mut context_start = start.saturating_sub(CONTEXT_BYTES);
+    while !text.is_char_boundary(context_start) {
+        context_start -= 1;
+    }
+    let mut context_end = (end + CONTEXT_BYTES).min(text.len());
+    while !text.is_char_boundary(context_end) {
+        context_end += 1;
+    }
+    text[context_start..context_end].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_with_case_whole_word_and_regex() {
+        let text = "The cat sat on the concatenated Cat_mat. CAT! Ünïcode cat.";
+        let spans = |query: &str, options: FindOptions| -> Vec<&str> {
+            let finder = TextFinder::new(query, options).unwrap();
+            finder.find_all(text).into_iter().map(|(start, end)| &text[start..end]).collect()
+        };
+
+        assert_eq!(spans("cat", FindOptions::default()), ["cat", "cat", "Cat", "CAT", "cat"]);
+        let case_sensitive = FindOptions { case_sensitive: true, ..FindOptions::default() };
+        assert_eq!(spans("cat", case_sensitive), ["cat", "cat", "cat"]);
+        let whole_word = FindOptions { whole_word: true, ..FindOptions::default() };
+        assert_eq!(spans("cat", whole_word), ["cat", "CAT", "cat"]);
+        assert_eq!(spans("cat!", whole_word), ["CAT!"]);
+        let regex = FindOptions { regex: true, whole_word: true, ..FindOptions::default() };
+        assert_eq!(spans(r"[a-z]at", regex), ["cat", "sat", "CAT", "cat"]);
+        assert_eq!(spans(r"x*", FindOptions { regex: true, ..FindOptions::default() }).len(), 0);
+
+        assert!(TextFinder::new("(unclosed", regex).is_err());
+        // Literal queries are never read as patterns
+        assert!(TextFinder::new("(unclosed", FindOptions::default()).is_ok());
+        assert!(TextFinder::new("", FindOptions::default()).is_err());
+
+        let start = text.find("Ünïcode").unwrap();
+        assert!(match_context(text, start, start + 2).ends_with("Ünïcode cat."));
+    }
+}
@@ -6,6 +6,7 @@ use regex::Regex;
 pub struct TextSearch {
     stop_words: Vec<String>,
     stemmer: Option<Box<dyn Stemmer>>,
+    tokenizer: Box<dyn Tokenizer>,
 }
 
 impl TextSearch {
@@ -14,9 +15,44 @@ impl TextSearch {
         Self {
             stop_words: default_stop_words(),
             stemmer: None,
+            tokenizer: Box::new(WordTokenizer),
         }
     }
 
+    /// Create a text search configured for a book's language
+    ///
+    /// `language` is the book's language tag (e.g. "ja", "pt-BR"). When it is
+    /// missing, the script of `sample_text` decides the tokenizer.
+    pub fn for_language(language: Option<&str>, sample_text: &str) -> Self {
+        let config = TokenizerConfig::for_language(language, sample_text);
+        let stop_words = if config.is_english {
+            default_stop_words()
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            stop_words,
+            stemmer: config.stemmer,
+            tokenizer: config.tokenizer,
+        }
+    }
+
+    /// Split text into normalized, stemmed index terms
+    pub fn tokenize(&self, text: &str) -> Vec<Token> {
+        self.tokenizer
+            .tokenize(text)
+            .into_iter()
+            .map(|token| match &self.stemmer {
+                Some(stemmer) => Token {
+                    text: stemmer.stem(&token.text),
+                    ..token
+                },
+                None => token,
+            })
+            .collect()
+    }
+
     /// Search for a query in text content
     pub fn search(&self, content: &str, query: &str) -> Result<Vec<SearchResult>> {
         let mut results = Vec::new();
@@ -138,16 +174,15 @@ impl TextSearch {
 
     /// Build search index from text content
     pub fn build_index(&self, content: &str) -> SearchIndex {
-        let mut word_positions: HashMap<String, Vec<usize>> = HashMap::new();
-        let normalized_content = self.normalize_text(content);
+        let mut word_positions: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
         
-        let mut current_pos = 0;
-        for word in normalized_content.split_whitespace() {
-            if !self.stop_words.contains(&word.to_string()) {
-                let positions = word_positions.entry(word.to_string()).or_insert_with(Vec::new);
-                positions.push(current_pos);
+        for token in self.tokenize(content) {
+            if !self.stop_words.contains(&token.text) {
+                word_positions
+                    .entry(token.text)
+                    .or_insert_with(Vec::new)
+                    .push((token.start, token.end));
             }
-            current_pos += word.len() + 1; // +1 for space
         }
         
         SearchIndex {
@@ -158,8 +193,7 @@ impl TextSearch {
 
     /// Search using pre-built index
     pub fn search_with_index(&self, index: &SearchIndex, query: &str) -> Result<Vec<SearchResult>> {
-        let normalized_query = self.normalize_text(query);
-        let query_terms: Vec<&str> = normalized_query.split_whitespace().collect();
+        let query_terms = self.tokenize(query);
         
         if query_terms.is_empty() {
             return Ok(Vec::new());
@@ -169,15 +203,14 @@ impl TextSearch {
         
         // Find positions for each term
         for term in query_terms {
-            if let Some(positions) = index.word_positions.get(term) {
-                for &pos in positions {
-                    let word_end = pos + term.len();
-                    let context = self.extract_context(&index.content, pos, word_end);
+            if let Some(positions) = index.word_positions.get(&term.text) {
+                for &(start, end) in positions {
+                    let context = self.extract_context(&index.content, start, end);
                     
                     results.push(SearchResult {
-                        start_offset: pos,
-                        end_offset: word_end,
-                        matched_text: term.to_string(),
+                        start_offset: start,
+                        end_offset: end,
+                        matched_text: index.content[start..end].to_string(),
                         context,
                         relevance_score: 0.8,
                         match_type: MatchType::IndexedTerm,
@@ -192,6 +225,7 @@ impl TextSearch {
                 .unwrap_or(std::cmp::Ordering::Equal)
                 .then_with(|| a.start_offset.cmp(&b.start_offset))
         });
+        results.dedup_by(|a, b| a.start_offset == b.start_offset);
 
         Ok(results)
     }
@@ -266,15 +300,220 @@ pub enum MatchType {
 /// Pre-built search index for faster searching
 #[derive(Debug, Clone)]
 pub struct SearchIndex {
-    pub word_positions: HashMap<String, Vec<usize>>,
+    pub word_positions: HashMap<String, Vec<(usize, usize)>>, // Byte spans in `content`
     pub content: String,
 }
 
+/// Index term with its byte span in the source text
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Token {
+    fn new(source: &str, start: usize, end: usize) -> Self {
+        Self {
+            text: source[start..end].to_lowercase(),
+            start,
+            end,
+        }
+    }
+}
+
+/// Trait for splitting text into index terms
+pub trait Tokenizer: Send + Sync {
+    fn tokenize(&self, text: &str) -> Vec<Token>;
+}
+
+/// Tokenizer for space-delimited scripts
+pub struct WordTokenizer;
+
+impl Tokenizer for WordTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut word_start = None;
+
+        for (i, c) in text.char_indices() {
+            if c.is_alphanumeric() {
+                word_start.get_or_insert(i);
+            } else if let Some(start) = word_start.take() {
+                tokens.push(Token::new(text, start, i));
+            }
+        }
+        if let Some(start) = word_start {
+            tokens.push(Token::new(text, start, text.len()));
+        }
+
+        tokens
+    }
+}
+
+/// Tokenizer for Chinese, Japanese, Korean and Thai text
+///
+/// Runs of these scripts have no word separators, so they are indexed as
+/// overlapping character bigrams; other text is split into words.
+pub struct CjkTokenizer;
+
+impl CjkTokenizer {
+    /// Emit bigrams for a run of (offset, char) pairs
+    fn push_bigrams(text: &str, run: &[(usize, char)], tokens: &mut Vec<Token>) {
+        match run {
+            [] => {}
+            [(start, c)] => tokens.push(Token::new(text, *start, start + c.len_utf8())),
+            _ => {
+                for pair in run.windows(2) {
+                    let (start, _) = pair[0];
+                    let (last, c) = pair[1];
+                    tokens.push(Token::new(text, start, last + c.len_utf8()));
+                }
+            }
+        }
+    }
+}
+
+impl Tokenizer for CjkTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut run = Vec::new();
+        let mut word_start = None;
+
+        for (i, c) in text.char_indices() {
+            if is_unsegmented_script(c) {
+                if let Some(start) = word_start.take() {
+                    tokens.push(Token::new(text, start, i));
+                }
+                run.push((i, c));
+                continue;
+            }
+
+            Self::push_bigrams(text, &run, &mut tokens);
+            run.clear();
+
+            if c.is_alphanumeric() {
+                word_start.get_or_insert(i);
+            } else if let Some(start) = word_start.take() {
+                tokens.push(Token::new(text, start, i));
+            }
+        }
+        Self::push_bigrams(text, &run, &mut tokens);
+        if let Some(start) = word_start {
+            tokens.push(Token::new(text, start, text.len()));
+        }
+
+        tokens
+    }
+}
+
+/// Check if a character belongs to a script written without spaces between words
+fn is_unsegmented_script(c: char) -> bool {
+    matches!(c as u32,
+        0x0E00..=0x0E7F   // Thai
+        | 0x3040..=0x30FF // Hiragana and Katakana
+        | 0x3400..=0x4DBF // CJK Extension A
+        | 0x4E00..=0x9FFF // CJK Unified Ideographs
+        | 0xAC00..=0xD7AF // Hangul syllables
+        | 0xF900..=0xFAFF // CJK Compatibility Ideographs
+    )
+}
+
+/// Tokenizer and stemmer selected for a book's language
+pub struct TokenizerConfig {
+    pub tokenizer: Box<dyn Tokenizer>,
+    pub stemmer: Option<Box<dyn Stemmer>>,
+    pub is_english: bool,
+}
+
+impl TokenizerConfig {
+    /// Pick a tokenizer and stemmer from a language tag, falling back to the text's script
+    pub fn for_language(language: Option<&str>, sample_text: &str) -> Self {
+        let primary = language
+            .and_then(|tag| tag.split(|c| c == '-' || c == '_').next())
+            .map(|subtag| subtag.trim().to_lowercase())
+            .filter(|subtag| !subtag.is_empty());
+
+        let unsegmented = match primary.as_deref() {
+            Some("zh" | "ja" | "ko" | "th") => true,
+            Some(_) => false,
+            None => Self::is_mostly_unsegmented(sample_text),
+        };
+
+        let tokenizer: Box<dyn Tokenizer> = if unsegmented {
+            Box::new(CjkTokenizer)
+        } else {
+            Box::new(WordTokenizer)
+        };
+        let stemmer = primary
+            .as_deref()
+            .and_then(SnowballStemmer::for_language)
+            .map(|stemmer| Box::new(stemmer) as Box<dyn Stemmer>);
+
+        Self {
+            tokenizer,
+            stemmer,
+            is_english: matches!(primary.as_deref(), None | Some("en")) && !unsegmented,
+        }
+    }
+
+    /// Check whether most letters in the text are from unsegmented scripts
+    fn is_mostly_unsegmented(text: &str) -> bool {
+        let (unsegmented, letters) = text
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .take(2000)
+            .fold((0usize, 0usize), |(unsegmented, letters), c| {
+                (unsegmented + is_unsegmented_script(c) as usize, letters + 1)
+            });
+        letters > 0 && unsegmented * 2 > letters
+    }
+}
+
 /// Trait for text stemming
-pub trait Stemmer {
+pub trait Stemmer: Send + Sync {
     fn stem(&self, word: &str) -> String;
 }
 
+/// Snowball stemmer for a specific language
+pub struct SnowballStemmer(rust_stemmers::Stemmer);
+
+impl SnowballStemmer {
+    /// Create a stemmer for an ISO 639-1 language code, if Snowball supports it
+    pub fn for_language(code: &str) -> Option<Self> {
+        use rust_stemmers::Algorithm;
+
+        let algorithm = match code {
+            "ar" => Algorithm::Arabic,
+            "da" => Algorithm::Danish,
+            "de" => Algorithm::German,
+            "el" => Algorithm::Greek,
+            "en" => Algorithm::English,
+            "es" => Algorithm::Spanish,
+            "fi" => Algorithm::Finnish,
+            "fr" => Algorithm::French,
+            "hu" => Algorithm::Hungarian,
+            "it" => Algorithm::Italian,
+            "nl" => Algorithm::Dutch,
+            "no" | "nb" | "nn" => Algorithm::Norwegian,
+            "pt" => Algorithm::Portuguese,
+            "ro" => Algorithm::Romanian,
+            "ru" => Algorithm::Russian,
+            "sv" => Algorithm::Swedish,
+            "ta" => Algorithm::Tamil,
+            "tr" => Algorithm::Turkish,
+            _ => return None,
+        };
+
+        Some(Self(rust_stemmers::Stemmer::create(algorithm)))
+    }
+}
+
+impl Stemmer for SnowballStemmer {
+    fn stem(&self, word: &str) -> String {
+        self.0.stem(word).into_owned()
+    }
+}
+
 /// Simple stemmer implementation
 pub struct SimpleStemmer;
 
@@ -359,4 +598,36 @@ impl Default for TextSearch {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(search: &TextSearch, text: &str) -> Vec<String> {
+        search.tokenize(text).into_iter().map(|token| token.text).collect()
+    }
+
+    #[test]
+    fn test_cjk_text_is_indexed_as_bigrams() {
+        let search = TextSearch::for_language(Some("ja"), "");
+        assert_eq!(terms(&search, "東京都 Tokyo"), vec!["東京", "京都", "tokyo"]);
+
+        let index = search.build_index("私は東京都に住んでいます");
+        let results = search.search_with_index(&index, "東京").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].matched_text, "東京");
+    }
+
+    #[test]
+    fn test_tokenizer_follows_language_and_script() {
+        let portuguese = TextSearch::for_language(Some("pt-BR"), "");
+        assert_eq!(terms(&portuguese, "livros"), terms(&portuguese, "livro"));
+
+        // No language tag: the script of the sample text decides
+        let detected = TextSearch::for_language(None, "这是一本书");
+        assert_eq!(terms(&detected, "一本书"), vec!["一本", "本书"]);
+
+        let english = TextSearch::for_language(None, "A plain English book");
+        assert_eq!(terms(&english, "Reading, books"), vec!["reading", "books"]);
+    }
+}